@@ -0,0 +1,146 @@
+use crate::bans;
+use crate::state;
+use crate::types::{CompositeKey, StorablePrincipal};
+use candid::Principal;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+// --- Accounts ---
+// Users reach Darely two ways: directly with their own principal (dfx, the web API, other
+// canisters), or through the OpenChat bot, which calls as itself and names the OpenChat user it
+// acts for in an endpoint's trailing `on_behalf_of` argument. Both kinds of identity resolve to
+// one canonical account id, the principal every stable map is keyed by. An identity without a
+// link is its own account; linking joins a direct principal and an OpenChat user so both reach
+// the same profile.
+
+const LINK_CODE_TTL_NANOS: u64 = 10 * 60 * 1_000_000_000;
+const LINK_CODE_LEN: usize = 8;
+// No 0/O or 1/I, so codes survive being read aloud or retyped
+const LINK_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Identity {
+    Direct(Principal),
+    OpenChat(Principal), // OpenChat user id
+}
+
+impl Identity {
+    fn key(&self) -> CompositeKey<String, StorablePrincipal> {
+        match self {
+            Identity::Direct(principal) => CompositeKey("direct".to_string(), StorablePrincipal(*principal)),
+            Identity::OpenChat(user_id) => CompositeKey("openchat".to_string(), StorablePrincipal(*user_id)),
+        }
+    }
+
+    fn principal(&self) -> Principal {
+        match self {
+            Identity::Direct(principal) | Identity::OpenChat(principal) => *principal,
+        }
+    }
+}
+
+thread_local! {
+    // Pending link codes: code -> (issuing identity, expiry)
+    static LINK_CODES: RefCell<BTreeMap<String, (Identity, u64)>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+pub fn resolve(identity: Identity) -> Principal {
+    state::ACCOUNT_LINKS.with(|links| links.borrow().get(&identity.key()))
+        .map_or(identity.principal(), |account| account.0)
+}
+
+fn is_trusted_bot(principal: Principal) -> bool {
+    state::get_config().trusted_bots.is_some_and(|bots| bots.contains(&principal))
+}
+
+fn caller_identity(on_behalf_of: Option<Principal>) -> Result<Identity, String> {
+    let caller = ic_cdk::api::caller();
+    match on_behalf_of {
+        None => Ok(Identity::Direct(caller)),
+        Some(user_id) if is_trusted_bot(caller) => Ok(Identity::OpenChat(user_id)),
+        Some(_) => Err("Only the Darely OpenChat bot can act on behalf of other users.".to_string()),
+    }
+}
+
+// The account a call acts for: the OpenChat user named by a trusted bot, otherwise the caller.
+// Banned accounts are turned away here (see bans.rs).
+pub fn caller_account(on_behalf_of: Option<Principal>) -> Result<Principal, String> {
+    let user = resolve(caller_identity(on_behalf_of)?);
+    bans::check(user)?;
+    Ok(user)
+}
+
+// Whether the call is the trusted bot relaying a user's command (it enforces chat roles itself)
+pub fn relayed_by_bot(on_behalf_of: Option<Principal>) -> bool {
+    on_behalf_of.is_some() && is_trusted_bot(ic_cdk::api::caller())
+}
+
+// Another user named in a command: an OpenChat user id when the bot relays it, otherwise a principal
+pub fn target_account(target: Principal, on_behalf_of: Option<Principal>) -> Principal {
+    match on_behalf_of {
+        Some(_) => resolve(Identity::OpenChat(target)),
+        None => resolve(Identity::Direct(target)),
+    }
+}
+
+// --- Linking ---
+
+// A random code of up to 32 characters from LINK_CODE_ALPHABET (also used for invite codes)
+pub async fn random_code(len: usize) -> Result<String, String> {
+    let (random_bytes,) = ic_cdk::api::management_canister::main::raw_rand().await
+        .map_err(|(code, message)| format!("Failed to get randomness: {:?} {}", code, message))?;
+    Ok(random_bytes.iter()
+        .take(len)
+        .map(|byte| LINK_CODE_ALPHABET[*byte as usize % LINK_CODE_ALPHABET.len()] as char)
+        .collect())
+}
+
+// Issues a short-lived code; entering it from the other identity links the two
+pub async fn start_link(on_behalf_of: Option<Principal>) -> Result<String, String> {
+    let identity = caller_identity(on_behalf_of)?;
+    let code = random_code(LINK_CODE_LEN).await?;
+    let now = ic_cdk::api::time();
+    LINK_CODES.with(|codes| {
+        let mut codes = codes.borrow_mut();
+        codes.retain(|_, (_, expires_at)| *expires_at > now);
+        // One pending code per identity
+        codes.retain(|_, (issuer, _)| *issuer != identity);
+        codes.insert(code.clone(), (identity, now + LINK_CODE_TTL_NANOS));
+    });
+    Ok(code)
+}
+
+fn has_data(account: Principal) -> bool {
+    let key = StorablePrincipal(account);
+    state::is_registered(account)
+        || state::POINT_BALANCES.with(|b| b.borrow().contains_key(&key))
+}
+
+// Links the caller's identity with the one that issued `code`. Returns the shared account.
+pub fn confirm_link(code: &str, on_behalf_of: Option<Principal>) -> Result<Principal, String> {
+    let identity = caller_identity(on_behalf_of)?;
+    let now = ic_cdk::api::time();
+    let code = code.trim().to_uppercase();
+    let (issuer, _) = LINK_CODES.with(|codes| codes.borrow_mut().remove(&code))
+        .filter(|(_, expires_at)| *expires_at > now)
+        .ok_or_else(|| "That link code is invalid or has expired.".to_string())?;
+    let one_of_each = matches!(
+        (issuer, identity),
+        (Identity::Direct(_), Identity::OpenChat(_)) | (Identity::OpenChat(_), Identity::Direct(_))
+    );
+    if !one_of_each {
+        return Err("Enter the code from your other identity: OpenChat if you started directly, or directly if you started in OpenChat.".to_string());
+    }
+    let (issuer_account, own_account) = (resolve(issuer), resolve(identity));
+    if issuer_account == own_account {
+        return Err("These identities are already linked.".to_string());
+    }
+    // Data isn't merged, so the side without any joins the other
+    let (joining, account) = match (has_data(issuer_account), has_data(own_account)) {
+        (true, true) => return Err("Both identities already have a Darely profile, and profiles can't be merged.".to_string()),
+        (false, _) => (issuer, own_account),
+        (true, false) => (identity, issuer_account),
+    };
+    state::ACCOUNT_LINKS.with(|links| links.borrow_mut().insert(joining.key(), StorablePrincipal(account)));
+    Ok(account)
+}
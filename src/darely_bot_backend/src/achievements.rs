@@ -0,0 +1,127 @@
+use crate::events;
+use crate::moderation;
+use crate::state;
+use crate::themes;
+use crate::types::Badge;
+use crate::REWARD_MILESTONES;
+use candid::Principal;
+
+// --- Achievement Verification ---
+// Read-only badge checks for other dapps (e.g. "30-day streak holders get access"). Badges are
+// derived from live state rather than minted, so they can't be transferred and are lost when the
+// underlying condition no longer holds (a streak badge only covers the current streak).
+
+pub const MAX_BATCH: usize = 100;
+const MAX_STREAK_BADGE: u32 = 10_000;
+
+pub fn catalog() -> Vec<Badge> {
+    let mut badges = vec![
+        Badge { id: "registered".to_string(), description: "Has a Darely profile.".to_string() },
+        Badge { id: "first-dare".to_string(), description: "Completed at least one dare that passed review.".to_string() },
+        Badge { id: "moderator".to_string(), description: "Is a Darely moderator.".to_string() },
+        Badge {
+            id: "streak-<n>".to_string(),
+            description: format!("Current streak of at least n (1-{}), e.g. streak-30.", MAX_STREAK_BADGE),
+        },
+    ];
+    badges.extend(themes::list().into_iter().map(|theme| Badge {
+        id: format!("theme-{}-<year>", theme.slug),
+        description: format!("Completed a {} dare while the theme was running that year.", theme.input.name),
+    }));
+    badges.extend(events::list().into_iter().map(|event| Badge {
+        id: events::badge_id(event.id),
+        description: format!("Took part in the global event \"{}\" and the community reached its goal.", event.title),
+    }));
+    badges.extend(REWARD_MILESTONES.iter().map(|milestone| Badge {
+        id: format!("reward-{}", milestone),
+        description: format!("Redeemed the streak {} reward.", milestone),
+    }));
+    badges.extend(REWARD_MILESTONES.iter().map(|milestone| Badge {
+        id: format!("lucky-{}", milestone),
+        description: format!("Won the badge tier when spinning the wheel for the streak {} reward.", milestone),
+    }));
+    badges
+}
+
+fn has_completed_dare(user: Principal) -> bool {
+    state::SUBMISSIONS.with(|s| {
+        s.borrow().iter().any(|(_, submission)| {
+            submission.user == user
+                && submission.reward_task_id.is_none()
+                && submission.review.as_ref().is_some_and(|review| review.approved)
+        })
+    })
+}
+
+// Unknown badge ids verify as false
+pub fn verify(user: Principal, badge_id: &str) -> bool {
+    let Some(profile) = state::get_profile(user) else {
+        return false;
+    };
+    match badge_id {
+        "registered" => true,
+        "first-dare" => has_completed_dare(user),
+        "moderator" => moderation::is_moderator(user),
+        _ => {
+            if let Some(n) = badge_id.strip_prefix("streak-").and_then(|n| n.parse::<u32>().ok()) {
+                return (1..=MAX_STREAK_BADGE).contains(&n) && profile.streak >= n;
+            }
+            if badge_id.starts_with("theme-") {
+                return profile.theme_badges.is_some_and(|badges| badges.iter().any(|badge| badge == badge_id));
+            }
+            if let Some(id) = badge_id.strip_prefix("event-").and_then(|id| id.parse::<u64>().ok()) {
+                return profile.event_badges.is_some_and(|badges| badges.contains(&id));
+            }
+            if let Some(m) = badge_id.strip_prefix("lucky-").and_then(|m| m.parse::<u32>().ok()) {
+                return profile.lucky_badges.is_some_and(|badges| badges.contains(&m));
+            }
+            if let Some(m) = badge_id.strip_prefix("reward-").and_then(|m| m.parse::<u32>().ok()) {
+                return profile.redeemed_milestones.contains(&m);
+            }
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Review, Submission};
+
+    fn submit(id: u64, user: Principal, approved: Option<bool>) {
+        let submission = Submission {
+            id,
+            user,
+            proof_parts: vec!["photo".to_string()],
+            submitted_at: 0,
+            dare_id: Some(1),
+            review: approved.map(|approved| Review {
+                moderator: Principal::anonymous(),
+                approved,
+                reviewed_at: 0,
+                sla_applied: None,
+                reason: None,
+            }),
+            chat_id: None,
+            hint_used: None,
+            reward_task_id: None,
+            rating: None,
+            dare_text: None,
+            escalation_xp: None,
+            completed_at: None,
+            streak_pending: None,
+            previous_completed_at: None,
+        };
+        state::SUBMISSIONS.with(|s| s.borrow_mut().insert(id, submission));
+    }
+
+    #[test]
+    fn first_dare_waits_for_an_approved_review() {
+        let user = Principal::from_slice(&[7]);
+        submit(1, user, None);
+        submit(2, user, Some(false));
+        assert!(!has_completed_dare(user));
+        submit(3, user, Some(true));
+        assert!(has_completed_dare(user));
+    }
+}
@@ -0,0 +1,122 @@
+use crate::repository::{self, DareRepo};
+use crate::types::{DareAnalysis, DareSource, Difficulty};
+use std::collections::BTreeSet;
+
+// --- Keyword Heuristics ---
+
+// Words that usually mean a dare is quick and low-stakes
+const EASY_KEYWORDS: &[&str] = &[
+    "compliment", "smile", "emoji", "share", "post", "text", "send", "like", "message",
+    "selfie", "photo", "water", "thank", "wave", "gif", "meme",
+];
+// Words that usually mean a dare needs effort, courage, or a long time
+const HARD_KEYWORDS: &[&str] = &[
+    "stranger", "public", "sing", "dance", "perform", "video", "cold", "ice", "run", "marathon",
+    "fast", "without", "all", "speech", "record", "hours", "km", "miles", "costume",
+];
+
+// Base completion estimates per difficulty (minutes)
+const EASY_MINUTES: u32 = 5;
+const MEDIUM_MINUTES: u32 = 20;
+const HARD_MINUTES: u32 = 60;
+
+// Dares sharing at least this share of their words are flagged as near-duplicates
+const DUPLICATE_THRESHOLD_PERCENT: u32 = 70;
+
+// Lowercased alphanumeric words of a dare text
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn suggest_difficulty(words: &[String]) -> Difficulty {
+    let count_hits = |keywords: &[&str]| words.iter().filter(|w| keywords.contains(&w.as_str())).count() as i32;
+    let easy_hits = count_hits(EASY_KEYWORDS);
+    let hard_hits = count_hits(HARD_KEYWORDS);
+    // Long dares tend to involve more steps
+    let length_bonus = if words.len() > 25 { 1 } else { 0 };
+    let score = hard_hits * 2 + length_bonus - easy_hits;
+    if score >= 2 {
+        Difficulty::Hard
+    } else if score < 0 || (easy_hits > 0 && score == 0) {
+        Difficulty::Easy
+    } else {
+        Difficulty::Medium
+    }
+}
+
+// Uses an explicit duration in the text ("for 10 minutes", "2 hours") when present
+fn estimate_minutes(words: &[String], difficulty: &Difficulty) -> u32 {
+    for pair in words.windows(2) {
+        if let Ok(amount) = pair[0].parse::<u32>() {
+            match pair[1].as_str() {
+                "minute" | "minutes" | "min" | "mins" => return amount,
+                "hour" | "hours" => return amount.saturating_mul(60),
+                "day" | "days" => return amount.saturating_mul(24 * 60),
+                _ => {}
+            }
+        }
+    }
+    match difficulty {
+        Difficulty::Easy => EASY_MINUTES,
+        Difficulty::Medium => MEDIUM_MINUTES,
+        Difficulty::Hard => HARD_MINUTES,
+    }
+}
+
+// Word-set overlap (Jaccard index) as a percentage
+fn similarity_percent(a: &BTreeSet<String>, b: &BTreeSet<String>) -> u32 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0;
+    }
+    (a.intersection(b).count() * 100 / union) as u32
+}
+
+// Finds the stored dare most similar to `words`, if it crosses the duplicate threshold
+fn find_near_duplicate(words: &[String]) -> Option<(u64, u32)> {
+    let word_set: BTreeSet<String> = words.iter().cloned().collect();
+    let mut best: Option<(u64, u32)> = None;
+    repository::dares().scan(&mut |dare| {
+        if dare.source != DareSource::Custom {
+            let other: BTreeSet<String> = tokenize(&dare.text).into_iter().collect();
+            let similarity = similarity_percent(&word_set, &other);
+            // Equal scores go to the later dare
+            if similarity >= DUPLICATE_THRESHOLD_PERCENT && best.is_none_or(|(_, top)| similarity >= top) {
+                best = Some((dare.id, similarity));
+            }
+        }
+        true
+    });
+    best
+}
+
+// --- Public API ---
+
+pub fn analyze_dare(text: &str) -> DareAnalysis {
+    let words = tokenize(text);
+    let suggested_difficulty = suggest_difficulty(&words);
+    let estimated_minutes = estimate_minutes(&words, &suggested_difficulty);
+    let duplicate = find_near_duplicate(&words);
+    DareAnalysis {
+        suggested_difficulty,
+        estimated_minutes,
+        near_duplicate_of: duplicate.map(|(id, _)| id),
+        similarity_percent: duplicate.map_or(0, |(_, similarity)| similarity),
+    }
+}
+
+// Human-readable summary used in /add_dare confirmations
+pub fn describe(analysis: &DareAnalysis) -> String {
+    let mut summary = format!(
+        "Suggested difficulty: {:?} (~{} min to complete).",
+        analysis.suggested_difficulty, analysis.estimated_minutes
+    );
+    if let Some(id) = analysis.near_duplicate_of {
+        summary.push_str(&format!(" Warning: {}% similar to existing dare #{}.", analysis.similarity_percent, id));
+    }
+    summary
+}
@@ -0,0 +1,208 @@
+use crate::ranking;
+use crate::repository::{self, DareRepo, UserRepo};
+use crate::rewards;
+use crate::router::{error_response, json_response, ParsedRequest, API_VERSION};
+use crate::selection;
+use crate::state;
+use crate::suggestions;
+use crate::types::{DareSource, Difficulty, HttpResponse};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+// --- REST Route Handlers ---
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 100;
+
+fn parse_difficulty(value: &str) -> Option<Difficulty> {
+    match value.to_lowercase().as_str() {
+        "easy" => Some(Difficulty::Easy),
+        "medium" => Some(Difficulty::Medium),
+        "hard" => Some(Difficulty::Hard),
+        _ => None,
+    }
+}
+
+fn page_bounds(request: &ParsedRequest) -> (usize, usize) {
+    let offset = request.query_param("offset").and_then(|v| v.parse().ok()).unwrap_or(0);
+    let limit = request.query_param("limit").and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+    (offset, limit)
+}
+
+// GET /api/v1/dares
+pub fn list_dares(request: &ParsedRequest) -> HttpResponse {
+    let difficulty = match request.query_param("difficulty") {
+        Some(value) => match parse_difficulty(value) {
+            Some(difficulty) => Some(difficulty),
+            None => return error_response(400, "Invalid difficulty. Use Easy, Medium or Hard."),
+        },
+        None => None,
+    };
+    let (offset, limit) = page_bounds(request);
+    let mut matching: Vec<Value> = Vec::new();
+    repository::dares().scan(&mut |dare| {
+        if dare.source != DareSource::Custom && difficulty.as_ref().is_none_or(|d| &dare.difficulty == d) {
+            matching.push(json!({
+                "id": dare.id,
+                "text": dare.text,
+                "difficulty": format!("{:?}", dare.difficulty),
+                "source": format!("{:?}", dare.source),
+            }));
+        }
+        true
+    });
+    let total = matching.len();
+    let dares: Vec<Value> = matching.into_iter().skip(offset).take(limit).collect();
+    json_response(200, &json!({ "total": total, "offset": offset, "dares": dares }))
+}
+
+// GET /api/v1/leaderboard
+pub fn leaderboard(_request: &ParsedRequest) -> HttpResponse {
+    let snapshot = ranking::snapshot();
+    let entries: Vec<Value> = snapshot.entries.into_iter()
+        .enumerate()
+        .map(|(index, (principal, streak))| json!({ "rank": index + 1, "principal": principal.to_text(), "streak": streak }))
+        .collect();
+    json_response(200, &json!({ "entries": entries, "computed_at": snapshot.computed_at, "users_ranked": snapshot.users_ranked }))
+}
+
+// GET /api/v1/stats
+pub fn stats(_request: &ParsedRequest) -> HttpResponse {
+    let users = repository::users().len();
+    let dares = repository::dares().len();
+    let curated_dares = selection::count_by_source(DareSource::Curated);
+    let submissions = state::SUBMISSIONS.with(|s| s.borrow().len());
+    json_response(200, &json!({
+        "users": users,
+        "dares": dares,
+        "curated_dares": curated_dares,
+        "submissions": submissions,
+        "api_version": API_VERSION,
+    }))
+}
+
+// GET /api/v1/me/stats (session required)
+pub fn my_stats(request: &ParsedRequest) -> HttpResponse {
+    let Some(user) = request.caller else {
+        return error_response(401, "Session required.");
+    };
+    match state::get_profile(user) {
+        Some(profile) => json_response(200, &json!({
+            "principal": user.to_text(),
+            "streak": profile.streak,
+            "redeemed_milestones": profile.redeemed_milestones,
+            "current_dare_id": profile.current_dare_id,
+            "outstanding_task_ids": rewards::outstanding_tasks(user).into_iter().map(|(task, _)| task.id).collect::<Vec<u64>>(),
+        })),
+        None => error_response(404, "User not found. Please /register first."),
+    }
+}
+
+#[derive(Deserialize)]
+struct SuggestBody {
+    text: String,
+    difficulty: Option<String>,
+}
+
+// POST /api/v1/suggest (signed or API key; the suggestion is attributed to the signer or key)
+pub fn suggest(request: &ParsedRequest) -> HttpResponse {
+    let body: SuggestBody = match serde_json::from_slice(&request.body) {
+        Ok(body) => body,
+        Err(e) => return error_response(400, &format!("Invalid JSON body: {}", e)),
+    };
+    let difficulty = match body.difficulty.as_deref() {
+        Some(value) => match parse_difficulty(value) {
+            Some(difficulty) => Some(difficulty),
+            None => return error_response(400, "Invalid difficulty. Use Easy, Medium or Hard."),
+        },
+        None => None,
+    };
+    match suggestions::add_suggestion(body.text, difficulty, request.caller, request.api_key) {
+        Ok(id) => json_response(201, &json!({ "id": id })),
+        Err(e) => error_response(400, &e),
+    }
+}
+
+// --- Schemas (used by the OpenAPI document) ---
+
+fn difficulty_schema() -> Value {
+    json!({ "type": "string", "enum": ["Easy", "Medium", "Hard"] })
+}
+
+pub fn dare_list_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "total": { "type": "integer" },
+            "offset": { "type": "integer" },
+            "dares": { "type": "array", "items": {
+                "type": "object",
+                "properties": {
+                    "id": { "type": "integer" },
+                    "text": { "type": "string" },
+                    "difficulty": difficulty_schema(),
+                    "source": { "type": "string", "enum": ["Curated", "Llm"] },
+                },
+            } },
+        },
+    })
+}
+
+pub fn leaderboard_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "entries": { "type": "array", "items": {
+                "type": "object",
+                "properties": {
+                    "rank": { "type": "integer" },
+                    "principal": { "type": "string" },
+                    "streak": { "type": "integer" },
+                },
+            } },
+            "computed_at": { "type": "integer" },
+            "users_ranked": { "type": "integer" },
+        },
+    })
+}
+
+pub fn stats_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "users": { "type": "integer" },
+            "dares": { "type": "integer" },
+            "curated_dares": { "type": "integer" },
+            "submissions": { "type": "integer" },
+            "api_version": { "type": "string" },
+        },
+    })
+}
+
+pub fn my_stats_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "principal": { "type": "string" },
+            "streak": { "type": "integer" },
+            "redeemed_milestones": { "type": "array", "items": { "type": "integer" } },
+            "current_dare_id": { "type": "integer", "nullable": true },
+            "outstanding_task_ids": { "type": "array", "items": { "type": "integer" } },
+        },
+    })
+}
+
+pub fn suggest_request_schema() -> Value {
+    json!({
+        "type": "object",
+        "required": ["text"],
+        "properties": {
+            "text": { "type": "string", "maxLength": crate::MAX_DARE_TEXT_LEN },
+            "difficulty": difficulty_schema(),
+        },
+    })
+}
+
+pub fn suggest_response_schema() -> Value {
+    json!({ "type": "object", "properties": { "id": { "type": "integer" } } })
+}
@@ -0,0 +1,139 @@
+use crate::environment;
+use crate::sessions;
+use crate::state;
+use crate::types::{ApiKey, ApiKeyInfo, ApiScope};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+
+// --- Partner API Keys ---
+// Admin-issued keys for the REST API, sent as `X-API-Key: dk_<base64url(key id | 24 random bytes)>`.
+// Only the SHA-256 of a key is stored; the key itself is returned once at creation. Each key has
+// scopes and a per-minute request limit (fixed one-minute windows, relaxed outside prod, see
+// environment.rs). Revoked keys stop working immediately but stay listed for auditing.
+
+const KEY_PREFIX: &str = "dk_";
+const SECRET_LEN: usize = 24;
+const MINUTE_NANOS: u64 = 60 * 1_000_000_000;
+const DEFAULT_RATE_LIMIT: u32 = 60;
+const MAX_RATE_LIMIT: u32 = 10_000;
+const MAX_NAME_LEN: usize = 60;
+
+impl ApiScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::ReadLeaderboard => "read:leaderboard",
+            ApiScope::ReadDares => "read:dares",
+            ApiScope::WriteSuggestions => "write:suggestions",
+        }
+    }
+}
+
+// Creates a key and returns it in full (the only time it is visible)
+pub async fn create(name: String, scopes: Vec<ApiScope>, rate_limit_per_minute: Option<u32>) -> Result<String, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(format!("Key name must be 1-{} characters.", MAX_NAME_LEN));
+    }
+    if scopes.is_empty() {
+        return Err("A key needs at least one scope.".to_string());
+    }
+    let rate_limit = rate_limit_per_minute.unwrap_or(DEFAULT_RATE_LIMIT);
+    if rate_limit == 0 || rate_limit > MAX_RATE_LIMIT {
+        return Err(format!("rate_limit_per_minute must be between 1 and {}.", MAX_RATE_LIMIT));
+    }
+    let (random_bytes,) = ic_cdk::api::management_canister::main::raw_rand().await
+        .map_err(|(code, message)| format!("Failed to get randomness: {:?} {}", code, message))?;
+
+    let mut scopes = scopes;
+    scopes.sort();
+    scopes.dedup();
+    state::API_KEYS.with(|keys_ref| {
+        let mut keys = keys_ref.borrow_mut();
+        // The id is assigned after the await so concurrent creations can't collide
+        let id = keys.last_key_value().map_or(0, |(id, _)| id + 1);
+        let mut raw = id.to_be_bytes().to_vec();
+        raw.extend_from_slice(&random_bytes[..SECRET_LEN]);
+        keys.insert(id, ApiKey {
+            id,
+            name,
+            key_hash: Sha256::digest(&raw).to_vec(),
+            scopes,
+            rate_limit_per_minute: rate_limit,
+            created_at: ic_cdk::api::time(),
+            revoked_at: None,
+            last_used_at: None,
+            request_count: 0,
+            window_started_at: 0,
+            window_count: 0,
+        });
+        Ok(format!("{}{}", KEY_PREFIX, BASE64_URL.encode(raw)))
+    })
+}
+
+pub fn revoke(id: u64) -> Result<(), String> {
+    state::API_KEYS.with(|keys_ref| {
+        let mut keys = keys_ref.borrow_mut();
+        let mut key = keys.get(&id).ok_or_else(|| format!("API key {} not found.", id))?;
+        if key.revoked_at.is_some() {
+            return Err(format!("API key {} is already revoked.", id));
+        }
+        key.revoked_at = Some(ic_cdk::api::time());
+        keys.insert(id, key);
+        Ok(())
+    })
+}
+
+pub fn list() -> Vec<ApiKeyInfo> {
+    state::API_KEYS.with(|keys| {
+        keys.borrow().iter().map(|(_, key)| ApiKeyInfo {
+            id: key.id,
+            name: key.name,
+            scopes: key.scopes,
+            rate_limit_per_minute: key.rate_limit_per_minute,
+            created_at: key.created_at,
+            revoked_at: key.revoked_at,
+            last_used_at: key.last_used_at,
+            request_count: key.request_count,
+        }).collect()
+    })
+}
+
+// Checks a presented key for the scope and counts the request against its rate limit.
+// Returns the key id, or the HTTP status and message to reject the request with.
+pub fn authorize(presented: &str, scope: ApiScope) -> Result<u64, (u16, String)> {
+    let invalid = || (401, "Invalid API key.".to_string());
+    let raw = presented.strip_prefix(KEY_PREFIX)
+        .and_then(|encoded| BASE64_URL.decode(encoded).ok())
+        .filter(|raw| raw.len() == 8 + SECRET_LEN)
+        .ok_or_else(invalid)?;
+    let id = u64::from_be_bytes(raw[..8].try_into().unwrap());
+    let now = ic_cdk::api::time();
+
+    state::API_KEYS.with(|keys_ref| {
+        let mut keys = keys_ref.borrow_mut();
+        let mut key = keys.get(&id).ok_or_else(invalid)?;
+        if !sessions::constant_time_eq(&Sha256::digest(&raw), &key.key_hash) {
+            return Err(invalid());
+        }
+        if key.revoked_at.is_some() {
+            return Err((401, "This API key has been revoked.".to_string()));
+        }
+        if !key.scopes.contains(&scope) {
+            return Err((403, format!("This API key lacks the {} scope.", scope.as_str())));
+        }
+        if now.saturating_sub(key.window_started_at) >= MINUTE_NANOS {
+            key.window_started_at = now;
+            key.window_count = 0;
+        }
+        let limit = key.rate_limit_per_minute.saturating_mul(environment::rate_limit_multiplier());
+        if key.window_count >= limit {
+            return Err((429, format!("Rate limit of {} requests per minute exceeded.", limit)));
+        }
+        key.window_count += 1;
+        key.request_count += 1;
+        key.last_used_at = Some(now);
+        keys.insert(id, key);
+        Ok(id)
+    })
+}
@@ -0,0 +1,65 @@
+use crate::types::{ApiVersion, DeprecatedMethod};
+
+// --- API Versioning ---
+// The candid interface is versioned so frontends and generated TypeScript clients can target a
+// stable interface across upgrades. candid/v<N>.did keeps each version's interface: older files
+// are frozen, and the current version's file follows the code until the version is released.
+// Within a version, changes are additive only (new methods, new optional fields). Renaming or
+// removing a method, or changing its arguments, bumps API_VERSION; the old method stays listed in
+// DEPRECATED for at least one version before it is removed.
+//
+// darely_bot_backend.did and the current version's file are generated from `export_candid!`;
+// `UPDATE_CANDID=1 cargo test` rewrites both, and a plain `cargo test` fails while they are stale.
+
+pub const API_VERSION: u32 = 1;
+
+// (method, deprecated since, replacement, removed in)
+const DEPRECATED: &[(&str, u32, Option<&str>, Option<u32>)] = &[];
+
+pub fn current() -> ApiVersion {
+    ApiVersion {
+        version: API_VERSION,
+        frozen: (1..API_VERSION).collect(),
+        deprecated: DEPRECATED.iter().map(|&(method, since, replacement, removed_in)| DeprecatedMethod {
+            method: method.to_string(),
+            since,
+            replacement: replacement.map(str::to_string),
+            removed_in,
+        }).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn manifest_path(file: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(file)
+    }
+
+    #[test]
+    fn candid_files_match_the_interface() {
+        let interface = crate::__export_service();
+        let files = [manifest_path("darely_bot_backend.did"), manifest_path(&format!("candid/v{}.did", API_VERSION))];
+        if std::env::var_os("UPDATE_CANDID").is_some() {
+            for file in &files {
+                std::fs::write(file, &interface).unwrap();
+            }
+        }
+        for file in &files {
+            let stored = std::fs::read_to_string(file).unwrap_or_default();
+            assert!(stored == interface, "{} is stale; run `UPDATE_CANDID=1 cargo test`.", file.display());
+        }
+    }
+
+    #[test]
+    fn every_version_has_an_interface_file() {
+        for version in 1..=API_VERSION {
+            assert!(manifest_path(&format!("candid/v{}.did", version)).exists(), "candid/v{}.did is missing", version);
+        }
+        for &(method, since, _, removed_in) in DEPRECATED {
+            assert!(since <= API_VERSION && removed_in.is_none_or(|removed_in| removed_in > since), "{}", method);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use crate::history;
+use crate::moderation;
+use crate::outbox;
+use crate::state;
+use crate::types::{Appeal, AppealDecision, HistoryEvent, Review};
+use candid::Principal;
+
+// --- Appeals ---
+// A user can appeal a rejected dare submission once. The appeal goes to a moderator other than
+// the one who rejected it (admins can decide it too, e.g. when no other moderator exists). A
+// granted appeal approves the submission and gives back the streak point and XP the rejection
+// took; either outcome is logged to the user's history and sent to them as a DM.
+
+const MAX_ARGUMENT_LEN: usize = 500;
+
+pub fn file(user: Principal, submission_id: u64, argument: &str) -> Result<Appeal, String> {
+    let argument = argument.trim();
+    if argument.is_empty() || argument.len() > MAX_ARGUMENT_LEN {
+        return Err(format!("Explain your appeal in 1-{} bytes.", MAX_ARGUMENT_LEN));
+    }
+    let submission = state::SUBMISSIONS.with(|s| s.borrow().get(&submission_id))
+        .filter(|submission| submission.user == user)
+        .ok_or_else(|| format!("You have no submission #{}.", submission_id))?;
+    if submission.reward_task_id.is_some() {
+        return Err("Bonus task proofs can't be appealed; send a new proof with /complete_task.".to_string());
+    }
+    let rejected_by = match &submission.review {
+        Some(review) if !review.approved => review.moderator,
+        _ => return Err(format!("Submission #{} wasn't rejected.", submission_id)),
+    };
+    if state::APPEALS.with(|a| a.borrow().contains_key(&submission_id)) {
+        return Err(format!("Submission #{} has already been appealed.", submission_id));
+    }
+    let appeal = Appeal {
+        submission_id,
+        user,
+        argument: argument.to_string(),
+        filed_at: ic_cdk::api::time(),
+        rejected_by,
+        assigned_to: moderation::pick_moderator(rejected_by),
+        decision: None,
+    };
+    state::APPEALS.with(|a| a.borrow_mut().insert(submission_id, appeal.clone()));
+    history::log_event(user, HistoryEvent::AppealFiled { submission_id });
+    if let Some(moderator) = appeal.assigned_to {
+        outbox::queue(moderator, format!("⚖️ New appeal for submission #{}: {}", submission_id, appeal.argument), None);
+    }
+    Ok(appeal)
+}
+
+// Open appeals the caller may decide (all of them for admins)
+pub fn pending_for(caller: Principal, is_admin: bool) -> Vec<Appeal> {
+    state::APPEALS.with(|a| {
+        a.borrow().iter()
+            .map(|(_, appeal)| appeal)
+            .filter(|appeal| appeal.decision.is_none())
+            .filter(|appeal| is_admin || may_decide(appeal, caller))
+            .collect()
+    })
+}
+
+fn may_decide(appeal: &Appeal, moderator: Principal) -> bool {
+    moderator != appeal.rejected_by
+        && moderator != appeal.user
+        && moderation::is_moderator(moderator)
+        && appeal.assigned_to.is_none_or(|assigned| assigned == moderator)
+}
+
+pub fn decide(moderator: Principal, is_admin: bool, submission_id: u64, granted: bool, note: Option<String>) -> Result<(), String> {
+    let note = note.map(|note| note.trim().to_string()).filter(|note| !note.is_empty());
+    if note.as_ref().is_some_and(|note| note.len() > moderation::MAX_REASON_LEN) {
+        return Err(format!("The note is too long (max {} bytes).", moderation::MAX_REASON_LEN));
+    }
+    let mut appeal = state::APPEALS.with(|a| a.borrow().get(&submission_id))
+        .ok_or_else(|| format!("No appeal for submission #{}.", submission_id))?;
+    if appeal.decision.is_some() {
+        return Err(format!("The appeal for submission #{} was already decided.", submission_id));
+    }
+    if moderator == appeal.user {
+        return Err("You can't decide your own appeal.".to_string());
+    }
+    if moderator == appeal.rejected_by {
+        return Err("You rejected this submission, so another moderator has to decide the appeal.".to_string());
+    }
+    if !is_admin && !may_decide(&appeal, moderator) {
+        return Err("This appeal is assigned to another moderator.".to_string());
+    }
+    let now = ic_cdk::api::time();
+    if granted {
+        moderation::overturn(submission_id, Review {
+            moderator,
+            approved: true,
+            reviewed_at: now,
+            sla_applied: None,
+            reason: note.clone(),
+        })?;
+    }
+    appeal.decision = Some(AppealDecision { moderator, granted, decided_at: now, note: note.clone() });
+    let user = appeal.user;
+    state::APPEALS.with(|a| a.borrow_mut().insert(submission_id, appeal));
+    history::log_event(user, HistoryEvent::AppealDecided { submission_id, granted });
+
+    let mut text = if granted {
+        format!("⚖️ Your appeal for submission #{} was granted: it's approved and your streak point is back.", submission_id)
+    } else {
+        format!("⚖️ Your appeal for submission #{} was turned down.", submission_id)
+    };
+    if let Some(note) = note {
+        text.push_str(&format!(" Note: {}", note));
+    }
+    outbox::queue(user, text, None);
+    Ok(())
+}
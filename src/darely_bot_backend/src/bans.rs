@@ -0,0 +1,32 @@
+use crate::state;
+use crate::types::StorablePrincipal;
+use candid::Principal;
+
+// --- Bans ---
+// An admin can ban a user: every command made for that account is refused (see
+// accounts::caller_account) until they are unbanned. The profile and history are kept as they
+// are, so the ban can be lifted without loss, and the admin user listing can filter on it.
+
+pub fn banned_at(user: Principal) -> Option<u64> {
+    state::BANNED_USERS.with(|b| b.borrow().get(&StorablePrincipal(user)))
+}
+
+pub fn check(user: Principal) -> Result<(), String> {
+    match banned_at(user) {
+        Some(_) => Err("This account has been banned from Darely.".to_string()),
+        None => Ok(()),
+    }
+}
+
+// Returns whether anything changed
+pub fn set(user: Principal, banned: bool) -> bool {
+    state::BANNED_USERS.with(|b| {
+        let mut bans = b.borrow_mut();
+        let key = StorablePrincipal(user);
+        match (banned, bans.contains_key(&key)) {
+            (true, false) => bans.insert(key, ic_cdk::api::time()).is_none(),
+            (false, true) => bans.remove(&key).is_some(),
+            _ => false,
+        }
+    })
+}
@@ -0,0 +1,112 @@
+use crate::analysis;
+use crate::repository::{self, DareRepo};
+use crate::state;
+use crate::types::{Dare, UserProfile};
+use candid::Principal;
+
+// --- Per-user Blocklists ---
+// Users can block individual dares and tags. A tag is a single keyword: a dare "has" the tag
+// when its text contains that word (or its plural). Lists are capped to keep profiles bounded.
+
+const MAX_BLOCKED_DARES: usize = 25;
+const MAX_BLOCKED_TAGS: usize = 10;
+const MAX_TAG_LEN: usize = 24;
+
+// A user's blocklist as applied during dare selection
+pub struct Blocklist {
+    pub dare_ids: Vec<u64>,
+    pub tags: Vec<String>,
+}
+
+impl Blocklist {
+    pub fn of(profile: &UserProfile) -> Self {
+        Blocklist {
+            dare_ids: profile.blocked_dare_ids.clone().unwrap_or_default(),
+            tags: profile.blocked_tags.clone().unwrap_or_default(),
+        }
+    }
+
+    pub fn matches_text(&self, text: &str) -> bool {
+        if self.tags.is_empty() {
+            return false;
+        }
+        analysis::tokenize(text).iter().any(|word| {
+            let singular = word.strip_suffix('s').unwrap_or(word);
+            self.tags.iter().any(|tag| tag == word || tag == singular)
+        })
+    }
+
+    pub fn blocks(&self, dare: &Dare) -> bool {
+        self.dare_ids.contains(&dare.id) || self.matches_text(&dare.text)
+    }
+}
+
+// Tags are single lowercase words (letters and digits, matching how dare texts are tokenized)
+fn normalize_tag(tag: &str) -> Result<String, String> {
+    let tag = tag.trim().trim_start_matches('#').to_lowercase();
+    if tag.is_empty() || tag.len() > MAX_TAG_LEN || !tag.chars().all(char::is_alphanumeric) {
+        return Err(format!("A tag must be a single word of up to {} characters.", MAX_TAG_LEN));
+    }
+    Ok(tag)
+}
+
+pub fn block_dare(user: Principal, dare_id: u64) -> Result<(), String> {
+    if repository::dares().get(dare_id).is_none() {
+        return Err(format!("Dare #{} not found.", dare_id));
+    }
+    state::update_profile(user, |profile| {
+        if profile.penalty_since.is_some() && profile.penalty_dare_id == Some(dare_id) {
+            return Err("A hardcore penalty dare can't be blocked; finish it first.".to_string());
+        }
+        let blocked = profile.blocked_dare_ids.get_or_insert_with(Vec::new);
+        if blocked.contains(&dare_id) {
+            return Err(format!("Dare #{} is already blocked.", dare_id));
+        }
+        if blocked.len() >= MAX_BLOCKED_DARES {
+            return Err(format!("You can block at most {} dares. Use /unblock_dare first.", MAX_BLOCKED_DARES));
+        }
+        blocked.push(dare_id);
+        // Don't keep the blocked dare as the active one
+        if profile.current_dare_id == Some(dare_id) {
+            profile.current_dare_id = None;
+            profile.current_dare_chat = None;
+            profile.current_dare_text = None;
+        }
+        Ok(())
+    })
+}
+
+pub fn unblock_dare(user: Principal, dare_id: u64) -> Result<(), String> {
+    state::update_profile(user, |profile| {
+        let blocked = profile.blocked_dare_ids.get_or_insert_with(Vec::new);
+        let before = blocked.len();
+        blocked.retain(|&id| id != dare_id);
+        if blocked.len() == before { Err(format!("Dare #{} is not blocked.", dare_id)) } else { Ok(()) }
+    })
+}
+
+// Returns the normalized tag
+pub fn block_tag(user: Principal, tag: &str) -> Result<String, String> {
+    let tag = normalize_tag(tag)?;
+    state::update_profile(user, |profile| {
+        let blocked = profile.blocked_tags.get_or_insert_with(Vec::new);
+        if blocked.contains(&tag) {
+            return Err(format!("'{}' is already blocked.", tag));
+        }
+        if blocked.len() >= MAX_BLOCKED_TAGS {
+            return Err(format!("You can block at most {} tags. Use /unblock_tag first.", MAX_BLOCKED_TAGS));
+        }
+        blocked.push(tag.clone());
+        Ok(tag.clone())
+    })
+}
+
+pub fn unblock_tag(user: Principal, tag: &str) -> Result<String, String> {
+    let tag = normalize_tag(tag)?;
+    state::update_profile(user, |profile| {
+        let blocked = profile.blocked_tags.get_or_insert_with(Vec::new);
+        let before = blocked.len();
+        blocked.retain(|t| t != &tag);
+        if blocked.len() == before { Err(format!("'{}' is not blocked.", tag)) } else { Ok(tag.clone()) }
+    })
+}
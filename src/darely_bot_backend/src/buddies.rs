@@ -0,0 +1,189 @@
+use crate::gallery;
+use crate::history;
+use crate::invites::{self, Consent};
+use crate::outbox;
+use crate::state;
+use crate::types::{BuddyLink, HistoryEvent, StorablePrincipal};
+use candid::Principal;
+
+// --- Buddies ---
+// Two members can pair up to keep each other going. `/buddy pair @user` sends a request that the
+// other member has to accept (or decline) within three days (see invites.rs); each user has at
+// most one buddy.
+// Buddies get a DM when the other completes a dare and when the other has gone two days without
+// one (once per dry spell). Every week the pair is evaluated: when both grew their streak and
+// neither went quiet, both earn BUDDY_XP. The pair is stored under both users, each side holding
+// its own weekly snapshot.
+
+static CONSENT: Consent = Consent { invites: &state::BUDDY_INVITES, noun: "buddy request" };
+const LAPSE_NANOS: u64 = 2 * 24 * 60 * 60 * 1_000_000_000;
+const WEEK_NANOS: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+pub const BUDDY_XP: u64 = 50;
+
+fn link(user: Principal) -> Option<BuddyLink> {
+    state::BUDDIES.with(|b| b.borrow().get(&StorablePrincipal(user)))
+}
+
+fn save(user: Principal, link: BuddyLink) {
+    state::BUDDIES.with(|b| b.borrow_mut().insert(StorablePrincipal(user), link));
+}
+
+fn streak(user: Principal) -> u32 {
+    state::get_profile(user).map_or(0, |profile| profile.streak)
+}
+
+fn new_link(buddy: Principal, streak: u32, now: u64) -> BuddyLink {
+    BuddyLink {
+        buddy,
+        paired_at: now,
+        week_started_at: now,
+        streak_at_week_start: streak,
+        last_completed_at: None,
+        lapse_notified: false,
+        lapsed_this_week: false,
+        weeks_together: 0,
+    }
+}
+
+pub fn pair(user: Principal, target: Principal) -> Result<String, String> {
+    let now = ic_cdk::api::time();
+    if user == target {
+        return Err("You can't be your own buddy.".to_string());
+    }
+    if !state::is_registered(user) {
+        return Err("User not found. Please /register first.".to_string());
+    }
+    if !state::is_registered(target) {
+        return Err(format!("{} hasn't joined Darely yet.", gallery::display_name(target)));
+    }
+    if let Some(link) = link(user) {
+        return Err(format!("You're already buddies with {}. Use /buddy unpair first.", gallery::display_name(link.buddy)));
+    }
+    if link(target).is_some() {
+        return Err(format!("{} already has a buddy.", gallery::display_name(target)));
+    }
+    let message = format!("🤝 {} wants to be your Darely buddy. Reply /buddy accept or /buddy decline within 3 days.", gallery::display_name(user));
+    match CONSENT.request(user, target, now, message)? {
+        invites::Request::Mutual => accept(user),
+        invites::Request::Sent => Ok(format!("Buddy request sent to {}.", gallery::display_name(target))),
+    }
+}
+
+pub fn accept(user: Principal) -> Result<String, String> {
+    let now = ic_cdk::api::time();
+    let inviter = CONSENT.take(user, now)?.from;
+    if link(user).is_some() || link(inviter).is_some() {
+        return Err("One of you has paired with someone else in the meantime.".to_string());
+    }
+    save(user, new_link(inviter, streak(user), now));
+    save(inviter, new_link(user, streak(inviter), now));
+    outbox::queue(inviter, format!("🤝 {} accepted: you're now buddies!", gallery::display_name(user)), None);
+    Ok(format!(
+        "🤝 You and {} are now buddies. Keep both your streaks going for a week to earn {} XP each.",
+        gallery::display_name(inviter), BUDDY_XP
+    ))
+}
+
+pub fn decline(user: Principal) -> Result<String, String> {
+    CONSENT.decline(user)
+}
+
+pub fn unpair(user: Principal) -> Result<String, String> {
+    let link = link(user).ok_or_else(|| "You don't have a buddy.".to_string())?;
+    state::BUDDIES.with(|b| {
+        let mut buddies = b.borrow_mut();
+        buddies.remove(&StorablePrincipal(user));
+        buddies.remove(&StorablePrincipal(link.buddy));
+    });
+    outbox::queue(link.buddy, format!("{} ended your buddy pairing.", gallery::display_name(user)), None);
+    Ok(format!("You and {} are no longer buddies.", gallery::display_name(link.buddy)))
+}
+
+pub fn describe(user: Principal) -> Result<String, String> {
+    let now = ic_cdk::api::time();
+    let Some(link) = link(user) else {
+        return Ok(match CONSENT.open(user, now) {
+            Some(invite) => format!("{} wants to be your buddy: /buddy accept or /buddy decline.", gallery::display_name(invite.from)),
+            None => "You don't have a buddy yet. Pair up with /buddy pair @user.".to_string(),
+        });
+    };
+    let days_left = (link.week_started_at + WEEK_NANOS).saturating_sub(now).div_ceil(24 * 60 * 60 * 1_000_000_000);
+    Ok(format!(
+        "🤝 Your buddy is {} (streak {}). Weeks kept together: {}. This week ends in {} day(s).",
+        gallery::display_name(link.buddy), streak(link.buddy), link.weeks_together, days_left
+    ))
+}
+
+// Called for every submitted dare: tells the user's buddy
+pub fn note_completion(user: Principal, streak: u32) {
+    let Some(mut link) = link(user) else { return };
+    link.last_completed_at = Some(ic_cdk::api::time());
+    link.lapse_notified = false;
+    let buddy = link.buddy;
+    save(user, link);
+    outbox::queue(buddy, format!("🎉 Your buddy {} just completed a dare (streak {}).", gallery::display_name(user), streak), None);
+}
+
+// Timer: expires invites, reports lapses and evaluates finished weeks. Returns the messages queued.
+pub fn run() -> u32 {
+    let now = ic_cdk::api::time();
+    CONSENT.purge(now);
+
+    let links: Vec<(Principal, BuddyLink)> = state::BUDDIES.with(|b| b.borrow().iter().map(|(key, link)| (key.0, link)).collect());
+    let mut queued = 0;
+    for (user, mut link) in links.iter().cloned() {
+        let quiet_since = link.last_completed_at.unwrap_or(link.paired_at);
+        if !link.lapse_notified && now.saturating_sub(quiet_since) >= LAPSE_NANOS {
+            link.lapse_notified = true;
+            link.lapsed_this_week = true;
+            save(user, link.clone());
+            outbox::queue(link.buddy, format!(
+                "⏰ Your buddy {} hasn't done a dare in 2 days. Give them a nudge!", gallery::display_name(user)
+            ), None);
+            queued += 1;
+        }
+    }
+
+    // Each pair is evaluated once, from the side with the smaller principal
+    for (user, _) in links.into_iter().filter(|(user, link)| *user < link.buddy) {
+        let (Some(mine), Some(theirs)) = (link(user), link_of_buddy(user)) else { continue };
+        if now < mine.week_started_at + WEEK_NANOS {
+            continue;
+        }
+        let kept = |link: &BuddyLink, owner: Principal| !link.lapsed_this_week && streak(owner) > link.streak_at_week_start;
+        let buddy = mine.buddy;
+        let both_kept = kept(&mine, user) && kept(&theirs, buddy);
+        for (owner, mut side) in [(user, mine), (buddy, theirs)] {
+            side.week_started_at = now;
+            side.streak_at_week_start = streak(owner);
+            side.lapsed_this_week = side.lapse_notified;
+            if both_kept {
+                side.weeks_together += 1;
+            }
+            let partner = side.buddy;
+            save(owner, side);
+            if both_kept && award(owner, partner) {
+                outbox::queue(owner, format!(
+                    "🏅 You and {} both kept your streaks all week: +{} XP!", gallery::display_name(partner), BUDDY_XP
+                ), None);
+                queued += 1;
+            }
+        }
+    }
+    queued
+}
+
+fn link_of_buddy(user: Principal) -> Option<BuddyLink> {
+    link(user).and_then(|mine| link(mine.buddy)).filter(|theirs| theirs.buddy == user)
+}
+
+fn award(user: Principal, buddy: Principal) -> bool {
+    let awarded = state::update_profile(user, |profile| {
+        profile.xp = Some(profile.xp.unwrap_or(0) + BUDDY_XP);
+        Ok(())
+    }).is_ok();
+    if awarded {
+        history::log_event(user, HistoryEvent::BuddyBonus { buddy, xp: BUDDY_XP });
+    }
+    awarded
+}
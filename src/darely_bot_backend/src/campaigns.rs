@@ -0,0 +1,266 @@
+use crate::outbox;
+use crate::repository::{self, UserRepo};
+use crate::state;
+use crate::types::{
+    Campaign, CampaignInput, CampaignReport, CampaignScan, Enrollment, StorablePrincipal,
+};
+use candid::Principal;
+use std::collections::BTreeMap;
+
+// --- Re-engagement Campaigns ---
+// An admin defines a message template and an audience (users whose last submission is N+ days
+// old). The campaign timer enrolls matching users once and queues the staged messages into the
+// outbox as each stage comes due; the OpenChat bot drains the outbox and delivers them as DMs.
+// Stages stop once the user submits again. A user asking for a dare after a message counts as a
+// response, submitting one as a reactivation.
+//
+// The timer works through the profiles a batch per tick, with its cursor in stable memory so a
+// pass survives upgrades, and starts a new pass at most hourly. Inactivity is measured from the
+// profile's last_completed_at, so a pass reads nothing but the profiles and their enrollments.
+
+const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+const MAX_STAGES: usize = 5;
+const MAX_STAGE_DAY: u32 = 30;
+const MAX_TEMPLATE_LEN: usize = 500;
+const MAX_NAME_LEN: usize = 60;
+const BATCH_SIZE: usize = 1_000;
+const PASS_INTERVAL_NANOS: u64 = 60 * 60 * 1_000_000_000;
+
+pub fn validate(input: &CampaignInput) -> Result<(), String> {
+    if input.name.trim().is_empty() || input.name.len() > MAX_NAME_LEN {
+        return Err(format!("Campaign name must be 1-{} characters.", MAX_NAME_LEN));
+    }
+    if input.template.trim().is_empty() || input.template.len() > MAX_TEMPLATE_LEN {
+        return Err(format!("Message template must be 1-{} characters.", MAX_TEMPLATE_LEN));
+    }
+    if input.audience.lapsed_days == 0 {
+        return Err("lapsed_days must be at least 1.".to_string());
+    }
+    if input.stage_days.is_empty() || input.stage_days.len() > MAX_STAGES {
+        return Err(format!("A campaign needs 1-{} stages.", MAX_STAGES));
+    }
+    if input.stage_days.windows(2).any(|w| w[0] >= w[1]) || input.stage_days.iter().any(|&d| d > MAX_STAGE_DAY) {
+        return Err(format!("stage_days must be strictly ascending and at most {}.", MAX_STAGE_DAY));
+    }
+    Ok(())
+}
+
+pub fn create(input: CampaignInput) -> Result<u64, String> {
+    validate(&input)?;
+    state::CAMPAIGNS.with(|campaigns_ref| {
+        let mut campaigns = campaigns_ref.borrow_mut();
+        let id = campaigns.last_key_value().map_or(0, |(id, _)| id + 1);
+        campaigns.insert(id, Campaign {
+            id,
+            name: input.name.trim().to_string(),
+            template: input.template,
+            audience: input.audience,
+            stage_days: input.stage_days,
+            created_at: ic_cdk::api::time(),
+            active: true,
+            enrolled: 0,
+            messages_sent: 0,
+            responded: 0,
+            reactivated: 0,
+        });
+        Ok(id)
+    })
+}
+
+fn update_campaign<F: FnOnce(&mut Campaign)>(campaign_id: u64, f: F) -> bool {
+    state::CAMPAIGNS.with(|campaigns_ref| {
+        let mut campaigns = campaigns_ref.borrow_mut();
+        match campaigns.get(&campaign_id) {
+            Some(mut campaign) => {
+                f(&mut campaign);
+                campaigns.insert(campaign_id, campaign);
+                true
+            }
+            None => false,
+        }
+    })
+}
+
+pub fn set_active(campaign_id: u64, active: bool) -> Result<(), String> {
+    if update_campaign(campaign_id, |campaign| campaign.active = active) {
+        Ok(())
+    } else {
+        Err(format!("Campaign {} not found.", campaign_id))
+    }
+}
+
+fn percent(part: u64, whole: u64) -> u32 {
+    (part * 100).checked_div(whole).unwrap_or(0) as u32
+}
+
+pub fn reports() -> Vec<CampaignReport> {
+    // Rates are relative to users who actually received a message
+    let mut messaged: BTreeMap<u64, u64> = BTreeMap::new();
+    state::ENROLLMENTS.with(|e| {
+        for (_, enrollments) in e.borrow().iter() {
+            for entry in enrollments.entries.iter().filter(|entry| entry.stages_sent > 0) {
+                *messaged.entry(entry.campaign_id).or_default() += 1;
+            }
+        }
+    });
+    state::CAMPAIGNS.with(|c| {
+        c.borrow().iter().map(|(id, campaign)| {
+            let reached = messaged.get(&id).copied().unwrap_or(0);
+            CampaignReport {
+                response_rate_percent: percent(campaign.responded, reached),
+                reactivation_rate_percent: percent(campaign.reactivated, reached),
+                campaign,
+            }
+        }).collect()
+    })
+}
+
+// --- Activity Tracking ---
+
+pub enum Activity {
+    Responded, // Asked for a dare
+    Reactivated, // Submitted a dare
+}
+
+// Records the user's activity against every campaign that has messaged them
+pub fn note_activity(user: Principal, activity: Activity) {
+    let key = StorablePrincipal(user);
+    let Some(mut enrollments) = state::ENROLLMENTS.with(|e| e.borrow().get(&key)) else { return };
+    let now = ic_cdk::api::time();
+    let mut changed = false;
+    for entry in enrollments.entries.iter_mut().filter(|entry| entry.stages_sent > 0) {
+        if entry.responded_at.is_none() {
+            entry.responded_at = Some(now);
+            update_campaign(entry.campaign_id, |campaign| campaign.responded += 1);
+            changed = true;
+        }
+        if matches!(activity, Activity::Reactivated) && entry.reactivated_at.is_none() {
+            entry.reactivated_at = Some(now);
+            update_campaign(entry.campaign_id, |campaign| campaign.reactivated += 1);
+            changed = true;
+        }
+    }
+    if changed {
+        state::ENROLLMENTS.with(|e| e.borrow_mut().insert(key, enrollments));
+    }
+}
+
+// --- Timer ---
+
+fn render(template: &str, streak: u32, days_inactive: u64, stage: usize) -> String {
+    template
+        .replace("{streak}", &streak.to_string())
+        .replace("{days_inactive}", &days_inactive.to_string())
+        .replace("{stage}", &(stage + 1).to_string())
+}
+
+// The pass so far, and whether a new one may start: at most one per PASS_INTERVAL_NANOS
+fn pass_state(now: u64) -> Option<CampaignScan> {
+    let mut scan = state::CAMPAIGN_SCAN.with(|c| c.borrow().get().clone());
+    if scan.cursor.is_none() {
+        if scan.started_at > 0 && now.saturating_sub(scan.started_at) < PASS_INTERVAL_NANOS {
+            return None;
+        }
+        scan.started_at = now;
+    }
+    Some(scan)
+}
+
+// Enrolls newly lapsed users and queues every message stage that has come due, for the next
+// BATCH_SIZE profiles of the current pass. Returns the number of messages queued.
+pub fn run() -> usize {
+    let campaigns: Vec<Campaign> = state::CAMPAIGNS.with(|c| {
+        c.borrow().iter().map(|(_, campaign)| campaign).filter(|campaign| campaign.active).collect()
+    });
+    if campaigns.is_empty() {
+        return 0;
+    }
+    let now = ic_cdk::api::time();
+    let Some(mut scan) = pass_state(now) else { return 0 };
+    // Users who have never submitted have no last_completed_at and can't lapse
+    let mut profiles: Vec<(Principal, u32, Option<u64>)> = Vec::with_capacity(BATCH_SIZE);
+    repository::users().scan(scan.cursor, &mut |user, profile| {
+        profiles.push((user, profile.streak, profile.last_completed_at));
+        profiles.len() < BATCH_SIZE
+    });
+    scan.cursor = profiles.last().map(|&(user, _, _)| user).filter(|_| profiles.len() == BATCH_SIZE);
+    state::CAMPAIGN_SCAN.with(|c| c.borrow_mut().set(scan).expect("Failed to write campaign scan"));
+
+    let mut queued = 0;
+    for (user, streak, last) in profiles {
+        let Some(last) = last else { continue };
+        let days_inactive = now.saturating_sub(last) / DAY_NANOS;
+        let key = StorablePrincipal(user);
+        let mut enrollments = state::ENROLLMENTS.with(|e| e.borrow().get(&key)).unwrap_or_default();
+        let mut changed = false;
+
+        for campaign in &campaigns {
+            let position = enrollments.entries.iter().position(|entry| entry.campaign_id == campaign.id);
+            let index = match position {
+                Some(index) => index,
+                None => {
+                    let eligible = days_inactive >= campaign.audience.lapsed_days as u64
+                        && campaign.audience.min_streak.is_none_or(|min| streak >= min);
+                    if !eligible {
+                        continue;
+                    }
+                    enrollments.entries.push(Enrollment {
+                        campaign_id: campaign.id,
+                        enrolled_at: now,
+                        stages_sent: 0,
+                        last_sent_at: None,
+                        responded_at: None,
+                        reactivated_at: None,
+                    });
+                    update_campaign(campaign.id, |c| c.enrolled += 1);
+                    changed = true;
+                    enrollments.entries.len() - 1
+                }
+            };
+            let entry = &mut enrollments.entries[index];
+            // The user came back since enrolling; no further nudges
+            if entry.reactivated_at.is_some() || last > entry.enrolled_at {
+                continue;
+            }
+            let stage = entry.stages_sent as usize;
+            let Some(&due_day) = campaign.stage_days.get(stage) else { continue };
+            if now.saturating_sub(entry.enrolled_at) < due_day as u64 * DAY_NANOS {
+                continue;
+            }
+            outbox::queue(user, render(&campaign.template, streak, days_inactive, stage), Some(campaign.id));
+            entry.stages_sent += 1;
+            entry.last_sent_at = Some(now);
+            update_campaign(campaign.id, |c| c.messages_sent += 1);
+            changed = true;
+            queued += 1;
+        }
+        if changed {
+            state::ENROLLMENTS.with(|e| e.borrow_mut().insert(key, enrollments));
+        }
+    }
+    queued
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR_NANOS: u64 = 60 * 60 * 1_000_000_000;
+
+    fn save(scan: CampaignScan) {
+        state::CAMPAIGN_SCAN.with(|c| c.borrow_mut().set(scan).expect("Failed to write campaign scan"));
+    }
+
+    #[test]
+    fn passes_resume_at_once_and_restart_hourly() {
+        assert_eq!(pass_state(5).map(|scan| scan.started_at), Some(5));
+
+        save(CampaignScan { cursor: Some(Principal::from_slice(&[1])), started_at: 10 * HOUR_NANOS });
+        let resumed = pass_state(10 * HOUR_NANOS + 1).expect("a pass in progress continues");
+        assert_eq!((resumed.cursor, resumed.started_at), (Some(Principal::from_slice(&[1])), 10 * HOUR_NANOS));
+
+        save(CampaignScan { cursor: None, started_at: 10 * HOUR_NANOS });
+        assert!(pass_state(11 * HOUR_NANOS - 1).is_none());
+        assert_eq!(pass_state(11 * HOUR_NANOS).map(|scan| scan.started_at), Some(11 * HOUR_NANOS));
+    }
+}
@@ -0,0 +1,118 @@
+use crate::certification;
+use crate::identity::OID_CANISTER_SIG;
+use crate::repository::{self, DareRepo};
+use crate::state;
+use crate::types::{CompletionCertificate, Submission};
+use candid::Principal;
+use serde_bytes::ByteBuf;
+use serde_json::json;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+// --- Completion Certificates ---
+// Shareable proof that a user completed a dare. An update call prepares a JSON payload and adds a
+// canister signature over it to the certified tree; a follow-up query returns the payload with the
+// signature (IC certificate + witness) and the canister signature public key. Anyone can verify it
+// off-chain against the IC root key, like an Internet Identity delegation. All certificates share
+// one public key, so verifiers can pin it. Prepared certificates live on the heap for a few
+// minutes (and not across upgrades); the signed result stays valid indefinitely.
+
+const SEED: &[u8] = b"darely-completion-certificates";
+const DOMAIN: &[u8] = b"darely-completion-certificate";
+const PREPARED_TTL_NANOS: u64 = 10 * 60 * 1_000_000_000;
+
+struct Prepared {
+    payload: String,
+    expires_at: u64,
+}
+
+thread_local! {
+    static PREPARED: RefCell<BTreeMap<(Principal, u64), Prepared>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+// The signed message: length-prefixed domain separator followed by the payload
+fn message(payload: &str) -> Vec<u8> {
+    let mut message = vec![DOMAIN.len() as u8];
+    message.extend_from_slice(DOMAIN);
+    message.extend_from_slice(payload.as_bytes());
+    message
+}
+
+// DER SubjectPublicKeyInfo of this canister's signature key for SEED
+pub fn public_key() -> Vec<u8> {
+    let canister_id = ic_cdk::id();
+    let mut key = vec![canister_id.as_slice().len() as u8];
+    key.extend_from_slice(canister_id.as_slice());
+    key.extend_from_slice(SEED);
+
+    let mut algorithm = vec![0x06, OID_CANISTER_SIG.len() as u8];
+    algorithm.extend_from_slice(OID_CANISTER_SIG);
+    let mut bit_string = vec![0x03, key.len() as u8 + 1, 0x00];
+    bit_string.extend_from_slice(&key);
+
+    let mut der = vec![0x30, (algorithm.len() + 2 + bit_string.len()) as u8, 0x30, algorithm.len() as u8];
+    der.extend_from_slice(&algorithm);
+    der.extend_from_slice(&bit_string);
+    der
+}
+
+// The user's most recent submission for the dare that a review approved
+fn completion(user: Principal, dare_id: u64) -> Option<Submission> {
+    state::SUBMISSIONS.with(|s| {
+        s.borrow().iter()
+            .rev()
+            .map(|(_, submission)| submission)
+            .find(|submission| submission.user == user && submission.dare_id == Some(dare_id) && approved(submission))
+    })
+}
+
+fn approved(submission: &Submission) -> bool {
+    submission.review.as_ref().is_some_and(|review| review.approved)
+}
+
+// Builds and signs a certificate for an approved completion. Returns when the prepared
+// certificate expires.
+pub fn prepare(user: Principal, dare_id: u64) -> Result<u64, String> {
+    let submission = completion(user, dare_id)
+        .ok_or_else(|| format!("You have no approved completion of dare #{}. Certificates are issued once a submission passes review.", dare_id))?;
+    let dare = repository::dares().get(dare_id)
+        .ok_or_else(|| format!("Dare #{} not found.", dare_id))?;
+    let now = ic_cdk::api::time();
+    let payload = json!({
+        "type": "darely/completion",
+        "version": 1,
+        "canister": ic_cdk::id().to_text(),
+        "user": user.to_text(),
+        "dare_id": dare_id,
+        "dare_text": dare.text,
+        "submission_id": submission.id,
+        "completed_at": submission.submitted_at,
+        "status": "approved",
+        "issued_at": now,
+    }).to_string();
+
+    let expires_at = now + PREPARED_TTL_NANOS;
+    certification::add_signature(SEED, &message(&payload), expires_at);
+    PREPARED.with(|prepared| {
+        let mut prepared = prepared.borrow_mut();
+        prepared.retain(|_, entry| entry.expires_at > now);
+        prepared.insert((user, dare_id), Prepared { payload, expires_at });
+    });
+    Ok(expires_at)
+}
+
+// Returns a certificate prepared earlier (must be called as a query to get a data certificate)
+pub fn get(user: Principal, dare_id: u64) -> Result<CompletionCertificate, String> {
+    let payload = PREPARED.with(|prepared| {
+        prepared.borrow().get(&(user, dare_id))
+            .filter(|entry| entry.expires_at > ic_cdk::api::time())
+            .map(|entry| entry.payload.clone())
+    }).ok_or_else(|| format!("No certificate prepared for dare #{}. Call prepare_completion_certificate first.", dare_id))?;
+    let signature = certification::canister_signature(SEED, &message(&payload))
+        .ok_or_else(|| "The certificate signature is not available; prepare it again.".to_string())?;
+    Ok(CompletionCertificate {
+        payload,
+        signature: ByteBuf::from(signature),
+        public_key: ByteBuf::from(public_key()),
+    })
+}
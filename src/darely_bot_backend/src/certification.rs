@@ -0,0 +1,314 @@
+use crate::encoding::{self, ContentEncoding};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+// --- Certified Response Cache ---
+// Parameterless GET routes are rendered periodically (in update context), their bodies hashed into
+// an `http_assets` hash tree, and the root set as the canister's certified data. Queries then serve
+// the cached bodies with an IC-Certificate header so the HTTP gateway can verify them.
+// The gateway decodes gzip/deflate bodies before hashing, so certifying the identity body also
+// certifies its compressed variants, which are precomputed here as well.
+// The certified tree also carries a `sig` subtree for canister signatures (see certificates.rs)
+// and the ICRC-3 `last_block_index` / `last_block_hash` of the points block log (see icrc3.rs).
+
+type Hash = [u8; 32];
+
+pub struct CachedResponse {
+    pub hash: Hash, // SHA-256 of the identity body
+    pub body: Vec<u8>,
+    pub gzip: Option<Vec<u8>>,
+    pub deflate: Option<Vec<u8>>,
+}
+
+impl CachedResponse {
+    fn new(body: Vec<u8>) -> Self {
+        let compressible = body.len() >= encoding::MIN_COMPRESSIBLE_BYTES;
+        CachedResponse {
+            hash: Sha256::digest(&body).into(),
+            gzip: compressible.then(|| encoding::compress(&body, ContentEncoding::Gzip)),
+            deflate: compressible.then(|| encoding::compress(&body, ContentEncoding::Deflate)),
+            body,
+        }
+    }
+
+    // Returns the body variant for the negotiated encoding (None = identity)
+    pub fn variant(&self, encoding: Option<ContentEncoding>) -> (Option<ContentEncoding>, &[u8]) {
+        match encoding {
+            Some(ContentEncoding::Gzip) if self.gzip.is_some() => (encoding, self.gzip.as_deref().unwrap()),
+            Some(ContentEncoding::Deflate) if self.deflate.is_some() => (encoding, self.deflate.as_deref().unwrap()),
+            _ => (None, &self.body),
+        }
+    }
+}
+
+thread_local! {
+    // Heap-only: rebuilt on init/post_upgrade and by the refresh timer
+    static RESPONSES: RefCell<BTreeMap<String, CachedResponse>> = const { RefCell::new(BTreeMap::new()) };
+    // Canister signatures: (seed hash, message hash) -> expiry (nanos). Heap-only, like the responses.
+    static SIGNATURES: RefCell<BTreeMap<(Hash, Hash), u64>> = const { RefCell::new(BTreeMap::new()) };
+    // ICRC-3 tip of the points block log: (last block index, last block hash)
+    static LEDGER_TIP: RefCell<Option<(u64, Hash)>> = const { RefCell::new(None) };
+}
+
+// --- Hash Tree (IC interface spec, "Certificate" section) ---
+
+#[derive(Clone)]
+enum HashTree {
+    Empty,
+    Fork(Box<HashTree>, Box<HashTree>),
+    Labeled(Vec<u8>, Box<HashTree>),
+    Leaf(Vec<u8>),
+    Pruned(Hash),
+}
+
+fn domain_hasher(separator: &str) -> Sha256 {
+    let mut hasher = Sha256::new();
+    hasher.update([separator.len() as u8]);
+    hasher.update(separator.as_bytes());
+    hasher
+}
+
+impl HashTree {
+    fn digest(&self) -> Hash {
+        match self {
+            HashTree::Empty => domain_hasher("ic-hashtree-empty").finalize().into(),
+            HashTree::Fork(left, right) => {
+                let mut hasher = domain_hasher("ic-hashtree-fork");
+                hasher.update(left.digest());
+                hasher.update(right.digest());
+                hasher.finalize().into()
+            }
+            HashTree::Labeled(label, subtree) => {
+                let mut hasher = domain_hasher("ic-hashtree-labeled");
+                hasher.update(label);
+                hasher.update(subtree.digest());
+                hasher.finalize().into()
+            }
+            HashTree::Leaf(value) => {
+                let mut hasher = domain_hasher("ic-hashtree-leaf");
+                hasher.update(value);
+                hasher.finalize().into()
+            }
+            HashTree::Pruned(hash) => *hash,
+        }
+    }
+
+    // CBOR encoding: [0] | [1, l, r] | [2, label, t] | [3, value] | [4, hash]
+    fn write_cbor(&self, out: &mut Vec<u8>) {
+        match self {
+            HashTree::Empty => {
+                out.push(0x81);
+                out.push(0);
+            }
+            HashTree::Fork(left, right) => {
+                out.push(0x83);
+                out.push(1);
+                left.write_cbor(out);
+                right.write_cbor(out);
+            }
+            HashTree::Labeled(label, subtree) => {
+                out.push(0x83);
+                out.push(2);
+                write_cbor_bytes(label, out);
+                subtree.write_cbor(out);
+            }
+            HashTree::Leaf(value) => {
+                out.push(0x82);
+                out.push(3);
+                write_cbor_bytes(value, out);
+            }
+            HashTree::Pruned(hash) => {
+                out.push(0x82);
+                out.push(4);
+                write_cbor_bytes(hash, out);
+            }
+        }
+    }
+}
+
+fn write_cbor_bytes(bytes: &[u8], out: &mut Vec<u8>) {
+    let len = bytes.len();
+    if len < 24 {
+        out.push(0x40 | len as u8);
+    } else if len < 0x100 {
+        out.push(0x58);
+        out.push(len as u8);
+    } else if len < 0x10000 {
+        out.push(0x59);
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        out.push(0x5a);
+        out.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+    out.extend_from_slice(bytes);
+}
+
+// Balanced tree over sorted (label, subtree) entries
+fn labeled_tree(entries: Vec<(Vec<u8>, HashTree)>) -> HashTree {
+    fn build(mut entries: Vec<(Vec<u8>, HashTree)>) -> HashTree {
+        match entries.len() {
+            0 => HashTree::Empty,
+            1 => {
+                let (label, subtree) = entries.pop().unwrap();
+                HashTree::Labeled(label, Box::new(subtree))
+            }
+            len => {
+                let right = entries.split_off(len / 2);
+                HashTree::Fork(Box::new(build(entries)), Box::new(build(right)))
+            }
+        }
+    }
+    build(entries)
+}
+
+// Keeps the subtrees at `paths` and prunes everything off those paths, which keeps the root hash
+// unchanged
+fn witness(tree: &HashTree, paths: &[&[&[u8]]]) -> HashTree {
+    if paths.is_empty() {
+        return HashTree::Pruned(tree.digest());
+    }
+    if paths.iter().any(|path| path.is_empty()) {
+        return tree.clone();
+    }
+    match tree {
+        HashTree::Fork(left, right) => match (witness(left, paths), witness(right, paths)) {
+            (HashTree::Pruned(_), HashTree::Pruned(_)) => HashTree::Pruned(tree.digest()),
+            (left, right) => HashTree::Fork(Box::new(left), Box::new(right)),
+        },
+        HashTree::Labeled(label, subtree) => {
+            let rests: Vec<&[&[u8]]> = paths.iter().filter(|path| path[0] == label.as_slice()).map(|path| &path[1..]).collect();
+            if rests.is_empty() {
+                HashTree::Pruned(tree.digest())
+            } else {
+                HashTree::Labeled(label.clone(), Box::new(witness(subtree, &rests)))
+            }
+        }
+        HashTree::Empty => HashTree::Empty,
+        _ => HashTree::Pruned(tree.digest()),
+    }
+}
+
+fn asset_tree() -> HashTree {
+    RESPONSES.with(|responses| {
+        labeled_tree(responses.borrow().iter()
+            .map(|(path, response)| (path.as_bytes().to_vec(), HashTree::Leaf(response.hash.to_vec())))
+            .collect())
+    })
+}
+
+// sig / <seed hash> / <message hash> -> empty leaf
+fn signature_tree() -> HashTree {
+    let mut by_seed: BTreeMap<Hash, Vec<(Vec<u8>, HashTree)>> = BTreeMap::new();
+    SIGNATURES.with(|signatures| {
+        for (seed_hash, message_hash) in signatures.borrow().keys() {
+            by_seed.entry(*seed_hash).or_default().push((message_hash.to_vec(), HashTree::Leaf(Vec::new())));
+        }
+    });
+    labeled_tree(by_seed.into_iter().map(|(seed_hash, messages)| (seed_hash.to_vec(), labeled_tree(messages))).collect())
+}
+
+// The full certified tree; the ledger tip and `sig` branches are only present when set
+fn certified_tree() -> HashTree {
+    // Top-level labels in order: "http_assets" < "last_block_hash" < "last_block_index" < "sig"
+    let mut branches = vec![(b"http_assets".to_vec(), asset_tree())];
+    if let Some((index, hash)) = LEDGER_TIP.with(|tip| *tip.borrow()) {
+        branches.push((b"last_block_hash".to_vec(), HashTree::Leaf(hash.to_vec())));
+        branches.push((b"last_block_index".to_vec(), HashTree::Leaf(leb128(index))));
+    }
+    if !SIGNATURES.with(|signatures| signatures.borrow().is_empty()) {
+        branches.push((b"sig".to_vec(), signature_tree()));
+    }
+    labeled_tree(branches)
+}
+
+fn leb128(mut value: u64) -> Vec<u8> {
+    let mut out = Vec::new();
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return out;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn update_certified_data() {
+    ic_cdk::api::set_certified_data(&certified_tree().digest());
+}
+
+// --- Public API ---
+
+// Replaces the cached bodies and updates the certified data. Must run in update/init context.
+pub fn certify(bodies: Vec<(String, Vec<u8>)>) {
+    RESPONSES.with(|responses| {
+        let mut responses = responses.borrow_mut();
+        responses.clear();
+        for (path, body) in bodies {
+            responses.insert(path, CachedResponse::new(body));
+        }
+    });
+    update_certified_data();
+}
+
+// Looks up a cached response and applies `f` to it (avoids cloning bodies)
+pub fn with_cached<R>(path: &str, f: impl FnOnce(&CachedResponse) -> R) -> Option<R> {
+    RESPONSES.with(|responses| responses.borrow().get(path).map(f))
+}
+
+// IC-Certificate header value for a cached path, when a certificate is available (queries only)
+pub fn certificate_header(path: &str) -> Option<String> {
+    let certificate = ic_cdk::api::data_certificate()?;
+    let mut tree_cbor = vec![0xd9, 0xd9, 0xf7]; // CBOR self-describe tag
+    witness(&certified_tree(), &[&[b"http_assets", path.as_bytes()]]).write_cbor(&mut tree_cbor);
+    Some(format!("certificate=:{}:, tree=:{}:", BASE64.encode(certificate), BASE64.encode(tree_cbor)))
+}
+
+// Adds a canister signature over `message` (dropping expired ones) and updates the certified
+// data. The signature can be fetched with `canister_signature` in a later query call.
+pub fn add_signature(seed: &[u8], message: &[u8], expires_at: u64) {
+    let now = ic_cdk::api::time();
+    SIGNATURES.with(|signatures| {
+        let mut signatures = signatures.borrow_mut();
+        signatures.retain(|_, expiry| *expiry > now);
+        signatures.insert((Sha256::digest(seed).into(), Sha256::digest(message).into()), expires_at);
+    });
+    update_certified_data();
+}
+
+// CBOR canister signature {certificate, tree} for a message added with `add_signature` (queries only)
+pub fn canister_signature(seed: &[u8], message: &[u8]) -> Option<Vec<u8>> {
+    let seed_hash: Hash = Sha256::digest(seed).into();
+    let message_hash: Hash = Sha256::digest(message).into();
+    if !SIGNATURES.with(|signatures| signatures.borrow().contains_key(&(seed_hash, message_hash))) {
+        return None;
+    }
+    let certificate = ic_cdk::api::data_certificate()?;
+    let mut out = vec![0xd9, 0xd9, 0xf7, 0xa2]; // Self-describe tag, map with 2 entries
+    out.push(0x60 | 11);
+    out.extend_from_slice(b"certificate");
+    write_cbor_bytes(&certificate, &mut out);
+    out.push(0x60 | 4);
+    out.extend_from_slice(b"tree");
+    witness(&certified_tree(), &[&[b"sig", &seed_hash, &message_hash]]).write_cbor(&mut out);
+    Some(out)
+}
+
+// Certifies the latest block of the points log (update/init context)
+pub fn set_ledger_tip(index: u64, hash: Hash) {
+    LEDGER_TIP.with(|tip| *tip.borrow_mut() = Some((index, hash)));
+    update_certified_data();
+}
+
+// ICRC-3 tip certificate: the IC certificate and a CBOR witness for the tip labels (queries only)
+pub fn ledger_tip_certificate() -> Option<(Vec<u8>, Vec<u8>)> {
+    LEDGER_TIP.with(|tip| tip.borrow().is_some()).then_some(())?;
+    let certificate = ic_cdk::api::data_certificate()?;
+    let mut tree_cbor = vec![0xd9, 0xd9, 0xf7];
+    witness(&certified_tree(), &[&[b"last_block_hash"], &[b"last_block_index"]]).write_cbor(&mut tree_cbor);
+    Some((certificate, tree_cbor))
+}
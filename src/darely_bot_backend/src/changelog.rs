@@ -0,0 +1,67 @@
+use crate::outbox;
+use crate::state;
+use crate::themes;
+use crate::types::ChangelogEntry;
+use candid::Principal;
+
+// --- Changelog ---
+// Admins record what a release changed with add_changelog before upgrading. post_upgrade posts
+// every entry not announced yet as "What's new" to the chats subscribed to announcements, once,
+// and /changelog shows the latest entries to anyone.
+
+const MAX_VERSION_LEN: usize = 32;
+const MAX_NOTES_LEN: usize = 1_000;
+pub const DEFAULT_LIMIT: usize = 3;
+pub const MAX_LIMIT: usize = 20;
+
+pub fn add(version: String, notes: String, by: Principal) -> Result<u64, String> {
+    let (version, notes) = (version.trim().to_string(), notes.trim().to_string());
+    if version.is_empty() || version.chars().count() > MAX_VERSION_LEN {
+        return Err(format!("The version must be 1 to {} characters.", MAX_VERSION_LEN));
+    }
+    if notes.is_empty() || notes.chars().count() > MAX_NOTES_LEN {
+        return Err(format!("The notes must be 1 to {} characters.", MAX_NOTES_LEN));
+    }
+    state::CHANGELOG.with(|c| {
+        let mut changelog = c.borrow_mut();
+        if changelog.values().any(|entry| entry.version == version) {
+            return Err(format!("There already is a changelog entry for {}.", version));
+        }
+        let id = changelog.last_key_value().map_or(0, |(last, _)| last + 1);
+        changelog.insert(id, ChangelogEntry { id, version, notes, added_at: ic_cdk::api::time(), added_by: by, announced_at: None });
+        Ok(id)
+    })
+}
+
+// Newest first
+pub fn recent(limit: usize) -> Vec<ChangelogEntry> {
+    state::CHANGELOG.with(|c| c.borrow().iter().rev().take(limit).map(|(_, entry)| entry).collect())
+}
+
+pub fn format(entries: &[ChangelogEntry]) -> String {
+    if entries.is_empty() {
+        return "No changes have been announced yet.".to_string();
+    }
+    entries.iter().map(|entry| format!("🆕 {}\n{}", entry.version, entry.notes)).collect::<Vec<_>>().join("\n\n")
+}
+
+// Called from post_upgrade: queues unannounced entries to the subscribed chats. Returns how many.
+pub fn announce_pending() -> usize {
+    let pending: Vec<ChangelogEntry> = state::CHANGELOG.with(|c| c.borrow().values().filter(|entry| entry.announced_at.is_none()).collect());
+    if pending.is_empty() {
+        return 0;
+    }
+    let text = format!("✨ What's new in Darely\n\n{}", format(&pending));
+    for chat_id in themes::subscribed_chats() {
+        outbox::queue_chat(&chat_id, text.clone());
+    }
+    let now = ic_cdk::api::time();
+    state::CHANGELOG.with(|c| {
+        let mut changelog = c.borrow_mut();
+        for mut entry in pending.iter().cloned() {
+            entry.announced_at = Some(now);
+            changelog.insert(entry.id, entry);
+        }
+    });
+    pending.len()
+}
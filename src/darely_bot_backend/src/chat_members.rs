@@ -0,0 +1,33 @@
+use crate::state;
+use crate::types::{CompositeKey, StorablePrincipal};
+use candid::Principal;
+
+// --- Chat Members ---
+// Which users have played in which chats, recorded whenever get_dare is called with a chat id.
+// The first per-chat per-user index; chat-scoped features (leaderboards, settings) can build on
+// the same (chat id, user) key.
+
+pub fn record(chat_id: &str, user: Principal) {
+    let key = CompositeKey(chat_id.to_string(), StorablePrincipal(user));
+    state::CHAT_MEMBERS.with(|m| m.borrow_mut().insert(key, ic_cdk::api::time()));
+}
+
+pub fn is_member(chat_id: &str, user: Principal) -> bool {
+    let key = CompositeKey(chat_id.to_string(), StorablePrincipal(user));
+    state::CHAT_MEMBERS.with(|m| m.borrow().contains_key(&key))
+}
+
+// Users seen in the chat with their last activity, ordered by principal
+pub fn members(chat_id: &str) -> Vec<(Principal, u64)> {
+    state::CHAT_MEMBERS.with(|m| {
+        state::range_by_first(&m.borrow(), &chat_id.to_string())
+            .into_iter()
+            .map(|(user, last_active)| (user.0, last_active))
+            .collect()
+    })
+}
+
+// Chats the user has played in with their last activity there
+pub fn chats_of(user: Principal) -> Vec<(String, u64)> {
+    state::CHAT_MEMBERS.with(|m| state::filter_by_second(&m.borrow(), &StorablePrincipal(user)))
+}
@@ -0,0 +1,134 @@
+// --- Chat Scopes ---
+// The OpenChat bot passes the chat a command came from as a string built from the command's
+// scope:
+//   "<group id>"                           a group chat
+//   "<community id>/<channel id>"          a channel in a community
+//   "community:<community id>"             a community-level command outside any channel
+//   "direct" or "direct:<user>"            the user's direct chat with the bot
+//   "<any of the above>#<root message>"    a thread under a message in that chat
+// Per-chat features (chat configs, galleries, members, disabled dares, group metadata) are keyed
+// by `chat_key`: a thread shares its parent chat's key, while direct chats, communities and
+// scopes this canister doesn't know yet have none, so the global defaults apply there instead of
+// the command failing.
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChatScope {
+    Direct,
+    Group { group: String },
+    Channel { community: String, channel: String },
+    Community { community: String },
+    Thread { parent: Box<ChatScope>, root_message_index: u32 },
+    Unknown { raw: String }, // A scope added to OpenChat after this canister was written
+}
+
+const DIRECT_PREFIX: &str = "direct";
+const COMMUNITY_PREFIX: &str = "community:";
+
+pub fn parse(chat_id: &str) -> ChatScope {
+    let chat_id = chat_id.trim();
+    if let Some((parent, root)) = chat_id.rsplit_once('#') {
+        return match (parse(parent), root.parse::<u32>()) {
+            (ChatScope::Thread { .. } | ChatScope::Unknown { .. }, _) | (_, Err(_)) => unknown(chat_id),
+            (parent, Ok(root_message_index)) => ChatScope::Thread { parent: Box::new(parent), root_message_index },
+        };
+    }
+    if chat_id == DIRECT_PREFIX || chat_id.starts_with("direct:") {
+        return ChatScope::Direct;
+    }
+    if let Some(community) = chat_id.strip_prefix(COMMUNITY_PREFIX) {
+        return if valid_id(community) { ChatScope::Community { community: community.to_string() } } else { unknown(chat_id) };
+    }
+    if let Some((community, channel)) = chat_id.split_once('/') {
+        return if valid_id(community) && !channel.is_empty() && channel.bytes().all(|b| b.is_ascii_digit()) {
+            ChatScope::Channel { community: community.to_string(), channel: channel.to_string() }
+        } else {
+            unknown(chat_id)
+        };
+    }
+    if valid_id(chat_id) { ChatScope::Group { group: chat_id.to_string() } } else { unknown(chat_id) }
+}
+
+fn unknown(chat_id: &str) -> ChatScope {
+    ChatScope::Unknown { raw: chat_id.to_string() }
+}
+
+// Canister ids: lowercase letters, digits and dashes
+fn valid_id(id: &str) -> bool {
+    !id.is_empty() && id.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+}
+
+impl ChatScope {
+    // The key per-chat state is stored under; None = no per-chat state (global defaults apply)
+    pub fn chat_key(&self) -> Option<String> {
+        match self {
+            ChatScope::Group { group } => Some(group.clone()),
+            ChatScope::Channel { community, channel } => Some(format!("{}/{}", community, channel)),
+            ChatScope::Thread { parent, .. } => parent.chat_key(),
+            ChatScope::Direct | ChatScope::Community { .. } | ChatScope::Unknown { .. } => None,
+        }
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            ChatScope::Direct => "a direct chat",
+            ChatScope::Group { .. } => "a group chat",
+            ChatScope::Channel { .. } => "a channel",
+            ChatScope::Community { .. } => "a community",
+            ChatScope::Thread { .. } => "a thread",
+            ChatScope::Unknown { .. } => "an unrecognized chat",
+        }
+    }
+}
+
+// Resolves the chat a user command came from; anything without per-chat state becomes None
+pub fn chat_key(chat_id: Option<&str>) -> Option<String> {
+    chat_id.and_then(|chat_id| parse(chat_id).chat_key())
+}
+
+// For commands and admin settings that only make sense in a group chat or channel
+pub fn require_chat_key(chat_id: &str) -> Result<String, String> {
+    let scope = parse(chat_id);
+    scope.chat_key().ok_or_else(|| format!(
+        "'{}' is {}; this only works in a group chat or a community channel.", chat_id.trim(), scope.describe()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GROUP: &str = "rrkah-fqaaa-aaaaa-aaaaq-cai";
+
+    #[test]
+    fn parses_every_scope() {
+        assert_eq!(parse(GROUP), ChatScope::Group { group: GROUP.to_string() });
+        assert_eq!(parse(&format!("{}/42", GROUP)), ChatScope::Channel { community: GROUP.to_string(), channel: "42".to_string() });
+        assert_eq!(parse(&format!("community:{}", GROUP)), ChatScope::Community { community: GROUP.to_string() });
+        assert_eq!(parse("direct"), ChatScope::Direct);
+        assert_eq!(parse("direct:2vxsx-fae"), ChatScope::Direct);
+        assert_eq!(
+            parse(&format!("{}/42#7", GROUP)),
+            ChatScope::Thread {
+                parent: Box::new(ChatScope::Channel { community: GROUP.to_string(), channel: "42".to_string() }),
+                root_message_index: 7,
+            }
+        );
+    }
+
+    #[test]
+    fn threads_share_their_parent_chat_key() {
+        assert_eq!(chat_key(Some(&format!("{}#3", GROUP))), Some(GROUP.to_string()));
+        assert_eq!(chat_key(Some(&format!("{}/42#3", GROUP))), Some(format!("{}/42", GROUP)));
+        assert_eq!(chat_key(Some("direct#3")), None);
+    }
+
+    #[test]
+    fn unknown_scopes_degrade_to_no_chat() {
+        for raw in ["voice:abc", "", "Group With Spaces", "abc/def", "abc#x", "abc#1#2"] {
+            assert!(matches!(parse(raw), ChatScope::Unknown { .. }), "{}", raw);
+            assert_eq!(chat_key(Some(raw)), None);
+        }
+        assert!(require_chat_key("direct").unwrap_err().contains("a direct chat"));
+        assert_eq!(require_chat_key(GROUP), Ok(GROUP.to_string()));
+    }
+}
@@ -0,0 +1,92 @@
+use crate::history;
+use crate::points;
+use crate::state;
+use crate::timezone;
+use crate::types::{HistoryEvent, UserProfile};
+use candid::Principal;
+
+// --- Daily Check-in ---
+// /checkin pays a small random points bonus once per local day (see timezone.rs). Checking in on
+// consecutive days builds a check-in streak, separate from the dare streak, that adds
+// STREAK_BONUS points per day in a row up to MAX_STREAK_BONUS_DAYS; missing a day starts it over.
+// The random part comes from raw_rand so the payout can't be timed.
+
+const MIN_BASE: u64 = 1;
+const MAX_BASE: u64 = 5;
+const STREAK_BONUS: u64 = 2;
+const MAX_STREAK_BONUS_DAYS: u32 = 6;
+
+// Points for a check-in: MIN_BASE-MAX_BASE at random, plus the escalation for the streak
+fn bonus(roll: u8, checkin_streak: u32) -> u64 {
+    let base = MIN_BASE + roll as u64 % (MAX_BASE - MIN_BASE + 1);
+    base + STREAK_BONUS * checkin_streak.saturating_sub(1).min(MAX_STREAK_BONUS_DAYS) as u64
+}
+
+// The check-in streak after checking in on `today`
+fn next_streak(profile: &UserProfile, today: i64) -> Result<u32, String> {
+    match profile.last_checkin_day {
+        Some(day) if day >= today => Err("You already checked in today. Come back tomorrow!".to_string()),
+        Some(day) if day == today - 1 => Ok(profile.checkin_streak.unwrap_or(0) + 1),
+        _ => Ok(1),
+    }
+}
+
+// Shown by /stats
+pub fn describe(user: Principal, profile: &UserProfile) -> String {
+    let today = timezone::local_day_number(user, ic_cdk::api::time());
+    let streak = match profile.last_checkin_day {
+        Some(day) if day >= today - 1 => profile.checkin_streak.unwrap_or(0),
+        _ => 0,
+    };
+    let status = if profile.last_checkin_day == Some(today) { "done today" } else { "/checkin for today's bonus" };
+    format!("Check-in streak: {} ({})", streak, status)
+}
+
+pub async fn check_in(user: Principal) -> Result<String, String> {
+    let today = timezone::local_day_number(user, ic_cdk::api::time());
+    let profile = state::get_profile(user).ok_or_else(|| "User not found. Please /register first.".to_string())?;
+    next_streak(&profile, today)?;
+    let (random_bytes,) = ic_cdk::api::management_canister::main::raw_rand().await
+        .map_err(|(code, message)| format!("Failed to get randomness: {:?} {}", code, message))?;
+    let roll = random_bytes.first().copied().unwrap_or(0);
+
+    // Another check-in may have landed during the randomness call
+    let checkin_streak = state::update_profile(user, |profile| {
+        let checkin_streak = next_streak(profile, today)?;
+        profile.last_checkin_day = Some(today);
+        profile.checkin_streak = Some(checkin_streak);
+        Ok(checkin_streak)
+    })?;
+    let amount = bonus(roll, checkin_streak);
+    points::mint(user, amount, Some("daily check-in"))?;
+    history::log_event(user, HistoryEvent::CheckedIn { checkin_streak, points: amount });
+    Ok(match checkin_streak {
+        1 => format!("✅ Checked in: +{} points. Come back tomorrow for a bigger bonus!", amount),
+        days => format!("✅ Checked in {} days in a row: +{} points.", days, amount),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bonus_escalates_and_caps() {
+        assert_eq!(bonus(0, 1), 1);
+        assert_eq!(bonus(4, 1), 5);
+        assert_eq!(bonus(5, 1), 1);
+        assert_eq!(bonus(0, 2), 3);
+        assert_eq!(bonus(0, 7), 13);
+        assert_eq!(bonus(0, 30), 13);
+    }
+
+    #[test]
+    fn streak_continues_only_on_the_next_day() {
+        let mut profile = UserProfile { last_checkin_day: Some(100), checkin_streak: Some(3), ..Default::default() };
+        assert_eq!(next_streak(&profile, 101), Ok(4));
+        assert_eq!(next_streak(&profile, 102), Ok(1));
+        assert!(next_streak(&profile, 100).is_err());
+        profile.last_checkin_day = None;
+        assert_eq!(next_streak(&profile, 100), Ok(1));
+    }
+}
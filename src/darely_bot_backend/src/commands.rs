@@ -0,0 +1,355 @@
+use crate::chat_scope::{self, ChatScope};
+use crate::correlation;
+use crate::pauses;
+use crate::registration;
+use crate::state;
+use crate::types::{CommandDefinition, Difficulty, ScopePolicy, Visibility};
+use candid::Principal;
+use std::collections::BTreeSet;
+
+// --- Command Definitions ---
+// Bot commands with the chats each may run in and who sees their replies. The OpenChat bot
+// declares its commands from `get_command_definitions`, and the endpoints that receive a chat id
+// run it through `enforce`, which checks the policy and resolves the chat key (see
+// chat_scope.rs) in one place. A call without a chat id comes from the user's own identity
+// (website, dfx) and counts as a direct chat.
+//
+// Where OpenChat supports it, the bot posts successful replies with the command's visibility:
+// personal results (stats, rank, link codes) only to the sender, celebrations and shared views
+// (redemptions, leaderboards) to the whole chat. Errors always go to the sender only. Admins can
+// override the default per command.
+//
+// Shortcuts like /start are composed from other commands through `invoke`, which runs a command
+// with the same scope check it gets when sent on its own, instead of repeating its logic.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    Register,
+    Dare,
+    Redeem,
+    Gallery,
+    Link,
+    Leaderboard,
+    Rank,
+    Stats,
+    Start,
+    PauseBot,
+    ResumeBot,
+    Changelog,
+    DareStats,
+    RequestVouch,
+    Vouch,
+    Buddy,
+    Event,
+    Marketplace,
+    PublishDares,
+    ImportDares,
+    Hardcore,
+    Checkin,
+    Quest,
+    Escalate,
+    Duo,
+    Recap,
+    Done,
+    Accept,
+    Decline,
+    Setup,
+    Config,
+}
+
+impl Command {
+    pub const ALL: [Command; 31] = [
+        Command::Register, Command::Dare, Command::Redeem, Command::Gallery, Command::Link, Command::Leaderboard, Command::Rank, Command::Stats,
+        Command::Start, Command::PauseBot, Command::ResumeBot, Command::Changelog, Command::DareStats,
+        Command::RequestVouch, Command::Vouch, Command::Buddy, Command::Event,
+        Command::Marketplace, Command::PublishDares, Command::ImportDares, Command::Hardcore,
+        Command::Checkin, Command::Quest, Command::Escalate, Command::Duo, Command::Recap, Command::Done,
+        Command::Accept, Command::Decline, Command::Setup, Command::Config,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Command::Register => "register",
+            Command::Dare => "dare",
+            Command::Redeem => "redeem",
+            Command::Gallery => "gallery",
+            Command::Link => "link",
+            Command::Leaderboard => "leaderboard",
+            Command::Rank => "rank",
+            Command::Stats => "stats",
+            Command::Start => "start",
+            Command::PauseBot => "pause_bot",
+            Command::ResumeBot => "resume_bot",
+            Command::Changelog => "changelog",
+            Command::DareStats => "dare_stats",
+            Command::RequestVouch => "request_vouch",
+            Command::Vouch => "vouch",
+            Command::Buddy => "buddy",
+            Command::Event => "event",
+            Command::Marketplace => "marketplace",
+            Command::PublishDares => "publish_dares",
+            Command::ImportDares => "import_dares",
+            Command::Hardcore => "hardcore",
+            Command::Checkin => "checkin",
+            Command::Quest => "quest",
+            Command::Escalate => "escalate",
+            Command::Duo => "duo",
+            Command::Recap => "recap",
+            Command::Done => "done",
+            Command::Accept => "accept",
+            Command::Decline => "decline",
+            Command::Setup => "setup",
+            Command::Config => "config",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Command::Register => "Join Darely",
+            Command::Dare => "Get a dare",
+            Command::Redeem => "Redeem reached streak milestones and spin the reward wheel",
+            Command::Gallery => "See this chat's latest completed dares",
+            Command::Link => "Link your OpenChat and direct identities with a one-time code",
+            Command::Leaderboard => "See the top streaks",
+            Command::Rank => "See your streak rank and percentile",
+            Command::Stats => "See your streak, XP, points and open bonus tasks",
+            Command::Start => "Join Darely if you haven't yet and get your first dare",
+            Command::PauseBot => "Pause Darely's posts (and optionally its game commands) in this chat",
+            Command::ResumeBot => "Resume Darely in this chat",
+            Command::Changelog => "See what's new in Darely",
+            Command::DareStats => "See who created a dare and how often their dares were completed",
+            Command::RequestVouch => "Did your dare offline? Ask two members to vouch for it to keep your streak",
+            Command::Vouch => "Confirm that a member did their dare offline",
+            Command::Buddy => "Pair up with a buddy: /buddy pair @user, accept, decline or unpair",
+            Command::Event => "See the community's progress on the current global event",
+            Command::Marketplace => "Browse dares and packs shared by other chats",
+            Command::PublishDares => "Share this chat's favourite curated dares on the marketplace",
+            Command::ImportDares => "Import a marketplace pack (or one of its dares) into this chat",
+            Command::Hardcore => "Turn hardcore mode on or off: a lapsed streak resets and leaves a penalty dare to finish first",
+            Command::Checkin => "Check in once a day for bonus points; consecutive days pay more",
+            Command::Quest => "Browse questlines, start one, or leave the one you're on",
+            Command::Escalate => "Take the harder follow-up offered after a dare for multiplied XP, or decline it",
+            Command::Duo => "Share a streak with one other member: /duo invite @user, accept, decline or leave",
+            Command::Recap => "Post a recap of your month: /recap, /recap last or /recap YYYY-MM",
+            Command::Done => "Finished your dare but the proof needs time? Stop the clock now and send it within a day",
+            Command::Accept => "Take on the dare /dare just offered you",
+            Command::Decline => "Turn down the dare /dare just offered you and ask for another (limited per day)",
+            Command::Setup => "Set Darely up for this chat: dare mix, verification, weekly digest and language",
+            Command::Config => "Export this chat's settings as a code, or import a code from another chat",
+        }
+    }
+
+    pub fn scope(self) -> ScopePolicy {
+        match self {
+            Command::Register | Command::Dare | Command::Redeem | Command::Leaderboard | Command::Rank | Command::Stats | Command::Start
+            | Command::Changelog | Command::DareStats | Command::RequestVouch | Command::Vouch | Command::Buddy | Command::Event | Command::Marketplace | Command::Hardcore | Command::Checkin
+            | Command::Quest | Command::Escalate | Command::Duo | Command::Recap | Command::Done
+            | Command::Accept | Command::Decline => ScopePolicy::Any,
+            Command::Gallery | Command::PauseBot | Command::ResumeBot | Command::PublishDares | Command::ImportDares | Command::Setup
+            | Command::Config => ScopePolicy::GroupOnly,
+            // The link code would let anyone who sees it take over the account
+            Command::Link => ScopePolicy::DirectOnly,
+        }
+    }
+
+    fn default_visibility(self) -> Visibility {
+        match self {
+            Command::Register | Command::Dare | Command::Redeem | Command::Gallery | Command::Leaderboard | Command::Start
+            | Command::PauseBot | Command::ResumeBot | Command::RequestVouch | Command::Vouch | Command::Escalate | Command::Recap | Command::Done
+            | Command::Accept => Visibility::Public,
+            Command::Link | Command::Rank | Command::Stats | Command::Changelog | Command::DareStats | Command::Buddy
+            | Command::Marketplace | Command::Hardcore | Command::Checkin | Command::Quest | Command::Duo | Command::Decline
+            | Command::Setup | Command::Config => Visibility::Ephemeral,
+            Command::Event | Command::PublishDares | Command::ImportDares => Visibility::Public,
+        }
+    }
+
+    fn owner_only(self) -> bool {
+        matches!(self, Command::PauseBot | Command::ResumeBot | Command::PublishDares | Command::ImportDares | Command::Setup | Command::Config)
+    }
+
+    // Commands a paused chat can turn away (see pauses.rs); read-only ones keep working
+    fn is_gameplay(self) -> bool {
+        matches!(self, Command::Register | Command::Dare | Command::Redeem | Command::Gallery | Command::Start | Command::RequestVouch | Command::Vouch | Command::Hardcore
+            | Command::Checkin | Command::Quest | Command::Escalate | Command::Done | Command::Accept | Command::Decline)
+    }
+
+    pub fn visibility(self) -> Visibility {
+        state::get_config().command_visibility
+            .and_then(|overrides| overrides.into_iter().find(|(name, _)| name == self.name()))
+            .map_or_else(|| self.default_visibility(), |(_, visibility)| visibility)
+    }
+}
+
+pub fn find(name: &str) -> Option<Command> {
+    let name = name.trim().trim_start_matches('/');
+    Command::ALL.into_iter().find(|command| command.name() == name)
+}
+
+pub fn definitions() -> Vec<CommandDefinition> {
+    Command::ALL.iter().map(|&command| CommandDefinition {
+        name: command.name().to_string(),
+        description: command.description().to_string(),
+        scope: command.scope(),
+        visibility: command.visibility(),
+        owner_only: command.owner_only(),
+    }).collect()
+}
+
+// Checked by config.rs: overrides must name known commands, each at most once
+pub fn validate_visibility(overrides: &[(String, Visibility)]) -> Result<(), String> {
+    let mut seen = BTreeSet::new();
+    for (name, _) in overrides {
+        if !Command::ALL.iter().any(|command| command.name() == name) {
+            return Err(format!("Unknown command '{}'.", name));
+        }
+        if !seen.insert(name) {
+            return Err(format!("'{}' is listed more than once.", name));
+        }
+    }
+    Ok(())
+}
+
+// Stores an admin override; None (or the default) removes it
+pub fn set_visibility(overrides: &mut Option<Vec<(String, Visibility)>>, command: Command, visibility: Option<Visibility>) {
+    let mut list = overrides.take().unwrap_or_default();
+    list.retain(|(name, _)| name != command.name());
+    if let Some(visibility) = visibility.filter(|&visibility| visibility != command.default_visibility()) {
+        list.push((command.name().to_string(), visibility));
+    }
+    *overrides = (!list.is_empty()).then_some(list);
+}
+
+fn is_direct(scope: &ChatScope) -> bool {
+    match scope {
+        ChatScope::Direct => true,
+        ChatScope::Thread { parent, .. } => is_direct(parent),
+        _ => false,
+    }
+}
+
+// Checks that `command` may run in the chat it came from (and isn't paused there) and returns
+// that chat's key (None = no per-chat state, see chat_scope.rs)
+pub fn enforce(command: Command, chat_id: Option<&str>) -> Result<Option<String>, String> {
+    correlation::set_operation(&format!("/{}", command.name()));
+    let scope = chat_id.map(chat_scope::parse);
+    let allowed = match command.scope() {
+        ScopePolicy::Any => true,
+        ScopePolicy::DirectOnly => scope.as_ref().is_none_or(is_direct),
+        ScopePolicy::GroupOnly => scope.as_ref().and_then(ChatScope::chat_key).is_some(),
+    };
+    if !allowed {
+        let place = scope.as_ref().map_or("a direct call", ChatScope::describe);
+        return Err(match command.scope() {
+            ScopePolicy::DirectOnly => format!("/{} only works in your direct chat with Darely, not in {}.", command.name(), place),
+            _ => format!("/{} only works in a group chat or a community channel, not in {}.", command.name(), place),
+        });
+    }
+    let chat_key = scope.and_then(|scope| scope.chat_key());
+    if let Some(chat_key) = chat_key.as_deref().filter(|_| command.is_gameplay()) {
+        pauses::check_command(command.name(), chat_key)?;
+    }
+    Ok(chat_key)
+}
+
+// --- Internal Invocation ---
+
+pub enum Invocation {
+    Register { invite_code: Option<String> },
+    Dare { difficulty: Difficulty },
+}
+
+impl Invocation {
+    fn command(&self) -> Command {
+        match self {
+            Invocation::Register { .. } => Command::Register,
+            Invocation::Dare { .. } => Command::Dare,
+        }
+    }
+}
+
+// Runs a command for `user` as if it had been sent from `chat_id`
+pub async fn invoke(user: Principal, invocation: Invocation, chat_id: Option<&str>) -> Result<String, String> {
+    let chat_id = enforce(invocation.command(), chat_id)?;
+    match invocation {
+        Invocation::Register { invite_code } => registration::register(user, chat_id.as_deref(), invite_code.as_deref()),
+        Invocation::Dare { difficulty } => crate::dare_for(user, difficulty, chat_id).await,
+    }
+}
+
+// /start: registers the user unless they already are, then assigns a dare. Stops after the
+// registration reply when it didn't let the user in (invite-only request, waitlist).
+pub async fn start(user: Principal, difficulty: Difficulty, invite_code: Option<String>, chat_id: Option<&str>) -> Result<String, String> {
+    enforce(Command::Start, chat_id)?;
+    let mut replies = Vec::new();
+    if !state::is_registered(user) {
+        replies.push(invoke(user, Invocation::Register { invite_code }, chat_id).await?);
+        if !state::is_registered(user) {
+            return Ok(replies.remove(0));
+        }
+    }
+    replies.push(invoke(user, Invocation::Dare { difficulty }, chat_id).await?);
+    Ok(replies.join("\n\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ChatConfig, ChatPause};
+    use std::future::Future;
+    use std::task::{Context, Poll, Waker};
+
+    const GROUP: &str = "rrkah-fqaaa-aaaaa-aaaaq-cai";
+
+    #[test]
+    fn enforces_each_policy() {
+        assert_eq!(enforce(Command::Dare, Some(GROUP)), Ok(Some(GROUP.to_string())));
+        assert_eq!(enforce(Command::Dare, Some("direct")), Ok(None));
+        assert_eq!(enforce(Command::Dare, None), Ok(None));
+
+        assert_eq!(enforce(Command::Link, None), Ok(None));
+        assert_eq!(enforce(Command::Link, Some("direct#4")), Ok(None));
+        assert!(enforce(Command::Link, Some(GROUP)).unwrap_err().contains("not in a group chat"));
+
+        assert_eq!(enforce(Command::Gallery, Some(&format!("{}#9", GROUP))), Ok(Some(GROUP.to_string())));
+        assert!(enforce(Command::Gallery, Some("direct")).unwrap_err().contains("not in a direct chat"));
+        assert!(enforce(Command::Gallery, None).unwrap_err().contains("not in a direct call"));
+        assert!(enforce(Command::Gallery, Some("voice:abc")).is_err());
+    }
+
+    #[test]
+    fn visibility_overrides_replace_and_clear() {
+        let mut overrides = None;
+        set_visibility(&mut overrides, Command::Leaderboard, Some(Visibility::Ephemeral));
+        assert_eq!(overrides, Some(vec![("leaderboard".to_string(), Visibility::Ephemeral)]));
+        set_visibility(&mut overrides, Command::Stats, Some(Visibility::Ephemeral)); // Already the default
+        assert_eq!(overrides.as_ref().map(Vec::len), Some(1));
+        set_visibility(&mut overrides, Command::Leaderboard, None);
+        assert_eq!(overrides, None);
+
+        assert!(validate_visibility(&[("rank".to_string(), Visibility::Public)]).is_ok());
+        assert!(validate_visibility(&[("/rank".to_string(), Visibility::Public)]).is_err());
+        assert!(validate_visibility(&[("nope".to_string(), Visibility::Public)]).is_err());
+        assert!(validate_visibility(&[("rank".to_string(), Visibility::Public), ("rank".to_string(), Visibility::Ephemeral)]).is_err());
+    }
+
+    #[test]
+    fn mentions_in_a_paused_chat_are_turned_away() {
+        let pause = ChatPause { paused_at: 0, until: None, commands_disabled: true, paused_by: Principal::anonymous() };
+        state::CHAT_CONFIGS.with(|c| c.borrow_mut().insert(GROUP.to_string(), ChatConfig { pause: Some(pause), ..Default::default() }));
+        // As handle_message_event runs "@Darely dare me"; the check fails before anything awaits
+        let mut mention = std::pin::pin!(invoke(Principal::anonymous(), Invocation::Dare { difficulty: Difficulty::Easy }, Some(GROUP)));
+        let reply = mention.as_mut().poll(&mut Context::from_waker(Waker::noop()));
+        state::CHAT_CONFIGS.with(|c| c.borrow_mut().remove(&GROUP.to_string()));
+        match reply {
+            Poll::Ready(Err(e)) => assert!(e.contains("paused in this chat"), "{}", e),
+            _ => panic!("a paused chat handed out a dare"),
+        }
+    }
+
+    #[test]
+    fn every_command_is_defined_once() {
+        let names: BTreeSet<&str> = Command::ALL.iter().map(|command| command.name()).collect();
+        assert_eq!(names.len(), Command::ALL.len());
+    }
+}
@@ -0,0 +1,299 @@
+use crate::chat_scope;
+use crate::commands;
+use crate::cors;
+use crate::features;
+use crate::history;
+use crate::identity;
+use crate::llm;
+use crate::moderation;
+use crate::outbox;
+use crate::prefill;
+use crate::proposals;
+use crate::purge;
+use crate::queue_alerts;
+use crate::registration;
+use crate::rewards;
+use crate::safety;
+use crate::scheduler;
+use crate::state;
+use crate::types::{Config, HistoryEvent, SourceMix};
+use candid::Principal;
+use serde_json::{Map, Value};
+
+// --- Config Schema ---
+// Every change to the canister config goes through here, whether from a dedicated admin setter
+// or from `set_config`, which applies a JSON merge patch (RFC 7386) to the whole document: keys
+// in the patch replace the stored values, nested objects merge, and null clears an optional
+// field. The patched config must still deserialize (required fields present, known keys only)
+// and pass the schema checks below before it is stored. Each stored change is logged to the
+// admin's history with the top-level fields it touched and their previous values.
+
+pub const MAX_LIST_LEN: usize = 50;
+pub const MAX_SOURCE_WEIGHT: u32 = 1_000;
+
+// Shared by the default mix and every per-chat override
+pub fn validate_mix(mix: &SourceMix) -> Result<(), String> {
+    if mix.curated == 0 && mix.llm == 0 {
+        return Err("At least one source weight must be non-zero.".to_string());
+    }
+    if mix.curated.max(mix.llm) > MAX_SOURCE_WEIGHT {
+        return Err(format!("Source weights can be at most {}.", MAX_SOURCE_WEIGHT));
+    }
+    Ok(())
+}
+
+// Checks the whole config and returns every problem found, each prefixed with its field
+pub fn validate(config: &Config) -> Result<(), Vec<String>> {
+    let mut errors = Vec::new();
+    let mut check = |field: &str, result: Result<(), String>| {
+        if let Err(error) = result {
+            errors.push(format!("{}: {}", field, error));
+        }
+    };
+
+    check("source_mix", validate_mix(&config.source_mix));
+    if let Some(origins) = &config.cors_allowed_origins {
+        check("cors_allowed_origins", list_len(origins.len()).and_then(|_| {
+            let normalized = cors::normalize_origins(origins.clone())?;
+            if &normalized != origins {
+                return Err("Origins must be lowercase, without a trailing slash and without duplicates.".to_string());
+            }
+            Ok(())
+        }));
+    }
+    if let Some(der) = &config.ic_root_key {
+        check("ic_root_key", identity::validate_root_key(der));
+    }
+    if let Some(sla) = &config.review_sla {
+        check("review_sla", moderation::validate_sla(sla));
+    }
+    if let Some(recipients) = &config.alert_recipients {
+        check("alert_recipients", principal_list(recipients));
+    }
+    if let Some(bots) = &config.trusted_bots {
+        check("trusted_bots", principal_list(bots));
+    }
+    if let Some(wheel) = &config.reward_wheel {
+        check("reward_wheel", rewards::validate_wheel(wheel));
+    }
+    if let Some(policy) = &config.redemption_policy {
+        check("redemption_policy", rewards::validate_policy(policy));
+    }
+    if let Some(alerts) = &config.queue_alerts {
+        check("queue_alerts", queue_alerts::validate(alerts));
+    }
+    if let Some(retention) = &config.purge_retention {
+        check("purge_retention", purge::validate(retention));
+    }
+    if let Some(policy) = &config.registration {
+        check("registration", registration::validate_policy(policy));
+    }
+    if let Some(overrides) = &config.command_visibility {
+        check("command_visibility", commands::validate_visibility(overrides));
+    }
+    if let Some(rollouts) = &config.feature_rollouts {
+        check("feature_rollouts", features::validate_rollouts(rollouts));
+    }
+    if let Some(params) = &config.llm_params {
+        check("llm_params", llm::validate_params(params));
+    }
+    if let Some(terms) = &config.llm_blocked_terms {
+        check("llm_blocked_terms", list_len(terms.len()).and_then(|_| safety::validate_terms(terms)));
+    }
+    if let Some(schedule) = &config.prefill {
+        check("prefill", prefill::validate_schedule(schedule));
+    }
+    if let Some(channel) = &config.incident_channel {
+        check("incident_channel", chat_scope::require_chat_key(channel).and_then(|key| {
+            if &key != channel {
+                return Err("Give a group chat or channel, without a thread suffix.".to_string());
+            }
+            Ok(())
+        }));
+    }
+    if let Some(declines) = config.daily_declines {
+        check("daily_declines", proposals::validate_declines(declines));
+    }
+    if let Some(hours) = config.streak_expiry_hours {
+        check("streak_expiry_hours", scheduler::validate_expiry(hours));
+    }
+    if let Some(cap) = config.outbox_max_in_flight {
+        check("outbox_max_in_flight", outbox::validate_max_in_flight(cap));
+    }
+
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
+
+fn list_len(len: usize) -> Result<(), String> {
+    if len > MAX_LIST_LEN {
+        return Err(format!("At most {} entries are allowed.", MAX_LIST_LEN));
+    }
+    Ok(())
+}
+
+// Setters store an empty list as None, so a patch has to as well
+fn principal_list(principals: &[Principal]) -> Result<(), String> {
+    list_len(principals.len())?;
+    if principals.is_empty() {
+        return Err("Use null instead of an empty list.".to_string());
+    }
+    if principals.contains(&Principal::anonymous()) {
+        return Err("The anonymous principal is not allowed.".to_string());
+    }
+    Ok(())
+}
+
+fn describe(errors: Vec<String>) -> String {
+    format!("Invalid config:\n{}", errors.join("\n"))
+}
+
+// --- Updates ---
+
+fn to_json(config: &Config) -> Value {
+    serde_json::to_value(config).expect("Config serializes to JSON")
+}
+
+// Validates and stores `config`, logging the changed top-level fields. Returns their names.
+fn store(admin: Principal, before: &Config, config: Config) -> Result<Vec<String>, String> {
+    validate(&config).map_err(describe)?;
+    let (old, new) = (to_json(before), to_json(&config));
+    let (Value::Object(old), Value::Object(new)) = (old, new) else {
+        return Err("Config must serialize to a JSON object.".to_string());
+    };
+    let previous: Map<String, Value> = old.into_iter().filter(|(field, value)| new.get(field) != Some(value)).collect();
+    if previous.is_empty() {
+        return Ok(Vec::new());
+    }
+    let fields: Vec<String> = previous.keys().cloned().collect();
+    state::update_config(|stored| *stored = config);
+    history::log_event(admin, HistoryEvent::ConfigChanged {
+        fields: fields.clone(),
+        previous: Value::Object(previous).to_string(),
+    });
+    Ok(fields)
+}
+
+// Applies a mutation through the schema checks; used by the dedicated admin setters
+pub fn update<F: FnOnce(&mut Config)>(admin: Principal, f: F) -> Result<(), String> {
+    let before = state::get_config();
+    let mut config = before.clone();
+    f(&mut config);
+    store(admin, &before, config).map(|_| ())
+}
+
+pub fn get_json() -> String {
+    serde_json::to_string_pretty(&to_json(&state::get_config())).expect("Config serializes to JSON")
+}
+
+// RFC 7386: objects merge key by key, null removes the key, anything else replaces the target
+fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let Value::Object(target) = target else { return };
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+// Collects patch keys that didn't survive deserialization, i.e. fields the schema doesn't know
+fn unknown_keys(patch: &Value, result: &Value, path: &str, unknown: &mut Vec<String>) {
+    let Value::Object(patch) = patch else { return };
+    for (key, value) in patch {
+        if value.is_null() {
+            continue;
+        }
+        let field = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+        match result.get(key) {
+            Some(inner) => unknown_keys(value, inner, &field, unknown),
+            None => unknown.push(field),
+        }
+    }
+}
+
+fn apply(config: &Config, patch: &Value) -> Result<Config, String> {
+    if !patch.is_object() {
+        return Err("The patch must be a JSON object.".to_string());
+    }
+    let mut document = to_json(config);
+    merge_patch(&mut document, patch);
+    let patched: Config = serde_json::from_value(document).map_err(|e| describe(vec![e.to_string()]))?;
+    let mut unknown = Vec::new();
+    unknown_keys(patch, &to_json(&patched), "", &mut unknown);
+    if !unknown.is_empty() {
+        return Err(describe(unknown.into_iter().map(|field| format!("{}: Unknown field.", field)).collect()));
+    }
+    Ok(patched)
+}
+
+// Applies a JSON merge patch to the stored config. Returns the changed top-level fields.
+pub fn set_json(admin: Principal, patch: &str) -> Result<Vec<String>, String> {
+    let patch: Value = serde_json::from_str(patch).map_err(|e| format!("The patch is not valid JSON: {}", e))?;
+    let before = state::get_config();
+    let patched = apply(&before, &patch)?;
+    store(admin, &before, patched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn merge_patch_follows_rfc_7386() {
+        let mut target = json!({ "a": "b", "c": { "d": "e", "f": "g" } });
+        merge_patch(&mut target, &json!({ "a": "z", "c": { "f": null } }));
+        assert_eq!(target, json!({ "a": "z", "c": { "d": "e" } }));
+
+        let mut target = json!({ "a": ["b"] });
+        merge_patch(&mut target, &json!({ "a": { "b": "c" } }));
+        assert_eq!(target, json!({ "a": { "b": "c" } }));
+    }
+
+    #[test]
+    fn patch_sets_nested_and_clears_optional_fields() {
+        let config = Config { rating_survey: Some(true), ..Config::default() };
+        let patched = apply(&config, &json!({
+            "source_mix": { "llm": 0 },
+            "rating_survey": null,
+            "registration": { "max_users": 100, "invite_only": true },
+        })).unwrap();
+        assert_eq!((patched.source_mix.curated, patched.source_mix.llm), (3, 0));
+        assert_eq!(patched.rating_survey, None);
+        assert!(patched.registration.is_some_and(|policy| policy.invite_only && policy.max_users == Some(100)));
+    }
+
+    #[test]
+    fn patch_rejects_unknown_and_missing_fields() {
+        let config = Config::default();
+        let error = apply(&config, &json!({ "source_mx": { "llm": 2 } })).unwrap_err();
+        assert!(error.contains("source_mx: Unknown field."));
+        let error = apply(&config, &json!({ "source_mix": { "wrong": 1 } })).unwrap_err();
+        assert!(error.contains("source_mix.wrong: Unknown field."));
+        assert!(apply(&config, &json!({ "source_mix": null })).unwrap_err().contains("missing field"));
+        assert!(apply(&config, &json!(["not", "an", "object"])).is_err());
+    }
+
+    #[test]
+    fn validate_reports_every_bad_field() {
+        let mut config = Config::default();
+        assert!(validate(&config).is_ok());
+        config.source_mix.curated = 0;
+        config.source_mix.llm = 0;
+        config.trusted_bots = Some(Vec::new());
+        config.cors_allowed_origins = Some(vec!["HTTPS://Example.com/".to_string()]);
+        let errors = validate(&config).unwrap_err();
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].starts_with("source_mix: "));
+        assert!(errors[1].starts_with("cors_allowed_origins: "));
+        assert!(errors[2].starts_with("trusted_bots: "));
+    }
+}
@@ -0,0 +1,106 @@
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+// --- Request Tracing ---
+// Every incoming command (through the reply helpers in formatting.rs), HTTP request and timer run
+// gets a correlation id, so the steps of one request can be followed through the logs. Log lines
+// written with correlation::log! start with it, LLM outcalls send it as an X-Correlation-Id header,
+// and outbox messages and import jobs store the id of the request that queued them: the bot gets
+// it with each message it delivers, and a job's later chunks log under it. Errors returned to
+// admins end with "(ref <id>)" to look up in the logs. Alongside the id goes the operation being
+// run (a command name, an HTTP route, "timer"), which incidents.rs uses for context.
+//
+// The id lives in a thread local, which other messages overwrite while a call awaits, so async
+// work runs inside `traced`, which puts its id back every time it resumes.
+
+thread_local! {
+    static CURRENT: RefCell<Option<String>> = const { RefCell::new(None) };
+    static COUNTER: Cell<u64> = const { Cell::new(0) };
+    static OPERATION: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+// Milliseconds since the epoch in hex, then a per-canister counter: sortable and unique
+fn make_id(now_nanos: u64, seq: u64) -> String {
+    format!("{:x}-{:x}", now_nanos / 1_000_000, seq)
+}
+
+// Starts a new request and returns its id
+pub fn begin() -> String {
+    let seq = COUNTER.get();
+    COUNTER.set(seq.wrapping_add(1));
+    let id = make_id(ic_cdk::api::time(), seq);
+    CURRENT.with(|current| *current.borrow_mut() = Some(id.clone()));
+    OPERATION.with(|operation| *operation.borrow_mut() = None);
+    id
+}
+
+// Names what the current request does, e.g. "/dare"
+pub fn set_operation(name: &str) {
+    OPERATION.with(|operation| *operation.borrow_mut() = Some(name.to_string()));
+}
+
+pub fn operation() -> String {
+    OPERATION.with(|operation| operation.borrow().clone()).unwrap_or_else(|| "an admin call".to_string())
+}
+
+// Continues work queued under `id` (a stored job or message); a new id when there is none
+pub fn adopt(id: Option<String>) {
+    match id {
+        Some(id) => CURRENT.with(|current| *current.borrow_mut() = Some(id)),
+        None => {
+            begin();
+        }
+    }
+}
+
+pub fn current() -> String {
+    CURRENT.with(|current| current.borrow().clone()).unwrap_or_else(|| "-".to_string())
+}
+
+// An error for an admin, with the id to find its trail in the logs
+pub fn tag_error(error: String) -> String {
+    format!("{} (ref {})", error, current())
+}
+
+// Logs a line prefixed with the current correlation id
+macro_rules! log {
+    ($($arg:tt)*) => {
+        ic_cdk::println!("[{}] {}", $crate::correlation::current(), format!($($arg)*))
+    };
+}
+pub(crate) use log;
+
+pub struct Traced<F> {
+    id: String,
+    inner: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for Traced<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        CURRENT.with(|current| *current.borrow_mut() = Some(self.id.clone()));
+        self.inner.as_mut().poll(cx)
+    }
+}
+
+// Runs `future` under the current correlation id, across its awaits
+pub fn traced<F: Future>(future: F) -> Traced<F> {
+    Traced { id: current(), inner: Box::pin(future) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_sort_by_time_and_stay_unique() {
+        assert_eq!(make_id(1_700_000_000_000_000_000, 0), "18bcfe56800-0");
+        assert_ne!(make_id(5_000_000, 1), make_id(5_000_000, 2));
+        assert_eq!(current(), "-");
+        adopt(Some("abc-1".to_string()));
+        assert_eq!(tag_error("Boom.".to_string()), "Boom. (ref abc-1)");
+    }
+}
@@ -0,0 +1,62 @@
+use crate::state;
+
+// --- CORS ---
+// Allowed origins come from Config::cors_allowed_origins ("*" allows any origin).
+// Routes opt in individually via Route::cors.
+
+const ALLOWED_HEADERS: &str = "Content-Type, Authorization, If-None-Match, X-Identity-Proof, X-API-Key";
+const EXPOSED_HEADERS: &str = "ETag, X-API-Version";
+const PREFLIGHT_MAX_AGE_SECS: u32 = 86_400;
+
+// The value for Access-Control-Allow-Origin, if this origin may call the API
+pub fn allowed_origin(origin: Option<&str>) -> Option<String> {
+    let origin = origin?;
+    let allowed = state::get_config().cors_allowed_origins.unwrap_or_default();
+    if allowed.iter().any(|o| o == "*") {
+        Some("*".to_string())
+    } else if allowed.iter().any(|o| o.trim_end_matches('/').eq_ignore_ascii_case(origin)) {
+        Some(origin.to_string())
+    } else {
+        None
+    }
+}
+
+// Headers added to every response of a CORS-enabled route
+pub fn response_headers(allow_origin: &str) -> Vec<(String, String)> {
+    vec![
+        ("Access-Control-Allow-Origin".to_string(), allow_origin.to_string()),
+        ("Access-Control-Expose-Headers".to_string(), EXPOSED_HEADERS.to_string()),
+        ("Vary".to_string(), "Origin".to_string()),
+    ]
+}
+
+// Headers for a successful preflight (OPTIONS) response
+pub fn preflight_headers(allow_origin: &str, methods: &[&str]) -> Vec<(String, String)> {
+    let mut allow_methods = methods.join(", ");
+    allow_methods.push_str(", OPTIONS");
+    vec![
+        ("Access-Control-Allow-Origin".to_string(), allow_origin.to_string()),
+        ("Access-Control-Allow-Methods".to_string(), allow_methods),
+        ("Access-Control-Allow-Headers".to_string(), ALLOWED_HEADERS.to_string()),
+        ("Access-Control-Max-Age".to_string(), PREFLIGHT_MAX_AGE_SECS.to_string()),
+        ("Vary".to_string(), "Origin".to_string()),
+    ]
+}
+
+// Normalizes admin input: trims, drops empties and trailing slashes, removes duplicates
+pub fn normalize_origins(origins: Vec<String>) -> Result<Vec<String>, String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for origin in origins {
+        let origin = origin.trim().trim_end_matches('/').to_lowercase();
+        if origin.is_empty() {
+            continue;
+        }
+        if origin != "*" && !origin.starts_with("https://") && !origin.starts_with("http://") {
+            return Err(format!("Invalid origin '{}': expected https://host[:port] or *.", origin));
+        }
+        if !normalized.contains(&origin) {
+            normalized.push(origin);
+        }
+    }
+    Ok(normalized)
+}
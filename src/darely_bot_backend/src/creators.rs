@@ -0,0 +1,80 @@
+use crate::gallery;
+use crate::repository::{self, DareRepo};
+use crate::state;
+use crate::timezone;
+use crate::types::{CreatorStats, DareSource, StorablePrincipal, Submission};
+use candid::Principal;
+use std::cmp::Reverse;
+
+// --- Dare Creators ---
+// Dares remember who created them: the admin who added a curated dare or the member who wrote a
+// custom one (LLM dares have no creator). Each approved submission of a dare credits its
+// creator with a completion, unless they completed their own dare, and the creator leaderboard
+// ranks creators by those completions. /dare_stats shows a dare's attribution with its
+// creator's totals.
+
+pub const DEFAULT_LEADERBOARD_SIZE: usize = 10;
+pub const MAX_LEADERBOARD_SIZE: usize = 50;
+
+fn update(creator: Principal, f: impl FnOnce(&mut CreatorStats)) {
+    state::CREATOR_STATS.with(|c| {
+        let mut stats = c.borrow_mut();
+        let mut entry = stats.get(&StorablePrincipal(creator)).unwrap_or_default();
+        f(&mut entry);
+        stats.insert(StorablePrincipal(creator), entry);
+    });
+}
+
+pub fn stats(creator: Principal) -> CreatorStats {
+    state::CREATOR_STATS.with(|c| c.borrow().get(&StorablePrincipal(creator))).unwrap_or_default()
+}
+
+// Called when a dare with a creator is stored
+pub fn note_created(creator: Principal) {
+    update(creator, |stats| stats.dares_created += 1);
+}
+
+// Called when a dare submission is approved (including on appeal)
+pub fn credit_completion(submission: &Submission) {
+    let Some(dare) = submission.dare_id.and_then(|dare_id| repository::dares().get(dare_id)) else { return };
+    let Some(creator) = dare.creator.filter(|&creator| creator != submission.user) else { return };
+    let now = ic_cdk::api::time();
+    update(creator, |stats| {
+        stats.completions += 1;
+        stats.last_completed_at = Some(now);
+    });
+}
+
+// Most completed creators first; ties go to the creator with more dares, then the lower principal
+pub fn leaderboard(limit: usize) -> Vec<(Principal, CreatorStats)> {
+    let mut entries: Vec<(Principal, CreatorStats)> = state::CREATOR_STATS.with(|c| {
+        c.borrow().iter().filter(|(_, stats)| stats.completions > 0).map(|(creator, stats)| (creator.0, stats)).collect()
+    });
+    entries.sort_by_key(|(creator, stats)| (Reverse(stats.completions), Reverse(stats.dares_created), *creator));
+    entries.truncate(limit);
+    entries
+}
+
+// /dare_stats <id>, with dates in the viewer's timezone
+pub fn describe_dare(dare_id: u64, viewer: Principal) -> Result<String, String> {
+    let dare = repository::dares().get(dare_id).ok_or_else(|| format!("Dare #{} not found.", dare_id))?;
+    let source = match dare.source {
+        DareSource::Curated => "curated",
+        DareSource::Llm => "AI-generated",
+        DareSource::Custom => "written by a member",
+    };
+    let mut lines = vec![format!("📊 Dare #{} ({:?}, {}): {}", dare.id, dare.difficulty, source, dare.text)];
+    let created = dare.created_at.map(|at| format!(" on {}", timezone::local_date(viewer, at))).unwrap_or_default();
+    match dare.creator {
+        Some(creator) => {
+            let stats = stats(creator);
+            lines.push(format!("Created by {}{}.", gallery::display_name(creator), created));
+            lines.push(format!(
+                "{} has created {} dares, completed {} times by others.",
+                gallery::display_name(creator), stats.dares_created, stats.completions
+            ));
+        }
+        None => lines.push(format!("No creator on record{}.", created)),
+    }
+    Ok(lines.join("\n"))
+}
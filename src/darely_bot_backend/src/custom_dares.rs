@@ -0,0 +1,83 @@
+use crate::analysis;
+use crate::blocklist;
+use crate::hardcore;
+use crate::outbox;
+use crate::state;
+use crate::types::{DareSource, Difficulty};
+use candid::Principal;
+
+// --- Custom Dares ("dare a friend") ---
+// Any member can write a dare for another member who has opted in. The dare is stored with the
+// Custom source, which keeps it out of the shared pools and public listings, and becomes the
+// target's active dare just like one from /get_dare: submitting it credits the streak, and a
+// rejected review takes the point back. The target is told through the outbox.
+
+const MIN_TEXT_LEN: usize = 10;
+// Member-written dares aren't curated, so requests that are clearly unsafe are refused outright
+const FORBIDDEN_WORDS: &[&str] = &[
+    "suicide", "kill", "cut", "weapon", "gun", "knife", "drug", "drugs", "steal", "nude", "naked",
+    "bleach", "drunk", "address", "password",
+];
+const PENALTY_OPEN: &str = "That user is finishing a hardcore penalty dare. Try again once they've submitted it.";
+
+pub fn set_opt_in(user: Principal, accept: bool) -> Result<(), String> {
+    state::update_profile(user, |profile| {
+        profile.accepts_custom_dares = Some(accept);
+        Ok(())
+    })
+}
+
+fn check_content(text: &str) -> Result<(), String> {
+    if text.len() < MIN_TEXT_LEN || text.len() > crate::MAX_DARE_TEXT_LEN {
+        return Err(format!("A custom dare must be {}-{} bytes.", MIN_TEXT_LEN, crate::MAX_DARE_TEXT_LEN));
+    }
+    if analysis::tokenize(text).iter().any(|word| FORBIDDEN_WORDS.contains(&word.as_str())) {
+        return Err("That dare isn't allowed. Keep custom dares safe and friendly.".to_string());
+    }
+    Ok(())
+}
+
+// Assigns `text` to `target` as their active dare. Without a difficulty the heuristic
+// suggestion is used. Returns the new dare's id and difficulty.
+pub fn issue(issuer: Principal, target: Principal, text: String, difficulty: Option<Difficulty>) -> Result<(u64, Difficulty), String> {
+    let text = text.trim().to_string();
+    if issuer == target {
+        return Err("You can't dare yourself. Use /get_dare instead.".to_string());
+    }
+    if !state::is_registered(issuer) {
+        return Err("User not found. Please /register first.".to_string());
+    }
+    let profile = state::get_profile(target)
+        .ok_or_else(|| "That user isn't registered with Darely.".to_string())?;
+    if profile.accepts_custom_dares != Some(true) {
+        return Err("That user doesn't accept custom dares.".to_string());
+    }
+    if profile.current_dare_id.is_some() {
+        return Err("That user already has an active dare. Try again once they've submitted it.".to_string());
+    }
+    hardcore::check_penalty(&profile).map_err(|_| PENALTY_OPEN.to_string())?;
+    check_content(&text)?;
+    if blocklist::Blocklist::of(&profile).matches_text(&text) {
+        return Err("That dare involves a topic the user has asked not to get dares about.".to_string());
+    }
+
+    let difficulty = difficulty.unwrap_or_else(|| analysis::analyze_dare(&text).suggested_difficulty);
+    let dare_id = crate::store_dare(text.clone(), difficulty.clone(), DareSource::Custom, Some(issuer))?;
+    state::update_profile(target, |profile| {
+        // Another dare may have been assigned since the checks above
+        if profile.current_dare_id.is_some() {
+            return Err("That user already has an active dare. Try again once they've submitted it.".to_string());
+        }
+        hardcore::check_penalty(profile).map_err(|_| PENALTY_OPEN.to_string())?;
+        profile.current_dare_id = Some(dare_id);
+        profile.current_dare_chat = None;
+        profile.current_dare_text = None;
+        Ok(())
+    })?;
+    outbox::queue(target, format!(
+        "A fellow Darely member dared you ({:?}): \"{}\". Submit your proof with /submit_dare. \
+         To stop receiving custom dares, use /custom_dares off.",
+        difficulty, text
+    ), None);
+    Ok((dare_id, difficulty))
+}
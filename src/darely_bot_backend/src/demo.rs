@@ -0,0 +1,126 @@
+use crate::exclusions;
+use crate::ranking;
+use crate::repository::{self, DareRepo, UserRepo};
+use crate::state;
+use crate::types::{DareSource, Difficulty, StorablePrincipal, UserProfile};
+use candid::Principal;
+
+// --- Demo Data ---
+// Showcase deployments start empty, so controllers can seed curated dares from the catalog below
+// and fake users with spread-out streaks. Both are recorded in their own stores as they're
+// seeded, so `purge` removes exactly what `seed` added and never a real user. Demo principals
+// start with DEMO_PRINCIPAL_TAG only to keep them apart from real ones while seeding. Dares live
+// in an append-only vector and can't be deleted, so purged demo dares are retired instead:
+// disabled in every chat and given no selection weight.
+
+pub const MAX_DEMO_USERS: u32 = 200;
+const DEMO_PRINCIPAL_TAG: [u8; 2] = [0xDA, 0xDE];
+const MAX_DEMO_STREAK: u64 = 30;
+
+const CATALOG: &[(&str, Difficulty)] = &[
+    ("Drink a full glass of water right now.", Difficulty::Easy),
+    ("Send a compliment to {random_member}.", Difficulty::Easy),
+    ("Do {number:10-25} jumping jacks.", Difficulty::Easy),
+    ("Share the last photo in your camera roll (or describe it).", Difficulty::Easy),
+    ("Tell the chat your favourite snack and why.", Difficulty::Easy),
+    ("Stand up and stretch for one minute.", Difficulty::Easy),
+    ("Write a haiku about your {day_of_week}.", Difficulty::Easy),
+    ("Hold a plank for {number:30-60} seconds.", Difficulty::Medium),
+    ("Go for a 15-minute walk without your phone.", Difficulty::Medium),
+    ("Learn to say \"thank you\" in a language you don't speak.", Difficulty::Medium),
+    ("Cook something you've never made before.", Difficulty::Medium),
+    ("Call a friend you haven't spoken to in a month.", Difficulty::Medium),
+    ("Tidy one drawer and post a before/after.", Difficulty::Medium),
+    ("Challenge {random_member} to a round of rock-paper-scissors.", Difficulty::Medium),
+    ("Take a cold shower.", Difficulty::Hard),
+    ("Do {number:50-100} push-ups today, in as many sets as you need.", Difficulty::Hard),
+    ("Spend the rest of the day without social media.", Difficulty::Hard),
+    ("Wake up an hour earlier tomorrow and watch the sunrise.", Difficulty::Hard),
+    ("Sing a song in a voice message to the chat.", Difficulty::Hard),
+    ("Run 5 km and share your time.", Difficulty::Hard),
+];
+
+fn demo_principal(seq: u64) -> Principal {
+    let mut bytes = DEMO_PRINCIPAL_TAG.to_vec();
+    bytes.extend_from_slice(&seq.to_be_bytes());
+    Principal::from_slice(&bytes)
+}
+
+fn seeded_texts() -> Vec<String> {
+    let ids: Vec<u64> = state::DEMO_DARES.with(|d| d.borrow().keys().collect());
+    ids.into_iter().filter_map(|id| repository::dares().get(id)).map(|dare| dare.text).collect()
+}
+
+// Adds up to `dares` catalog dares not seeded yet and `users` demo users.
+// Returns (dares added, users added).
+pub fn seed(dares: u32, users: u32) -> Result<(u32, u32), String> {
+    if dares as usize > CATALOG.len() {
+        return Err(format!("The demo catalog has {} dares.", CATALOG.len()));
+    }
+    if users > MAX_DEMO_USERS {
+        return Err(format!("At most {} demo users can be seeded at once.", MAX_DEMO_USERS));
+    }
+    let now = ic_cdk::api::time();
+    let seeded = seeded_texts();
+    let mut added_dares = 0;
+    for (text, difficulty) in CATALOG.iter().filter(|(text, _)| !seeded.iter().any(|s| s == text)).take(dares as usize) {
+        let dare_id = crate::store_dare(text.to_string(), difficulty.clone(), DareSource::Curated, None)?;
+        state::DEMO_DARES.with(|d| d.borrow_mut().insert(dare_id, now));
+        added_dares += 1;
+    }
+
+    let repo = repository::users();
+    let mut seq = 0;
+    let mut roll = now;
+    let mut added_users = 0;
+    while added_users < users {
+        let user = demo_principal(seq);
+        seq += 1;
+        if repo.contains(user) {
+            continue;
+        }
+        roll = roll.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        let mut profile = UserProfile::default();
+        ranking::set_streak(&mut profile, ((roll >> 33) % (MAX_DEMO_STREAK + 1)) as u32);
+        profile.xp = Some((roll >> 40) % 500);
+        repo.put(user, profile);
+        state::DEMO_USERS.with(|d| d.borrow_mut().insert(StorablePrincipal(user), now));
+        added_users += 1;
+    }
+    Ok((added_dares, added_users))
+}
+
+// Deletes every demo user and retires every demo dare. Returns (dares retired, users removed).
+pub fn purge() -> Result<(u32, u32), String> {
+    let dare_ids: Vec<u64> = state::DEMO_DARES.with(|d| d.borrow().keys().collect());
+    for &dare_id in &dare_ids {
+        if let Some(mut dare) = repository::dares().get(dare_id) {
+            exclusions::disable(dare_id, None)?;
+            dare.weight = 0;
+            repository::dares().set(&dare)?;
+        }
+        state::DEMO_DARES.with(|d| d.borrow_mut().remove(&dare_id));
+    }
+
+    let users: Vec<StorablePrincipal> = state::DEMO_USERS.with(|d| d.borrow().keys().collect());
+    for user in &users {
+        repository::users().remove(user.0);
+        state::DEMO_USERS.with(|d| d.borrow_mut().remove(user));
+    }
+    Ok((dare_ids.len() as u32, users.len() as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::templates;
+
+    #[test]
+    fn catalog_dares_render() {
+        for (text, _) in CATALOG {
+            assert!(templates::validate(text).is_ok(), "{}", text);
+        }
+        assert_eq!(&demo_principal(7).as_slice()[..2], &DEMO_PRINCIPAL_TAG);
+        assert_ne!(demo_principal(7), demo_principal(8));
+    }
+}
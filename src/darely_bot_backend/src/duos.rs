@@ -0,0 +1,275 @@
+use crate::gallery;
+use crate::invites::{self, Consent};
+use crate::outbox;
+use crate::state;
+use crate::types::{Duo, DuoDay, StorablePrincipal, Submission};
+use candid::Principal;
+
+// --- Duos ---
+// Two members (a couple, a household, a team of two) can share a duo streak: it grows by one on
+// every UTC day on which either of them submits a dare, and breaks after a day on which neither
+// did. Each member keeps their own profile, streak and history; the duo only adds the shared
+// counter. Forming one takes consent like a buddy pairing (see invites.rs): `/duo invite @user`
+// sends a request the other member accepts or declines within three days. Each user is in at most
+// one duo, and either member can leave it, which ends the duo for both. An undone or rejected
+// submission takes back its day while that day is the duo's latest and nobody else's submission
+// counted for it; once a later day has counted, it only lowers the dare count. A rejection that
+// is overturned on appeal counts again the same way.
+
+static CONSENT: Consent = Consent { invites: &state::DUO_INVITES, noun: "duo request" };
+const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+fn duo_of(user: Principal) -> Option<Duo> {
+    let id = state::DUO_MEMBERS.with(|m| m.borrow().get(&StorablePrincipal(user)))?;
+    state::DUOS.with(|d| d.borrow().get(&id))
+}
+
+fn partner(duo: &Duo, user: Principal) -> Principal {
+    duo.members.iter().copied().find(|&member| member != user).unwrap_or(user)
+}
+
+// The streak as of `day`: a day without a submission breaks it
+fn current_streak(duo: &Duo, day: u64) -> u32 {
+    match duo.last_day {
+        Some(last_day) if last_day + 1 >= day => duo.streak,
+        _ => 0,
+    }
+}
+
+// Counts a submission on `day`. Returns whether the streak grew (the day's first submission).
+fn count_day(duo: &mut Duo, user: Principal, day: u64, submission_id: u64) -> bool {
+    duo.completions += 1;
+    duo.last_completed_by = Some(user);
+    if duo.last_day == Some(day) {
+        if let Some(latest) = duo.latest_day.as_mut() {
+            latest.submissions.push(submission_id);
+        }
+        return false;
+    }
+    duo.latest_day = Some(DuoDay { submissions: vec![submission_id], streak: duo.streak, best_streak: duo.best_streak, last_day: duo.last_day });
+    duo.streak = current_streak(duo, day) + 1;
+    duo.best_streak = duo.best_streak.max(duo.streak);
+    duo.last_day = Some(day);
+    true
+}
+
+// Takes back a counted submission, and its day when it was the only one on the latest day
+fn uncount(duo: &mut Duo, submission_id: u64) {
+    duo.completions = duo.completions.saturating_sub(1);
+    let Some(latest) = duo.latest_day.as_mut() else { return };
+    if !latest.submissions.contains(&submission_id) {
+        return;
+    }
+    latest.submissions.retain(|&id| id != submission_id);
+    if latest.submissions.is_empty() {
+        duo.streak = latest.streak;
+        duo.best_streak = latest.best_streak;
+        duo.last_day = latest.last_day;
+        duo.latest_day = None;
+    }
+}
+
+// Counts a taken-back submission again (an overturned rejection). Its day only comes back while
+// no later day has counted; otherwise it just raises the dare count, as uncount only lowered it.
+fn recount(duo: &mut Duo, user: Principal, day: u64, submission_id: u64) {
+    if duo.last_day.is_some_and(|last_day| last_day > day) {
+        duo.completions += 1;
+        return;
+    }
+    count_day(duo, user, day, submission_id);
+}
+
+pub fn invite(user: Principal, target: Principal) -> Result<String, String> {
+    let now = ic_cdk::api::time();
+    if user == target {
+        return Err("You can't form a duo with yourself.".to_string());
+    }
+    if !state::is_registered(user) {
+        return Err("User not found. Please /register first.".to_string());
+    }
+    if !state::is_registered(target) {
+        return Err(format!("{} hasn't joined Darely yet.", gallery::display_name(target)));
+    }
+    if let Some(duo) = duo_of(user) {
+        return Err(format!("You're already in a duo with {}. Use /duo leave first.", gallery::display_name(partner(&duo, user))));
+    }
+    if duo_of(target).is_some() {
+        return Err(format!("{} is already in a duo.", gallery::display_name(target)));
+    }
+    let message = format!(
+        "🏠 {} wants to share a duo streak with you: a dare by either of you keeps it going each day. Reply /duo accept or /duo decline within 3 days.",
+        gallery::display_name(user)
+    );
+    match CONSENT.request(user, target, now, message)? {
+        invites::Request::Mutual => accept(user),
+        invites::Request::Sent => Ok(format!("Duo request sent to {}.", gallery::display_name(target))),
+    }
+}
+
+pub fn accept(user: Principal) -> Result<String, String> {
+    let now = ic_cdk::api::time();
+    let inviter = CONSENT.take(user, now)?.from;
+    if duo_of(user).is_some() || duo_of(inviter).is_some() {
+        return Err("One of you has joined another duo in the meantime.".to_string());
+    }
+    let id = state::DUOS.with(|d| {
+        let mut duos = d.borrow_mut();
+        let id = duos.last_key_value().map_or(0, |(id, _)| id + 1);
+        duos.insert(id, Duo {
+            id,
+            members: vec![inviter, user],
+            formed_at: now,
+            streak: 0,
+            best_streak: 0,
+            last_day: None,
+            last_completed_by: None,
+            completions: 0,
+            latest_day: None,
+        });
+        id
+    });
+    state::DUO_MEMBERS.with(|m| {
+        let mut members = m.borrow_mut();
+        members.insert(StorablePrincipal(inviter), id);
+        members.insert(StorablePrincipal(user), id);
+    });
+    outbox::queue(inviter, format!("🏠 {} accepted: your duo streak starts with the next dare either of you submits.", gallery::display_name(user)), None);
+    Ok(format!(
+        "🏠 You and {} are now a duo. A dare by either of you each day keeps your shared streak alive.", gallery::display_name(inviter)
+    ))
+}
+
+pub fn decline(user: Principal) -> Result<String, String> {
+    CONSENT.decline(user)
+}
+
+pub fn leave(user: Principal) -> Result<String, String> {
+    let duo = duo_of(user).ok_or_else(|| "You're not in a duo.".to_string())?;
+    state::DUOS.with(|d| d.borrow_mut().remove(&duo.id));
+    state::DUO_MEMBERS.with(|m| {
+        let mut members = m.borrow_mut();
+        for member in &duo.members {
+            members.remove(&StorablePrincipal(*member));
+        }
+    });
+    let other = partner(&duo, user);
+    outbox::queue(other, format!("{} ended your duo (best streak {}).", gallery::display_name(user), duo.best_streak), None);
+    Ok(format!("You and {} are no longer a duo. Your own streak and history are unchanged.", gallery::display_name(other)))
+}
+
+pub fn describe(user: Principal) -> Result<String, String> {
+    let now = ic_cdk::api::time();
+    let Some(duo) = duo_of(user) else {
+        return Ok(match CONSENT.open(user, now) {
+            Some(invite) => format!("{} wants to form a duo with you: /duo accept or /duo decline.", gallery::display_name(invite.from)),
+            None => "You're not in a duo. Share a streak with someone using /duo invite @user.".to_string(),
+        });
+    };
+    let day = now / DAY_NANOS;
+    let today = if duo.last_day == Some(day) { "today's dare is done" } else { "nobody has done today's dare yet" };
+    Ok(format!(
+        "🏠 Your duo with {}: streak {} (best {}), {} dares together; {}.",
+        gallery::display_name(partner(&duo, user)), current_streak(&duo, day), duo.best_streak, duo.completions, today
+    ))
+}
+
+// Called for every submitted dare: counts the day for the user's duo and tells their partner
+pub fn note_completion(user: Principal, submission_id: u64) {
+    let Some(mut duo) = duo_of(user) else { return };
+    let grew = count_day(&mut duo, user, ic_cdk::api::time() / DAY_NANOS, submission_id);
+    let (other, streak) = (partner(&duo, user), duo.streak);
+    state::DUOS.with(|d| d.borrow_mut().insert(duo.id, duo));
+    if grew {
+        outbox::queue(other, format!("🏠 {} kept your duo streak alive: {} day(s).", gallery::display_name(user), streak), None);
+    }
+}
+
+// Called when a submission is undone or rejected
+pub fn revert(submission: &Submission) {
+    let Some(mut duo) = duo_of(submission.user) else { return };
+    // Submitted before this duo was formed
+    if submission.submitted_at < duo.formed_at {
+        return;
+    }
+    uncount(&mut duo, submission.id);
+    state::DUOS.with(|d| d.borrow_mut().insert(duo.id, duo));
+}
+
+// Called when a rejection is overturned: counts the submission's day again
+pub fn restore(submission: &Submission) {
+    let Some(mut duo) = duo_of(submission.user) else { return };
+    if submission.submitted_at < duo.formed_at {
+        return;
+    }
+    recount(&mut duo, submission.user, submission.submitted_at / DAY_NANOS, submission.id);
+    state::DUOS.with(|d| d.borrow_mut().insert(duo.id, duo));
+}
+
+// Timer: drops expired invites. Returns how many were removed.
+pub fn purge_invites() -> usize {
+    CONSENT.purge(ic_cdk::api::time())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn either_member_keeps_the_streak_once_a_day() {
+        let (a, b) = (Principal::from_slice(&[1]), Principal::from_slice(&[2]));
+        let mut duo = Duo {
+            id: 0, members: vec![a, b], formed_at: 0, streak: 0, best_streak: 0, last_day: None, last_completed_by: None, completions: 0,
+            latest_day: None,
+        };
+        assert!(count_day(&mut duo, a, 10, 1));
+        assert!(!count_day(&mut duo, b, 10, 2));
+        assert!(count_day(&mut duo, b, 11, 3));
+        assert_eq!((duo.streak, duo.completions, duo.last_completed_by), (2, 3, Some(b)));
+        assert_eq!(current_streak(&duo, 12), 2);
+        assert_eq!(current_streak(&duo, 13), 0);
+
+        assert!(count_day(&mut duo, a, 14, 4));
+        assert_eq!((duo.streak, duo.best_streak), (1, 2));
+    }
+
+    #[test]
+    fn uncounting_takes_back_the_latest_day_only() {
+        let (a, b) = (Principal::from_slice(&[1]), Principal::from_slice(&[2]));
+        let mut duo = Duo {
+            id: 0, members: vec![a, b], formed_at: 0, streak: 0, best_streak: 0, last_day: None, last_completed_by: None, completions: 0,
+            latest_day: None,
+        };
+        count_day(&mut duo, a, 10, 1);
+        count_day(&mut duo, a, 11, 2);
+        count_day(&mut duo, b, 11, 3);
+        uncount(&mut duo, 2);
+        assert_eq!((duo.streak, duo.last_day, duo.completions), (2, Some(11), 2));
+        uncount(&mut duo, 3);
+        assert_eq!((duo.streak, duo.best_streak, duo.last_day, duo.completions), (1, 1, Some(10), 1));
+        // Day 10 is no longer the latest one on record
+        uncount(&mut duo, 1);
+        assert_eq!((duo.streak, duo.last_day, duo.completions), (1, Some(10), 0));
+    }
+
+    #[test]
+    fn an_overturned_rejection_counts_its_day_again() {
+        let (a, b) = (Principal::from_slice(&[1]), Principal::from_slice(&[2]));
+        let mut duo = Duo {
+            id: 0, members: vec![a, b], formed_at: 0, streak: 0, best_streak: 0, last_day: None, last_completed_by: None, completions: 0,
+            latest_day: None,
+        };
+        count_day(&mut duo, a, 10, 1);
+        count_day(&mut duo, b, 11, 2);
+        // Rejected, then overturned before anything else counted
+        uncount(&mut duo, 2);
+        assert_eq!((duo.streak, duo.last_day, duo.completions), (1, Some(10), 1));
+        recount(&mut duo, b, 11, 2);
+        assert_eq!((duo.streak, duo.best_streak, duo.last_day, duo.completions), (2, 2, Some(11), 2));
+
+        // Rejected, then a later day counted before the appeal came through
+        uncount(&mut duo, 2);
+        count_day(&mut duo, a, 12, 3);
+        recount(&mut duo, b, 11, 2);
+        assert_eq!((duo.streak, duo.last_day, duo.completions), (1, Some(12), 3));
+    }
+}
@@ -0,0 +1,67 @@
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+use std::io::Write;
+
+// Bodies smaller than this aren't worth compressing
+pub const MIN_COMPRESSIBLE_BYTES: usize = 512;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContentEncoding {
+    Gzip,
+    Deflate, // HTTP "deflate" is zlib-wrapped (RFC 1950)
+}
+
+impl ContentEncoding {
+    pub fn header_value(&self) -> &'static str {
+        match self {
+            ContentEncoding::Gzip => "gzip",
+            ContentEncoding::Deflate => "deflate",
+        }
+    }
+}
+
+// Picks the best supported encoding from an Accept-Encoding header (gzip preferred on ties)
+pub fn negotiate(accept_encoding: Option<&str>) -> Option<ContentEncoding> {
+    let mut best: Option<(ContentEncoding, f32)> = None;
+    for item in accept_encoding?.split(',') {
+        let mut parts = item.split(';').map(str::trim);
+        let name = parts.next().unwrap_or("").to_lowercase();
+        let quality = parts
+            .find_map(|param| param.strip_prefix("q="))
+            .and_then(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        let encoding = match name.as_str() {
+            "gzip" | "*" => ContentEncoding::Gzip,
+            "deflate" => ContentEncoding::Deflate,
+            _ => continue,
+        };
+        if quality <= 0.0 {
+            continue;
+        }
+        let better = match best {
+            None => true,
+            Some((current, current_q)) => quality > current_q
+                || (quality == current_q && encoding == ContentEncoding::Gzip && current != ContentEncoding::Gzip),
+        };
+        if better {
+            best = Some((encoding, quality));
+        }
+    }
+    best.map(|(encoding, _)| encoding)
+}
+
+pub fn compress(body: &[u8], encoding: ContentEncoding) -> Vec<u8> {
+    // Writing into a Vec can't fail, so the io::Results are safe to unwrap
+    match encoding {
+        ContentEncoding::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body).unwrap();
+            encoder.finish().unwrap()
+        }
+        ContentEncoding::Deflate => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body).unwrap();
+            encoder.finish().unwrap()
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use crate::ranking;
+use crate::repository::{self, UserRepo};
+use crate::state;
+use crate::types::{Environment, InitOrUpgradeArgs, UserProfile};
+use candid::Principal;
+
+// --- Deployment Environment ---
+// Install and upgrade take an optional `InitOrUpgradeArgs` naming the environment, which is kept
+// in stable memory so an upgrade without args keeps it (a canister that never got one is prod).
+// It switches defaults: dev makes no LLM outcalls (dares come from the stored pool), API key
+// rate limits are relaxed outside prod, and endpoints that fabricate state refuse to run in prod.
+
+pub const MAX_SIMULATED_USERS: u32 = 1_000;
+const SIMULATED_PRINCIPAL_TAG: [u8; 2] = [0xDA, 0x5E];
+const MAX_SIMULATED_STREAK: u64 = 40;
+
+impl Environment {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Environment::Dev => "dev",
+            Environment::Staging => "staging",
+            Environment::Prod => "prod",
+        }
+    }
+}
+
+pub fn current() -> Environment {
+    state::ENVIRONMENT.with(|e| *e.borrow().get())
+}
+
+// Called from init and post_upgrade
+pub fn apply(args: Option<InitOrUpgradeArgs>) {
+    let Some(environment) = args.and_then(|args| args.environment) else { return };
+    state::ENVIRONMENT.with(|e| e.borrow_mut().set(environment).expect("Failed to write stable environment"));
+}
+
+pub fn llm_enabled() -> bool {
+    current() != Environment::Dev
+}
+
+// API key limits are multiplied by this, so load tests don't need keys with prod-sized limits
+pub fn rate_limit_multiplier() -> u32 {
+    match current() {
+        Environment::Dev => 100,
+        Environment::Staging => 10,
+        Environment::Prod => 1,
+    }
+}
+
+// Guards admin tools that write synthetic data
+pub fn ensure_non_prod(action: &str) -> Result<(), String> {
+    if current() == Environment::Prod {
+        return Err(format!("{} is only available in dev and staging.", action));
+    }
+    Ok(())
+}
+
+// --- Simulated Users ---
+
+fn simulated_principal(seq: u64) -> Principal {
+    let mut bytes = SIMULATED_PRINCIPAL_TAG.to_vec();
+    bytes.extend_from_slice(&seq.to_be_bytes());
+    Principal::from_slice(&bytes)
+}
+
+// Registers `count` synthetic users with spread-out streaks, e.g. to load-test the leaderboard.
+// Returns how many were added.
+pub fn simulate_users(count: u32) -> Result<u32, String> {
+    ensure_non_prod("simulate_users")?;
+    if count == 0 || count > MAX_SIMULATED_USERS {
+        return Err(format!("count must be between 1 and {}.", MAX_SIMULATED_USERS));
+    }
+    let users = repository::users();
+    let mut seq = users.len();
+    let mut roll = ic_cdk::api::time();
+    let mut added = 0;
+    while added < count {
+        let user = simulated_principal(seq);
+        seq += 1;
+        if users.get(user).is_some() {
+            continue;
+        }
+        roll = roll.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        let mut profile = UserProfile::default();
+        ranking::set_streak(&mut profile, ((roll >> 33) % (MAX_SIMULATED_STREAK + 1)) as u32);
+        users.put(user, profile);
+        added += 1;
+    }
+    Ok(added)
+}
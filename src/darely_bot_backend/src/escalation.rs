@@ -0,0 +1,257 @@
+use crate::blocklist;
+use crate::exclusions;
+use crate::hardcore;
+use crate::history;
+use crate::outbox;
+use crate::randomness;
+use crate::repository::{self, DareRepo, UserRepo};
+use crate::selection;
+use crate::state;
+use crate::submissions;
+use crate::types::{CompositeKey, Dare, DareSource, Difficulty, Escalation, HistoryEvent, StorablePrincipal, UserProfile};
+use candid::Principal;
+
+// --- Escalation Ladder ---
+// Completing a dare offers a harder follow-up: /escalate within OFFER_HOURS hands out a curated
+// dare one difficulty up (Hard stays Hard), and submitting it within another OFFER_HOURS pays its
+// XP times the rung's multiplier and offers the next rung, up to MAX_RUNG. Declining is free.
+// Missing the deadline only loses the bonus: the follow-up stays active as a normal dare, and the
+// timer tells the player. The bonus is stored on the submission, so an undo or a rejection takes
+// it back along with DARE_XP. An undo also drops the offer its submission made.
+//
+// The timer reads only the profiles that are due: ESCALATION_INDEX holds every open offer and
+// taken escalation under its deadline, kept in step by every profile write. A /done claimed in
+// time keeps its entry due until the proof arrives or the claim lapses.
+
+const OFFER_HOURS: u64 = 2;
+const OFFER_NANOS: u64 = OFFER_HOURS * 60 * 60 * 1_000_000_000;
+const MAX_RUNG: u32 = 3;
+
+fn multiplier(rung: u32) -> u64 {
+    rung as u64 + 1
+}
+
+fn harder(difficulty: &Difficulty) -> Difficulty {
+    match difficulty {
+        Difficulty::Easy => Difficulty::Medium,
+        Difficulty::Medium | Difficulty::Hard => Difficulty::Hard,
+    }
+}
+
+// The rung a submission of `dare_id` finishes, if any, and the offer that follows it. Only
+// submissions of a known dare get an offer.
+fn advance(current: Option<&Escalation>, dare_id: Option<u64>, difficulty: Option<&Difficulty>, completed_at: u64, now: u64) -> (Option<u32>, Option<Escalation>) {
+    let completed = current
+        .filter(|escalation| escalation.dare_id.is_some() && escalation.dare_id == dare_id && completed_at <= escalation.deadline)
+        .map(|escalation| escalation.rung);
+    let rung = completed.unwrap_or(0) + 1;
+    let offer = difficulty.filter(|_| dare_id.is_some() && rung <= MAX_RUNG).map(|difficulty| Escalation {
+        rung,
+        difficulty: harder(difficulty),
+        deadline: now + OFFER_NANOS,
+        dare_id: None,
+    });
+    (completed, offer)
+}
+
+// Called by record_submission inside its profile update; `completed_at` is the /done moment when
+// the proof came later. Returns the bonus XP earned, if any.
+pub fn settle(profile: &mut UserProfile, dare_id: Option<u64>, completed_at: u64, now: u64) -> Option<(u32, u64)> {
+    let difficulty = dare_id.and_then(|id| repository::dares().get(id)).map(|dare| dare.difficulty);
+    let (completed, offer) = advance(profile.escalation.as_ref(), dare_id, difficulty.as_ref(), completed_at, now);
+    profile.escalation = offer;
+    let rung = completed?;
+    let bonus_xp = submissions::DARE_XP * (multiplier(rung) - 1);
+    profile.xp = Some(profile.xp.unwrap_or(0) + bonus_xp);
+    Some((rung, bonus_xp))
+}
+
+// Appended to the submission reply: the bonus just earned and the offer that follows
+pub fn submission_note(user: Principal) -> String {
+    let Some(profile) = state::get_profile(user) else { return String::new() };
+    let mut note = String::new();
+    let bonus = profile.last_submission_id
+        .and_then(|id| state::SUBMISSIONS.with(|s| s.borrow().get(&id)))
+        .and_then(|submission| submission.escalation_xp);
+    if let Some(bonus_xp) = bonus {
+        note.push_str(&format!("\n🔥 Escalation complete: +{} bonus XP.", bonus_xp));
+    }
+    if let Some(offer) = profile.escalation.filter(|offer| offer.dare_id.is_none()) {
+        note.push_str(&format!(
+            "\n⬆️ Want to escalate? /escalate within {} hours for a {:?} dare worth x{} XP, or /escalate decline.",
+            OFFER_HOURS, offer.difficulty, multiplier(offer.rung)
+        ));
+    }
+    note
+}
+
+fn pick(profile: &UserProfile, difficulty: &Difficulty, chat_id: Option<&str>) -> Option<Dare> {
+    let blocklist = blocklist::Blocklist::of(profile);
+    let disabled = exclusions::disabled_in(chat_id);
+    let mut candidates = selection::candidates(difficulty, Some(DareSource::Curated), profile);
+    candidates.retain(|dare| !blocklist.blocks(dare) && !disabled.contains(&dare.id) && Some(dare.id) != profile.current_dare_id);
+    selection::pick_weighted(&candidates, randomness::next_u64()).cloned()
+}
+
+// /escalate: takes the offered follow-up
+pub fn accept(user: Principal, chat_id: Option<&str>) -> Result<String, String> {
+    let now = ic_cdk::api::time();
+    let profile = state::get_profile(user).ok_or_else(|| "User not found. Please /register first.".to_string())?;
+    hardcore::check_penalty(&profile)?;
+    submissions::check_claim(&profile, now)?;
+    let offer = match &profile.escalation {
+        Some(escalation) if escalation.dare_id.is_some() => return Err("You're already on an escalation; submit it first.".to_string()),
+        Some(escalation) if now <= escalation.deadline => escalation.clone(),
+        _ => return Err("No escalation on offer. Complete a dare to get one.".to_string()),
+    };
+    if profile.current_dare_id.is_some() {
+        return Err("Submit your current dare first.".to_string());
+    }
+    let dare = pick(&profile, &offer.difficulty, chat_id)
+        .ok_or_else(|| format!("No {:?} dare is available for you right now.", offer.difficulty))?;
+    let dare_id = dare.id;
+    let text = crate::assign_stored_dare(&StorablePrincipal(user), dare, chat_id);
+    state::update_profile(user, |profile| {
+        profile.escalation = Some(Escalation { dare_id: Some(dare_id), deadline: now + OFFER_NANOS, ..offer.clone() });
+        Ok(())
+    })?;
+    Ok(format!(
+        "⬆️ Escalation {}: {}\nSubmit it within {} hours for x{} XP. Too late and it's still a normal dare.",
+        offer.rung, text, OFFER_HOURS, multiplier(offer.rung)
+    ))
+}
+
+// /escalate decline: drops the offer, or the bonus of a taken escalation
+pub fn decline(user: Principal) -> Result<String, String> {
+    let escalation = state::update_profile(user, |profile| Ok(profile.escalation.take()))?
+        .ok_or_else(|| "You have no escalation to decline.".to_string())?;
+    Ok(match escalation.dare_id {
+        Some(_) => "Escalation dropped. Your dare stays active as a normal dare.".to_string(),
+        None => "Escalation declined. No harm done.".to_string(),
+    })
+}
+
+// --- Timer ---
+
+// A /done claimed in time holds the escalation open while the proof is on its way
+fn expired(profile: &UserProfile, now: u64) -> bool {
+    let claimed_at = submissions::claimed_at(profile, now).unwrap_or(now);
+    profile.escalation.as_ref().is_some_and(|escalation| now > escalation.deadline && claimed_at > escalation.deadline)
+}
+
+// Drops offers and escalations past their deadline, telling players who had taken one. Returns
+// how many accepted escalations lapsed.
+pub fn run() -> u32 {
+    let now = ic_cdk::api::time();
+    let due: Vec<(u64, Principal)> = state::ESCALATION_INDEX.with(|i| {
+        i.borrow().iter().take_while(|(key, _)| key.0 < now).map(|(key, _)| (key.0, key.1 .0)).collect()
+    });
+    let mut lapsed = 0;
+    for (deadline_at, user) in due {
+        // Entries left behind by a profile that was quarantined
+        if state::get_profile(user).and_then(|profile| deadline(&profile)) != Some(deadline_at) {
+            state::ESCALATION_INDEX.with(|i| i.borrow_mut().remove(&CompositeKey(deadline_at, StorablePrincipal(user))));
+            continue;
+        }
+        let taken = state::update_profile(user, |profile| {
+            if !expired(profile, now) {
+                return Ok(None);
+            }
+            Ok(profile.escalation.take().filter(|escalation| escalation.dare_id.is_some()))
+        });
+        if let Ok(Some(escalation)) = taken {
+            lapsed += 1;
+            history::log_event(user, HistoryEvent::EscalationLapsed { rung: escalation.rung });
+            outbox::queue(user, format!(
+                "⏰ Your escalation ran out, so the x{} bonus is gone. The dare is still yours as a normal dare.",
+                multiplier(escalation.rung)
+            ), None);
+        }
+    }
+    lapsed
+}
+
+// --- Escalation Index ---
+
+// The index key's time for a profile with an escalation
+pub fn deadline(profile: &UserProfile) -> Option<u64> {
+    profile.escalation.as_ref().map(|escalation| escalation.deadline)
+}
+
+// Keeps the index in step with a profile write; `before` is None for a profile without one
+pub fn reindex(user: Principal, before: Option<u64>, after: Option<u64>) {
+    if before == after {
+        return;
+    }
+    state::ESCALATION_INDEX.with(|i| {
+        let mut index = i.borrow_mut();
+        if let Some(deadline) = before {
+            index.remove(&CompositeKey(deadline, StorablePrincipal(user)));
+        }
+        if let Some(deadline) = after {
+            index.insert(CompositeKey(deadline, StorablePrincipal(user)), ());
+        }
+    });
+}
+
+// Indexes every profile when the index is empty (run on init and post_upgrade)
+pub fn rebuild_index() {
+    if state::ESCALATION_INDEX.with(|i| !i.borrow().is_empty()) {
+        return;
+    }
+    let mut entries = Vec::new();
+    repository::users().scan(None, &mut |user, profile| {
+        if let Some(deadline) = deadline(&profile) {
+            entries.push(CompositeKey(deadline, StorablePrincipal(user)));
+        }
+        true
+    });
+    state::ESCALATION_INDEX.with(|i| {
+        let mut index = i.borrow_mut();
+        for key in entries {
+            index.insert(key, ());
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_000 * OFFER_NANOS;
+
+    fn taken(rung: u32, dare_id: u64) -> Escalation {
+        Escalation { rung, difficulty: Difficulty::Hard, deadline: NOW + 1, dare_id: Some(dare_id) }
+    }
+
+    #[test]
+    fn completions_climb_the_ladder() {
+        let (completed, offer) = advance(None, Some(1), Some(&Difficulty::Easy), NOW, NOW);
+        assert_eq!(completed, None);
+        assert_eq!(offer, Some(Escalation { rung: 1, difficulty: Difficulty::Medium, deadline: NOW + OFFER_NANOS, dare_id: None }));
+
+        let (completed, offer) = advance(Some(&taken(1, 7)), Some(7), Some(&Difficulty::Hard), NOW, NOW);
+        assert_eq!(completed, Some(1));
+        assert_eq!(offer.map(|offer| (offer.rung, offer.difficulty)), Some((2, Difficulty::Hard)));
+
+        let (completed, offer) = advance(Some(&taken(MAX_RUNG, 7)), Some(7), Some(&Difficulty::Hard), NOW, NOW);
+        assert_eq!((completed, offer), (Some(MAX_RUNG), None));
+    }
+
+    #[test]
+    fn late_or_other_dares_only_lose_the_bonus() {
+        let late = Escalation { deadline: NOW - 1, ..taken(2, 7) };
+        let (completed, offer) = advance(Some(&late), Some(7), Some(&Difficulty::Hard), NOW, NOW);
+        assert_eq!(completed, None);
+        assert_eq!(offer.map(|offer| offer.rung), Some(1));
+
+        // Proof that arrives late still counts when /done was claimed before the deadline
+        let (completed, _) = advance(Some(&late), Some(7), Some(&Difficulty::Hard), NOW - 2, NOW);
+        assert_eq!(completed, Some(2));
+
+        let (completed, _) = advance(Some(&taken(2, 7)), Some(8), Some(&Difficulty::Hard), NOW, NOW);
+        assert_eq!(completed, None);
+        assert_eq!(advance(Some(&taken(2, 7)), None, None, NOW, NOW), (None, None));
+        assert_eq!(multiplier(1), 2);
+    }
+}
@@ -0,0 +1,259 @@
+use crate::outbox;
+use crate::state::{self, KeyPart};
+use crate::themes;
+use crate::types::{CompositeKey, GlobalEvent, StorablePrincipal, Submission};
+use candid::Principal;
+use std::cell::Cell;
+use std::ops::Bound;
+use std::time::Duration;
+
+// --- Global Events ---
+// Community-wide goals ("Complete 1,000 dares this week") with one counter shared by every chat.
+// Each dare submitted while an event runs counts once towards it when it is approved, and the
+// submitter becomes a participant. Progress is announced in the chats subscribed to announcements
+// as it passes 25%, 50%, 75% and 100%. Once the target is reached, the event timer hands the event
+// badge ("event-<id>", see achievements.rs) to every participant, BADGE_BATCH at a time with a
+// cursor in between; anyone who counts towards it afterwards, until the event ends, gets it right
+// away. When an event ends short of its target the timer says so and no badge is given. A
+// cancelled event that already reached its target still gets its badges before it closes.
+
+const MAX_TITLE_LEN: usize = 100;
+const MAX_DURATION_DAYS: u32 = 90;
+const MAX_LEAD_DAYS: u64 = 365;
+const BADGE_BATCH: usize = 500;
+const MAX_TARGET: u64 = 10_000_000;
+const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+const THRESHOLDS: [u8; 4] = [25, 50, 75, 100];
+const BAR_WIDTH: u64 = 10;
+
+pub fn badge_id(event_id: u64) -> String {
+    format!("event-{}", event_id)
+}
+
+fn percent(event: &GlobalEvent) -> u8 {
+    (event.progress.min(event.target) * 100 / event.target.max(1)) as u8
+}
+
+// "▓▓▓▓░░░░░░ 412/1000 (41%)"
+pub fn progress_bar(progress: u64, target: u64) -> String {
+    let filled = (progress.min(target) * BAR_WIDTH / target.max(1)) as usize;
+    format!(
+        "{}{} {}/{} ({}%)",
+        "▓".repeat(filled), "░".repeat(BAR_WIDTH as usize - filled), progress, target, progress.min(target) * 100 / target.max(1)
+    )
+}
+
+fn save(event: GlobalEvent) {
+    state::GLOBAL_EVENTS.with(|e| e.borrow_mut().insert(event.id, event));
+}
+
+fn announce(text: String) {
+    for chat_id in themes::subscribed_chats() {
+        outbox::queue_chat(&chat_id, text.clone());
+    }
+}
+
+// --- Admin ---
+
+pub fn create(title: String, target: u64, starts_at: Option<u64>, duration_days: u32, admin: Principal) -> Result<u64, String> {
+    let title = title.trim().to_string();
+    if title.is_empty() || title.len() > MAX_TITLE_LEN {
+        return Err(format!("Event title must be 1-{} characters.", MAX_TITLE_LEN));
+    }
+    if target == 0 || target > MAX_TARGET {
+        return Err(format!("Event target must be 1-{} dares.", MAX_TARGET));
+    }
+    if duration_days == 0 || duration_days > MAX_DURATION_DAYS {
+        return Err(format!("Events can last 1-{} days.", MAX_DURATION_DAYS));
+    }
+    let now = ic_cdk::api::time();
+    let starts_at = starts_at.unwrap_or(now).max(now);
+    if starts_at - now > MAX_LEAD_DAYS * DAY_NANOS {
+        return Err(format!("Events can start at most {} days ahead.", MAX_LEAD_DAYS));
+    }
+    state::GLOBAL_EVENTS.with(|events_ref| {
+        let mut events = events_ref.borrow_mut();
+        let id = events.last_key_value().map_or(0, |(id, _)| id + 1);
+        events.insert(id, GlobalEvent {
+            id,
+            title,
+            target,
+            starts_at,
+            ends_at: starts_at + duration_days as u64 * DAY_NANOS,
+            progress: 0,
+            participants: 0,
+            announced_percent: 0,
+            succeeded_at: None,
+            closed: false,
+            created_by: admin,
+            badge_cursor: None,
+        });
+        Ok(id)
+    })
+}
+
+// Ends an event early; badges already handed out are kept, and a reached target still gets its
+// badges from the timer before the event closes
+pub fn cancel(event_id: u64) -> Result<(), String> {
+    let now = ic_cdk::api::time();
+    let mut event = state::GLOBAL_EVENTS.with(|e| e.borrow().get(&event_id))
+        .ok_or_else(|| format!("Event {} not found.", event_id))?;
+    if event.closed || event.ends_at <= now {
+        return Err(format!("Event {} has already ended.", event_id));
+    }
+    event.ends_at = now;
+    if event.progress < event.target || event.succeeded_at.is_some() {
+        event.closed = true;
+    }
+    save(event);
+    Ok(())
+}
+
+pub fn list() -> Vec<GlobalEvent> {
+    state::GLOBAL_EVENTS.with(|e| e.borrow().iter().map(|(_, event)| event).collect())
+}
+
+fn running(now: u64) -> Vec<GlobalEvent> {
+    list().into_iter().filter(|event| !event.closed && event.starts_at <= now && now < event.ends_at).collect()
+}
+
+// /event: progress of the running events
+pub fn describe(user: Principal) -> String {
+    let now = ic_cdk::api::time();
+    let events = running(now);
+    if events.is_empty() {
+        return "No global event is running right now.".to_string();
+    }
+    events.iter().map(|event| {
+        let contributed = state::EVENT_PARTICIPANTS.with(|p| p.borrow().get(&CompositeKey(event.id, StorablePrincipal(user)))).unwrap_or(0);
+        let days_left = (event.ends_at - now).div_ceil(DAY_NANOS);
+        format!(
+            "🌍 {}\n{}\n{} participants, {} day(s) left. Your dares: {}.",
+            event.title, progress_bar(event.progress, event.target), event.participants, days_left, contributed
+        )
+    }).collect::<Vec<_>>().join("\n\n")
+}
+
+// --- Progress ---
+
+fn grant_badge(user: Principal, event_id: u64) {
+    let _ = state::update_profile(user, |profile| {
+        let badges = profile.event_badges.get_or_insert_with(Vec::new);
+        if !badges.contains(&event_id) {
+            badges.push(event_id);
+        }
+        Ok(())
+    });
+}
+
+// Called on every approval (including granted appeals); counts for the events that were running
+// when the dare was submitted and haven't closed since
+pub fn note_completion(submission: &Submission) {
+    if submission.reward_task_id.is_some() {
+        return;
+    }
+    let (user, submitted_at) = (submission.user, submission.submitted_at);
+    let counting = list().into_iter()
+        .filter(|event| !event.closed && event.starts_at <= submitted_at && submitted_at < event.ends_at);
+    for mut event in counting {
+        let key = CompositeKey(event.id, StorablePrincipal(user));
+        let contributed = state::EVENT_PARTICIPANTS.with(|p| p.borrow().get(&key));
+        if contributed.is_none() {
+            event.participants += 1;
+        }
+        state::EVENT_PARTICIPANTS.with(|p| p.borrow_mut().insert(key, contributed.unwrap_or(0) + 1));
+        event.progress += 1;
+        // Participants from before the target was reached are left to the timer's batches
+        if event.progress >= event.target {
+            grant_badge(user, event.id);
+        }
+        let reached = THRESHOLDS.into_iter().filter(|&threshold| percent(&event) >= threshold).max();
+        if let Some(threshold) = reached.filter(|&threshold| threshold > event.announced_percent) {
+            event.announced_percent = threshold;
+            announce(if threshold == 100 {
+                format!("🎉 Goal reached: {}! {}. Every participant earns the event badge.", event.title, progress_bar(event.progress, event.target))
+            } else {
+                format!("🌍 {}: {}% there! {}", event.title, threshold, progress_bar(event.progress, event.target))
+            });
+        }
+        save(event);
+    }
+}
+
+thread_local! {
+    // A follow-up batch is already scheduled
+    static BATCH_SCHEDULED: Cell<bool> = const { Cell::new(false) };
+}
+
+// Hands the badge to the next BADGE_BATCH participants; returns whether everyone has it now
+fn grant_batch(event: &mut GlobalEvent) -> bool {
+    let start = match event.badge_cursor {
+        Some(after) => Bound::Excluded(CompositeKey(event.id, StorablePrincipal(after))),
+        None => Bound::Included(CompositeKey(event.id, StorablePrincipal::min_value())),
+    };
+    let batch: Vec<Principal> = state::EVENT_PARTICIPANTS.with(|p| p.borrow()
+        .range((start, Bound::Unbounded))
+        .take_while(|(key, _)| key.0 == event.id)
+        .take(BADGE_BATCH)
+        .map(|(key, _)| key.1 .0)
+        .collect());
+    for &user in &batch {
+        grant_badge(user, event.id);
+    }
+    event.badge_cursor = batch.last().copied();
+    batch.len() < BADGE_BATCH
+}
+
+// Timer: hands out badges for reached targets and closes ended events. Returns how many events changed.
+pub fn run() -> u32 {
+    let now = ic_cdk::api::time();
+    let mut changed = 0;
+    let mut granting = false;
+    for mut event in list().into_iter().filter(|event| !event.closed) {
+        let succeeded = event.progress >= event.target;
+        let ended = now >= event.ends_at;
+        if !ended && (!succeeded || event.succeeded_at.is_some()) {
+            continue;
+        }
+        if succeeded && event.succeeded_at.is_none() {
+            if !grant_batch(&mut event) {
+                save(event);
+                granting = true;
+                continue;
+            }
+            event.badge_cursor = None;
+            event.succeeded_at = Some(now);
+        }
+        if ended {
+            if !succeeded {
+                announce(format!(
+                    "⌛ {} has ended at {}. So close, thanks to all {} participants!",
+                    event.title, progress_bar(event.progress, event.target), event.participants
+                ));
+            }
+            event.closed = true;
+        }
+        save(event);
+        changed += 1;
+    }
+    if granting && !BATCH_SCHEDULED.get() {
+        BATCH_SCHEDULED.set(true);
+        ic_cdk_timers::set_timer(Duration::ZERO, || {
+            BATCH_SCHEDULED.set(false);
+            run();
+        });
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_bar_fills_and_caps() {
+        assert_eq!(progress_bar(0, 1000), "░░░░░░░░░░ 0/1000 (0%)");
+        assert_eq!(progress_bar(412, 1000), "▓▓▓▓░░░░░░ 412/1000 (41%)");
+        assert_eq!(progress_bar(1200, 1000), "▓▓▓▓▓▓▓▓▓▓ 1200/1000 (100%)");
+    }
+}
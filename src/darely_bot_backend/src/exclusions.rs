@@ -0,0 +1,60 @@
+use crate::repository::{self, DareRepo};
+use crate::state;
+use crate::types::CompositeKey;
+use std::collections::BTreeSet;
+
+// --- Dare Exclusions ---
+// Admins can take a dare out of selection without deleting it, either everywhere or in one chat
+// (e.g. a weather-dependent dare, or one that doesn't suit a particular group). Entries are keyed
+// by (chat id, dare id) with the empty chat id standing for "all chats"; dare_for drops every
+// excluded dare from its candidates, including pinned ones.
+
+const ALL_CHATS: &str = "";
+
+fn key(dare_id: u64, chat_id: Option<&str>) -> CompositeKey<String, u64> {
+    CompositeKey(chat_id.unwrap_or(ALL_CHATS).to_string(), dare_id)
+}
+
+fn validate_chat(chat_id: Option<&str>) -> Result<(), String> {
+    if chat_id.is_some_and(|chat_id| chat_id.trim().is_empty()) {
+        return Err("The chat id cannot be empty; pass null to disable the dare everywhere.".to_string());
+    }
+    Ok(())
+}
+
+// Returns false when the dare was already disabled there
+pub fn disable(dare_id: u64, chat_id: Option<&str>) -> Result<bool, String> {
+    validate_chat(chat_id)?;
+    if repository::dares().get(dare_id).is_none() {
+        return Err(format!("Dare #{} not found.", dare_id));
+    }
+    let previous = state::DISABLED_DARES.with(|d| d.borrow_mut().insert(key(dare_id, chat_id), ic_cdk::api::time()));
+    Ok(previous.is_none())
+}
+
+// Returns false when the dare wasn't disabled there
+pub fn enable(dare_id: u64, chat_id: Option<&str>) -> Result<bool, String> {
+    validate_chat(chat_id)?;
+    Ok(state::DISABLED_DARES.with(|d| d.borrow_mut().remove(&key(dare_id, chat_id))).is_some())
+}
+
+// Dares excluded in the chat: the global list plus the chat's own
+pub fn disabled_in(chat_id: Option<&str>) -> BTreeSet<u64> {
+    state::DISABLED_DARES.with(|d| {
+        let disabled = d.borrow();
+        let mut dare_ids: BTreeSet<u64> = state::range_by_first(&disabled, &ALL_CHATS.to_string()).into_iter().map(|(id, _)| id).collect();
+        if let Some(chat_id) = chat_id.filter(|chat_id| !chat_id.is_empty()) {
+            dare_ids.extend(state::range_by_first(&disabled, &chat_id.to_string()).into_iter().map(|(id, _)| id));
+        }
+        dare_ids
+    })
+}
+
+// (chat id or None for all chats, dare id, disabled at)
+pub fn list() -> Vec<(Option<String>, u64, u64)> {
+    state::DISABLED_DARES.with(|d| {
+        d.borrow().iter()
+            .map(|(key, disabled_at)| ((!key.0.is_empty()).then_some(key.0), key.1, disabled_at))
+            .collect()
+    })
+}
@@ -0,0 +1,146 @@
+use crate::state;
+use crate::types::{CohortStats, FeatureRollout};
+use candid::Principal;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+
+// --- Feature Rollouts ---
+// Risky features can be switched on for a share of users first. Each user falls into a bucket
+// from 0 to 99, hashed from the feature name and their principal (so the cohorts of different
+// features don't line up), and gets the feature when the bucket is below the rollout percent.
+// A feature without a rollout is on for everyone. Per feature, exposures (times the feature was
+// checked for a user) and completions (dares submitted) are counted separately for the enabled
+// and the disabled cohort; changing the percent starts the counts over.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Feature {
+    LlmDares, // Fresh dares from the LLM; without it users only get stored dares
+    RatingSurvey, // The difficulty/fun survey after an approval (when the survey is on at all)
+}
+
+impl Feature {
+    pub const ALL: [Feature; 2] = [Feature::LlmDares, Feature::RatingSurvey];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::LlmDares => "llm_dares",
+            Feature::RatingSurvey => "rating_survey",
+        }
+    }
+}
+
+pub fn find(name: &str) -> Option<Feature> {
+    Feature::ALL.into_iter().find(|feature| feature.name() == name.trim())
+}
+
+// Checked by config.rs: rollouts must name known features, each at most once, with 0-100 percent
+pub fn validate_rollouts(rollouts: &[(String, u8)]) -> Result<(), String> {
+    let mut seen = BTreeSet::new();
+    for (name, percent) in rollouts {
+        if find(name).is_none() {
+            return Err(format!("Unknown feature '{}'.", name));
+        }
+        if !seen.insert(name) {
+            return Err(format!("'{}' is listed more than once.", name));
+        }
+        if *percent > 100 {
+            return Err(format!("'{}': the percent can be at most 100.", name));
+        }
+    }
+    Ok(())
+}
+
+// Stores a rollout; None removes it (the feature is then on for everyone)
+pub fn set_rollout(rollouts: &mut Option<Vec<(String, u8)>>, feature: Feature, percent: Option<u8>) {
+    let mut list = rollouts.take().unwrap_or_default();
+    list.retain(|(name, _)| name != feature.name());
+    if let Some(percent) = percent {
+        list.push((feature.name().to_string(), percent));
+    }
+    *rollouts = (!list.is_empty()).then_some(list);
+}
+
+fn percent(feature: Feature) -> u8 {
+    state::get_config().feature_rollouts
+        .and_then(|rollouts| rollouts.into_iter().find(|(name, _)| name == feature.name()))
+        .map_or(100, |(_, percent)| percent)
+}
+
+fn bucket(feature: Feature, user: Principal) -> u8 {
+    let digest = Sha256::new().chain_update(feature.name()).chain_update(user.as_slice()).finalize();
+    (u64::from_be_bytes(digest[..8].try_into().unwrap()) % 100) as u8
+}
+
+fn in_rollout(feature: Feature, user: Principal) -> bool {
+    bucket(feature, user) < percent(feature)
+}
+
+fn count(feature: Feature, enabled: bool, f: impl FnOnce(&mut CohortStats)) {
+    state::FEATURE_METRICS.with(|m| {
+        let mut metrics = m.borrow_mut();
+        let mut entry = metrics.get(&feature.name().to_string()).unwrap_or_default();
+        if entry.since == 0 {
+            entry.since = ic_cdk::api::time();
+        }
+        f(if enabled { &mut entry.enabled } else { &mut entry.disabled });
+        metrics.insert(feature.name().to_string(), entry);
+    });
+}
+
+// Whether `user` gets the feature; counts an exposure in their cohort
+pub fn enabled(feature: Feature, user: Principal) -> bool {
+    let enabled = in_rollout(feature, user);
+    count(feature, enabled, |stats| stats.exposures += 1);
+    enabled
+}
+
+// Called when a dare is submitted: one completion in the user's cohort of every feature
+pub fn note_completion(user: Principal) {
+    for feature in Feature::ALL {
+        count(feature, in_rollout(feature, user), |stats| stats.completions += 1);
+    }
+}
+
+// Called after a rollout change, whose cohorts can't be compared with the earlier ones
+pub fn reset_metrics(feature: Feature) {
+    state::FEATURE_METRICS.with(|m| m.borrow_mut().remove(&feature.name().to_string()));
+}
+
+pub fn report() -> Vec<FeatureRollout> {
+    Feature::ALL.iter().map(|&feature| {
+        let metrics = state::FEATURE_METRICS.with(|m| m.borrow().get(&feature.name().to_string())).unwrap_or_default();
+        FeatureRollout { feature: feature.name().to_string(), percent: percent(feature), metrics }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_are_stable_and_differ_per_feature() {
+        let users: Vec<Principal> = (0u8..200).map(|i| Principal::from_slice(&[i, 7])).collect();
+        for &user in &users {
+            assert_eq!(bucket(Feature::LlmDares, user), bucket(Feature::LlmDares, user));
+            assert!(bucket(Feature::LlmDares, user) < 100);
+        }
+        assert!(users.iter().any(|&user| bucket(Feature::LlmDares, user) != bucket(Feature::RatingSurvey, user)));
+        let below_half = users.iter().filter(|&&user| bucket(Feature::LlmDares, user) < 50).count();
+        assert!((60..=140).contains(&below_half), "{}", below_half);
+    }
+
+    #[test]
+    fn rollouts_replace_and_validate() {
+        let mut rollouts = None;
+        set_rollout(&mut rollouts, Feature::LlmDares, Some(10));
+        set_rollout(&mut rollouts, Feature::LlmDares, Some(25));
+        assert_eq!(rollouts, Some(vec![("llm_dares".to_string(), 25)]));
+        set_rollout(&mut rollouts, Feature::LlmDares, None);
+        assert_eq!(rollouts, None);
+
+        assert!(validate_rollouts(&[("rating_survey".to_string(), 0)]).is_ok());
+        assert!(validate_rollouts(&[("rating_survey".to_string(), 101)]).is_err());
+        assert!(validate_rollouts(&[("duels".to_string(), 5)]).is_err());
+        assert!(validate_rollouts(&[("llm_dares".to_string(), 5), ("llm_dares".to_string(), 6)]).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+use crate::correlation;
+use crate::incidents;
+use crate::state;
+use candid::Principal;
+use ic_cdk::api::caller;
+use std::future::Future;
+
+// --- Response Formatting ---
+// Every text reply of the candid API passes through here. Users with accessibility mode on get
+// plain text: no emoji or markdown, and terse symbols ("#3", "~5", "70%") spelled out so screen
+// readers announce them sensibly. Everyone else gets the reply unchanged.
+//
+// Each reply helper also starts the request's correlation id (see correlation.rs); errors
+// returned to admins carry it. Unexpected errors are captured as incidents (see incidents.rs).
+
+fn wants_plain_text(user: Principal) -> bool {
+    state::get_profile(user)
+        .is_some_and(|profile| profile.plain_text == Some(true))
+}
+
+fn format_for(user: Principal, result: Result<String, String>) -> Result<String, String> {
+    let result = incidents::capture(result);
+    // Admins calling for themselves; the bot relays to chat users, who have no use for the ref
+    let admin = user == caller() && ic_cdk::api::is_controller(&user);
+    let result = result.map_err(|error| if admin { correlation::tag_error(error) } else { error });
+    if !wants_plain_text(user) {
+        return result;
+    }
+    result.map(|text| plain_text(&text)).map_err(|text| plain_text(&text))
+}
+
+// Formats a handler's reply for the caller
+pub fn reply_with(handler: impl FnOnce() -> Result<String, String>) -> Result<String, String> {
+    correlation::begin();
+    format_for(caller(), handler())
+}
+
+// Async variant; the caller is read before the handler runs
+pub async fn reply_async(handler: impl Future<Output = Result<String, String>>) -> Result<String, String> {
+    let user = caller();
+    correlation::begin();
+    format_for(user, correlation::traced(handler).await)
+}
+
+// Formats a handler's reply for the account a call acts for (see accounts.rs)
+pub fn reply_for(user: Principal, handler: impl FnOnce() -> Result<String, String>) -> Result<String, String> {
+    correlation::begin();
+    format_for(user, handler())
+}
+
+pub async fn reply_async_for(user: Principal, handler: impl Future<Output = Result<String, String>>) -> Result<String, String> {
+    correlation::begin();
+    format_for(user, correlation::traced(handler).await)
+}
+
+pub fn text(reply: String) -> String {
+    text_for(caller(), reply)
+}
+
+pub fn text_for(user: Principal, reply: String) -> String {
+    if wants_plain_text(user) { plain_text(&reply) } else { reply }
+}
+
+// Emoji, pictographs, and the invisible characters that glue them together
+fn is_emoji(c: char) -> bool {
+    matches!(c as u32,
+        0x1F000..=0x1FAFF // Emoticons, pictographs, flags, symbols
+        | 0x2600..=0x27BF // Misc symbols, dingbats
+        | 0x2B00..=0x2BFF // Arrows, stars
+        | 0xFE00..=0xFE0F // Variation selectors
+        | 0x200D // Zero-width joiner
+        | 0x20E3 // Combining keycap
+        | 0xE0020..=0xE007F // Tag characters
+    )
+}
+
+// Strips markdown markup from one line, keeping its text
+fn strip_markdown_line(line: &str) -> String {
+    let trimmed = line.trim_start();
+    let content = trimmed.trim_start_matches('#').trim_start_matches('>');
+    let content = if content.len() != trimmed.len() { content.trim_start() } else { content };
+    let content = content.strip_prefix("- ").or_else(|| content.strip_prefix("* ")).unwrap_or(content);
+
+    // [label](url) -> label (url)
+    let mut out = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find('[') {
+        let link = rest[start..].find("](").and_then(|mid| {
+            let end = rest[start + mid..].find(')')?;
+            Some((start + mid, start + mid + end))
+        });
+        match link {
+            Some((mid, end)) => {
+                out.push_str(&rest[..start]);
+                out.push_str(&rest[start + 1..mid]);
+                out.push_str(" (");
+                out.push_str(&rest[mid + 2..end]);
+                out.push(')');
+                rest = &rest[end + 1..];
+            }
+            None => break,
+        }
+    }
+    out.push_str(rest);
+    out.chars().filter(|&c| c != '*' && c != '`').collect()
+}
+
+// Spells out symbols that screen readers announce awkwardly
+fn describe_symbols(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    for (i, &c) in chars.iter().enumerate() {
+        let next_is_digit = chars.get(i + 1).is_some_and(|n| n.is_ascii_digit());
+        match c {
+            '#' if next_is_digit => out.push_str("number "),
+            '~' if next_is_digit => out.push_str("about "),
+            '%' => out.push_str(" percent"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+pub fn plain_text(text: &str) -> String {
+    let without_emoji: String = text.chars().filter(|&c| !is_emoji(c)).collect();
+    let lines: Vec<String> = without_emoji.lines()
+        .map(strip_markdown_line)
+        .map(|line| describe_symbols(&line))
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect();
+    lines.join("\n").trim().to_string()
+}
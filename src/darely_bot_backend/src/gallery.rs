@@ -0,0 +1,162 @@
+use crate::outbox;
+use crate::repository::{self, DareRepo};
+use crate::state;
+use crate::timezone;
+use crate::types::{CompositeKey, DigestSchedule, GalleryEntry};
+use candid::Principal;
+
+// --- Proof Gallery ---
+// Approved submissions are indexed per chat (the chat the dare was requested in), so /gallery can
+// show a group its latest completions: the dare, who did it and a taste of the proof.
+
+pub const DEFAULT_GALLERY_SIZE: usize = 5;
+pub const MAX_GALLERY_SIZE: usize = 20;
+const PROOF_SNIPPET_CHARS: usize = 120;
+const MAX_NICKNAME_LEN: usize = 24;
+
+pub fn index(chat_id: &str, submission_id: u64) {
+    state::CHAT_GALLERY.with(|g| g.borrow_mut().insert(CompositeKey(chat_id.to_string(), submission_id), ic_cdk::api::time()));
+}
+
+pub fn set_nickname(user: Principal, nickname: Option<String>) -> Result<(), String> {
+    let nickname = nickname.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+    if let Some(name) = nickname.as_deref() {
+        let allowed = |c: char| c.is_alphanumeric() || matches!(c, ' ' | '_' | '-' | '.');
+        if name.chars().count() > MAX_NICKNAME_LEN || !name.chars().all(allowed) {
+            return Err(format!("Nicknames are up to {} letters, digits, spaces, '_', '-' or '.'.", MAX_NICKNAME_LEN));
+        }
+    }
+    state::update_profile(user, |profile| {
+        profile.nickname = nickname.clone();
+        Ok(())
+    })
+}
+
+pub fn display_name(user: Principal) -> String {
+    state::get_profile(user)
+        .and_then(|profile| profile.nickname)
+        .unwrap_or_else(|| user.to_text().split('-').next().unwrap_or_default().to_string())
+}
+
+fn snippet(proof_parts: &[String]) -> String {
+    let mut words = proof_parts.iter().flat_map(|part| part.split_whitespace());
+    if let Some(link) = words.find(|word| word.starts_with("https://") || word.starts_with("http://")) {
+        return link.to_string();
+    }
+    let text = proof_parts.join(" ");
+    match text.char_indices().nth(PROOF_SNIPPET_CHARS) {
+        Some((cut, _)) => format!("{}...", &text[..cut]),
+        None => text,
+    }
+}
+
+// The chat's most recent approved submissions, newest first
+pub fn latest(chat_id: &str, count: usize) -> Vec<GalleryEntry> {
+    let range = CompositeKey(chat_id.to_string(), 0)..=CompositeKey(chat_id.to_string(), u64::MAX);
+    state::CHAT_GALLERY.with(|g| {
+        g.borrow().range(range).rev()
+            .filter_map(|(key, approved_at)| {
+                let submission = state::SUBMISSIONS.with(|s| s.borrow().get(&key.1))?;
+                let dare_text = submission.dare_text.clone().or_else(|| {
+                    submission.dare_id.and_then(|id| repository::dares().get(id)).map(|dare| dare.text)
+                });
+                Some(GalleryEntry {
+                    submission_id: key.1,
+                    submitter: display_name(submission.user),
+                    dare_text,
+                    proof: snippet(&submission.proof_parts),
+                    approved_at,
+                })
+            })
+            .take(count)
+            .collect()
+    })
+}
+
+pub fn render(entries: &[GalleryEntry]) -> String {
+    if entries.is_empty() {
+        return "No approved completions in this chat yet. Be the first: /get_dare".to_string();
+    }
+    let lines: Vec<String> = entries.iter().map(|entry| {
+        format!(
+            "🏅 {} completed \"{}\"\n   Proof: {}",
+            entry.submitter,
+            entry.dare_text.as_deref().unwrap_or("a dare"),
+            entry.proof
+        )
+    }).collect();
+    format!("Latest completions:\n{}", lines.join("\n"))
+}
+
+// --- Weekly Digest ---
+// A chat can have a digest posted once a week at a set UTC weekday and hour (chosen in /setup):
+// how many dares its members completed in the last seven days and the latest few. An hourly timer
+// posts it through the outbox, so paused chats are skipped.
+
+const WEEK_NANOS: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+const HOUR_NANOS: u64 = 60 * 60 * 1_000_000_000;
+const DIGEST_ENTRIES: usize = 3;
+
+pub fn validate_digest(schedule: &DigestSchedule) -> Result<(), String> {
+    if schedule.weekday > 6 || schedule.hour > 23 {
+        return Err("The digest needs a weekday from 0 (Sunday) to 6 and an hour from 0 to 23 UTC.".to_string());
+    }
+    Ok(())
+}
+
+// Whether the schedule's hour has come and no digest went out for it yet
+fn due(schedule: &DigestSchedule, sent_at: Option<u64>, now: u64) -> bool {
+    let days = (now / (24 * HOUR_NANOS)) as i64;
+    let hour = (now / HOUR_NANOS) % 24;
+    timezone::weekday(days) == schedule.weekday as i64
+        && hour == schedule.hour as u64
+        && sent_at.is_none_or(|at| now.saturating_sub(at) >= HOUR_NANOS)
+}
+
+fn digest(chat_id: &str, now: u64) -> String {
+    let range = CompositeKey(chat_id.to_string(), 0)..=CompositeKey(chat_id.to_string(), u64::MAX);
+    let completed = state::CHAT_GALLERY.with(|g| {
+        g.borrow().range(range).filter(|(_, approved_at)| now.saturating_sub(*approved_at) < WEEK_NANOS).count()
+    });
+    if completed == 0 {
+        return "📰 Weekly digest: no dares completed here this week. Who's first? /dare".to_string();
+    }
+    format!("📰 Weekly digest: {} dare(s) completed here this week.\n{}", completed, render(&latest(chat_id, DIGEST_ENTRIES)))
+}
+
+// Timer: posts the digests that are due. Returns how many were posted.
+pub fn run_digests() -> usize {
+    let now = ic_cdk::api::time();
+    let due_chats: Vec<String> = state::CHAT_CONFIGS.with(|c| c.borrow().iter()
+        .filter(|(_, config)| config.digest.is_some_and(|schedule| due(&schedule, config.digest_sent_at, now)))
+        .map(|(chat_id, _)| chat_id)
+        .collect());
+    for chat_id in &due_chats {
+        outbox::queue_chat(chat_id, digest(chat_id, now));
+        state::CHAT_CONFIGS.with(|c| {
+            let mut configs = c.borrow_mut();
+            if let Some(mut config) = configs.get(chat_id) {
+                config.digest_sent_at = Some(now);
+                configs.insert(chat_id.clone(), config);
+            }
+        });
+    }
+    due_chats.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digests_go_out_once_in_their_hour() {
+        // 2024-01-07 was a Sunday
+        let sunday_nine = (timezone::days_from_civil(2024, 1, 7) as u64 * 24 + 9) * HOUR_NANOS;
+        let schedule = DigestSchedule { weekday: 0, hour: 9 };
+        assert!(due(&schedule, None, sunday_nine));
+        assert!(due(&schedule, Some(sunday_nine - WEEK_NANOS), sunday_nine + 60));
+        assert!(!due(&schedule, Some(sunday_nine), sunday_nine + 60));
+        assert!(!due(&schedule, None, sunday_nine + HOUR_NANOS));
+        assert!(!due(&DigestSchedule { weekday: 1, hour: 9 }, None, sunday_nine));
+    }
+}
@@ -0,0 +1,148 @@
+use crate::blocklist;
+use crate::exclusions;
+use crate::history;
+use crate::outbox;
+use crate::randomness;
+use crate::ranking;
+use crate::repository::{self, DareRepo, UserRepo};
+use crate::selection;
+use crate::state;
+use crate::submissions;
+use crate::templates;
+use crate::types::{Dare, DareSource, Difficulty, HistoryEvent, UserProfile};
+use candid::Principal;
+
+// --- Hardcore Mode ---
+// Opt-in stakes for players who want them: going LAPSE_HOURS without submitting a dare resets the
+// streak and assigns a mandatory penalty dare, a Hard curated dare the player has unlocked (or
+// PENALTY_TEXT when there is none). /dare, and everything built on it, refuses to hand out
+// anything else until the penalty dare is submitted. A streak freeze from the reward wheel is
+// spent instead of lapsing, and restarts the clock. The clock starts when the player opts in,
+// and nobody can leave hardcore mode with a penalty open.
+
+const LAPSE_HOURS: u64 = 48;
+const LAPSE_NANOS: u64 = LAPSE_HOURS * 60 * 60 * 1_000_000_000;
+const PENALTY_TEXT: &str = "Penalty dare: do 50 burpees without stopping and post your proof.";
+
+pub fn set(user: Principal, enabled: bool) -> Result<String, String> {
+    let now = ic_cdk::api::time();
+    state::update_profile(user, |profile| {
+        if enabled {
+            if profile.hardcore_since.is_some() {
+                return Err("Hardcore mode is already on.".to_string());
+            }
+            profile.hardcore_since = Some(now);
+            return Ok(format!(
+                "💀 Hardcore mode is on. Go {} hours without a dare and your streak resets, with a penalty dare to finish before any other.",
+                LAPSE_HOURS
+            ));
+        }
+        if profile.penalty_since.is_some() {
+            return Err("Finish your penalty dare before leaving hardcore mode.".to_string());
+        }
+        if profile.hardcore_since.take().is_none() {
+            return Err("Hardcore mode is already off.".to_string());
+        }
+        Ok("Hardcore mode is off.".to_string())
+    })
+}
+
+fn current_dare_text(profile: &UserProfile) -> String {
+    profile.current_dare_text.clone()
+        .or_else(|| profile.current_dare_id.and_then(|id| repository::dares().get(id)).map(|dare| dare.text))
+        .unwrap_or_else(|| PENALTY_TEXT.to_string())
+}
+
+// Checked by dare_for, /undo and custom dares before anything is assigned
+pub fn check_penalty(profile: &UserProfile) -> Result<(), String> {
+    if profile.penalty_since.is_none() {
+        return Ok(());
+    }
+    Err(format!(
+        "💀 Your penalty dare comes first: {}\nSubmit it with /submit_dare to unlock normal dares.", current_dare_text(profile)
+    ))
+}
+
+// --- Timer ---
+
+// An open /done claim counts as activity until its proof window runs out
+fn last_active(profile: &UserProfile, now: u64) -> u64 {
+    let submitted = profile.last_submission_id
+        .and_then(|id| state::SUBMISSIONS.with(|s| s.borrow().get(&id)))
+        .map_or(0, |submission| submission.submitted_at);
+    let claimed = submissions::claimed_at(profile, now).unwrap_or(0);
+    submitted.max(claimed).max(profile.hardcore_since.unwrap_or(0)).max(profile.freeze_used_at.unwrap_or(0))
+}
+
+fn lapsed(profile: &UserProfile, now: u64) -> bool {
+    profile.hardcore_since.is_some() && profile.penalty_since.is_none() && now.saturating_sub(last_active(profile, now)) >= LAPSE_NANOS
+}
+
+fn pick_penalty(user: Principal, profile: &UserProfile) -> Option<Dare> {
+    let blocklist = blocklist::Blocklist::of(profile);
+    let disabled = exclusions::disabled_in(None);
+    let mut candidates = selection::candidates(&Difficulty::Hard, Some(DareSource::Curated), profile);
+    candidates.retain(|dare| !blocklist.blocks(dare) && !disabled.contains(&dare.id));
+    let dare = selection::pick_weighted(&candidates, randomness::next_u64()).cloned()?;
+    let rendered = templates::render_dare(&dare, user, None);
+    Some(Dare { text: rendered.unwrap_or(dare.text), ..dare })
+}
+
+enum Outcome {
+    Frozen { streak: u32, freezes_left: u32 },
+    Penalty { streak_lost: u32, dare_id: Option<u64>, text: String },
+}
+
+fn lapse(user: Principal, now: u64) -> Option<Outcome> {
+    let profile = state::get_profile(user).filter(|profile| lapsed(profile, now))?;
+    let penalty = pick_penalty(user, &profile);
+    let outcome = state::update_profile(user, |profile| {
+        if !lapsed(profile, now) {
+            return Ok(None);
+        }
+        if let Some(freezes) = profile.streak_freezes.filter(|&freezes| freezes > 0) {
+            profile.streak_freezes = Some(freezes - 1);
+            profile.freeze_used_at = Some(now);
+            return Ok(Some(Outcome::Frozen { streak: profile.streak, freezes_left: freezes - 1 }));
+        }
+        let streak_lost = profile.streak;
+        ranking::set_streak(profile, 0);
+        profile.penalty_since = Some(now);
+        profile.current_dare_id = penalty.as_ref().map(|dare| dare.id);
+        profile.penalty_dare_id = profile.current_dare_id;
+        profile.current_dare_chat = None;
+        profile.current_dare_text = Some(penalty.as_ref().map_or(PENALTY_TEXT.to_string(), |dare| dare.text.clone()));
+        Ok(Some(Outcome::Penalty { streak_lost, dare_id: profile.current_dare_id, text: current_dare_text(profile) }))
+    }).ok().flatten()?;
+
+    match &outcome {
+        Outcome::Frozen { streak, freezes_left } => {
+            history::log_event(user, HistoryEvent::StreakFreezeUsed { streak: *streak });
+            outbox::queue(user, format!(
+                "🧊 {} hours without a dare, but a streak freeze saved your streak of {} ({} left). The clock starts again now.",
+                LAPSE_HOURS, streak, freezes_left
+            ), None);
+        }
+        Outcome::Penalty { streak_lost, dare_id, text } => {
+            history::log_event(user, HistoryEvent::HardcoreLapsed { streak_lost: *streak_lost, penalty_dare_id: *dare_id });
+            outbox::queue(user, format!(
+                "💀 Hardcore: {} hours without a dare, so your streak of {} is gone. Your penalty dare: {}\nSubmit it to unlock normal dares.",
+                LAPSE_HOURS, streak_lost, text
+            ), None);
+        }
+    }
+    Some(outcome)
+}
+
+// Resets the streaks of hardcore players who went quiet. Returns how many lapsed (or spent a freeze).
+pub fn run() -> u32 {
+    let now = ic_cdk::api::time();
+    let mut players = Vec::new();
+    repository::users().scan(None, &mut |user, profile| {
+        if lapsed(&profile, now) {
+            players.push(user);
+        }
+        true
+    });
+    players.into_iter().filter_map(|user| lapse(user, now)).count() as u32
+}
@@ -0,0 +1,84 @@
+use crate::llm;
+use crate::points;
+use crate::repository::{self, DareRepo};
+use crate::state;
+use crate::templates;
+use candid::Principal;
+
+// --- Hints ---
+// A dare can carry a hint, written by the admin on /add_dare or generated by the LLM the first
+// time someone asks (then stored for everyone after, so a template dare is described to the LLM
+// with generic placeholder values rather than the asker's rendering). /hint reveals the active
+// dare's hint for a points cost; paying once covers repeat requests for the same dare, and the
+// submission and its history entry record that the hint was used.
+
+pub const HINT_COST: u64 = 5;
+pub const MAX_HINT_LEN: usize = 200;
+
+// Trims an optional hint; empty counts as none
+pub fn validate(hint: Option<String>) -> Result<Option<String>, String> {
+    let hint = hint.map(|h| h.trim().to_string()).filter(|h| !h.is_empty());
+    if hint.as_ref().is_some_and(|h| h.len() > MAX_HINT_LEN) {
+        return Err(format!("Hint is too long (max {} bytes).", MAX_HINT_LEN));
+    }
+    Ok(hint)
+}
+
+pub fn set(dare_id: u64, hint: Option<String>) -> Result<(), String> {
+    let dares = repository::dares();
+    let mut dare = dares.get(dare_id).ok_or_else(|| format!("Dare #{} not found.", dare_id))?;
+    dare.hint = hint;
+    dares.set(&dare)
+}
+
+// Cuts an LLM hint to MAX_HINT_LEN on a character boundary
+fn clamp(mut hint: String) -> String {
+    if hint.len() > MAX_HINT_LEN {
+        let cut = (0..=MAX_HINT_LEN).rev().find(|&i| hint.is_char_boundary(i)).unwrap_or(0);
+        hint.truncate(cut);
+    }
+    hint
+}
+
+// Returns the hint for the user's active dare, charging HINT_COST the first time
+pub async fn reveal(user: Principal) -> Result<String, String> {
+    let profile = state::get_profile(user)
+        .ok_or_else(|| "User not found. Please /register first.".to_string())?;
+    let dare_id = profile.current_dare_id
+        .ok_or_else(|| "You don't have an active dare. Use /get_dare first.".to_string())?;
+    let dare = repository::dares().get(dare_id)
+        .ok_or_else(|| format!("Dare #{} not found.", dare_id))?;
+    let paid = profile.hint_dare_id == Some(dare_id);
+    if !paid && points::balance(user) < HINT_COST {
+        return Err(format!("A hint costs {} points; you have {}.", HINT_COST, points::balance(user)));
+    }
+
+    let hint = match dare.hint {
+        Some(hint) => hint,
+        None => {
+            let hint = llm::fetch_llm_hint(&templates::generic_dare(&dare), profile.current_dare_chat.as_deref()).await
+                .map(clamp)
+                .map_err(|e| format!("No hint is available for this dare right now: {}", e))?;
+            set(dare_id, Some(hint.clone()))?;
+            hint
+        }
+    };
+    if !paid {
+        // The active dare may have changed while the hint was generated
+        state::update_profile(user, |profile| {
+            if profile.current_dare_id != Some(dare_id) {
+                return Err("Your active dare changed in the meantime. Use /hint again.".to_string());
+            }
+            profile.hint_dare_id = Some(dare_id);
+            Ok(())
+        })?;
+        if let Err(e) = points::burn(user, HINT_COST, Some("hint")) {
+            let _ = state::update_profile(user, |profile| {
+                profile.hint_dare_id = None;
+                Ok(())
+            });
+            return Err(e);
+        }
+    }
+    Ok(hint)
+}
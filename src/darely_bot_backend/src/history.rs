@@ -0,0 +1,27 @@
+use crate::replication;
+use crate::state;
+use crate::types::{HistoryEntry, HistoryEvent, ReplicatedMap};
+use candid::Principal;
+
+// Appends an event to the activity log
+pub fn log_event(user: Principal, event: HistoryEvent) {
+    let seq = state::HISTORY.with(|history_ref| {
+        let mut history = history_ref.borrow_mut();
+        let seq = history.last_key_value().map_or(0, |(seq, _)| seq + 1);
+        history.insert(seq, HistoryEntry { user, timestamp: ic_cdk::api::time(), event });
+        seq
+    });
+    replication::note(ReplicatedMap::History, &seq);
+}
+
+// Returns up to `limit` of the user's most recent events, newest first
+pub fn events_for(user: Principal, limit: usize) -> Vec<HistoryEntry> {
+    state::HISTORY.with(|history_ref| {
+        history_ref.borrow().iter()
+            .rev()
+            .map(|(_, entry)| entry)
+            .filter(|entry| entry.user == user)
+            .take(limit)
+            .collect()
+    })
+}
@@ -0,0 +1,151 @@
+use crate::certification;
+use crate::state;
+use crate::types::{BlockWithId, GetBlocksArgs, GetBlocksResult, ICRC3DataCertificate, ICRC3Value, SupportedBlockType};
+use candid::{Nat, Principal};
+use serde_bytes::ByteBuf;
+use sha2::{Digest, Sha256};
+
+// --- ICRC-3 Block Log ---
+// Every points mint, burn and transfer is appended as an ICRC-1 style block ("1mint", "1burn",
+// "1xfer") linked to its predecessor by `phash`, the ICRC-3 representation-independent hash of
+// the previous block. The index and hash of the latest block are part of the certified data, so
+// explorers can verify the whole log from `icrc3_get_tip_certificate` and `icrc3_get_blocks`.
+
+pub const MAX_BLOCKS_PER_CALL: u64 = 100;
+
+type Hash = [u8; 32];
+
+// ICRC-3 value hashing
+pub fn hash_value(value: &ICRC3Value) -> Hash {
+    match value {
+        ICRC3Value::Blob(bytes) => Sha256::digest(bytes).into(),
+        ICRC3Value::Text(text) => Sha256::digest(text.as_bytes()).into(),
+        ICRC3Value::Nat(nat) => {
+            let mut buf = Vec::new();
+            nat.encode(&mut buf).expect("LEB128 encoding into a Vec cannot fail");
+            Sha256::digest(buf).into()
+        }
+        ICRC3Value::Int(int) => {
+            let mut buf = Vec::new();
+            int.encode(&mut buf).expect("SLEB128 encoding into a Vec cannot fail");
+            Sha256::digest(buf).into()
+        }
+        ICRC3Value::Array(values) => {
+            let mut hasher = Sha256::new();
+            for value in values {
+                hasher.update(hash_value(value));
+            }
+            hasher.finalize().into()
+        }
+        ICRC3Value::Map(entries) => {
+            let mut pairs: Vec<Vec<u8>> = entries.iter()
+                .map(|(key, value)| [Sha256::digest(key.as_bytes()).as_slice(), &hash_value(value)].concat())
+                .collect();
+            pairs.sort();
+            Sha256::digest(pairs.concat()).into()
+        }
+    }
+}
+
+// An ICRC-1 account with the default subaccount
+pub fn account(owner: Principal) -> ICRC3Value {
+    ICRC3Value::Array(vec![ICRC3Value::Blob(ByteBuf::from(owner.as_slice().to_vec()))])
+}
+
+pub fn nat(value: u64) -> ICRC3Value {
+    ICRC3Value::Nat(Nat::from(value))
+}
+
+fn last_block() -> Option<(u64, ICRC3Value)> {
+    state::BLOCKS.with(|blocks| blocks.borrow().last_key_value())
+}
+
+// Appends a block with the given type and transaction fields. Returns its index.
+pub fn append(block_type: &str, tx: Vec<(String, ICRC3Value)>) -> u64 {
+    let previous = last_block();
+    let index = previous.as_ref().map_or(0, |(index, _)| index + 1);
+    let mut fields = vec![
+        ("btype".to_string(), ICRC3Value::Text(block_type.to_string())),
+        ("ts".to_string(), nat(ic_cdk::api::time())),
+        ("tx".to_string(), ICRC3Value::Map(tx)),
+    ];
+    if let Some((_, previous)) = previous {
+        fields.push(("phash".to_string(), ICRC3Value::Blob(ByteBuf::from(hash_value(&previous).to_vec()))));
+    }
+    let block = ICRC3Value::Map(fields);
+    let hash = hash_value(&block);
+    state::BLOCKS.with(|blocks| blocks.borrow_mut().insert(index, block));
+    certification::set_ledger_tip(index, hash);
+    index
+}
+
+// Re-certifies the tip after an upgrade (the certified tree lives on the heap)
+pub fn certify_tip() {
+    if let Some((index, block)) = last_block() {
+        certification::set_ledger_tip(index, hash_value(&block));
+    }
+}
+
+fn to_u64(nat: &Nat) -> u64 {
+    u64::try_from(&nat.0).unwrap_or(u64::MAX)
+}
+
+pub fn get_blocks(args: Vec<GetBlocksArgs>) -> GetBlocksResult {
+    let log_length = state::BLOCKS.with(|blocks| blocks.borrow().len());
+    let mut budget = MAX_BLOCKS_PER_CALL;
+    let mut found = Vec::new();
+    state::BLOCKS.with(|blocks| {
+        let blocks = blocks.borrow();
+        for range in &args {
+            let start = to_u64(&range.start);
+            let length = to_u64(&range.length).min(budget);
+            found.extend(blocks.range(start..start.saturating_add(length)).map(|(id, block)| BlockWithId { id: Nat::from(id), block }));
+            budget -= length;
+        }
+    });
+    GetBlocksResult { log_length: Nat::from(log_length), blocks: found, archived_blocks: Vec::new() }
+}
+
+pub fn tip_certificate() -> Option<ICRC3DataCertificate> {
+    let (certificate, hash_tree) = certification::ledger_tip_certificate()?;
+    Some(ICRC3DataCertificate { certificate: ByteBuf::from(certificate), hash_tree: ByteBuf::from(hash_tree) })
+}
+
+pub fn supported_block_types() -> Vec<SupportedBlockType> {
+    let url = "https://github.com/dfinity/ICRC-1/blob/main/standards/ICRC-3/README.md".to_string();
+    ["1mint", "1burn", "1xfer"].iter()
+        .map(|block_type| SupportedBlockType { block_type: block_type.to_string(), url: url.clone() })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(hash: Hash) -> String {
+        hash.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn blob(bytes: &[u8]) -> ICRC3Value {
+        ICRC3Value::Blob(ByteBuf::from(bytes.to_vec()))
+    }
+
+    // The examples published with the ICRC-3 standard
+    #[test]
+    fn hashes_match_the_spec_vectors() {
+        assert_eq!(hex(hash_value(&nat(42))), "684888c0ebb17f374298b65ee2807526c066094c701bcc7ebbe1c1095f494fc1");
+        assert_eq!(hex(hash_value(&ICRC3Value::Int(candid::Int::from(-42)))), "de5a6f78116eca62d7fc5ce159d23ae6b889b365a1739ad2cf36f925a140d0cc");
+        assert_eq!(hex(hash_value(&ICRC3Value::Text("Hello, World!".to_string()))), "dffd6021bb2bd5b0af676290809ec3a53191dd81c7f70a4b28688a362182986f");
+        assert_eq!(hex(hash_value(&blob(&[1, 2, 3, 4]))), "9f64a747e1b97f131fabb6b447296c9b6f0201e79fb3c5356e6c77e89b6a806a");
+        let array = ICRC3Value::Array(vec![nat(3), ICRC3Value::Text("foo".to_string()), blob(&[5, 6])]);
+        assert_eq!(hex(hash_value(&array)), "514a04011caa503990d446b7dec5d79e19c221ae607fb08b2848c67734d468d6");
+        let map = ICRC3Value::Map(vec![
+            ("from".to_string(), blob(b"\x00\xab\xcd\xef\x00\x12\x34\x00\x56\x78\x9a\x00\xbc\xde\xf0\x00\x01\x23\x45\x67\x89\x00\xab\xcd\xef\x01")),
+            ("to".to_string(), blob(b"\x00\xab\x0d\xef\x00\x12\x34\x00\x56\x78\x9a\x00\xbc\xde\xf0\x00\x01\x23\x45\x67\x89\x00\xab\xcd\xef\x01")),
+            ("amount".to_string(), nat(42)),
+            ("created_at".to_string(), nat(1_699_218_263)),
+            ("memo".to_string(), nat(0)),
+        ]);
+        assert_eq!(hex(hash_value(&map)), "c56ece650e1de4269c5bdeff7875949e3e2033f85b2d193c2ff4f7f78bdcfc75");
+    }
+}
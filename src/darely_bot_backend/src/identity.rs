@@ -0,0 +1,407 @@
+use crate::state;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use bls12_381::hash_to_curve::{ExpandMsgXmd, HashToCurve};
+use bls12_381::{G1Affine, G1Projective, G2Affine};
+use candid::Principal;
+use ic_certification::{Certificate, HashTree, LookupResult};
+use serde::Deserialize;
+use serde_bytes::ByteBuf;
+use sha2::{Digest, Sha256};
+
+// --- Signed Web Requests ---
+// Write routes take an `X-Identity-Proof` header: base64 of a JSON document
+//   { "public_key", "delegations": [{ "pubkey", "expiration", "targets"?, "signature" }],
+//     "signature", "nonce", "timestamp" }
+// Binary fields are base64, nanosecond times decimal strings, targets principal text.
+// The caller is the self-authenticating principal of `public_key` (for Internet Identity, the II
+// canister-signature key). Each delegation must be signed by the previous key, and the last key
+// signs the request itself. Nonces are accepted once, within a few minutes of `timestamp`.
+
+const MAX_DELEGATIONS: usize = 20; // Same limit as the IC's ingress checks
+const MAX_NONCE_LEN: usize = 64;
+const MAX_USED_NONCES: u64 = 10_000;
+const REQUEST_VALIDITY_NANOS: u64 = 5 * 60 * 1_000_000_000; // 5 minutes either side of now
+
+const BLS_DST: &[u8] = b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_NUL_";
+
+// Raw BLS key of the IC mainnet root (last 96 bytes of its DER encoding)
+const MAINNET_ROOT_KEY: [u8; 96] = [
+    0x81, 0x4c, 0x0e, 0x6e, 0xc7, 0x1f, 0xab, 0x58, 0x3b, 0x08, 0xbd, 0x81, 0x37, 0x3c, 0x25, 0x5c,
+    0x3c, 0x37, 0x1b, 0x2e, 0x84, 0x86, 0x3c, 0x98, 0xa4, 0xf1, 0xe0, 0x8b, 0x74, 0x23, 0x5d, 0x14,
+    0xfb, 0x5d, 0x9c, 0x0c, 0xd5, 0x46, 0xd9, 0x68, 0x5f, 0x91, 0x3a, 0x0c, 0x0b, 0x2c, 0xc5, 0x34,
+    0x15, 0x83, 0xbf, 0x4b, 0x43, 0x92, 0xe4, 0x67, 0xdb, 0x96, 0xd6, 0x5b, 0x9b, 0xb4, 0xcb, 0x71,
+    0x71, 0x12, 0xf8, 0x47, 0x2e, 0x0d, 0x5a, 0x4d, 0x14, 0x50, 0x5f, 0xfd, 0x74, 0x84, 0xb0, 0x12,
+    0x91, 0x09, 0x1c, 0x5f, 0x87, 0xb9, 0x88, 0x83, 0x46, 0x3f, 0x98, 0x09, 0x1a, 0x0b, 0xaa, 0xae,
+];
+
+// Algorithm OIDs (DER content bytes)
+const OID_ED25519: &[u8] = &[0x2b, 0x65, 0x70];
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const OID_P256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+pub const OID_CANISTER_SIG: &[u8] = &[0x2b, 0x06, 0x01, 0x04, 0x01, 0x83, 0xb8, 0x43, 0x01, 0x02];
+const OID_BLS: &[u8] = &[0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0xdc, 0x7c, 0x05, 0x03, 0x01, 0x02, 0x01];
+
+// --- Proof Format ---
+
+#[derive(Deserialize)]
+struct DelegationJson {
+    pubkey: String,
+    expiration: String,
+    targets: Option<Vec<String>>,
+    signature: String,
+}
+
+#[derive(Deserialize)]
+struct ProofJson {
+    public_key: String,
+    #[serde(default)]
+    delegations: Vec<DelegationJson>,
+    signature: String,
+    nonce: String,
+    timestamp: String,
+}
+
+struct Delegation {
+    pubkey: Vec<u8>,
+    expiration: u64,
+    targets: Option<Vec<Principal>>,
+    signature: Vec<u8>,
+}
+
+fn decode_b64(value: &str, field: &str) -> Result<Vec<u8>, String> {
+    BASE64.decode(value.trim()).map_err(|_| format!("Invalid base64 in '{}'.", field))
+}
+
+fn parse_nanos(value: &str, field: &str) -> Result<u64, String> {
+    value.trim().parse().map_err(|_| format!("Invalid timestamp in '{}'.", field))
+}
+
+fn parse_delegation(json: &DelegationJson) -> Result<Delegation, String> {
+    let targets = match &json.targets {
+        Some(targets) => Some(targets.iter()
+            .map(|t| Principal::from_text(t).map_err(|_| format!("Invalid delegation target '{}'.", t)))
+            .collect::<Result<Vec<_>, _>>()?),
+        None => None,
+    };
+    Ok(Delegation {
+        pubkey: decode_b64(&json.pubkey, "pubkey")?,
+        expiration: parse_nanos(&json.expiration, "expiration")?,
+        targets,
+        signature: decode_b64(&json.signature, "signature")?,
+    })
+}
+
+// --- Public Keys (DER SubjectPublicKeyInfo) ---
+
+enum PublicKey {
+    Ed25519(ed25519_dalek::VerifyingKey),
+    EcdsaP256(p256::ecdsa::VerifyingKey),
+    Canister { canister_id: Principal, seed: Vec<u8> },
+}
+
+// Reads one DER element with the expected tag, returning (content, rest)
+fn der_element(bytes: &[u8], tag: u8) -> Result<(&[u8], &[u8]), String> {
+    let invalid = || "Invalid DER public key.".to_string();
+    if bytes.len() < 2 || bytes[0] != tag {
+        return Err(invalid());
+    }
+    let (len, header) = match bytes[1] {
+        len @ 0..=0x7f => (len as usize, 2),
+        0x81 if bytes.len() > 2 => (bytes[2] as usize, 3),
+        0x82 if bytes.len() > 3 => (u16::from_be_bytes([bytes[2], bytes[3]]) as usize, 4),
+        _ => return Err(invalid()),
+    };
+    let end = header + len;
+    if bytes.len() < end {
+        return Err(invalid());
+    }
+    Ok((&bytes[header..end], &bytes[end..]))
+}
+
+// SubjectPublicKeyInfo fields
+struct Spki<'a> {
+    algorithm: &'a [u8], // OID
+    parameter: Option<&'a [u8]>, // Parameter OID (e.g. the curve)
+    key: &'a [u8],
+}
+
+impl Spki<'_> {
+    fn fields(&self) -> (&[u8], Option<&[u8]>, &[u8]) {
+        (self.algorithm, self.parameter, self.key)
+    }
+}
+
+fn parse_spki(der: &[u8]) -> Result<Spki<'_>, String> {
+    let (spki, _) = der_element(der, 0x30)?;
+    let (algorithm, rest) = der_element(spki, 0x30)?;
+    let (oid, params) = der_element(algorithm, 0x06)?;
+    let param_oid = if params.is_empty() { None } else { Some(der_element(params, 0x06)?.0) };
+    let (bit_string, _) = der_element(rest, 0x03)?;
+    match bit_string.split_first() {
+        Some((0, key)) => Ok(Spki { algorithm: oid, parameter: param_oid, key }),
+        _ => Err("Invalid DER public key.".to_string()),
+    }
+}
+
+fn parse_public_key(der: &[u8]) -> Result<PublicKey, String> {
+    match parse_spki(der)?.fields() {
+        (OID_ED25519, None, key) => {
+            let key: [u8; 32] = key.try_into().map_err(|_| "Invalid Ed25519 key.".to_string())?;
+            ed25519_dalek::VerifyingKey::from_bytes(&key)
+                .map(PublicKey::Ed25519)
+                .map_err(|_| "Invalid Ed25519 key.".to_string())
+        }
+        (OID_EC_PUBLIC_KEY, Some(OID_P256), key) => p256::ecdsa::VerifyingKey::from_sec1_bytes(key)
+            .map(PublicKey::EcdsaP256)
+            .map_err(|_| "Invalid ECDSA P-256 key.".to_string()),
+        (OID_CANISTER_SIG, None, key) => {
+            let (&id_len, rest) = key.split_first().ok_or("Invalid canister signature key.")?;
+            if rest.len() < id_len as usize {
+                return Err("Invalid canister signature key.".to_string());
+            }
+            let (canister_id, seed) = rest.split_at(id_len as usize);
+            Ok(PublicKey::Canister { canister_id: Principal::from_slice(canister_id), seed: seed.to_vec() })
+        }
+        _ => Err("Unsupported public key type (expected Ed25519, ECDSA P-256 or a canister signature key).".to_string()),
+    }
+}
+
+fn verify_signature(der: &[u8], message: &[u8], signature: &[u8]) -> Result<(), String> {
+    let invalid = || "Invalid signature.".to_string();
+    match parse_public_key(der)? {
+        PublicKey::Ed25519(key) => {
+            let signature = ed25519_dalek::Signature::from_slice(signature).map_err(|_| invalid())?;
+            key.verify_strict(message, &signature).map_err(|_| invalid())
+        }
+        PublicKey::EcdsaP256(key) => {
+            use p256::ecdsa::signature::Verifier;
+            let signature = p256::ecdsa::Signature::from_slice(signature).map_err(|_| invalid())?;
+            key.verify(message, &signature).map_err(|_| invalid())
+        }
+        PublicKey::Canister { canister_id, seed } => verify_canister_signature(canister_id, &seed, message, signature),
+    }
+}
+
+// --- Canister Signatures (IC interface spec, "Canister signatures") ---
+
+#[derive(Deserialize)]
+struct CanisterSignature {
+    certificate: ByteBuf,
+    tree: HashTree,
+}
+
+fn verify_bls(signature: &[u8], message: &[u8], public_key: &[u8]) -> Result<(), String> {
+    let invalid = || "Invalid certificate signature.".to_string();
+    let signature: [u8; 48] = signature.try_into().map_err(|_| invalid())?;
+    let public_key: [u8; 96] = public_key.try_into().map_err(|_| invalid())?;
+    let signature = Option::<G1Affine>::from(G1Affine::from_compressed(&signature)).ok_or_else(invalid)?;
+    let public_key = Option::<G2Affine>::from(G2Affine::from_compressed(&public_key)).ok_or_else(invalid)?;
+    let hashed = G1Affine::from(<G1Projective as HashToCurve<ExpandMsgXmd<sha2_09::Sha256>>>::hash_to_curve(message, BLS_DST));
+    if bls12_381::pairing(&signature, &G2Affine::generator()) == bls12_381::pairing(&hashed, &public_key) {
+        Ok(())
+    } else {
+        Err(invalid())
+    }
+}
+
+fn root_key() -> Result<Vec<u8>, String> {
+    match state::get_config().ic_root_key {
+        Some(der) => bls_key_from_der(&der),
+        None => Ok(MAINNET_ROOT_KEY.to_vec()),
+    }
+}
+
+fn bls_key_from_der(der: &[u8]) -> Result<Vec<u8>, String> {
+    match parse_spki(der)?.fields() {
+        (OID_BLS, _, key) if key.len() == 96 => Ok(key.to_vec()),
+        _ => Err("Invalid BLS public key.".to_string()),
+    }
+}
+
+fn lookup<'a>(tree: &'a HashTree, path: &[&[u8]]) -> Option<&'a [u8]> {
+    match tree.lookup_path(path) {
+        LookupResult::Found(value) => Some(value),
+        _ => None,
+    }
+}
+
+// Checks the certificate's BLS signature, following a subnet delegation if present
+fn verify_certificate(certificate: &Certificate, canister_id: Principal) -> Result<(), String> {
+    let signing_key = match &certificate.delegation {
+        None => root_key()?,
+        Some(delegation) => {
+            let parent: Certificate = serde_cbor::from_slice(&delegation.certificate)
+                .map_err(|_| "Malformed delegation certificate.".to_string())?;
+            if parent.delegation.is_some() {
+                return Err("Nested certificate delegations are not allowed.".to_string());
+            }
+            verify_bls(&parent.signature, &state_root_message(&parent), &root_key()?)?;
+
+            let subnet_id = delegation.subnet_id.as_slice();
+            let ranges = lookup(&parent.tree, &[b"subnet", subnet_id, b"canister_ranges"])
+                .ok_or("Delegation certificate has no canister ranges.")?;
+            let ranges: Vec<(ByteBuf, ByteBuf)> = serde_cbor::from_slice(ranges)
+                .map_err(|_| "Malformed canister ranges.".to_string())?;
+            let id = canister_id.as_slice();
+            if !ranges.iter().any(|(low, high)| low.as_slice() <= id && id <= high.as_slice()) {
+                return Err("Signing canister is outside the delegated subnet's ranges.".to_string());
+            }
+            let subnet_key = lookup(&parent.tree, &[b"subnet", subnet_id, b"public_key"])
+                .ok_or("Delegation certificate has no subnet key.")?;
+            bls_key_from_der(subnet_key)?
+        }
+    };
+    verify_bls(&certificate.signature, &state_root_message(certificate), &signing_key)
+}
+
+fn state_root_message(certificate: &Certificate) -> Vec<u8> {
+    let mut message = b"\x0dic-state-root".to_vec();
+    message.extend_from_slice(&certificate.tree.digest());
+    message
+}
+
+fn verify_canister_signature(canister_id: Principal, seed: &[u8], message: &[u8], signature: &[u8]) -> Result<(), String> {
+    let signature: CanisterSignature = serde_cbor::from_slice(signature)
+        .map_err(|_| "Malformed canister signature.".to_string())?;
+    let certificate: Certificate = serde_cbor::from_slice(&signature.certificate)
+        .map_err(|_| "Malformed certificate.".to_string())?;
+    verify_certificate(&certificate, canister_id)?;
+
+    let certified_data = lookup(&certificate.tree, &[b"canister", canister_id.as_slice(), b"certified_data"])
+        .ok_or("Certificate has no certified data for the signing canister.")?;
+    if certified_data != signature.tree.digest() {
+        return Err("Canister signature tree doesn't match the certified data.".to_string());
+    }
+    let seed_hash = Sha256::digest(seed);
+    let message_hash = Sha256::digest(message);
+    match lookup(&signature.tree, &[b"sig", &seed_hash[..], &message_hash[..]]) {
+        Some(_) => Ok(()),
+        None => Err("Canister signature doesn't cover this message.".to_string()),
+    }
+}
+
+// --- Signed Messages ---
+
+fn leb128(mut value: u64) -> Vec<u8> {
+    let mut out = Vec::new();
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return out;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+// Representation-independent hash of a delegation (IC interface spec, "Hash of map")
+fn delegation_message(delegation: &Delegation) -> Vec<u8> {
+    let mut fields: Vec<Vec<u8>> = vec![
+        [Sha256::digest(b"pubkey"), Sha256::digest(&delegation.pubkey)].concat(),
+        [Sha256::digest(b"expiration"), Sha256::digest(leb128(delegation.expiration))].concat(),
+    ];
+    if let Some(targets) = &delegation.targets {
+        let mut targets_hasher = Sha256::new();
+        for target in targets {
+            targets_hasher.update(Sha256::digest(target.as_slice()));
+        }
+        fields.push([Sha256::digest(b"targets"), targets_hasher.finalize()].concat());
+    }
+    fields.sort();
+    let mut message = b"\x1aic-request-auth-delegation".to_vec();
+    message.extend_from_slice(&Sha256::digest(fields.concat()));
+    message
+}
+
+// What the last key in the chain signs: binds method, path, body, nonce and timestamp
+fn request_message(method: &str, path: &str, body: &[u8], nonce: &str, timestamp: u64) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_bytes());
+    hasher.update([0]);
+    hasher.update(path.as_bytes());
+    hasher.update([0]);
+    hasher.update(Sha256::digest(body));
+    hasher.update(nonce.as_bytes());
+    hasher.update([0]);
+    hasher.update(timestamp.to_be_bytes());
+    let mut message = b"\x12darely-api-request".to_vec();
+    message.extend_from_slice(&hasher.finalize());
+    message
+}
+
+// --- Replay Protection ---
+
+// Records the nonce, failing if it was already used. Also drops expired entries.
+fn consume_nonce(user: Principal, nonce: &str, expires_at: u64) -> Result<(), String> {
+    let now = ic_cdk::api::time();
+    let mut hasher = Sha256::new();
+    hasher.update(user.as_slice());
+    hasher.update(nonce.as_bytes());
+    let key: [u8; 32] = hasher.finalize().into();
+    state::USED_NONCES.with(|nonces_ref| {
+        let mut nonces = nonces_ref.borrow_mut();
+        let expired: Vec<[u8; 32]> = nonces.iter()
+            .filter(|(_, expiry)| *expiry <= now)
+            .map(|(key, _)| key)
+            .collect();
+        for key in expired {
+            nonces.remove(&key);
+        }
+        if nonces.contains_key(&key) {
+            return Err("This request was already processed (nonce reused).".to_string());
+        }
+        if nonces.len() >= MAX_USED_NONCES {
+            return Err("Too many signed requests right now. Please try again shortly.".to_string());
+        }
+        nonces.insert(key, expires_at);
+        Ok(())
+    })
+}
+
+// --- Public API ---
+
+// Verifies an `X-Identity-Proof` header for a request and returns the signer's principal.
+// Consumes the nonce, so it must run in update context.
+pub fn verify_request(proof: &str, method: &str, path: &str, body: &[u8]) -> Result<Principal, String> {
+    let json = decode_b64(proof, "X-Identity-Proof")?;
+    let proof: ProofJson = serde_json::from_slice(&json).map_err(|e| format!("Malformed identity proof: {}", e))?;
+
+    let now = ic_cdk::api::time();
+    let timestamp = parse_nanos(&proof.timestamp, "timestamp")?;
+    if timestamp.abs_diff(now) > REQUEST_VALIDITY_NANOS {
+        return Err("Request timestamp is too far from the current time.".to_string());
+    }
+    if proof.nonce.is_empty() || proof.nonce.len() > MAX_NONCE_LEN {
+        return Err(format!("Nonce must be 1-{} bytes.", MAX_NONCE_LEN));
+    }
+    if proof.delegations.len() > MAX_DELEGATIONS {
+        return Err(format!("Too many delegations (max {}).", MAX_DELEGATIONS));
+    }
+
+    let public_key = decode_b64(&proof.public_key, "public_key")?;
+    let mut signing_key = public_key.clone();
+    for json in &proof.delegations {
+        let delegation = parse_delegation(json)?;
+        if delegation.expiration < now {
+            return Err("Delegation has expired. Please sign in again.".to_string());
+        }
+        if delegation.targets.as_ref().is_some_and(|targets| !targets.contains(&ic_cdk::id())) {
+            return Err("Delegation is not valid for this canister.".to_string());
+        }
+        verify_signature(&signing_key, &delegation_message(&delegation), &delegation.signature)?;
+        signing_key = delegation.pubkey;
+    }
+    let signature = decode_b64(&proof.signature, "signature")?;
+    verify_signature(&signing_key, &request_message(method, path, body, &proof.nonce, timestamp), &signature)?;
+
+    let user = Principal::self_authenticating(&public_key);
+    consume_nonce(user, &proof.nonce, timestamp + REQUEST_VALIDITY_NANOS)?;
+    Ok(user)
+}
+
+// Validates an admin-supplied root key (DER, as printed by `dfx ping`)
+pub fn validate_root_key(der: &[u8]) -> Result<(), String> {
+    bls_key_from_der(der).map(|_| ())
+}
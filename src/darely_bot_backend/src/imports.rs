@@ -0,0 +1,346 @@
+use crate::analysis;
+use crate::correlation;
+use crate::incidents;
+use crate::hints;
+use crate::repository::{self, DareRepo};
+use crate::state;
+use crate::templates;
+use crate::types::{DareSource, Difficulty, ImportJob, ImportRow};
+use candid::Principal;
+use ic_cdk::api::management_canister::http_request::{
+    http_request, CanisterHttpRequestArgument, HttpMethod, HttpResponse, TransformArgs, TransformContext,
+};
+use serde_json::Value;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeSet;
+
+// --- Content Import from URL ---
+// /import_url fetches a dare list over an HTTPS outcall and queues it as an import job. The file
+// is a JSON array (of dare texts or of {"text", "difficulty", "hint"} objects, optionally under a
+// "dares" key) or a CSV with text[,difficulty[,hint]] columns and an optional header row. Rows are
+// validated like /add_dare and de-duplicated against each other and the stored dares up front,
+// and the reply summarizes what was found. The import timer then stores CHUNK_SIZE rows per tick,
+// checking for duplicates again in case the repository changed meanwhile, so a large file never
+// holds up a single message. A row without a difficulty gets the analysis' suggestion.
+//
+// An import takes one of MAX_OPEN_JOBS slots from before its outcall until its job finishes. The
+// stored dares' texts are read once and then kept on the heap while imports run, so each tick
+// only reads the dares added since; the cache is dropped once no job is left.
+
+const MAX_RESPONSE_BYTES: u64 = 1_000_000;
+const HTTP_REQUEST_CYCLES: u128 = 30_000_000_000; // Unused cycles are refunded
+const MAX_URL_LEN: usize = 2_000;
+const MAX_ROWS: usize = 2_000;
+const MAX_OPEN_JOBS: usize = 3;
+const CHUNK_SIZE: usize = 25;
+
+thread_local! {
+    // Imports whose file is still being fetched (see Reservation)
+    static FETCHING: Cell<usize> = const { Cell::new(0) };
+    // Normalized texts of the pooled dares among the first `.0` ids
+    static KNOWN_TEXTS: RefCell<Option<(u64, BTreeSet<String>)>> = const { RefCell::new(None) };
+}
+
+// --- Parsing ---
+
+fn parse_difficulty(value: &str) -> Result<Option<Difficulty>, String> {
+    match value.trim().to_lowercase().as_str() {
+        "" => Ok(None),
+        "easy" => Ok(Some(Difficulty::Easy)),
+        "medium" => Ok(Some(Difficulty::Medium)),
+        "hard" => Ok(Some(Difficulty::Hard)),
+        other => Err(format!("unknown difficulty '{}'", other)),
+    }
+}
+
+fn row(text: &str, difficulty: &str, hint: &str) -> Result<ImportRow, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("empty dare text".to_string());
+    }
+    if text.len() > crate::MAX_DARE_TEXT_LEN {
+        return Err("dare text too long".to_string());
+    }
+    templates::validate(text)?;
+    let hint = hints::validate(Some(hint.to_string()))?;
+    Ok(ImportRow { text: text.to_string(), difficulty: parse_difficulty(difficulty)?, hint })
+}
+
+fn parse_json(body: &str) -> Result<Vec<Result<ImportRow, String>>, String> {
+    let document: Value = serde_json::from_str(body).map_err(|e| format!("The file is not valid JSON: {}", e))?;
+    let items = match &document {
+        Value::Array(items) => items,
+        Value::Object(object) => object.get("dares").and_then(Value::as_array)
+            .ok_or_else(|| "A JSON object needs a \"dares\" array.".to_string())?,
+        _ => return Err("Expected a JSON array of dares.".to_string()),
+    };
+    Ok(items.iter().map(|item| match item {
+        Value::String(text) => row(text, "", ""),
+        Value::Object(fields) => {
+            let field = |name: &str| fields.get(name).and_then(Value::as_str).unwrap_or_default();
+            row(field("text"), field("difficulty"), field("hint"))
+        }
+        _ => Err("not a string or an object".to_string()),
+    }).collect())
+}
+
+// Splits one CSV line, honouring double quotes ("a, b" and "" for a literal quote)
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            _ => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+fn parse_csv(body: &str) -> Vec<Result<ImportRow, String>> {
+    let mut lines = body.lines().filter(|line| !line.trim().is_empty()).peekable();
+    let mut columns = [0, 1, 2]; // text, difficulty, hint
+    if let Some(header) = lines.peek().map(|line| csv_fields(line)) {
+        let names: Vec<String> = header.iter().map(|name| name.trim().to_lowercase()).collect();
+        if names.iter().any(|name| name == "text") {
+            let position = |name: &str| names.iter().position(|n| n == name).unwrap_or(usize::MAX);
+            columns = [position("text"), position("difficulty"), position("hint")];
+            lines.next();
+        }
+    }
+    lines.map(|line| {
+        let fields = csv_fields(line);
+        let field = |i: usize| fields.get(i).map_or("", String::as_str);
+        row(field(columns[0]), field(columns[1]), field(columns[2]))
+    }).collect()
+}
+
+// JSON when the URL or the body says so, CSV otherwise
+pub fn parse(url: &str, body: &str) -> Result<Vec<Result<ImportRow, String>>, String> {
+    let path = url.split(['?', '#']).next().unwrap_or_default().to_lowercase();
+    let body = body.trim_start_matches('\u{feff}');
+    if path.ends_with(".json") || body.trim_start().starts_with(['[', '{']) {
+        parse_json(body)
+    } else {
+        Ok(parse_csv(body))
+    }
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+// Runs `f` on the normalized texts of every pooled dare, reading only the dares added since the
+// last call (the repository only appends)
+fn with_known_texts<R>(f: impl FnOnce(&mut BTreeSet<String>) -> R) -> R {
+    let dares = repository::dares();
+    KNOWN_TEXTS.with(|k| {
+        let mut known = k.borrow_mut();
+        let (read, texts) = known.get_or_insert_with(|| (0, BTreeSet::new()));
+        for id in *read..dares.len() {
+            if let Some(dare) = dares.get(id).filter(|dare| dare.source != DareSource::Custom) {
+                texts.insert(normalize(&dare.text));
+            }
+        }
+        *read = dares.len();
+        f(texts)
+    })
+}
+
+fn open_jobs() -> usize {
+    state::IMPORT_JOBS.with(|j| j.borrow().iter().filter(|(_, job)| job.finished_at.is_none()).count())
+}
+
+// Holds an import slot during the outcall. Dropped when the call returns or traps, so a failed
+// fetch gives its slot back.
+struct Reservation;
+
+impl Reservation {
+    fn take() -> Result<Reservation, String> {
+        let open = open_jobs() + FETCHING.get();
+        if open >= MAX_OPEN_JOBS {
+            return Err(format!("{} imports are still running; try again when one finishes.", open));
+        }
+        FETCHING.set(FETCHING.get() + 1);
+        Ok(Reservation)
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        FETCHING.set(FETCHING.get().saturating_sub(1));
+    }
+}
+
+// --- Fetching ---
+
+// Drops the headers (dates, request ids) that would differ between replicas and break consensus
+pub fn transform(args: TransformArgs) -> HttpResponse {
+    HttpResponse { status: args.response.status, headers: Vec::new(), body: args.response.body }
+}
+
+async fn fetch(url: &str) -> Result<String, String> {
+    let request = CanisterHttpRequestArgument {
+        url: url.to_string(),
+        method: HttpMethod::GET,
+        body: None,
+        max_response_bytes: Some(MAX_RESPONSE_BYTES),
+        transform: Some(TransformContext::from_name("transform_import_response".to_string(), Vec::new())),
+        headers: Vec::new(),
+    };
+    let (response,) = http_request(request, HTTP_REQUEST_CYCLES).await
+        .map_err(|(code, message)| format!("Could not fetch the file: {:?} {}", code, message))?;
+    if response.status < 200u32 || response.status >= 300u32 {
+        return Err(format!("Could not fetch the file (status {}).", response.status));
+    }
+    String::from_utf8(response.body).map_err(|_| "The file is not UTF-8 text.".to_string())
+}
+
+// /import_url <link>: fetches and checks the list, then queues it. Returns the summary.
+pub async fn start(url: String, admin: Principal) -> Result<String, String> {
+    let url = url.trim().to_string();
+    if !url.starts_with("https://") || url.len() > MAX_URL_LEN {
+        return Err(format!("Give an https:// link of at most {} characters.", MAX_URL_LEN));
+    }
+    // Kept until the job is stored below
+    let _slot = Reservation::take()?;
+    let body = fetch(&url).await?;
+    let parsed = parse(&url, &body)?;
+    if parsed.len() > MAX_ROWS {
+        return Err(format!("The file has {} rows; import at most {} at a time.", parsed.len(), MAX_ROWS));
+    }
+
+    let mut seen = BTreeSet::new();
+    let (mut rows, mut invalid, mut duplicates) = (Vec::new(), Vec::new(), 0);
+    with_known_texts(|known| {
+        for (line, parsed_row) in parsed.iter().enumerate() {
+            match parsed_row {
+                Ok(row) if !known.contains(&normalize(&row.text)) && seen.insert(normalize(&row.text)) => rows.push(row.clone()),
+                Ok(_) => duplicates += 1,
+                Err(e) => invalid.push(format!("row {}: {}", line + 1, e)),
+            }
+        }
+    });
+    let queued = rows.len();
+    let id = state::IMPORT_JOBS.with(|j| {
+        let mut jobs = j.borrow_mut();
+        let id = jobs.last_key_value().map_or(0, |(id, _)| id + 1);
+        let now = ic_cdk::api::time();
+        jobs.insert(id, ImportJob {
+            id,
+            url,
+            created_by: admin,
+            created_at: now,
+            rows,
+            fetched: parsed.len() as u32,
+            invalid: invalid.len() as u32,
+            duplicates,
+            imported: 0,
+            finished_at: (queued == 0).then_some(now),
+            correlation_id: Some(correlation::current()),
+        });
+        id
+    });
+
+    let mut summary = format!(
+        "📥 Import #{}: {} rows fetched, {} queued, {} duplicates and {} invalid skipped.",
+        id, parsed.len(), queued, duplicates, invalid.len()
+    );
+    if queued > 0 {
+        summary.push_str(&format!(" They're added {} at a time; check progress with /import_status {}.", CHUNK_SIZE, id));
+    }
+    if !invalid.is_empty() {
+        let shown: Vec<&str> = invalid.iter().take(5).map(String::as_str).collect();
+        summary.push_str(&format!("\nInvalid: {}{}", shown.join("; "), if invalid.len() > shown.len() { "; ..." } else { "" }));
+    }
+    Ok(summary)
+}
+
+// --- Import Job ---
+
+fn store(row: ImportRow, admin: Principal) -> Result<(), String> {
+    let difficulty = row.difficulty.unwrap_or_else(|| analysis::analyze_dare(&row.text).suggested_difficulty);
+    let id = crate::store_dare(row.text, difficulty, DareSource::Curated, Some(admin))?;
+    hints::set(id, row.hint)
+}
+
+// Called by the import timer: stores the next chunk of the oldest running job. Returns the
+// number of dares added.
+pub fn run() -> usize {
+    let Some(mut job) = state::IMPORT_JOBS.with(|j| j.borrow().iter().map(|(_, job)| job).find(|job| job.finished_at.is_none())) else {
+        if FETCHING.get() == 0 {
+            KNOWN_TEXTS.with(|k| k.borrow_mut().take());
+        }
+        return 0;
+    };
+    correlation::adopt(job.correlation_id.clone());
+    let chunk: Vec<ImportRow> = job.rows.drain(..job.rows.len().min(CHUNK_SIZE)).collect();
+    let mut added = 0;
+    for row in chunk {
+        if !with_known_texts(|known| known.insert(normalize(&row.text))) {
+            job.duplicates += 1;
+            continue;
+        }
+        match store(row, job.created_by) {
+            Ok(()) => added += 1,
+            Err(e) => {
+                correlation::log!("Import #{} skipped a row: {}", job.id, incidents::observe(e));
+                job.invalid += 1;
+            }
+        }
+    }
+    job.imported += added as u32;
+    if job.rows.is_empty() {
+        job.finished_at = Some(ic_cdk::api::time());
+        correlation::log!("Import #{} finished: {}.", job.id, describe_counts(&job));
+    }
+    state::IMPORT_JOBS.with(|j| j.borrow_mut().insert(job.id, job));
+    added
+}
+
+fn describe_counts(job: &ImportJob) -> String {
+    format!("{} of {} rows imported, {} duplicates, {} invalid", job.imported, job.fetched, job.duplicates, job.invalid)
+}
+
+pub fn status(job_id: u64) -> Result<String, String> {
+    let job = state::IMPORT_JOBS.with(|j| j.borrow().get(&job_id)).ok_or_else(|| format!("Import #{} not found.", job_id))?;
+    let state = if job.finished_at.is_some() { "finished".to_string() } else { format!("{} rows left", job.rows.len()) };
+    Ok(format!("📥 Import #{} from {} ({}): {}.", job.id, job.url, state, describe_counts(&job)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(rows: &[Result<ImportRow, String>]) -> Vec<Option<&str>> {
+        rows.iter().map(|row| row.as_ref().ok().map(|row| row.text.as_str())).collect()
+    }
+
+    #[test]
+    fn csv_handles_headers_quotes_and_bad_rows() {
+        let rows = parse_csv("hint,text,difficulty\n,\"Sing, loudly\",hard\n\"Say \"\"hi\"\"\",Wave,\n,,easy\n,Jump,extreme\n");
+        assert_eq!(texts(&rows), vec![Some("Sing, loudly"), Some("Wave"), None, None]);
+        let sing = rows[0].as_ref().unwrap();
+        assert!(matches!(sing.difficulty, Some(Difficulty::Hard)) && sing.hint.is_none());
+        assert_eq!(rows[1].as_ref().unwrap().hint.as_deref(), Some("Say \"hi\""));
+
+        let rows = parse_csv("Compliment a friend\nDo 20 push-ups,medium\n");
+        assert_eq!(texts(&rows), vec![Some("Compliment a friend"), Some("Do 20 push-ups")]);
+    }
+
+    #[test]
+    fn json_takes_strings_objects_and_a_dares_key() {
+        let rows = parse("https://x.io/list", r#"["Smile", {"text": "Run 5 km", "difficulty": "Hard", "hint": "Pace"}, 3]"#).unwrap();
+        assert_eq!(texts(&rows), vec![Some("Smile"), Some("Run 5 km"), None]);
+        let rows = parse("https://x.io/list.json?v=2", r#"{"dares": ["Smile"]}"#).unwrap();
+        assert_eq!(texts(&rows), vec![Some("Smile")]);
+        assert!(parse("https://x.io/list.json", "Smile").is_err());
+        assert!(parse("https://x.io/list", r#"{"items": []}"#).is_err());
+    }
+}
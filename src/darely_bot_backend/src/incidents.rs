@@ -0,0 +1,189 @@
+use crate::correlation;
+use crate::outbox;
+use crate::state;
+use crate::types::Incident;
+
+// --- Incidents ---
+// Errors nobody should see in normal operation (storage failures, records that don't decode,
+// replication arriving out of order) are created with `unexpected`, which marks them. The reply
+// helpers in formatting.rs pass every command result through `capture`: a marked error is
+// recorded under its signature (the operation plus the error with numbers blanked out, so
+// "Dare #5" and "Dare #6" count as one), with a count and the latest correlation id, and the
+// caller gets a short apology with the ref instead of internals. The first occurrence of a
+// signature is posted to the admin channel (the alert recipients when none is set); repeats
+// within the hour are summarized by the timer once the hour is up. Timers and background jobs
+// pass their errors through `observe` before logging them, and every record the quarantine moves
+// aside (see quarantine.rs) is recorded too.
+//
+// Panics trap, and a trap rolls back everything the message wrote, so they can't be counted
+// here; the panic hook logs them with the operation and correlation id and puts the ref in the
+// reject message.
+
+const MARKER: &str = "\u{1}unexpected\u{1}";
+const REPORT_EVERY_NANOS: u64 = 60 * 60 * 1_000_000_000;
+const MAX_SIGNATURE_LEN: usize = 200;
+const MAX_INCIDENTS: u64 = 500;
+
+// An error to capture as an incident when it reaches the reply helpers
+pub fn unexpected(error: impl std::fmt::Display) -> String {
+    format!("{}{}", MARKER, error)
+}
+
+fn signature(operation: &str, error: &str) -> String {
+    let mut blanked = String::new();
+    for c in error.chars() {
+        if !c.is_ascii_digit() {
+            blanked.push(c);
+        } else if !blanked.ends_with('#') {
+            blanked.push('#');
+        }
+    }
+    let mut signature = format!("{}: {}", operation, blanked);
+    if signature.len() > MAX_SIGNATURE_LEN {
+        let end = (0..=MAX_SIGNATURE_LEN).rev().find(|&i| signature.is_char_boundary(i)).unwrap_or(0);
+        signature.truncate(end);
+    }
+    signature
+}
+
+fn report(text: String) {
+    match state::get_config().incident_channel {
+        Some(chat_key) => outbox::queue_chat(&chat_key, text),
+        None => outbox::alert_admins(&text),
+    }
+}
+
+// Records an occurrence of `error` under the current operation
+pub fn record(error: &str) {
+    let now = ic_cdk::api::time();
+    let operation = correlation::operation();
+    let signature = signature(&operation, error);
+    correlation::log!("Unexpected error in {}: {}", operation, error);
+    let mut incident = state::INCIDENTS.with(|i| i.borrow().get(&signature)).unwrap_or_else(|| Incident {
+        signature: signature.clone(),
+        operation,
+        count: 0,
+        first_seen: now,
+        last_seen: now,
+        last_error: String::new(),
+        last_correlation_id: String::new(),
+        reported_at: None,
+        unreported: 0,
+    });
+    incident.count += 1;
+    incident.last_seen = now;
+    incident.last_error = error.to_string();
+    incident.last_correlation_id = correlation::current();
+    if incident.reported_at.is_none_or(|at| now.saturating_sub(at) >= REPORT_EVERY_NANOS) {
+        report(format!("🛑 Unexpected error in {} (ref {}): {}", incident.operation, incident.last_correlation_id, error));
+        incident.reported_at = Some(now);
+        incident.unreported = 0;
+    } else {
+        incident.unreported += 1;
+    }
+    state::INCIDENTS.with(|i| {
+        let mut incidents = i.borrow_mut();
+        // A flood of distinct signatures shouldn't grow the map without bound
+        if incidents.len() < MAX_INCIDENTS || incidents.contains_key(&signature) {
+            incidents.insert(signature, incident);
+        }
+    });
+}
+
+// Records a marked error and replaces it with an apology; other results pass through
+pub fn capture(result: Result<String, String>) -> Result<String, String> {
+    result.map_err(|error| match error.strip_prefix(MARKER) {
+        Some(error) => {
+            record(error);
+            format!("Something went wrong on our side (ref {}). The admins have been told.", correlation::current())
+        }
+        None => error,
+    })
+}
+
+// For errors that don't reach the reply helpers (timers, background jobs): records a marked
+// error and returns it without the marker, ready to log
+pub fn observe(error: String) -> String {
+    match error.strip_prefix(MARKER) {
+        Some(plain) => {
+            record(plain);
+            plain.to_string()
+        }
+        None => error,
+    }
+}
+
+// Timer: summarizes repeats that were held back during the last hour. Returns how many
+// signatures were reported.
+pub fn run() -> usize {
+    let now = ic_cdk::api::time();
+    let due: Vec<Incident> = state::INCIDENTS.with(|i| i.borrow().iter()
+        .map(|(_, incident)| incident)
+        .filter(|incident| incident.unreported > 0 && incident.reported_at.is_none_or(|at| now.saturating_sub(at) >= REPORT_EVERY_NANOS))
+        .collect());
+    for mut incident in due.iter().cloned() {
+        report(format!(
+            "🛑 {} happened {} more time(s) in the last hour ({} in total). Latest (ref {}): {}",
+            incident.signature, incident.unreported, incident.count, incident.last_correlation_id, incident.last_error
+        ));
+        incident.reported_at = Some(now);
+        incident.unreported = 0;
+        state::INCIDENTS.with(|i| i.borrow_mut().insert(incident.signature.clone(), incident));
+    }
+    due.len()
+}
+
+pub fn list() -> Vec<Incident> {
+    let mut incidents: Vec<Incident> = state::INCIDENTS.with(|i| i.borrow().iter().map(|(_, incident)| incident).collect());
+    incidents.sort_by_key(|incident| std::cmp::Reverse(incident.last_seen));
+    incidents
+}
+
+// Forgets a signature once it's dealt with; None clears them all
+pub fn clear(signature: Option<String>) -> Result<String, String> {
+    match signature {
+        Some(signature) => {
+            state::INCIDENTS.with(|i| i.borrow_mut().remove(&signature)).ok_or_else(|| "No incident with that signature.".to_string())?;
+            Ok("Incident cleared.".to_string())
+        }
+        None => {
+            let signatures: Vec<String> = state::INCIDENTS.with(|i| i.borrow().iter().map(|(signature, _)| signature).collect());
+            state::INCIDENTS.with(|i| {
+                let mut incidents = i.borrow_mut();
+                for signature in &signatures {
+                    incidents.remove(signature);
+                }
+            });
+            Ok(format!("{} incidents cleared.", signatures.len()))
+        }
+    }
+}
+
+// Logs panics with their operation and correlation id, and puts the ref in the reject message.
+// Installed from init and post_upgrade, after ic-cdk's own hook.
+pub fn install_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        let message = format!("Panicked in {}: {}", correlation::operation(), info);
+        correlation::log!("{}", message);
+        ic_cdk::api::trap(&format!("{} (ref {})", message, correlation::current()));
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signatures_ignore_numbers() {
+        assert_eq!(signature("/dare", "Dare #12 arrived before dare #3."), "/dare: Dare # arrived before dare #.");
+        assert_eq!(signature("/dare", "Dare #12 arrived"), signature("/dare", "Dare #7 arrived"));
+        assert_ne!(signature("/dare", "Dare #1"), signature("/submit", "Dare #1"));
+        assert!(signature("timer", &"é".repeat(300)).len() <= MAX_SIGNATURE_LEN);
+    }
+
+    #[test]
+    fn only_marked_errors_are_captured() {
+        assert_eq!(capture(Err("User not found.".to_string())), Err("User not found.".to_string()));
+        assert_eq!(capture(Ok("Done.".to_string())), Ok("Done.".to_string()));
+    }
+}
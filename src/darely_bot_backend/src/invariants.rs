@@ -0,0 +1,171 @@
+use crate::repository::{self, DareRepo, TaskRepo, UserRepo};
+use crate::state;
+use crate::types::{StateFinding, StateReport, StorablePrincipal, UserProfile};
+use std::collections::BTreeSet;
+
+// --- State Invariants ---
+// A maintenance job that scans the stable structures for records pointing at things that no
+// longer exist (or never did) and for profile fields that contradict each other. With
+// `repair` unset it only reports (dry run); with it set, each finding is fixed in place by
+// dropping or clearing the offending reference, never by inventing data.
+
+const MAX_REPORTED_FINDINGS: usize = 200;
+
+struct Scan {
+    repair: bool,
+    findings: Vec<StateFinding>,
+    total: u64,
+}
+
+impl Scan {
+    fn report(&mut self, check: &str, detail: String) {
+        self.total += 1;
+        if self.findings.len() < MAX_REPORTED_FINDINGS {
+            self.findings.push(StateFinding { check: check.to_string(), detail });
+        }
+    }
+}
+
+fn check_profile(scan: &mut Scan, user: &StorablePrincipal, profile: &mut UserProfile, dare_count: u64) -> bool {
+    let mut changed = false;
+    if let Some(dare_id) = profile.current_dare_id.filter(|&id| id >= dare_count) {
+        scan.report("current_dare", format!("{} is assigned dare #{}, which doesn't exist.", user.0, dare_id));
+        profile.current_dare_id = None;
+        profile.current_dare_text = None;
+        changed = true;
+    }
+    if let Some(submission_id) = profile.last_submission_id {
+        let owner = state::SUBMISSIONS.with(|s| s.borrow().get(&submission_id)).map(|submission| submission.user);
+        if owner != Some(user.0) {
+            scan.report("last_submission", format!("{}'s undoable submission #{} is missing or belongs to someone else.", user.0, submission_id));
+            profile.last_submission_id = None;
+            changed = true;
+        }
+    }
+    if let Some(blocked) = profile.blocked_dare_ids.as_mut() {
+        let before = blocked.len();
+        blocked.retain(|&id| id < dare_count);
+        if blocked.len() != before {
+            scan.report("blocked_dares", format!("{} blocks {} dare(s) that don't exist.", user.0, before - blocked.len()));
+            changed = true;
+        }
+    }
+    if let Some(task_id) = profile.current_redemption_task_id {
+        if repository::tasks().get(task_id).is_none() {
+            scan.report("redemption_task", format!("{} holds reward task #{}, which doesn't exist.", user.0, task_id));
+            profile.current_redemption_task_id = None;
+            changed = true;
+        }
+    }
+    let unique: BTreeSet<u32> = profile.redeemed_milestones.iter().copied().collect();
+    let valid: Vec<u32> = unique.into_iter().filter(|m| crate::REWARD_MILESTONES.contains(m)).collect();
+    if valid != profile.redeemed_milestones {
+        scan.report("redeemed_milestones", format!(
+            "{} has duplicate or unknown redeemed milestones {:?}.", user.0, profile.redeemed_milestones
+        ));
+        profile.redeemed_milestones = valid;
+        changed = true;
+    }
+    changed
+}
+
+fn check_profiles(scan: &mut Scan) {
+    let dare_count = repository::dares().len();
+    let mut profiles: Vec<(StorablePrincipal, UserProfile)> = Vec::new();
+    repository::users().scan(None, &mut |user, profile| {
+        profiles.push((StorablePrincipal(user), profile));
+        true
+    });
+    for (user, mut profile) in profiles {
+        let version = state::profile_version(&profile);
+        if check_profile(scan, &user, &mut profile, dare_count) && scan.repair {
+            state::put_profile_if_unchanged(&user, profile, version);
+        }
+    }
+}
+
+fn check_submissions(scan: &mut Scan) {
+    let dare_count = repository::dares().len();
+    let dangling: Vec<u64> = state::SUBMISSIONS.with(|s| {
+        s.borrow().iter()
+            .filter(|(_, submission)| submission.dare_id.is_some_and(|id| id >= dare_count))
+            .map(|(id, _)| id)
+            .collect()
+    });
+    for id in dangling {
+        scan.report("submission_dare", format!("Submission #{} refers to a dare that doesn't exist.", id));
+        if scan.repair {
+            if let Some(mut submission) = state::SUBMISSIONS.with(|s| s.borrow().get(&id)) {
+                submission.dare_id = None;
+                state::put_submission(submission);
+            }
+        }
+    }
+}
+
+fn check_review_queue(scan: &mut Scan) {
+    // A task is orphaned once its submission is gone or already reviewed
+    let orphaned: Vec<u64> = state::REVIEW_QUEUE.with(|q| {
+        q.borrow().iter()
+            .map(|(id, _)| id)
+            .filter(|id| state::SUBMISSIONS.with(|s| s.borrow().get(id)).is_none_or(|submission| submission.review.is_some()))
+            .collect()
+    });
+    for id in orphaned {
+        scan.report("review_queue", format!("Review task for submission #{} has nothing left to review.", id));
+        if scan.repair {
+            state::REVIEW_QUEUE.with(|q| q.borrow_mut().remove(&id));
+        }
+    }
+}
+
+fn check_likes(scan: &mut Scan) {
+    let dare_count = repository::dares().len();
+    let orphaned: Vec<u64> = state::DARE_LIKES.with(|l| {
+        l.borrow().iter().map(|(id, _)| id).filter(|&id| id >= dare_count).collect()
+    });
+    for id in orphaned {
+        scan.report("dare_likes", format!("Likes are recorded for dare #{}, which doesn't exist.", id));
+        if scan.repair {
+            state::DARE_LIKES.with(|l| l.borrow_mut().remove(&id));
+        }
+    }
+}
+
+fn check_enrollments(scan: &mut Scan) {
+    let campaigns: BTreeSet<u64> = state::CAMPAIGNS.with(|c| c.borrow().iter().map(|(id, _)| id).collect());
+    let all: Vec<_> = state::ENROLLMENTS.with(|e| e.borrow().iter().collect());
+    for (user, mut enrollments) in all {
+        let before = enrollments.entries.len();
+        enrollments.entries.retain(|entry| campaigns.contains(&entry.campaign_id));
+        if enrollments.entries.len() == before {
+            continue;
+        }
+        scan.report("enrollments", format!("{} is enrolled in {} campaign(s) that don't exist.", user.0, before - enrollments.entries.len()));
+        if scan.repair {
+            state::ENROLLMENTS.with(|e| {
+                let mut map = e.borrow_mut();
+                if enrollments.entries.is_empty() {
+                    map.remove(&user);
+                } else {
+                    map.insert(user, enrollments);
+                }
+            });
+        }
+    }
+}
+
+pub fn validate(repair: bool) -> StateReport {
+    let mut scan = Scan { repair, findings: Vec::new(), total: 0 };
+    check_profiles(&mut scan);
+    check_submissions(&mut scan);
+    check_review_queue(&mut scan);
+    check_likes(&mut scan);
+    check_enrollments(&mut scan);
+    StateReport {
+        dry_run: !repair,
+        total_findings: scan.total,
+        repaired: if repair { scan.total } else { 0 },
+        findings: scan.findings,
+    }
+}
@@ -0,0 +1,79 @@
+use crate::gallery;
+use crate::outbox;
+use crate::state::Memory;
+use crate::types::{BuddyInvite, StorablePrincipal};
+use candid::Principal;
+use ic_stable_structures::BTreeMap as StableBTreeMap;
+use std::cell::RefCell;
+use std::thread::LocalKey;
+
+// --- Invites ---
+// The consent step shared by buddy pairings and duos: a member's request is stored under the
+// invitee, who has INVITE_TTL_NANOS to accept or decline it. Each invitee holds one request at a
+// time, and asking someone who already asked you counts as accepting. Sending the same request
+// twice changes nothing (no new timestamp, no second DM). What an accepted request creates is up
+// to the caller.
+
+const INVITE_TTL_NANOS: u64 = 3 * 24 * 60 * 60 * 1_000_000_000;
+
+pub struct Consent {
+    pub invites: &'static LocalKey<RefCell<StableBTreeMap<StorablePrincipal, BuddyInvite, Memory>>>,
+    pub noun: &'static str, // "buddy request", "duo request"
+}
+
+pub enum Request {
+    Sent,
+    Mutual, // The target had already asked the user: accept theirs
+}
+
+impl Consent {
+    pub fn open(&self, invitee: Principal, now: u64) -> Option<BuddyInvite> {
+        self.invites.with(|i| i.borrow().get(&StorablePrincipal(invitee)))
+            .filter(|invite| now.saturating_sub(invite.invited_at) < INVITE_TTL_NANOS)
+    }
+
+    // Stores `user`'s request to `target` and DMs them `message`
+    pub fn request(&self, user: Principal, target: Principal, now: u64, message: String) -> Result<Request, String> {
+        if self.open(user, now).is_some_and(|invite| invite.from == target) {
+            return Ok(Request::Mutual);
+        }
+        match self.open(target, now) {
+            Some(invite) if invite.from == user => {
+                return Err(format!("You already sent {} a {}. It's open until they answer or it expires.", gallery::display_name(target), self.noun));
+            }
+            Some(_) => return Err(format!("{} already has a pending {}.", gallery::display_name(target), self.noun)),
+            None => {}
+        }
+        self.invites.with(|i| i.borrow_mut().insert(StorablePrincipal(target), BuddyInvite { from: user, invited_at: now }));
+        outbox::queue(target, message, None);
+        Ok(Request::Sent)
+    }
+
+    // Removes and returns the invitee's open request, for accepting it
+    pub fn take(&self, invitee: Principal, now: u64) -> Result<BuddyInvite, String> {
+        let invite = self.open(invitee, now).ok_or_else(|| format!("You have no pending {}.", self.noun))?;
+        self.invites.with(|i| i.borrow_mut().remove(&StorablePrincipal(invitee)));
+        Ok(invite)
+    }
+
+    pub fn decline(&self, user: Principal) -> Result<String, String> {
+        let invite = self.take(user, ic_cdk::api::time())?;
+        outbox::queue(invite.from, format!("{} declined your {}.", gallery::display_name(user), self.noun), None);
+        Ok(format!("Declined {}'s {}.", gallery::display_name(invite.from), self.noun))
+    }
+
+    // Timer: drops expired requests. Returns how many were removed.
+    pub fn purge(&self, now: u64) -> usize {
+        self.invites.with(|i| {
+            let mut invites = i.borrow_mut();
+            let expired: Vec<StorablePrincipal> = invites.iter()
+                .filter(|(_, invite)| now.saturating_sub(invite.invited_at) >= INVITE_TTL_NANOS)
+                .map(|(key, _)| key)
+                .collect();
+            for key in &expired {
+                invites.remove(key);
+            }
+            expired.len()
+        })
+    }
+}
@@ -0,0 +1,332 @@
+use crate::correlation;
+use crate::outbox;
+use crate::state;
+use crate::types::{ICRC3Value, LedgerAudit, LedgerCheckpoint, StorablePrincipal, SystemBalances};
+use candid::Principal;
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+
+// --- Double-Entry Ledger ---
+// Every points mutation is a posting that debits one account and credits another by the same
+// amount, so the balances of all accounts always sum to zero. Users and escrow can't go below
+// zero; the treasury issues points and goes negative by everything it has minted; the burn
+// account only receives. User balances live in POINT_BALANCES, the system accounts in
+// LEDGER_ACCOUNTS. A periodic self-audit checks the zero-sum invariant and replays the ICRC-3
+// block log against the stored balances, alerting admins on any mismatch.
+//
+// The replay is incremental: LEDGER_CHECKPOINT records how far the block log has been replayed
+// and REPLAYED_BALANCES what it implies for each user, so an audit only reads the blocks appended
+// since the last one (at most MAX_REPLAY_BLOCKS; a backlog is worked off over several audits).
+// Users those blocks touched are compared one by one, everyone else through the totals.
+
+const ALERT_COOLDOWN_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+const MAX_REPORTED_PROBLEMS: usize = 20;
+const MAX_REPLAY_BLOCKS: u64 = 20_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Account {
+    User(Principal),
+    Treasury,
+    Escrow,
+    Burn,
+}
+
+// Audited together with the user accounts
+const SYSTEM_ACCOUNTS: [Account; 3] = [Account::Treasury, Account::Escrow, Account::Burn];
+
+thread_local! {
+    static LAST_AUDIT: RefCell<Option<LedgerAudit>> = const { RefCell::new(None) };
+    static LAST_ALERT_AT: RefCell<u64> = const { RefCell::new(0) };
+}
+
+fn system_balances() -> SystemBalances {
+    state::LEDGER_ACCOUNTS.with(|cell| cell.borrow().get().clone())
+}
+
+fn set_system_balances(balances: SystemBalances) {
+    state::LEDGER_ACCOUNTS.with(|cell| cell.borrow_mut().set(balances).expect("Failed to write ledger accounts"));
+}
+
+fn user_balance(user: Principal) -> u64 {
+    state::POINT_BALANCES.with(|b| b.borrow().get(&StorablePrincipal(user))).unwrap_or(0)
+}
+
+fn set_user_balance(user: Principal, balance: u64) {
+    state::POINT_BALANCES.with(|b| {
+        let mut balances = b.borrow_mut();
+        if balance == 0 {
+            balances.remove(&StorablePrincipal(user));
+        } else {
+            balances.insert(StorablePrincipal(user), balance);
+        }
+    });
+}
+
+pub fn balance(account: Account) -> i128 {
+    let system = system_balances();
+    match account {
+        Account::User(user) => user_balance(user) as i128,
+        Account::Treasury => system.treasury as i128,
+        Account::Escrow => system.escrow as i128,
+        Account::Burn => system.burned as i128,
+    }
+}
+
+fn describe(account: Account) -> String {
+    match account {
+        Account::User(user) => user.to_string(),
+        Account::Treasury => "treasury".to_string(),
+        Account::Escrow => "escrow".to_string(),
+        Account::Burn => "burn".to_string(),
+    }
+}
+
+// Moves `amount` from one account to another. Nothing is written unless both sides succeed.
+pub fn post(from: Account, to: Account, amount: u64) -> Result<(), String> {
+    if amount == 0 {
+        return Err("Amount must be positive.".to_string());
+    }
+    if from == to {
+        return Err("A posting needs two different accounts.".to_string());
+    }
+    if from == Account::Burn {
+        return Err("Burned points can't be moved.".to_string());
+    }
+    let mut system = system_balances();
+    let mut users: Vec<(Principal, u64)> = Vec::new();
+    match from {
+        Account::User(user) => {
+            let current = user_balance(user);
+            let remaining = current.checked_sub(amount)
+                .ok_or_else(|| format!("Insufficient points: the balance is {}.", current))?;
+            users.push((user, remaining));
+        }
+        Account::Treasury => {
+            system.treasury = system.treasury.checked_sub_unsigned(amount).ok_or("Treasury overflow.")?;
+        }
+        Account::Escrow => {
+            system.escrow = system.escrow.checked_sub(amount)
+                .ok_or_else(|| format!("Insufficient escrow: the balance is {}.", system.escrow))?;
+        }
+        Account::Burn => unreachable!(),
+    }
+    match to {
+        Account::User(user) => {
+            users.push((user, user_balance(user).checked_add(amount).ok_or("Balance overflow.")?));
+        }
+        Account::Treasury => {
+            system.treasury = system.treasury.checked_add_unsigned(amount).ok_or("Treasury overflow.")?;
+        }
+        Account::Escrow => system.escrow = system.escrow.checked_add(amount).ok_or("Escrow overflow.")?,
+        Account::Burn => system.burned = system.burned.checked_add(amount).ok_or("Burn overflow.")?,
+    }
+    for (user, balance) in users {
+        set_user_balance(user, balance);
+    }
+    set_system_balances(system);
+    Ok(())
+}
+
+// --- Block Replay ---
+
+fn field<'a>(map: &'a ICRC3Value, key: &str) -> Option<&'a ICRC3Value> {
+    match map {
+        ICRC3Value::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+        _ => None,
+    }
+}
+
+fn owner(account: Option<&ICRC3Value>) -> Option<Principal> {
+    match account? {
+        ICRC3Value::Array(parts) => match parts.first()? {
+            ICRC3Value::Blob(bytes) => Principal::try_from_slice(bytes).ok(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn amount(tx: &ICRC3Value) -> Option<u64> {
+    match field(tx, "amt")? {
+        ICRC3Value::Nat(nat) => u64::try_from(&nat.0).ok(),
+        _ => None,
+    }
+}
+
+// The user a block debits and the user it credits (None = the treasury on a mint, the burn
+// account on a burn) and the amount
+fn movement(index: u64, block: &ICRC3Value) -> Result<(Option<Principal>, Option<Principal>, u64), String> {
+    let btype = match field(block, "btype") {
+        Some(ICRC3Value::Text(btype)) => btype.as_str(),
+        _ => "",
+    };
+    let tx = field(block, "tx").ok_or_else(|| format!("Block {} has no transaction.", index))?;
+    let amt = amount(tx).ok_or_else(|| format!("Block {} has no readable amount.", index))?;
+    match (btype, owner(field(tx, "from")), owner(field(tx, "to"))) {
+        ("1mint", _, Some(to)) => Ok((None, Some(to), amt)),
+        ("1burn", Some(from), _) => Ok((Some(from), None, amt)),
+        ("1xfer", Some(from), Some(to)) => Ok((Some(from), Some(to), amt)),
+        _ => Err(format!("Block {} is not a readable {} block.", index, btype)),
+    }
+}
+
+fn replayed_balance(user: Principal) -> u64 {
+    state::REPLAYED_BALANCES.with(|b| b.borrow().get(&StorablePrincipal(user))).unwrap_or(0)
+}
+
+fn set_replayed_balance(user: Principal, balance: u64) {
+    state::REPLAYED_BALANCES.with(|b| {
+        let mut balances = b.borrow_mut();
+        if balance == 0 {
+            balances.remove(&StorablePrincipal(user));
+        } else {
+            balances.insert(StorablePrincipal(user), balance);
+        }
+    });
+}
+
+// Replays the blocks after the checkpoint, at most MAX_REPLAY_BLOCKS, and moves it forward.
+// Returns the users those blocks touched and how many blocks were replayed.
+fn catch_up(checkpoint: &mut LedgerCheckpoint) -> (BTreeSet<Principal>, u64) {
+    let blocks: Vec<(u64, ICRC3Value)> = state::BLOCKS.with(|blocks| {
+        blocks.borrow().range(checkpoint.next_block..).take(MAX_REPLAY_BLOCKS as usize).collect()
+    });
+    let mut touched = BTreeSet::new();
+    for (index, block) in &blocks {
+        checkpoint.next_block = index + 1;
+        let (from, to, amt) = match movement(*index, block) {
+            Ok(movement) => movement,
+            Err(problem) => {
+                if checkpoint.problems.len() < MAX_REPORTED_PROBLEMS {
+                    checkpoint.problems.push(problem);
+                }
+                continue;
+            }
+        };
+        match from {
+            Some(user) => {
+                let balance = replayed_balance(user);
+                if balance < amt && checkpoint.problems.len() < MAX_REPORTED_PROBLEMS {
+                    checkpoint.problems.push(format!("Block {} takes {} points from {}, who only had {}.", index, amt, user, balance));
+                }
+                set_replayed_balance(user, balance.saturating_sub(amt));
+                touched.insert(user);
+            }
+            None => checkpoint.minted = checkpoint.minted.saturating_add(amt),
+        }
+        match to {
+            Some(user) => {
+                set_replayed_balance(user, replayed_balance(user).saturating_add(amt));
+                touched.insert(user);
+            }
+            None => checkpoint.burned = checkpoint.burned.saturating_add(amt),
+        }
+    }
+    (touched, blocks.len() as u64)
+}
+
+// Mint and burn totals of the whole block log
+fn replay_totals() -> (u64, u64) {
+    let (mut minted, mut burned) = (0u64, 0u64);
+    state::BLOCKS.with(|blocks| {
+        for (index, block) in blocks.borrow().iter() {
+            match movement(index, &block) {
+                Ok((None, _, amt)) => minted = minted.saturating_add(amt),
+                Ok((_, None, amt)) => burned = burned.saturating_add(amt),
+                _ => {}
+            }
+        }
+    });
+    (minted, burned)
+}
+
+// Ledgers that predate the system accounts start them from the block log
+pub fn seed_system_accounts() {
+    let unseeded = system_balances() == SystemBalances::default();
+    if unseeded && state::BLOCKS.with(|blocks| !blocks.borrow().is_empty()) {
+        let (minted, burned) = replay_totals();
+        set_system_balances(SystemBalances {
+            treasury: -(minted as i64),
+            escrow: 0,
+            burned,
+        });
+    }
+}
+
+// --- Self-Audit ---
+
+pub fn audit() -> LedgerAudit {
+    let mut problems = Vec::new();
+    let user_total: i128 = state::POINT_BALANCES.with(|b| b.borrow().iter().map(|(_, balance)| balance as i128).sum());
+    let system_total: i128 = SYSTEM_ACCOUNTS.iter().map(|&account| balance(account)).sum();
+    if user_total + system_total != 0 {
+        problems.push(format!(
+            "Balances don't sum to zero: users {} + system {} = {}.",
+            user_total, system_total, user_total + system_total
+        ));
+    }
+
+    let mut checkpoint = state::LEDGER_CHECKPOINT.with(|c| c.borrow().get().clone());
+    let (touched, replayed) = catch_up(&mut checkpoint);
+    state::LEDGER_CHECKPOINT.with(|c| c.borrow_mut().set(checkpoint.clone())).expect("Failed to save the ledger checkpoint");
+    problems.extend(checkpoint.problems.iter().cloned());
+    let pending = state::BLOCKS.with(|blocks| blocks.borrow().len()).saturating_sub(checkpoint.next_block);
+    // Balances are only comparable with the replay once it has reached the tip
+    if pending == 0 {
+        for user in touched {
+            let (expected, actual) = (replayed_balance(user), user_balance(user));
+            if expected != actual {
+                problems.push(format!("{} holds {} points but the block log says {}.", user, actual, expected));
+            }
+        }
+        let (minted, burned) = (checkpoint.minted as i128, checkpoint.burned as i128);
+        if user_total != minted - burned {
+            problems.push(format!("Users hold {} points but the block log says {}.", user_total, minted - burned));
+        }
+        // Escrow movements aren't logged as blocks, so only mints and burns can be checked here
+        let treasury = balance(Account::Treasury);
+        if treasury + balance(Account::Escrow) != -minted {
+            problems.push(format!("Treasury and escrow hold {} but {} points were minted.", treasury + balance(Account::Escrow), minted));
+        }
+        if balance(Account::Burn) != burned {
+            problems.push(format!("The burn account holds {} but {} points were burned.", balance(Account::Burn), burned));
+        }
+    }
+    problems.truncate(MAX_REPORTED_PROBLEMS);
+
+    let report = LedgerAudit {
+        checked_at: ic_cdk::api::time(),
+        ok: problems.is_empty(),
+        user_total: user_total as u64,
+        accounts: SYSTEM_ACCOUNTS.iter().map(|&account| (describe(account), balance(account) as i64)).collect(),
+        blocks_replayed: replayed,
+        blocks_pending: pending,
+        problems,
+    };
+    LAST_AUDIT.with(|last| *last.borrow_mut() = Some(report.clone()));
+    report
+}
+
+pub fn last_audit() -> Option<LedgerAudit> {
+    LAST_AUDIT.with(|last| last.borrow().clone())
+}
+
+// Timer entry point: audits and alerts admins about a mismatch at most once a day
+pub fn run_audit() {
+    let report = audit();
+    if report.ok {
+        return;
+    }
+    correlation::log!("Ledger audit failed: {}", report.problems.join(" "));
+    let now = ic_cdk::api::time();
+    let due = LAST_ALERT_AT.with(|last| now.saturating_sub(*last.borrow()) >= ALERT_COOLDOWN_NANOS);
+    if due {
+        LAST_ALERT_AT.with(|last| *last.borrow_mut() = now);
+        let mut text = format!("⚠️ Points ledger audit found {} problem(s):", report.problems.len());
+        for problem in &report.problems {
+            text.push_str("\n- ");
+            text.push_str(problem);
+        }
+        outbox::alert_admins(&text);
+    }
+}
@@ -1,174 +1,2931 @@
 // Declare modules
+mod accounts;
+mod achievements;
+mod analysis;
+mod api;
+mod api_keys;
+mod api_version;
+mod appeals;
+mod bans;
+mod blocklist;
+mod buddies;
+mod campaigns;
+mod certificates;
+mod certification;
+mod changelog;
+mod checkin;
+mod chat_members;
+mod chat_scope;
+mod commands;
+mod config;
+mod correlation;
+mod creators;
+mod custom_dares;
+mod demo;
+mod cors;
 mod types;
 mod state;
 mod llm;
+mod llm_usage;
+mod listing;
+mod marketplace;
+mod moderation;
+mod outbox;
+mod pauses;
+mod popularity;
+mod pins;
+mod points;
+mod prefill;
+mod presets;
+mod proposals;
+mod preflight;
+mod quarantine;
+mod purge;
+mod encoding;
+mod environment;
+mod duos;
+mod invites;
+mod escalation;
+mod events;
+mod exclusions;
+mod features;
+mod formatting;
+mod gallery;
+mod hardcore;
+mod hints;
+mod history;
+mod icrc3;
+mod identity;
+mod imports;
+mod incidents;
+mod invariants;
+mod ledger;
+mod membership;
+mod mentions;
+mod queue_alerts;
+mod quests;
+mod randomness;
+mod ratings;
+mod ranking;
+mod recap;
+mod registration;
+mod registry;
+mod replication;
+mod repository;
+mod rewards;
+mod safety;
+mod scheduler;
+mod roadmap;
+mod router;
+mod selection;
+mod sessions;
+mod setup;
+mod submissions;
+mod suggestions;
+mod telemetry;
+mod templates;
+mod themes;
+mod timezone;
+mod truths;
+mod vouches;
 
 // Use items from modules
-use types::{Difficulty, StorablePrincipal, UserProfile};
-use state::{USER_PROFILES, DARE_REPOSITORY}; // Access state directly or via helper functions if defined
-use llm::fetch_llm_dare; // Import the LLM interaction function
+use types::{
+    CachedGroupMetadata, ChatConfig, Dare, DareSource, DayWindow, Difficulty, HistoryEntry, HttpRequest, HttpResponse, ModeratorStats,
+    PendingDare, ReviewItem, ReviewSla, SessionToken, TrendingDare, SourceMix, StorablePrincipal, Suggestion, UserProfile,
+};
 
+use repository::{DareRepo, UserRepo};
 use ic_cdk::api::caller;
+use ic_cdk::api::management_canister::http_request;
 use ic_cdk::{init, post_upgrade, pre_upgrade, query, update};
-use std::collections::BTreeSet; // Keep for redeem_reward logic
+use std::time::Duration;
 
 // --- Constants (Can also live in state.rs or a config.rs) ---
-const MAX_LEADERBOARD_SIZE: usize = 20;
 const REWARD_MILESTONES: &[u32] = &[3, 7, 15, 30];
+const MAX_DARE_TEXT_LEN: usize = 500; // Keeps Dare well within its 1024-byte storage bound
+const PURGE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+const REGISTRY_REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const CERTIFICATION_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+const REVIEW_MAINTENANCE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const CAMPAIGN_INTERVAL: Duration = Duration::from_secs(60);
+const LEDGER_AUDIT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const THEME_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const LEADERBOARD_INTERVAL: Duration = Duration::from_secs(30);
+const WAITLIST_INTERVAL: Duration = Duration::from_secs(60);
+const PAUSE_INTERVAL: Duration = Duration::from_secs(60);
+const BUDDY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const EVENT_INTERVAL: Duration = Duration::from_secs(10 * 60);
+const HARDCORE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const STREAK_EXPIRY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const IMPORT_INTERVAL: Duration = Duration::from_secs(30);
+const PREFILL_INTERVAL: Duration = Duration::from_secs(60);
+const LLM_SPEND_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const ESCALATION_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const REPLICATION_INTERVAL: Duration = Duration::from_secs(30);
+const INCIDENT_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const RESEED_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const DIGEST_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const DEFAULT_HISTORY_LIMIT: u32 = 20;
+const MAX_HISTORY_LIMIT: u32 = 100;
+const PENDING_DARE_TIMEOUT_NANOS: u64 = 30 * 60 * 1_000_000_000;
+
+// --- Helpers ---
+
+// Admin endpoints are restricted to canister controllers
+fn ensure_admin() -> Result<(), String> {
+    if ic_cdk::api::is_controller(&caller()) {
+        Ok(())
+    } else {
+        Err("Only admins can use this command.".to_string())
+    }
+}
+
+// Appends a dare to the repository and returns its id. A custom dare's creator is also its issuer.
+fn store_dare(text: String, difficulty: Difficulty, source: DareSource, creator: Option<candid::Principal>) -> Result<u64, String> {
+    let issued_by = creator.filter(|_| source == DareSource::Custom);
+    let id = repository::dares().push(Dare {
+        id: 0, text, difficulty, source, weight: selection::DEFAULT_DARE_WEIGHT, issued_by, hint: None,
+        creator, created_at: Some(ic_cdk::api::time()), min_level: None, min_streak: None,
+    })?;
+    if let Some(creator) = creator {
+        creators::note_created(creator);
+    }
+    Ok(id)
+}
+
+// Records the dare a user was just given (in `chat_id`, if any) as their active dare
+fn assign_dare(user: &StorablePrincipal, dare_id: Option<u64>, chat_id: Option<&str>, rendered: Option<String>) {
+    // A missing profile just means there is nobody to assign the dare to
+    let _ = state::update_profile(user.0, |profile| {
+        profile.current_dare_id = dare_id;
+        profile.current_dare_chat = chat_id.map(str::to_string);
+        profile.current_dare_text = rendered.clone();
+        Ok(())
+    });
+}
+
+// Assigns a stored dare with its template placeholders filled in and returns the text to show
+fn assign_stored_dare(user: &StorablePrincipal, dare: Dare, chat_id: Option<&str>) -> String {
+    let rendered = templates::render_dare(&dare, user.0, chat_id);
+    assign_dare(user, Some(dare.id), chat_id, rendered.clone());
+    rendered.unwrap_or(dare.text)
+}
+
+// /dare's picks: assigned, or proposed for /accept when acceptance is on (see proposals.rs).
+// `source` is the pin or stock the pick came from. Returns the text to show.
+fn offer_dare(
+    user: &StorablePrincipal, dare_id: Option<u64>, chat_id: Option<&str>, rendered: Option<String>, text: String, propose: bool,
+    source: Option<types::ProposalSource>,
+) -> String {
+    if !propose {
+        assign_dare(user, dare_id, chat_id, rendered);
+        proposals::use_up(user.0, dare_id, source.as_ref());
+        return text;
+    }
+    let proposal = types::DareProposal { dare_id, chat_id: chat_id.map(str::to_string), rendered, text, proposed_at: ic_cdk::api::time(), source };
+    proposals::propose(user.0, proposal)
+}
+
+fn offer_stored_dare(user: &StorablePrincipal, dare: Dare, chat_id: Option<&str>, propose: bool, source: Option<types::ProposalSource>) -> String {
+    let rendered = templates::render_dare(&dare, user.0, chat_id);
+    let text = rendered.clone().unwrap_or(dare.text);
+    offer_dare(user, Some(dare.id), chat_id, rendered, text, propose, source)
+}
+
+// Applies a weight change to a stored dare and returns the new weight
+fn adjust_dare_weight(dare_id: u64, adjust: fn(u32) -> u32) -> Result<u32, String> {
+    let dares = repository::dares();
+    let mut dare = dares.get(dare_id).ok_or_else(|| format!("Dare #{} not found.", dare_id))?;
+    dare.weight = adjust(dare.weight);
+    dares.set(&dare)?;
+    Ok(dare.weight)
+}
 
 // --- Initialization and Upgrades ---
 
+// A warm standby or read replica (see replication.rs) mirrors the primary, so its timers stay idle
+// until it's promoted
+fn set_timer_interval(interval: Duration, mut func: impl FnMut() + 'static) {
+    ic_cdk_timers::set_timer_interval(interval, move || {
+        if !replication::is_standby() {
+            correlation::begin();
+            correlation::set_operation("timer");
+            func();
+        }
+    });
+}
+
+// Timers don't survive upgrades, so they are (re)started from both init and post_upgrade
+fn start_timers() {
+    set_timer_interval(PURGE_INTERVAL, || {
+        let purged = purge::run();
+        if purged.total() > 0 {
+            correlation::log!("Purged stale state: {}.", purged.describe());
+        }
+    });
+    set_timer_interval(REVIEW_MAINTENANCE_INTERVAL, || {
+        let auto_reviewed = moderation::apply_sla();
+        if auto_reviewed > 0 {
+            correlation::log!("Auto-reviewed {} overdue submissions.", auto_reviewed);
+        }
+        let reassigned = moderation::reassign_stale();
+        if reassigned > 0 {
+            correlation::log!("Reassigned {} stale review claims.", reassigned);
+        }
+        if queue_alerts::run() {
+            correlation::log!("Alerted admins about the moderation backlog.");
+        }
+    });
+    set_timer_interval(CAMPAIGN_INTERVAL, || {
+        let queued = campaigns::run();
+        if queued > 0 {
+            correlation::log!("Queued {} campaign messages.", queued);
+        }
+    });
+    set_timer_interval(LEDGER_AUDIT_INTERVAL, ledger::run_audit);
+    set_timer_interval(THEME_INTERVAL, || {
+        let changed = themes::run();
+        if changed > 0 {
+            correlation::log!("Switched {} seasonal themes on or off.", changed);
+        }
+    });
+    // Derived from mirrored state, so read replicas (and standbys) keep it fresh too
+    ic_cdk_timers::set_timer_interval(LEADERBOARD_INTERVAL, ranking::tick);
+    set_timer_interval(WAITLIST_INTERVAL, || {
+        let admitted = registration::admit_waitlisted();
+        if admitted > 0 {
+            correlation::log!("Admitted {} waitlisted users.", admitted);
+        }
+    });
+    set_timer_interval(PAUSE_INTERVAL, || {
+        let resumed = pauses::resume_expired();
+        if resumed > 0 {
+            correlation::log!("Resumed {} chats whose pause expired.", resumed);
+        }
+    });
+    set_timer_interval(BUDDY_INTERVAL, || {
+        let queued = buddies::run();
+        if queued > 0 {
+            correlation::log!("Queued {} buddy notifications.", queued);
+        }
+        let expired = duos::purge_invites();
+        if expired > 0 {
+            correlation::log!("Dropped {} expired duo requests.", expired);
+        }
+    });
+    set_timer_interval(EVENT_INTERVAL, || {
+        let changed = events::run();
+        if changed > 0 {
+            correlation::log!("Settled {} global events.", changed);
+        }
+    });
+    set_timer_interval(HARDCORE_INTERVAL, || {
+        let lapsed = hardcore::run();
+        if lapsed > 0 {
+            correlation::log!("{} hardcore players lapsed.", lapsed);
+        }
+    });
+    set_timer_interval(DIGEST_INTERVAL, || {
+        let posted = gallery::run_digests();
+        if posted > 0 {
+            correlation::log!("Posted {} weekly digests.", posted);
+        }
+    });
+    set_timer_interval(STREAK_EXPIRY_INTERVAL, || {
+        let expired = scheduler::run();
+        if expired > 0 {
+            correlation::log!("{} streaks expired.", expired);
+        }
+    });
+    set_timer_interval(ESCALATION_INTERVAL, || {
+        let lapsed = escalation::run();
+        if lapsed > 0 {
+            correlation::log!("{} escalations ran out.", lapsed);
+        }
+    });
+    set_timer_interval(IMPORT_INTERVAL, || {
+        let added = imports::run();
+        if added > 0 {
+            correlation::log!("Imported {} dares from a URL.", added);
+        }
+    });
+    set_timer_interval(PREFILL_INTERVAL, prefill::tick);
+    set_timer_interval(LLM_SPEND_INTERVAL, || {
+        if llm_usage::run() {
+            correlation::log!("Sent the monthly LLM spend summary.");
+        }
+    });
+    set_timer_interval(INCIDENT_INTERVAL, || {
+        let reported = incidents::run();
+        if reported > 0 {
+            correlation::log!("Summarized {} repeated incidents.", reported);
+        }
+    });
+    // The entropy pool is seeded right away (raw_rand can't be called from init) and then hourly
+    ic_cdk_timers::set_timer(Duration::ZERO, || ic_cdk::spawn(randomness::reseed()));
+    set_timer_interval(RESEED_INTERVAL, || ic_cdk::spawn(correlation::traced(randomness::reseed())));
+    set_timer_interval(REGISTRY_REFRESH_INTERVAL, || ic_cdk::spawn(correlation::traced(registry::refresh_stale())));
+    // Not through set_timer_interval: a standby's only job is to mirror, and promotion needs no restart
+    ic_cdk_timers::set_timer_interval(REPLICATION_INTERVAL, replication::tick);
+    // Certified REST responses (leaderboard, stats, ...) are re-rendered periodically, on read replicas too
+    ic_cdk_timers::set_timer_interval(CERTIFICATION_REFRESH_INTERVAL, router::refresh_certified_routes);
+}
+
 #[init]
-fn init() {
+fn init(args: Option<types::InitOrUpgradeArgs>) {
     // Canister initialization logic
-    ic_cdk::println!("Darely Bot Canister Initialized (LLM Version - Refactored).");
+    incidents::install_panic_hook();
+    environment::apply(args);
+    ic_cdk::println!("Darely Bot Canister Initialized (LLM Version - Refactored, {}).", environment::current().as_str());
     // Note: Static dare initialization is removed as get_dare now uses LLM.
-    // If you add fallback logic using DARE_REPOSITORY, initialize it here.
+    // If you add fallback logic using the dare repository, initialize it here.
+    membership::rebuild();
+    ranking::rebuild_index();
+    scheduler::rebuild_index();
+    escalation::rebuild_index();
+    pauses::rebuild_index();
+    start_timers();
+    icrc3::certify_tip();
+    router::refresh_certified_routes();
 }
 
 #[pre_upgrade]
 fn pre_upgrade() {
     // Logic to run before upgrade (stable structures handle state automatically)
     ic_cdk::println!("Running pre_upgrade...");
+    preflight::ensure_upgrade_allowed();
 }
 
 #[post_upgrade]
-fn post_upgrade() {
+fn post_upgrade(args: Option<types::InitOrUpgradeArgs>) {
     // Logic to run after upgrade (stable structures handle state automatically)
     ic_cdk::println!("Running post_upgrade...");
+    incidents::install_panic_hook();
+    // Before anything reads them: cells that no longer decode are reset (see quarantine.rs)
+    quarantine::check_cells();
+    environment::apply(args);
+    // Map records that no longer decode are moved aside in batches; scans skip them meanwhile
+    quarantine::start_sweep();
+    ledger::seed_system_accounts();
+    // The registration filter lives on the heap, so it's rebuilt from the profiles
+    membership::rebuild();
+    ranking::rebuild_index();
+    scheduler::rebuild_index();
+    escalation::rebuild_index();
+    pauses::rebuild_index();
+    let announced = changelog::announce_pending();
+    if announced > 0 {
+        correlation::log!("Announced {} changelog entries.", announced);
+    }
+    start_timers();
+    icrc3::certify_tip();
+    router::refresh_certified_routes();
 }
 
 
 // --- Canister Endpoints ---
 
+// `chat_id` is the chat /register was sent from; `invite_code` is needed while registration is invite-only
 #[update]
-fn register() -> Result<String, String> {
-    // Registers a new user if they don't exist.
-    let caller_principal = caller();
-    let storable_caller = StorablePrincipal(caller_principal);
-    // Access state via the imported static variable
-    state::USER_PROFILES.with(|profiles_ref| {
-        let mut profiles = profiles_ref.borrow_mut();
-        if profiles.contains_key(&storable_caller) {
-            Err(String::from("You are already registered."))
-        } else {
-            profiles.insert(storable_caller, UserProfile::default());
-            Ok(format!("Successfully registered! Welcome, Principal {}.", caller_principal))
-        }
+async fn register(chat_id: Option<String>, invite_code: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    let invocation = commands::Invocation::Register { invite_code };
+    formatting::reply_async_for(user, commands::invoke(user, invocation, chat_id.as_deref())).await
+}
+
+// /start [difficulty]: /register (when needed) followed by /dare, defaulting to an easy dare
+#[update]
+async fn start(difficulty: Option<Difficulty>, chat_id: Option<String>, invite_code: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    let difficulty = difficulty.unwrap_or(Difficulty::Easy);
+    formatting::reply_async_for(user, commands::start(user, difficulty, invite_code, chat_id.as_deref())).await
+}
+
+// --- Registration Gating (admin) ---
+
+// Caps the number of users and/or makes registration invite-only; pass null to open it fully
+#[update]
+fn set_registration_policy(policy: Option<types::RegistrationPolicy>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        if let Some(policy) = &policy { registration::validate_policy(policy)?; }
+        config::update(caller(), |config| config.registration = policy)?;
+        Ok("Registration policy updated.".to_string())
+    })
+}
+
+// Turns /register on or off in one chat, whatever the canister-wide setting
+#[update]
+fn set_chat_registration(chat_id: String, open: bool) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let chat_id = chat_scope::require_chat_key(&chat_id)?;
+        state::CHAT_CONFIGS.with(|configs_ref| {
+            let mut configs = configs_ref.borrow_mut();
+            let mut chat_config = configs.get(&chat_id).unwrap_or_default();
+            chat_config.registration_open = Some(open);
+            configs.insert(chat_id.clone(), chat_config);
+        });
+        Ok(format!("Registration {} in chat {}.", if open { "opened" } else { "closed" }, chat_id))
+    })
+}
+
+// `max_uses` = null for a code that never runs out
+#[update]
+async fn create_invite_code(max_uses: Option<u32>) -> Result<String, String> {
+    ensure_admin()?;
+    registration::create_invite(caller(), max_uses).await
+}
+
+#[query]
+fn list_invite_codes() -> Result<Vec<(String, types::InviteCode)>, String> {
+    ensure_admin()?;
+    Ok(registration::list_invites())
+}
+
+#[update]
+fn revoke_invite_code(code: String) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        registration::revoke_invite(&code)?;
+        Ok(format!("Invite code {} revoked.", code.trim().to_uppercase()))
+    })
+}
+
+#[query]
+fn list_registration_requests() -> Result<Vec<types::RegistrationRequest>, String> {
+    ensure_admin()?;
+    Ok(registration::pending_requests())
+}
+
+// Approving registers the user, or waitlists them while the cap is reached; either way they get a DM
+#[update]
+fn decide_registration(user: candid::Principal, approve: bool) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        registration::decide(user, approve)?;
+        Ok(format!("Registration for {} {}.", user, if approve { "approved" } else { "denied" }))
+    })
+}
+
+// Users waiting for a spot under the user cap, in admission order
+#[query]
+fn list_waitlist() -> Result<Vec<types::WaitlistEntry>, String> {
+    ensure_admin()?;
+    Ok(registration::waitlist())
+}
+
+#[query]
+fn get_waitlist_position(on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || match registration::waitlist_position(user) {
+        Some(position) => Ok(format!("You're on the waitlist at position {}. You'll get a DM when a spot opens.", position)),
+        None => Err("You're not on the waitlist.".to_string()),
     })
 }
 
 #[query]
-fn get_my_profile() -> Result<UserProfile, String> {
+fn get_my_profile(on_behalf_of: Option<candid::Principal>) -> Result<UserProfile, String> {
     // Returns the profile of the calling user.
-    let caller_principal = caller();
-    let storable_caller = StorablePrincipal(caller_principal);
-    state::USER_PROFILES.with(|profiles_ref| {
-         profiles_ref.borrow().get(&storable_caller) // Get profile using storable key
-             .ok_or_else(|| String::from("User not found. Please /register first."))
+    let user = accounts::caller_account(on_behalf_of)?;
+    state::get_profile(user).ok_or_else(|| String::from("User not found. Please /register first."))
+}
+
+// --- Account Linking (/link) ---
+
+// Starts linking the caller's OpenChat and direct identities; enter the code from the other one.
+// Only from a direct chat (or a direct call), since the code grants access to the account.
+#[update]
+async fn start_account_link(on_behalf_of: Option<candid::Principal>, chat_id: Option<String>) -> Result<String, String> {
+    commands::enforce(commands::Command::Link, chat_id.as_deref())?;
+    let code = accounts::start_link(on_behalf_of).await?;
+    Ok(formatting::text_for(accounts::caller_account(on_behalf_of)?, format!(
+        "Your link code is {}. Within 10 minutes, enter it with confirm_account_link from your other identity \
+        (directly if you are in OpenChat, or through the bot's /link command otherwise).", code
+    )))
+}
+
+#[update]
+fn confirm_account_link(code: String, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        let account = accounts::confirm_link(&code, on_behalf_of)?;
+        Ok(format!("Linked! Both identities now use account {}.", account))
     })
 }
 
-// Updated get_dare endpoint calling the llm module function
+// get_dare: picks a curated dare or generates one via the LLM, according to the source mix
 #[update]
-async fn get_dare(difficulty_request: Difficulty) -> Result<String, String> {
-    let caller_principal = caller();
-    let storable_caller = StorablePrincipal(caller_principal);
+async fn get_dare(difficulty_request: Difficulty, chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    let invocation = commands::Invocation::Dare { difficulty: difficulty_request };
+    formatting::reply_async_for(user, commands::invoke(user, invocation, chat_id.as_deref())).await
+}
+
+// /accept: takes on the dare /dare proposed
+#[update]
+fn accept_dare(chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        commands::enforce(commands::Command::Accept, chat_id.as_deref())?;
+        proposals::accept(user)
+    })
+}
+
+// /decline: turns the proposed dare down, within the daily limit
+#[update]
+fn decline_dare(chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        commands::enforce(commands::Command::Decline, chat_id.as_deref())?;
+        proposals::decline(user)
+    })
+}
+
+// Chat messages the OpenChat bot reads in autonomous mode; only "@Darely dare me" gets a reply (None = ignore)
+#[update]
+async fn handle_message_event(chat_id: String, sender: candid::Principal, text: String) -> Result<Option<String>, String> {
+    let user = accounts::caller_account(Some(sender))?;
+    let Some(request) = mentions::parse(&text, caller()) else { return Ok(None) };
+    // Same checks as /dare sent from the chat (scope, pauses)
+    let invocation = commands::Invocation::Dare { difficulty: request.difficulty };
+    formatting::reply_async_for(user, commands::invoke(user, invocation, Some(&chat_id))).await.map(Some)
+}
+
+// Shared by /get_dare and mentions (through commands::invoke)
+async fn dare_for(user: candid::Principal, difficulty_request: Difficulty, chat_id: Option<String>) -> Result<String, String> {
+    let storable_caller = StorablePrincipal(user);
 
     // 1. Check if user is registered
-    if state::USER_PROFILES.with(|p| p.borrow().get(&storable_caller)).is_none() {
-        return Err("User not found. Please /register first.".to_string());
+    let profile = state::get_profile(user)
+        .ok_or_else(|| "User not found. Please /register first.".to_string())?;
+    hardcore::check_penalty(&profile)?;
+    let now = ic_cdk::api::time();
+    submissions::check_claim(&profile, now)?;
+    if let Some(reply) = proposals::waiting(&profile, now) {
+        return Ok(reply);
+    }
+    let propose = proposals::proposes(&profile, now);
+    let snapshot_version = state::profile_version(&profile);
+    let mut blocklist = blocklist::Blocklist::of(&profile);
+    campaigns::note_activity(user, campaigns::Activity::Responded);
+    if let Some(chat_id) = chat_id.as_deref() {
+        chat_members::record(chat_id, user);
+    }
+
+    // 2. An admin-pinned dare takes precedence over the normal selection (unless it's disabled here)
+    let disabled = exclusions::disabled_in(chat_id.as_deref());
+    if let Some((dare, source)) = pins::find(user, &profile).filter(|(dare, _)| !disabled.contains(&dare.id)) {
+        return Ok(offer_stored_dare(&storable_caller, dare, chat_id.as_deref(), propose, Some(source)));
+    }
+    // A player on a quest gets its next step
+    if let Some(dare) = quests::next_step(user, &profile) {
+        return Ok(offer_stored_dare(&storable_caller, dare, chat_id.as_deref(), propose, None));
+    }
+
+    // 3. Decide between the curated pool and a fresh LLM dare
+    let seed = randomness::next_u64();
+    let mix = selection::source_mix_for_chat(chat_id.as_deref());
+    let llm_rolled_out = features::enabled(features::Feature::LlmDares, user);
+    let mut curated = selection::candidates(&difficulty_request, Some(DareSource::Curated), &profile);
+    curated.retain(|dare| !blocklist.blocks(dare) && !disabled.contains(&dare.id));
+    marketplace::boost_imported(&mut curated, chat_id.as_deref());
+    if !llm_rolled_out || selection::choose_source(&mix, !curated.is_empty(), seed) == DareSource::Curated {
+        if let Some(dare) = selection::pick_weighted(&curated, seed) {
+            return Ok(offer_stored_dare(&storable_caller, dare.clone(), chat_id.as_deref(), propose, None));
+        }
+    }
+
+    // 4. A pre-generated LLM dare saves the outcall (see prefill.rs); the stock is in English
+    if llm_rolled_out && llm::language_for(chat_id.as_deref()).is_none() {
+        if let Some(dare) = prefill::find(&difficulty_request, |dare| !blocklist.blocks(dare) && !disabled.contains(&dare.id)) {
+            return Ok(offer_stored_dare(&storable_caller, dare, chat_id.as_deref(), propose, Some(types::ProposalSource::Prefill)));
+        }
     }
 
-    // 2. Call the LLM fetching logic from the llm module
+    // 5. Call the LLM fetching logic from the llm module, steering it away from blocked tags
     // The fetch_llm_dare function now handles API key check, HTTPS call, and parsing
-    match llm::fetch_llm_dare(difficulty_request).await {
-        Ok(dare_text) => {
-            // Optional: Log the generated dare?
-            // state::DARE_REPOSITORY.with(|repo| repo.borrow_mut().push(&Dare{...}));
-            Ok(dare_text)
+    let fetched = if llm_rolled_out {
+        llm::fetch_llm_dare(difficulty_request.clone(), &blocklist.tags, llm_usage::LlmFeature::Dare, chat_id.as_deref()).await
+    } else {
+        Err("LLM dares aren't available to you yet".to_string())
+    };
+    // Other commands may have run during the call; check against the blocklist as it is now
+    if let Some(current) = state::get_profile(user) {
+        if state::profile_version(&current) != snapshot_version {
+            blocklist = blocklist::Blocklist::of(&current);
         }
-        Err(e) => {
-            // Propagate the error from the LLM module
-            Err(format!("Failed to get dare from LLM: {}", e))
+    }
+    let llm_error = match fetched {
+        Ok(dare_text) => {
+            // Log the generated dare so it gets an id and can serve as a fallback later
+            let dare_id = store_dare(dare_text.clone(), difficulty_request.clone(), DareSource::Llm, None)
+                .map_err(|e| correlation::log!("Could not log LLM dare: {}", incidents::observe(e)))
+                .ok();
+            if !blocklist.matches_text(&dare_text) {
+                return Ok(offer_dare(&storable_caller, dare_id, chat_id.as_deref(), None, dare_text, propose, None));
+            }
+            "the generated dare matched your blocklist".to_string()
         }
+        Err(e) => e,
+    };
+
+    // Fall back to any stored dare of this difficulty before giving up
+    let mut stored = selection::candidates(&difficulty_request, None, &profile);
+    stored.retain(|dare| !blocklist.blocks(dare) && !disabled.contains(&dare.id));
+    match selection::pick_weighted(&stored, seed) {
+        Some(dare) => Ok(offer_stored_dare(&storable_caller, dare.clone(), chat_id.as_deref(), propose, None)),
+        None => Err(format!("Failed to get dare from LLM: {}", llm_error)),
     }
 }
 
-// submit_dare endpoint (remains mostly the same, simplified verification)
+// /hint: reveals the active dare's hint (costs points the first time)
 #[update]
-fn submit_dare(proof: String) -> Result<String, String> {
-    if proof.trim().is_empty() { return Err("Proof cannot be empty.".to_string()); }
-    let caller_principal = caller();
-    let storable_caller = StorablePrincipal(caller_principal);
-
-    state::USER_PROFILES.with(|profiles_ref| {
-        let mut profiles = profiles_ref.borrow_mut();
-        if let Some(mut profile) = profiles.remove(&storable_caller) { // Use remove/insert pattern
-            // NOTE: Verification logic is simplified. Cannot check against a specific dare ID.
-            profile.streak += 1;
-            let streak = profile.streak;
-            profiles.insert(storable_caller.clone(), profile); // Re-insert updated
-            Ok(format!("Dare submitted successfully! Your new streak is {}. You can now /get_dare again.", streak))
-        } else {
-            Err("User not found. Please /register first.".to_string())
+async fn get_hint(on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_async_for(user, async move {
+        let hint = hints::reveal(user).await?;
+        Ok(format!("Hint: {}", hint))
+    }).await
+}
+
+// --- Gallery ---
+
+// /gallery [n]: the chat's latest approved completions
+#[query]
+fn get_gallery(chat_id: String, count: Option<u32>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        // GroupOnly always resolves to a chat key
+        let chat_id = commands::enforce(commands::Command::Gallery, Some(&chat_id))?.unwrap_or_default();
+        // Proofs stay within the chat: only players seen there can list them
+        if !chat_members::is_member(&chat_id, user) {
+            return Err("Only members who have played in this chat can see its gallery. Get a dare here first.".to_string());
         }
+        let count = count.map_or(gallery::DEFAULT_GALLERY_SIZE, |n| n as usize).clamp(1, gallery::MAX_GALLERY_SIZE);
+        Ok(gallery::render(&gallery::latest(&chat_id, count)))
+    })
+}
+
+// /submission_status: the latest submission's review state and an estimated review time
+#[query]
+fn submission_status(on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || moderation::submission_status(user))
+}
+
+// /rate_dare <submission_id> <difficulty> <fun>: the post-approval survey, 1-5 each
+#[update]
+fn rate_dare(submission_id: u64, difficulty: u8, fun: u8, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        let dare_id = ratings::rate(user, submission_id, types::DareRating { difficulty, fun })?;
+        Ok(format!("Thanks! Your rating for dare #{} was recorded.", dare_id))
+    })
+}
+
+// Name shown in the gallery; null clears it
+#[update]
+fn set_nickname(nickname: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        gallery::set_nickname(user, nickname)?;
+        Ok("Nickname updated.".to_string())
+    })
+}
+
+// --- Truths ---
+
+// /truth: a question to answer publicly in the chat
+#[update]
+fn get_truth(on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        let truth = truths::assign(user, randomness::next_u64())?;
+        Ok(format!("Truth: {} Answer in the chat, then confirm with /answer_truth.", truth.text))
+    })
+}
+
+#[update]
+fn answer_truth(answer: String, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        let (answered, xp) = truths::answer(user, &answer)?;
+        Ok(format!("Truth answered! +{} XP ({} XP total, {} truths answered).", truths::TRUTH_XP, xp, answered))
     })
 }
 
-// redeem_reward endpoint (no changes needed from previous version)
+// --- Custom Dares ---
+
+// /dare_user @user <text> [difficulty]: the target must have opted in with /custom_dares on
+#[update]
+fn dare_user(target: candid::Principal, text: String, difficulty: Option<Difficulty>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    let target = accounts::target_account(target, on_behalf_of);
+    formatting::reply_for(user, || {
+        let (dare_id, difficulty) = custom_dares::issue(user, target, text, difficulty)?;
+        Ok(format!("Dare #{} ({:?}) sent! It's now their active dare.", dare_id, difficulty))
+    })
+}
+
+// /request_vouch: asks other members to confirm the active dare was done offline
+#[update]
+fn request_vouch(chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        commands::enforce(commands::Command::RequestVouch, chat_id.as_deref())?;
+        vouches::request(user)
+    })
+}
+
+// /vouch @user: confirms their offline dare; the second vouch keeps their streak
 #[update]
-fn redeem_reward() -> Result<String, String> {
-     let caller_principal = caller();
-     let storable_caller = StorablePrincipal(caller_principal);
-     let mut final_message = String::new();
-     let mut user_found = false;
+fn vouch(target: candid::Principal, chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    let target = accounts::target_account(target, on_behalf_of);
+    formatting::reply_for(user, || {
+        commands::enforce(commands::Command::Vouch, chat_id.as_deref())?;
+        vouches::vouch(user, target)
+    })
+}
+
+// /event: shared progress of the running global events
+#[query]
+fn get_global_event(chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        commands::enforce(commands::Command::Event, chat_id.as_deref())?;
+        Ok(events::describe(user))
+    })
+}
 
-     state::USER_PROFILES.with(|profiles_ref| {
-         let mut profiles = profiles_ref.borrow_mut();
-         if let Some(mut profile) = profiles.remove(&storable_caller) {
-             user_found = true;
-             let current_streak = profile.streak;
-             let mut already_redeemed = BTreeSet::from_iter(profile.redeemed_milestones.iter().cloned());
-             let mut profile_updated = false;
-             let mut specific_reward_msg = String::new();
+// /buddy pair @user: asks another member to be your buddy
+#[update]
+fn buddy_pair(target: candid::Principal, chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    let target = accounts::target_account(target, on_behalf_of);
+    formatting::reply_for(user, || {
+        commands::enforce(commands::Command::Buddy, chat_id.as_deref())?;
+        buddies::pair(user, target)
+    })
+}
 
-             for &milestone in REWARD_MILESTONES {
-                 if current_streak >= milestone && !already_redeemed.contains(&milestone) {
-                     already_redeemed.insert(milestone);
-                     profile_updated = true;
-                     specific_reward_msg = format!("Congratulations! You've redeemed the streak {} reward!", milestone);
-                     break;
-                 }
-             }
+// /buddy accept: accepts the pending buddy request
+#[update]
+fn buddy_accept(chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        commands::enforce(commands::Command::Buddy, chat_id.as_deref())?;
+        buddies::accept(user)
+    })
+}
 
-             if profile_updated {
-                 profile.redeemed_milestones = already_redeemed.into_iter().collect();
-                 final_message = specific_reward_msg;
-             } else {
-                 final_message = format!("No new rewards available at your current streak of {}.", current_streak);
-             }
-             profiles.insert(storable_caller.clone(), profile);
-         } else {
-             user_found = false;
-         }
-     });
+// /buddy decline
+#[update]
+fn buddy_decline(chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        commands::enforce(commands::Command::Buddy, chat_id.as_deref())?;
+        buddies::decline(user)
+    })
+}
 
-     if user_found { Ok(final_message) }
-     else { Err("User not found. Please /register first.".to_string()) }
+// /buddy unpair
+#[update]
+fn buddy_unpair(chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        commands::enforce(commands::Command::Buddy, chat_id.as_deref())?;
+        buddies::unpair(user)
+    })
 }
 
-// get_leaderboard endpoint (no changes needed from previous version)
+// /buddy: shows the current buddy (or pending request) and this week's progress
 #[query]
-fn get_leaderboard() -> Vec<(candid::Principal, u32)> { // Ensure return type uses candid::Principal
-    let mut leaderboard: Vec<(candid::Principal, u32)> = state::USER_PROFILES.with(|profiles_ref| {
-        profiles_ref.borrow().iter()
-            .map(|(storable_principal, profile)| (storable_principal.0, profile.streak)) // Extract raw Principal
-            .collect()
-    });
-    leaderboard.sort_by(|a, b| b.1.cmp(&a.1));
-    leaderboard.truncate(MAX_LEADERBOARD_SIZE);
-    leaderboard
+fn get_buddy(chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        commands::enforce(commands::Command::Buddy, chat_id.as_deref())?;
+        buddies::describe(user)
+    })
+}
+
+// /duo invite @user: asks another member to share a duo streak
+#[update]
+fn duo_invite(target: candid::Principal, chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    let target = accounts::target_account(target, on_behalf_of);
+    formatting::reply_for(user, || {
+        commands::enforce(commands::Command::Duo, chat_id.as_deref())?;
+        duos::invite(user, target)
+    })
+}
+
+// /duo accept
+#[update]
+fn duo_accept(chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        commands::enforce(commands::Command::Duo, chat_id.as_deref())?;
+        duos::accept(user)
+    })
+}
+
+// /duo decline
+#[update]
+fn duo_decline(chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        commands::enforce(commands::Command::Duo, chat_id.as_deref())?;
+        duos::decline(user)
+    })
+}
+
+// /duo leave: ends the duo for both members
+#[update]
+fn duo_leave(chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        commands::enforce(commands::Command::Duo, chat_id.as_deref())?;
+        duos::leave(user)
+    })
+}
+
+// /duo: the shared streak (or the pending request)
+#[query]
+fn get_duo(chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        commands::enforce(commands::Command::Duo, chat_id.as_deref())?;
+        duos::describe(user)
+    })
+}
+
+// /recap [period]: a markdown recap of the caller's month, for the bot to post in the chat
+#[query]
+fn get_recap(period: Option<String>, chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        commands::enforce(commands::Command::Recap, chat_id.as_deref())?;
+        recap::render(user, period)
+    })
+}
+
+// /quest: open questlines and the caller's progress
+#[query]
+fn get_quests(chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        commands::enforce(commands::Command::Quest, chat_id.as_deref())?;
+        Ok(quests::describe(user))
+    })
+}
+
+// /quest start <id>: the next /dare hands out the quest's first step
+#[update]
+fn start_quest(quest_id: u64, chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        commands::enforce(commands::Command::Quest, chat_id.as_deref())?;
+        quests::start(user, quest_id)
+    })
+}
+
+// /quest abandon
+#[update]
+fn abandon_quest(chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        commands::enforce(commands::Command::Quest, chat_id.as_deref())?;
+        quests::abandon(user)
+    })
+}
+
+// /escalate: takes the harder follow-up offered after a completion
+#[update]
+fn accept_escalation(chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        commands::enforce(commands::Command::Escalate, chat_id.as_deref())?;
+        escalation::accept(user, chat_scope::chat_key(chat_id.as_deref()).as_deref())
+    })
+}
+
+// /escalate decline
+#[update]
+fn decline_escalation(chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        commands::enforce(commands::Command::Escalate, chat_id.as_deref())?;
+        escalation::decline(user)
+    })
+}
+
+// /hardcore on|off: opts in to streak resets and penalty dares after a lapse
+#[update]
+fn set_hardcore(enabled: bool, chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        commands::enforce(commands::Command::Hardcore, chat_id.as_deref())?;
+        hardcore::set(user, enabled)
+    })
+}
+
+#[update]
+fn set_custom_dares(accept: bool, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        custom_dares::set_opt_in(user, accept)?;
+        Ok(if accept {
+            "Other members can now dare you with /dare_user.".to_string()
+        } else {
+            "You no longer receive custom dares.".to_string()
+        })
+    })
+}
+
+// submit_dare endpoint (single-message proof)
+#[update]
+fn submit_dare(proof: String, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        if proof.trim().is_empty() { return Err("Proof cannot be empty.".to_string()); }
+        let streak = submissions::record_submission(user, vec![proof.trim().to_string()])?;
+        Ok(format!("Dare submitted successfully! {} You can now /get_dare again.{}", submissions::streak_note(user, streak), escalation::submission_note(user)))
+    })
+}
+
+// /done: marks the active dare finished now, with the proof to follow within a day
+#[update]
+fn claim_done(chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        commands::enforce(commands::Command::Done, chat_id.as_deref())?;
+        submissions::claim_done(user)
+    })
+}
+
+// --- Multi-part Submissions (/submit start, parts..., /submit done) ---
+
+#[update]
+fn submit_start(on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        if state::get_profile(user).is_none() {
+            return Err("User not found. Please /register first.".to_string());
+        }
+        submissions::start_draft(user);
+        Ok("Submission started. Send your proof parts, then /submit done.".to_string())
+    })
+}
+
+#[update]
+fn submit_part(proof_part: String, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        let count = submissions::append_part(user, proof_part)?;
+        Ok(format!("Proof part {} added.", count))
+    })
+}
+
+#[update]
+fn submit_done(on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        let parts = submissions::take_draft(user)?;
+        let part_count = parts.len();
+        let streak = submissions::record_submission(user, parts)?;
+        Ok(format!(
+            "Dare submitted successfully with {} proof parts! {} You can now /get_dare again.{}",
+            part_count, submissions::streak_note(user, streak), escalation::submission_note(user)
+        ))
+    })
+}
+
+#[update]
+fn submit_cancel(on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        if submissions::discard_draft(user) {
+            Ok("Submission draft discarded.".to_string())
+        } else {
+            Err("No submission in progress.".to_string())
+        }
+    })
+}
+
+// Reverts the caller's last submission if made within the last 5 minutes
+#[update]
+fn undo(on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        let streak = submissions::undo_last_submission(user)?;
+        Ok(format!("Your last submission was undone. Your streak is back to {} and your dare is active again.", streak))
+    })
+}
+
+#[query]
+fn get_my_history(limit: Option<u32>, on_behalf_of: Option<candid::Principal>) -> Result<Vec<HistoryEntry>, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    let limit = limit.unwrap_or(DEFAULT_HISTORY_LIMIT).min(MAX_HISTORY_LIMIT) as usize;
+    Ok(history::events_for(user, limit))
+}
+
+// Suggest a dare for admins to review
+#[update]
+fn suggest_dare(text: String, difficulty: Option<Difficulty>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        let id = suggestions::add_suggestion(text, difficulty, Some(user), None)?;
+        Ok(format!("Thanks! Your suggestion #{} was sent to the admins for review.", id))
+    })
+}
+
+// --- Accessibility ---
+
+// Turns plain-text replies (no emoji or markdown, symbols spelled out) on or off
+#[update]
+fn set_accessibility_mode(enabled: bool, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        state::update_profile(user, |profile| {
+            profile.plain_text = Some(enabled);
+            Ok(())
+        })?;
+        Ok(if enabled {
+            "Accessibility mode is on. Replies will be plain text.".to_string()
+        } else {
+            "Accessibility mode is off.".to_string()
+        })
+    })
+}
+
+// --- Time Zone (/timezone) ---
+
+// Sets the caller's time zone; daily windows then start at their local midnight
+#[update]
+fn set_timezone(timezone: String, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        let timezone = timezone::normalize(&timezone)?;
+        state::update_profile(user, |profile| {
+            profile.timezone = Some(timezone.clone());
+            Ok(())
+        })?;
+        let today = timezone::today(user);
+        let minutes_left = today.resets_at.saturating_sub(ic_cdk::api::time()) / 60_000_000_000;
+        Ok(format!("Time zone set to {}. It's {} there; your day resets in {}h {}m.",
+            timezone, today.local_date, minutes_left / 60, minutes_left % 60))
+    })
+}
+
+#[query]
+fn get_today(on_behalf_of: Option<candid::Principal>) -> Result<DayWindow, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    if !state::is_registered(user) {
+        return Err("User not found. Please /register first.".to_string());
+    }
+    Ok(timezone::today(user))
+}
+
+// Chats the caller has requested dares in, with their last activity there (nanos)
+#[query]
+fn get_my_chats(on_behalf_of: Option<candid::Principal>) -> Result<Vec<(String, u64)>, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    Ok(chat_members::chats_of(user))
+}
+
+// --- Blocklist (/block_dare, /block_tag) ---
+
+#[update]
+fn block_dare(dare_id: u64, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        blocklist::block_dare(user, dare_id)?;
+        Ok(format!("Dare #{} blocked. You won't receive it again.", dare_id))
+    })
+}
+
+#[update]
+fn unblock_dare(dare_id: u64, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        blocklist::unblock_dare(user, dare_id)?;
+        Ok(format!("Dare #{} unblocked.", dare_id))
+    })
+}
+
+#[update]
+fn block_tag(tag: String, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        let tag = blocklist::block_tag(user, &tag)?;
+        Ok(format!("Blocked '{}'. You won't receive dares involving it.", tag))
+    })
+}
+
+#[update]
+fn unblock_tag(tag: String, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        let tag = blocklist::unblock_tag(user, &tag)?;
+        Ok(format!("Unblocked '{}'.", tag))
+    })
+}
+
+// --- Likes & Trending ---
+
+// /like_dare <id>: likes a dare (None = your current dare, or the one you just submitted)
+#[update]
+fn like_dare(dare_id: Option<u64>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        let profile = state::get_profile(user)
+            .ok_or_else(|| "User not found. Please /register first.".to_string())?;
+        let last_submitted = || profile.last_submission_id
+            .and_then(|id| state::SUBMISSIONS.with(|s| s.borrow().get(&id)))
+            .and_then(|submission| submission.dare_id);
+        let dare_id = dare_id.or(profile.current_dare_id).or_else(last_submitted)
+            .ok_or_else(|| "Which dare? Use /like_dare <id>.".to_string())?;
+        let total = popularity::like(user, dare_id)?;
+        Ok(format!("You liked dare #{} ({} likes).", dare_id, total))
+    })
+}
+
+// /trending: the most-liked dares of the last 7 days
+#[query]
+fn get_trending(limit: Option<u32>) -> Vec<TrendingDare> {
+    let limit = limit.map_or(popularity::DEFAULT_TRENDING_SIZE, |l| l as usize).min(popularity::MAX_TRENDING_SIZE);
+    popularity::trending(limit)
+}
+
+// --- Submission Review (/review claim, /review pass <id>, ...) ---
+
+// Claims the next submission assigned to the calling moderator (or returns the current claim)
+#[update]
+fn review_claim() -> Result<ReviewItem, String> {
+    moderation::claim(caller())
+}
+
+// Hands a claimed submission to the next moderator in rotation
+#[update]
+fn review_pass(submission_id: u64) -> Result<String, String> {
+    formatting::reply_with(|| {
+        match moderation::pass(caller(), submission_id)? {
+            Some(moderator) => Ok(format!("Passed submission {} to {}.", submission_id, moderator)),
+            None => Ok(format!("Passed submission {}. No other moderator is available; it stays in the queue.", submission_id)),
+        }
+    })
+}
+
+#[update]
+fn review_approve(submission_id: u64) -> Result<String, String> {
+    formatting::reply_with(|| {
+        moderation::decide(caller(), submission_id, true, None)?;
+        Ok(format!("Submission {} approved.", submission_id))
+    })
+}
+
+// Rejecting takes back the streak point the submission earned. The reason is shown to the
+// submitter.
+#[update]
+fn review_reject(submission_id: u64, reason: Option<String>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        moderation::decide(caller(), submission_id, false, reason)?;
+        Ok(format!("Submission {} rejected.", submission_id))
+    })
+}
+
+// /appeal <submission_id> <argument>: asks another moderator to look at a rejected submission
+#[update]
+fn appeal(submission_id: u64, argument: String, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        let appeal = appeals::file(user, submission_id, &argument)?;
+        Ok(match appeal.assigned_to {
+            Some(_) => format!("⚖️ Appeal filed. Another moderator will look at submission #{}.", submission_id),
+            None => format!("⚖️ Appeal filed. An admin will look at submission #{}.", submission_id),
+        })
+    })
+}
+
+// Open appeals the caller can decide (all open appeals for admins)
+#[query]
+fn list_appeals() -> Result<Vec<types::Appeal>, String> {
+    let is_admin = ensure_admin().is_ok();
+    if !is_admin && !moderation::is_moderator(caller()) {
+        return Err("Only moderators can review appeals.".to_string());
+    }
+    Ok(appeals::pending_for(caller(), is_admin))
+}
+
+// Granting approves the submission and restores the streak point; the note goes to the user
+#[update]
+fn decide_appeal(submission_id: u64, granted: bool, note: Option<String>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        appeals::decide(caller(), ensure_admin().is_ok(), submission_id, granted, note)?;
+        Ok(format!("Appeal for submission {} {}.", submission_id, if granted { "granted" } else { "denied" }))
+    })
+}
+
+#[query]
+fn get_my_review_stats() -> Result<ModeratorStats, String> {
+    moderation::stats(caller())
+}
+
+// --- Web Sessions ---
+
+// Returns a short-lived token the website sends as `Authorization: Bearer <token>` to user-scoped REST routes
+#[update]
+async fn create_session() -> Result<SessionToken, String> {
+    let caller_principal = caller();
+    if caller_principal == candid::Principal::anonymous() {
+        return Err("Anonymous callers cannot create sessions.".to_string());
+    }
+    // Sessions act for the caller's account, which may be a linked OpenChat account
+    let account = accounts::caller_account(None)?;
+    if state::get_profile(account).is_none() {
+        return Err("User not found. Please /register first.".to_string());
+    }
+    sessions::ensure_secret().await?;
+    Ok(sessions::create(account))
+}
+
+// Revokes all of the caller's web sessions
+#[update]
+fn end_sessions() -> String {
+    let ended = sessions::end_all(accounts::resolve(accounts::Identity::Direct(caller())));
+    formatting::text(format!("Ended {} session(s).", ended))
+}
+
+// --- Achievement Verification (inter-canister API) ---
+
+#[query]
+fn list_badges() -> Vec<types::Badge> {
+    achievements::catalog()
+}
+
+#[query]
+fn verify_achievement(user: candid::Principal, badge_id: String) -> bool {
+    achievements::verify(user, &badge_id)
+}
+
+// Same as verify_achievement for up to 100 (principal, badge) pairs, answered in order
+#[query]
+fn verify_achievements(checks: Vec<(candid::Principal, String)>) -> Result<Vec<bool>, String> {
+    if checks.len() > achievements::MAX_BATCH {
+        return Err(format!("At most {} checks per call.", achievements::MAX_BATCH));
+    }
+    Ok(checks.iter().map(|(user, badge_id)| achievements::verify(*user, badge_id)).collect())
+}
+
+// --- Points (ICRC-3 block log, see points.rs and icrc3.rs) ---
+
+#[query]
+fn get_my_points(on_behalf_of: Option<candid::Principal>) -> Result<u64, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    Ok(points::balance(user))
+}
+
+#[update]
+fn transfer_points(to: candid::Principal, amount: u64, memo: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        // Points go to the account behind the recipient's principal
+        let to = accounts::resolve(accounts::Identity::Direct(to));
+        if !state::is_registered(to) {
+            return Err("The recipient isn't registered.".to_string());
+        }
+        let block = points::transfer(user, to, amount, memo.as_deref())?;
+        Ok(format!("Sent {} points (block {}). Your balance is {}.", amount, block, points::balance(user)))
+    })
+}
+
+#[query]
+fn icrc3_get_blocks(args: Vec<types::GetBlocksArgs>) -> types::GetBlocksResult {
+    icrc3::get_blocks(args)
+}
+
+// Every block is kept in this canister, so there are no archives
+#[query]
+fn icrc3_get_archives(_args: types::GetArchivesArgs) -> Vec<types::ICRC3ArchiveInfo> {
+    Vec::new()
+}
+
+#[query]
+fn icrc3_get_tip_certificate() -> Option<types::ICRC3DataCertificate> {
+    icrc3::tip_certificate()
+}
+
+#[query]
+fn icrc3_supported_block_types() -> Vec<types::SupportedBlockType> {
+    icrc3::supported_block_types()
+}
+
+// --- Completion Certificates ---
+
+// Signs a shareable certificate for a completed dare; fetch it with get_completion_certificate
+#[update]
+fn prepare_completion_certificate(dare_id: u64, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        certificates::prepare(user, dare_id)?;
+        Ok(format!("Certificate for dare #{} is ready. Fetch it within 10 minutes with get_completion_certificate.", dare_id))
+    })
+}
+
+#[query]
+fn get_completion_certificate(dare_id: u64, on_behalf_of: Option<candid::Principal>) -> Result<types::CompletionCertificate, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    certificates::get(user, dare_id)
+}
+
+// /roadmap: upcoming reward milestones with estimated dates at the caller's pace
+#[query]
+fn get_roadmap(on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || roadmap::render(user))
+}
+
+// /complete_task <proof>: sends the proof for your bonus task to review
+#[update]
+fn complete_task(proof: String, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        let submission_id = rewards::complete_task(user, &proof)?;
+        Ok(format!("Task proof sent for review (submission {}).", submission_id))
+    })
+}
+
+// /stats: progress summary, including bonus tasks still open
+#[query]
+fn get_my_stats(on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        let profile = state::get_profile(user)
+            .ok_or_else(|| "User not found. Please /register first.".to_string())?;
+        let mut lines = vec![format!(
+            "Streak: {} | Level: {} | XP: {} | Points: {} | Truths answered: {} | Streak freezes: {}",
+            profile.streak,
+            selection::level(profile.xp.unwrap_or(0)),
+            profile.xp.unwrap_or(0),
+            points::balance(user),
+            profile.truths_answered.unwrap_or(0),
+            profile.streak_freezes.unwrap_or(0)
+        )];
+        lines.push(checkin::describe(user, &profile));
+        if profile.penalty_since.is_some() {
+            lines.push("💀 Hardcore mode: finish your penalty dare to unlock normal dares.".to_string());
+        } else if profile.hardcore_since.is_some() {
+            lines.push("💀 Hardcore mode is on.".to_string());
+        }
+        let tasks = rewards::outstanding_tasks(user);
+        if !tasks.is_empty() {
+            lines.push("Outstanding bonus tasks:".to_string());
+            lines.extend(tasks.into_iter().map(|(task, submission_id)| match submission_id {
+                Some(id) => format!("- {} (in review, submission {})", task.text, id),
+                None => format!("- {} (send proof with /complete_task)", task.text),
+            }));
+        }
+        Ok(lines.join("\n"))
+    })
+}
+
+// /checkin: once-per-local-day random points bonus, growing with consecutive days
+#[update]
+async fn check_in(chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    commands::enforce(commands::Command::Checkin, chat_id.as_deref())?;
+    formatting::reply_async_for(user, checkin::check_in(user)).await
+}
+
+// redeem_reward endpoint: redeems the lowest reached milestone (or, with `all`, every reached
+// milestone) and spins the reward wheel for each. The chat picks the redemption policy applied
+// to the streak.
+#[update]
+async fn redeem_reward(chat_id: Option<String>, all: Option<bool>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    let chat_id = commands::enforce(commands::Command::Redeem, chat_id.as_deref())?;
+    formatting::reply_async_for(user, rewards::redeem(user, chat_id.as_deref(), all.unwrap_or(false))).await
+}
+
+// The bot commands with the chats each may run in, for the OpenChat bot's own definition
+#[query]
+fn get_command_definitions() -> Vec<types::CommandDefinition> {
+    commands::definitions()
+}
+
+// /changelog: the latest release notes (newest first)
+#[query]
+fn get_changelog(limit: Option<u32>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        let limit = limit.map_or(changelog::DEFAULT_LIMIT, |l| l as usize).clamp(1, changelog::MAX_LIMIT);
+        Ok(changelog::format(&changelog::recent(limit)))
+    })
+}
+
+// Records release notes; they are posted to subscribed chats after the next upgrade
+#[update]
+fn add_changelog(version: String, notes: String) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let id = changelog::add(version, notes, caller())?;
+        Ok(format!("Changelog entry #{} added. It will be announced after the next upgrade.", id))
+    })
+}
+
+// Candid interface version, the frozen versions and deprecated methods, for generated clients
+#[query]
+fn api_version() -> types::ApiVersion {
+    api_version::current()
+}
+
+// /status: whether the canister is up, which environment it runs in and how big it is, plus the
+// read replicas frontends can send leaderboard and analytics queries to
+#[query]
+fn get_status() -> Result<String, String> {
+    formatting::reply_with(|| {
+        let environment = environment::current();
+        let mut status = format!(
+            "✅ Darely is up ({}). {} registered users, {} dares. LLM dares are {}.",
+            environment.as_str(),
+            repository::users().len(),
+            repository::dares().len(),
+            if environment::llm_enabled() { "on" } else { "off" }
+        );
+        let replicas = replication::query_endpoints();
+        if !replicas.is_empty() {
+            status.push_str(&format!("\nRead replicas: {}", replicas.join(", ")));
+        }
+        Ok(status)
+    })
+}
+
+// get_leaderboard endpoint (ranking logic lives in ranking.rs, shared with the REST API)
+#[query]
+fn get_leaderboard() -> Vec<(candid::Principal, u32)> { // Ensure return type uses candid::Principal
+    ranking::top_streaks(ranking::MAX_LEADERBOARD_SIZE)
+}
+
+// Creators whose dares others completed most, with their totals
+#[query]
+fn get_creator_leaderboard(limit: Option<u32>) -> Vec<(candid::Principal, types::CreatorStats)> {
+    let limit = limit.map_or(creators::DEFAULT_LEADERBOARD_SIZE, |l| l as usize).min(creators::MAX_LEADERBOARD_SIZE);
+    creators::leaderboard(limit)
+}
+
+// /dare_stats <id>: the dare's creator and their totals
+#[query]
+fn get_dare_stats(dare_id: u64, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    formatting::reply_with(|| creators::describe_dare(dare_id, accounts::caller_account(on_behalf_of)?))
+}
+
+// The leaderboard with when it was computed and how many users it covers
+#[query]
+fn get_leaderboard_snapshot() -> types::LeaderboardSnapshot {
+    ranking::snapshot()
+}
+
+// /rank: the caller's streak rank and percentile, with the same tie-breaks as the leaderboard.
+// Exact unless the caller shares their streak with more than a thousand users (see ranking.rs).
+#[query]
+fn get_my_rank(on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        let rank = ranking::rank_of(user)
+            .ok_or_else(|| "User not found. Please /register first.".to_string())?;
+        Ok(format!(
+            "🏆 You're {}#{} of {} by streak (top {}%).",
+            if rank.exact { "" } else { "about " }, rank.position, rank.total, ranking::top_percent(rank.position, rank.total)
+        ))
+    })
+}
+
+
+// --- Admin Endpoints: Dare Curation ---
+
+// Analyzes a new dare (suggested difficulty, completion time, near-duplicates). The dare is added
+// right away when the admin's difficulty matches the suggestion and nothing looks duplicated;
+// otherwise it is held until /confirm_dare.
+#[update]
+fn add_dare(text: String, difficulty: Option<Difficulty>, hint: Option<String>, min_level: Option<u32>, min_streak: Option<u32>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let hint = hints::validate(hint)?;
+        selection::validate_gating(min_level, min_streak)?;
+        let text = text.trim().to_string();
+        if text.is_empty() { return Err("Dare text cannot be empty.".to_string()); }
+        if text.len() > MAX_DARE_TEXT_LEN {
+            return Err(format!("Dare text is too long (max {} bytes).", MAX_DARE_TEXT_LEN));
+        }
+        templates::validate(&text)?;
+
+        let analysis = analysis::analyze_dare(&text);
+        let summary = analysis::describe(&analysis);
+        if difficulty.as_ref() == Some(&analysis.suggested_difficulty) && analysis.near_duplicate_of.is_none() {
+            let id = store_dare(text, analysis.suggested_difficulty, DareSource::Curated, Some(caller()))?;
+            hints::set(id, hint)?;
+            selection::set_gating(id, min_level, min_streak)?;
+            return Ok(format!("Dare #{} added ({}). {}", id, selection::describe_gating(min_level, min_streak), summary));
+        }
+
+        let suggested = analysis.suggested_difficulty.clone();
+        let pending = PendingDare { text, requested_difficulty: difficulty, hint, analysis, created_at: ic_cdk::api::time(), min_level, min_streak };
+        state::PENDING_DARES.with(|p| p.borrow_mut().insert(StorablePrincipal(caller()), pending));
+        Ok(format!(
+            "{} Use /confirm_dare to accept {:?}, /confirm_dare <difficulty> to override, or /cancel_dare to discard.",
+            summary, suggested
+        ))
+    })
+}
+
+// Adds the caller's pending dare, with the suggested difficulty or an override
+#[update]
+fn confirm_dare(difficulty_override: Option<Difficulty>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let storable_caller = StorablePrincipal(caller());
+        let pending = state::PENDING_DARES.with(|p| p.borrow_mut().remove(&storable_caller))
+            .filter(|pending| ic_cdk::api::time().saturating_sub(pending.created_at) <= PENDING_DARE_TIMEOUT_NANOS)
+            .ok_or_else(|| "No dare is awaiting confirmation. Use /add_dare first.".to_string())?;
+        let difficulty = difficulty_override.unwrap_or(pending.analysis.suggested_difficulty);
+        let id = store_dare(pending.text, difficulty.clone(), DareSource::Curated, Some(caller()))?;
+        hints::set(id, pending.hint)?;
+        selection::set_gating(id, pending.min_level, pending.min_streak)?;
+        Ok(format!("Dare #{} added as {:?} ({}).", id, difficulty, selection::describe_gating(pending.min_level, pending.min_streak)))
+    })
+}
+
+#[update]
+fn cancel_dare() -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        match state::PENDING_DARES.with(|p| p.borrow_mut().remove(&StorablePrincipal(caller()))) {
+            Some(_) => Ok("Pending dare discarded.".to_string()),
+            None => Err("No dare is awaiting confirmation.".to_string()),
+        }
+    })
+}
+
+#[update]
+fn boost_dare(dare_id: u64) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let weight = adjust_dare_weight(dare_id, selection::boosted_weight)?;
+        Ok(format!("Dare #{} boosted. New weight: {}.", dare_id, weight))
+    })
+}
+
+#[update]
+fn demote_dare(dare_id: u64) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let weight = adjust_dare_weight(dare_id, selection::demoted_weight)?;
+        Ok(format!("Dare #{} demoted. New weight: {}.", dare_id, weight))
+    })
+}
+
+// Sets the level and streak a player needs before a dare is offered to them (null removes one)
+#[update]
+fn set_dare_gating(dare_id: u64, min_level: Option<u32>, min_streak: Option<u32>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        selection::set_gating(dare_id, min_level, min_streak)?;
+        Ok(format!("Dare #{} is now {}.", dare_id, selection::describe_gating(min_level, min_streak)))
+    })
+}
+
+// Sets the default curated/LLM mix used by chats without an override
+#[update]
+fn set_default_source_mix(mix: SourceMix) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        config::validate_mix(&mix)?;
+        config::update(caller(), |config| config.source_mix = mix)?;
+        Ok("Default source mix updated.".to_string())
+    })
+}
+
+// Overrides the curated/LLM mix for one chat; pass null to revert to the default
+#[update]
+fn set_chat_source_mix(chat_id: String, mix: Option<SourceMix>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let chat_id = chat_scope::require_chat_key(&chat_id)?;
+        if let Some(mix) = &mix { config::validate_mix(mix)?; }
+        state::CHAT_CONFIGS.with(|configs_ref| {
+            let mut configs = configs_ref.borrow_mut();
+            let mut chat_config = configs.get(&chat_id).unwrap_or_default();
+            chat_config.source_mix = mix;
+            configs.insert(chat_id.clone(), chat_config);
+        });
+        Ok(format!("Source mix for chat {} updated.", chat_id))
+    })
+}
+
+// Subscribes a chat to seasonal theme announcements (or unsubscribes it)
+#[update]
+fn set_chat_announcements(chat_id: String, enabled: bool) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let chat_id = chat_scope::require_chat_key(&chat_id)?;
+        state::CHAT_CONFIGS.with(|configs_ref| {
+            let mut configs = configs_ref.borrow_mut();
+            let mut chat_config = configs.get(&chat_id).unwrap_or_default();
+            chat_config.announcements = Some(enabled);
+            configs.insert(chat_id.clone(), chat_config);
+        });
+        Ok(format!("Theme announcements for chat {} turned {}.", chat_id, if enabled { "on" } else { "off" }))
+    })
+}
+
+// --- Admin Endpoints: Content Import ---
+
+// /import_url <https link>: fetches a CSV or JSON dare list and queues the new, valid rows
+#[update]
+async fn import_url(url: String) -> Result<String, String> {
+    formatting::reply_async(async {
+        ensure_admin()?;
+        imports::start(url, caller()).await
+    }).await
+}
+
+#[query]
+fn import_status(job_id: u64) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        imports::status(job_id)
+    })
+}
+
+// Strips the fetched file's headers so every replica sees the same response
+#[query]
+fn transform_import_response(args: http_request::TransformArgs) -> http_request::HttpResponse {
+    imports::transform(args)
+}
+
+// --- Admin Endpoints: LLM Prefill ---
+
+// --- Warm Standby (admin) ---
+
+// On the primary: the canister to mirror state to; null stops replication
+#[update]
+fn set_replication_standby(standby: Option<candid::Principal>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        replication::set_standby(standby)
+    })
+}
+
+// On the standby: the primary allowed to push; null stops accepting pushes without promoting
+#[update]
+fn set_replication_primary(primary: Option<candid::Principal>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        replication::set_primary(primary)
+    })
+}
+
+// Called by the primary's replication timer
+#[update]
+fn apply_replication(writes: Vec<types::ReplicatedWrite>) -> Result<u64, String> {
+    correlation::begin();
+    correlation::set_operation("apply_replication");
+    replication::apply(caller(), writes)
+}
+
+// Registers a canister set up like a standby (set_replication_primary) as a read replica that
+// serves leaderboard and analytics queries; `endpoint` defaults to its icp0.io URL
+#[update]
+fn register_read_replica(canister: candid::Principal, endpoint: Option<String>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        replication::register_replica(canister, endpoint)
+    })
+}
+
+#[update]
+fn unregister_read_replica(canister: candid::Principal) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        replication::unregister_replica(canister)
+    })
+}
+
+// Disaster recovery: the standby stops mirroring and takes over as the primary
+#[update]
+fn promote_standby() -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        replication::promote()
+    })
+}
+
+#[query]
+fn replication_status() -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        Ok(replication::status())
+    })
+}
+
+// /prefill_dares <n>: pre-generates n LLM dares per difficulty (inside the off-peak window, if set)
+#[update]
+fn prefill_dares(per_difficulty: u32) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        prefill::queue(per_difficulty)
+    })
+}
+
+#[update]
+fn cancel_prefill() -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        prefill::cancel()
+    })
+}
+
+#[query]
+fn prefill_status() -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        Ok(prefill::status())
+    })
+}
+
+// Daily top-up to `per_difficulty` unserved dares in an off-peak UTC window; null = manual batches only
+#[update]
+fn set_prefill_schedule(schedule: Option<types::PrefillSchedule>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        if let Some(schedule) = &schedule { prefill::validate_schedule(schedule)?; }
+        config::update(caller(), |config| config.prefill = schedule)?;
+        Ok("Prefill schedule updated.".to_string())
+    })
+}
+
+// /set_llm_params <difficulty> [params]: the model, temperature and token limit for generating
+// dares of one difficulty; null = back to the defaults
+#[update]
+fn set_llm_params(difficulty: Difficulty, params: Option<types::LlmParams>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let params = params.map(|params| types::LlmParams { model: params.model.trim().to_string(), ..params });
+        if let Some(params) = &params { llm::validate_params(&[(difficulty.clone(), params.clone())])?; }
+        let described = params.as_ref().map(|p| format!("{} at temperature {} with up to {} tokens", p.model, p.temperature, p.max_tokens));
+        config::update(caller(), |config| llm::set_params(&mut config.llm_params, difficulty.clone(), params))?;
+        Ok(match described {
+            Some(described) => format!("{:?} dares now use {}.", difficulty, described),
+            None => format!("{:?} dares use the default LLM parameters again.", difficulty),
+        })
+    })
+}
+
+// The parameters each difficulty generates with, defaults included
+#[query]
+fn get_llm_params() -> Result<Vec<(Difficulty, types::LlmParams)>, String> {
+    ensure_admin()?;
+    Ok([Difficulty::Easy, Difficulty::Medium, Difficulty::Hard].into_iter()
+        .map(|difficulty| { let params = llm::params_for(&difficulty); (difficulty, params) })
+        .collect())
+}
+
+// Words or phrases that get a generated dare rejected, on top of the built-in categories; empty = none
+#[update]
+fn set_llm_blocked_terms(terms: Vec<String>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let terms: Vec<String> = terms.iter().map(|term| term.trim().to_lowercase()).collect();
+        let count = terms.len();
+        config::update(caller(), |config| config.llm_blocked_terms = (!terms.is_empty()).then_some(terms))?;
+        Ok(format!("{} blocked term(s) configured.", count))
+    })
+}
+
+// LLM dares the safety check held back, newest first
+#[query]
+fn list_rejected_dares(offset: u64, limit: u64) -> Result<Vec<types::RejectedDare>, String> {
+    ensure_admin()?;
+    Ok(safety::list(offset as usize, limit.min(100) as usize))
+}
+
+#[update]
+fn dismiss_rejected_dare(id: u64) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        safety::take(id)?;
+        Ok(format!("Rejected dare #{} dismissed.", id))
+    })
+}
+
+// A false positive: stores the sample as a curated dare
+#[update]
+fn approve_rejected_dare(id: u64) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let rejected = safety::take(id)?;
+        let dare_id = store_dare(rejected.text, rejected.difficulty, DareSource::Curated, Some(caller()))?;
+        Ok(format!("Rejected dare #{} approved as dare #{}.", id, dare_id))
+    })
+}
+
+// Token and cycle spend of LLM calls by feature, chat and day; null = the current month
+#[query]
+fn get_llm_spend_report(period: Option<types::SpendPeriod>) -> Result<types::LlmSpendReport, String> {
+    ensure_admin()?;
+    let period = period.unwrap_or_else(|| {
+        let (year, month, _) = timezone::civil_from_days((ic_cdk::api::time() / (24 * 60 * 60 * 1_000_000_000)) as i64);
+        types::SpendPeriod::Month(format!("{:04}-{:02}", year, month))
+    });
+    llm_usage::report(&period)
+}
+
+// --- Dare Marketplace ---
+
+// /publish_dares: shares curated dares with other chats. The bot relays it only from group
+// owners; controllers can call it directly.
+#[update]
+fn publish_dares(chat_id: String, title: String, dare_ids: Vec<u64>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        let by = if accounts::relayed_by_bot(on_behalf_of) {
+            accounts::caller_account(on_behalf_of)?
+        } else {
+            ensure_admin()?;
+            caller()
+        };
+        let chat_key = commands::enforce(commands::Command::PublishDares, Some(&chat_id))?
+            .ok_or_else(|| "Dares can only be published from a group chat or a community channel.".to_string())?;
+        let id = marketplace::publish(&chat_key, title, dare_ids, by)?;
+        Ok(format!("🛒 Published as listing #{}. Other chats can import it with /import_dares {}.", id, id))
+    })
+}
+
+// /marketplace [page]: listings shared by other chats
+#[query]
+fn browse_marketplace(page: Option<u32>, chat_id: Option<String>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    let user = accounts::caller_account(on_behalf_of)?;
+    formatting::reply_for(user, || {
+        commands::enforce(commands::Command::Marketplace, chat_id.as_deref())?;
+        Ok(marketplace::browse(page.unwrap_or(1).max(1) - 1))
+    })
+}
+
+// /import_dares <listing> [dare]: brings a pack (or one of its dares) into this chat. Relayed only
+// from group owners, like /publish_dares.
+#[update]
+fn import_dares(chat_id: String, listing_id: u64, dare_id: Option<u64>, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        if !accounts::relayed_by_bot(on_behalf_of) {
+            ensure_admin()?;
+        }
+        let chat_key = commands::enforce(commands::Command::ImportDares, Some(&chat_id))?
+            .ok_or_else(|| "Dares can only be imported into a group chat or a community channel.".to_string())?;
+        let (listing, imported) = marketplace::import(&chat_key, listing_id, dare_id)?;
+        Ok(format!(
+            "🛒 Imported {} new dare(s) from \"{}\" by {}. They'll come up more often in this chat.",
+            imported, listing.title, gallery::display_name(listing.published_by)
+        ))
+    })
+}
+
+#[query]
+fn list_marketplace() -> Vec<types::MarketplaceListing> {
+    marketplace::list()
+}
+
+#[update]
+fn remove_marketplace_listing(listing_id: u64) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let listing = marketplace::remove(listing_id)?;
+        Ok(format!("Listing #{} (\"{}\") removed; chats that imported it keep their dares.", listing.id, listing.title))
+    })
+}
+
+// /pause_bot [hours]: silences the bot's posts in a chat (and its game commands if asked). The
+// bot relays it only from group owners; controllers can call it directly.
+#[update]
+fn pause_bot(chat_id: String, duration_hours: Option<u32>, disable_commands: bool, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        let by = if accounts::relayed_by_bot(on_behalf_of) {
+            accounts::caller_account(on_behalf_of)?
+        } else {
+            ensure_admin()?;
+            caller()
+        };
+        commands::enforce(commands::Command::PauseBot, Some(&chat_id))?;
+        pauses::pause(&chat_id, duration_hours, disable_commands, by)
+    })
+}
+
+// Sent by the OpenChat bot when it's installed in a group; the reply offers /setup
+#[update]
+fn handle_installed_event(chat_id: String, installer: candid::Principal) -> Result<String, String> {
+    if !accounts::relayed_by_bot(Some(installer)) {
+        return Err("Only the Darely bot can report installations.".to_string());
+    }
+    formatting::reply_with(|| {
+        chat_scope::require_chat_key(&chat_id)?;
+        Ok(setup::welcome())
+    })
+}
+
+// /setup: a guided conversation for a group owner to configure the chat; each /setup_reply
+// answers the next question. The bot relays it only from group owners, who get the owner-level
+// questions; controllers can call it directly for the admin-only ones too.
+#[update]
+fn setup_start(chat_id: String, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        let by = if accounts::relayed_by_bot(on_behalf_of) {
+            accounts::caller_account(on_behalf_of)?
+        } else {
+            ensure_admin()?;
+            caller()
+        };
+        commands::enforce(commands::Command::Setup, Some(&chat_id))?;
+        setup::start(by, &chat_id, !accounts::relayed_by_bot(on_behalf_of))
+    })
+}
+
+#[update]
+fn setup_reply(chat_id: String, message: String, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        let by = if accounts::relayed_by_bot(on_behalf_of) {
+            accounts::caller_account(on_behalf_of)?
+        } else {
+            ensure_admin()?;
+            caller()
+        };
+        commands::enforce(commands::Command::Setup, Some(&chat_id))?;
+        setup::reply(by, &chat_id, &message, !accounts::relayed_by_bot(on_behalf_of))
+    })
+}
+
+// /config export and /config import <code>: copy a chat's settings to another chat as a preset
+// code (owners only, relayed like /pause_bot)
+#[update]
+fn export_chat_config(chat_id: String, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        if accounts::relayed_by_bot(on_behalf_of) {
+            accounts::caller_account(on_behalf_of)?;
+        } else {
+            ensure_admin()?;
+        }
+        commands::enforce(commands::Command::Config, Some(&chat_id))?;
+        presets::export(&chat_id)
+    })
+}
+
+#[update]
+fn import_chat_config(chat_id: String, code: String, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        // Relayed owners can only import the owner-level settings (see presets.rs)
+        let admin = if accounts::relayed_by_bot(on_behalf_of) {
+            accounts::caller_account(on_behalf_of)?;
+            false
+        } else {
+            ensure_admin()?;
+            true
+        };
+        commands::enforce(commands::Command::Config, Some(&chat_id))?;
+        presets::import(&chat_id, &code, admin)
+    })
+}
+
+#[update]
+fn resume_bot(chat_id: String, on_behalf_of: Option<candid::Principal>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        if !accounts::relayed_by_bot(on_behalf_of) {
+            ensure_admin()?;
+        }
+        commands::enforce(commands::Command::ResumeBot, Some(&chat_id))?;
+        if !pauses::resume(&chat_id)? {
+            return Err("Darely isn't paused in this chat.".to_string());
+        }
+        Ok("▶️ Darely is back in this chat.".to_string())
+    })
+}
+
+#[query]
+fn get_chat_config(chat_id: String) -> ChatConfig {
+    chat_scope::chat_key(Some(&chat_id)).and_then(|chat_id| state::get_chat_config(&chat_id)).unwrap_or_default()
+}
+
+// Users who have requested dares in the chat, with their last activity there (nanos)
+#[query]
+fn list_chat_members(chat_id: String) -> Result<Vec<(candid::Principal, u64)>, String> {
+    ensure_admin()?;
+    Ok(chat_members::members(&chat_scope::require_chat_key(&chat_id)?))
+}
+
+
+// --- Admin Endpoints: Listings ---
+
+#[query]
+fn list_users(filter: types::UserFilter, page: types::Page) -> Result<types::UserPage, String> {
+    ensure_admin()?;
+    Ok(listing::users(&filter, &page))
+}
+
+#[query]
+fn list_submissions(
+    status: Option<types::SubmissionStatus>,
+    sort: Option<types::SubmissionSort>,
+    page: types::Page,
+) -> Result<types::SubmissionPage, String> {
+    ensure_admin()?;
+    Ok(listing::submissions(status, sort, &page))
+}
+
+#[query]
+fn list_suggestions(offset: u64, limit: u64) -> Result<Vec<Suggestion>, String> {
+    ensure_admin()?;
+    Ok(suggestions::list(offset as usize, limit.min(100) as usize))
+}
+
+// Removes a suggestion from the queue (after adding it with /add_dare, or to reject it)
+#[update]
+fn dismiss_suggestion(suggestion_id: u64) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        suggestions::remove(suggestion_id)
+            .map(|_| format!("Suggestion #{} removed.", suggestion_id))
+            .ok_or_else(|| format!("Suggestion #{} not found.", suggestion_id))
+    })
+}
+
+// --- Admin Endpoints: Pinned Dares ---
+
+// /pin_dare <id> [@user|all]: the target's next /get_dare returns this dare (null target = everyone)
+#[update]
+fn pin_dare(dare_id: u64, target: Option<candid::Principal>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let target = target.map(|t| accounts::resolve(accounts::Identity::Direct(t)));
+        pins::pin(dare_id, target, caller())?;
+        Ok(match target {
+            Some(user) => format!("Dare #{} pinned as the next dare for {}.", dare_id, user),
+            None => format!("Dare #{} pinned as everyone's next dare.", dare_id),
+        })
+    })
+}
+
+#[update]
+fn unpin_dare(target: Option<candid::Principal>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let target = target.map(|t| accounts::resolve(accounts::Identity::Direct(t)));
+        let pin = pins::unpin(target)?;
+        Ok(format!("Dare #{} unpinned.", pin.dare_id))
+    })
+}
+
+#[query]
+fn list_pinned_dares() -> Result<Vec<(Option<candid::Principal>, types::PinnedDare)>, String> {
+    ensure_admin()?;
+    Ok(pins::list())
+}
+
+// --- Admin Endpoints: Questlines ---
+
+// /create_quest: starts a guided conversation; each /quest_reply answers the next question
+#[update]
+fn create_quest() -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        Ok(quests::start_draft(caller()))
+    })
+}
+
+#[update]
+fn quest_reply(message: String) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        quests::reply(caller(), &message)
+    })
+}
+
+// Closes a quest to new players; those already on it can finish
+#[update]
+fn archive_quest(quest_id: u64) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let quest = quests::archive(quest_id)?;
+        Ok(format!("Quest {} (\"{}\") archived.", quest.id, quest.name))
+    })
+}
+
+#[query]
+fn list_quests() -> Result<Vec<types::Quest>, String> {
+    ensure_admin()?;
+    Ok(quests::list())
+}
+
+// --- Admin Endpoints: Disabled Dares ---
+
+// /disable_dare <id> [chat]: keeps the dare out of selection everywhere (null) or in one chat
+#[update]
+fn disable_dare(dare_id: u64, chat_id: Option<String>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let chat_id = chat_id.as_deref().map(chat_scope::require_chat_key).transpose()?;
+        let scope = chat_id.as_ref().map_or("everywhere".to_string(), |chat_id| format!("in chat {}", chat_id));
+        if exclusions::disable(dare_id, chat_id.as_deref())? {
+            Ok(format!("Dare #{} disabled {}.", dare_id, scope))
+        } else {
+            Ok(format!("Dare #{} was already disabled {}.", dare_id, scope))
+        }
+    })
+}
+
+#[update]
+fn enable_dare(dare_id: u64, chat_id: Option<String>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let chat_id = chat_id.as_deref().map(chat_scope::require_chat_key).transpose()?;
+        let scope = chat_id.as_ref().map_or("everywhere".to_string(), |chat_id| format!("in chat {}", chat_id));
+        if exclusions::enable(dare_id, chat_id.as_deref())? {
+            Ok(format!("Dare #{} enabled {} again.", dare_id, scope))
+        } else {
+            Err(format!("Dare #{} isn't disabled {}.", dare_id, scope))
+        }
+    })
+}
+
+// (chat id or null for all chats, dare id, disabled at)
+#[query]
+fn list_disabled_dares() -> Result<Vec<(Option<String>, u64, u64)>, String> {
+    ensure_admin()?;
+    Ok(exclusions::list())
+}
+
+// --- Admin Endpoints: Reward Wheel ---
+
+#[update]
+fn set_reward_wheel(wheel: types::RewardWheel) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        rewards::validate_wheel(&wheel)?;
+        config::update(caller(), |config| config.reward_wheel = Some(wheel))?;
+        Ok("Reward wheel updated.".to_string())
+    })
+}
+
+#[query]
+fn get_reward_wheel() -> types::RewardWheel {
+    rewards::wheel()
+}
+
+// Sets what redeeming does to the streak; pass null to go back to keeping it
+#[update]
+fn set_redemption_policy(policy: Option<types::RedemptionPolicy>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        if let Some(policy) = &policy { rewards::validate_policy(policy)?; }
+        config::update(caller(), |config| config.redemption_policy = policy)?;
+        Ok("Redemption policy updated.".to_string())
+    })
+}
+
+// Overrides the redemption policy for one chat; pass null to revert to the global policy
+#[update]
+fn set_chat_redemption_policy(chat_id: String, policy: Option<types::RedemptionPolicy>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let chat_id = chat_scope::require_chat_key(&chat_id)?;
+        if let Some(policy) = &policy { rewards::validate_policy(policy)?; }
+        state::CHAT_CONFIGS.with(|configs_ref| {
+            let mut configs = configs_ref.borrow_mut();
+            let mut chat_config = configs.get(&chat_id).unwrap_or_default();
+            chat_config.redemption_policy = policy;
+            configs.insert(chat_id.clone(), chat_config);
+        });
+        Ok(format!("Redemption policy for chat {} updated.", chat_id))
+    })
+}
+
+// `stock` limits how many users can be given the task; null = unlimited
+#[update]
+fn add_reward_task(text: String, stock: Option<u32>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let id = rewards::add_task(&text, stock)?;
+        Ok(format!("Reward task #{} added.", id))
+    })
+}
+
+#[query]
+fn list_reward_tasks(offset: u64, limit: u64) -> Result<Vec<(types::RewardTask, types::RewardTaskStatus)>, String> {
+    ensure_admin()?;
+    Ok(rewards::list_tasks(offset as usize, limit.min(100) as usize))
+}
+
+#[update]
+fn edit_reward_task(task_id: u64, text: String) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        rewards::edit_task(caller(), task_id, &text)?;
+        Ok(format!("Reward task #{} updated.", task_id))
+    })
+}
+
+#[update]
+fn restock_reward_task(task_id: u64, stock: Option<u32>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        rewards::restock_task(caller(), task_id, stock)?;
+        Ok(match stock {
+            Some(stock) => format!("Reward task #{} now has {} left.", task_id, stock),
+            None => format!("Reward task #{} is now unlimited.", task_id),
+        })
+    })
+}
+
+// Limits when the task can be won (nanosecond timestamps; null leaves that end open)
+#[update]
+fn schedule_reward_task(task_id: u64, starts_at: Option<u64>, ends_at: Option<u64>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        rewards::schedule_task(caller(), task_id, starts_at, ends_at)?;
+        Ok(format!("Availability window for reward task #{} updated.", task_id))
+    })
+}
+
+// Refused while a user holds the task or has its proof in review
+#[update]
+fn remove_reward_task(task_id: u64) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        rewards::remove_task(caller(), task_id)?;
+        Ok(format!("Reward task #{} removed.", task_id))
+    })
+}
+
+// --- Admin Endpoints: Truths ---
+
+#[update]
+fn add_truth(text: String) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let id = truths::add(&text)?;
+        Ok(format!("Truth #{} added.", id))
+    })
+}
+
+// Bulk import, e.g. from a question list; duplicates and invalid lines are skipped
+#[update]
+fn import_truths(texts: Vec<String>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let (added, skipped) = truths::import(texts)?;
+        Ok(format!("Imported {} truths ({} skipped as duplicates or invalid).", added, skipped))
+    })
+}
+
+#[update]
+fn remove_truth(truth_id: u64) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        truths::remove(truth_id)?;
+        Ok(format!("Truth #{} removed.", truth_id))
+    })
+}
+
+#[query]
+fn list_truths(offset: u64, limit: u64) -> Result<Vec<types::Truth>, String> {
+    ensure_admin()?;
+    Ok(truths::list(offset as usize, limit.min(100) as usize))
+}
+
+// --- Admin Endpoints: Seasonal Themes ---
+
+#[update]
+fn create_theme(input: types::ThemeInput) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let id = themes::create(input)?;
+        themes::run();
+        Ok(format!("Theme {} created. Tag its dares with /tag_theme_dares.", id))
+    })
+}
+
+#[update]
+fn tag_theme_dares(theme_id: u64, dare_ids: Vec<u64>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let count = themes::tag_dares(theme_id, dare_ids)?;
+        Ok(format!("Theme {} now has {} dares.", theme_id, count))
+    })
+}
+
+#[update]
+fn delete_theme(theme_id: u64) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        themes::delete(theme_id)?;
+        Ok(format!("Theme {} deleted.", theme_id))
+    })
+}
+
+#[query]
+fn list_themes() -> Vec<types::Theme> {
+    themes::list()
+}
+
+// --- Admin Endpoints: Global Events ---
+
+#[update]
+fn create_global_event(title: String, target: u64, starts_at: Option<u64>, duration_days: u32) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let id = events::create(title, target, starts_at, duration_days, caller())?;
+        Ok(format!("Global event {} created. Its badge is \"{}\".", id, events::badge_id(id)))
+    })
+}
+
+#[update]
+fn cancel_global_event(event_id: u64) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        events::cancel(event_id)?;
+        Ok(format!("Global event {} cancelled.", event_id))
+    })
+}
+
+#[query]
+fn list_global_events() -> Vec<types::GlobalEvent> {
+    events::list()
+}
+
+// --- Admin Endpoints: REST API ---
+
+// Replaces the list of browser origins allowed to call the REST API ("*" allows any)
+#[update]
+fn set_cors_allowed_origins(origins: Vec<String>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let origins = cors::normalize_origins(origins)?;
+        let count = origins.len();
+        config::update(caller(), |config| config.cors_allowed_origins = Some(origins))?;
+        Ok(format!("CORS allowed origins updated ({} entries).", count))
+    })
+}
+
+// Sets the DER root key used to verify Internet Identity delegations on signed REST writes.
+// Only needed on local replicas (`dfx ping` prints it); None restores the mainnet key.
+#[update]
+fn set_ic_root_key(root_key: Option<Vec<u8>>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        if let Some(der) = &root_key {
+            identity::validate_root_key(der)?;
+        }
+        let message = if root_key.is_some() { "IC root key set." } else { "IC root key reset to mainnet." };
+        config::update(caller(), |config| config.ic_root_key = root_key)?;
+        Ok(message.to_string())
+    })
+}
+
+// --- Admin Endpoints: Moderators ---
+
+#[update]
+fn add_moderator(moderator: candid::Principal) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        if moderation::add_moderator(moderator) {
+            Ok(format!("{} is now a moderator.", moderator))
+        } else {
+            Err(format!("{} is already a moderator.", moderator))
+        }
+    })
+}
+
+// Bans a user (every command for the account is refused) or lifts the ban
+#[update]
+fn set_user_banned(user: candid::Principal, banned: bool) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        if !state::is_registered(user) && banned {
+            return Err("That user isn't registered.".to_string());
+        }
+        Ok(match (bans::set(user, banned), banned) {
+            (true, true) => format!("{} is banned.", user),
+            (true, false) => format!("{} is no longer banned.", user),
+            (false, true) => format!("{} was already banned.", user),
+            (false, false) => format!("{} isn't banned.", user),
+        })
+    })
+}
+
+// Removes a moderator; their open review assignments go to the others
+#[update]
+fn remove_moderator(moderator: candid::Principal) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        if moderation::remove_moderator(moderator) {
+            Ok(format!("{} is no longer a moderator.", moderator))
+        } else {
+            Err(format!("{} is not a moderator.", moderator))
+        }
+    })
+}
+
+// Sets (or disables) auto-approval/rejection of submissions pending review too long
+#[update]
+fn set_review_sla(sla: Option<ReviewSla>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let message = match &sla {
+            Some(sla) => {
+                moderation::validate_sla(sla)?;
+                format!("Submissions pending review for more than {} hours will be auto-{}.",
+                    sla.max_pending_hours, if sla.action == types::SlaAction::Approve { "approved" } else { "rejected" })
+            }
+            None => "Review SLA disabled.".to_string(),
+        };
+        config::update(caller(), |config| config.review_sla = sla)?;
+        Ok(message)
+    })
+}
+
+// How long each kind of expired record is kept before the purge timer deletes it
+#[update]
+fn set_purge_retention(retention: Option<types::PurgeRetention>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        if let Some(retention) = &retention { purge::validate(retention)?; }
+        config::update(caller(), |config| config.purge_retention = retention)?;
+        Ok("Purge retention updated.".to_string())
+    })
+}
+
+// Runs the stale state purge now (it also runs every 10 minutes)
+#[update]
+fn purge_stale_state() -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        Ok(format!("Purged {}.", purge::run().describe()))
+    })
+}
+
+// Rolls a feature out to a percent of users (hashed from their principal); None = everyone
+#[update]
+fn set_feature_rollout(feature: String, percent: Option<u8>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let feature = features::find(&feature).ok_or_else(|| format!(
+            "Unknown feature '{}'. Features: {}.", feature, features::Feature::ALL.map(|f| f.name()).join(", ")
+        ))?;
+        config::update(caller(), |config| features::set_rollout(&mut config.feature_rollouts, feature, percent))?;
+        features::reset_metrics(feature);
+        Ok(match percent {
+            Some(percent) => format!("{} is now on for {}% of users. Its cohort metrics start over.", feature.name(), percent),
+            None => format!("{} is now on for everyone.", feature.name()),
+        })
+    })
+}
+
+// Each feature's rollout percent with exposures and completions per cohort
+#[query]
+fn get_feature_rollouts() -> Result<Vec<types::FeatureRollout>, String> {
+    ensure_admin()?;
+    Ok(features::report())
+}
+
+// Turns the post-approval difficulty/fun survey on or off
+#[update]
+fn set_rating_survey(enabled: bool) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        config::update(caller(), |config| config.rating_survey = Some(enabled))?;
+        Ok(format!("Rating survey turned {}.", if enabled { "on" } else { "off" }))
+    })
+}
+
+// Whether a submission raises the streak once a moderator approves it (the default), rather than
+// right away (taken back on rejection). Submissions already made keep the rule they were made under.
+#[update]
+fn set_streak_on_approval(enabled: bool) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        config::update(caller(), |config| config.streak_on_approval = Some(enabled))?;
+        Ok(if enabled {
+            "Streaks now go up when a submission is approved.".to_string()
+        } else {
+            "Streaks now go up on submission and are taken back if it's rejected.".to_string()
+        })
+    })
+}
+
+// /set_dare_acceptance [declines]: /dare proposes and waits for /accept, allowing this many
+// /decline per day; null = dares are assigned at once
+#[update]
+fn set_dare_acceptance(daily_declines: Option<u32>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        if let Some(declines) = daily_declines { proposals::validate_declines(declines)?; }
+        config::update(caller(), |config| config.daily_declines = daily_declines)?;
+        Ok(match daily_declines {
+            Some(declines) => format!("/dare now proposes dares, with {} declines per day.", declines),
+            None => "/dare assigns dares at once again.".to_string(),
+        })
+    })
+}
+
+// /set_streak_expiry [hours]: resets streaks after this many hours without a completed dare;
+// null = streaks never expire
+#[update]
+fn set_streak_expiry(hours: Option<u32>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        if let Some(hours) = hours { scheduler::validate_expiry(hours)?; }
+        config::update(caller(), |config| config.streak_expiry_hours = hours)?;
+        Ok(match hours {
+            Some(hours) => format!("Streaks now expire after {} hours without a dare.", hours),
+            None => "Streaks no longer expire.".to_string(),
+        })
+    })
+}
+
+// Players' difficulty/fun ratings per dare, with suggested difficulty changes
+#[query]
+fn get_dare_ratings(offset: u64, limit: u64) -> Result<Vec<types::DareRatingReport>, String> {
+    ensure_admin()?;
+    Ok(ratings::report(offset as usize, limit.min(100) as usize))
+}
+
+// Sets (or disables) backlog alerts for the review and suggestion queues
+#[update]
+fn set_queue_alerts(alerts: Option<types::QueueAlerts>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        if let Some(alerts) = &alerts { queue_alerts::validate(alerts)?; }
+        let message = if alerts.is_some() { "Queue alerts updated." } else { "Queue alerts disabled." };
+        config::update(caller(), |config| config.queue_alerts = alerts)?;
+        Ok(message.to_string())
+    })
+}
+
+#[query]
+fn list_moderators() -> Result<Vec<(candid::Principal, ModeratorStats)>, String> {
+    ensure_admin()?;
+    Ok(moderation::list_moderators())
+}
+
+// --- Admin Endpoints: Re-engagement Campaigns ---
+
+#[update]
+fn create_campaign(input: types::CampaignInput) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let id = campaigns::create(input)?;
+        Ok(format!("Campaign {} created. Lapsed users are enrolled on the next hourly run.", id))
+    })
+}
+
+#[update]
+fn set_campaign_active(campaign_id: u64, active: bool) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        campaigns::set_active(campaign_id, active)?;
+        Ok(format!("Campaign {} {}.", campaign_id, if active { "resumed" } else { "paused" }))
+    })
+}
+
+#[query]
+fn list_campaigns() -> Result<Vec<types::CampaignReport>, String> {
+    ensure_admin()?;
+    Ok(campaigns::reports())
+}
+
+// Called by the OpenChat bot to pick up queued DMs; returned messages are removed from the outbox
+#[update]
+fn drain_outbox(limit: Option<u32>) -> Result<Vec<types::OutboundMessage>, String> {
+    ensure_admin()?;
+    let limit = limit.map_or(outbox::MAX_DRAIN_BATCH, |l| l as usize).min(outbox::MAX_DRAIN_BATCH);
+    Ok(outbox::drain(limit))
+}
+
+// Called by the OpenChat bot once OpenChat accepted drained messages (needed with a delivery cap)
+#[update]
+fn ack_outbox(ids: Vec<u64>) -> Result<u32, String> {
+    ensure_admin()?;
+    Ok(outbox::ack(&ids))
+}
+
+// Caps deliveries the bot has drained but not acknowledged; None removes messages when drained
+#[update]
+fn set_outbox_max_in_flight(cap: Option<u32>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        if let Some(cap) = cap { outbox::validate_max_in_flight(cap)?; }
+        config::update(caller(), |config| config.outbox_max_in_flight = cap)?;
+        Ok(match cap {
+            Some(cap) => format!("At most {} outbox deliveries can be in flight; drained messages now need an ack.", cap),
+            None => "Outbox delivery cap removed; drained messages are removed at once.".to_string(),
+        })
+    })
+}
+
+#[query]
+fn get_outbox_status() -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let queued = state::OUTBOX.with(|o| o.borrow().len());
+        let cap = state::get_config().outbox_max_in_flight.map_or("no cap".to_string(), |cap| format!("cap {}", cap));
+        Ok(format!("📬 {} messages in the outbox, {} in flight ({}).", queued, outbox::in_flight(), cap))
+    })
+}
+
+// --- Admin Endpoints: Points ---
+
+#[update]
+fn grant_points(user: candid::Principal, amount: u64, memo: Option<String>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let block = points::mint(user, amount, memo.as_deref())?;
+        Ok(format!("Minted {} points for {} (block {}).", amount, user, block))
+    })
+}
+
+#[update]
+fn revoke_points(user: candid::Principal, amount: u64, memo: Option<String>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let block = points::burn(user, amount, memo.as_deref())?;
+        Ok(format!("Burned {} points from {} (block {}).", amount, user, block))
+    })
+}
+
+// Runs the ledger self-audit now (it also runs hourly)
+#[update]
+fn run_ledger_audit() -> Result<types::LedgerAudit, String> {
+    ensure_admin()?;
+    Ok(ledger::audit())
+}
+
+// Result of the most recent ledger audit since the last upgrade
+#[query]
+fn get_ledger_audit() -> Result<Option<types::LedgerAudit>, String> {
+    ensure_admin()?;
+    Ok(ledger::last_audit())
+}
+
+// Scans stored state for dangling references and contradictory fields; repair = false is a dry run
+#[update]
+fn validate_state(repair: bool) -> Result<types::StateReport, String> {
+    ensure_admin()?;
+    Ok(invariants::validate(repair))
+}
+
+// Checks that every stored record decodes and fits its bound; an unsafe result blocks the next upgrade
+#[update]
+fn preflight_upgrade() -> Result<types::PreflightReport, String> {
+    ensure_admin()?;
+    Ok(preflight::run())
+}
+
+// Lets an upgrade proceed despite the last preflight's warnings
+#[update]
+fn override_upgrade_block() -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        preflight::clear_block();
+        Ok("Upgrade block cleared.".to_string())
+    })
+}
+
+// Moves records that don't decode out of their maps (also runs after every upgrade). The sweep
+// runs in batches; admins are alerted if it quarantines anything.
+#[update]
+fn quarantine_corrupt_records() -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        if !quarantine::start_sweep() {
+            return Err("A sweep is already running.".to_string());
+        }
+        Ok("Sweep started; admins get a summary if it quarantines anything.".to_string())
+    })
+}
+
+#[query]
+fn list_corrupt_records(offset: u64, limit: u64) -> Result<Vec<types::CorruptRecord>, String> {
+    ensure_admin()?;
+    Ok(quarantine::list(offset, limit))
+}
+
+// Writes a quarantined record back once it decodes: as stored (after fixing the types) or as `value`
+#[update]
+fn repair_corrupt_record(id: u64, value: Option<serde_bytes::ByteBuf>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        quarantine::repair(id, value.map(serde_bytes::ByteBuf::into_vec))
+    })
+}
+
+#[update]
+fn delete_corrupt_record(id: u64) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        quarantine::delete(id)
+    })
+}
+
+// Seeds catalog dares and fake users for showcase deployments; undone by purge_demo_data
+#[update]
+fn seed_demo_data(users: u32, dares: u32) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let (added_dares, added_users) = demo::seed(dares, users)?;
+        Ok(format!("Seeded {} demo dares and {} demo users.", added_dares, added_users))
+    })
+}
+
+// Deletes the demo users and retires the demo dares
+#[update]
+fn purge_demo_data() -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let (retired, removed) = demo::purge()?;
+        Ok(format!("Retired {} demo dares and removed {} demo users.", retired, removed))
+    })
+}
+
+// Registers synthetic users with random streaks for load testing; refused in prod
+#[update]
+fn simulate_users(count: u32) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let added = environment::simulate_users(count)?;
+        Ok(format!("Added {} simulated users.", added))
+    })
+}
+
+// Encoded sizes of user profile writes, bucketed by powers of two
+#[query]
+fn get_profile_size_histogram() -> Result<types::SizeHistogram, String> {
+    ensure_admin()?;
+    Ok(telemetry::profile_sizes())
+}
+
+// Bots allowed to pass `on_behalf_of` for OpenChat users (the Darely OpenChat bot's principal)
+#[update]
+fn set_trusted_bots(bots: Vec<candid::Principal>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let count = bots.len();
+        config::update(caller(), |config| config.trusted_bots = (!bots.is_empty()).then_some(bots))?;
+        Ok(format!("{} trusted bot(s) configured.", count))
+    })
+}
+
+// The group chat or channel that gets unexpected-error reports; null = the alert recipients
+#[update]
+fn set_incident_channel(chat_id: Option<String>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let channel = chat_id.as_deref().map(chat_scope::require_chat_key).transpose()?;
+        let reply = match &channel {
+            Some(channel) => format!("Unexpected errors are reported to {}.", channel),
+            None => "Unexpected errors are reported to the alert recipients.".to_string(),
+        };
+        config::update(caller(), |config| config.incident_channel = channel)?;
+        Ok(reply)
+    })
+}
+
+// Unexpected errors by signature, most recent first
+#[query]
+fn list_incidents() -> Result<Vec<types::Incident>, String> {
+    ensure_admin()?;
+    Ok(incidents::list())
+}
+
+// Forgets an incident once it's fixed; null clears them all
+#[update]
+fn clear_incidents(signature: Option<String>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        incidents::clear(signature)
+    })
+}
+
+// Admins who receive operational alerts (e.g. ledger audit failures) as DMs via the outbox
+#[update]
+fn set_alert_recipients(recipients: Vec<candid::Principal>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let count = recipients.len();
+        config::update(caller(), |config| config.alert_recipients = (!recipients.is_empty()).then_some(recipients))?;
+        Ok(format!("Alerts go to {} recipient(s).", count))
+    })
+}
+
+// --- Admin Endpoints: Partner API Keys ---
+
+// Returns the new key; it is not stored in readable form, so it can't be shown again
+#[update]
+async fn create_api_key(name: String, scopes: Vec<types::ApiScope>, rate_limit_per_minute: Option<u32>) -> Result<String, String> {
+    ensure_admin()?;
+    api_keys::create(name, scopes, rate_limit_per_minute).await
+}
+
+#[update]
+fn revoke_api_key(key_id: u64) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        api_keys::revoke(key_id)?;
+        Ok(format!("API key {} revoked.", key_id))
+    })
+}
+
+#[query]
+fn list_api_keys() -> Result<Vec<types::ApiKeyInfo>, String> {
+    ensure_admin()?;
+    Ok(api_keys::list())
+}
+
+// --- Admin Endpoints: Community Registry ---
+
+// Sets (or clears) the OpenChat community registry canister used for group metadata
+#[update]
+fn set_registry_canister(registry: Option<candid::Principal>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        config::update(caller(), |config| config.registry_canister = registry)?;
+        Ok("Registry canister updated.".to_string())
+    })
+}
+
+// Fetches fresh metadata for a chat right away instead of waiting for the timer
+#[update]
+async fn sync_group_metadata(chat_id: String) -> Result<String, String> {
+    formatting::reply_async(async move {
+        ensure_admin()?;
+        let metadata = registry::sync_group(chat_scope::require_chat_key(&chat_id)?).await?;
+        Ok(format!("Synced {} ({} members).", metadata.name, metadata.member_count))
+    }).await
+}
+
+#[query]
+fn get_group_metadata(chat_id: String) -> Option<CachedGroupMetadata> {
+    chat_scope::chat_key(Some(&chat_id)).and_then(|chat_id| registry::cached(&chat_id))
+}
+
+// --- Admin Endpoints: Config ---
+
+// Who sees a command's successful replies; pass null to go back to the command's default
+#[update]
+fn set_command_visibility(command: String, visibility: Option<types::Visibility>) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let command = commands::find(&command).ok_or_else(|| format!("Unknown command '{}'.", command))?;
+        config::update(caller(), |config| commands::set_visibility(&mut config.command_visibility, command, visibility))?;
+        let audience = match command.visibility() {
+            types::Visibility::Public => "everyone in the chat",
+            types::Visibility::Ephemeral => "only the sender",
+        };
+        Ok(format!("/{} replies are now shown to {}.", command.name(), audience))
+    })
+}
+
+// The whole canister config as JSON, the document `set_config` patches
+#[query]
+fn get_config() -> Result<String, String> {
+    ensure_admin()?;
+    Ok(config::get_json())
+}
+
+// Applies a JSON merge patch (RFC 7386): keys replace values, objects merge, null clears a field.
+// Nothing is stored unless the patched config passes validation; all errors are listed.
+#[update]
+fn set_config(patch: String) -> Result<String, String> {
+    formatting::reply_with(|| {
+        ensure_admin()?;
+        let fields = config::set_json(caller(), &patch)?;
+        if fields.is_empty() {
+            return Ok("Config unchanged.".to_string());
+        }
+        Ok(format!("Config updated: {}.", fields.join(", ")))
+    })
+}
+
+// --- HTTP Gateway (REST API, see router.rs) ---
+
+#[query]
+fn http_request(request: HttpRequest) -> HttpResponse {
+    correlation::begin();
+    correlation::set_operation(&format!("HTTP {}", request.method));
+    router::handle_query(request)
+}
+
+#[update]
+fn http_request_update(request: HttpRequest) -> HttpResponse {
+    correlation::begin();
+    correlation::set_operation(&format!("HTTP {}", request.method));
+    router::handle_update(request)
 }
 
 
@@ -0,0 +1,112 @@
+use crate::bans;
+use crate::repository::{self, UserRepo};
+use crate::state;
+use crate::types::{Page, Submission, SubmissionPage, SubmissionSort, SubmissionStatus, UserFilter, UserPage, UserSort, UserSummary};
+use candid::Principal;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+// --- Admin Listings ---
+// Filtered, paginated views over users and submissions for operator dashboards. Each call scans
+// the underlying maps once but only returns the requested page, plus the total match count.
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 200;
+const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+// The requested page and the total item count. Pages past the end (or past usize on wasm32) are
+// empty rather than overflowing the offset.
+fn paginate<T>(items: impl Iterator<Item = T>, page: &Page) -> (Vec<T>, u64) {
+    let size = page.size.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE) as usize;
+    let offset = (page.number as usize).saturating_mul(size);
+    let mut total = 0;
+    let items = items
+        .inspect(|_| total += 1)
+        .enumerate()
+        .filter(|(index, _)| *index >= offset && index - offset < size)
+        .map(|(_, item)| item)
+        .collect();
+    (items, total)
+}
+
+// Latest history event per user
+fn last_activity() -> BTreeMap<Principal, u64> {
+    let mut latest = BTreeMap::new();
+    state::HISTORY.with(|history_ref| {
+        for (_, entry) in history_ref.borrow().iter() {
+            latest.insert(entry.user, entry.timestamp); // The log is in time order
+        }
+    });
+    latest
+}
+
+pub fn users(filter: &UserFilter, page: &Page) -> UserPage {
+    let now = ic_cdk::api::time();
+    let activity = last_activity();
+    // Only the sort keys are kept; profiles are fetched again for the requested page
+    let mut matches: Vec<(Principal, u32, Option<u64>)> = Vec::new();
+    repository::users().scan(None, &mut |user, profile| {
+        let last_active_at = activity.get(&user).copied();
+        let matched = filter.min_streak.is_none_or(|min| profile.streak >= min)
+            && filter.banned.is_none_or(|banned| bans::banned_at(user).is_some() == banned)
+            && filter.inactive_days.is_none_or(|days| {
+                // Users who never did anything count as inactive
+                last_active_at.is_none_or(|at| now.saturating_sub(at) >= days as u64 * DAY_NANOS)
+            });
+        if matched {
+            matches.push((user, profile.streak, last_active_at));
+        }
+        true
+    });
+    // Ties are broken by principal so pages stay stable between calls
+    match filter.sort.clone().unwrap_or(UserSort::StreakDesc) {
+        UserSort::StreakDesc => matches.sort_by_key(|&(user, streak, _)| (Reverse(streak), user)),
+        UserSort::StreakAsc => matches.sort_by_key(|&(user, streak, _)| (streak, user)),
+        UserSort::LastActiveDesc => matches.sort_by_key(|&(user, _, last_active_at)| (Reverse(last_active_at), user)),
+        UserSort::LastActiveAsc => matches.sort_by_key(|&(user, _, last_active_at)| (last_active_at, user)),
+    }
+    let (page, total) = paginate(matches.into_iter(), page);
+    let users = page.into_iter()
+        .filter_map(|(user, _, last_active_at)| {
+            let profile = repository::users().get(user)?;
+            Some(UserSummary { user, profile, last_active_at, banned_at: bans::banned_at(user) })
+        })
+        .collect();
+    UserPage { users, total }
+}
+
+fn status_of(submission: &Submission) -> SubmissionStatus {
+    match &submission.review {
+        None => SubmissionStatus::Pending,
+        Some(review) if review.approved => SubmissionStatus::Approved,
+        Some(_) => SubmissionStatus::Rejected,
+    }
+}
+
+pub fn submissions(status: Option<SubmissionStatus>, sort: Option<SubmissionSort>, page: &Page) -> SubmissionPage {
+    state::SUBMISSIONS.with(|s| {
+        let stored = s.borrow();
+        let matching = |submission: &Submission| status.is_none_or(|status| status_of(submission) == status);
+        // Ids are assigned in submission order
+        let (submissions, total) = if sort.unwrap_or(SubmissionSort::NewestFirst) == SubmissionSort::NewestFirst {
+            paginate(stored.iter().rev().map(|(_, submission)| submission).filter(matching), page)
+        } else {
+            paginate(stored.iter().map(|(_, submission)| submission).filter(matching), page)
+        };
+        SubmissionPage { submissions, total }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_past_the_end_are_empty() {
+        let page = |number, size| Page { number, size: Some(size) };
+        assert_eq!(paginate(0..120, &page(1, 50)), ((50..100).collect(), 120));
+        assert_eq!(paginate(0..120, &page(2, 50)), ((100..120).collect(), 120));
+        assert_eq!(paginate(0..120, &page(3, 50)), (Vec::new(), 120));
+        assert_eq!(paginate(0..120, &page(u32::MAX, MAX_PAGE_SIZE)), (Vec::new(), 120));
+    }
+}
@@ -1,15 +1,91 @@
-use crate::types::{Difficulty, OpenAIRequest, OpenAIMessage, OpenAIResponse}; // Use local types
+use crate::correlation;
+use crate::environment;
+use crate::llm_usage::{self, LlmFeature};
+use crate::safety;
+use crate::state;
+use crate::types::{Difficulty, LlmParams, LlmUsage, OpenAIRequest, OpenAIMessage, OpenAIResponse}; // Use local types
 use ic_cdk::api::management_canister::http_request::{
-    http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod, HttpResponse,
+    http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod,
 };
-use serde_json;
 
 // --- Configuration (Consider moving to a config module or constants in lib.rs/state.rs) ---
 const OPENAI_API_URL: &str = "https://api.openai.com/v1/chat/completions";
 const OPENAI_MODEL: &str = "gpt-3.5-turbo"; // Or gpt-4o-mini etc.
 const DARE_MAX_TOKENS: u32 = 60;
+const DARE_TEMPERATURE: f32 = 0.8;
+const HINT_MAX_TOKENS: u32 = 60;
+const MAX_MODEL_LEN: usize = 64;
+pub const MAX_DARE_TOKENS: u32 = 300; // Dare texts are capped at 500 bytes anyway
 const HTTP_REQUEST_CYCLES: u128 = 70_000_000_000; // Adjust based on testing!
 
+// --- Generation Parameters ---
+// Admins can give each difficulty its own model, temperature and token limit (e.g. a more
+// capable model for Hard dares, a cheap one for Easy); a difficulty without an entry uses the
+// defaults above. Hints always use the defaults.
+
+// --- Chat Language ---
+// A chat can have its LLM dares and hints written in another language (set through /setup). The
+// model is simply asked to write in it, so any language name it understands works.
+
+const MAX_LANGUAGE_LEN: usize = 30;
+
+pub fn validate_language(language: &str) -> Result<(), String> {
+    let allowed = |c: char| c.is_alphabetic() || matches!(c, ' ' | '-');
+    if language.is_empty() || language.chars().count() > MAX_LANGUAGE_LEN || !language.chars().all(allowed) {
+        return Err(format!("Give a language name of up to {} letters, e.g. \"Spanish\".", MAX_LANGUAGE_LEN));
+    }
+    Ok(())
+}
+
+pub fn language_for(chat_id: Option<&str>) -> Option<String> {
+    chat_id.and_then(state::get_chat_config).and_then(|config| config.language)
+}
+
+fn language_instruction(chat_id: Option<&str>) -> String {
+    language_for(chat_id).map_or_else(String::new, |language| format!(" Write it in {}.", language))
+}
+
+pub fn default_params() -> LlmParams {
+    LlmParams { model: OPENAI_MODEL.to_string(), temperature: DARE_TEMPERATURE, max_tokens: DARE_MAX_TOKENS }
+}
+
+pub fn params_for(difficulty: &Difficulty) -> LlmParams {
+    state::get_config().llm_params.unwrap_or_default().into_iter()
+        .find(|(d, _)| d == difficulty)
+        .map_or_else(default_params, |(_, params)| params)
+}
+
+pub fn validate_params(list: &[(Difficulty, LlmParams)]) -> Result<(), String> {
+    for (i, (difficulty, params)) in list.iter().enumerate() {
+        if list[..i].iter().any(|(d, _)| d == difficulty) {
+            return Err(format!("{:?} is listed more than once.", difficulty));
+        }
+        let model_ok = !params.model.is_empty() && params.model.len() <= MAX_MODEL_LEN
+            && params.model.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | ':'));
+        if !model_ok {
+            return Err(format!("{:?}: the model must be an OpenAI model name of at most {} characters.", difficulty, MAX_MODEL_LEN));
+        }
+        if !(0.0..=2.0).contains(&params.temperature) {
+            return Err(format!("{:?}: the temperature must be between 0 and 2.", difficulty));
+        }
+        if params.max_tokens == 0 || params.max_tokens > MAX_DARE_TOKENS {
+            return Err(format!("{:?}: max_tokens must be between 1 and {}.", difficulty, MAX_DARE_TOKENS));
+        }
+    }
+    Ok(())
+}
+
+// Stores a difficulty's parameters; None (or the defaults) removes its entry
+pub fn set_params(list: &mut Option<Vec<(Difficulty, LlmParams)>>, difficulty: Difficulty, params: Option<LlmParams>) {
+    let mut entries = list.take().unwrap_or_default();
+    entries.retain(|(d, _)| d != &difficulty);
+    if let Some(params) = params.filter(|params| params != &default_params()) {
+        entries.push((difficulty, params));
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+    *list = (!entries.is_empty()).then_some(entries);
+}
+
 // --- API Key Handling ---
 
 // Placeholder for securely getting API key
@@ -18,7 +94,7 @@ const HTTP_REQUEST_CYCLES: u128 = 70_000_000_000; // Adjust based on testing!
 fn get_openai_api_key() -> Result<String, String> {
     let key = "YOUR_OPENAI_API_KEY_HERE"; // <<<!!! REPLACE AND SECURE THIS !!!>>>
     if key == "YOUR_OPENAI_API_KEY_HERE" {
-        ic_cdk::println!("WARNING: Using placeholder API key in llm.rs. Replace get_openai_api_key() with a secure method!");
+        correlation::log!("WARNING: Using placeholder API key in llm.rs. Replace get_openai_api_key() with a secure method!");
         return Err("API Key is not configured securely. Update get_openai_api_key() in llm.rs.".to_string());
     }
     Ok(key.to_string())
@@ -26,23 +102,72 @@ fn get_openai_api_key() -> Result<String, String> {
 
 // --- Core LLM Interaction Logic ---
 
-// Fetches a dare from the LLM based on difficulty
-pub async fn fetch_llm_dare(difficulty: Difficulty) -> Result<String, String> {
-    let api_key = get_openai_api_key()?; // Propagate error if key not set
-
+// Fetches a dare from the LLM based on difficulty, avoiding the given topics (the user's blocked tags).
+// `feature` and `chat_id` attribute the call's spend (see llm_usage.rs).
+pub async fn fetch_llm_dare(difficulty: Difficulty, avoid_topics: &[String], feature: LlmFeature, chat_id: Option<&str>) -> Result<String, String> {
     // Construct Prompt
     let difficulty_str = format!("{:?}", difficulty).to_lowercase();
-    let prompt = format!(
+    let mut prompt = format!(
         "You are an assistant generating dares for an online community bot. Generate one short, fun, creative dare with '{}' difficulty. The dare should be actionable online or briefly in real life. IMPORTANT: Respond ONLY with the text of the dare itself, without any extra formatting, quotation marks, or preamble like 'Here is a dare:'.",
         difficulty_str
     );
+    if !avoid_topics.is_empty() {
+        prompt.push_str(&format!(" The dare must NOT involve or mention any of these: {}.", avoid_topics.join(", ")));
+    }
+    prompt.push_str(&language_instruction(chat_id));
+
+    // Unsafe dares are held for review and regenerated (see safety.rs)
+    let params = params_for(&difficulty);
+    for _ in 0..safety::MAX_ATTEMPTS {
+        let dare_text = complete(&prompt, &params, feature, chat_id).await?;
+        if dare_text.is_empty() {
+            return Err("LLM returned an empty dare.".to_string());
+        }
+        match safety::check(&dare_text) {
+            None => return Ok(dare_text),
+            Some(category) => safety::reject(dare_text, difficulty.clone(), category),
+        }
+    }
+    Err(format!("the LLM's last {} dares failed the safety check", safety::MAX_ATTEMPTS))
+}
+
+// Asks the LLM for a hint that helps with a dare without doing it for the user
+pub async fn fetch_llm_hint(dare_text: &str, chat_id: Option<&str>) -> Result<String, String> {
+    let prompt = format!(
+        "You are an assistant for an online dare bot. Give one short, practical hint that helps someone complete this dare without spoiling the fun: \"{}\". IMPORTANT: Respond ONLY with the hint itself, without any extra formatting, quotation marks, or preamble.{}",
+        dare_text, language_instruction(chat_id)
+    );
+    let params = LlmParams { model: OPENAI_MODEL.to_string(), temperature: 0.5, max_tokens: HINT_MAX_TOKENS };
+    // Unsafe hints are dropped and regenerated like dares, but not kept for review
+    for _ in 0..safety::MAX_ATTEMPTS {
+        let hint = complete(&prompt, &params, LlmFeature::Hint, chat_id).await?;
+        if hint.is_empty() {
+            return Err("LLM returned an empty hint.".to_string());
+        }
+        match safety::check(&hint) {
+            None => return Ok(hint),
+            Some(category) => correlation::log!("Rejected an LLM hint as {:?}.", category),
+        }
+    }
+    Err(format!("the LLM's last {} hints failed the safety check", safety::MAX_ATTEMPTS))
+}
 
-     // Prepare Request Body
+// Sends a single-message chat completion and returns the cleaned reply text
+async fn complete(prompt: &str, params: &LlmParams, feature: LlmFeature, chat_id: Option<&str>) -> Result<String, String> {
+    if !environment::llm_enabled() {
+        return Err("LLM calls are disabled in the dev environment.".to_string());
+    }
+    let api_key = get_openai_api_key()?; // Propagate error if key not set
+
+    // Prepare Request Body
     let request_body = OpenAIRequest {
-        model: OPENAI_MODEL,
-        messages: vec![OpenAIMessage { role: "user", content: &prompt }],
-        max_tokens: DARE_MAX_TOKENS,
-        temperature: 0.8, // Adjust creativity
+        model: &params.model,
+        messages: vec![
+            OpenAIMessage { role: "system", content: safety::SYSTEM_PROMPT },
+            OpenAIMessage { role: "user", content: prompt },
+        ],
+        max_tokens: params.max_tokens,
+        temperature: params.temperature,
     };
     // Use map_err for better error context
     let request_body_json = serde_json::to_string(&request_body)
@@ -53,6 +178,7 @@ pub async fn fetch_llm_dare(difficulty: Difficulty) -> Result<String, String> {
     let request_headers = vec![
         HttpHeader { name: "Authorization".to_string(), value: format!("Bearer {}", api_key) },
         HttpHeader { name: "Content-Type".to_string(), value: "application/json".to_string()},
+        HttpHeader { name: "X-Correlation-Id".to_string(), value: correlation::current() },
     ];
 
     let request = CanisterHttpRequestArgument {
@@ -65,44 +191,83 @@ pub async fn fetch_llm_dare(difficulty: Difficulty) -> Result<String, String> {
     };
 
     // Make HTTPS Outcall
-    ic_cdk::println!("Making HTTPS outcall to OpenAI...");
-    match http_request(request, HTTP_REQUEST_CYCLES).await {
+    correlation::log!("Making HTTPS outcall to OpenAI...");
+    let result = http_request(request, HTTP_REQUEST_CYCLES).await;
+    let mut usage = LlmUsage {
+        calls: 1,
+        cycles: HTTP_REQUEST_CYCLES.saturating_sub(ic_cdk::api::call::msg_cycles_refunded128()),
+        ..Default::default()
+    };
+    let reply = match result {
         Ok((response,)) => {
-            ic_cdk::println!("Received response, status: {}", response.status);
-            if response.status >= 200 && response.status < 300 {
+            correlation::log!("Received response, status: {}", response.status);
+            if response.status >= 200u32 && response.status < 300u32 {
                 // Parse successful response
                 match serde_json::from_slice::<OpenAIResponse>(&response.body) {
                     Ok(openai_response) => {
+                        if let Some(tokens) = &openai_response.usage {
+                            usage.prompt_tokens = tokens.prompt_tokens;
+                            usage.completion_tokens = tokens.completion_tokens;
+                        }
                         if let Some(choice) = openai_response.choices.first() {
-                            ic_cdk::println!("Successfully parsed dare from LLM.");
+                            correlation::log!("Successfully parsed LLM response.");
                             // Clean the response text
-                            let dare_text = choice.message.content.trim().trim_matches('"').to_string();
-                            if dare_text.is_empty() {
-                                Err("LLM returned an empty dare.".to_string())
-                            } else {
-                                Ok(dare_text)
-                            }
+                            Ok(choice.message.content.trim().trim_matches('"').to_string())
                         } else {
                             Err("LLM response contained no choices.".to_string())
                         }
                     }
                     Err(e) => {
                         let raw_body = String::from_utf8_lossy(&response.body);
-                        ic_cdk::println!("Failed to parse JSON response: {:?}\nRaw Body: {}", e, raw_body);
+                        correlation::log!("Failed to parse JSON response: {:?}\nRaw Body: {}", e, raw_body);
                         Err(format!("LLM Response Parse Error: {} (Check raw body in logs)", e))
                     }
                 }
             } else {
                 // Handle HTTP error status codes
                 let raw_body = String::from_utf8_lossy(&response.body);
-                ic_cdk::println!("HTTP Error Status: {}, Body: {}", response.status, raw_body);
+                correlation::log!("HTTP Error Status: {}, Body: {}", response.status, raw_body);
                 Err(format!("LLM API Error (Status {}): {}", response.status, raw_body))
             }
         }
         Err((code, message)) => {
             // Handle canister HTTPS outcall errors
-            ic_cdk::println!("HTTPS Outcall failed: {:?} {}", code, message);
+            correlation::log!("HTTPS Outcall failed: {:?} {}", code, message);
             Err(format!("HTTPS Outcall Error: {:?} {}", code, message))
         }
+    };
+    usage.failures = u64::from(reply.is_err());
+    llm_usage::record(feature, chat_id, &params.model, usage);
+    reply
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(model: &str, temperature: f32, max_tokens: u32) -> LlmParams {
+        LlmParams { model: model.to_string(), temperature, max_tokens }
+    }
+
+    #[test]
+    fn params_are_checked_per_difficulty() {
+        assert!(validate_params(&[(Difficulty::Hard, params("gpt-4o", 1.0, 100)), (Difficulty::Easy, params("gpt-4o-mini", 0.0, 40))]).is_ok());
+        assert!(validate_params(&[(Difficulty::Hard, params("gpt-4o", 1.0, 100)), (Difficulty::Hard, params("gpt-4o", 1.0, 100))]).is_err());
+        assert!(validate_params(&[(Difficulty::Hard, params("", 1.0, 100))]).is_err());
+        assert!(validate_params(&[(Difficulty::Hard, params("gpt 4", 1.0, 100))]).is_err());
+        assert!(validate_params(&[(Difficulty::Hard, params("gpt-4o", 2.5, 100))]).is_err());
+        assert!(validate_params(&[(Difficulty::Hard, params("gpt-4o", f32::NAN, 100))]).is_err());
+        assert!(validate_params(&[(Difficulty::Hard, params("gpt-4o", 1.0, 0))]).is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn setting_the_defaults_clears_the_entry() {
+        let mut list = None;
+        set_params(&mut list, Difficulty::Hard, Some(params("gpt-4o", 0.9, 80)));
+        set_params(&mut list, Difficulty::Easy, Some(params("gpt-4o-mini", 0.7, 40)));
+        assert_eq!(list.as_ref().map(|l| l.iter().map(|(d, _)| d.clone()).collect::<Vec<_>>()), Some(vec![Difficulty::Easy, Difficulty::Hard]));
+        set_params(&mut list, Difficulty::Easy, Some(default_params()));
+        set_params(&mut list, Difficulty::Hard, None);
+        assert_eq!(list, None);
+    }
+}
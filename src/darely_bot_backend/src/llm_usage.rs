@@ -0,0 +1,221 @@
+use crate::outbox;
+use crate::state;
+use crate::timezone;
+use crate::types::{CompositeKey, LlmSpendReport, LlmUsage, SpendPeriod};
+use std::collections::BTreeMap;
+
+// --- LLM Spend Accounting ---
+// Every LLM outcall adds its tokens (from the API's usage field) and the cycles it was charged
+// to a per-day entry keyed by the feature that made it and the chat it was made for. The report
+// sums a month or the last n days by feature, chat and day and estimates the USD cost from the
+// cycles' XDR value and the token prices of the model each call used (priced when recorded). Once a
+// month the alert recipients get the previous month's summary. Entries older than RETENTION_DAYS
+// are dropped with it.
+
+const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+const RETENTION_DAYS: u64 = 400;
+pub const MAX_REPORT_DAYS: u32 = 400;
+// List prices in USD per million (prompt, completion) tokens, matched by model name prefix, so
+// the more specific names come first. A model that isn't listed is priced as the dearest one.
+const MODEL_PRICES: &[(&str, f64, f64)] = &[
+    ("gpt-4o-mini", 0.15, 0.6),
+    ("gpt-4o", 2.5, 10.0),
+    ("gpt-4-turbo", 10.0, 30.0),
+    ("gpt-4", 30.0, 60.0),
+    ("gpt-3.5-turbo", 0.5, 1.5),
+];
+// Entries from before calls were priced by model were all made with it
+const LEGACY_MODEL: &str = "gpt-3.5-turbo";
+// The cycles' approximate dollar value (1T cycles = 1 XDR)
+const USD_PER_TRILLION_CYCLES: f64 = 1.35;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LlmFeature {
+    Dare,
+    Hint,
+    Prefill,
+}
+
+impl LlmFeature {
+    pub fn name(self) -> &'static str {
+        match self {
+            LlmFeature::Dare => "dare",
+            LlmFeature::Hint => "hint",
+            LlmFeature::Prefill => "prefill",
+        }
+    }
+}
+
+// Token cost in millionths of a USD
+fn token_micros(model: &str, prompt_tokens: u64, completion_tokens: u64) -> u64 {
+    let (_, prompt, completion) = MODEL_PRICES.iter().copied()
+        .find(|(name, _, _)| model.starts_with(name))
+        .unwrap_or_else(|| MODEL_PRICES.iter().copied().fold(("", 0.0, 0.0), |max, price| if price.2 > max.2 { price } else { max }));
+    (prompt_tokens as f64 * prompt + completion_tokens as f64 * completion).round() as u64
+}
+
+fn token_cost(usage: &LlmUsage) -> u64 {
+    usage.token_usd_micros.unwrap_or_else(|| token_micros(LEGACY_MODEL, usage.prompt_tokens, usage.completion_tokens))
+}
+
+fn add(total: &mut LlmUsage, usage: &LlmUsage) {
+    total.token_usd_micros = Some(token_cost(total) + token_cost(usage));
+    total.calls += usage.calls;
+    total.failures += usage.failures;
+    total.prompt_tokens += usage.prompt_tokens;
+    total.completion_tokens += usage.completion_tokens;
+    total.cycles += usage.cycles;
+}
+
+fn date(day: u64) -> String {
+    let (year, month, day) = timezone::civil_from_days(day as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// Called by llm.rs after every outcall made with `model`
+pub fn record(feature: LlmFeature, chat_id: Option<&str>, model: &str, mut usage: LlmUsage) {
+    usage.token_usd_micros = Some(token_micros(model, usage.prompt_tokens, usage.completion_tokens));
+    let day = ic_cdk::api::time() / DAY_NANOS;
+    let key = CompositeKey(day, CompositeKey(feature.name().to_string(), chat_id.unwrap_or_default().to_string()));
+    state::LLM_USAGE.with(|u| {
+        let mut map = u.borrow_mut();
+        let mut total = map.get(&key).unwrap_or_default();
+        add(&mut total, &usage);
+        map.insert(key, total);
+    });
+}
+
+pub fn estimated_usd(usage: &LlmUsage) -> f64 {
+    token_cost(usage) as f64 / 1e6 + usage.cycles as f64 * USD_PER_TRILLION_CYCLES / 1e12
+}
+
+// (first day, last day), inclusive
+fn days_of(period: &SpendPeriod, today: u64) -> Result<(u64, u64), String> {
+    match period {
+        SpendPeriod::LastDays(days) => {
+            if *days == 0 || *days > MAX_REPORT_DAYS {
+                return Err(format!("Report on 1-{} days.", MAX_REPORT_DAYS));
+            }
+            Ok((today.saturating_sub(*days as u64 - 1), today))
+        }
+        SpendPeriod::Month(month) => {
+            let (year, month) = month.split_once('-')
+                .and_then(|(year, month)| Some((year.parse::<i64>().ok()?, month.parse::<u32>().ok()?)))
+                .filter(|(year, month)| (1970..=9999).contains(year) && (1..=12).contains(month))
+                .ok_or_else(|| "Give the month as YYYY-MM.".to_string())?;
+            let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+            let first = timezone::days_from_civil(year, month, 1) as u64;
+            Ok((first, timezone::days_from_civil(next_year, next_month, 1) as u64 - 1))
+        }
+    }
+}
+
+pub fn report(period: &SpendPeriod) -> Result<LlmSpendReport, String> {
+    let (from, to) = days_of(period, ic_cdk::api::time() / DAY_NANOS)?;
+    let mut total = LlmUsage::default();
+    let (mut by_feature, mut by_chat, mut by_day) = (BTreeMap::new(), BTreeMap::new(), BTreeMap::new());
+    state::LLM_USAGE.with(|u| {
+        let start = CompositeKey(from, CompositeKey(String::new(), String::new()));
+        for (CompositeKey(day, CompositeKey(feature, chat)), usage) in u.borrow().range(start..).take_while(|(key, _)| key.0 <= to) {
+            add(&mut total, &usage);
+            add(by_feature.entry(feature).or_default(), &usage);
+            add(by_chat.entry(chat).or_default(), &usage);
+            add(by_day.entry(date(day)).or_default(), &usage);
+        }
+    });
+    let mut by_chat: Vec<(String, LlmUsage)> = by_chat.into_iter().collect();
+    by_chat.sort_by(|a, b| b.1.cycles.cmp(&a.1.cycles).then(b.1.calls.cmp(&a.1.calls)));
+    Ok(LlmSpendReport {
+        from_date: date(from),
+        to_date: date(to),
+        estimated_usd: estimated_usd(&total),
+        total,
+        by_feature: by_feature.into_iter().collect(),
+        by_chat,
+        by_day: by_day.into_iter().collect(),
+    })
+}
+
+fn summary(month: &str, report: &LlmSpendReport) -> String {
+    let features: Vec<String> = report.by_feature.iter()
+        .map(|(feature, usage)| format!("{} {} (${:.2})", feature, usage.calls, estimated_usd(usage)))
+        .collect();
+    format!(
+        "💸 LLM spend for {}: {} calls ({} failed), {} + {} tokens, {:.2}B cycles, about ${:.2}.\nBy feature: {}.",
+        month, report.total.calls, report.total.failures, report.total.prompt_tokens, report.total.completion_tokens,
+        report.total.cycles as f64 / 1e9, report.estimated_usd, features.join(", ")
+    )
+}
+
+// Called by the spend timer: once a new month has begun, DMs last month's summary to the alert
+// recipients and drops entries past the retention. Returns whether a summary went out.
+pub fn run() -> bool {
+    let today = ic_cdk::api::time() / DAY_NANOS;
+    let (year, month, _) = timezone::civil_from_days(today as i64);
+    let month_index = year as u64 * 12 + month as u64 - 1;
+    if state::LLM_SPEND_SUMMARY.with(|c| *c.borrow().get()) >= month_index {
+        return false;
+    }
+    state::LLM_SPEND_SUMMARY.with(|c| c.borrow_mut().set(month_index)).expect("Failed to save LLM spend summary month");
+
+    let previous = month_index - 1;
+    let label = format!("{:04}-{:02}", previous / 12, previous % 12 + 1);
+    let sent = match report(&SpendPeriod::Month(label.clone())) {
+        Ok(report) if report.total.calls > 0 => {
+            outbox::alert_admins(&summary(&label, &report));
+            true
+        }
+        _ => false,
+    };
+
+    let cutoff = CompositeKey(today.saturating_sub(RETENTION_DAYS), CompositeKey(String::new(), String::new()));
+    state::LLM_USAGE.with(|u| {
+        let mut map = u.borrow_mut();
+        let expired: Vec<_> = map.range(..cutoff).map(|(key, _)| key).collect();
+        for key in expired {
+            map.remove(&key);
+        }
+    });
+    sent
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn periods_cover_whole_days() {
+        let today = timezone::days_from_civil(2026, 3, 15) as u64;
+        assert_eq!(days_of(&SpendPeriod::LastDays(1), today), Ok((today, today)));
+        assert_eq!(days_of(&SpendPeriod::LastDays(7), today), Ok((today - 6, today)));
+        assert!(days_of(&SpendPeriod::LastDays(0), today).is_err());
+
+        let (from, to) = days_of(&SpendPeriod::Month("2024-02".to_string()), today).unwrap();
+        assert_eq!((date(from), date(to)), ("2024-02-01".to_string(), "2024-02-29".to_string()));
+        let (from, to) = days_of(&SpendPeriod::Month("2025-12".to_string()), today).unwrap();
+        assert_eq!((date(from), date(to)), ("2025-12-01".to_string(), "2025-12-31".to_string()));
+        assert!(days_of(&SpendPeriod::Month("2025-13".to_string()), today).is_err());
+        assert!(days_of(&SpendPeriod::Month("March".to_string()), today).is_err());
+    }
+
+    #[test]
+    fn estimate_adds_tokens_and_cycles() {
+        let usage = LlmUsage {
+            calls: 1, failures: 0, prompt_tokens: 1_000_000, completion_tokens: 1_000_000, cycles: 1_000_000_000_000, token_usd_micros: None,
+        };
+        assert!((estimated_usd(&usage) - 3.35).abs() < 1e-9);
+        let mut total = LlmUsage::default();
+        add(&mut total, &usage);
+        add(&mut total, &LlmUsage { token_usd_micros: Some(750_000), ..usage });
+        assert!((estimated_usd(&total) - 5.45).abs() < 1e-9);
+    }
+
+    #[test]
+    fn tokens_are_priced_by_model() {
+        assert_eq!(token_micros("gpt-4o-mini-2024-07-18", 1_000_000, 1_000_000), 750_000);
+        assert_eq!(token_micros("gpt-4o", 1_000_000, 0), 2_500_000);
+        assert_eq!(token_micros("gpt-3.5-turbo", 2_000, 1_000), 2_500);
+        // Unknown models are priced as the dearest listed one
+        assert_eq!(token_micros("o1-preview", 1_000_000, 0), 30_000_000);
+    }
+}
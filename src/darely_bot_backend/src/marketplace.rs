@@ -0,0 +1,171 @@
+use crate::exclusions;
+use crate::gallery;
+use crate::registry;
+use crate::repository::{self, DareRepo};
+use crate::state;
+use crate::themes;
+use crate::types::{CompositeKey, Dare, DareSource, MarketplaceListing, Submission};
+use candid::Principal;
+
+// --- Dare Marketplace ---
+// Chat owners can share their favourite curated dares with other chats: /publish_dares lists one
+// dare or a pack under a title, /marketplace browses the listings with their usage (imports by
+// other chats, approved completions, counted on each approval), and /import_dares brings a whole
+// pack or a single dare of it into another chat. Curated dares are shared by every chat already, so
+// importing one means the chat asks for more of it: it is re-enabled there if the chat had disabled
+// it, and gets IMPORT_BOOST_PERCENT extra selection weight in that chat. Listings keep the
+// publishing chat and owner, and each import remembers the first listing it came from, so
+// attribution survives both.
+
+const MAX_TITLE_LEN: usize = 60;
+const MAX_PACK_SIZE: usize = 20;
+const MAX_LISTINGS_PER_CHAT: usize = 10;
+const PAGE_SIZE: usize = 10;
+pub const IMPORT_BOOST_PERCENT: u32 = 100;
+
+fn get(listing_id: u64) -> Result<MarketplaceListing, String> {
+    state::MARKETPLACE.with(|m| m.borrow().get(&listing_id)).ok_or_else(|| format!("Listing {} not found.", listing_id))
+}
+
+fn chat_name(chat_key: &str) -> String {
+    registry::cached(chat_key).map_or_else(|| format!("chat {}", chat_key), |cached| cached.metadata.name)
+}
+
+pub fn publish(chat_key: &str, title: String, dare_ids: Vec<u64>, by: Principal) -> Result<u64, String> {
+    let title = title.trim().to_string();
+    if title.is_empty() || title.len() > MAX_TITLE_LEN {
+        return Err(format!("Listing title must be 1-{} characters.", MAX_TITLE_LEN));
+    }
+    let mut unique = Vec::new();
+    for dare_id in dare_ids {
+        if !unique.contains(&dare_id) {
+            unique.push(dare_id);
+        }
+    }
+    if unique.is_empty() || unique.len() > MAX_PACK_SIZE {
+        return Err(format!("A listing has 1-{} dares.", MAX_PACK_SIZE));
+    }
+    for &dare_id in &unique {
+        let dare = repository::dares().get(dare_id).ok_or_else(|| format!("Dare #{} not found.", dare_id))?;
+        if dare.source != DareSource::Curated {
+            return Err(format!("Dare #{} isn't curated; only curated dares can be published.", dare_id));
+        }
+    }
+    state::MARKETPLACE.with(|m| {
+        let mut listings = m.borrow_mut();
+        if listings.iter().filter(|(_, listing)| listing.publisher_chat == chat_key).count() >= MAX_LISTINGS_PER_CHAT {
+            return Err(format!("A chat can have at most {} listings.", MAX_LISTINGS_PER_CHAT));
+        }
+        let id = listings.last_key_value().map_or(0, |(id, _)| id + 1);
+        listings.insert(id, MarketplaceListing {
+            id,
+            title,
+            dare_ids: unique,
+            publisher_chat: chat_key.to_string(),
+            published_by: by,
+            published_at: ic_cdk::api::time(),
+            imports: 0,
+            completions: Some(0),
+        });
+        Ok(id)
+    })
+}
+
+pub fn remove(listing_id: u64) -> Result<MarketplaceListing, String> {
+    state::MARKETPLACE.with(|m| m.borrow_mut().remove(&listing_id)).ok_or_else(|| format!("Listing {} not found.", listing_id))
+}
+
+pub fn list() -> Vec<MarketplaceListing> {
+    state::MARKETPLACE.with(|m| m.borrow().iter().map(|(_, listing)| listing).collect())
+}
+
+// Called on every approval (including granted appeals): counts it for each listing with the dare
+pub fn credit_completion(submission: &Submission) {
+    let Some(dare_id) = submission.dare_id else { return };
+    state::MARKETPLACE.with(|m| {
+        let mut listings = m.borrow_mut();
+        let credited: Vec<MarketplaceListing> = listings.iter()
+            .map(|(_, listing)| listing)
+            .filter(|listing| listing.dare_ids.contains(&dare_id))
+            .collect();
+        for mut listing in credited {
+            listing.completions = Some(listing.completions.unwrap_or(0) + 1);
+            listings.insert(listing.id, listing);
+        }
+    });
+}
+
+// /marketplace [page]: most imported listings first
+pub fn browse(page: u32) -> String {
+    let mut listings = list();
+    if listings.is_empty() {
+        return "The marketplace is empty. Chat owners can share dares with /publish_dares.".to_string();
+    }
+    listings.sort_by_key(|listing| (std::cmp::Reverse(listing.imports), listing.id));
+    let pages = listings.len().div_ceil(PAGE_SIZE);
+    let lines: Vec<String> = listings.into_iter().skip(page as usize * PAGE_SIZE).take(PAGE_SIZE).map(|listing| {
+        format!(
+            "#{} {} ({} dare(s)) by {} from {}: imported by {} chat(s), completed {} times",
+            listing.id, listing.title, listing.dare_ids.len(), gallery::display_name(listing.published_by),
+            chat_name(&listing.publisher_chat), listing.imports, listing.completions.unwrap_or(0)
+        )
+    }).collect();
+    if lines.is_empty() {
+        return format!("The marketplace has {} page(s).", pages);
+    }
+    format!(
+        "🛒 Dare marketplace (page {}/{}):\n{}\nImport with /import_dares <listing> [dare].",
+        page + 1, pages, lines.join("\n")
+    )
+}
+
+// Imports a listing (or one dare of it) into a chat. Returns how many dares were new there.
+pub fn import(chat_key: &str, listing_id: u64, dare_id: Option<u64>) -> Result<(MarketplaceListing, usize), String> {
+    let mut listing = get(listing_id)?;
+    if listing.publisher_chat == chat_key {
+        return Err("This chat published that listing; its dares are already at home here.".to_string());
+    }
+    let dare_ids = match dare_id {
+        Some(dare_id) if !listing.dare_ids.contains(&dare_id) => {
+            return Err(format!("Dare #{} isn't part of listing {}.", dare_id, listing_id));
+        }
+        Some(dare_id) => vec![dare_id],
+        None => listing.dare_ids.clone(),
+    };
+    let mut imported = 0;
+    for dare_id in dare_ids {
+        // A dare already imported here stays credited to the listing it first came from
+        let key = CompositeKey(chat_key.to_string(), dare_id);
+        let new = state::CHAT_IMPORTS.with(|i| {
+            let mut imports = i.borrow_mut();
+            if imports.contains_key(&key) {
+                return false;
+            }
+            imports.insert(key, listing_id);
+            true
+        });
+        if new {
+            imported += 1;
+        }
+        exclusions::enable(dare_id, Some(chat_key))?;
+    }
+    let first_import = state::CHAT_IMPORTS.with(|i| state::range_by_first(&i.borrow(), &chat_key.to_string()))
+        .into_iter().filter(|&(_, from)| from == listing_id).count() == imported;
+    if imported > 0 && first_import {
+        listing.imports += 1;
+        state::MARKETPLACE.with(|m| m.borrow_mut().insert(listing_id, listing.clone()));
+    }
+    Ok((listing, imported))
+}
+
+// Raises the weight of dares the chat imported (see dare_for)
+pub fn boost_imported(dares: &mut [Dare], chat_id: Option<&str>) {
+    let Some(chat_key) = chat_id else { return };
+    let imported = state::CHAT_IMPORTS.with(|i| state::range_by_first(&i.borrow(), &chat_key.to_string()));
+    if imported.is_empty() {
+        return;
+    }
+    for dare in dares.iter_mut().filter(|dare| imported.iter().any(|&(id, _)| id == dare.id)) {
+        dare.weight = themes::boosted_weight(dare.weight, IMPORT_BOOST_PERCENT);
+    }
+}
@@ -0,0 +1,167 @@
+use crate::repository::{self, UserRepo};
+use candid::Principal;
+use std::cell::RefCell;
+use std::time::Duration;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+// --- Registration Filter ---
+// Every command checks whether the caller is registered, which costs a stable memory read and a
+// profile decode. A bloom filter over the registered principals lives on the heap, so an
+// unregistered caller is turned away without touching stable memory; a "maybe" still falls
+// through to the real lookup. Every profile write goes through state.rs, which adds the user
+// here, so the filter never misses a registered user. On init and post_upgrade it is filled from
+// the profile keys in timer-driven batches, and every caller takes the exact lookup until that is
+// done. A filter that fills up keeps serving (with more false positives) while a replacement with
+// twice the capacity is built the same way.
+
+const BITS_PER_USER: u64 = 10; // About 1% false positives at capacity with 7 hashes
+const HASHES: u64 = 7;
+const MIN_CAPACITY: u64 = 1_024;
+const BUILD_BATCH: usize = 10_000; // Principals per timer tick; keys only, no profile decodes
+
+struct Filter {
+    bits: Vec<u64>,
+    capacity: u64,
+    users: u64,
+}
+
+impl Filter {
+    fn with_capacity(capacity: u64) -> Self {
+        let words = (capacity * BITS_PER_USER).div_ceil(64) as usize;
+        Filter { bits: vec![0; words], capacity, users: 0 }
+    }
+
+    // Double hashing: bit i is h1 + i * h2
+    fn positions(&self, user: &Principal) -> impl Iterator<Item = usize> {
+        let hash = |seed: u8| {
+            let mut hasher = DefaultHasher::new();
+            seed.hash(&mut hasher);
+            user.as_slice().hash(&mut hasher);
+            hasher.finish()
+        };
+        let (h1, h2) = (hash(0), hash(1) | 1);
+        let bits = self.bits.len() as u64 * 64;
+        (0..HASHES).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % bits) as usize)
+    }
+
+    fn insert(&mut self, user: &Principal) {
+        for bit in self.positions(user).collect::<Vec<_>>() {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+        self.users += 1;
+    }
+
+    fn may_contain(&self, user: &Principal) -> bool {
+        self.positions(user).all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+}
+
+// Adds a user unless the filter already answers "maybe" for them
+fn add(filter: &mut Filter, user: &Principal) {
+    if !filter.may_contain(user) {
+        filter.insert(user);
+    }
+}
+
+// A replacement filter being filled, and the last principal added to it
+struct Build {
+    filter: Filter,
+    cursor: Option<Principal>,
+}
+
+impl Build {
+    fn new(registered: u64) -> Self {
+        Build { filter: Filter::with_capacity((registered * 2).max(MIN_CAPACITY)), cursor: None }
+    }
+
+    // Adds the next `limit` registered users. Returns whether every one is in.
+    fn fill(&mut self, users: &dyn UserRepo, limit: usize) -> bool {
+        let mut added = 0;
+        users.scan_keys(self.cursor, &mut |user| {
+            self.filter.insert(&user);
+            self.cursor = Some(user);
+            added += 1;
+            added < limit
+        });
+        added < limit
+    }
+}
+
+thread_local! {
+    static FILTER: RefCell<Option<Filter>> = const { RefCell::new(None) };
+    static BUILD: RefCell<Option<Build>> = const { RefCell::new(None) };
+}
+
+// Starts refilling the filter from the stored profiles unless a refill is running. The current
+// filter (if any) keeps answering meanwhile; it has no false negatives, just more false positives.
+pub fn rebuild() {
+    let started = BUILD.with(|b| {
+        let mut build = b.borrow_mut();
+        if build.is_some() {
+            return false;
+        }
+        *build = Some(Build::new(repository::users().len()));
+        true
+    });
+    if started {
+        ic_cdk_timers::set_timer(Duration::ZERO, build_batch);
+    }
+}
+
+// Timer: adds the next batch, then schedules the one after or swaps the finished filter in
+fn build_batch() {
+    let Some(mut build) = BUILD.with(|b| b.borrow_mut().take()) else {
+        return;
+    };
+    if build.fill(&repository::users(), BUILD_BATCH) {
+        FILTER.with(|f| *f.borrow_mut() = Some(build.filter));
+        return;
+    }
+    BUILD.with(|b| *b.borrow_mut() = Some(build));
+    ic_cdk_timers::set_timer(Duration::ZERO, build_batch);
+}
+
+pub fn insert(user: Principal) {
+    // The scan may already be past this user
+    BUILD.with(|b| {
+        if let Some(build) = b.borrow_mut().as_mut() {
+            add(&mut build.filter, &user);
+        }
+    });
+    let full = FILTER.with(|f| {
+        f.borrow_mut().as_mut().is_some_and(|filter| {
+            add(filter, &user);
+            filter.users > filter.capacity
+        })
+    });
+    // The replacement is sized for twice the registered users
+    if full {
+        rebuild();
+    }
+}
+
+// False means definitely not registered. Until the first filter is built every user may be.
+pub fn may_be_registered(user: Principal) -> bool {
+    FILTER.with(|f| f.borrow().as_ref().is_none_or(|filter| filter.may_contain(&user)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::memory::MemoryUsers;
+    use crate::types::UserProfile;
+
+    #[test]
+    fn batched_build_covers_every_user() {
+        let users = MemoryUsers::default();
+        for id in 0..25u8 {
+            users.put(Principal::from_slice(&[id, 1]), UserProfile::default());
+        }
+        let mut build = Build::new(users.len());
+        assert!(!build.fill(&users, 10));
+        assert!(!build.fill(&users, 10));
+        assert!(build.fill(&users, 10));
+        assert_eq!(build.filter.users, 25);
+        assert!((0..25u8).all(|id| build.filter.may_contain(&Principal::from_slice(&[id, 1]))));
+    }
+}
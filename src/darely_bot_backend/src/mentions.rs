@@ -0,0 +1,48 @@
+use crate::types::Difficulty;
+use candid::Principal;
+
+// --- Mentions ---
+// In autonomous mode the OpenChat bot forwards the chat messages it can read to
+// handle_message_event. A message that mentions the bot, either as an OpenChat mention
+// (`@UserId(<bot id>)`) or by name, and says "dare me" asks for a dare the same way /get_dare
+// does, optionally naming a difficulty ("@Darely dare me hard"). Everything else is ignored.
+
+const BOT_NAME: &str = "darely";
+const DEFAULT_DIFFICULTY: Difficulty = Difficulty::Easy;
+
+pub struct DareRequest {
+    pub difficulty: Difficulty,
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric() && c != '@')
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// Returns the message with the bot's mentions removed, or None if it doesn't mention the bot
+fn strip_mentions(text: &str, bot: Principal) -> Option<Vec<String>> {
+    let oc_mention = format!("@UserId({})", bot);
+    let mentioned = text.contains(&oc_mention);
+    let mut words = words(&text.replace(&oc_mention, " "));
+    let by_name = format!("@{}", BOT_NAME);
+    let before = words.len();
+    words.retain(|word| *word != by_name);
+    (mentioned || words.len() < before).then_some(words)
+}
+
+pub fn parse(text: &str, bot: Principal) -> Option<DareRequest> {
+    let words = strip_mentions(text, bot)?;
+    if !words.windows(2).any(|pair| pair[0] == "dare" && pair[1] == "me") {
+        return None;
+    }
+    let difficulty = words.iter().find_map(|word| match word.as_str() {
+        "easy" => Some(Difficulty::Easy),
+        "medium" => Some(Difficulty::Medium),
+        "hard" => Some(Difficulty::Hard),
+        _ => None,
+    });
+    Some(DareRequest { difficulty: difficulty.unwrap_or(DEFAULT_DIFFICULTY) })
+}
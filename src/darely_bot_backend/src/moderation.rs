@@ -0,0 +1,508 @@
+use crate::buddies;
+use crate::creators;
+use crate::duos;
+use crate::events;
+use crate::gallery;
+use crate::history;
+use crate::marketplace;
+use crate::outbox;
+use crate::quests;
+use crate::ranking;
+use crate::ratings;
+use crate::repository::{self, DareRepo};
+use crate::rewards;
+use crate::state;
+use crate::submissions;
+use crate::themes;
+use crate::types::{HistoryEvent, ModeratorStats, Review, ReviewItem, ReviewSla, ReviewTask, SlaAction, StorablePrincipal, Submission};
+use candid::Principal;
+
+// --- Review Queue ---
+// Every submission is queued for review and assigned round-robin: the next task goes to the
+// moderator who was assigned least recently (skipping anyone who passed on it). A moderator
+// claims their assigned tasks one at a time with /review claim, then approves, rejects or passes.
+// Assignments that sit untouched past the timeout are handed to the next moderator.
+
+const CLAIM_TIMEOUT_NANOS: u64 = 30 * 60 * 1_000_000_000; // 30 minutes
+pub const MAX_REASON_LEN: usize = 300;
+const HOUR_NANOS: u64 = 60 * 60 * 1_000_000_000;
+pub const MAX_SLA_HOURS: u32 = 30 * 24;
+
+pub fn is_moderator(user: Principal) -> bool {
+    state::MODERATORS.with(|m| m.borrow().contains_key(&StorablePrincipal(user)))
+}
+
+fn ensure_moderator(user: Principal) -> Result<(), String> {
+    if is_moderator(user) { Ok(()) } else { Err("Only moderators can review submissions.".to_string()) }
+}
+
+fn update_stats<F: FnOnce(&mut ModeratorStats)>(moderator: Principal, f: F) {
+    state::MODERATORS.with(|moderators_ref| {
+        let mut moderators = moderators_ref.borrow_mut();
+        let key = StorablePrincipal(moderator);
+        if let Some(mut stats) = moderators.get(&key) {
+            f(&mut stats);
+            moderators.insert(key, stats);
+        }
+    });
+}
+
+// The moderator whose turn it is, excluding `skip` (never-assigned moderators go first)
+fn next_moderator(skip: &[Principal]) -> Option<Principal> {
+    state::MODERATORS.with(|m| {
+        m.borrow().iter()
+            .filter(|(moderator, _)| !skip.contains(&moderator.0))
+            .min_by_key(|(_, stats)| stats.last_assigned_at)
+            .map(|(moderator, _)| moderator.0)
+    })
+}
+
+// A moderator other than `avoid` for work outside the review queue (e.g. appeals). Doesn't
+// advance the rotation.
+pub fn pick_moderator(avoid: Principal) -> Option<Principal> {
+    next_moderator(&[avoid])
+}
+
+// Assigns the task to the next moderator in rotation (or leaves it unassigned if none is left).
+// `avoid` is skipped unless nobody else is available.
+fn assign(task: &mut ReviewTask, now: u64, avoid: Option<Principal>) {
+    let mut skip = task.passed_by.clone();
+    skip.extend(avoid);
+    task.claimed_at = None;
+    task.assigned_to = next_moderator(&skip).or_else(|| next_moderator(&task.passed_by));
+    task.assigned_at = task.assigned_to.map(|_| now);
+    if let Some(moderator) = task.assigned_to {
+        update_stats(moderator, |stats| {
+            stats.assigned += 1;
+            stats.last_assigned_at = Some(now);
+        });
+    }
+}
+
+fn save(task: ReviewTask) {
+    state::REVIEW_QUEUE.with(|q| q.borrow_mut().insert(task.submission_id, task));
+}
+
+pub fn enqueue(submission_id: u64) {
+    let now = ic_cdk::api::time();
+    let mut task = ReviewTask {
+        submission_id,
+        queued_at: now,
+        assigned_to: None,
+        assigned_at: None,
+        claimed_at: None,
+        passed_by: Vec::new(),
+    };
+    assign(&mut task, now, None);
+    save(task);
+}
+
+// Drops a task whose submission no longer exists (e.g. undone)
+pub fn dequeue(submission_id: u64) {
+    state::REVIEW_QUEUE.with(|q| q.borrow_mut().remove(&submission_id));
+}
+
+fn review_item(submission_id: u64) -> Result<ReviewItem, String> {
+    let submission = state::SUBMISSIONS.with(|s| s.borrow().get(&submission_id))
+        .ok_or_else(|| format!("Submission {} not found.", submission_id))?;
+    let dare_text = submission.dare_text.clone().or_else(|| {
+        submission.dare_id.and_then(|id| repository::dares().get(id)).map(|dare| dare.text)
+    });
+    Ok(ReviewItem { submission, dare_text })
+}
+
+// --- Moderator Actions ---
+
+// Returns the moderator's current claim, or claims their oldest assigned task. Unassigned tasks
+// (queued while no moderator was available) are picked up here too.
+pub fn claim(moderator: Principal) -> Result<ReviewItem, String> {
+    ensure_moderator(moderator)?;
+    let now = ic_cdk::api::time();
+    let tasks: Vec<ReviewTask> = state::REVIEW_QUEUE.with(|q| q.borrow().iter().map(|(_, task)| task).collect());
+
+    if let Some(task) = tasks.iter().find(|t| t.assigned_to == Some(moderator) && t.claimed_at.is_some()) {
+        return review_item(task.submission_id);
+    }
+    let mut task = tasks.iter()
+        .find(|t| t.assigned_to == Some(moderator))
+        .or_else(|| tasks.iter().find(|t| t.assigned_to.is_none() && !t.passed_by.contains(&moderator)))
+        .cloned()
+        .ok_or_else(|| "No submissions are waiting for your review.".to_string())?;
+    if task.assigned_to.is_none() {
+        task.assigned_to = Some(moderator);
+        task.assigned_at = Some(now);
+        update_stats(moderator, |stats| {
+            stats.assigned += 1;
+            stats.last_assigned_at = Some(now);
+        });
+    }
+    task.claimed_at = Some(now);
+    let submission_id = task.submission_id;
+    save(task);
+    review_item(submission_id)
+}
+
+fn assigned_task(moderator: Principal, submission_id: u64) -> Result<ReviewTask, String> {
+    ensure_moderator(moderator)?;
+    let task = state::REVIEW_QUEUE.with(|q| q.borrow().get(&submission_id))
+        .ok_or_else(|| format!("Submission {} is not waiting for review.", submission_id))?;
+    if task.assigned_to != Some(moderator) {
+        return Err(format!("Submission {} is not assigned to you.", submission_id));
+    }
+    Ok(task)
+}
+
+// Releases the task to the next moderator; this moderator won't be assigned it again
+pub fn pass(moderator: Principal, submission_id: u64) -> Result<Option<Principal>, String> {
+    let mut task = assigned_task(moderator, submission_id)?;
+    task.passed_by.push(moderator);
+    assign(&mut task, ic_cdk::api::time(), None);
+    let reassigned_to = task.assigned_to;
+    save(task);
+    update_stats(moderator, |stats| stats.passed += 1);
+    Ok(reassigned_to)
+}
+
+// Records the decision. A rejected submission's streak point is taken back.
+pub fn decide(moderator: Principal, submission_id: u64, approved: bool, reason: Option<String>) -> Result<(), String> {
+    let reason = reason.map(|reason| reason.trim().to_string()).filter(|reason| !reason.is_empty());
+    if reason.as_ref().is_some_and(|reason| reason.len() > MAX_REASON_LEN) {
+        return Err(format!("The reason is too long (max {} bytes).", MAX_REASON_LEN));
+    }
+    assigned_task(moderator, submission_id)?;
+    finalize(submission_id, Review { moderator, approved, reviewed_at: ic_cdk::api::time(), sla_applied: None, reason })?;
+    if approved {
+        update_stats(moderator, |stats| stats.approved += 1);
+    } else {
+        update_stats(moderator, |stats| stats.rejected += 1);
+    }
+    Ok(())
+}
+
+// Stores the review on the submission, removes it from the queue, and applies the outcome: an
+// approval adds a streak point that was waiting for it, a rejection takes back the one counted
+// on submission (and the XP). Reward task proofs go back to rewards.rs instead.
+fn finalize(submission_id: u64, review: Review) -> Result<(), String> {
+    let mut submission = state::SUBMISSIONS.with(|s| s.borrow().get(&submission_id))
+        .ok_or_else(|| format!("Submission {} not found.", submission_id))?;
+    let approved = review.approved;
+    submission.review = Some(review);
+    let (user, dare_id, reward_task_id, xp) = (submission.user, submission.dare_id, submission.reward_task_id, submissions::xp_of(&submission));
+    let streak_pending = submission.streak_pending == Some(true);
+    let completed_at = submission.completed_at.unwrap_or(submission.submitted_at);
+    if approved {
+        if let Some(chat_id) = submission.chat_id.as_deref() {
+            gallery::index(chat_id, submission_id);
+        }
+        ratings::prompt(&submission);
+        creators::credit_completion(&submission);
+        marketplace::credit_completion(&submission);
+        events::note_completion(&submission);
+    } else if reward_task_id.is_none() {
+        duos::revert(&submission);
+    }
+    state::put_submission(submission);
+    dequeue(submission_id);
+
+    if let Some(task_id) = reward_task_id {
+        rewards::task_reviewed(user, task_id, submission_id, approved);
+        return Ok(());
+    }
+    if approved {
+        quests::note_approval(user, submission_id, dare_id);
+        themes::note_approval(user, dare_id, completed_at);
+    }
+    if approved && streak_pending {
+        // The submitter may have been removed since; the approval still stands
+        let raised = state::update_profile(user, |profile| {
+            ranking::set_streak(profile, streak_after_review(profile.streak, approved, streak_pending));
+            // The expiry clock waited for the approval too (see scheduler.rs)
+            profile.last_completed_at = profile.last_completed_at.max(Some(completed_at));
+            Ok((profile.streak, profile.redeemed_milestones.clone()))
+        });
+        if let Ok((streak, redeemed)) = raised {
+            note_streak_raised(user, streak, &redeemed);
+        }
+    }
+    if !approved {
+        // The submitter may have been removed since; the rejection still stands
+        let _ = state::update_profile(user, |profile| {
+            ranking::set_streak(profile, streak_after_review(profile.streak, approved, streak_pending));
+            profile.xp = Some(profile.xp.unwrap_or(0).saturating_sub(xp));
+            if profile.last_submission_id == Some(submission_id) {
+                profile.last_submission_id = None; // Nothing left to undo
+            }
+            quests::revert(profile, submission_id);
+            Ok(())
+        });
+        history::log_event(user, HistoryEvent::SubmissionRejected { submission_id, dare_id });
+    }
+    Ok(())
+}
+
+// The streak after a review: an approval adds the point a submission left to it, a rejection
+// takes back one counted on submission
+fn streak_after_review(streak: u32, approved: bool, streak_pending: bool) -> u32 {
+    match (approved, streak_pending) {
+        (true, true) => streak + 1,
+        (false, false) => streak.saturating_sub(1),
+        _ => streak,
+    }
+}
+
+// An approval raised the streak: the buddy DM and milestone notice a submission that counts at
+// once would have brought
+fn note_streak_raised(user: Principal, streak: u32, redeemed: &[u32]) {
+    buddies::note_completion(user, streak);
+    let mut text = format!("✅ Your dare was approved: your streak is now {}.", streak);
+    if let Some(note) = rewards::milestone_note(streak, redeemed) {
+        text.push(' ');
+        text.push_str(&note);
+    }
+    outbox::queue(user, text, None);
+}
+
+// Turns a rejection into an approval (a granted appeal) and gives back the streak point and XP
+pub fn overturn(submission_id: u64, review: Review) -> Result<(), String> {
+    let mut submission = state::SUBMISSIONS.with(|s| s.borrow().get(&submission_id))
+        .ok_or_else(|| format!("Submission {} not found.", submission_id))?;
+    if submission.review.as_ref().is_none_or(|review| review.approved) {
+        return Err(format!("Submission {} isn't rejected.", submission_id));
+    }
+    let (user, xp) = (submission.user, submissions::xp_of(&submission));
+    let completed_at = submission.completed_at.unwrap_or(submission.submitted_at);
+    if let Some(chat_id) = submission.chat_id.as_deref() {
+        gallery::index(chat_id, submission_id);
+    }
+    ratings::prompt(&submission);
+    creators::credit_completion(&submission);
+    marketplace::credit_completion(&submission);
+    events::note_completion(&submission);
+    quests::note_approval(user, submission_id, submission.dare_id);
+    duos::restore(&submission);
+    themes::note_approval(user, submission.dare_id, completed_at);
+    submission.review = Some(review);
+    state::put_submission(submission);
+    // The submitter may have been removed since; the approval still stands
+    let raised = state::update_profile(user, |profile| {
+        ranking::set_streak(profile, profile.streak + 1);
+        profile.xp = Some(profile.xp.unwrap_or(0) + xp);
+        profile.last_completed_at = profile.last_completed_at.max(Some(completed_at));
+        Ok((profile.streak, profile.redeemed_milestones.clone()))
+    });
+    if let Ok((streak, redeemed)) = raised {
+        note_streak_raised(user, streak, &redeemed);
+    }
+    Ok(())
+}
+
+pub fn stats(moderator: Principal) -> Result<ModeratorStats, String> {
+    state::MODERATORS.with(|m| m.borrow().get(&StorablePrincipal(moderator)))
+        .ok_or_else(|| "Only moderators can review submissions.".to_string())
+}
+
+// --- Admin ---
+
+pub fn add_moderator(moderator: Principal) -> bool {
+    state::MODERATORS.with(|moderators_ref| {
+        let mut moderators = moderators_ref.borrow_mut();
+        let key = StorablePrincipal(moderator);
+        if moderators.contains_key(&key) {
+            return false;
+        }
+        moderators.insert(key, ModeratorStats { added_at: ic_cdk::api::time(), ..Default::default() });
+        true
+    })
+}
+
+// Removes a moderator and hands their open tasks to the others
+pub fn remove_moderator(moderator: Principal) -> bool {
+    let removed = state::MODERATORS.with(|m| m.borrow_mut().remove(&StorablePrincipal(moderator))).is_some();
+    if removed {
+        let now = ic_cdk::api::time();
+        for mut task in tasks_assigned_to(Some(moderator)) {
+            assign(&mut task, now, None);
+            save(task);
+        }
+    }
+    removed
+}
+
+fn tasks_assigned_to(moderator: Option<Principal>) -> Vec<ReviewTask> {
+    state::REVIEW_QUEUE.with(|q| {
+        q.borrow().iter()
+            .map(|(_, task)| task)
+            .filter(|task| task.assigned_to == moderator)
+            .collect()
+    })
+}
+
+pub fn list_moderators() -> Vec<(Principal, ModeratorStats)> {
+    state::MODERATORS.with(|m| m.borrow().iter().map(|(moderator, stats)| (moderator.0, stats)).collect())
+}
+
+// --- Timer ---
+
+// Reassigns stale assignments (and assigns tasks queued while no moderator was around).
+// Returns how many tasks were reassigned.
+pub fn reassign_stale() -> usize {
+    let now = ic_cdk::api::time();
+    let stale: Vec<ReviewTask> = state::REVIEW_QUEUE.with(|q| {
+        q.borrow().iter()
+            .map(|(_, task)| task)
+            .filter(|task| match task.claimed_at.or(task.assigned_at) {
+                Some(since) => now.saturating_sub(since) > CLAIM_TIMEOUT_NANOS,
+                None => true,
+            })
+            .collect()
+    });
+    let mut reassigned = 0;
+    for mut task in stale {
+        let previous = task.assigned_to;
+        if let Some(moderator) = previous {
+            update_stats(moderator, |stats| stats.timed_out += 1);
+        }
+        assign(&mut task, now, previous);
+        if task.assigned_to.is_some() {
+            reassigned += 1;
+        }
+        save(task);
+    }
+    reassigned
+}
+
+// Applies the configured SLA to submissions queued longer than allowed. Returns how many were
+// auto-reviewed.
+pub fn apply_sla() -> usize {
+    let Some(sla) = state::get_config().review_sla else { return 0 };
+    let now = ic_cdk::api::time();
+    let max_pending = sla.max_pending_hours as u64 * HOUR_NANOS;
+    let overdue: Vec<u64> = state::REVIEW_QUEUE.with(|q| {
+        q.borrow().iter()
+            .filter(|(_, task)| now.saturating_sub(task.queued_at) > max_pending)
+            .map(|(id, _)| id)
+            .collect()
+    });
+    let mut applied = 0;
+    for submission_id in overdue {
+        let review = Review {
+            moderator: ic_cdk::id(),
+            approved: sla.action == SlaAction::Approve,
+            reviewed_at: now,
+            sla_applied: Some(sla.clone()),
+            reason: None,
+        };
+        match finalize(submission_id, review) {
+            Ok(()) => applied += 1,
+            // The submission is gone; drop the orphaned task
+            Err(_) => dequeue(submission_id),
+        }
+    }
+    applied
+}
+
+pub fn validate_sla(sla: &ReviewSla) -> Result<(), String> {
+    if sla.max_pending_hours == 0 || sla.max_pending_hours > MAX_SLA_HOURS {
+        return Err(format!("max_pending_hours must be between 1 and {}.", MAX_SLA_HOURS));
+    }
+    Ok(())
+}
+
+// --- Submission Status ---
+
+const DAY_NANOS: u64 = 24 * HOUR_NANOS;
+
+fn latest_submission(user: Principal) -> Option<Submission> {
+    state::SUBMISSIONS.with(|s| s.borrow().iter().rev().map(|(_, submission)| submission).find(|submission| submission.user == user))
+}
+
+// Reviews completed over the last day, as a measure of how fast the queue moves
+fn reviews_last_day(now: u64) -> u64 {
+    state::SUBMISSIONS.with(|s| {
+        s.borrow().iter()
+            .rev()
+            // Anything reviewed in the last day was submitted within the longest possible wait
+            .take_while(|(_, submission)| now.saturating_sub(submission.submitted_at) <= DAY_NANOS + MAX_SLA_HOURS as u64 * HOUR_NANOS)
+            .filter(|(_, submission)| submission.review.as_ref().is_some_and(|review| now.saturating_sub(review.reviewed_at) <= DAY_NANOS))
+            .count() as u64
+    })
+}
+
+// Rough wait for the submission: its queue position over last day's review rate, capped by the SLA
+fn estimate_wait_hours(task: &ReviewTask, now: u64) -> Option<u64> {
+    let position = state::REVIEW_QUEUE.with(|q| q.borrow().range(..task.submission_id).count() as u64) + 1;
+    let per_day = reviews_last_day(now);
+    let estimate = (per_day > 0).then(|| (position * 24).div_ceil(per_day));
+    let sla_left = state::get_config().review_sla.map(|sla| {
+        (sla.max_pending_hours as u64 * HOUR_NANOS).saturating_sub(now.saturating_sub(task.queued_at)).div_ceil(HOUR_NANOS)
+    });
+    match (estimate, sla_left) {
+        (Some(estimate), Some(sla_left)) => Some(estimate.min(sla_left)),
+        (estimate, sla_left) => estimate.or(sla_left),
+    }
+}
+
+// /submission_status: where the user's latest submission stands
+pub fn submission_status(user: Principal) -> Result<String, String> {
+    let now = ic_cdk::api::time();
+    let mut lines = Vec::new();
+    if let Some(parts) = submissions::draft_parts(user) {
+        lines.push(format!("📝 You have a draft with {} part(s). Finish it with /submit done.", parts));
+    }
+    let Some(submission) = latest_submission(user) else {
+        lines.push("You haven't submitted anything yet.".to_string());
+        return Ok(lines.join("\n"));
+    };
+    let subject = match submission.reward_task_id {
+        Some(task_id) => format!("Submission #{} (bonus task #{})", submission.id, task_id),
+        None => format!("Submission #{}", submission.id),
+    };
+    let queued = state::REVIEW_QUEUE.with(|q| q.borrow().get(&submission.id));
+    match (&submission.review, queued) {
+        (Some(review), _) if review.approved => lines.push(format!("✅ {} was approved.", subject)),
+        (Some(review), _) => {
+            let reason = match (&review.reason, &review.sla_applied) {
+                (Some(reason), _) => format!(" Reason: {}", reason),
+                (None, Some(sla)) => format!(" It wasn't reviewed within {} hours.", sla.max_pending_hours),
+                (None, None) => String::new(),
+            };
+            lines.push(format!("❌ {} was rejected.{}", subject, reason));
+            if submission.reward_task_id.is_none() {
+                let appeal = state::APPEALS.with(|a| a.borrow().get(&submission.id));
+                // A granted appeal approves the submission, so only open or denied ones show here
+                lines.push(match appeal {
+                    Some(appeal) if appeal.decision.is_some() => "⚖️ Your appeal was turned down.".to_string(),
+                    Some(_) => "⚖️ Your appeal is waiting for a decision.".to_string(),
+                    None => format!("You can contest it with /appeal {} <why it should count>.", submission.id),
+                });
+            }
+        }
+        (None, Some(task)) => {
+            let waited = now.saturating_sub(task.queued_at) / HOUR_NANOS;
+            let estimate = match estimate_wait_hours(&task, now) {
+                Some(0) | Some(1) => "within the hour".to_string(),
+                Some(hours) => format!("in about {} hours", hours),
+                None => "once a moderator is available".to_string(),
+            };
+            lines.push(format!("⏳ {} is waiting for review ({}h so far). Expect a decision {}.", subject, waited, estimate));
+        }
+        (None, None) => lines.push(format!("{} is not in the review queue.", subject)),
+    }
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn review_settles_the_streak_point_once() {
+        // Approving: only a point left to the approval is added
+        assert_eq!(streak_after_review(4, true, true), 5);
+        assert_eq!(streak_after_review(4, true, false), 4);
+        // Rejecting: only a point counted on submission is taken back
+        assert_eq!(streak_after_review(4, false, true), 4);
+        assert_eq!(streak_after_review(4, false, false), 3);
+        assert_eq!(streak_after_review(0, false, false), 0);
+    }
+}
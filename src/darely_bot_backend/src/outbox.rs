@@ -0,0 +1,109 @@
+use crate::chat_scope;
+use crate::correlation;
+use crate::pauses;
+use crate::state;
+use crate::types::OutboundMessage;
+use candid::Principal;
+
+// --- Outbox ---
+// Messages waiting for the OpenChat bot, which drains them with drain_outbox and delivers them:
+// direct messages, or group posts when `chat_id` is set. Used by campaigns, admin alerts, custom
+// dares and theme announcements. Each message carries the correlation id of the request that
+// queued it, so the bot can log its delivery under the same id.
+//
+// Backpressure: with `outbox_max_in_flight` set, a drain hands out messages on a lease instead of
+// removing them, and never more than the cap minus the deliveries still unacknowledged. The bot
+// acks each message once OpenChat accepted it; until then bursts (campaigns, theme
+// announcements, alerts) simply wait in the queue. A lease that runs out without an ack puts the
+// message back, so delivery is at least once. Without a cap, drained messages are removed at once.
+// Leases are also kept in an index by message id, so counting the deliveries in flight and
+// finding the next unleased messages doesn't decode the whole queue.
+
+pub const MAX_DRAIN_BATCH: usize = 100;
+pub const MAX_IN_FLIGHT_LIMIT: u32 = 1_000;
+const LEASE_NANOS: u64 = 5 * 60 * 1_000_000_000;
+
+pub fn queue(user: Principal, text: String, campaign_id: Option<u64>) {
+    push(user, text, campaign_id, None);
+}
+
+fn push(user: Principal, text: String, campaign_id: Option<u64>, chat_id: Option<String>) {
+    let now = ic_cdk::api::time();
+    state::OUTBOX.with(|outbox_ref| {
+        let mut outbox = outbox_ref.borrow_mut();
+        // Time-based ids stay unique and increasing even after the outbox has been drained
+        let id = outbox.last_key_value().map_or(now, |(last, _)| now.max(last + 1));
+        outbox.insert(id, OutboundMessage { id, user, text, campaign_id, queued_at: now, chat_id, leased_until: None, correlation_id: Some(correlation::current()) });
+    });
+}
+
+// Queues a post to a group chat, unless the chat is paused (see pauses.rs)
+pub fn queue_chat(chat_id: &str, text: String) {
+    if chat_scope::chat_key(Some(chat_id)).is_some_and(|chat_key| pauses::is_silenced(&chat_key)) {
+        return;
+    }
+    push(ic_cdk::api::id(), text, None, Some(chat_id.to_string()));
+}
+
+// Sends an alert to every configured admin alert recipient
+pub fn alert_admins(text: &str) {
+    for recipient in state::get_config().alert_recipients.unwrap_or_default() {
+        queue(recipient, text.to_string(), None);
+    }
+}
+
+pub fn validate_max_in_flight(cap: u32) -> Result<(), String> {
+    if cap == 0 || cap > MAX_IN_FLIGHT_LIMIT {
+        return Err(format!("The cap must be between 1 and {}.", MAX_IN_FLIGHT_LIMIT));
+    }
+    Ok(())
+}
+
+fn is_leased(id: u64, now: u64) -> bool {
+    state::OUTBOX_LEASES.with(|l| l.borrow().get(&id)).is_some_and(|until| until > now)
+}
+
+// Deliveries handed to the bot and not yet acknowledged. Only the lease index is read, which
+// holds no more than the messages drained under a cap and not acked since.
+pub fn in_flight() -> u64 {
+    let now = ic_cdk::api::time();
+    state::OUTBOX_LEASES.with(|l| l.borrow().iter().filter(|&(_, until)| until > now).count() as u64)
+}
+
+// Returns the oldest queued messages for delivery: removed without a cap, leased with one
+pub fn drain(limit: usize) -> Vec<OutboundMessage> {
+    let Some(cap) = state::get_config().outbox_max_in_flight else {
+        return state::OUTBOX.with(|outbox_ref| {
+            let mut outbox = outbox_ref.borrow_mut();
+            let ids: Vec<u64> = outbox.keys().take(limit).collect();
+            // Leases from before the cap was lifted
+            state::OUTBOX_LEASES.with(|l| ids.iter().for_each(|id| { l.borrow_mut().remove(id); }));
+            ids.into_iter().filter_map(|id| outbox.remove(&id)).collect()
+        });
+    };
+    let now = ic_cdk::api::time();
+    let limit = limit.min((cap as u64).saturating_sub(in_flight()) as usize);
+    // Keys only: the messages themselves are decoded just for the batch handed out
+    let ready: Vec<u64> = state::OUTBOX.with(|o| o.borrow().keys().filter(|&id| !is_leased(id, now)).take(limit).collect());
+    ready.into_iter().filter_map(|id| {
+        let mut message = state::OUTBOX.with(|o| o.borrow().get(&id))?;
+        message.leased_until = Some(now + LEASE_NANOS);
+        state::OUTBOX.with(|o| o.borrow_mut().insert(id, message.clone()));
+        state::OUTBOX_LEASES.with(|l| l.borrow_mut().insert(id, now + LEASE_NANOS));
+        Some(message)
+    }).collect()
+}
+
+// Removes delivered messages; returns how many were still in the outbox
+pub fn ack(ids: &[u64]) -> u32 {
+    state::OUTBOX_LEASES.with(|l| {
+        let mut leases = l.borrow_mut();
+        for id in ids {
+            leases.remove(id);
+        }
+    });
+    state::OUTBOX.with(|outbox_ref| {
+        let mut outbox = outbox_ref.borrow_mut();
+        ids.iter().filter(|id| outbox.remove(id).is_some()).count() as u32
+    })
+}
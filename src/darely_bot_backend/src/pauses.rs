@@ -0,0 +1,146 @@
+use crate::chat_scope;
+use crate::outbox;
+use crate::state;
+use crate::types::{ChatConfig, ChatPause, CompositeKey, PauseIndexKey};
+use candid::Principal;
+
+// --- Chat Pauses ---
+// Group owners can pause Darely in their chat with /pause_bot, for a number of hours or until
+// /resume_bot. A paused chat gets no proactive posts (theme announcements and anything else
+// queued for the chat), and with `disable_commands` gameplay commands are turned away too while
+// read-only ones (leaderboard, rank, stats) keep working. The pause lives in the chat config and
+// a timer lifts it once it expires, reading only the due entries of PAUSE_INDEX, which holds every
+// pause with an end time. Only controllers, or the OpenChat bot relaying a command
+// it declared owner-only, can pause or resume a chat.
+
+const HOUR_NANOS: u64 = 60 * 60 * 1_000_000_000;
+pub const MAX_PAUSE_HOURS: u32 = 30 * 24;
+
+fn update_chat_config(chat_key: &str, f: impl FnOnce(&mut ChatConfig)) {
+    let (before, after) = state::CHAT_CONFIGS.with(|configs_ref| {
+        let mut configs = configs_ref.borrow_mut();
+        let mut chat_config = configs.get(&chat_key.to_string()).unwrap_or_default();
+        let before = ends_at(&chat_config);
+        f(&mut chat_config);
+        let after = ends_at(&chat_config);
+        configs.insert(chat_key.to_string(), chat_config);
+        (before, after)
+    });
+    reindex(chat_key, before, after);
+}
+
+// The chat's pause, unless there is none or it has expired
+pub fn active(chat_key: &str) -> Option<ChatPause> {
+    let pause = state::get_chat_config(chat_key)?.pause?;
+    pause.until.is_none_or(|until| until > ic_cdk::api::time()).then_some(pause)
+}
+
+fn describe_until(until: Option<u64>, now: impl FnOnce() -> u64) -> String {
+    match until {
+        Some(until) => format!("for another {} hour(s)", until.saturating_sub(now()).div_ceil(HOUR_NANOS)),
+        None => "until a group owner sends /resume_bot".to_string(),
+    }
+}
+
+pub fn pause(chat_id: &str, duration_hours: Option<u32>, disable_commands: bool, by: Principal) -> Result<String, String> {
+    let chat_key = chat_scope::require_chat_key(chat_id)?;
+    if let Some(hours) = duration_hours {
+        if hours == 0 || hours > MAX_PAUSE_HOURS {
+            return Err(format!("The duration must be between 1 and {} hours.", MAX_PAUSE_HOURS));
+        }
+    }
+    let now = ic_cdk::api::time();
+    let until = duration_hours.map(|hours| now + hours as u64 * HOUR_NANOS);
+    update_chat_config(&chat_key, |config| {
+        config.pause = Some(ChatPause { paused_at: now, until, commands_disabled: disable_commands, paused_by: by });
+    });
+    Ok(format!(
+        "⏸️ Darely is paused here {}. No announcements will be posted{}.",
+        describe_until(until, || now),
+        if disable_commands { " and game commands are off" } else { "" }
+    ))
+}
+
+// Returns false when the chat wasn't paused
+pub fn resume(chat_id: &str) -> Result<bool, String> {
+    let chat_key = chat_scope::require_chat_key(chat_id)?;
+    let was_paused = active(&chat_key).is_some();
+    if state::get_chat_config(&chat_key).is_some_and(|config| config.pause.is_some()) {
+        update_chat_config(&chat_key, |config| config.pause = None);
+    }
+    Ok(was_paused)
+}
+
+// Proactive posts to a paused chat are dropped rather than queued
+pub fn is_silenced(chat_key: &str) -> bool {
+    active(chat_key).is_some()
+}
+
+// Turns gameplay commands away while the chat is paused with commands disabled
+pub fn check_command(command_name: &str, chat_key: &str) -> Result<(), String> {
+    match active(chat_key).filter(|pause| pause.commands_disabled) {
+        Some(pause) => Err(format!(
+            "Darely is paused in this chat {}, so /{} is off.",
+            describe_until(pause.until, ic_cdk::api::time), command_name
+        )),
+        None => Ok(()),
+    }
+}
+
+// Timer entry point: lifts expired pauses and tells the chat. Returns how many were lifted.
+pub fn resume_expired() -> usize {
+    let now = ic_cdk::api::time();
+    let due: Vec<(u64, String)> = state::PAUSE_INDEX.with(|i| {
+        i.borrow().iter().take_while(|(key, _)| key.0 <= now).map(|(key, _)| (key.0, key.1)).collect()
+    });
+    let mut resumed = 0;
+    for (until, chat_key) in due {
+        // Entries left behind by a chat config written elsewhere
+        if state::get_chat_config(&chat_key).as_ref().and_then(ends_at) != Some(until) {
+            state::PAUSE_INDEX.with(|i| i.borrow_mut().remove(&CompositeKey(until, chat_key)));
+            continue;
+        }
+        update_chat_config(&chat_key, |config| config.pause = None);
+        outbox::queue_chat(&chat_key, "▶️ Darely is back! Send /dare to pick up where you left off.".to_string());
+        resumed += 1;
+    }
+    resumed
+}
+
+// --- Pause Index ---
+
+// The index key's time for a chat paused for a set time
+fn ends_at(config: &ChatConfig) -> Option<u64> {
+    config.pause.as_ref().and_then(|pause| pause.until)
+}
+
+fn reindex(chat_key: &str, before: Option<u64>, after: Option<u64>) {
+    if before == after {
+        return;
+    }
+    state::PAUSE_INDEX.with(|i| {
+        let mut index = i.borrow_mut();
+        if let Some(until) = before {
+            index.remove(&CompositeKey(until, chat_key.to_string()));
+        }
+        if let Some(until) = after {
+            index.insert(CompositeKey(until, chat_key.to_string()), ());
+        }
+    });
+}
+
+// Indexes every chat config when the index is empty (run on init and post_upgrade)
+pub fn rebuild_index() {
+    if state::PAUSE_INDEX.with(|i| !i.borrow().is_empty()) {
+        return;
+    }
+    let entries: Vec<PauseIndexKey> = state::CHAT_CONFIGS.with(|c| {
+        c.borrow().iter().filter_map(|(chat_key, config)| ends_at(&config).map(|until| CompositeKey(until, chat_key))).collect()
+    });
+    state::PAUSE_INDEX.with(|i| {
+        let mut index = i.borrow_mut();
+        for key in entries {
+            index.insert(key, ());
+        }
+    });
+}
@@ -0,0 +1,91 @@
+use crate::blocklist;
+use crate::repository::{self, DareRepo};
+use crate::state;
+use crate::types::{Dare, DareSource, PinnedDare, ProposalSource, StorablePrincipal, UserProfile};
+use candid::Principal;
+
+// --- Pinned Dares ---
+// An admin can pin a dare as the next assignment for one user or for everyone (useful for
+// events). A user's pin is consumed by their next /get_dare; an "all" pin, stored under the
+// anonymous principal, stays until it is replaced or removed and is handed to each user once,
+// tracked by the profile's pin_seen_at. A user's own pin wins over an "all" pin. Pins of dares
+// the user has blocked are dropped without being served. A pin that /dare only proposes stays
+// in place until the player accepts it.
+
+fn pin_key(target: Option<Principal>) -> StorablePrincipal {
+    StorablePrincipal(target.unwrap_or_else(Principal::anonymous))
+}
+
+pub fn pin(dare_id: u64, target: Option<Principal>, admin: Principal) -> Result<(), String> {
+    let dare = repository::dares().get(dare_id)
+        .ok_or_else(|| format!("Dare #{} not found.", dare_id))?;
+    if dare.source == DareSource::Custom {
+        return Err("Custom dares belong to the member they were written for and can't be pinned.".to_string());
+    }
+    if let Some(user) = target {
+        if !state::is_registered(user) {
+            return Err("That user isn't registered.".to_string());
+        }
+    }
+    let pinned = PinnedDare { dare_id, pinned_at: ic_cdk::api::time(), pinned_by: admin };
+    state::PINNED_DARES.with(|p| p.borrow_mut().insert(pin_key(target), pinned));
+    Ok(())
+}
+
+pub fn unpin(target: Option<Principal>) -> Result<PinnedDare, String> {
+    state::PINNED_DARES.with(|p| p.borrow_mut().remove(&pin_key(target)))
+        .ok_or_else(|| "No dare is pinned for that target.".to_string())
+}
+
+// (target, pin); None = everyone
+pub fn list() -> Vec<(Option<Principal>, PinnedDare)> {
+    state::PINNED_DARES.with(|p| {
+        p.borrow().iter()
+            .map(|(key, pin)| ((key.0 != Principal::anonymous()).then_some(key.0), pin))
+            .collect()
+    })
+}
+
+// The pin that applies to the user, if any: its dare and whose pin it is. Nothing is used up
+// until `consume`, so a proposed pin (see proposals.rs) waits for /accept.
+pub fn find(user: Principal, profile: &UserProfile) -> Option<(Dare, ProposalSource)> {
+    let (pin, source) = match state::PINNED_DARES.with(|p| p.borrow().get(&StorablePrincipal(user))) {
+        Some(pin) => (pin, ProposalSource::OwnPin),
+        None => {
+            let global = state::PINNED_DARES.with(|p| p.borrow().get(&pin_key(None)))?;
+            if profile.pin_seen_at.is_some_and(|seen| seen >= global.pinned_at) {
+                return None;
+            }
+            let pinned_at = global.pinned_at;
+            (global, ProposalSource::AllPin { pinned_at })
+        }
+    };
+    let dare = repository::dares().get(pin.dare_id)?;
+    if blocklist::Blocklist::of(profile).blocks(&dare) {
+        // Dropped without being served
+        consume(user, dare.id, &source);
+        return None;
+    }
+    Some((dare, source))
+}
+
+// Marks a pin as served: the user's own pin is removed (unless it was replaced meanwhile), an
+// "all" pin is recorded as seen
+pub fn consume(user: Principal, dare_id: u64, source: &ProposalSource) {
+    match source {
+        ProposalSource::OwnPin => state::PINNED_DARES.with(|p| {
+            let mut pins = p.borrow_mut();
+            if pins.get(&StorablePrincipal(user)).is_some_and(|pin| pin.dare_id == dare_id) {
+                pins.remove(&StorablePrincipal(user));
+            }
+        }),
+        ProposalSource::AllPin { pinned_at } => {
+            // A missing profile just means there is nobody to record it for
+            let _ = state::update_profile(user, |profile| {
+                profile.pin_seen_at = Some(profile.pin_seen_at.unwrap_or(0).max(*pinned_at));
+                Ok(())
+            });
+        }
+        ProposalSource::Prefill => {}
+    }
+}
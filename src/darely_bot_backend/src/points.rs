@@ -0,0 +1,63 @@
+use crate::icrc3;
+use crate::ledger::{self, Account};
+use crate::types::ICRC3Value;
+use candid::Principal;
+use serde_bytes::ByteBuf;
+
+// --- Points ---
+// Darely's internal points currency. Balances move only through ledger postings (mints come
+// from the treasury, burns go to the burn account); every change is also recorded as an ICRC-3
+// block, so the balances can be audited by replaying the log.
+
+const MAX_MEMO_LEN: usize = 32;
+
+pub fn balance(user: Principal) -> u64 {
+    ledger::balance(Account::User(user)) as u64
+}
+
+fn with_memo(mut tx: Vec<(String, ICRC3Value)>, memo: Option<&str>) -> Result<Vec<(String, ICRC3Value)>, String> {
+    if let Some(memo) = memo {
+        if memo.len() > MAX_MEMO_LEN {
+            return Err(format!("Memo is too long (max {} bytes).", MAX_MEMO_LEN));
+        }
+        tx.push(("memo".to_string(), ICRC3Value::Blob(ByteBuf::from(memo.as_bytes().to_vec()))));
+    }
+    Ok(tx)
+}
+
+// Creates points. Returns the block index.
+pub fn mint(to: Principal, amount: u64, memo: Option<&str>) -> Result<u64, String> {
+    if amount == 0 {
+        return Err("Amount must be positive.".to_string());
+    }
+    let tx = with_memo(vec![("amt".to_string(), icrc3::nat(amount)), ("to".to_string(), icrc3::account(to))], memo)?;
+    ledger::post(Account::Treasury, Account::User(to), amount)?;
+    Ok(icrc3::append("1mint", tx))
+}
+
+// Destroys points. Returns the block index.
+pub fn burn(from: Principal, amount: u64, memo: Option<&str>) -> Result<u64, String> {
+    if amount == 0 {
+        return Err("Amount must be positive.".to_string());
+    }
+    let tx = with_memo(vec![("amt".to_string(), icrc3::nat(amount)), ("from".to_string(), icrc3::account(from))], memo)?;
+    ledger::post(Account::User(from), Account::Burn, amount)?;
+    Ok(icrc3::append("1burn", tx))
+}
+
+// Moves points between users. Returns the block index.
+pub fn transfer(from: Principal, to: Principal, amount: u64, memo: Option<&str>) -> Result<u64, String> {
+    if amount == 0 {
+        return Err("Amount must be positive.".to_string());
+    }
+    if from == to {
+        return Err("You can't transfer points to yourself.".to_string());
+    }
+    let tx = with_memo(vec![
+        ("amt".to_string(), icrc3::nat(amount)),
+        ("from".to_string(), icrc3::account(from)),
+        ("to".to_string(), icrc3::account(to)),
+    ], memo)?;
+    ledger::post(Account::User(from), Account::User(to), amount)?;
+    Ok(icrc3::append("1xfer", tx))
+}
@@ -0,0 +1,121 @@
+use crate::repository::{self, DareRepo};
+use crate::state;
+use crate::types::{DareLikes, DareSource, Like, TrendingDare};
+use candid::Principal;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+// --- Likes & Trending ---
+// Each user can like a dare once. Likes from the last week make a dare "trending" and
+// raise its selection weight (see selection::candidates). Custom dares are private to the member
+// they were written for, so they can't be liked and never trend.
+
+const WEEK_NANOS: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+const WEIGHT_PER_WEEKLY_LIKE: u32 = 10;
+pub const DEFAULT_TRENDING_SIZE: usize = 10;
+pub const MAX_TRENDING_SIZE: usize = 50;
+
+// Records a like. Returns the dare's total like count.
+pub fn like(user: Principal, dare_id: u64) -> Result<u64, String> {
+    let dare = repository::dares().get(dare_id).ok_or_else(|| format!("Dare #{} not found.", dare_id))?;
+    if dare.source == DareSource::Custom {
+        return Err("Custom dares are private, so they can't be liked.".to_string());
+    }
+    state::DARE_LIKES.with(|likes_ref| {
+        let mut likes = likes_ref.borrow_mut();
+        let mut dare_likes = likes.get(&dare_id).unwrap_or_default();
+        if dare_likes.likes.iter().any(|like| like.user == user) {
+            return Err(format!("You already liked dare #{}.", dare_id));
+        }
+        dare_likes.likes.push(Like { user, liked_at: ic_cdk::api::time() });
+        let total = dare_likes.likes.len() as u64;
+        likes.insert(dare_id, dare_likes);
+        Ok(total)
+    })
+}
+
+fn count_recent(dare_likes: &DareLikes, now: u64) -> u64 {
+    dare_likes.likes.iter().filter(|like| now.saturating_sub(like.liked_at) < WEEK_NANOS).count() as u64
+}
+
+// Likes from the last 7 days per dare (dares without recent likes are omitted)
+pub fn weekly_like_counts() -> BTreeMap<u64, u64> {
+    let now = ic_cdk::api::time();
+    state::DARE_LIKES.with(|likes_ref| {
+        likes_ref.borrow().iter()
+            .map(|(dare_id, dare_likes)| (dare_id, count_recent(&dare_likes, now)))
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    })
+}
+
+// Selection weight bonus for a dare with this many likes this week
+pub fn weight_bonus(weekly_likes: u64) -> u32 {
+    (weekly_likes.min(u32::MAX as u64) as u32).saturating_mul(WEIGHT_PER_WEEKLY_LIKE)
+}
+
+// Most-liked dares this week, highest first (ties: more total likes, then lower id)
+pub fn trending(limit: usize) -> Vec<TrendingDare> {
+    let likes: Vec<(u64, DareLikes)> = state::DARE_LIKES.with(|likes_ref| likes_ref.borrow().iter().collect());
+    rank(&repository::dares(), likes, ic_cdk::api::time(), limit)
+}
+
+// Likes recorded before custom dares were refused are skipped here
+fn rank(dares: &impl DareRepo, likes: Vec<(u64, DareLikes)>, now: u64, limit: usize) -> Vec<TrendingDare> {
+    let mut rows: Vec<TrendingDare> = likes.into_iter()
+        .filter_map(|(dare_id, dare_likes)| {
+            let likes_this_week = count_recent(&dare_likes, now);
+            if likes_this_week == 0 {
+                return None;
+            }
+            let dare = dares.get(dare_id).filter(|dare| dare.source != DareSource::Custom)?;
+            Some(TrendingDare {
+                dare_id,
+                text: dare.text,
+                difficulty: dare.difficulty,
+                likes_this_week,
+                total_likes: dare_likes.likes.len() as u64,
+            })
+        })
+        .collect();
+    rows.sort_by_key(|row| (Reverse(row.likes_this_week), Reverse(row.total_likes), row.dare_id));
+    rows.truncate(limit);
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::memory::MemoryDares;
+    use crate::types::{Dare, Difficulty};
+
+    const NOW: u64 = 10 * WEEK_NANOS;
+
+    fn dare(text: &str, source: DareSource) -> Dare {
+        Dare { id: 0, text: text.to_string(), difficulty: Difficulty::Easy, source, weight: 100, issued_by: None, hint: None, creator: None, created_at: None, min_level: None, min_streak: None }
+    }
+
+    fn liked(count: u8) -> DareLikes {
+        DareLikes { likes: (0..count).map(|i| Like { user: Principal::from_slice(&[i]), liked_at: NOW - 1 }).collect() }
+    }
+
+    #[test]
+    fn liked_custom_dares_never_trend() {
+        let dares = MemoryDares::default();
+        let public = dares.push(dare("Sing a song", DareSource::Curated)).unwrap();
+        let private = dares.push(dare("Tell Sam about the party", DareSource::Custom)).unwrap();
+        let rows = rank(&dares, vec![(public, liked(1)), (private, liked(5))], NOW, 10);
+        assert_eq!(rows.iter().map(|row| row.dare_id).collect::<Vec<_>>(), vec![public]);
+    }
+
+    #[test]
+    fn trending_counts_only_this_weeks_likes() {
+        let dares = MemoryDares::default();
+        let quiet = dares.push(dare("Hum", DareSource::Curated)).unwrap();
+        let busy = dares.push(dare("Dance", DareSource::Llm)).unwrap();
+        let old = DareLikes { likes: vec![Like { user: Principal::anonymous(), liked_at: NOW - WEEK_NANOS }] };
+        let rows = rank(&dares, vec![(quiet, old), (busy, liked(2))], NOW, 10);
+        assert_eq!(rows.len(), 1);
+        assert_eq!((rows[0].dare_id, rows[0].likes_this_week), (busy, 2));
+    }
+}
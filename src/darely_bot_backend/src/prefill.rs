@@ -0,0 +1,276 @@
+use crate::correlation;
+use crate::incidents;
+use crate::llm;
+use crate::llm_usage::LlmFeature;
+use crate::outbox;
+use crate::repository::{self, DareRepo};
+use crate::state;
+use crate::types::{Dare, DareSource, Difficulty, PrefillJob, PrefillSchedule};
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+
+// --- LLM Prefill ---
+// A batch job that generates LLM dares ahead of time, so /dare can serve a fresh one without a
+// live outcall. Each generated dare is stored as an LLM dare and kept in a stock; when /dare
+// rolls the LLM source it takes an unserved dare of the right difficulty from the stock first,
+// and only calls the LLM when none is left. A dare /dare only proposes leaves the stock when it
+// is accepted. Once served, a dare stays in the repository as a
+// stored fallback like any other LLM dare.
+//
+// /prefill_dares <n> queues n dares per difficulty. With a schedule in the config, the timer
+// also queues a top-up to `per_difficulty` unserved dares once a day, and batches only run
+// inside the schedule's off-peak window. The timer generates BATCH_SIZE dares per tick, one
+// outcall at a time; after MAX_FAILURES failures in a row the batch is dropped and the alert
+// recipients are told, as they are when a batch finishes. A running tick holds a lease for
+// RUN_LEASE_NANOS, so one that trapped mid-batch doesn't stop the batches for good.
+
+pub const MAX_PER_DIFFICULTY: u32 = 200;
+const BATCH_SIZE: usize = 3;
+const MAX_FAILURES: u32 = 5;
+const HOUR_NANOS: u64 = 60 * 60 * 1_000_000_000;
+const RUN_LEASE_NANOS: u64 = 15 * 60 * 1_000_000_000;
+const DIFFICULTIES: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard];
+
+thread_local! {
+    // When the tick whose outcalls are still running started; the next tick leaves the batch to it
+    static RUNNING_SINCE: Cell<Option<u64>> = const { Cell::new(None) };
+    // The stock's difficulties, loaded from the stored dares once per upgrade
+    static STOCK: RefCell<Option<BTreeMap<u64, Difficulty>>> = const { RefCell::new(None) };
+}
+
+pub fn validate_schedule(schedule: &PrefillSchedule) -> Result<(), String> {
+    if schedule.per_difficulty == 0 || schedule.per_difficulty > MAX_PER_DIFFICULTY {
+        return Err(format!("per_difficulty must be between 1 and {}.", MAX_PER_DIFFICULTY));
+    }
+    if schedule.start_hour_utc > 23 || schedule.end_hour_utc > 23 || schedule.start_hour_utc == schedule.end_hour_utc {
+        return Err("The window needs two different hours between 0 and 23.".to_string());
+    }
+    Ok(())
+}
+
+fn in_window(schedule: &PrefillSchedule, now: u64) -> bool {
+    let hour = (now / HOUR_NANOS % 24) as u8;
+    if schedule.start_hour_utc < schedule.end_hour_utc {
+        (schedule.start_hour_utc..schedule.end_hour_utc).contains(&hour)
+    } else {
+        hour >= schedule.start_hour_utc || hour < schedule.end_hour_utc
+    }
+}
+
+fn job() -> PrefillJob {
+    state::PREFILL_JOB.with(|j| j.borrow().get().clone())
+}
+
+fn save(job: PrefillJob) {
+    state::PREFILL_JOB.with(|j| j.borrow_mut().set(job)).expect("Failed to save prefill job");
+}
+
+// --- Stock ---
+
+fn with_stock<R>(f: impl FnOnce(&mut BTreeMap<u64, Difficulty>) -> R) -> R {
+    STOCK.with(|s| {
+        let mut stock = s.borrow_mut();
+        let stock = stock.get_or_insert_with(|| {
+            let ids: Vec<u64> = state::PREFILL_STOCK.with(|s| s.borrow().iter().map(|(id, _)| id).collect());
+            let dares = repository::dares();
+            ids.into_iter().filter_map(|id| dares.get(id)).map(|dare| (dare.id, dare.difficulty)).collect()
+        });
+        f(stock)
+    })
+}
+
+// Oldest first
+fn stocked(difficulty: &Difficulty) -> Vec<u64> {
+    with_stock(|stock| stock.iter().filter(|(_, d)| *d == difficulty).map(|(id, _)| *id).collect())
+}
+
+fn stock_count(difficulty: &Difficulty) -> u32 {
+    stocked(difficulty).len() as u32
+}
+
+// Called once a stocked dare is assigned
+pub fn unstock(id: u64) {
+    with_stock(|stock| stock.remove(&id));
+    state::PREFILL_STOCK.with(|s| s.borrow_mut().remove(&id));
+}
+
+// The oldest unserved dare of this difficulty that `accept` allows. It stays in stock until
+// `unstock`, so a proposed one (see proposals.rs) is only used up by /accept.
+pub fn find(difficulty: &Difficulty, accept: impl Fn(&Dare) -> bool) -> Option<Dare> {
+    let dares = repository::dares();
+    for id in stocked(difficulty) {
+        match dares.get(id) {
+            Some(dare) if &dare.difficulty == difficulty => {
+                if accept(&dare) {
+                    return Some(dare);
+                }
+            }
+            // Deleted, or given another difficulty since
+            _ => unstock(id),
+        }
+    }
+    None
+}
+
+// --- Batches ---
+
+pub fn queue(per_difficulty: u32) -> Result<String, String> {
+    if per_difficulty == 0 || per_difficulty > MAX_PER_DIFFICULTY {
+        return Err(format!("Queue between 1 and {} dares per difficulty.", MAX_PER_DIFFICULTY));
+    }
+    let mut job = job();
+    if !job.remaining.is_empty() {
+        return Err("A prefill batch is already running; /cancel_prefill it first.".to_string());
+    }
+    job.remaining = DIFFICULTIES.iter().map(|difficulty| (difficulty.clone(), per_difficulty)).collect();
+    job.started_at = ic_cdk::api::time();
+    job.generated = 0;
+    job.failed = 0;
+    job.correlation_id = Some(correlation::current());
+    save(job);
+    Ok(match state::get_config().prefill {
+        Some(schedule) => format!(
+            "Queued {} dares per difficulty; they're generated between {}:00 and {}:00 UTC.",
+            per_difficulty, schedule.start_hour_utc, schedule.end_hour_utc
+        ),
+        None => format!("Queued {} dares per difficulty; generating now.", per_difficulty),
+    })
+}
+
+pub fn cancel() -> Result<String, String> {
+    let mut job = job();
+    let left: u32 = job.remaining.drain(..).map(|(_, count)| count).sum();
+    if left == 0 {
+        return Err("No prefill batch is running.".to_string());
+    }
+    let generated = job.generated;
+    save(job);
+    Ok(format!("Prefill cancelled after {} dares; {} were left.", generated, left))
+}
+
+pub fn status() -> String {
+    let job = job();
+    let stock: Vec<String> = DIFFICULTIES.iter().map(|difficulty| format!("{:?} {}", difficulty, stock_count(difficulty))).collect();
+    let mut text = format!("Prefilled dares in stock: {}.", stock.join(", "));
+    if !job.remaining.is_empty() {
+        let left: Vec<String> = job.remaining.iter().map(|(difficulty, count)| format!("{:?} {}", difficulty, count)).collect();
+        text.push_str(&format!(" Batch running: {} generated, {} failed, left: {}.", job.generated, job.failed, left.join(", ")));
+    }
+    text
+}
+
+// Queues the daily top-up once the schedule's window opens
+fn schedule_top_up(job: &mut PrefillJob, schedule: &PrefillSchedule, now: u64) {
+    let day = now / (24 * HOUR_NANOS);
+    if !job.remaining.is_empty() || job.last_scheduled_day == Some(day) {
+        return;
+    }
+    job.last_scheduled_day = Some(day);
+    job.remaining = DIFFICULTIES.iter()
+        .map(|difficulty| (difficulty.clone(), schedule.per_difficulty.saturating_sub(stock_count(difficulty))))
+        .filter(|(_, count)| *count > 0)
+        .collect();
+    job.started_at = now;
+    job.generated = 0;
+    job.failed = 0;
+    job.correlation_id = Some(correlation::current());
+}
+
+// Called by the prefill timer
+pub fn tick() {
+    let now = ic_cdk::api::time();
+    if RUNNING_SINCE.get().is_some_and(|since| now.saturating_sub(since) < RUN_LEASE_NANOS) {
+        return;
+    }
+    let schedule = state::get_config().prefill;
+    if schedule.as_ref().is_some_and(|schedule| !in_window(schedule, now)) {
+        return;
+    }
+    let mut job = job();
+    if let Some(schedule) = &schedule {
+        schedule_top_up(&mut job, schedule, now);
+        save(job.clone());
+    }
+    if job.remaining.is_empty() {
+        return;
+    }
+    RUNNING_SINCE.set(Some(now));
+    correlation::adopt(job.correlation_id);
+    ic_cdk::spawn(correlation::traced(async move {
+        generate_batch().await;
+        // A tick that took over after the lease ran out owns the flag now
+        if RUNNING_SINCE.get() == Some(now) {
+            RUNNING_SINCE.set(None);
+        }
+    }));
+}
+
+async fn generate_batch() {
+    for _ in 0..BATCH_SIZE {
+        let Some(difficulty) = job().remaining.first().map(|(difficulty, _)| difficulty.clone()) else { return };
+        let result = match llm::fetch_llm_dare(difficulty.clone(), &[], LlmFeature::Prefill, None).await {
+            Ok(text) => crate::store_dare(text, difficulty.clone(), DareSource::Llm, None).map_err(incidents::observe),
+            Err(e) => Err(e),
+        };
+        // The batch may have been cancelled during the outcall
+        let mut job = job();
+        let Some(position) = job.remaining.iter().position(|(d, _)| d == &difficulty) else { return };
+        match result {
+            Ok(dare_id) => {
+                state::PREFILL_STOCK.with(|s| s.borrow_mut().insert(dare_id, ic_cdk::api::time()));
+                with_stock(|stock| stock.insert(dare_id, difficulty.clone()));
+                job.generated += 1;
+                job.failed = 0;
+                job.remaining[position].1 -= 1;
+                if job.remaining[position].1 == 0 {
+                    job.remaining.remove(position);
+                }
+                if job.remaining.is_empty() {
+                    outbox::alert_admins(&format!("✅ LLM prefill finished: {} dares generated.", job.generated));
+                }
+            }
+            Err(e) => {
+                correlation::log!("Prefill could not generate a {:?} dare: {}", difficulty, e);
+                job.failed += 1;
+                if job.failed >= MAX_FAILURES {
+                    job.remaining.clear();
+                    outbox::alert_admins(&format!(
+                        "⚠️ LLM prefill stopped after {} failures in a row ({} dares generated). Last error: {}", MAX_FAILURES, job.generated, e
+                    ));
+                }
+            }
+        }
+        let done = job.remaining.is_empty();
+        save(job);
+        if done {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(start_hour_utc: u8, end_hour_utc: u8) -> PrefillSchedule {
+        PrefillSchedule { per_difficulty: 10, start_hour_utc, end_hour_utc }
+    }
+
+    #[test]
+    fn window_may_wrap_past_midnight() {
+        let at = |hour: u64| hour * HOUR_NANOS + 24 * HOUR_NANOS * 100;
+        assert!(in_window(&schedule(2, 5), at(2)));
+        assert!(!in_window(&schedule(2, 5), at(5)));
+        assert!(in_window(&schedule(22, 5), at(23)));
+        assert!(in_window(&schedule(22, 5), at(0)));
+        assert!(!in_window(&schedule(22, 5), at(12)));
+    }
+
+    #[test]
+    fn schedule_needs_a_window_and_a_sane_size() {
+        assert!(validate_schedule(&schedule(22, 5)).is_ok());
+        assert!(validate_schedule(&schedule(3, 3)).is_err());
+        assert!(validate_schedule(&schedule(3, 24)).is_err());
+        assert!(validate_schedule(&PrefillSchedule { per_difficulty: 0, ..schedule(1, 2) }).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+use crate::state::{self, Memory};
+use crate::types::{
+    ApiKey, Appeal, BuddyInvite, BuddyLink, CachedGroupMetadata, ChangelogEntry, DareRatings, ChatUserKey, Duo, EscalationIndexKey, ExpiryIndexKey, PauseIndexKey, JournalEntry, FeatureMetrics, CompositeKey, GlobalEvent, Campaign, ChatConfig, CorruptRecord, CreatorStats, DareLikes, Enrollments, HistoryEntry, ICRC3Value, ImportJob, Incident, InviteCode, LlmUsage, LlmUsageKey, MarketplaceListing, ModeratorStats,
+    OutboundMessage, PendingDare, Quest, QuestDraft, PinnedDare, RewardTask, PreflightReport, ReadReplica, RegistrationRequest, RejectedDare, ReviewTask, Session, SetupWizard, StorablePrincipal, StoreReport, StreakIndexKey, Submission,
+    SubmissionDraft, Suggestion, Theme, Truth, UserProfile, VouchRequest, WaitlistEntry,
+};
+use candid::CandidType;
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::{BTreeMap as StableBTreeMap, Storable};
+use serde::de::DeserializeOwned;
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+// --- Upgrade Preflight ---
+// Before an upgrade, every stable map is reopened with raw values so each record can be decoded
+// with the current types without trapping on the first bad one. The report lists record counts,
+// undecodable records and the largest encoded record per store against its `Bound::Bounded`
+// limit. Stores with undecodable records or records close to their bound make the report unsafe
+// and arm an upgrade block: pre_upgrade then traps until a clean preflight or an explicit
+// override. Upgrades without a preflight are not affected.
+
+// Records above this share of their bound leave too little room for new fields
+const BOUND_WARNING_PERCENT: u64 = 80;
+
+thread_local! {
+    static UPGRADE_BLOCKED: RefCell<bool> = const { RefCell::new(false) };
+}
+
+// A stored value read as its raw encoding
+pub(crate) struct RawValue(pub Vec<u8>);
+
+impl Storable for RawValue {
+    fn to_bytes(&self) -> Cow<'_, [u8]> { Cow::Borrowed(&self.0) }
+    fn from_bytes(bytes: Cow<[u8]>) -> Self { RawValue(bytes.into_owned()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// A stored key read as its raw encoding, compared byte by byte
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct RawKey(pub Vec<u8>);
+
+impl Storable for RawKey {
+    fn to_bytes(&self) -> Cow<'_, [u8]> { Cow::Borrowed(&self.0) }
+    fn from_bytes(bytes: Cow<[u8]>) -> Self { RawKey(bytes.into_owned()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// A stable vector element read as its raw encoding; it keeps T's bound, which the vector checks
+pub(crate) struct RawItem<T>(pub Vec<u8>, pub std::marker::PhantomData<T>);
+
+impl<T: Storable> Storable for RawItem<T> {
+    fn to_bytes(&self) -> Cow<'_, [u8]> { Cow::Borrowed(&self.0) }
+    fn from_bytes(bytes: Cow<[u8]>) -> Self { RawItem(bytes.into_owned(), std::marker::PhantomData) }
+    const BOUND: Bound = T::BOUND;
+}
+
+// Fixed-size values can't grow, so only variable-size bounds are reported
+fn bound_of<V: Storable>() -> Option<u64> {
+    match V::BOUND {
+        Bound::Bounded { max_size, is_fixed_size: false } => Some(max_size as u64),
+        _ => None,
+    }
+}
+
+fn decode_candid<V: CandidType + DeserializeOwned>(bytes: &[u8]) -> Result<(), String> {
+    candid::decode_one::<V>(bytes).map(|_| ()).map_err(|e| e.to_string())
+}
+
+// Natively encoded u64 values (balances, nonce expiries)
+fn decode_u64(bytes: &[u8]) -> Result<(), String> {
+    if bytes.len() == 8 { Ok(()) } else { Err(format!("expected 8 bytes, found {}", bytes.len())) }
+}
+
+// Set-like maps store nothing per key
+fn decode_unit(bytes: &[u8]) -> Result<(), String> {
+    if bytes.is_empty() { Ok(()) } else { Err(format!("expected no bytes, found {}", bytes.len())) }
+}
+
+fn new_report(name: &str, bound: Option<u64>) -> StoreReport {
+    StoreReport { name: name.to_string(), records: 0, undecodable: 0, first_error: None, max_size: 0, bound }
+}
+
+fn note(report: &mut StoreReport, size: usize, decoded: Result<(), String>) {
+    report.records += 1;
+    report.max_size = report.max_size.max(size as u64);
+    if let Err(e) = decoded {
+        report.undecodable += 1;
+        report.first_error.get_or_insert(e);
+    }
+}
+
+// `initialize` touches the typed map first so its memory holds a map before it is reopened
+fn scan_map<K, V>(name: &str, memory_id: MemoryId, initialize: impl FnOnce() -> u64, decode: fn(&[u8]) -> Result<(), String>) -> (MemoryId, StoreReport)
+where
+    K: Storable + Ord + Clone,
+    V: Storable,
+{
+    initialize();
+    let memory = state::MEMORY_MANAGER.with(|m| m.borrow().get(memory_id));
+    let raw: StableBTreeMap<K, RawValue, Memory> = StableBTreeMap::load(memory);
+    let mut report = new_report(name, bound_of::<V>());
+    for (_, RawValue(bytes)) in raw.iter() {
+        note(&mut report, bytes.len(), decode(&bytes));
+    }
+    (memory_id, report)
+}
+
+// Cells and the dare vector decode when the canister starts, so only their sizes are measured
+fn scan_values<V: Storable>(name: &str, memory_id: MemoryId, values: impl Iterator<Item = V>) -> (MemoryId, StoreReport) {
+    let mut report = new_report(name, bound_of::<V>());
+    for value in values {
+        note(&mut report, value.to_bytes().len(), Ok(()));
+    }
+    (memory_id, report)
+}
+
+fn warnings_for(report: &StoreReport) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if report.undecodable > 0 {
+        warnings.push(format!(
+            "{}: {} of {} records don't decode with the current types ({}).",
+            report.name, report.undecodable, report.records, report.first_error.as_deref().unwrap_or("unknown error")
+        ));
+    }
+    if let Some(bound) = report.bound {
+        if report.max_size * 100 >= bound * BOUND_WARNING_PERCENT {
+            warnings.push(format!(
+                "{}: the largest record is {} of {} bytes; adding fields may push records over the bound.",
+                report.name, report.max_size, bound
+            ));
+        }
+    }
+    warnings
+}
+
+// Every stable store with the memory it lives in
+fn scan_all() -> Vec<(MemoryId, StoreReport)> {
+    use state::*;
+    vec![
+        scan_map::<StorablePrincipal, UserProfile>("user_profiles", USER_PROFILES_MEM_ID, || USER_PROFILES.with(|m| m.borrow().len()), decode_candid::<UserProfile>),
+        scan_values("dare_repository", DARES_MEM_ID, DARE_REPOSITORY.with(|v| v.borrow().iter().collect::<Vec<_>>()).into_iter()),
+        scan_values("config", CONFIG_MEM_ID, std::iter::once(get_config())),
+        scan_map::<String, ChatConfig>("chat_configs", CHAT_CONFIGS_MEM_ID, || CHAT_CONFIGS.with(|m| m.borrow().len()), decode_candid::<ChatConfig>),
+        scan_map::<u64, Submission>("submissions", SUBMISSIONS_MEM_ID, || SUBMISSIONS.with(|m| m.borrow().len()), decode_candid::<Submission>),
+        scan_map::<StorablePrincipal, SubmissionDraft>("submission_drafts", SUBMISSION_DRAFTS_MEM_ID, || SUBMISSION_DRAFTS.with(|m| m.borrow().len()), decode_candid::<SubmissionDraft>),
+        scan_map::<u64, HistoryEntry>("history", HISTORY_MEM_ID, || HISTORY.with(|m| m.borrow().len()), decode_candid::<HistoryEntry>),
+        scan_map::<StorablePrincipal, PendingDare>("pending_dares", PENDING_DARES_MEM_ID, || PENDING_DARES.with(|m| m.borrow().len()), decode_candid::<PendingDare>),
+        scan_map::<String, CachedGroupMetadata>("group_metadata", GROUP_METADATA_MEM_ID, || GROUP_METADATA.with(|m| m.borrow().len()), decode_candid::<CachedGroupMetadata>),
+        scan_map::<u64, Suggestion>("suggestions", SUGGESTIONS_MEM_ID, || SUGGESTIONS.with(|m| m.borrow().len()), decode_candid::<Suggestion>),
+        scan_map::<u64, Session>("sessions", SESSIONS_MEM_ID, || SESSIONS.with(|m| m.borrow().len()), decode_candid::<Session>),
+        scan_values("session_secret", SESSION_SECRET_MEM_ID, std::iter::once(SESSION_SECRET.with(|c| c.borrow().get().clone()))),
+        scan_values("next_session_id", NEXT_SESSION_ID_MEM_ID, std::iter::once(NEXT_SESSION_ID.with(|c| *c.borrow().get()))),
+        scan_map::<[u8; 32], u64>("used_nonces", USED_NONCES_MEM_ID, || USED_NONCES.with(|m| m.borrow().len()), decode_u64),
+        scan_map::<u64, ReviewTask>("review_queue", REVIEW_QUEUE_MEM_ID, || REVIEW_QUEUE.with(|m| m.borrow().len()), decode_candid::<ReviewTask>),
+        scan_map::<StorablePrincipal, ModeratorStats>("moderators", MODERATORS_MEM_ID, || MODERATORS.with(|m| m.borrow().len()), decode_candid::<ModeratorStats>),
+        scan_map::<u64, DareLikes>("dare_likes", DARE_LIKES_MEM_ID, || DARE_LIKES.with(|m| m.borrow().len()), decode_candid::<DareLikes>),
+        scan_map::<u64, Campaign>("campaigns", CAMPAIGNS_MEM_ID, || CAMPAIGNS.with(|m| m.borrow().len()), decode_candid::<Campaign>),
+        scan_map::<StorablePrincipal, Enrollments>("enrollments", ENROLLMENTS_MEM_ID, || ENROLLMENTS.with(|m| m.borrow().len()), decode_candid::<Enrollments>),
+        scan_map::<u64, OutboundMessage>("outbox", OUTBOX_MEM_ID, || OUTBOX.with(|m| m.borrow().len()), decode_candid::<OutboundMessage>),
+        scan_map::<u64, u64>("outbox_leases", OUTBOX_LEASES_MEM_ID, || OUTBOX_LEASES.with(|m| m.borrow().len()), decode_u64),
+        scan_map::<u64, ApiKey>("api_keys", API_KEYS_MEM_ID, || API_KEYS.with(|m| m.borrow().len()), decode_candid::<ApiKey>),
+        scan_map::<StorablePrincipal, u64>("point_balances", POINT_BALANCES_MEM_ID, || POINT_BALANCES.with(|m| m.borrow().len()), decode_u64),
+        scan_map::<u64, ICRC3Value>("blocks", BLOCKS_MEM_ID, || BLOCKS.with(|m| m.borrow().len()), decode_candid::<ICRC3Value>),
+        scan_map::<ChatUserKey, u64>("chat_members", CHAT_MEMBERS_MEM_ID, || CHAT_MEMBERS.with(|m| m.borrow().len()), decode_u64),
+        scan_map::<CompositeKey<String, StorablePrincipal>, StorablePrincipal>("account_links", ACCOUNT_LINKS_MEM_ID, || ACCOUNT_LINKS.with(|m| m.borrow().len()), decode_candid::<StorablePrincipal>),
+        scan_map::<u64, Truth>("truths", TRUTHS_MEM_ID, || TRUTHS.with(|m| m.borrow().len()), decode_candid::<Truth>),
+        scan_map::<u64, Theme>("themes", THEMES_MEM_ID, || THEMES.with(|m| m.borrow().len()), decode_candid::<Theme>),
+        scan_map::<StorablePrincipal, PinnedDare>("pinned_dares", PINNED_DARES_MEM_ID, || PINNED_DARES.with(|m| m.borrow().len()), decode_candid::<PinnedDare>),
+        scan_map::<CompositeKey<String, u64>, u64>("chat_gallery", CHAT_GALLERY_MEM_ID, || CHAT_GALLERY.with(|m| m.borrow().len()), decode_u64),
+        scan_map::<u64, RewardTask>("reward_tasks", REWARD_TASKS_MEM_ID, || REWARD_TASKS.with(|m| m.borrow().len()), decode_candid::<RewardTask>),
+        scan_map::<u64, Appeal>("appeals", APPEALS_MEM_ID, || APPEALS.with(|m| m.borrow().len()), decode_candid::<Appeal>),
+        scan_map::<u64, DareRatings>("dare_ratings", DARE_RATINGS_MEM_ID, || DARE_RATINGS.with(|m| m.borrow().len()), decode_candid::<DareRatings>),
+        scan_map::<CompositeKey<String, u64>, u64>("disabled_dares", DISABLED_DARES_MEM_ID, || DISABLED_DARES.with(|m| m.borrow().len()), decode_u64),
+        scan_map::<String, InviteCode>("invite_codes", INVITE_CODES_MEM_ID, || INVITE_CODES.with(|m| m.borrow().len()), decode_candid::<InviteCode>),
+        scan_map::<StorablePrincipal, RegistrationRequest>("registration_requests", REGISTRATION_REQUESTS_MEM_ID, || REGISTRATION_REQUESTS.with(|m| m.borrow().len()), decode_candid::<RegistrationRequest>),
+        scan_map::<u64, WaitlistEntry>("waitlist", WAITLIST_MEM_ID, || WAITLIST.with(|m| m.borrow().len()), decode_candid::<WaitlistEntry>),
+        scan_map::<StreakIndexKey, ()>("streak_index", STREAK_INDEX_MEM_ID, || STREAK_INDEX.with(|m| m.borrow().len()), decode_unit),
+        scan_map::<ExpiryIndexKey, ()>("expiry_index", EXPIRY_INDEX_MEM_ID, || EXPIRY_INDEX.with(|m| m.borrow().len()), decode_unit),
+        scan_map::<EscalationIndexKey, ()>("escalation_index", ESCALATION_INDEX_MEM_ID, || ESCALATION_INDEX.with(|m| m.borrow().len()), decode_unit),
+        scan_map::<PauseIndexKey, ()>("pause_index", PAUSE_INDEX_MEM_ID, || PAUSE_INDEX.with(|m| m.borrow().len()), decode_unit),
+        scan_map::<u32, u64>("streak_counts", STREAK_COUNTS_MEM_ID, || STREAK_COUNTS.with(|m| m.borrow().len()), decode_u64),
+        scan_map::<u64, u64>("demo_dares", DEMO_DARES_MEM_ID, || DEMO_DARES.with(|m| m.borrow().len()), decode_u64),
+        scan_map::<StorablePrincipal, u64>("banned_users", BANNED_USERS_MEM_ID, || BANNED_USERS.with(|m| m.borrow().len()), decode_u64),
+        scan_map::<StorablePrincipal, u64>("demo_users", DEMO_USERS_MEM_ID, || DEMO_USERS.with(|m| m.borrow().len()), decode_u64),
+        scan_map::<String, FeatureMetrics>("feature_metrics", FEATURE_METRICS_MEM_ID, || FEATURE_METRICS.with(|m| m.borrow().len()), decode_candid::<FeatureMetrics>),
+        scan_map::<u64, ChangelogEntry>("changelog", CHANGELOG_MEM_ID, || CHANGELOG.with(|m| m.borrow().len()), decode_candid::<ChangelogEntry>),
+        scan_map::<StorablePrincipal, CreatorStats>("creator_stats", CREATOR_STATS_MEM_ID, || CREATOR_STATS.with(|m| m.borrow().len()), decode_candid::<CreatorStats>),
+        scan_map::<StorablePrincipal, VouchRequest>("vouch_requests", VOUCH_REQUESTS_MEM_ID, || VOUCH_REQUESTS.with(|m| m.borrow().len()), decode_candid::<VouchRequest>),
+        scan_map::<StorablePrincipal, BuddyLink>("buddies", BUDDIES_MEM_ID, || BUDDIES.with(|m| m.borrow().len()), decode_candid::<BuddyLink>),
+        scan_map::<StorablePrincipal, BuddyInvite>("buddy_invites", BUDDY_INVITES_MEM_ID, || BUDDY_INVITES.with(|m| m.borrow().len()), decode_candid::<BuddyInvite>),
+        scan_map::<u64, GlobalEvent>("global_events", GLOBAL_EVENTS_MEM_ID, || GLOBAL_EVENTS.with(|m| m.borrow().len()), decode_candid::<GlobalEvent>),
+        scan_map::<CompositeKey<u64, StorablePrincipal>, u64>("event_participants", EVENT_PARTICIPANTS_MEM_ID, || EVENT_PARTICIPANTS.with(|m| m.borrow().len()), decode_u64),
+        scan_map::<u64, MarketplaceListing>("marketplace", MARKETPLACE_MEM_ID, || MARKETPLACE.with(|m| m.borrow().len()), decode_candid::<MarketplaceListing>),
+        scan_map::<CompositeKey<String, u64>, u64>("chat_imports", CHAT_IMPORTS_MEM_ID, || CHAT_IMPORTS.with(|m| m.borrow().len()), decode_u64),
+        scan_map::<u64, Quest>("quests", QUESTS_MEM_ID, || QUESTS.with(|m| m.borrow().len()), decode_candid::<Quest>),
+        scan_map::<StorablePrincipal, QuestDraft>("quest_drafts", QUEST_DRAFTS_MEM_ID, || QUEST_DRAFTS.with(|m| m.borrow().len()), decode_candid::<QuestDraft>),
+        scan_map::<StorablePrincipal, SetupWizard>("setup_wizards", SETUP_WIZARDS_MEM_ID, || SETUP_WIZARDS.with(|m| m.borrow().len()), decode_candid::<SetupWizard>),
+        scan_map::<u64, ImportJob>("import_jobs", IMPORT_JOBS_MEM_ID, || IMPORT_JOBS.with(|m| m.borrow().len()), decode_candid::<ImportJob>),
+        scan_map::<LlmUsageKey, LlmUsage>("llm_usage", LLM_USAGE_MEM_ID, || LLM_USAGE.with(|m| m.borrow().len()), decode_candid::<LlmUsage>),
+        scan_map::<u64, RejectedDare>("rejected_dares", REJECTED_DARES_MEM_ID, || REJECTED_DARES.with(|m| m.borrow().len()), decode_candid::<RejectedDare>),
+        scan_values("next_rejected_dare_id", NEXT_REJECTED_DARE_ID_MEM_ID, std::iter::once(NEXT_REJECTED_DARE_ID.with(|c| *c.borrow().get()))),
+        scan_map::<u64, Duo>("duos", DUOS_MEM_ID, || DUOS.with(|m| m.borrow().len()), decode_candid::<Duo>),
+        scan_map::<StorablePrincipal, u64>("duo_members", DUO_MEMBERS_MEM_ID, || DUO_MEMBERS.with(|m| m.borrow().len()), decode_u64),
+        scan_map::<StorablePrincipal, BuddyInvite>("duo_invites", DUO_INVITES_MEM_ID, || DUO_INVITES.with(|m| m.borrow().len()), decode_candid::<BuddyInvite>),
+        scan_map::<u64, JournalEntry>("replication_journal", REPLICATION_JOURNAL_MEM_ID, || REPLICATION_JOURNAL.with(|m| m.borrow().len()), decode_candid::<JournalEntry>),
+        scan_map::<StorablePrincipal, ReadReplica>("read_replicas", READ_REPLICAS_MEM_ID, || READ_REPLICAS.with(|m| m.borrow().len()), decode_candid::<ReadReplica>),
+        scan_map::<String, Incident>("incidents", INCIDENTS_MEM_ID, || INCIDENTS.with(|m| m.borrow().len()), decode_candid::<Incident>),
+        scan_map::<u64, CorruptRecord>("corrupt_records", CORRUPT_RECORDS_MEM_ID, || CORRUPT_RECORDS.with(|m| m.borrow().len()), decode_candid::<CorruptRecord>),
+        scan_values("next_corrupt_record_id", NEXT_CORRUPT_RECORD_ID_MEM_ID, std::iter::once(NEXT_CORRUPT_RECORD_ID.with(|c| *c.borrow().get()))),
+        scan_map::<u64, u64>("prefill_stock", PREFILL_STOCK_MEM_ID, || PREFILL_STOCK.with(|m| m.borrow().len()), decode_u64),
+        scan_values("ledger_accounts", LEDGER_ACCOUNTS_MEM_ID, std::iter::once(LEDGER_ACCOUNTS.with(|c| c.borrow().get().clone()))),
+        scan_values("ledger_checkpoint", LEDGER_CHECKPOINT_MEM_ID, std::iter::once(LEDGER_CHECKPOINT.with(|c| c.borrow().get().clone()))),
+        scan_map::<StorablePrincipal, u64>("replayed_balances", REPLAYED_BALANCES_MEM_ID, || REPLAYED_BALANCES.with(|m| m.borrow().len()), decode_u64),
+        scan_values("profile_sizes", PROFILE_SIZES_MEM_ID, std::iter::once(PROFILE_SIZES.with(|c| c.borrow().get().clone()))),
+        scan_values("leaderboard", LEADERBOARD_MEM_ID, std::iter::once(LEADERBOARD.with(|c| c.borrow().get().clone()))),
+        scan_values("campaign_scan", CAMPAIGN_SCAN_MEM_ID, std::iter::once(CAMPAIGN_SCAN.with(|c| c.borrow().get().clone()))),
+        scan_values("leaderboard_build", LEADERBOARD_BUILD_MEM_ID, std::iter::once(LEADERBOARD_BUILD.with(|c| c.borrow().get().clone()))),
+        scan_values("llm_spend_summary", LLM_SPEND_SUMMARY_MEM_ID, std::iter::once(LLM_SPEND_SUMMARY.with(|c| *c.borrow().get()))),
+        scan_values("replication", REPLICATION_MEM_ID, std::iter::once(REPLICATION.with(|c| c.borrow().get().clone()))),
+        scan_values("entropy", ENTROPY_MEM_ID, std::iter::once(ENTROPY.with(|c| c.borrow().get().clone()))),
+        scan_values("prefill_job", PREFILL_JOB_MEM_ID, std::iter::once(PREFILL_JOB.with(|c| c.borrow().get().clone()))),
+        scan_values("environment", ENVIRONMENT_MEM_ID, std::iter::once(ENVIRONMENT.with(|c| *c.borrow().get()))),
+    ]
+}
+
+pub fn run() -> PreflightReport {
+    let stores: Vec<StoreReport> = scan_all().into_iter().map(|(_, report)| report).collect();
+    let warnings: Vec<String> = stores.iter().flat_map(warnings_for).collect();
+    let safe = warnings.is_empty();
+    UPGRADE_BLOCKED.with(|blocked| *blocked.borrow_mut() = !safe);
+    PreflightReport { safe, upgrade_blocked: !safe, stores, warnings }
+}
+
+pub fn clear_block() {
+    UPGRADE_BLOCKED.with(|blocked| *blocked.borrow_mut() = false);
+}
+
+// Called from pre_upgrade
+pub fn ensure_upgrade_allowed() {
+    if UPGRADE_BLOCKED.with(|blocked| *blocked.borrow()) {
+        ic_cdk::trap("Upgrade blocked: the last preflight_upgrade found problems. Fix them and rerun it, or call override_upgrade_block.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn every_memory_id_is_scanned() {
+        let declared: BTreeSet<MemoryId> = include_str!("state.rs").lines()
+            .filter_map(|line| line.split("MemoryId::new(").nth(1))
+            .filter_map(|rest| rest.split(')').next()?.parse::<u8>().ok())
+            .map(MemoryId::new)
+            .collect();
+        let scanned: Vec<MemoryId> = scan_all().into_iter().map(|(memory_id, _)| memory_id).collect();
+        assert_eq!(scanned.iter().copied().collect::<BTreeSet<_>>(), declared);
+        assert_eq!(scanned.len(), declared.len(), "a memory is scanned twice");
+    }
+}
@@ -0,0 +1,192 @@
+use crate::chat_scope;
+use crate::config;
+use crate::gallery;
+use crate::llm;
+use crate::rewards;
+use crate::state;
+use crate::types::{ChatConfig, DigestSchedule, RedemptionPolicy, SourceMix};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+// --- Config Presets ---
+// `/config export` packs a chat's settings into a code, `dcfg1_<base64url(JSON)>`, that
+// `/config import` applies in another chat, so a community network can copy one setup to all of
+// its groups. Only the shareable settings travel: the dare mix, announcements, redemption policy,
+// registration, verification mode, digest schedule and language. A pause and the digest's send
+// time belong to the chat and are left alone. Import also takes the bare JSON, so a preset can be
+// written by hand; every field is checked as if it had been set on its own, and settings missing
+// from the preset are reset to the defaults. Group owners can import too, but only the digest and
+// language: the other settings have admin-only setters, so an owner's import leaves them as they
+// are in the chat.
+
+const CODE_PREFIX: &str = "dcfg1_";
+const MAX_CODE_LEN: usize = 2_000;
+
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct Preset {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source_mix: Option<SourceMix>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    announcements: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    redemption_policy: Option<RedemptionPolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    registration_open: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    streak_on_approval: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    digest: Option<DigestSchedule>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
+}
+
+impl Preset {
+    fn of(config: ChatConfig) -> Self {
+        Preset {
+            source_mix: config.source_mix,
+            announcements: config.announcements,
+            redemption_policy: config.redemption_policy,
+            registration_open: config.registration_open,
+            streak_on_approval: config.streak_on_approval,
+            digest: config.digest,
+            language: config.language,
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if let Some(mix) = &self.source_mix {
+            config::validate_mix(mix).map_err(|e| format!("source_mix: {}", e))?;
+        }
+        if let Some(policy) = &self.redemption_policy {
+            rewards::validate_policy(policy).map_err(|e| format!("redemption_policy: {}", e))?;
+        }
+        if let Some(digest) = &self.digest {
+            gallery::validate_digest(digest).map_err(|e| format!("digest: {}", e))?;
+        }
+        if let Some(language) = &self.language {
+            llm::validate_language(language).map_err(|e| format!("language: {}", e))?;
+        }
+        Ok(())
+    }
+
+    // The admin-only settings the preset would change in `config`
+    fn admin_changes(&self, config: &ChatConfig) -> Vec<&'static str> {
+        [
+            ("dare mix", self.source_mix != config.source_mix),
+            ("announcements", self.announcements != config.announcements),
+            ("redemption policy", self.redemption_policy != config.redemption_policy),
+            ("registration", self.registration_open != config.registration_open),
+            ("verification", self.streak_on_approval != config.streak_on_approval),
+        ].into_iter().filter(|&(_, changed)| changed).map(|(name, _)| name).collect()
+    }
+
+    // Writes the preset over the shareable settings, keeping the chat's own state. Without `admin`
+    // only the owner-level settings are written.
+    fn apply_to(self, config: &mut ChatConfig, admin: bool) {
+        if config.digest != self.digest {
+            config.digest_sent_at = None;
+        }
+        if admin {
+            config.source_mix = self.source_mix;
+            config.announcements = self.announcements;
+            config.redemption_policy = self.redemption_policy;
+            config.registration_open = self.registration_open;
+            config.streak_on_approval = self.streak_on_approval;
+        }
+        config.digest = self.digest;
+        config.language = self.language;
+    }
+}
+
+fn encode(preset: &Preset) -> String {
+    let json = serde_json::to_vec(preset).expect("Preset serializes to JSON");
+    format!("{}{}", CODE_PREFIX, BASE64_URL.encode(json))
+}
+
+// Accepts an exported code or the preset's JSON
+fn decode(code: &str) -> Result<Preset, String> {
+    let code = code.trim();
+    if code.len() > MAX_CODE_LEN {
+        return Err("That preset is too long.".to_string());
+    }
+    let json = if code.starts_with('{') {
+        code.as_bytes().to_vec()
+    } else {
+        let encoded = code.strip_prefix(CODE_PREFIX)
+            .ok_or_else(|| format!("A preset code starts with \"{}\"; paste the whole code from /config export.", CODE_PREFIX))?;
+        BASE64_URL.decode(encoded).map_err(|_| "That preset code is damaged; copy it again.".to_string())?
+    };
+    let preset: Preset = serde_json::from_slice(&json).map_err(|e| format!("Invalid preset: {}", e))?;
+    preset.validate()?;
+    Ok(preset)
+}
+
+// /config export
+pub fn export(chat_id: &str) -> Result<String, String> {
+    let chat_key = chat_scope::require_chat_key(chat_id)?;
+    let preset = Preset::of(state::get_chat_config(&chat_key).unwrap_or_default());
+    Ok(format!("📦 This chat's settings, ready for /config import in another chat:\n{}", encode(&preset)))
+}
+
+// /config import <code>; `admin` is false for a group owner relayed by the bot
+pub fn import(chat_id: &str, code: &str, admin: bool) -> Result<String, String> {
+    let chat_key = chat_scope::require_chat_key(chat_id)?;
+    let preset = decode(code)?;
+    let skipped = state::CHAT_CONFIGS.with(|configs_ref| {
+        let mut configs = configs_ref.borrow_mut();
+        let mut config = configs.get(&chat_key).unwrap_or_default();
+        let skipped = if admin { Vec::new() } else { preset.admin_changes(&config) };
+        preset.apply_to(&mut config, admin);
+        configs.insert(chat_key.clone(), config);
+        skipped
+    });
+    if skipped.is_empty() {
+        return Ok(format!("✅ Settings imported into chat {}.", chat_key));
+    }
+    Ok(format!(
+        "✅ Digest and language imported into chat {}. Only an admin can change the {}, so they were left as they are.",
+        chat_key, skipped.join(", ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_round_trip_and_are_checked() {
+        let preset = Preset {
+            source_mix: Some(SourceMix { curated: 1, llm: 2 }),
+            digest: Some(DigestSchedule { weekday: 1, hour: 18 }),
+            language: Some("Spanish".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(decode(&encode(&preset)), Ok(preset));
+        assert_eq!(decode(r#"{"registration_open":false}"#), Ok(Preset { registration_open: Some(false), ..Default::default() }));
+        assert!(decode(r#"{"digest":{"weekday":7,"hour":0}}"#).unwrap_err().starts_with("digest:"));
+        assert!(decode(r#"{"pause":null}"#).is_err());
+        assert!(decode("dcfg1_%%%").is_err());
+        assert!(decode("hello").is_err());
+        assert!(decode(r#"{"source_mix":{"curated":0,"llm":5000}}"#).unwrap_err().starts_with("source_mix:"));
+    }
+
+    #[test]
+    fn owner_imports_leave_admin_settings_alone() {
+        let preset = || Preset {
+            source_mix: Some(SourceMix { curated: 0, llm: 1 }),
+            redemption_policy: Some(RedemptionPolicy::Keep),
+            language: Some("Spanish".to_string()),
+            ..Default::default()
+        };
+        let mut config = ChatConfig { registration_open: Some(false), ..Default::default() };
+        assert_eq!(preset().admin_changes(&config), vec!["dare mix", "redemption policy", "registration"]);
+        preset().apply_to(&mut config, false);
+        assert_eq!((&config.source_mix, config.redemption_policy, config.registration_open), (&None, None, Some(false)));
+        assert_eq!(config.language.as_deref(), Some("Spanish"));
+        preset().apply_to(&mut config, true);
+        assert_eq!(config.redemption_policy, Some(RedemptionPolicy::Keep));
+        assert_eq!(config.registration_open, None);
+    }
+}
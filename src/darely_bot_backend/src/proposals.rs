@@ -0,0 +1,151 @@
+use crate::hardcore;
+use crate::pins;
+use crate::prefill;
+use crate::state;
+use crate::submissions;
+use crate::types::{DareProposal, ProposalSource, UserProfile};
+use candid::Principal;
+
+// --- Dare Proposals ---
+// With `daily_declines` set in the config, /dare proposes its pick instead of assigning it: the
+// player has PROPOSAL_MINUTES to /accept it, which makes it the active dare, or /decline it and
+// ask again. Declines are limited per rolling day, and a proposal left unanswered counts as one,
+// so waiting it out is no free skip. Once a player has no declines left, /dare assigns at once.
+// Only /dare's picks are proposed; escalations and custom dares are assigned as before. A pinned
+// or prefilled pick is only used up when accepted, so a declined pin comes back on the next /dare.
+
+const PROPOSAL_MINUTES: u64 = 10;
+const PROPOSAL_NANOS: u64 = PROPOSAL_MINUTES * 60 * 1_000_000_000;
+const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+pub const MAX_DAILY_DECLINES: u32 = 20;
+
+pub fn validate_declines(declines: u32) -> Result<(), String> {
+    if declines == 0 || declines > MAX_DAILY_DECLINES {
+        return Err(format!("Daily declines must be between 1 and {}.", MAX_DAILY_DECLINES));
+    }
+    Ok(())
+}
+
+fn is_open(proposal: &DareProposal, now: u64) -> bool {
+    now.saturating_sub(proposal.proposed_at) < PROPOSAL_NANOS
+}
+
+// Declines within the last day, counting a lapsed proposal from when it ran out
+fn declines(profile: &UserProfile, now: u64) -> Vec<u64> {
+    let lapsed = profile.proposed_dare.as_ref()
+        .filter(|proposal| !is_open(proposal, now))
+        .map(|proposal| proposal.proposed_at + PROPOSAL_NANOS);
+    profile.declined_at.clone().unwrap_or_default().into_iter()
+        .chain(lapsed)
+        .filter(|&at| now.saturating_sub(at) < DAY_NANOS)
+        .collect()
+}
+
+fn declines_left(profile: &UserProfile, now: u64) -> Option<u32> {
+    let allowed = state::get_config().daily_declines?;
+    Some(allowed.saturating_sub(declines(profile, now).len() as u32))
+}
+
+// Whether /dare should propose rather than assign
+pub fn proposes(profile: &UserProfile, now: u64) -> bool {
+    declines_left(profile, now).is_some_and(|left| left > 0)
+}
+
+// The reply to /dare while a proposal is still waiting for an answer
+pub fn waiting(profile: &UserProfile, now: u64) -> Option<String> {
+    let proposal = profile.proposed_dare.as_ref().filter(|proposal| is_open(proposal, now))?;
+    Some(format!("You already have a dare on offer: {}\n/accept it or /decline it first.", proposal.text))
+}
+
+// Offers a dare, settling any lapsed proposal as a decline. Returns the text to show.
+pub fn propose(user: Principal, proposal: DareProposal) -> String {
+    let now = proposal.proposed_at;
+    let text = proposal.text.clone();
+    let left = state::update_profile(user, |profile| {
+        profile.declined_at = Some(declines(profile, now));
+        profile.proposed_dare = Some(proposal.clone());
+        Ok(declines_left(profile, now).unwrap_or(0))
+    });
+    match left {
+        Ok(left) => format!(
+            "{}\n\n/accept within {} minutes to take it on, or /decline for another ({} declines left today).",
+            text, PROPOSAL_MINUTES, left
+        ),
+        // A missing profile just means there is nobody to propose to
+        Err(_) => text,
+    }
+}
+
+fn take_open(profile: &mut UserProfile, now: u64) -> Result<DareProposal, String> {
+    match profile.proposed_dare.take() {
+        Some(proposal) if is_open(&proposal, now) => Ok(proposal),
+        Some(_) => Err("Your proposed dare has expired. Use /dare for a new one.".to_string()),
+        None => Err("You don't have a dare on offer. Use /dare to get one.".to_string()),
+    }
+}
+
+// Uses up the pin or stocked dare a pick came from, once it is assigned
+pub fn use_up(user: Principal, dare_id: Option<u64>, source: Option<&ProposalSource>) {
+    let (Some(dare_id), Some(source)) = (dare_id, source) else { return };
+    match source {
+        ProposalSource::Prefill => prefill::unstock(dare_id),
+        pin => pins::consume(user, dare_id, pin),
+    }
+}
+
+// /accept: the proposal becomes the active dare
+pub fn accept(user: Principal) -> Result<String, String> {
+    let now = ic_cdk::api::time();
+    let (reply, dare_id, source) = state::update_profile(user, |profile| {
+        hardcore::check_penalty(profile)?;
+        submissions::check_claim(profile, now)?;
+        let proposal = take_open(profile, now)?;
+        profile.current_dare_id = proposal.dare_id;
+        profile.current_dare_chat = proposal.chat_id;
+        profile.current_dare_text = proposal.rendered;
+        let reply = format!("✅ Dare accepted: {}\nSubmit your proof with /submit_dare when you're done.", proposal.text);
+        Ok((reply, proposal.dare_id, proposal.source))
+    })?;
+    use_up(user, dare_id, source.as_ref());
+    Ok(reply)
+}
+
+// /decline: drops the proposal, using up one of the day's declines
+pub fn decline(user: Principal) -> Result<String, String> {
+    let now = ic_cdk::api::time();
+    state::update_profile(user, |profile| {
+        take_open(profile, now)?;
+        let mut declined = declines(profile, now);
+        declined.push(now);
+        profile.declined_at = Some(declined);
+        let left = declines_left(profile, now).unwrap_or(0);
+        Ok(if left > 0 {
+            format!("Dare declined. Use /dare for another ({} declines left today).", left)
+        } else {
+            "Dare declined. That was your last decline today, so your next /dare is yours to keep.".to_string()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 100 * DAY_NANOS;
+
+    fn proposal(proposed_at: u64) -> DareProposal {
+        DareProposal { dare_id: Some(1), chat_id: None, rendered: None, text: "Sing.".to_string(), proposed_at, source: None }
+    }
+
+    #[test]
+    fn lapsed_proposals_and_recent_declines_count() {
+        let profile = UserProfile { declined_at: Some(vec![NOW - DAY_NANOS, NOW - 60]), ..Default::default() };
+        assert_eq!(declines(&profile, NOW), vec![NOW - 60]);
+        let open = UserProfile { proposed_dare: Some(proposal(NOW - 60)), ..profile.clone() };
+        assert_eq!(declines(&open, NOW).len(), 1);
+        assert!(waiting(&open, NOW).is_some());
+        let lapsed = UserProfile { proposed_dare: Some(proposal(NOW - PROPOSAL_NANOS)), ..profile };
+        assert_eq!(declines(&lapsed, NOW), vec![NOW - 60, NOW]);
+        assert!(waiting(&lapsed, NOW).is_none());
+    }
+}
@@ -0,0 +1,79 @@
+use crate::state;
+use crate::submissions;
+use crate::types::PurgeRetention;
+use ic_stable_structures::{BTreeMap as StableBTreeMap, Storable};
+
+// --- Stale State Purge ---
+// A maintenance timer deletes records that can no longer be used: idle submission drafts, expired
+// REST sessions, admin dares left unconfirmed, and replay-protection nonces past their expiry.
+// Each category can keep expired records for a configured number of hours first (e.g. to debug
+// a user's session); by default they go as soon as they expire. Counts are written to the log.
+
+const HOUR_NANOS: u64 = 60 * 60 * 1_000_000_000;
+pub const MAX_RETENTION_HOURS: u32 = 90 * 24;
+
+#[derive(Default)]
+pub struct PurgeCounts {
+    pub drafts: usize,
+    pub sessions: usize,
+    pub pending_dares: usize,
+    pub nonces: usize,
+}
+
+impl PurgeCounts {
+    pub fn total(&self) -> usize {
+        self.drafts + self.sessions + self.pending_dares + self.nonces
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} drafts, {} sessions, {} pending dares, {} nonces",
+            self.drafts, self.sessions, self.pending_dares, self.nonces
+        )
+    }
+}
+
+pub fn validate(retention: &PurgeRetention) -> Result<(), String> {
+    let hours = [retention.drafts_hours, retention.sessions_hours, retention.pending_dares_hours, retention.nonces_hours];
+    if hours.iter().flatten().any(|&h| h > MAX_RETENTION_HOURS) {
+        return Err(format!("Retention can be at most {} hours.", MAX_RETENTION_HOURS));
+    }
+    Ok(())
+}
+
+fn hours(retention: Option<u32>) -> u64 {
+    retention.unwrap_or(0) as u64 * HOUR_NANOS
+}
+
+// Removes every entry whose expiry (as computed by `expires_at`) is older than `cutoff`
+fn purge_map<K: Storable + Ord + Clone, V: Storable>(
+    map: &mut StableBTreeMap<K, V, state::Memory>,
+    cutoff: u64,
+    expires_at: impl Fn(&V) -> u64,
+) -> usize {
+    let expired: Vec<K> = map.iter().filter(|(_, value)| expires_at(value) <= cutoff).map(|(key, _)| key).collect();
+    for key in &expired {
+        map.remove(key);
+    }
+    expired.len()
+}
+
+// Timer entry point
+pub fn run() -> PurgeCounts {
+    let retention = state::get_config().purge_retention.unwrap_or_default();
+    let now = ic_cdk::api::time();
+    PurgeCounts {
+        drafts: submissions::purge_expired_drafts(hours(retention.drafts_hours)),
+        sessions: state::SESSIONS.with(|s| {
+            purge_map(&mut s.borrow_mut(), now.saturating_sub(hours(retention.sessions_hours)), |session| session.expires_at)
+        }),
+        pending_dares: state::PENDING_DARES.with(|p| {
+            purge_map(&mut p.borrow_mut(), now.saturating_sub(hours(retention.pending_dares_hours)), |pending| {
+                pending.created_at.saturating_add(crate::PENDING_DARE_TIMEOUT_NANOS)
+            })
+        }),
+        nonces: state::USED_NONCES.with(|n| {
+            purge_map(&mut n.borrow_mut(), now.saturating_sub(hours(retention.nonces_hours)), |&expires_at| expires_at)
+        }),
+    }
+}
@@ -0,0 +1,614 @@
+use crate::exclusions;
+use crate::incidents;
+use crate::membership;
+use crate::outbox;
+use crate::preflight::{RawItem, RawKey, RawValue};
+use crate::ranking;
+use crate::replication;
+use crate::state::{self, Memory};
+use crate::types::{
+    try_decode_record, ApiKey, Appeal, BuddyInvite, BuddyLink, CachedGroupMetadata, Campaign, CampaignScan, ChangelogEntry, ChatConfig, CompositeKey, Config, CorruptRecord, CreatorStats, Dare, DareLikes, DareRatings, DareSource, Difficulty, Duo, Enrollments, EntropyPool, Environment,
+    FeatureMetrics, GlobalEvent, HistoryEntry, ImportJob, Incident, InviteCode, JournalEntry, LeaderboardBuild, LeaderboardSnapshot, LedgerCheckpoint, LlmUsage, LlmUsageKey, MarketplaceListing, ModeratorStats, OutboundMessage,
+    PendingDare, PinnedDare, PrefillJob, Quest, QuestDraft, ReadReplica, RegistrationRequest, RejectedDare, ReplicatedMap, ReplicationState, ReviewTask, RewardTask, Session, SetupWizard, SizeHistogram, StorablePrincipal, Submission,
+    SubmissionDraft, Suggestion, SystemBalances, Theme, Truth, UserProfile, VouchRequest, WaitlistEntry,
+};
+use candid::{CandidType, Principal};
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::Memory as _;
+use ic_stable_structures::{BTreeMap as StableBTreeMap, StableCell, StableVec, Storable};
+use serde::de::DeserializeOwned;
+use serde_bytes::ByteBuf;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::ops::Bound;
+use std::time::Duration;
+
+// --- Corrupt Record Quarantine ---
+// A stored record that doesn't decode traps whoever reads it, and a trap in the middle of an
+// iteration makes the whole map unusable. Records are checked in three places:
+// - the sweep reopens every candid map with raw keys and values, and the dare vector with raw
+//   items, SWEEP_BATCH records per timer call with a cursor in between, so large maps stay within
+//   the instruction limit. It starts after every upgrade, where type changes would surface, and
+//   on demand;
+// - the candid cells are checked first thing in post_upgrade, since a cell decodes in full the
+//   first time anything touches it;
+// - scans of the profiles, dares and reward tasks (see repository.rs) decode record by record and
+//   skip one that doesn't decode, so a bad record doesn't break them before the sweep gets to it.
+// An unreadable record (or one whose key doesn't decode) goes into CORRUPT_RECORDS with its raw
+// key and value, and counts as an incident (see incidents.rs). A map entry is removed; a dare keeps
+// its id, so its slot gets a retired placeholder; a cell is reset to its default.
+//
+// A raw key finds its entry only when the map orders keys like their encoded bytes, which holds
+// for u64s, strings and principals. Maps with composite keys are resumed by position instead,
+// and their entries removed through the typed key.
+//
+// Admins inspect quarantined records with list_corrupt_records. Once the types are fixed (or with
+// corrected bytes) repair_corrupt_record writes a record back if it decodes; otherwise
+// delete_corrupt_record drops it. Quarantined keys are journaled for replication like any
+// other removal or write of a replicated map.
+
+pub const MAX_PAGE_SIZE: u64 = 100;
+const SWEEP_BATCH: usize = 500;
+const CELL_MAGIC: &[u8; 3] = b"SCL";
+
+// A record that doesn't decode: raw key, raw value, error
+pub type Unreadable = (Vec<u8>, Vec<u8>, String);
+
+// Keys read without trusting them. BYTE_ORDER: the map orders these keys like their encoded
+// bytes, so a raw key can find its entry and resume a sweep.
+pub(crate) trait SweptKey: Storable + Ord + Clone {
+    const BYTE_ORDER: bool;
+    fn check(bytes: &[u8]) -> Result<(), String>;
+}
+
+impl SweptKey for u64 {
+    const BYTE_ORDER: bool = true;
+    fn check(bytes: &[u8]) -> Result<(), String> {
+        if bytes.len() != 8 {
+            return Err(format!("A u64 key has 8 bytes, not {}.", bytes.len()));
+        }
+        Ok(())
+    }
+}
+
+impl SweptKey for String {
+    const BYTE_ORDER: bool = true;
+    fn check(bytes: &[u8]) -> Result<(), String> {
+        std::str::from_utf8(bytes).map(|_| ()).map_err(|e| format!("The key isn't UTF-8: {}", e))
+    }
+}
+
+// Candid puts the length before the bytes, as Principal's ordering does
+impl SweptKey for StorablePrincipal {
+    const BYTE_ORDER: bool = true;
+    fn check(bytes: &[u8]) -> Result<(), String> {
+        try_decode_record::<Principal>(bytes).map(|_| ())
+    }
+}
+
+// The first part's length comes first, so the bytes order shorter first parts first
+impl<A: SweptKey, B: SweptKey> SweptKey for CompositeKey<A, B> {
+    const BYTE_ORDER: bool = false;
+    fn check(bytes: &[u8]) -> Result<(), String> {
+        let truncated = || "The composite key is truncated.".to_string();
+        let (len, rest) = bytes.split_at_checked(4).ok_or_else(truncated)?;
+        let len = u32::from_be_bytes(len.try_into().unwrap()) as usize;
+        let (first, second) = rest.split_at_checked(len).ok_or_else(truncated)?;
+        A::check(first)?;
+        B::check(second)
+    }
+}
+
+// Where a store's sweep resumes
+#[derive(Clone, Default)]
+enum Cursor {
+    #[default]
+    Start,
+    // The raw key of the last record swept
+    After(Vec<u8>),
+    // How many records were swept and kept (for maps resumed by position, and the dare vector)
+    Skip(u64),
+}
+
+// A store the sweep covers
+struct Store {
+    name: &'static str,
+    replicated: Option<ReplicatedMap>,
+    // Checks up to SWEEP_BATCH records from the cursor; returns the unreadable ones and where to
+    // resume, or None when the store is done
+    sweep: fn(Cursor) -> (Vec<Unreadable>, Option<Cursor>),
+    // Takes an unreadable record out of the store, given its raw key; false if it can't be found
+    remove: fn(&[u8]) -> bool,
+    // Writes a raw key and value back once the value decodes
+    restore: fn(&[u8], &[u8]) -> Result<(), String>,
+}
+
+fn memory(id: MemoryId) -> Memory {
+    state::MEMORY_MANAGER.with(|m| m.borrow().get(id))
+}
+
+fn quarantine(store: &str, key: Vec<u8>, value: Vec<u8>, error: String) {
+    // The incident map can't take a record about itself
+    if store != "incidents" {
+        incidents::record(&format!("Unreadable record in {}: {}", store, error));
+    }
+    state::CORRUPT_RECORDS.with(|r| {
+        let mut records = r.borrow_mut();
+        // Records from before the counter existed are skipped over
+        let id = state::NEXT_CORRUPT_RECORD_ID.with(|c| *c.borrow().get())
+            .max(records.last_key_value().map_or(0, |(id, _)| id + 1));
+        state::NEXT_CORRUPT_RECORD_ID.with(|c| c.borrow_mut().set(id + 1)).expect("Failed to save next corrupt record id");
+        records.insert(id, CorruptRecord {
+            id,
+            store: store.to_string(),
+            key: ByteBuf::from(key),
+            value: ByteBuf::from(value),
+            error,
+            quarantined_at: ic_cdk::api::time(),
+        });
+    });
+}
+
+// --- Maps ---
+
+fn raw_map(memory_id: MemoryId) -> StableBTreeMap<RawKey, RawValue, Memory> {
+    StableBTreeMap::load(memory(memory_id))
+}
+
+fn unreadable<K: SweptKey, V: CandidType + DeserializeOwned>(key: &[u8], value: &[u8]) -> Option<String> {
+    K::check(key).and_then(|_| try_decode_record::<V>(value).map(|_| ())).err()
+}
+
+fn sweep_map<K: SweptKey, V: CandidType + DeserializeOwned>(memory_id: MemoryId, cursor: Cursor) -> (Vec<Unreadable>, Option<Cursor>) {
+    let raw = raw_map(memory_id);
+    let skipped = match cursor {
+        Cursor::Skip(skipped) => skipped,
+        _ => 0,
+    };
+    let batch: Vec<(Vec<u8>, Vec<u8>)> = match cursor {
+        Cursor::After(key) => raw.range((Bound::Excluded(RawKey(key)), Bound::Unbounded)).take(SWEEP_BATCH).map(|(RawKey(k), RawValue(v))| (k, v)).collect(),
+        _ => raw.iter().skip(skipped as usize).take(SWEEP_BATCH).map(|(RawKey(k), RawValue(v))| (k, v)).collect(),
+    };
+    let full = batch.len() == SWEEP_BATCH;
+    let last_key = batch.last().map(|(key, _)| key.clone());
+    let scanned = batch.len() as u64;
+    let found: Vec<Unreadable> = batch.into_iter()
+        .filter_map(|(key, value)| unreadable::<K, V>(&key, &value).map(|error| (key, value, error)))
+        .collect();
+    let next = match last_key {
+        Some(key) if full && K::BYTE_ORDER => Some(Cursor::After(key)),
+        // Removed records no longer take up positions
+        Some(_) if full => Some(Cursor::Skip(skipped + scanned - found.len() as u64)),
+        _ => None,
+    };
+    (found, next)
+}
+
+fn remove_map<K: SweptKey>(memory_id: MemoryId, key: &[u8], reload: impl FnOnce()) -> bool {
+    let removed = if K::BYTE_ORDER || K::check(key).is_err() {
+        raw_map(memory_id).remove(&RawKey(key.to_vec())).is_some()
+    } else {
+        let mut typed: StableBTreeMap<K, RawValue, Memory> = StableBTreeMap::load(memory(memory_id));
+        typed.remove(&K::from_bytes(Cow::Borrowed(key))).is_some()
+    };
+    // The typed map caches its root and length, so after raw removals it's reloaded
+    reload();
+    removed
+}
+
+fn restore_map<K: SweptKey, V: CandidType + DeserializeOwned>(memory_id: MemoryId, reload: impl FnOnce(), key: &[u8], value: &[u8]) -> Result<(), String> {
+    K::check(key).map_err(|e| format!("The key doesn't decode ({}), so the record can only be deleted.", e))?;
+    try_decode_record::<V>(value).map_err(|e| format!("The value still doesn't decode: {}", e))?;
+    let mut typed: StableBTreeMap<K, RawValue, Memory> = StableBTreeMap::load(memory(memory_id));
+    typed.insert(K::from_bytes(Cow::Borrowed(key)), RawValue(value.to_vec()));
+    reload();
+    Ok(())
+}
+
+// Visits a map's records in key order after `after`, skipping those that don't decode, which are
+// returned for `isolate`. Callers hold the typed map's borrow meanwhile, as a typed scan would.
+pub fn scan_map<K: SweptKey, V: CandidType + DeserializeOwned>(memory_id: MemoryId, after: Option<&K>, visit: &mut dyn FnMut(K, V) -> bool) -> Vec<Unreadable> {
+    let raw = raw_map(memory_id);
+    let lower = match after {
+        Some(key) if K::BYTE_ORDER => Bound::Excluded(RawKey(key.to_bytes().into_owned())),
+        _ => Bound::Unbounded,
+    };
+    let mut found = Vec::new();
+    for (RawKey(key), RawValue(value)) in raw.range((lower, Bound::Unbounded)) {
+        if let Some(error) = unreadable::<K, V>(&key, &value) {
+            found.push((key, value, error));
+            continue;
+        }
+        let typed_key = K::from_bytes(Cow::Borrowed(&key));
+        if !K::BYTE_ORDER && after.is_some_and(|after| typed_key <= *after) {
+            continue;
+        }
+        if !visit(typed_key, try_decode_record(&value).expect("checked above")) {
+            break;
+        }
+    }
+    found
+}
+
+macro_rules! reload {
+    ($map:ident, $mem:ident) => {
+        || state::$map.with(|m| *m.borrow_mut() = StableBTreeMap::load(memory(state::$mem)))
+    };
+}
+
+macro_rules! store {
+    ($name:literal, $map:ident, $mem:ident, $key:ty, $value:ty, $replicated:expr) => {
+        Store {
+            name: $name,
+            replicated: $replicated,
+            sweep: |cursor| {
+                // Initializes the typed map, so its memory holds a map before it's reopened
+                state::$map.with(|m| m.borrow().len());
+                sweep_map::<$key, $value>(state::$mem, cursor)
+            },
+            remove: |key| remove_map::<$key>(state::$mem, key, reload!($map, $mem)),
+            restore: |key, value| restore_map::<$key, $value>(state::$mem, reload!($map, $mem), key, value),
+        }
+    };
+}
+
+// --- Dare Vector ---
+// Dare ids are positions in the vector, so a record's key is its id (as a u64 key)
+
+fn raw_dares() -> StableVec<RawItem<Dare>, Memory> {
+    // Initializes the typed vector first, so its memory holds one before it's reopened
+    state::DARE_REPOSITORY.with(|r| r.borrow().len());
+    StableVec::init(memory(state::DARES_MEM_ID)).expect("The dare repository is a stable vector")
+}
+
+fn sweep_dares(cursor: Cursor) -> (Vec<Unreadable>, Option<Cursor>) {
+    let start = match cursor {
+        Cursor::Skip(start) => start,
+        _ => 0,
+    };
+    let dares = raw_dares();
+    let end = (start + SWEEP_BATCH as u64).min(dares.len());
+    let found = (start..end)
+        .filter_map(|id| {
+            let RawItem(value, _) = dares.get(id)?;
+            try_decode_record::<Dare>(&value).err().map(|error| (id.to_bytes().into_owned(), value, error))
+        })
+        .collect();
+    (found, (end < dares.len()).then_some(Cursor::Skip(end)))
+}
+
+// Visits the dares in id order, skipping those that don't decode, which are returned for
+// `isolate`. Callers hold the typed vector's borrow meanwhile.
+pub fn scan_dares(visit: &mut dyn FnMut(Dare) -> bool) -> Vec<Unreadable> {
+    let mut found = Vec::new();
+    for (id, RawItem(value, _)) in raw_dares().iter().enumerate() {
+        match try_decode_record::<Dare>(&value) {
+            Ok(dare) => {
+                if !visit(dare) {
+                    break;
+                }
+            }
+            Err(error) => found.push(((id as u64).to_bytes().into_owned(), value, error)),
+        }
+    }
+    found
+}
+
+fn dare_id(key: &[u8]) -> Option<u64> {
+    let id = u64::from_be_bytes(key.try_into().ok()?);
+    (id < state::DARE_REPOSITORY.with(|r| r.borrow().len())).then_some(id)
+}
+
+// The slot keeps a dare that is never picked: no weight, disabled in every chat
+fn retire_dare(key: &[u8]) -> bool {
+    let Some(id) = dare_id(key) else {
+        return false;
+    };
+    let placeholder = Dare {
+        id, text: "(quarantined)".to_string(), difficulty: Difficulty::Easy, source: DareSource::Curated, weight: 0,
+        issued_by: None, hint: None, creator: None, created_at: None, min_level: None, min_streak: None,
+    };
+    state::DARE_REPOSITORY.with(|r| r.borrow().set(id, &placeholder));
+    exclusions::disable(id, None).is_ok()
+}
+
+fn restore_dare(key: &[u8], value: &[u8]) -> Result<(), String> {
+    let id = dare_id(key).ok_or_else(|| "The key isn't a dare id.".to_string())?;
+    let mut dare = try_decode_record::<Dare>(value).map_err(|e| format!("The value still doesn't decode: {}", e))?;
+    dare.id = id;
+    state::DARE_REPOSITORY.with(|r| r.borrow().set(id, &dare));
+    exclusions::enable(id, None)?;
+    Ok(())
+}
+
+// --- Cells ---
+// A cell's record has an empty key
+
+fn check_cell<T: CandidType + DeserializeOwned>(memory_id: MemoryId) -> Vec<Unreadable> {
+    let memory = memory(memory_id);
+    let mut magic = [0u8; 3];
+    if memory.size() > 0 {
+        memory.read(0, &mut magic);
+    }
+    // Only an existing cell is reopened; initializing a raw one would write an empty value
+    if &magic != CELL_MAGIC {
+        return Vec::new();
+    }
+    let Ok(cell) = StableCell::init(memory, RawValue(Vec::new())) else {
+        return Vec::new();
+    };
+    let value = cell.get().0.clone();
+    try_decode_record::<T>(&value).err().map(|error| (Vec::new(), value, error)).into_iter().collect()
+}
+
+macro_rules! cell {
+    ($name:literal, $cell:ident, $mem:ident, $value:ty) => {
+        Store {
+            name: $name,
+            replicated: None,
+            sweep: |_| (check_cell::<$value>(state::$mem), None),
+            remove: |_| StableCell::new(memory(state::$mem), <$value>::default()).is_ok(),
+            restore: |_, value| {
+                let value = try_decode_record::<$value>(value).map_err(|e| format!("The value still doesn't decode: {}", e))?;
+                state::$cell.with(|c| c.borrow_mut().set(value)).map_err(|e| format!("Failed to store the value: {:?}", e))?;
+                Ok(())
+            },
+        }
+    };
+}
+
+fn cells() -> Vec<Store> {
+    vec![
+        cell!("config", CONFIG, CONFIG_MEM_ID, Config),
+        cell!("ledger_accounts", LEDGER_ACCOUNTS, LEDGER_ACCOUNTS_MEM_ID, SystemBalances),
+        cell!("ledger_checkpoint", LEDGER_CHECKPOINT, LEDGER_CHECKPOINT_MEM_ID, LedgerCheckpoint),
+        cell!("profile_sizes", PROFILE_SIZES, PROFILE_SIZES_MEM_ID, SizeHistogram),
+        cell!("leaderboard", LEADERBOARD, LEADERBOARD_MEM_ID, LeaderboardSnapshot),
+        cell!("leaderboard_build", LEADERBOARD_BUILD, LEADERBOARD_BUILD_MEM_ID, LeaderboardBuild),
+        cell!("campaign_scan", CAMPAIGN_SCAN, CAMPAIGN_SCAN_MEM_ID, CampaignScan),
+        cell!("environment", ENVIRONMENT, ENVIRONMENT_MEM_ID, Environment),
+        cell!("prefill_job", PREFILL_JOB, PREFILL_JOB_MEM_ID, PrefillJob),
+        cell!("replication", REPLICATION, REPLICATION_MEM_ID, ReplicationState),
+        cell!("entropy", ENTROPY, ENTROPY_MEM_ID, EntropyPool),
+    ]
+}
+
+fn stores() -> Vec<Store> {
+    let mut stores = vec![
+        Store { name: "dares", replicated: Some(ReplicatedMap::Dares), sweep: sweep_dares, remove: retire_dare, restore: restore_dare },
+        store!("user_profiles", USER_PROFILES, USER_PROFILES_MEM_ID, StorablePrincipal, UserProfile, Some(ReplicatedMap::Profiles)),
+        store!("chat_configs", CHAT_CONFIGS, CHAT_CONFIGS_MEM_ID, String, ChatConfig, None),
+        store!("submissions", SUBMISSIONS, SUBMISSIONS_MEM_ID, u64, Submission, Some(ReplicatedMap::Submissions)),
+        store!("submission_drafts", SUBMISSION_DRAFTS, SUBMISSION_DRAFTS_MEM_ID, StorablePrincipal, SubmissionDraft, None),
+        store!("history", HISTORY, HISTORY_MEM_ID, u64, HistoryEntry, Some(ReplicatedMap::History)),
+        store!("pending_dares", PENDING_DARES, PENDING_DARES_MEM_ID, StorablePrincipal, PendingDare, None),
+        store!("group_metadata", GROUP_METADATA, GROUP_METADATA_MEM_ID, String, CachedGroupMetadata, None),
+        store!("suggestions", SUGGESTIONS, SUGGESTIONS_MEM_ID, u64, Suggestion, None),
+        store!("sessions", SESSIONS, SESSIONS_MEM_ID, u64, Session, None),
+        store!("review_queue", REVIEW_QUEUE, REVIEW_QUEUE_MEM_ID, u64, ReviewTask, None),
+        store!("moderators", MODERATORS, MODERATORS_MEM_ID, StorablePrincipal, ModeratorStats, None),
+        store!("dare_likes", DARE_LIKES, DARE_LIKES_MEM_ID, u64, DareLikes, None),
+        store!("campaigns", CAMPAIGNS, CAMPAIGNS_MEM_ID, u64, Campaign, None),
+        store!("enrollments", ENROLLMENTS, ENROLLMENTS_MEM_ID, StorablePrincipal, Enrollments, None),
+        store!("outbox", OUTBOX, OUTBOX_MEM_ID, u64, OutboundMessage, None),
+        store!("api_keys", API_KEYS, API_KEYS_MEM_ID, u64, ApiKey, None),
+        store!("truths", TRUTHS, TRUTHS_MEM_ID, u64, Truth, None),
+        store!("themes", THEMES, THEMES_MEM_ID, u64, Theme, None),
+        store!("pinned_dares", PINNED_DARES, PINNED_DARES_MEM_ID, StorablePrincipal, PinnedDare, None),
+        store!("reward_tasks", REWARD_TASKS, REWARD_TASKS_MEM_ID, u64, RewardTask, None),
+        store!("appeals", APPEALS, APPEALS_MEM_ID, u64, Appeal, None),
+        store!("dare_ratings", DARE_RATINGS, DARE_RATINGS_MEM_ID, u64, DareRatings, None),
+        store!("invite_codes", INVITE_CODES, INVITE_CODES_MEM_ID, String, InviteCode, None),
+        store!("registration_requests", REGISTRATION_REQUESTS, REGISTRATION_REQUESTS_MEM_ID, StorablePrincipal, RegistrationRequest, None),
+        store!("waitlist", WAITLIST, WAITLIST_MEM_ID, u64, WaitlistEntry, None),
+        store!("feature_metrics", FEATURE_METRICS, FEATURE_METRICS_MEM_ID, String, FeatureMetrics, None),
+        store!("changelog", CHANGELOG, CHANGELOG_MEM_ID, u64, ChangelogEntry, None),
+        store!("creator_stats", CREATOR_STATS, CREATOR_STATS_MEM_ID, StorablePrincipal, CreatorStats, None),
+        store!("vouch_requests", VOUCH_REQUESTS, VOUCH_REQUESTS_MEM_ID, StorablePrincipal, VouchRequest, None),
+        store!("buddies", BUDDIES, BUDDIES_MEM_ID, StorablePrincipal, BuddyLink, None),
+        store!("buddy_invites", BUDDY_INVITES, BUDDY_INVITES_MEM_ID, StorablePrincipal, BuddyInvite, None),
+        store!("global_events", GLOBAL_EVENTS, GLOBAL_EVENTS_MEM_ID, u64, GlobalEvent, None),
+        store!("marketplace", MARKETPLACE, MARKETPLACE_MEM_ID, u64, MarketplaceListing, None),
+        store!("quests", QUESTS, QUESTS_MEM_ID, u64, Quest, None),
+        store!("quest_drafts", QUEST_DRAFTS, QUEST_DRAFTS_MEM_ID, StorablePrincipal, QuestDraft, None),
+        store!("setup_wizards", SETUP_WIZARDS, SETUP_WIZARDS_MEM_ID, StorablePrincipal, SetupWizard, None),
+        store!("import_jobs", IMPORT_JOBS, IMPORT_JOBS_MEM_ID, u64, ImportJob, None),
+        store!("llm_usage", LLM_USAGE, LLM_USAGE_MEM_ID, LlmUsageKey, LlmUsage, None),
+        store!("rejected_dares", REJECTED_DARES, REJECTED_DARES_MEM_ID, u64, RejectedDare, None),
+        store!("duos", DUOS, DUOS_MEM_ID, u64, Duo, None),
+        store!("duo_invites", DUO_INVITES, DUO_INVITES_MEM_ID, StorablePrincipal, BuddyInvite, None),
+        store!("replication_journal", REPLICATION_JOURNAL, REPLICATION_JOURNAL_MEM_ID, u64, JournalEntry, None),
+        store!("read_replicas", READ_REPLICAS, READ_REPLICAS_MEM_ID, StorablePrincipal, ReadReplica, None),
+        store!("incidents", INCIDENTS, INCIDENTS_MEM_ID, String, Incident, None),
+    ];
+    stores.extend(cells());
+    stores
+}
+
+// Derived in-memory indexes follow the profiles they were built from
+fn profiles_changed() {
+    membership::rebuild();
+    ranking::rebuild_index();
+}
+
+// Moves one unreadable record of `store` into CORRUPT_RECORDS
+fn isolate_in(store: &Store, (key, value, mut error): Unreadable) {
+    if !(store.remove)(&key) {
+        error.push_str(" (the record couldn't be taken out, so reading it may still fail)");
+    }
+    if let Some(map) = store.replicated {
+        replication::note(map, &RawValue(key.clone()));
+    }
+    quarantine(store.name, key, value, error);
+}
+
+// Quarantines the unreadable records a scan skipped. The profile indexes are rebuilt by a timer,
+// as the scan may belong to a rebuild.
+pub fn isolate(name: &str, found: Vec<Unreadable>) {
+    if found.is_empty() {
+        return;
+    }
+    let Some(store) = stores().into_iter().find(|store| store.name == name) else {
+        return;
+    };
+    let count = found.len();
+    for record in found {
+        isolate_in(&store, record);
+    }
+    if store.name == "user_profiles" {
+        ic_cdk_timers::set_timer(Duration::ZERO, profiles_changed);
+    }
+    outbox::alert_admins(&format!("⚠️ Quarantined {} unreadable records in {}. Inspect them with list_corrupt_records.", count, name));
+}
+
+// A sweep in progress: the store it's in, where it resumes, and what it has quarantined so far.
+// It lives on the heap, so an upgrade mid-sweep just starts a new one.
+#[derive(Default)]
+struct SweepJob {
+    store: usize,
+    cursor: Cursor,
+    found: Vec<(&'static str, u64)>,
+}
+
+thread_local! {
+    static SWEEP: RefCell<Option<SweepJob>> = const { RefCell::new(None) };
+}
+
+// Starts a sweep unless one is running; returns whether it started
+pub fn start_sweep() -> bool {
+    let started = SWEEP.with(|s| {
+        let mut sweep = s.borrow_mut();
+        if sweep.is_some() {
+            return false;
+        }
+        *sweep = Some(SweepJob::default());
+        true
+    });
+    if started {
+        ic_cdk_timers::set_timer(Duration::ZERO, sweep_batch);
+    }
+    started
+}
+
+// Timer: sweeps the next batch and schedules the one after, or reports once every store is done
+fn sweep_batch() {
+    let Some(mut job) = SWEEP.with(|s| s.borrow_mut().take()) else {
+        return;
+    };
+    let stores = stores();
+    let Some(store) = stores.get(job.store) else {
+        report(&job.found);
+        return;
+    };
+    let (found, next) = (store.sweep)(job.cursor.clone());
+    let count = found.len() as u64;
+    for record in found {
+        isolate_in(store, record);
+    }
+    if count > 0 {
+        if store.name == "user_profiles" {
+            profiles_changed();
+        }
+        match job.found.last_mut() {
+            Some((name, total)) if *name == store.name => *total += count,
+            _ => job.found.push((store.name, count)),
+        }
+    }
+    match next {
+        Some(cursor) => job.cursor = cursor,
+        None => {
+            job.store += 1;
+            job.cursor = Cursor::Start;
+        }
+    }
+    SWEEP.with(|s| *s.borrow_mut() = Some(job));
+    ic_cdk_timers::set_timer(Duration::ZERO, sweep_batch);
+}
+
+fn report(found: &[(&str, u64)]) {
+    if found.is_empty() {
+        ic_cdk::println!("Quarantine sweep done: every stored record decodes.");
+        return;
+    }
+    let found: Vec<String> = found.iter().map(|(name, count)| format!("{} in {}", count, name)).collect();
+    let summary = format!("Quarantined unreadable records: {}. Inspect them with list_corrupt_records.", found.join(", "));
+    ic_cdk::println!("{}", summary);
+    outbox::alert_admins(&format!("⚠️ {}", summary));
+}
+
+// First thing after an upgrade: a cell decodes in full when it's first read, so one that no
+// longer decodes is reset before anything reads it
+pub fn check_cells() {
+    let mut found = Vec::new();
+    for store in cells() {
+        let (records, _) = (store.sweep)(Cursor::Start);
+        if !records.is_empty() {
+            found.push((store.name, records.len() as u64));
+        }
+        for record in records {
+            isolate_in(&store, record);
+        }
+    }
+    if !found.is_empty() {
+        report(&found);
+    }
+}
+
+pub fn list(offset: u64, limit: u64) -> Vec<CorruptRecord> {
+    state::CORRUPT_RECORDS.with(|r| r.borrow().iter()
+        .skip(offset as usize)
+        .take(limit.min(MAX_PAGE_SIZE) as usize)
+        .map(|(_, record)| record)
+        .collect())
+}
+
+// Writes a quarantined record back: its original bytes (after a fix to the types) or `value`
+pub fn repair(id: u64, value: Option<Vec<u8>>) -> Result<String, String> {
+    let record = state::CORRUPT_RECORDS.with(|r| r.borrow().get(&id)).ok_or_else(|| format!("No quarantined record #{}.", id))?;
+    let store = stores().into_iter().find(|store| store.name == record.store)
+        .ok_or_else(|| format!("The {} map is no longer covered by the quarantine.", record.store))?;
+    let value = value.unwrap_or_else(|| record.value.to_vec());
+    (store.restore)(&record.key, &value)?;
+    state::CORRUPT_RECORDS.with(|r| r.borrow_mut().remove(&id));
+    if let Some(map) = store.replicated {
+        replication::note(map, &RawValue(record.key.to_vec()));
+    }
+    if store.name == "user_profiles" {
+        profiles_changed();
+    }
+    Ok(format!("Record #{} is back in {}.", id, store.name))
+}
+
+pub fn delete(id: u64) -> Result<String, String> {
+    let record = state::CORRUPT_RECORDS.with(|r| r.borrow_mut().remove(&id)).ok_or_else(|| format!("No quarantined record #{}.", id))?;
+    Ok(format!("Quarantined record #{} from {} deleted.", id, record.store))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_candid_map_is_covered_once() {
+        let names: Vec<&str> = stores().iter().map(|store| store.name).collect();
+        let unique: std::collections::BTreeSet<&&str> = names.iter().collect();
+        assert_eq!(unique.len(), names.len());
+        assert!(names.contains(&"user_profiles") && names.contains(&"incidents"));
+        assert!(names.contains(&"dares") && names.contains(&"config"));
+    }
+
+    #[test]
+    fn keys_are_checked_and_sorted_like_their_bytes() {
+        assert!(u64::check(&7u64.to_be_bytes()).is_ok() && u64::check(&[1, 2]).is_err());
+        assert!(String::check(&[0xff]).is_err());
+        assert!(StorablePrincipal::check(&[1, 2, 3]).is_err());
+        let principals = [Principal::from_slice(&[9]), Principal::from_slice(&[1, 2]), Principal::from_slice(&[1, 3]), Principal::anonymous()];
+        for pair in principals.windows(2) {
+            let (a, b) = (StorablePrincipal(pair[0]), StorablePrincipal(pair[1]));
+            assert_eq!(a.cmp(&b), a.to_bytes().cmp(&b.to_bytes()));
+        }
+        let key = CompositeKey(5u64, CompositeKey("dare".to_string(), String::new()));
+        assert!(<CompositeKey<u64, CompositeKey<String, String>>>::check(&key.to_bytes()).is_ok());
+        assert!(<CompositeKey<u64, CompositeKey<String, String>>>::check(&key.to_bytes()[..6]).is_err());
+    }
+}
@@ -0,0 +1,352 @@
+use crate::history;
+use crate::correlation;
+use crate::exclusions;
+use crate::outbox;
+use crate::points;
+use crate::repository::{self, DareRepo};
+use crate::state;
+use crate::templates;
+use crate::types::{Dare, DareSource, HistoryEvent, Quest, QuestDraft, QuestDraftStage, QuestProgress, QuestStep, StorablePrincipal, UserProfile};
+use candid::Principal;
+
+// --- Questlines ---
+// A quest is a fixed sequence of dares finished one after another for a points and XP reward.
+// /quest start <id> puts the player on one; while it lasts, /dare hands out the next step (after an
+// admin pin, before the pools). A step counts once its submission is approved: until then /dare
+// serves normal dares, and a rejected or undone submission makes the step come up again. The
+// approval of the last step pays the reward. A step whose dare has been disabled everywhere is
+// skipped, so a retired dare can't strand a player; one disabled only in a chat is still served
+// there, as the player chose the quest.
+//
+// Quests have too many parts for one command, so admins build them in a conversation: after
+// /create_quest every /quest_reply message answers the current question (name, then one step per
+// message until "done", then the reward, then "save"). The draft is a small per-admin state
+// machine in stable memory, so the conversation survives upgrades; it is dropped after
+// DRAFT_TIMEOUT of silence, and "cancel" discards it at any stage. A step is either "#<dare id>"
+// or new dare text, which is stored as a quest-only curated dare with no selection weight so the
+// pools never hand it out on its own. At most MAX_OPEN_QUESTS quests are open at a time, and a
+// player's completed quests only keep the open ones (history keeps the rest).
+
+const DRAFT_TIMEOUT_NANOS: u64 = 30 * 60 * 1_000_000_000;
+const MAX_NAME_LEN: usize = 60;
+const MAX_STEPS: usize = 10;
+const MAX_REWARD_POINTS: u64 = 1_000;
+const MAX_REWARD_XP: u64 = 1_000;
+const MAX_OPEN_QUESTS: usize = 50;
+
+// --- Editor ---
+
+fn draft(admin: Principal, now: u64) -> Option<QuestDraft> {
+    state::QUEST_DRAFTS.with(|d| d.borrow().get(&StorablePrincipal(admin)))
+        .filter(|draft| now.saturating_sub(draft.updated_at) <= DRAFT_TIMEOUT_NANOS)
+}
+
+fn save_draft(admin: Principal, mut draft: QuestDraft, now: u64) {
+    draft.updated_at = now;
+    state::QUEST_DRAFTS.with(|d| d.borrow_mut().insert(StorablePrincipal(admin), draft));
+}
+
+fn discard_draft(admin: Principal) {
+    state::QUEST_DRAFTS.with(|d| d.borrow_mut().remove(&StorablePrincipal(admin)));
+}
+
+fn step_prompt(draft: &QuestDraft) -> String {
+    format!("Send step {}: new dare text, or #<id> for a stored dare. Send \"done\" when the quest is complete.", draft.steps.len() + 1)
+}
+
+fn describe_step(step: &QuestStep) -> String {
+    match step {
+        QuestStep::Existing(id) => repository::dares().get(*id).map_or_else(|| format!("#{}", id), |dare| format!("#{} {}", id, dare.text)),
+        QuestStep::New(text) => text.clone(),
+    }
+}
+
+fn summary(draft: &QuestDraft) -> String {
+    let steps: Vec<String> = draft.steps.iter().enumerate().map(|(i, step)| format!("{}. {}", i + 1, describe_step(step))).collect();
+    format!(
+        "🗺️ {}\n{}\nReward: {} points, {} XP.\nSend \"save\" to publish it or \"cancel\" to discard it.",
+        draft.name.as_deref().unwrap_or_default(), steps.join("\n"), draft.reward_points, draft.reward_xp
+    )
+}
+
+fn parse_step(text: &str) -> Result<QuestStep, String> {
+    if let Some(id) = text.strip_prefix('#') {
+        let id = id.trim().parse::<u64>().map_err(|_| format!("\"{}\" isn't a dare id.", text))?;
+        repository::dares().get(id).ok_or_else(|| format!("Dare #{} not found.", id))?;
+        return Ok(QuestStep::Existing(id));
+    }
+    if text.len() > crate::MAX_DARE_TEXT_LEN {
+        return Err(format!("Dare text is too long (max {} bytes).", crate::MAX_DARE_TEXT_LEN));
+    }
+    templates::validate(text)?;
+    Ok(QuestStep::New(text.to_string()))
+}
+
+// "<points> [xp]"
+fn parse_reward(text: &str) -> Result<(u64, u64), String> {
+    let usage = || format!("Send the reward as \"<points> [xp]\", e.g. \"50 20\" (at most {} points and {} XP).", MAX_REWARD_POINTS, MAX_REWARD_XP);
+    let mut parts = text.split_whitespace().map(str::parse::<u64>);
+    let points = parts.next().and_then(Result::ok).ok_or_else(usage)?;
+    let xp = match parts.next() {
+        Some(xp) => xp.map_err(|_| usage())?,
+        None => 0,
+    };
+    if parts.next().is_some() || points > MAX_REWARD_POINTS || xp > MAX_REWARD_XP {
+        return Err(usage());
+    }
+    Ok((points, xp))
+}
+
+// /create_quest: starts (or restarts) the conversation
+pub fn start_draft(admin: Principal) -> String {
+    let now = ic_cdk::api::time();
+    save_draft(admin, QuestDraft { stage: QuestDraftStage::Name, name: None, steps: Vec::new(), reward_points: 0, reward_xp: 0, updated_at: now }, now);
+    "🗺️ Let's build a questline. What is it called? (Send \"cancel\" at any point to stop.)".to_string()
+}
+
+// /quest_reply <message>: answers the draft's current question and returns the next one
+pub fn reply(admin: Principal, message: &str) -> Result<String, String> {
+    let now = ic_cdk::api::time();
+    let mut draft = draft(admin, now).ok_or_else(|| "No quest is being built. Start with /create_quest.".to_string())?;
+    let message = message.trim();
+    if message.eq_ignore_ascii_case("cancel") {
+        discard_draft(admin);
+        return Ok("Quest discarded.".to_string());
+    }
+    let reply = match draft.stage {
+        QuestDraftStage::Name => {
+            if message.is_empty() || message.len() > MAX_NAME_LEN {
+                return Err(format!("The name must be 1-{} characters.", MAX_NAME_LEN));
+            }
+            draft.name = Some(message.to_string());
+            draft.stage = QuestDraftStage::Steps;
+            step_prompt(&draft)
+        }
+        QuestDraftStage::Steps if message.eq_ignore_ascii_case("done") => {
+            if draft.steps.is_empty() {
+                return Err("A quest needs at least one step.".to_string());
+            }
+            draft.stage = QuestDraftStage::Reward;
+            "What does finishing it pay? Send \"<points> [xp]\", e.g. \"50 20\".".to_string()
+        }
+        QuestDraftStage::Steps => {
+            if draft.steps.len() >= MAX_STEPS {
+                return Err(format!("A quest has at most {} steps. Send \"done\" to continue.", MAX_STEPS));
+            }
+            draft.steps.push(parse_step(message)?);
+            if draft.steps.len() == MAX_STEPS {
+                format!("Step {} added. That's the maximum; send \"done\" to continue.", MAX_STEPS)
+            } else {
+                format!("Step {} added. {}", draft.steps.len(), step_prompt(&draft))
+            }
+        }
+        QuestDraftStage::Reward => {
+            (draft.reward_points, draft.reward_xp) = parse_reward(message)?;
+            draft.stage = QuestDraftStage::Confirm;
+            summary(&draft)
+        }
+        QuestDraftStage::Confirm if message.eq_ignore_ascii_case("save") => {
+            let id = publish(admin, draft)?;
+            discard_draft(admin);
+            return Ok(format!("🗺️ Quest {} published. Players start it with /quest start {}.", id, id));
+        }
+        QuestDraftStage::Confirm => return Ok(summary(&draft)),
+    };
+    save_draft(admin, draft, now);
+    Ok(reply)
+}
+
+fn publish(admin: Principal, draft: QuestDraft) -> Result<u64, String> {
+    let mut steps = Vec::new();
+    for step in draft.steps {
+        steps.push(match step {
+            QuestStep::Existing(id) => id,
+            QuestStep::New(text) => {
+                let analysis = crate::analysis::analyze_dare(&text);
+                let id = crate::store_dare(text, analysis.suggested_difficulty, DareSource::Curated, Some(admin))?;
+                let dares = repository::dares();
+                let dare = dares.get(id).ok_or_else(|| format!("Dare #{} not found.", id))?;
+                dares.set(&Dare { weight: 0, ..dare })?;
+                id
+            }
+        });
+    }
+    let now = ic_cdk::api::time();
+    state::QUESTS.with(|q| {
+        let mut quests = q.borrow_mut();
+        if quests.iter().filter(|(_, quest)| !quest.archived).count() >= MAX_OPEN_QUESTS {
+            return Err(format!("At most {} quests can be open; archive one first.", MAX_OPEN_QUESTS));
+        }
+        let id = quests.last_key_value().map_or(0, |(id, _)| id + 1);
+        quests.insert(id, Quest {
+            id,
+            name: draft.name.unwrap_or_default(),
+            steps,
+            reward_points: draft.reward_points,
+            reward_xp: draft.reward_xp,
+            created_by: admin,
+            created_at: now,
+            archived: false,
+        });
+        Ok(id)
+    })
+}
+
+pub fn archive(quest_id: u64) -> Result<Quest, String> {
+    let mut quest = get(quest_id)?;
+    quest.archived = true;
+    state::QUESTS.with(|q| q.borrow_mut().insert(quest_id, quest.clone()));
+    Ok(quest)
+}
+
+// --- Playing ---
+
+fn get(quest_id: u64) -> Result<Quest, String> {
+    state::QUESTS.with(|q| q.borrow().get(&quest_id)).ok_or_else(|| format!("Quest {} not found.", quest_id))
+}
+
+pub fn list() -> Vec<Quest> {
+    state::QUESTS.with(|q| q.borrow().iter().map(|(_, quest)| quest).collect())
+}
+
+// /quest: open quests and the player's progress
+pub fn describe(user: Principal) -> String {
+    let profile = state::get_profile(user).unwrap_or_default();
+    let completed = profile.quests_completed.unwrap_or_default();
+    let mut lines: Vec<String> = list().into_iter().filter(|quest| !quest.archived).map(|quest| {
+        let status = match &profile.quest {
+            Some(progress) if progress.quest_id == quest.id && progress.pending_submission.is_some() => {
+                format!("in progress, step {}/{} waiting for review", progress.step + 1, quest.steps.len())
+            }
+            Some(progress) if progress.quest_id == quest.id => format!("in progress, step {}/{}", progress.step + 1, quest.steps.len()),
+            _ if completed.contains(&quest.id) => "completed".to_string(),
+            _ => format!("{} steps", quest.steps.len()),
+        };
+        format!("#{} {} ({}): {} points, {} XP", quest.id, quest.name, status, quest.reward_points, quest.reward_xp)
+    }).collect();
+    if lines.is_empty() {
+        return "No quests are open right now.".to_string();
+    }
+    lines.insert(0, "🗺️ Quests (start one with /quest start <id>):".to_string());
+    lines.join("\n")
+}
+
+pub fn start(user: Principal, quest_id: u64) -> Result<String, String> {
+    let quest = get(quest_id)?;
+    if quest.archived {
+        return Err(format!("Quest {} is closed.", quest_id));
+    }
+    let now = ic_cdk::api::time();
+    state::update_profile(user, |profile| {
+        if profile.quests_completed.as_ref().is_some_and(|done| done.contains(&quest_id)) {
+            return Err("You've already completed this quest.".to_string());
+        }
+        if let Some(progress) = &profile.quest {
+            return Err(format!("You're already on quest {}. Finish it (or /quest abandon) first.", progress.quest_id));
+        }
+        profile.quest = Some(QuestProgress { quest_id, step: 0, started_at: now, pending_submission: None });
+        Ok(())
+    })?;
+    Ok(format!("🗺️ Quest \"{}\" started: {} steps. Your next /dare is step 1.", quest.name, quest.steps.len()))
+}
+
+pub fn abandon(user: Principal) -> Result<String, String> {
+    let progress = state::update_profile(user, |profile| profile.quest.take().ok_or_else(|| "You aren't on a quest.".to_string()))?;
+    Ok(format!("You left quest {}.", progress.quest_id))
+}
+
+// The step /dare hands out while the player is on a quest, skipping steps disabled everywhere.
+// None while the current step waits for review.
+pub fn next_step(user: Principal, profile: &UserProfile) -> Option<Dare> {
+    let progress = profile.quest.as_ref().filter(|progress| progress.pending_submission.is_none())?;
+    let quest = get(progress.quest_id).ok()?;
+    let retired = exclusions::disabled_in(None);
+    let mut step = progress.step;
+    while quest.steps.get(step as usize).is_some_and(|id| retired.contains(id)) {
+        step += 1;
+    }
+    if step != progress.step {
+        advance(user, &quest, progress.step, step);
+    }
+    quest.steps.get(step as usize).and_then(|&id| repository::dares().get(id))
+}
+
+// Called while a submission is recorded: the current step now waits for its review
+pub fn note_submission(profile: &mut UserProfile, dare_id: Option<u64>, submission_id: u64) {
+    let Some(progress) = profile.quest.as_mut().filter(|progress| progress.pending_submission.is_none()) else { return };
+    let Ok(quest) = get(progress.quest_id) else { return };
+    if dare_id.is_some() && quest.steps.get(progress.step as usize).copied() == dare_id {
+        progress.pending_submission = Some(submission_id);
+    }
+}
+
+// Called when a submission is rejected or undone: its step comes up again
+pub fn revert(profile: &mut UserProfile, submission_id: u64) {
+    if let Some(progress) = profile.quest.as_mut().filter(|progress| progress.pending_submission == Some(submission_id)) {
+        progress.pending_submission = None;
+    }
+}
+
+// Called on every approval (including granted appeals): completes the step it was for
+pub fn note_approval(user: Principal, submission_id: u64, dare_id: Option<u64>) {
+    let Some(dare_id) = dare_id else { return };
+    let Some(progress) = state::get_profile(user).and_then(|profile| profile.quest) else { return };
+    let Ok(quest) = get(progress.quest_id) else { return };
+    // An appeal can approve a step after its rejection put it back
+    let current = quest.steps.get(progress.step as usize) == Some(&dare_id);
+    if progress.pending_submission == Some(submission_id) || (progress.pending_submission.is_none() && current) {
+        advance(user, &quest, progress.step, progress.step + 1);
+    }
+}
+
+// Moves the player from step `from` to `to`, finishing the quest past its last step
+fn advance(user: Principal, quest: &Quest, from: u32, to: u32) {
+    let open: Vec<u64> = list().into_iter().filter(|quest| !quest.archived).map(|quest| quest.id).collect();
+    let finished = state::update_profile(user, |profile| {
+        let Some(progress) = profile.quest.as_mut().filter(|progress| progress.quest_id == quest.id && progress.step == from) else { return Ok(None) };
+        progress.step = to;
+        progress.pending_submission = None;
+        if (to as usize) < quest.steps.len() {
+            return Ok(Some(false));
+        }
+        profile.quest = None;
+        let completed = profile.quests_completed.get_or_insert_with(Vec::new);
+        completed.retain(|id| open.contains(id));
+        completed.push(quest.id);
+        profile.xp = Some(profile.xp.unwrap_or(0) + quest.reward_xp);
+        Ok(Some(true))
+    });
+    match finished {
+        Ok(Some(true)) => {
+            if quest.reward_points > 0 {
+                if let Err(e) = points::mint(user, quest.reward_points, Some("quest reward")) {
+                    correlation::log!("Could not pay quest {} reward to {}: {}", quest.id, user, e);
+                }
+            }
+            history::log_event(user, HistoryEvent::QuestCompleted { quest_id: quest.id, points: quest.reward_points, xp: quest.reward_xp });
+            outbox::queue(user, format!(
+                "🏆 Quest \"{}\" complete! +{} points, +{} XP.", quest.name, quest.reward_points, quest.reward_xp
+            ), None);
+        }
+        Ok(Some(false)) => {
+            outbox::queue(user, format!("🗺️ Quest \"{}\": step {}/{} is next. Use /dare.", quest.name, to + 1, quest.steps.len()), None);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reward_takes_points_and_optional_xp() {
+        assert_eq!(parse_reward("50"), Ok((50, 0)));
+        assert_eq!(parse_reward(" 50  20 "), Ok((50, 20)));
+        assert!(parse_reward("").is_err());
+        assert!(parse_reward("fifty").is_err());
+        assert!(parse_reward("50 20 10").is_err());
+        assert!(parse_reward("50 lots").is_err());
+        assert!(parse_reward(&format!("{}", MAX_REWARD_POINTS + 1)).is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use crate::outbox;
+use crate::state;
+use crate::types::QueueAlerts;
+use std::cell::RefCell;
+
+// --- Queue Alerts ---
+// The review maintenance timer checks the review and suggestion queues against the configured
+// thresholds (item count and age of the oldest item). When any is exceeded, admins on the alert
+// list get a DM summarizing the backlog with the commands to work it off, at most once per
+// cooldown. The last alert time lives on the heap, so an upgrade may let one extra alert through.
+
+const HOUR_NANOS: u64 = 60 * 60 * 1_000_000_000;
+pub const MAX_COOLDOWN_HOURS: u32 = 7 * 24;
+
+thread_local! {
+    static LAST_ALERT_AT: RefCell<u64> = const { RefCell::new(0) };
+}
+
+pub fn validate(alerts: &QueueAlerts) -> Result<(), String> {
+    if alerts.cooldown_hours == 0 || alerts.cooldown_hours > MAX_COOLDOWN_HOURS {
+        return Err(format!("cooldown_hours must be between 1 and {}.", MAX_COOLDOWN_HOURS));
+    }
+    let limits = [
+        alerts.max_pending_submissions,
+        alerts.max_submission_age_hours,
+        alerts.max_pending_suggestions,
+        alerts.max_suggestion_age_hours,
+    ];
+    if limits.iter().all(Option::is_none) {
+        return Err("Set at least one threshold.".to_string());
+    }
+    if limits.contains(&Some(0)) {
+        return Err("Thresholds must be positive.".to_string());
+    }
+    Ok(())
+}
+
+// (pending items, age of the oldest one in hours)
+fn review_backlog(now: u64) -> (u64, u64) {
+    state::REVIEW_QUEUE.with(|q| {
+        let queue = q.borrow();
+        let oldest = queue.iter().map(|(_, task)| task.queued_at).min();
+        (queue.len(), oldest.map_or(0, |queued_at| now.saturating_sub(queued_at) / HOUR_NANOS))
+    })
+}
+
+fn suggestion_backlog(now: u64) -> (u64, u64) {
+    state::SUGGESTIONS.with(|s| {
+        let suggestions = s.borrow();
+        // Ids are sequential, so the first suggestion is the oldest
+        let oldest = suggestions.first_key_value().map(|(_, suggestion)| suggestion.created_at);
+        (suggestions.len(), oldest.map_or(0, |created_at| now.saturating_sub(created_at) / HOUR_NANOS))
+    })
+}
+
+// Lines describing every exceeded threshold
+fn problems(alerts: &QueueAlerts, now: u64) -> Vec<String> {
+    let mut problems = Vec::new();
+    let (submissions, submission_age) = review_backlog(now);
+    if let Some(limit) = alerts.max_pending_submissions.filter(|&limit| submissions > limit as u64) {
+        problems.push(format!("{} submissions are waiting for review (limit {}).", submissions, limit));
+    }
+    if let Some(limit) = alerts.max_submission_age_hours.filter(|&limit| submission_age > limit as u64) {
+        problems.push(format!("The oldest submission has waited {}h (limit {}h).", submission_age, limit));
+    }
+    let (suggestions, suggestion_age) = suggestion_backlog(now);
+    if let Some(limit) = alerts.max_pending_suggestions.filter(|&limit| suggestions > limit as u64) {
+        problems.push(format!("{} suggestions are waiting (limit {}).", suggestions, limit));
+    }
+    if let Some(limit) = alerts.max_suggestion_age_hours.filter(|&limit| suggestion_age > limit as u64) {
+        problems.push(format!("The oldest suggestion has waited {}h (limit {}h).", suggestion_age, limit));
+    }
+    problems
+}
+
+// Timer entry point. Returns whether an alert was sent.
+pub fn run() -> bool {
+    let Some(alerts) = state::get_config().queue_alerts else { return false };
+    let now = ic_cdk::api::time();
+    let cooldown = alerts.cooldown_hours as u64 * HOUR_NANOS;
+    if LAST_ALERT_AT.with(|last| now.saturating_sub(*last.borrow()) < cooldown) {
+        return false;
+    }
+    let problems = problems(&alerts, now);
+    if problems.is_empty() {
+        return false;
+    }
+    LAST_ALERT_AT.with(|last| *last.borrow_mut() = now);
+    let mut text = "📥 The moderation queues need attention:".to_string();
+    for problem in &problems {
+        text.push_str("\n- ");
+        text.push_str(problem);
+    }
+    text.push_str("\nWork through them with /review claim and /list_suggestions, add reviewers with /add_moderator, or set an SLA with /set_review_sla.");
+    outbox::alert_admins(&text);
+    true
+}
@@ -0,0 +1,77 @@
+use crate::correlation;
+use crate::state;
+use crate::types::EntropyPool;
+use serde_bytes::ByteBuf;
+use sha2::{Digest, Sha256};
+
+// --- Randomness ---
+// Dare, truth and reward picks draw from an entropy pool seeded by the management canister's
+// raw_rand, so they are unpredictable to players yet identical on every replica. The pool keeps a
+// 32-byte seed and a draw counter in stable memory; each draw is SHA-256(seed, counter) and
+// advances the counter. A timer mixes fresh raw_rand bytes into the seed every hour, and once
+// right after install or upgrade. Until that first seed arrives, draws fall back to a mix of the
+// timestamp.
+//
+// Picks reduce a 64-bit draw modulo a weight total; for any total a pool could have, that bias
+// is below one in four billion.
+
+fn pool() -> EntropyPool {
+    state::ENTROPY.with(|e| e.borrow().get().clone())
+}
+
+fn derive(seed: &[u8], draw: u64) -> [u8; 32] {
+    Sha256::new().chain_update(seed).chain_update(draw.to_be_bytes()).finalize().into()
+}
+
+// Timestamp-derived fallback for the moments before the pool is first seeded
+fn timestamp_mix() -> u64 {
+    let nanos = ic_cdk::api::time();
+    // Mix the bits so consecutive timestamps don't map to consecutive picks
+    nanos.wrapping_mul(6364136223846793005).rotate_left(29) ^ nanos
+}
+
+// 32 fresh bytes from the pool; None while it's unseeded
+pub fn next_bytes() -> Option<[u8; 32]> {
+    let mut pool = pool();
+    if pool.seed.is_empty() {
+        return None;
+    }
+    let bytes = derive(&pool.seed, pool.draws);
+    pool.draws += 1;
+    state::ENTROPY.with(|e| e.borrow_mut().set(pool)).expect("Failed to save the entropy pool");
+    Some(bytes)
+}
+
+pub fn next_u64() -> u64 {
+    match next_bytes() {
+        Some(bytes) => u64::from_be_bytes(bytes[..8].try_into().unwrap_or_default()),
+        None => timestamp_mix(),
+    }
+}
+
+// Mixes fresh raw_rand bytes into the seed
+pub async fn reseed() {
+    let fresh = match ic_cdk::api::management_canister::main::raw_rand().await {
+        Ok((bytes,)) => bytes,
+        Err((code, message)) => {
+            correlation::log!("Could not reseed the entropy pool: {:?} {}", code, message);
+            return;
+        }
+    };
+    let mut pool = pool();
+    pool.seed = ByteBuf::from(Sha256::new().chain_update(&pool.seed).chain_update(&fresh).finalize().to_vec());
+    pool.seeded_at = Some(ic_cdk::api::time());
+    state::ENTROPY.with(|e| e.borrow_mut().set(pool)).expect("Failed to save the entropy pool");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_are_deterministic_per_seed_and_counter() {
+        assert_eq!(derive(b"seed", 0), derive(b"seed", 0));
+        assert_ne!(derive(b"seed", 0), derive(b"seed", 1));
+        assert_ne!(derive(b"seed", 0), derive(b"other", 0));
+    }
+}
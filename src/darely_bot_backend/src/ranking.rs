@@ -0,0 +1,312 @@
+use crate::repository::{self, UserRepo};
+use crate::state;
+use crate::state::KeyPart;
+use crate::types::{CompositeKey, LeaderboardBuild, LeaderboardSnapshot, StorablePrincipal, StreakIndexKey, UserProfile};
+use candid::Principal;
+use std::cmp::Reverse;
+
+pub const MAX_LEADERBOARD_SIZE: usize = 20;
+
+// --- Leaderboard Snapshots ---
+// Sorting every profile on each request doesn't scale to large user bases, so the leaderboard is
+// served from a snapshot. A timer scans the profiles a batch per tick, merging each batch's top
+// entries into a partial kept in stable memory (so a scan survives upgrades), and publishes the
+// result with its completion time once the scan reaches the end. A new scan starts on the next
+// tick. Until the first snapshot exists, the leaderboard is computed on demand.
+//
+// The order is total, so ranks don't flap between queries: highest streak first, then whoever
+// reached that streak earlier, then the lower principal. Profiles whose streak hasn't changed
+// since `streak_reached_at` was introduced count as having reached it earliest.
+
+const BATCH_SIZE: usize = 5_000;
+
+// (user, streak, streak_reached_at)
+type Ranked = (Principal, u32, Option<u64>);
+
+// Every streak change goes through here, so ties can be broken by who got there first
+pub fn set_streak(profile: &mut UserProfile, streak: u32) {
+    if profile.streak != streak {
+        profile.streak = streak;
+        profile.streak_reached_at = Some(ic_cdk::api::time());
+    }
+}
+
+fn ranked(user: Principal, profile: &UserProfile) -> Ranked {
+    (user, profile.streak, profile.streak_reached_at)
+}
+
+// Lower keys rank higher
+fn rank_key(&(user, streak, reached_at): &Ranked) -> (Reverse<u32>, Option<u64>, Principal) {
+    (Reverse(streak), reached_at, user)
+}
+
+fn rank(entries: &mut Vec<Ranked>) {
+    entries.sort_by_key(rank_key);
+    entries.truncate(MAX_LEADERBOARD_SIZE);
+}
+
+fn published(entries: Vec<Ranked>) -> Vec<(Principal, u32)> {
+    entries.into_iter().map(|(user, streak, _)| (user, streak)).collect()
+}
+
+fn compute_now() -> LeaderboardSnapshot {
+    let mut entries: Vec<Ranked> = Vec::new();
+    repository::users().scan(None, &mut |user, profile| {
+        entries.push(ranked(user, &profile));
+        true
+    });
+    let users_ranked = entries.len() as u64;
+    rank(&mut entries);
+    LeaderboardSnapshot { entries: published(entries), computed_at: ic_cdk::api::time(), users_ranked }
+}
+
+// Timer entry point: scans the next batch and publishes the snapshot when the scan is done
+pub fn tick() {
+    let now = ic_cdk::api::time();
+    let mut build = state::LEADERBOARD_BUILD.with(|b| b.borrow().get().clone());
+    if build.started_at == 0 {
+        build = LeaderboardBuild { started_at: now, ..LeaderboardBuild::default() };
+    }
+    let mut batch: Vec<Ranked> = Vec::with_capacity(BATCH_SIZE);
+    repository::users().scan(build.cursor, &mut |user, profile| {
+        batch.push(ranked(user, &profile));
+        batch.len() < BATCH_SIZE
+    });
+    let finished = batch.len() < BATCH_SIZE;
+    build.scanned += batch.len() as u64;
+    build.cursor = batch.last().map(|(user, _, _)| *user).or(build.cursor);
+    build.partial.extend(batch);
+    rank(&mut build.partial);
+
+    if finished {
+        let snapshot = LeaderboardSnapshot { entries: published(std::mem::take(&mut build.partial)), computed_at: now, users_ranked: build.scanned };
+        state::LEADERBOARD.with(|l| l.borrow_mut().set(snapshot).expect("Failed to write leaderboard snapshot"));
+        build = LeaderboardBuild::default();
+    }
+    state::LEADERBOARD_BUILD.with(|b| b.borrow_mut().set(build).expect("Failed to write leaderboard build"));
+}
+
+// The latest snapshot, or a fresh computation before the first scan has finished
+pub fn snapshot() -> LeaderboardSnapshot {
+    let snapshot = state::LEADERBOARD.with(|l| l.borrow().get().clone());
+    if snapshot.computed_at == 0 { compute_now() } else { snapshot }
+}
+
+// Top users by current streak, highest first
+pub fn top_streaks(limit: usize) -> Vec<(Principal, u32)> {
+    let mut entries = snapshot().entries;
+    entries.truncate(limit);
+    entries
+}
+
+// --- Streak Index ---
+// Ranks for users far below the top come from an index instead of a scan: STREAK_INDEX mirrors
+// every profile under a key that sorts in leaderboard order, and STREAK_COUNTS holds how many
+// users have each streak. A rank is the users in higher streak buckets (one map entry per
+// distinct streak) plus the user's place in their own bucket. That place is counted exactly up
+// to WITHIN_BUCKET_SCAN_LIMIT; in larger buckets it is interpolated from where the user's
+// `streak_reached_at` falls between the bucket's first and last.
+
+const WITHIN_BUCKET_SCAN_LIMIT: usize = 1_000;
+
+pub struct Rank {
+    pub position: u64, // 1 = first
+    pub total: u64,
+    pub exact: bool,
+}
+
+fn index_key(user: Principal, streak: u32, reached_at: Option<u64>) -> StreakIndexKey {
+    CompositeKey(u64::from(u32::MAX - streak), CompositeKey(reached_at.unwrap_or(0), StorablePrincipal(user)))
+}
+
+// First possible key in a streak's bucket
+fn bucket_start(inverted_streak: u64) -> StreakIndexKey {
+    CompositeKey(inverted_streak, CompositeKey(0, StorablePrincipal::min_value()))
+}
+
+fn adjust_count(streak: u32, added: bool) {
+    state::STREAK_COUNTS.with(|c| {
+        let mut counts = c.borrow_mut();
+        let count = counts.get(&streak).unwrap_or(0);
+        match (added, count) {
+            (true, _) => { counts.insert(streak, count + 1); }
+            (false, 0 | 1) => { counts.remove(&streak); }
+            (false, _) => { counts.insert(streak, count - 1); }
+        }
+    });
+}
+
+// Keeps the index in step with a profile write; `before` is None for a new profile
+pub fn reindex(user: Principal, before: Option<(u32, Option<u64>)>, after: (u32, Option<u64>)) {
+    if before == Some(after) {
+        return;
+    }
+    if let Some((streak, reached_at)) = before {
+        // Profiles not yet indexed (see rebuild_index) have nothing to take out
+        if state::STREAK_INDEX.with(|i| i.borrow_mut().remove(&index_key(user, streak, reached_at))).is_some() {
+            adjust_count(streak, false);
+        }
+    }
+    if state::STREAK_INDEX.with(|i| i.borrow_mut().insert(index_key(user, after.0, after.1), ())).is_none() {
+        adjust_count(after.0, true);
+    }
+}
+
+// Takes a deleted profile out of the index
+pub fn unindex(user: Principal, before: (u32, Option<u64>)) {
+    if state::STREAK_INDEX.with(|i| i.borrow_mut().remove(&index_key(user, before.0, before.1))).is_some() {
+        adjust_count(before.0, false);
+    }
+}
+
+// Indexes every profile unless the index already covers them all (run on init and post_upgrade)
+pub fn rebuild_index() {
+    let profiles = repository::users().len();
+    if state::STREAK_INDEX.with(|i| i.borrow().len()) == profiles {
+        return;
+    }
+    state::STREAK_INDEX.with(|i| i.borrow_mut().clear_new());
+    state::STREAK_COUNTS.with(|c| c.borrow_mut().clear_new());
+    let mut entries: Vec<Ranked> = Vec::new();
+    repository::users().scan(None, &mut |user, profile| {
+        entries.push(ranked(user, &profile));
+        true
+    });
+    for (user, streak, reached_at) in entries {
+        reindex(user, None, (streak, reached_at));
+    }
+}
+
+// Where `reached_at` falls between the bucket's first and last timestamps, as a share of `size`
+fn interpolate(size: u64, first: u64, last: u64, reached_at: u64) -> u64 {
+    if last <= first {
+        return size / 2;
+    }
+    let fraction = reached_at.saturating_sub(first).min(last - first) as f64 / (last - first) as f64;
+    (size as f64 * fraction) as u64
+}
+
+// The user's place in the full ranking, with the same tie-breaks as the leaderboard
+pub fn rank_of(user: Principal) -> Option<Rank> {
+    let profile = state::get_profile(user)?;
+    let own = index_key(user, profile.streak, profile.streak_reached_at);
+    let higher: u64 = match profile.streak.checked_add(1) {
+        Some(next) => state::STREAK_COUNTS.with(|c| c.borrow().range(next..).map(|(_, count)| count).sum()),
+        None => 0,
+    };
+    let start = bucket_start(own.0);
+    let counted = state::STREAK_INDEX.with(|i| {
+        i.borrow().range(start.clone()..own.clone()).take(WITHIN_BUCKET_SCAN_LIMIT + 1).count()
+    });
+    let total = repository::users().len();
+    if counted <= WITHIN_BUCKET_SCAN_LIMIT {
+        return Some(Rank { position: higher + counted as u64 + 1, total, exact: true });
+    }
+    let size = state::STREAK_COUNTS.with(|c| c.borrow().get(&profile.streak)).unwrap_or(1);
+    let (first, last) = state::STREAK_INDEX.with(|i| {
+        let index = i.borrow();
+        let first = index.range(start..).next().map(|(key, _)| key.1.0);
+        let last = index.range(..bucket_start(own.0 + 1)).next_back().map(|(key, _)| key.1.0);
+        (first.unwrap_or(0), last.unwrap_or(0))
+    });
+    let within = interpolate(size, first, last, own.1.0).clamp(WITHIN_BUCKET_SCAN_LIMIT as u64, size.saturating_sub(1).max(WITHIN_BUCKET_SCAN_LIMIT as u64));
+    Some(Rank { position: higher + within + 1, total, exact: false })
+}
+
+// "Top n%" for a position, rounded up so the leader is in the top 1%
+pub fn top_percent(position: u64, total: u64) -> u64 {
+    (position * 100).div_ceil(total.max(1)).clamp(1, 100)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::{Decode, Encode};
+
+    fn user(id: u8) -> Principal {
+        Principal::from_slice(&[id])
+    }
+
+    fn order(mut entries: Vec<Ranked>) -> Vec<Principal> {
+        rank(&mut entries);
+        entries.into_iter().map(|(user, _, _)| user).collect()
+    }
+
+    #[test]
+    fn higher_streak_ranks_first() {
+        let entries = vec![(user(1), 3, Some(10)), (user(2), 7, Some(50)), (user(3), 5, None)];
+        assert_eq!(order(entries), vec![user(2), user(3), user(1)]);
+    }
+
+    #[test]
+    fn ties_go_to_whoever_reached_the_streak_first() {
+        let entries = vec![(user(1), 4, Some(300)), (user(2), 4, Some(100)), (user(3), 4, Some(200))];
+        assert_eq!(order(entries), vec![user(2), user(3), user(1)]);
+    }
+
+    #[test]
+    fn untracked_timestamps_count_as_earliest() {
+        let entries = vec![(user(1), 4, Some(1)), (user(2), 4, None)];
+        assert_eq!(order(entries), vec![user(2), user(1)]);
+    }
+
+    #[test]
+    fn full_ties_go_to_the_lower_principal() {
+        let entries = vec![(user(9), 2, Some(5)), (user(4), 2, Some(5)), (user(6), 2, Some(5))];
+        assert_eq!(order(entries), vec![user(4), user(6), user(9)]);
+    }
+
+    #[test]
+    fn order_does_not_depend_on_input_order() {
+        let entries: Vec<Ranked> = (0..30u8).map(|id| (user(id), u32::from(id % 3), Some(u64::from(id % 4)))).collect();
+        let mut reversed = entries.clone();
+        reversed.reverse();
+        assert_eq!(order(entries), order(reversed));
+    }
+
+    #[test]
+    fn merging_batches_matches_ranking_everything_at_once() {
+        let entries: Vec<Ranked> = (0..60u8).map(|id| (user(id), u32::from(id % 5), Some(u64::from(id % 7)))).collect();
+        let mut partial = Vec::new();
+        for batch in entries.chunks(7) {
+            partial.extend_from_slice(batch);
+            rank(&mut partial);
+        }
+        assert_eq!(partial.len(), MAX_LEADERBOARD_SIZE);
+        assert_eq!(partial, { let mut all = entries.clone(); rank(&mut all); all });
+    }
+
+    #[test]
+    fn index_keys_sort_in_leaderboard_order() {
+        let entries: Vec<Ranked> = (0..40u8).map(|id| (user(id), u32::from(id % 4), Some(u64::from(id % 6) + 1))).collect();
+        let mut by_index = entries.clone();
+        by_index.sort_by_key(|&(user, streak, reached_at)| index_key(user, streak, reached_at));
+        let mut by_rank = entries;
+        by_rank.sort_by_key(rank_key);
+        assert_eq!(by_index, by_rank);
+    }
+
+    #[test]
+    fn interpolation_stays_inside_the_bucket() {
+        assert_eq!(interpolate(1_000, 100, 200, 150), 500);
+        assert_eq!(interpolate(1_000, 100, 200, 50), 0);
+        assert_eq!(interpolate(1_000, 100, 200, 900), 1_000);
+        assert_eq!(interpolate(1_000, 100, 100, 100), 500);
+    }
+
+    #[test]
+    fn top_percent_rounds_up() {
+        assert_eq!(top_percent(1, 400), 1);
+        assert_eq!(top_percent(12, 400), 3);
+        assert_eq!(top_percent(400, 400), 100);
+        assert_eq!(top_percent(1, 1), 100);
+    }
+
+    #[test]
+    fn builds_stored_without_timestamps_still_decode() {
+        let old: Vec<(Principal, u32)> = vec![(user(1), 3)];
+        let bytes = Encode!(&old).unwrap();
+        let decoded = Decode!(&bytes, Vec<Ranked>).unwrap();
+        assert_eq!(decoded, vec![(user(1), 3, None)]);
+    }
+}
@@ -0,0 +1,114 @@
+use crate::features::{self, Feature};
+use crate::outbox;
+use crate::repository::{self, DareRepo};
+use crate::state;
+use crate::types::{DareRating, DareRatingReport, DareRatings, Difficulty, Submission};
+use candid::Principal;
+use std::collections::BTreeMap;
+
+// --- Dare Ratings ---
+// When the survey is on, an approved dare submission is followed by a DM asking how hard and how
+// fun the dare actually was (1-5 each, once per submission). Answers are summed per dare. Once a
+// dare has enough ratings, its fun score scales its selection weight (60% at 1, 140% at 5) and
+// the admin report suggests a new difficulty when the players' average disagrees with the listed
+// one.
+
+const MIN_RATINGS: u64 = 5;
+
+pub fn survey_enabled() -> bool {
+    state::get_config().rating_survey.unwrap_or(false)
+}
+
+// Called by moderation when a dare submission is approved
+pub fn prompt(submission: &Submission) {
+    if !survey_enabled() || submission.rating.is_some() || submission.dare_id.is_none()
+        || !features::enabled(Feature::RatingSurvey, submission.user)
+    {
+        return;
+    }
+    outbox::queue(submission.user, format!(
+        "✅ Submission #{} was approved! How was it? Rate it with /rate_dare {} <difficulty 1-5> <fun 1-5>.",
+        submission.id, submission.id
+    ), None);
+}
+
+pub fn rate(user: Principal, submission_id: u64, rating: DareRating) -> Result<u64, String> {
+    if !(1..=5).contains(&rating.difficulty) || !(1..=5).contains(&rating.fun) {
+        return Err("Ratings go from 1 to 5.".to_string());
+    }
+    let mut submission = state::SUBMISSIONS.with(|s| s.borrow().get(&submission_id))
+        .filter(|submission| submission.user == user)
+        .ok_or_else(|| format!("You have no submission #{}.", submission_id))?;
+    let dare_id = submission.dare_id
+        .ok_or_else(|| format!("Submission #{} isn't for a dare.", submission_id))?;
+    if !submission.review.as_ref().is_some_and(|review| review.approved) {
+        return Err(format!("Submission #{} can be rated once it's approved.", submission_id));
+    }
+    if submission.rating.is_some() {
+        return Err(format!("You already rated submission #{}.", submission_id));
+    }
+    submission.rating = Some(rating);
+    state::put_submission(submission);
+    state::DARE_RATINGS.with(|ratings_ref| {
+        let mut ratings = ratings_ref.borrow_mut();
+        let mut totals = ratings.get(&dare_id).unwrap_or_default();
+        totals.count += 1;
+        totals.difficulty_sum += rating.difficulty as u64;
+        totals.fun_sum += rating.fun as u64;
+        ratings.insert(dare_id, totals);
+    });
+    Ok(dare_id)
+}
+
+// --- Weighting ---
+
+// 60-140% depending on the average fun score, or None while there are too few ratings
+fn weight_percent(totals: &DareRatings) -> Option<u32> {
+    (totals.count >= MIN_RATINGS).then(|| {
+        // 1.0 -> 60, 5.0 -> 140, linear in between
+        (60 + (totals.fun_sum * 20 / totals.count).saturating_sub(20)) as u32
+    })
+}
+
+// Weight adjustments for every dare with enough ratings (see selection::candidates)
+pub fn weight_percents() -> BTreeMap<u64, u32> {
+    state::DARE_RATINGS.with(|r| {
+        r.borrow().iter()
+            .filter_map(|(dare_id, totals)| weight_percent(&totals).map(|percent| (dare_id, percent)))
+            .collect()
+    })
+}
+
+// --- Admin Report ---
+
+fn difficulty_for(average: f32) -> Difficulty {
+    if average < 2.5 {
+        Difficulty::Easy
+    } else if average < 3.5 {
+        Difficulty::Medium
+    } else {
+        Difficulty::Hard
+    }
+}
+
+pub fn report(offset: usize, limit: usize) -> Vec<DareRatingReport> {
+    let rated: Vec<(u64, DareRatings)> = state::DARE_RATINGS.with(|r| r.borrow().iter().skip(offset).take(limit).collect());
+    rated.into_iter()
+        .filter_map(|(dare_id, totals)| {
+            let dare = repository::dares().get(dare_id)?;
+            let count = totals.count.max(1) as f32;
+            let avg_difficulty = totals.difficulty_sum as f32 / count;
+            let suggested = difficulty_for(avg_difficulty);
+            Some(DareRatingReport {
+                dare_id,
+                text: dare.text,
+                ratings: totals.count,
+                avg_difficulty,
+                avg_fun: totals.fun_sum as f32 / count,
+                suggested_difficulty: (totals.count >= MIN_RATINGS && suggested != dare.difficulty).then_some(suggested),
+                difficulty: dare.difficulty,
+                weight_percent: weight_percent(&totals).unwrap_or(100),
+            })
+        })
+        .collect()
+}
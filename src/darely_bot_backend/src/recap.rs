@@ -0,0 +1,159 @@
+use crate::gallery;
+use crate::repository::{self, DareRepo};
+use crate::state;
+use crate::templates;
+use crate::timezone;
+use crate::types::{Difficulty, HistoryEvent, RewardTier};
+use candid::Principal;
+use std::collections::{BTreeMap, BTreeSet};
+
+// --- Monthly Recap ---
+// /recap [period] sums up a month of the user's history as a markdown message for the chat:
+// dares completed (undone and rejected submissions don't count, granted appeals do), the longest
+// run of consecutive days with a dare, badges earned, and the difficulty the user completed most.
+// Months follow the user's timezone. The period is "last" for the previous month or YYYY-MM;
+// without one it's the month so far; recaps go back at most MAX_MONTHS_BACK months, which bounds
+// the walk back through the history. The layout is a message template filled by templates::fill.
+
+const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+const MAX_MONTHS_BACK: i64 = 12;
+const MONTHS: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December",
+];
+
+const TEMPLATE: &str = "📅 **{name}'s Darely recap: {month}**
+
+🎯 Dares completed: **{dares}**
+🔥 Best run: **{best_run}** day(s) in a row
+🏅 Badges earned: {badges}
+⭐ Favourite category: {favorite}
+🙊 Truths answered: {truths}
+🗺️ Quests completed: {quests}
+
+Current streak: {streak}. Keep it going with /dare!";
+
+// (label, first local day, last local day)
+fn month_bounds(period: Option<&str>, today: i64) -> Result<(String, i64, i64), String> {
+    let (this_year, this_month, _) = timezone::civil_from_days(today);
+    let (year, month) = match period.map(str::trim).filter(|period| !period.is_empty()) {
+        None => (this_year, this_month),
+        Some("last") if this_month == 1 => (this_year - 1, 12),
+        Some("last") => (this_year, this_month - 1),
+        Some(period) => period.split_once('-')
+            .and_then(|(year, month)| Some((year.parse::<i64>().ok()?, month.parse::<u32>().ok()?)))
+            .filter(|(year, month)| (1970..=9999).contains(year) && (1..=12).contains(month))
+            .ok_or_else(|| "Give the period as \"last\" or YYYY-MM.".to_string())?,
+    };
+    if (this_year * 12 + this_month as i64) - (year * 12 + month as i64) > MAX_MONTHS_BACK {
+        return Err(format!("Recaps go back {} months at most.", MAX_MONTHS_BACK));
+    }
+    let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+    let first = timezone::days_from_civil(year, month, 1);
+    let last = timezone::days_from_civil(next_year, next_month, 1) - 1;
+    Ok((format!("{} {}", MONTHS[month as usize - 1], year), first, last))
+}
+
+// Longest stretch of consecutive days
+fn longest_run(days: &BTreeSet<i64>) -> u32 {
+    let (mut best, mut run, mut previous) = (0, 0, None);
+    for &day in days {
+        run = if previous == Some(day - 1) { run + 1 } else { 1 };
+        best = best.max(run);
+        previous = Some(day);
+    }
+    best
+}
+
+// A submission still counts unless it was undone (removed) or stands rejected
+fn counts(submission_id: u64) -> bool {
+    state::SUBMISSIONS.with(|s| s.borrow().get(&submission_id))
+        .is_some_and(|submission| submission.review.is_none_or(|review| review.approved))
+}
+
+pub fn render(user: Principal, period: Option<String>) -> Result<String, String> {
+    let profile = state::get_profile(user).ok_or_else(|| "User not found. Please /register first.".to_string())?;
+    let now = ic_cdk::api::time();
+    let (label, first, last) = month_bounds(period.as_deref(), timezone::local_day_number(user, now))?;
+    if first > timezone::local_day_number(user, now) {
+        return Err("That month hasn't started yet.".to_string());
+    }
+    // Timezones are within a day of UTC, so this bound covers the whole local month
+    let since = (first.max(1) as u64 - 1) * DAY_NANOS;
+
+    let mut days = BTreeSet::new();
+    let mut by_difficulty: BTreeMap<Difficulty, u32> = BTreeMap::new();
+    let (mut dares, mut truths, mut quests) = (0u32, 0u32, 0u32);
+    let mut badges = Vec::new();
+    state::HISTORY.with(|history_ref| {
+        let history = history_ref.borrow();
+        let entries = history.iter().rev().map(|(_, entry)| entry).take_while(|entry| entry.timestamp >= since);
+        for entry in entries.filter(|entry| entry.user == user) {
+            let day = timezone::local_day_number(user, entry.timestamp);
+            if day < first || day > last {
+                continue;
+            }
+            match entry.event {
+                HistoryEvent::DareSubmitted { submission_id, dare_id, .. } if counts(submission_id) => {
+                    dares += 1;
+                    days.insert(day);
+                    if let Some(dare) = dare_id.and_then(|id| repository::dares().get(id)) {
+                        *by_difficulty.entry(dare.difficulty).or_default() += 1;
+                    }
+                }
+                HistoryEvent::TruthAnswered { .. } => truths += 1,
+                HistoryEvent::QuestCompleted { .. } => quests += 1,
+                HistoryEvent::RewardSpun { milestone, tier, .. } => {
+                    badges.push(format!("reward-{}", milestone));
+                    if tier == RewardTier::Badge {
+                        badges.push(format!("lucky-{}", milestone));
+                    }
+                }
+                _ => {}
+            }
+        }
+    });
+    badges.reverse();
+
+    let favorite = by_difficulty.into_iter()
+        .max_by_key(|(_, count)| *count)
+        .map_or_else(|| "none yet".to_string(), |(difficulty, count)| format!("{:?} dares ({})", difficulty, count));
+    let values = [
+        ("name", gallery::display_name(user)),
+        ("month", label),
+        ("dares", dares.to_string()),
+        ("best_run", longest_run(&days).to_string()),
+        ("badges", if badges.is_empty() { "none this month".to_string() } else { badges.join(", ") }),
+        ("favorite", favorite),
+        ("truths", truths.to_string()),
+        ("quests", quests.to_string()),
+        ("streak", profile.streak.to_string()),
+    ];
+    Ok(templates::fill(TEMPLATE, &values))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn periods_resolve_to_whole_months() {
+        let today = timezone::days_from_civil(2026, 1, 15);
+        let (label, first, last) = month_bounds(None, today).unwrap();
+        assert_eq!((label.as_str(), first, last), ("January 2026", timezone::days_from_civil(2026, 1, 1), timezone::days_from_civil(2026, 1, 31)));
+        let (label, first, last) = month_bounds(Some("last"), today).unwrap();
+        assert_eq!((label.as_str(), last - first + 1), ("December 2025", 31));
+        let (label, first, last) = month_bounds(Some("2025-02"), today).unwrap();
+        assert_eq!((label.as_str(), last - first + 1), ("February 2025", 28));
+        assert!(month_bounds(Some("2025-13"), today).is_err());
+        assert!(month_bounds(Some("lastish"), today).is_err());
+        assert!(month_bounds(Some("2025-01"), today).is_ok());
+        assert!(month_bounds(Some("2024-12"), today).is_err());
+    }
+
+    #[test]
+    fn best_run_counts_consecutive_days() {
+        assert_eq!(longest_run(&BTreeSet::new()), 0);
+        assert_eq!(longest_run(&[3, 4, 5, 7, 8].into_iter().collect()), 3);
+        assert_eq!(longest_run(&[1, 10].into_iter().collect()), 1);
+    }
+}
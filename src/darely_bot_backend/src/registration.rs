@@ -0,0 +1,243 @@
+use crate::accounts;
+use crate::outbox;
+use crate::repository::{self, UserRepo};
+use crate::state;
+use crate::types::{InviteCode, RegistrationPolicy, RegistrationRequest, StorablePrincipal, UserProfile, WaitlistEntry};
+use candid::Principal;
+use std::cell::RefCell;
+
+// --- Registration ---
+// /register is gated three ways: registration can be turned off canister-wide or per chat (a
+// chat's own setting wins, and a /register without a chat follows the canister's), the canister
+// can cap the number of registered users, and registration can be made invite-only. While
+// invite-only, a user either enters an admin-issued invite code or leaves a request that an admin
+// approves or denies; the outcome is sent to them as a DM. Whoever gets in while the cap is
+// reached joins a waitlist instead, and a timer admits waitlisted users in order as spots open
+// (e.g. after the cap is raised), with a DM. Every way in ends in `create_profile`. Both queues
+// hold each user once and are capped, and admins hear about new requests at most once per
+// ALERT_COOLDOWN_NANOS (the last alert time lives on the heap, like queue_alerts.rs).
+
+const INVITE_CODE_LEN: usize = 8;
+const MAX_PENDING_REQUESTS: u64 = 500;
+const MAX_WAITLIST_LEN: u64 = 5_000;
+const ALERT_COOLDOWN_NANOS: u64 = 60 * 60 * 1_000_000_000;
+
+thread_local! {
+    static LAST_REQUEST_ALERT_AT: RefCell<u64> = const { RefCell::new(0) };
+}
+
+fn policy() -> RegistrationPolicy {
+    state::get_config().registration.unwrap_or_default()
+}
+
+pub fn validate_policy(policy: &RegistrationPolicy) -> Result<(), String> {
+    if policy.max_users == Some(0) {
+        return Err("The user cap must be at least 1 (or null for no cap).".to_string());
+    }
+    Ok(())
+}
+
+fn is_open(chat_id: Option<&str>) -> bool {
+    chat_id.and_then(|chat_id| state::CHAT_CONFIGS.with(|c| c.borrow().get(&chat_id.to_string())))
+        .and_then(|config| config.registration_open)
+        .or(policy().open)
+        .unwrap_or(true)
+}
+
+// Spots left under the cap; None = no cap
+fn open_spots() -> Option<u64> {
+    let registered = repository::users().len();
+    policy().max_users.map(|max| max.saturating_sub(registered))
+}
+
+fn create_profile(user: Principal) {
+    repository::users().put(user, UserProfile::default());
+    state::REGISTRATION_REQUESTS.with(|r| r.borrow_mut().remove(&StorablePrincipal(user)));
+}
+
+// Registers `user` if the chat, the user cap and the invite rules allow it
+pub fn register(user: Principal, chat_id: Option<&str>, invite_code: Option<&str>) -> Result<String, String> {
+    if state::is_registered(user) {
+        return Err("You are already registered.".to_string());
+    }
+    if let Some(position) = waitlist_position(user) {
+        return Err(format!("You're already on the waitlist at position {}. You'll get a DM when a spot opens.", position));
+    }
+    if !is_open(chat_id) {
+        return Err(match chat_id {
+            Some(_) => "Registration is turned off in this chat. Try /register in another chat with Darely.",
+            None => "Registration is closed right now.",
+        }.to_string());
+    }
+    if policy().invite_only {
+        match invite_code.map(str::trim).filter(|code| !code.is_empty()) {
+            Some(code) => use_invite(code)?,
+            None => return request_approval(user, chat_id),
+        }
+    }
+    admit_or_waitlist(user, chat_id)
+}
+
+fn admit_or_waitlist(user: Principal, chat_id: Option<&str>) -> Result<String, String> {
+    if open_spots().is_none_or(|spots| spots > 0) {
+        create_profile(user);
+        return Ok(format!("Successfully registered! Welcome, Principal {}.", user));
+    }
+    let position = join_waitlist(user, chat_id)?;
+    Ok(format!("Darely is full right now, so you're on the waitlist at position {}. You'll get a DM as soon as a spot opens.", position))
+}
+
+fn request_approval(user: Principal, chat_id: Option<&str>) -> Result<String, String> {
+    let key = StorablePrincipal(user);
+    if state::REGISTRATION_REQUESTS.with(|r| r.borrow().contains_key(&key)) {
+        return Err("Darely is invite-only, and your registration request is still waiting for an admin. Have an invite code? Use /register <code>.".to_string());
+    }
+    let pending = state::REGISTRATION_REQUESTS.with(|r| r.borrow().len());
+    if pending >= MAX_PENDING_REQUESTS {
+        return Err("Darely is invite-only, and too many registration requests are waiting for an admin right now. Try again later, or use /register <code> with an invite code.".to_string());
+    }
+    let now = ic_cdk::api::time();
+    let request = RegistrationRequest {
+        user,
+        requested_at: now,
+        chat_id: chat_id.map(str::to_string),
+    };
+    state::REGISTRATION_REQUESTS.with(|r| r.borrow_mut().insert(key, request));
+    alert_request(user, pending + 1, now);
+    Ok("Darely is invite-only right now. Your registration request was sent to the admins, and you'll get a DM once it's decided. Have an invite code? Use /register <code>.".to_string())
+}
+
+// Requests that arrive within the cooldown are left for the next alert's count
+fn alert_request(user: Principal, pending: u64, now: u64) {
+    let due = LAST_REQUEST_ALERT_AT.with(|last| {
+        let mut last = last.borrow_mut();
+        let due = *last == 0 || now.saturating_sub(*last) >= ALERT_COOLDOWN_NANOS;
+        if due {
+            *last = now;
+        }
+        due
+    });
+    if due {
+        outbox::alert_admins(&format!(
+            "📝 {} asked to register ({} request(s) waiting). See list_registration_requests and decide with decide_registration.",
+            user, pending
+        ));
+    }
+}
+
+// --- Invite Codes ---
+
+pub async fn create_invite(admin: Principal, max_uses: Option<u32>) -> Result<String, String> {
+    if max_uses == Some(0) {
+        return Err("An invite needs at least 1 use (or null for unlimited).".to_string());
+    }
+    let code = accounts::random_code(INVITE_CODE_LEN).await?;
+    let invite = InviteCode { created_by: admin, created_at: ic_cdk::api::time(), max_uses, uses: 0 };
+    state::INVITE_CODES.with(|c| c.borrow_mut().insert(code.clone(), invite));
+    Ok(code)
+}
+
+fn use_invite(code: &str) -> Result<(), String> {
+    let code = code.to_uppercase();
+    let mut invite = state::INVITE_CODES.with(|c| c.borrow().get(&code))
+        .filter(|invite| invite.max_uses.is_none_or(|max| invite.uses < max))
+        .ok_or_else(|| "That invite code is invalid or has been used up.".to_string())?;
+    invite.uses += 1;
+    state::INVITE_CODES.with(|c| c.borrow_mut().insert(code, invite));
+    Ok(())
+}
+
+pub fn list_invites() -> Vec<(String, InviteCode)> {
+    state::INVITE_CODES.with(|c| c.borrow().iter().collect())
+}
+
+pub fn revoke_invite(code: &str) -> Result<(), String> {
+    state::INVITE_CODES.with(|c| c.borrow_mut().remove(&code.trim().to_uppercase()))
+        .map(|_| ())
+        .ok_or_else(|| format!("No invite code {}.", code))
+}
+
+// --- Approval ---
+
+pub fn pending_requests() -> Vec<RegistrationRequest> {
+    let mut requests: Vec<RegistrationRequest> = state::REGISTRATION_REQUESTS.with(|r| r.borrow().iter().map(|(_, request)| request).collect());
+    requests.sort_by_key(|request| request.requested_at);
+    requests
+}
+
+pub fn decide(user: Principal, approve: bool) -> Result<(), String> {
+    let key = StorablePrincipal(user);
+    if !state::REGISTRATION_REQUESTS.with(|r| r.borrow().contains_key(&key)) {
+        return Err(format!("{} has no pending registration request.", user));
+    }
+    if !approve {
+        state::REGISTRATION_REQUESTS.with(|r| r.borrow_mut().remove(&key));
+        outbox::queue(user, "Your Darely registration request was declined.".to_string(), None);
+        return Ok(());
+    }
+    let chat_id = state::REGISTRATION_REQUESTS.with(|r| r.borrow_mut().remove(&key)).and_then(|request| request.chat_id);
+    if state::is_registered(user) || waitlist_position(user).is_some() {
+        return Ok(());
+    }
+    let text = if open_spots().is_none_or(|spots| spots > 0) {
+        create_profile(user);
+        "🎉 Your Darely registration was approved! Try /dare to get started.".to_string()
+    } else {
+        match join_waitlist(user, chat_id.as_deref()) {
+            Ok(position) => format!("Your Darely registration was approved, but Darely is full right now. You're on the waitlist at position {}, and you'll get a DM as soon as a spot opens.", position),
+            Err(_) => "Your Darely registration was approved, but Darely and its waitlist are full right now. Try /register again later.".to_string(),
+        }
+    };
+    outbox::queue(user, text, None);
+    Ok(())
+}
+
+// --- Waitlist ---
+
+// Returns the user's position, keeping the one they already have
+fn join_waitlist(user: Principal, chat_id: Option<&str>) -> Result<u64, String> {
+    if let Some(position) = waitlist_position(user) {
+        return Ok(position);
+    }
+    let entry = WaitlistEntry { user, joined_at: ic_cdk::api::time(), chat_id: chat_id.map(str::to_string) };
+    state::WAITLIST.with(|w| {
+        let mut waitlist = w.borrow_mut();
+        if waitlist.len() >= MAX_WAITLIST_LEN {
+            return Err("Darely is full and so is its waitlist right now. Try /register again later.".to_string());
+        }
+        let seq = waitlist.last_key_value().map_or(0, |(seq, _)| seq + 1);
+        waitlist.insert(seq, entry);
+        Ok(waitlist.len())
+    })
+}
+
+// 1-based position, or None when the user isn't waiting
+pub fn waitlist_position(user: Principal) -> Option<u64> {
+    state::WAITLIST.with(|w| {
+        w.borrow().iter()
+            .position(|(_, entry)| entry.user == user)
+            .map(|index| index as u64 + 1)
+    })
+}
+
+pub fn waitlist() -> Vec<WaitlistEntry> {
+    state::WAITLIST.with(|w| w.borrow().iter().map(|(_, entry)| entry).collect())
+}
+
+// Timer entry point: admits waitlisted users in order while there are open spots. Returns how
+// many were admitted.
+pub fn admit_waitlisted() -> u32 {
+    let mut admitted = 0;
+    while let Some((seq, entry)) = state::WAITLIST.with(|w| w.borrow().first_key_value()) {
+        if !state::is_registered(entry.user) {
+            if open_spots().is_some_and(|spots| spots == 0) {
+                break;
+            }
+            create_profile(entry.user);
+            outbox::queue(entry.user, "🎉 A spot opened up and you're in! Welcome to Darely. Try /dare to get started.".to_string(), None);
+            admitted += 1;
+        }
+        state::WAITLIST.with(|w| w.borrow_mut().remove(&seq));
+    }
+    admitted
+}
@@ -0,0 +1,58 @@
+use crate::correlation;
+use crate::state;
+use crate::types::{CachedGroupMetadata, GroupMetadata};
+use candid::Principal;
+use std::collections::BTreeSet;
+
+// --- Configuration ---
+// Expected registry interface: group_metadata : (text) -> (opt GroupMetadata) query;
+const REGISTRY_METHOD: &str = "group_metadata";
+const METADATA_TTL_NANOS: u64 = 6 * 60 * 60 * 1_000_000_000; // Refresh cached metadata every 6 hours
+
+fn registry_canister() -> Result<Principal, String> {
+    state::get_config().registry_canister
+        .ok_or_else(|| "Community registry canister is not configured.".to_string())
+}
+
+// --- Fetching ---
+
+// Fetches metadata for one chat from the registry and caches it
+pub async fn sync_group(chat_id: String) -> Result<GroupMetadata, String> {
+    let registry = registry_canister()?;
+    let (metadata,): (Option<GroupMetadata>,) = ic_cdk::call(registry, REGISTRY_METHOD, (chat_id.clone(),))
+        .await
+        .map_err(|(code, message)| format!("Registry call failed: {:?} {}", code, message))?;
+    let metadata = metadata.ok_or_else(|| format!("Chat {} is not known to the registry.", chat_id))?;
+
+    let cached = CachedGroupMetadata { metadata: metadata.clone(), fetched_at: ic_cdk::api::time() };
+    state::GROUP_METADATA.with(|m| m.borrow_mut().insert(chat_id, cached));
+    Ok(metadata)
+}
+
+// Chats whose metadata is missing or older than the TTL (configured chats plus everything cached)
+fn stale_chats(now: u64) -> Vec<String> {
+    let mut chats: BTreeSet<String> = state::CHAT_CONFIGS.with(|c| c.borrow().iter().map(|(chat_id, _)| chat_id).collect());
+    state::GROUP_METADATA.with(|m| chats.extend(m.borrow().iter().map(|(chat_id, _)| chat_id)));
+    chats.into_iter()
+        .filter(|chat_id| {
+            state::GROUP_METADATA.with(|m| m.borrow().get(chat_id))
+                .is_none_or(|cached| now.saturating_sub(cached.fetched_at) > METADATA_TTL_NANOS)
+        })
+        .collect()
+}
+
+// Timer job: refreshes stale entries one call at a time
+pub async fn refresh_stale() {
+    if registry_canister().is_err() {
+        return;
+    }
+    for chat_id in stale_chats(ic_cdk::api::time()) {
+        if let Err(e) = sync_group(chat_id.clone()).await {
+            correlation::log!("Registry refresh for chat {} failed: {}", chat_id, e);
+        }
+    }
+}
+
+pub fn cached(chat_id: &str) -> Option<CachedGroupMetadata> {
+    state::GROUP_METADATA.with(|m| m.borrow().get(&chat_id.to_string()))
+}
@@ -0,0 +1,612 @@
+use crate::correlation;
+use crate::incidents;
+use crate::outbox;
+use crate::repository::{self, DareRepo, UserRepo};
+use crate::state;
+use crate::types::{Dare, HistoryEntry, JournalEntry, ReadReplica, ReplicatedMap, ReplicatedWrite, ReplicationState, SnapshotCursor, StorablePrincipal, Submission, UserProfile};
+use candid::Principal;
+use ic_stable_structures::{BTreeMap as StableBTreeMap, Storable};
+use serde_bytes::ByteBuf;
+use std::borrow::Cow;
+use std::cell::Cell;
+
+// --- Warm Standby ---
+// The primary mirrors its core state (profiles, submissions, history and the dare repository)
+// to a standby canister running the same code. Once a standby is set, every write to those
+// collections is journaled by key, and a timer pushes the keys' current values to the
+// standby's apply_replication in batches. A new standby first gets a snapshot, one batch of
+// keys at a time in key order; writes made meanwhile sit in the journal and follow it.
+// Journal entries are dropped once the standby has applied them. After MAX_FAILURES failed
+// pushes in a row the alert recipients are told; pushes keep retrying. A standby or read replica
+// more than MAX_JOURNAL_BACKLOG writes behind starts over with a new snapshot, so the journal
+// stays bounded while one is unreachable. Every fresh start bumps the target's epoch, and a push
+// that was in flight from an older epoch is discarded when it returns.
+//
+// The standby only accepts pushes from the primary it was given and keeps its own timers idle,
+// so it doesn't lapse streaks or send messages of its own. promote_standby turns it into a
+// primary: it stops accepting pushes and its timers start working. Config, chat settings and
+// queues (outbox, review queue) are not mirrored; admins set them up on the standby directly.
+//
+// Read replicas are fed the same way: each registered replica is a canister set up like a
+// standby, with its own snapshot and journal position, and the journal keeps entries until every
+// target has them (or until one falls too far behind, see above). Each tick pushes to every
+// target at once, so a slow one doesn't hold up the others. They exist to take leaderboard and
+// analytics queries off the primary: get_status lists the endpoints of replicas that are caught up
+// and healthy, and frontends spread their queries over them. Replicas are never promoted; that's
+// the standby's job.
+
+const MAX_BATCH_WRITES: usize = 100;
+const MAX_BATCH_BYTES: usize = 1_000_000;
+const MAX_FAILURES: u32 = 5;
+const MAX_JOURNAL_BACKLOG: u64 = 200_000;
+const APPLY_METHOD: &str = "apply_replication";
+const SNAPSHOT_ORDER: [ReplicatedMap; 4] = [ReplicatedMap::Profiles, ReplicatedMap::Submissions, ReplicatedMap::History, ReplicatedMap::Dares];
+
+thread_local! {
+    // Pushes in flight; the next tick waits for all of them
+    static IN_FLIGHT: Cell<usize> = const { Cell::new(0) };
+}
+
+fn replication() -> ReplicationState {
+    state::REPLICATION.with(|r| r.borrow().get().clone())
+}
+
+fn save(replication: ReplicationState) {
+    state::REPLICATION.with(|r| r.borrow_mut().set(replication)).expect("Failed to save replication state");
+}
+
+fn read_replicas() -> Vec<ReadReplica> {
+    state::READ_REPLICAS.with(|r| r.borrow().iter().map(|(_, replica)| replica).collect())
+}
+
+fn save_replica(replica: ReadReplica) {
+    state::READ_REPLICAS.with(|r| r.borrow_mut().insert(StorablePrincipal(replica.canister), replica));
+}
+
+// Whether writes need journaling: someone is being pushed to
+fn replicating() -> bool {
+    state::REPLICATION.with(|r| r.borrow().get().standby.is_some()) || state::READ_REPLICAS.with(|r| !r.borrow().is_empty())
+}
+
+// The sequence number the next journaled write gets. Numbers keep counting up after the journal
+// has been drained, so they never fall behind a target's position.
+fn journal_end() -> u64 {
+    if let Some((seq, _)) = state::REPLICATION_JOURNAL.with(|j| j.borrow().last_key_value()) {
+        return seq + 1;
+    }
+    let standby = replication().next_seq.unwrap_or(0);
+    read_replicas().iter().map(|replica| replica.next_seq).fold(standby, u64::max)
+}
+
+// Drops the journal entries every target has got, after restarting targets that fell too far
+// behind
+fn prune_journal() {
+    let end = journal_end();
+    for target in targets() {
+        let Some(mut progress) = target.progress() else { continue };
+        if end - progress.next_seq > MAX_JOURNAL_BACKLOG {
+            progress.start_over(end);
+            target.save(progress);
+            outbox::alert_admins(&format!(
+                "⚠️ {} fell more than {} writes behind, so it starts over with a new snapshot.", target.describe(), MAX_JOURNAL_BACKLOG
+            ));
+        }
+    }
+    let replication = replication();
+    let positions = replication.standby.map(|_| replication.next_seq.unwrap_or(0)).into_iter()
+        .chain(read_replicas().into_iter().map(|replica| replica.next_seq));
+    let keep_from = positions.min().unwrap_or(u64::MAX);
+    state::REPLICATION_JOURNAL.with(|j| {
+        let mut journal = j.borrow_mut();
+        while let Some((seq, _)) = journal.first_key_value().filter(|(seq, _)| *seq < keep_from) {
+            journal.remove(&seq);
+        }
+    });
+}
+
+pub fn is_standby() -> bool {
+    state::REPLICATION.with(|r| r.borrow().get().primary.is_some())
+}
+
+// Called after every write to a replicated collection
+pub fn note<K: Storable>(map: ReplicatedMap, key: &K) {
+    if !replicating() {
+        return;
+    }
+    let seq = journal_end();
+    state::REPLICATION_JOURNAL.with(|j| {
+        j.borrow_mut().insert(seq, JournalEntry { map, key: ByteBuf::from(key.to_bytes().into_owned()) });
+    });
+}
+
+// --- Primary ---
+
+pub fn set_standby(standby: Option<Principal>) -> Result<String, String> {
+    let mut replication = replication();
+    if replication.primary.is_some() {
+        return Err("This canister is a standby; promote it before giving it a standby of its own.".to_string());
+    }
+    if standby == Some(ic_cdk::api::id()) {
+        return Err("A canister can't be its own standby.".to_string());
+    }
+    if standby.is_some_and(|standby| state::READ_REPLICAS.with(|r| r.borrow().contains_key(&StorablePrincipal(standby)))) {
+        return Err("That canister is registered as a read replica.".to_string());
+    }
+    replication.next_seq = Some(journal_end());
+    replication.standby = standby;
+    replication.snapshot = standby.map(|_| SnapshotCursor { map: SNAPSHOT_ORDER[0], after: None });
+    // Setting the same standby again starts it over too, so a push in flight must not land
+    replication.epoch = Some(replication.epoch.unwrap_or(0) + 1);
+    replication.failures = 0;
+    replication.last_error = None;
+    save(replication);
+    prune_journal();
+    Ok(match standby {
+        Some(standby) => format!("Replicating to {}, starting with a full snapshot.", standby),
+        None => "Replication stopped.".to_string(),
+    })
+}
+
+fn entries<K: Storable + Ord + Clone, V: Storable>(
+    map: &StableBTreeMap<K, V, state::Memory>,
+    after: Option<&ByteBuf>,
+    limit: usize,
+) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let values = match after {
+        Some(after) => map.range((std::ops::Bound::Excluded(K::from_bytes(Cow::Borrowed(after))), std::ops::Bound::Unbounded)),
+        None => map.range(..),
+    };
+    values.take(limit).map(|(key, value)| (key.to_bytes().into_owned(), value.to_bytes().into_owned())).collect()
+}
+
+// Up to `limit` keys of `map` after `after`, with their values
+fn snapshot_chunk(map: ReplicatedMap, after: Option<&ByteBuf>, limit: usize) -> Vec<(Vec<u8>, Vec<u8>)> {
+    match map {
+        ReplicatedMap::Profiles => state::USER_PROFILES.with(|p| entries(&p.borrow(), after, limit)),
+        ReplicatedMap::Submissions => state::SUBMISSIONS.with(|s| entries(&s.borrow(), after, limit)),
+        ReplicatedMap::History => state::HISTORY.with(|h| entries(&h.borrow(), after, limit)),
+        ReplicatedMap::Dares => {
+            let start = after.map_or(0, |after| u64::from_bytes(Cow::Borrowed(after)) + 1);
+            let dares = repository::dares();
+            (start..dares.len().min(start + limit as u64))
+                .filter_map(|id| dares.get(id).map(|dare| (id.to_bytes().into_owned(), dare.to_bytes().into_owned())))
+                .collect()
+        }
+    }
+}
+
+// The key's value as of now; None once removed
+fn current(map: ReplicatedMap, key: &[u8]) -> Option<Vec<u8>> {
+    let key = Cow::Borrowed(key);
+    match map {
+        ReplicatedMap::Profiles => state::USER_PROFILES.with(|p| p.borrow().get(&StorablePrincipal::from_bytes(key))).map(|v| v.to_bytes().into_owned()),
+        ReplicatedMap::Submissions => state::SUBMISSIONS.with(|s| s.borrow().get(&u64::from_bytes(key))).map(|v| v.to_bytes().into_owned()),
+        ReplicatedMap::History => state::HISTORY.with(|h| h.borrow().get(&u64::from_bytes(key))).map(|v| v.to_bytes().into_owned()),
+        ReplicatedMap::Dares => repository::dares().get(u64::from_bytes(key)).map(|v| v.to_bytes().into_owned()),
+    }
+}
+
+fn write(map: ReplicatedMap, key: Vec<u8>, value: Option<Vec<u8>>) -> ReplicatedWrite {
+    ReplicatedWrite { map, key: ByteBuf::from(key), value: value.map(ByteBuf::from) }
+}
+
+fn size(write: &ReplicatedWrite) -> usize {
+    write.key.len() + write.value.as_ref().map_or(0, |value| value.len())
+}
+
+// Where the snapshot continues after a chunk of `map`: after its last key when the chunk was
+// full, otherwise at the start of the next map (None once every map is copied)
+fn next_cursor(map: ReplicatedMap, last_key_of_full_chunk: Option<Vec<u8>>) -> Option<SnapshotCursor> {
+    match last_key_of_full_chunk {
+        Some(key) => Some(SnapshotCursor { map, after: Some(ByteBuf::from(key)) }),
+        None => SNAPSHOT_ORDER.iter().skip_while(|next| **next != map).nth(1).map(|next| SnapshotCursor { map: *next, after: None }),
+    }
+}
+
+enum Batch {
+    // The snapshot chunk and where the cursor goes once it's applied
+    Snapshot(Vec<ReplicatedWrite>, Option<SnapshotCursor>),
+    // Journal entries up to and including this sequence number
+    Journal(Vec<ReplicatedWrite>, u64),
+}
+
+// Where a push target stands, copied out of and back into its stored record
+struct Progress {
+    snapshot: Option<SnapshotCursor>,
+    next_seq: u64,
+    batches_pushed: u64,
+    last_push_at: Option<u64>,
+    failures: u32,
+    last_error: Option<String>,
+    epoch: u64,
+}
+
+impl Progress {
+    // A new snapshot, with the journal from `end` on
+    fn start_over(&mut self, end: u64) {
+        self.snapshot = Some(SnapshotCursor { map: SNAPSHOT_ORDER[0], after: None });
+        self.next_seq = end;
+        self.epoch += 1;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Target {
+    Standby(Principal),
+    ReadReplica(Principal),
+}
+
+impl Target {
+    fn canister(self) -> Principal {
+        match self {
+            Target::Standby(canister) | Target::ReadReplica(canister) => canister,
+        }
+    }
+
+    fn describe(self) -> String {
+        match self {
+            Target::Standby(canister) => format!("the standby {}", canister),
+            Target::ReadReplica(canister) => format!("the read replica {}", canister),
+        }
+    }
+
+    // None once the target has been changed or unregistered
+    fn progress(self) -> Option<Progress> {
+        match self {
+            Target::Standby(canister) => {
+                let replication = replication();
+                (replication.standby == Some(canister)).then(|| Progress {
+                    snapshot: replication.snapshot,
+                    next_seq: replication.next_seq.unwrap_or(0),
+                    batches_pushed: replication.batches_pushed,
+                    last_push_at: replication.last_push_at,
+                    failures: replication.failures,
+                    last_error: replication.last_error,
+                    epoch: replication.epoch.unwrap_or(0),
+                })
+            }
+            Target::ReadReplica(canister) => state::READ_REPLICAS.with(|r| r.borrow().get(&StorablePrincipal(canister))).map(|replica| Progress {
+                snapshot: replica.snapshot,
+                next_seq: replica.next_seq,
+                batches_pushed: replica.batches_pushed,
+                last_push_at: replica.last_push_at,
+                failures: replica.failures,
+                last_error: replica.last_error,
+                epoch: replica.epoch.unwrap_or(0),
+            }),
+        }
+    }
+
+    fn save(self, progress: Progress) {
+        match self {
+            Target::Standby(_) => save(ReplicationState {
+                snapshot: progress.snapshot,
+                next_seq: Some(progress.next_seq),
+                batches_pushed: progress.batches_pushed,
+                last_push_at: progress.last_push_at,
+                failures: progress.failures,
+                last_error: progress.last_error,
+                epoch: Some(progress.epoch),
+                ..replication()
+            }),
+            Target::ReadReplica(canister) => {
+                if let Some(replica) = state::READ_REPLICAS.with(|r| r.borrow().get(&StorablePrincipal(canister))) {
+                    save_replica(ReadReplica {
+                        snapshot: progress.snapshot,
+                        next_seq: progress.next_seq,
+                        batches_pushed: progress.batches_pushed,
+                        last_push_at: progress.last_push_at,
+                        failures: progress.failures,
+                        last_error: progress.last_error,
+                        epoch: Some(progress.epoch),
+                        ..replica
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn targets() -> Vec<Target> {
+    replication().standby.map(Target::Standby).into_iter()
+        .chain(read_replicas().into_iter().map(|replica| Target::ReadReplica(replica.canister)))
+        .collect()
+}
+
+fn next_batch(progress: &Progress) -> Option<Batch> {
+    if let Some(cursor) = &progress.snapshot {
+        let chunk = snapshot_chunk(cursor.map, cursor.after.as_ref(), MAX_BATCH_WRITES);
+        let last_key = chunk.last().filter(|_| chunk.len() == MAX_BATCH_WRITES).map(|(key, _)| key.clone());
+        let next = next_cursor(cursor.map, last_key);
+        let writes = chunk.into_iter().map(|(key, value)| write(cursor.map, key, Some(value))).collect();
+        return Some(Batch::Snapshot(writes, next));
+    }
+    let mut writes: Vec<ReplicatedWrite> = Vec::new();
+    let mut bytes = 0;
+    let mut last_seq = None;
+    state::REPLICATION_JOURNAL.with(|j| {
+        for (seq, entry) in j.borrow().range(progress.next_seq..).take(MAX_BATCH_WRITES) {
+            let next = write(entry.map, entry.key.to_vec(), current(entry.map, &entry.key));
+            if bytes + size(&next) > MAX_BATCH_BYTES && !writes.is_empty() {
+                break;
+            }
+            bytes += size(&next);
+            writes.push(next);
+            last_seq = Some(seq);
+        }
+    });
+    last_seq.map(|seq| Batch::Journal(writes, seq))
+}
+
+// Called by the replication timer: one batch to each target that is behind
+pub fn tick() {
+    if IN_FLIGHT.get() > 0 {
+        return;
+    }
+    let batches: Vec<(Target, u64, Batch)> = targets().into_iter()
+        .filter_map(|target| {
+            let progress = target.progress()?;
+            Some((target, progress.epoch, next_batch(&progress)?))
+        })
+        .collect();
+    if batches.is_empty() {
+        return;
+    }
+    IN_FLIGHT.set(batches.len());
+    for (target, epoch, batch) in batches {
+        ic_cdk::spawn(correlation::traced(async move {
+            push(target, epoch, batch).await;
+            IN_FLIGHT.set(IN_FLIGHT.get() - 1);
+            if IN_FLIGHT.get() == 0 {
+                prune_journal();
+            }
+        }));
+    }
+}
+
+async fn push(target: Target, epoch: u64, batch: Batch) {
+    let writes = match &batch {
+        Batch::Snapshot(writes, _) | Batch::Journal(writes, _) => writes.clone(),
+    };
+    let result: Result<(Result<u64, String>,), _> = ic_cdk::call(target.canister(), APPLY_METHOD, (writes,)).await;
+    let error = match result {
+        Ok((Ok(_),)) => None,
+        Ok((Err(e),)) => Some(e),
+        Err((code, message)) => Some(format!("{:?}: {}", code, message)),
+    };
+    // The target may have been changed, or started over, during the call
+    let Some(mut progress) = target.progress().filter(|progress| progress.epoch == epoch) else { return };
+    if let Some(error) = error {
+        correlation::log!("Replication push to {} failed: {}", target.describe(), error);
+        progress.failures += 1;
+        if progress.failures == MAX_FAILURES {
+            outbox::alert_admins(&format!(
+                "⚠️ Replication to {} failed {} times in a row. Last error: {}", target.describe(), MAX_FAILURES, error
+            ));
+        }
+        progress.last_error = Some(error);
+        target.save(progress);
+        return;
+    }
+    match batch {
+        Batch::Snapshot(_, next) => {
+            if next.is_none() {
+                correlation::log!("Replication snapshot to {} finished.", target.describe());
+            }
+            progress.snapshot = next;
+        }
+        Batch::Journal(_, last_seq) => progress.next_seq = last_seq + 1,
+    }
+    progress.batches_pushed += 1;
+    progress.last_push_at = Some(ic_cdk::api::time());
+    progress.failures = 0;
+    progress.last_error = None;
+    target.save(progress);
+}
+
+// --- Read Replicas ---
+
+// `endpoint` is what frontends are told to query; by default the canister's icp0.io URL
+pub fn register_replica(canister: Principal, endpoint: Option<String>) -> Result<String, String> {
+    if replication().primary.is_some() {
+        return Err("This canister is a standby; only the primary feeds read replicas.".to_string());
+    }
+    if canister == ic_cdk::api::id() {
+        return Err("A canister can't be its own read replica.".to_string());
+    }
+    if replication().standby == Some(canister) {
+        return Err("That canister is the standby.".to_string());
+    }
+    let endpoint = endpoint.map(|endpoint| endpoint.trim().to_string()).filter(|endpoint| !endpoint.is_empty())
+        .unwrap_or_else(|| format!("https://{}.icp0.io", canister));
+    let next_seq = journal_end();
+    let existing = state::READ_REPLICAS.with(|r| r.borrow().get(&StorablePrincipal(canister)));
+    let replica = match existing {
+        // Re-registering only changes the endpoint
+        Some(replica) => ReadReplica { endpoint: endpoint.clone(), ..replica },
+        None => ReadReplica {
+            canister,
+            endpoint: endpoint.clone(),
+            registered_at: ic_cdk::api::time(),
+            snapshot: Some(SnapshotCursor { map: SNAPSHOT_ORDER[0], after: None }),
+            next_seq,
+            batches_pushed: 0,
+            last_push_at: None,
+            failures: 0,
+            last_error: None,
+            epoch: None,
+        },
+    };
+    save_replica(replica);
+    Ok(format!("Read replica {} registered at {}. It's advertised once its snapshot is done.", canister, endpoint))
+}
+
+pub fn unregister_replica(canister: Principal) -> Result<String, String> {
+    state::READ_REPLICAS.with(|r| r.borrow_mut().remove(&StorablePrincipal(canister)))
+        .ok_or_else(|| format!("{} isn't a registered read replica.", canister))?;
+    prune_journal();
+    Ok(format!("Read replica {} removed; it no longer gets pushes.", canister))
+}
+
+// A replica is worth querying once its snapshot is done and while pushes to it succeed
+fn serves_queries(replica: &ReadReplica) -> bool {
+    replica.snapshot.is_none() && replica.failures < MAX_FAILURES
+}
+
+// Endpoints frontends can send heavy queries to
+pub fn query_endpoints() -> Vec<String> {
+    read_replicas().into_iter().filter(serves_queries).map(|replica| replica.endpoint).collect()
+}
+
+// --- Standby ---
+
+pub fn set_primary(primary: Option<Principal>) -> Result<String, String> {
+    let mut replication = replication();
+    if (replication.standby.is_some() || state::READ_REPLICAS.with(|r| !r.borrow().is_empty())) && primary.is_some() {
+        return Err("This canister replicates to a standby or read replicas of its own; clear those first.".to_string());
+    }
+    replication.primary = primary;
+    save(replication);
+    Ok(match primary {
+        Some(primary) => format!("This canister now mirrors {}. Its timers stay idle unless it's promoted.", primary),
+        None => "This canister no longer accepts replication.".to_string(),
+    })
+}
+
+fn apply_write(write: ReplicatedWrite) -> Result<(), String> {
+    let key = Cow::Borrowed(write.key.as_slice());
+    match (write.map, write.value) {
+        (ReplicatedMap::Profiles, Some(value)) => {
+            repository::users().put(StorablePrincipal::from_bytes(key).0, UserProfile::from_bytes(Cow::Owned(value.into_vec())));
+        }
+        (ReplicatedMap::Profiles, None) => {
+            repository::users().remove(StorablePrincipal::from_bytes(key).0);
+        }
+        (ReplicatedMap::Submissions, value) => state::SUBMISSIONS.with(|s| {
+            let mut submissions = s.borrow_mut();
+            match value {
+                Some(value) => submissions.insert(u64::from_bytes(key), Submission::from_bytes(Cow::Owned(value.into_vec()))),
+                None => submissions.remove(&u64::from_bytes(key)),
+            };
+        }),
+        (ReplicatedMap::History, value) => state::HISTORY.with(|h| {
+            let mut history = h.borrow_mut();
+            match value {
+                Some(value) => history.insert(u64::from_bytes(key), HistoryEntry::from_bytes(Cow::Owned(value.into_vec()))),
+                None => history.remove(&u64::from_bytes(key)),
+            };
+        }),
+        (ReplicatedMap::Dares, Some(value)) => {
+            let dare = Dare::from_bytes(Cow::Owned(value.into_vec()));
+            let dares = repository::dares();
+            if dare.id < dares.len() {
+                dares.set(&dare)?;
+            } else if dare.id == dares.len() {
+                dares.push(dare)?;
+            } else {
+                return Err(format!("Dare #{} arrived before dare #{}.", dare.id, dares.len()));
+            }
+        }
+        // Dares are never removed
+        (ReplicatedMap::Dares, None) => {}
+    }
+    Ok(())
+}
+
+// Called by the primary. Returns how many writes were applied.
+pub fn apply(caller: Principal, writes: Vec<ReplicatedWrite>) -> Result<u64, String> {
+    if replication().primary != Some(caller) {
+        return Err("Only this standby's primary can push state to it.".to_string());
+    }
+    let count = writes.len() as u64;
+    for write in writes {
+        // Out-of-order writes mean the primary's journal is broken, so they're incidents
+        apply_write(write).inspect_err(|error| incidents::record(error))?;
+    }
+    Ok(count)
+}
+
+pub fn promote() -> Result<String, String> {
+    let mut replication = replication();
+    let primary = replication.primary.take().ok_or_else(|| "This canister isn't a standby.".to_string())?;
+    replication.promoted_at = Some(ic_cdk::api::time());
+    save(replication);
+    outbox::alert_admins(&format!("🚨 The standby {} was promoted and now acts as the primary.", ic_cdk::api::id()));
+    Ok(format!(
+        "Promoted. This canister no longer accepts pushes from {} and its timers are running. Point the bot at it, \
+        and if the old primary is still up, stop its replication with set_replication_standby(null).",
+        primary
+    ))
+}
+
+pub fn status() -> String {
+    let replication = replication();
+    let end = journal_end();
+    let mut lines = Vec::new();
+    if let Some(primary) = replication.primary {
+        lines.push(format!("Standby of {}.", primary));
+    }
+    if let Some(standby) = replication.standby {
+        let waiting = end - replication.next_seq.unwrap_or(0);
+        lines.push(format!("Replicating to {}: {} batches pushed, {} writes waiting.", standby, replication.batches_pushed, waiting));
+        if let Some(cursor) = &replication.snapshot {
+            lines.push(format!("Snapshot in progress ({:?}).", cursor.map));
+        }
+        if let Some(error) = &replication.last_error {
+            lines.push(format!("{} failed pushes in a row. Last error: {}", replication.failures, error));
+        }
+    }
+    for replica in read_replicas() {
+        let state = if replica.snapshot.is_some() { "snapshot in progress" } else if serves_queries(&replica) { "serving queries" } else { "failing" };
+        lines.push(format!(
+            "Read replica {} ({}): {}, {} batches pushed, {} writes waiting.",
+            replica.canister, replica.endpoint, state, replica.batches_pushed, end - replica.next_seq
+        ));
+        if let Some(error) = &replica.last_error {
+            lines.push(format!("  {} failed pushes in a row. Last error: {}", replica.failures, error));
+        }
+    }
+    if let Some(promoted_at) = replication.promoted_at {
+        lines.push(format!("Promoted from standby at {} (ns).", promoted_at));
+    }
+    if lines.is_empty() {
+        return "Replication is off.".to_string();
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_walks_every_map_in_order() {
+        let cursor = next_cursor(ReplicatedMap::Profiles, Some(vec![7])).unwrap();
+        assert_eq!((cursor.map, cursor.after.map(|key| key.into_vec())), (ReplicatedMap::Profiles, Some(vec![7])));
+        let cursor = next_cursor(ReplicatedMap::Profiles, None).unwrap();
+        assert_eq!((cursor.map, cursor.after), (ReplicatedMap::Submissions, None));
+        assert_eq!(next_cursor(ReplicatedMap::History, None).map(|cursor| cursor.map), Some(ReplicatedMap::Dares));
+        assert!(next_cursor(ReplicatedMap::Dares, None).is_none());
+    }
+
+    #[test]
+    fn replicas_serve_queries_once_caught_up_and_healthy() {
+        let mut replica = ReadReplica {
+            canister: Principal::from_slice(&[1]),
+            endpoint: "https://replica.example".to_string(),
+            registered_at: 0,
+            snapshot: Some(SnapshotCursor { map: ReplicatedMap::Profiles, after: None }),
+            next_seq: 0,
+            batches_pushed: 0,
+            last_push_at: None,
+            failures: 0,
+            last_error: None,
+            epoch: None,
+        };
+        assert!(!serves_queries(&replica));
+        replica.snapshot = None;
+        assert!(serves_queries(&replica));
+        replica.failures = MAX_FAILURES;
+        assert!(!serves_queries(&replica));
+    }
+}
@@ -0,0 +1,433 @@
+use crate::escalation;
+use crate::incidents;
+use crate::membership;
+use crate::quarantine;
+use crate::ranking;
+use crate::replication;
+use crate::scheduler;
+use crate::state;
+use crate::telemetry;
+use crate::types::{Dare, ReplicatedMap, RewardTask, StorablePrincipal, UserProfile};
+use candid::Principal;
+use ic_stable_structures::Storable;
+use std::ops::Bound;
+
+// --- Repositories ---
+// Profiles, dares and reward tasks are reached through these traits instead of the stable maps,
+// so command handlers don't depend on ic_stable_structures (borrowing, keys, Storable) and their
+// logic can run against the in-memory implementations in unit tests. The stable-structures
+// implementations are what `users()`, `dares()` and `tasks()` return in the canister; they also
+// keep the derived state in step (the registration filter on reads and writes, the streak, expiry
+// and escalation indexes and the replication journal on writes). Scans take a visitor that
+// returns false to stop early, so large maps aren't collected into memory, and skip records that
+// don't decode, which are quarantined afterwards (see quarantine.rs).
+
+pub trait UserRepo {
+    fn get(&self, user: Principal) -> Option<UserProfile>;
+    fn contains(&self, user: Principal) -> bool;
+    fn len(&self) -> u64;
+    // Unconditional write; handlers go through put_if_unchanged (or state::update_profile)
+    fn put(&self, user: Principal, profile: UserProfile);
+    // Deletes the profile (demo data only; real accounts are never deleted)
+    fn remove(&self, user: Principal) -> Option<UserProfile>;
+    // Profiles in principal order, starting after `after`
+    fn scan(&self, after: Option<Principal>, visit: &mut dyn FnMut(Principal, UserProfile) -> bool);
+    // Registered principals in order, starting after `after`, without decoding their profiles
+    fn scan_keys(&self, after: Option<Principal>, visit: &mut dyn FnMut(Principal) -> bool);
+
+    // Writes the profile with a bumped version unless the stored one no longer has `expected_version`
+    fn put_if_unchanged(&self, user: Principal, mut profile: UserProfile, expected_version: u64) -> bool {
+        let current = self.get(user).map_or(0, |stored| state::profile_version(&stored));
+        if current != expected_version {
+            return false;
+        }
+        profile.version = Some(expected_version + 1);
+        self.put(user, profile);
+        true
+    }
+}
+
+pub trait DareRepo {
+    fn get(&self, id: u64) -> Option<Dare>;
+    fn len(&self) -> u64;
+    // Appends the dare under the next id, which it returns
+    fn push(&self, dare: Dare) -> Result<u64, String>;
+    // Overwrites the dare stored under `dare.id`
+    fn set(&self, dare: &Dare) -> Result<(), String>;
+    // Dares in id order
+    fn scan(&self, visit: &mut dyn FnMut(Dare) -> bool);
+}
+
+pub trait TaskRepo {
+    fn get(&self, id: u64) -> Option<RewardTask>;
+    fn next_id(&self) -> u64;
+    fn put(&self, task: RewardTask);
+    fn remove(&self, id: u64) -> Option<RewardTask>;
+    // Tasks in id order
+    fn scan(&self, visit: &mut dyn FnMut(RewardTask) -> bool);
+}
+
+// --- Stable Structures ---
+
+pub struct StableUsers;
+pub struct StableDares;
+pub struct StableTasks;
+
+pub fn users() -> StableUsers {
+    StableUsers
+}
+
+pub fn dares() -> StableDares {
+    StableDares
+}
+
+pub fn tasks() -> StableTasks {
+    StableTasks
+}
+
+impl UserRepo for StableUsers {
+    // Unregistered users are turned away by the filter without a stable memory read
+    fn get(&self, user: Principal) -> Option<UserProfile> {
+        if !membership::may_be_registered(user) {
+            return None;
+        }
+        state::USER_PROFILES.with(|p| p.borrow().get(&StorablePrincipal(user)))
+    }
+
+    fn contains(&self, user: Principal) -> bool {
+        membership::may_be_registered(user) && state::USER_PROFILES.with(|p| p.borrow().contains_key(&StorablePrincipal(user)))
+    }
+
+    fn len(&self) -> u64 {
+        state::USER_PROFILES.with(|p| p.borrow().len())
+    }
+
+    fn put(&self, user: Principal, profile: UserProfile) {
+        let after = (profile.streak, profile.streak_reached_at);
+        // Sampled here rather than in to_bytes, which also runs for replication and index rebuilds
+        telemetry::record_profile_size(profile.to_bytes().len());
+        let clock_after = scheduler::expiry_clock(&profile);
+        let deadline_after = escalation::deadline(&profile);
+        let stored = state::USER_PROFILES.with(|p| p.borrow_mut().insert(StorablePrincipal(user), profile));
+        let before = stored.as_ref().map(|stored| (stored.streak, stored.streak_reached_at));
+        ranking::reindex(user, before, after);
+        scheduler::reindex(user, stored.as_ref().and_then(scheduler::expiry_clock), clock_after);
+        escalation::reindex(user, stored.as_ref().and_then(escalation::deadline), deadline_after);
+        replication::note(ReplicatedMap::Profiles, &StorablePrincipal(user));
+        // After the map is released: a full filter is rebuilt from the profiles
+        membership::insert(user);
+    }
+
+    // The membership filter keeps the user until its next rebuild, which only costs a map read
+    fn remove(&self, user: Principal) -> Option<UserProfile> {
+        let removed = state::USER_PROFILES.with(|p| p.borrow_mut().remove(&StorablePrincipal(user)))?;
+        ranking::unindex(user, (removed.streak, removed.streak_reached_at));
+        scheduler::reindex(user, scheduler::expiry_clock(&removed), None);
+        escalation::reindex(user, escalation::deadline(&removed), None);
+        replication::note(ReplicatedMap::Profiles, &StorablePrincipal(user));
+        Some(removed)
+    }
+
+    fn scan(&self, after: Option<Principal>, visit: &mut dyn FnMut(Principal, UserProfile) -> bool) {
+        let after = after.map(StorablePrincipal);
+        let unreadable = state::USER_PROFILES.with(|p| {
+            // Held as a typed scan would, so visitors can't write meanwhile
+            let _profiles = p.borrow();
+            quarantine::scan_map(state::USER_PROFILES_MEM_ID, after.as_ref(), &mut |user: StorablePrincipal, profile| visit(user.0, profile))
+        });
+        quarantine::isolate("user_profiles", unreadable);
+    }
+
+    fn scan_keys(&self, after: Option<Principal>, visit: &mut dyn FnMut(Principal) -> bool) {
+        let lower = after.map_or(Bound::Unbounded, |user| Bound::Excluded(StorablePrincipal(user)));
+        state::USER_PROFILES.with(|p| {
+            for user in p.borrow().keys_range((lower, Bound::Unbounded)) {
+                if !visit(user.0) {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+impl DareRepo for StableDares {
+    fn get(&self, id: u64) -> Option<Dare> {
+        state::DARE_REPOSITORY.with(|repo| repo.borrow().get(id))
+    }
+
+    fn len(&self) -> u64 {
+        state::DARE_REPOSITORY.with(|repo| repo.borrow().len())
+    }
+
+    fn push(&self, mut dare: Dare) -> Result<u64, String> {
+        let id = state::DARE_REPOSITORY.with(|repo| {
+            let repo = repo.borrow_mut();
+            dare.id = repo.len();
+            repo.push(&dare).map_err(|e| incidents::unexpected(format!("Failed to store dare: {:?}", e)))?;
+            Ok::<_, String>(dare.id)
+        })?;
+        replication::note(ReplicatedMap::Dares, &id);
+        Ok(id)
+    }
+
+    fn set(&self, dare: &Dare) -> Result<(), String> {
+        state::DARE_REPOSITORY.with(|repo| {
+            let repo = repo.borrow_mut();
+            if dare.id >= repo.len() {
+                return Err(format!("Dare #{} not found.", dare.id));
+            }
+            repo.set(dare.id, dare);
+            Ok(())
+        })?;
+        replication::note(ReplicatedMap::Dares, &dare.id);
+        Ok(())
+    }
+
+    fn scan(&self, visit: &mut dyn FnMut(Dare) -> bool) {
+        let unreadable = state::DARE_REPOSITORY.with(|repo| {
+            let _dares = repo.borrow();
+            quarantine::scan_dares(visit)
+        });
+        quarantine::isolate("dares", unreadable);
+    }
+}
+
+impl TaskRepo for StableTasks {
+    fn get(&self, id: u64) -> Option<RewardTask> {
+        state::REWARD_TASKS.with(|t| t.borrow().get(&id))
+    }
+
+    fn next_id(&self) -> u64 {
+        state::REWARD_TASKS.with(|t| t.borrow().last_key_value().map_or(0, |(id, _)| id + 1))
+    }
+
+    fn put(&self, task: RewardTask) {
+        state::REWARD_TASKS.with(|t| t.borrow_mut().insert(task.id, task));
+    }
+
+    fn remove(&self, id: u64) -> Option<RewardTask> {
+        state::REWARD_TASKS.with(|t| t.borrow_mut().remove(&id))
+    }
+
+    fn scan(&self, visit: &mut dyn FnMut(RewardTask) -> bool) {
+        let unreadable = state::REWARD_TASKS.with(|t| {
+            let _tasks = t.borrow();
+            quarantine::scan_map::<u64, RewardTask>(state::REWARD_TASKS_MEM_ID, None, &mut |_, task| visit(task))
+        });
+        quarantine::isolate("reward_tasks", unreadable);
+    }
+}
+
+// --- In-Memory (unit tests) ---
+
+#[cfg(test)]
+pub mod memory {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::BTreeMap;
+
+    #[derive(Default)]
+    pub struct MemoryUsers(pub RefCell<BTreeMap<Principal, UserProfile>>);
+
+    #[derive(Default)]
+    pub struct MemoryDares(pub RefCell<Vec<Dare>>);
+
+    #[derive(Default)]
+    pub struct MemoryTasks(pub RefCell<BTreeMap<u64, RewardTask>>);
+
+    impl UserRepo for MemoryUsers {
+        fn get(&self, user: Principal) -> Option<UserProfile> {
+            self.0.borrow().get(&user).cloned()
+        }
+
+        fn contains(&self, user: Principal) -> bool {
+            self.0.borrow().contains_key(&user)
+        }
+
+        fn len(&self) -> u64 {
+            self.0.borrow().len() as u64
+        }
+
+        fn put(&self, user: Principal, profile: UserProfile) {
+            self.0.borrow_mut().insert(user, profile);
+        }
+
+        fn remove(&self, user: Principal) -> Option<UserProfile> {
+            self.0.borrow_mut().remove(&user)
+        }
+
+        fn scan(&self, after: Option<Principal>, visit: &mut dyn FnMut(Principal, UserProfile) -> bool) {
+            let lower = after.map_or(Bound::Unbounded, Bound::Excluded);
+            let profiles: Vec<(Principal, UserProfile)> = self.0.borrow().range((lower, Bound::Unbounded))
+                .map(|(user, profile)| (*user, profile.clone()))
+                .collect();
+            for (user, profile) in profiles {
+                if !visit(user, profile) {
+                    break;
+                }
+            }
+        }
+
+        fn scan_keys(&self, after: Option<Principal>, visit: &mut dyn FnMut(Principal) -> bool) {
+            let lower = after.map_or(Bound::Unbounded, Bound::Excluded);
+            let users: Vec<Principal> = self.0.borrow().range((lower, Bound::Unbounded)).map(|(user, _)| *user).collect();
+            for user in users {
+                if !visit(user) {
+                    break;
+                }
+            }
+        }
+    }
+
+    impl DareRepo for MemoryDares {
+        fn get(&self, id: u64) -> Option<Dare> {
+            self.0.borrow().get(id as usize).cloned()
+        }
+
+        fn len(&self) -> u64 {
+            self.0.borrow().len() as u64
+        }
+
+        fn push(&self, mut dare: Dare) -> Result<u64, String> {
+            let mut dares = self.0.borrow_mut();
+            dare.id = dares.len() as u64;
+            dares.push(dare);
+            Ok(dares.len() as u64 - 1)
+        }
+
+        fn set(&self, dare: &Dare) -> Result<(), String> {
+            let mut dares = self.0.borrow_mut();
+            let slot = dares.get_mut(dare.id as usize).ok_or_else(|| format!("Dare #{} not found.", dare.id))?;
+            *slot = dare.clone();
+            Ok(())
+        }
+
+        fn scan(&self, visit: &mut dyn FnMut(Dare) -> bool) {
+            let dares = self.0.borrow().clone();
+            for dare in dares {
+                if !visit(dare) {
+                    break;
+                }
+            }
+        }
+    }
+
+    impl TaskRepo for MemoryTasks {
+        fn get(&self, id: u64) -> Option<RewardTask> {
+            self.0.borrow().get(&id).cloned()
+        }
+
+        fn next_id(&self) -> u64 {
+            self.0.borrow().last_key_value().map_or(0, |(id, _)| id + 1)
+        }
+
+        fn put(&self, task: RewardTask) {
+            self.0.borrow_mut().insert(task.id, task);
+        }
+
+        fn remove(&self, id: u64) -> Option<RewardTask> {
+            self.0.borrow_mut().remove(&id)
+        }
+
+        fn scan(&self, visit: &mut dyn FnMut(RewardTask) -> bool) {
+            let tasks: Vec<RewardTask> = self.0.borrow().values().cloned().collect();
+            for task in tasks {
+                if !visit(task) {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::memory::{MemoryDares, MemoryTasks, MemoryUsers};
+    use super::*;
+    use crate::types::{DareSource, Difficulty};
+
+    fn user(id: u8) -> Principal {
+        Principal::from_slice(&[id])
+    }
+
+    fn registered(ids: &[u8]) -> MemoryUsers {
+        let users = MemoryUsers::default();
+        for &id in ids {
+            users.put(user(id), UserProfile::default());
+        }
+        users
+    }
+
+    fn dare(text: &str) -> Dare {
+        Dare { id: 99, text: text.to_string(), difficulty: Difficulty::Easy, source: DareSource::Curated, weight: 100, issued_by: None, hint: None, creator: None, created_at: None, min_level: None, min_streak: None }
+    }
+
+    #[test]
+    fn update_profile_applies_the_change_and_bumps_the_version() {
+        let users = registered(&[1]);
+        let streak = state::update_profile_in(&users, user(1), |profile| {
+            profile.streak += 2;
+            Ok(profile.streak)
+        });
+        assert_eq!(streak, Ok(2));
+        let stored = users.get(user(1)).unwrap();
+        assert_eq!((stored.streak, stored.version), (2, Some(1)));
+    }
+
+    #[test]
+    fn update_profile_needs_a_registered_user() {
+        let users = registered(&[]);
+        let result = state::update_profile_in(&users, user(1), |_| Ok(()));
+        assert_eq!(result, Err("User not found. Please /register first.".to_string()));
+    }
+
+    #[test]
+    fn update_profile_refuses_to_overwrite_a_nested_write() {
+        let users = registered(&[1]);
+        let result = state::update_profile_in(&users, user(1), |profile| {
+            // The change writes the same profile through another path before returning
+            let mut nested = users.get(user(1)).unwrap();
+            nested.xp = Some(50);
+            assert!(users.put_if_unchanged(user(1), nested, 0));
+            profile.streak += 1;
+            Ok(())
+        });
+        assert!(result.is_err());
+        let stored = users.get(user(1)).unwrap();
+        assert_eq!((stored.streak, stored.xp, stored.version), (0, Some(50), Some(1)));
+    }
+
+    #[test]
+    fn scans_resume_after_the_cursor_and_stop_early() {
+        let users = registered(&[1, 2, 3, 4]);
+        let mut seen = Vec::new();
+        users.scan(Some(user(1)), &mut |user, _| {
+            seen.push(user);
+            seen.len() < 2
+        });
+        assert_eq!(seen, vec![user(2), user(3)]);
+    }
+
+    #[test]
+    fn dares_get_sequential_ids() {
+        let dares = MemoryDares::default();
+        assert_eq!(dares.push(dare("wave")), Ok(0));
+        assert_eq!(dares.push(dare("sing")), Ok(1));
+        let mut sung = dares.get(1).unwrap();
+        assert_eq!(sung.text, "sing");
+        sung.weight = 150;
+        dares.set(&sung).unwrap();
+        assert_eq!(dares.get(1).unwrap().weight, 150);
+        assert!(dares.set(&Dare { id: 7, ..sung }).is_err());
+    }
+
+    #[test]
+    fn task_ids_follow_the_highest_stored_id() {
+        let tasks = MemoryTasks::default();
+        assert_eq!(tasks.next_id(), 0);
+        tasks.put(RewardTask { id: 4, text: "Sing".to_string(), created_at: 0, stock: None, starts_at: None, ends_at: None });
+        assert_eq!(tasks.next_id(), 5);
+        assert_eq!(tasks.remove(4).map(|task| task.text), Some("Sing".to_string()));
+        assert_eq!(tasks.next_id(), 0);
+    }
+}
@@ -0,0 +1,466 @@
+use crate::history;
+use crate::moderation;
+use crate::points;
+use crate::randomness;
+use crate::ranking;
+use crate::repository::{self, TaskRepo, UserRepo};
+use crate::state;
+use crate::types::{HistoryEvent, RedemptionPolicy, RewardTask, RewardTaskStatus, RewardTier, RewardWheel, Submission};
+use crate::REWARD_MILESTONES;
+use candid::Principal;
+use sha2::{Digest, Sha256};
+
+// --- Reward Wheel ---
+// Redeeming a streak milestone spins a wheel instead of handing out a fixed reward. The tiers
+// (a bonus task, points, a streak freeze, a lucky badge) are picked by weight from the
+// raw_rand-seeded entropy pool (randomness.rs), so the outcome can't be predicted from the call
+// time. Admins configure the weights; every spin is logged as a history event. A bonus task stays
+// on the profile until its proof, sent with /complete_task, passes review; a rejected proof reopens
+// the task. A task can have limited stock ("first 10 redeemers"): each assignment uses one up, and
+// a sold-out task is no longer handed out. A seasonal task can also have an availability window
+// outside which it isn't handed out.
+
+const MAX_TASK_LEN: usize = 300;
+const MAX_TOTAL_WEIGHT: u32 = 10_000;
+
+pub fn wheel() -> RewardWheel {
+    state::get_config().reward_wheel.unwrap_or_default()
+}
+
+pub fn validate_wheel(wheel: &RewardWheel) -> Result<(), String> {
+    let total = wheel.task as u64 + wheel.points as u64 + wheel.streak_freeze as u64 + wheel.badge as u64;
+    if total == 0 || total > MAX_TOTAL_WEIGHT as u64 {
+        return Err(format!("Tier weights must add up to 1-{}.", MAX_TOTAL_WEIGHT));
+    }
+    if wheel.points > 0 && wheel.points_amount == 0 {
+        return Err("points_amount must be positive while the points tier has weight.".to_string());
+    }
+    Ok(())
+}
+
+fn spin(wheel: &RewardWheel, roll: u64) -> RewardTier {
+    let slices = [
+        (RewardTier::Task, wheel.task),
+        (RewardTier::Points, wheel.points),
+        (RewardTier::StreakFreeze, wheel.streak_freeze),
+        (RewardTier::Badge, wheel.badge),
+    ];
+    let total: u64 = slices.iter().map(|(_, weight)| *weight as u64).sum();
+    let mut point = roll % total.max(1);
+    for (tier, weight) in slices {
+        if point < weight as u64 {
+            return tier;
+        }
+        point -= weight as u64;
+    }
+    RewardTier::Points
+}
+
+// --- Reward Tasks ---
+
+fn validate_task_text(text: &str) -> Result<String, String> {
+    let text = text.trim();
+    if text.is_empty() || text.len() > MAX_TASK_LEN {
+        return Err(format!("Task text must be 1-{} bytes.", MAX_TASK_LEN));
+    }
+    Ok(text.to_string())
+}
+
+pub fn add_task(text: &str, stock: Option<u32>) -> Result<u64, String> {
+    let text = validate_task_text(text)?;
+    let tasks = repository::tasks();
+    let id = tasks.next_id();
+    tasks.put(RewardTask { id, text, created_at: ic_cdk::api::time(), stock, starts_at: None, ends_at: None });
+    Ok(id)
+}
+
+pub fn status(task: &RewardTask, now: u64) -> RewardTaskStatus {
+    if task.starts_at.is_some_and(|starts_at| now < starts_at) {
+        RewardTaskStatus::Upcoming
+    } else if task.ends_at.is_some_and(|ends_at| now >= ends_at) {
+        RewardTaskStatus::Expired
+    } else if task.stock == Some(0) {
+        RewardTaskStatus::SoldOut
+    } else {
+        RewardTaskStatus::Available
+    }
+}
+
+// Tasks the wheel can hand out right now
+pub fn available_tasks(now: u64) -> Vec<RewardTask> {
+    let mut available = Vec::new();
+    repository::tasks().scan(&mut |task| {
+        if status(&task, now) == RewardTaskStatus::Available {
+            available.push(task);
+        }
+        true
+    });
+    available
+}
+
+pub fn list_tasks(offset: usize, limit: usize) -> Vec<(RewardTask, RewardTaskStatus)> {
+    let now = ic_cdk::api::time();
+    let (mut page, mut skipped) = (Vec::new(), 0);
+    repository::tasks().scan(&mut |task| {
+        if skipped < offset {
+            skipped += 1;
+        } else {
+            let status = status(&task, now);
+            page.push((task, status));
+        }
+        page.len() < limit
+    });
+    page
+}
+
+// Applies `change` to a stored task and returns what it returns
+fn update_task<T>(task_id: u64, change: impl FnOnce(&mut RewardTask) -> T) -> Result<T, String> {
+    let tasks = repository::tasks();
+    let mut task = tasks.get(task_id).ok_or_else(|| format!("Reward task #{} not found.", task_id))?;
+    let result = change(&mut task);
+    tasks.put(task);
+    Ok(result)
+}
+
+// Edits and removals are logged to the admin's history so changes can be traced
+pub fn edit_task(admin: Principal, task_id: u64, text: &str) -> Result<(), String> {
+    let text = validate_task_text(text)?;
+    let previous_text = update_task(task_id, |task| std::mem::replace(&mut task.text, text))?;
+    history::log_event(admin, HistoryEvent::RewardTaskEdited { task_id, previous_text });
+    Ok(())
+}
+
+// Sets the stock left (None = unlimited); a sold-out task is handed out again once restocked
+pub fn restock_task(admin: Principal, task_id: u64, stock: Option<u32>) -> Result<(), String> {
+    let previous = update_task(task_id, |task| std::mem::replace(&mut task.stock, stock))?;
+    history::log_event(admin, HistoryEvent::RewardTaskRestocked { task_id, previous, stock });
+    Ok(())
+}
+
+// Limits when the task is handed out; either end may be open
+pub fn schedule_task(admin: Principal, task_id: u64, starts_at: Option<u64>, ends_at: Option<u64>) -> Result<(), String> {
+    if let (Some(starts_at), Some(ends_at)) = (starts_at, ends_at) {
+        if starts_at >= ends_at {
+            return Err("The window must start before it ends.".to_string());
+        }
+    }
+    update_task(task_id, |task| {
+        task.starts_at = starts_at;
+        task.ends_at = ends_at;
+    })?;
+    history::log_event(admin, HistoryEvent::RewardTaskScheduled { task_id, starts_at, ends_at });
+    Ok(())
+}
+
+fn take_stock(task_id: u64) {
+    let tasks = repository::tasks();
+    if let Some(mut task) = tasks.get(task_id).filter(|task| task.stock.is_some()) {
+        task.stock = task.stock.map(|stock| stock.saturating_sub(1));
+        tasks.put(task);
+    }
+}
+
+// Users holding the task or waiting for review of its proof
+fn task_holders(task_id: u64) -> usize {
+    let mut holding = 0;
+    repository::users().scan(None, &mut |_, profile| {
+        holding += usize::from(profile.current_redemption_task_id == Some(task_id));
+        true
+    });
+    let in_review = state::REVIEW_QUEUE.with(|q| {
+        q.borrow().iter()
+            .filter_map(|(id, _)| state::SUBMISSIONS.with(|s| s.borrow().get(&id)))
+            .filter(|submission| submission.reward_task_id == Some(task_id))
+            .count()
+    });
+    holding + in_review
+}
+
+// A task someone still has to complete or is being reviewed for can't be removed
+pub fn remove_task(admin: Principal, task_id: u64) -> Result<(), String> {
+    let holders = task_holders(task_id);
+    if holders > 0 {
+        return Err(format!("Reward task #{} is still held by {} user(s); edit it instead.", task_id, holders));
+    }
+    let task = repository::tasks().remove(task_id)
+        .ok_or_else(|| format!("Reward task #{} not found.", task_id))?;
+    history::log_event(admin, HistoryEvent::RewardTaskRemoved { task_id, text: task.text });
+    Ok(())
+}
+
+fn pick_task(roll: u64, now: u64) -> Option<RewardTask> {
+    let mut available = available_tasks(now);
+    if available.is_empty() {
+        return None;
+    }
+    let index = (roll % available.len() as u64) as usize;
+    Some(available.swap_remove(index))
+}
+
+// --- Redemption ---
+// A chat's policy overrides the global one. Keep is the default: redeeming has never touched the
+// streak here. Milestones above a reset or decayed streak can be earned and redeemed again.
+
+pub fn policy_for_chat(chat_id: Option<&str>) -> RedemptionPolicy {
+    chat_id
+        .and_then(state::get_chat_config)
+        .and_then(|chat_config| chat_config.redemption_policy)
+        .or(state::get_config().redemption_policy)
+        .unwrap_or(RedemptionPolicy::Keep)
+}
+
+pub fn validate_policy(policy: &RedemptionPolicy) -> Result<(), String> {
+    match policy {
+        RedemptionPolicy::Decay { percent } if *percent == 0 || *percent > 100 => {
+            Err("Decay percent must be 1-100.".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn apply_policy(policy: RedemptionPolicy, streak: u32) -> u32 {
+    match policy {
+        RedemptionPolicy::Reset => 0,
+        RedemptionPolicy::Keep => streak,
+        RedemptionPolicy::Decay { percent } => streak - (streak as u64 * percent.min(100) as u64 / 100) as u32,
+    }
+}
+
+fn next_milestone(streak: u32, redeemed: &[u32]) -> Option<u32> {
+    REWARD_MILESTONES.iter().copied().find(|&milestone| streak >= milestone && !redeemed.contains(&milestone))
+}
+
+// Told to the user when a dare brings their streak to a milestone they can redeem
+pub fn milestone_note(streak: u32, redeemed: &[u32]) -> Option<String> {
+    (REWARD_MILESTONES.contains(&streak) && !redeemed.contains(&streak))
+        .then(|| format!("🎁 You reached the streak {} reward: /redeem_reward to spin the wheel.", streak))
+}
+
+// Each spin needs two rolls (tier and task), so they are derived from one entropy draw
+fn roll(seed: &[u8], index: usize) -> u64 {
+    let digest = Sha256::new().chain_update(seed).chain_update((index as u64).to_be_bytes()).finalize();
+    let mut chunk = [0u8; 8];
+    chunk.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(chunk)
+}
+
+// Whether there are tasks in season and every one of them is sold out
+fn all_sold_out(statuses: &[RewardTaskStatus]) -> bool {
+    let mut in_season = statuses.iter().filter(|status| matches!(status, RewardTaskStatus::Available | RewardTaskStatus::SoldOut)).peekable();
+    in_season.peek().is_some() && in_season.all(|status| *status == RewardTaskStatus::SoldOut)
+}
+
+fn describe_prize(milestone: u32, tier: RewardTier, task: Option<&RewardTask>, points: Option<u64>, sold_out: bool) -> String {
+    match tier {
+        RewardTier::Task => format!(
+            "a bonus task: {} Send your proof with /complete_task.",
+            task.map(|t| t.text.as_str()).unwrap_or_default()
+        ),
+        RewardTier::Points if sold_out => format!("{} points, as every bonus task in season is out of stock", points.unwrap_or_default()),
+        RewardTier::Points => format!("{} points", points.unwrap_or_default()),
+        RewardTier::StreakFreeze => "a streak freeze, which protects your streak once".to_string(),
+        RewardTier::Badge => format!("the lucky-{} badge", milestone),
+    }
+}
+
+// Redeems the lowest reached milestone that hasn't been redeemed yet and spins the wheel for it.
+// With `all`, every reached milestone is claimed in one go and the reply is an itemized receipt.
+// Each milestone is marked redeemed as it is granted, so retrying never pays one twice; one whose
+// points can't be minted is unmarked again. When nothing was paid, the policy's streak change is
+// undone too.
+pub async fn redeem(user: Principal, chat_id: Option<&str>, all: bool) -> Result<String, String> {
+    let profile = state::get_profile(user)
+        .ok_or_else(|| "User not found. Please /register first.".to_string())?;
+    if next_milestone(profile.streak, &profile.redeemed_milestones).is_none() {
+        return Ok(format!("No new rewards available at your current streak of {}.", profile.streak));
+    }
+    let random_bytes = match randomness::next_bytes() {
+        Some(bytes) => bytes.to_vec(),
+        None => ic_cdk::api::management_canister::main::raw_rand().await
+            .map_err(|(code, message)| format!("Failed to get randomness: {:?} {}", code, message))?.0,
+    };
+    let wheel = wheel();
+    let policy = policy_for_chat(chat_id);
+    let now = ic_cdk::api::time();
+    let mut statuses = Vec::new();
+    repository::tasks().scan(&mut |task| {
+        statuses.push(status(&task, now));
+        true
+    });
+    let sold_out = all_sold_out(&statuses);
+    // (tier, task, whether a task spin fell back to points as the in-season tasks are sold out)
+    let spins: Vec<(RewardTier, Option<RewardTask>, bool)> = (0..REWARD_MILESTONES.len())
+        .map(|slot| {
+            let tier = spin(&wheel, roll(&random_bytes, slot * 2));
+            let task = if tier == RewardTier::Task { pick_task(roll(&random_bytes, slot * 2 + 1), now) } else { None };
+            // No tasks available: the spin pays out points instead
+            if tier == RewardTier::Task && task.is_none() { (RewardTier::Points, None, sold_out) } else { (tier, task, false) }
+        })
+        .collect();
+
+    // The streak may have changed during the randomness call, so the milestones are re-checked
+    let (granted, streak, before) = state::update_profile(user, |profile| {
+        let before = (profile.streak_reached_at, profile.redeemed_milestones.clone());
+        let mut granted = Vec::new();
+        for (tier, task, sold_out) in &spins {
+            let Some(milestone) = next_milestone(profile.streak, &profile.redeemed_milestones) else { break };
+            profile.redeemed_milestones.push(milestone);
+            profile.redeemed_milestones.sort_unstable();
+            // One bonus task at a time; a second task spin pays points
+            let tier = if *tier == RewardTier::Task && profile.current_redemption_task_id.is_some() { RewardTier::Points } else { *tier };
+            match tier {
+                RewardTier::Task => profile.current_redemption_task_id = task.as_ref().map(|t| t.id),
+                RewardTier::StreakFreeze => profile.streak_freezes = Some(profile.streak_freezes.unwrap_or(0) + 1),
+                RewardTier::Badge => profile.lucky_badges.get_or_insert_with(Vec::new).push(milestone),
+                RewardTier::Points => {}
+            }
+            granted.push((milestone, tier, task.clone().filter(|_| tier == RewardTier::Task), *sold_out));
+            if !all { break; }
+        }
+        if granted.is_empty() {
+            return Err("This reward was already redeemed.".to_string());
+        }
+        // The policy applies once per call, after everything reached has been claimed
+        let streak = (profile.streak, apply_policy(policy, profile.streak));
+        ranking::set_streak(profile, streak.1);
+        profile.redeemed_milestones.retain(|&redeemed| redeemed <= streak.1);
+        Ok((granted, streak, before))
+    })?;
+
+    let mut prizes = Vec::new();
+    let mut unpaid = Vec::new();
+    for (milestone, tier, task, sold_out) in granted {
+        let points = (tier == RewardTier::Points).then_some(wheel.points_amount.max(1));
+        if let Some(amount) = points {
+            if let Err(e) = points::mint(user, amount, Some("reward wheel")) {
+                unpaid.push((milestone, e));
+                continue;
+            }
+        }
+        if let Some(task) = &task {
+            take_stock(task.id);
+        }
+        history::log_event(user, HistoryEvent::RewardSpun { milestone, tier, task_id: task.as_ref().map(|t| t.id), points });
+        prizes.push((milestone, describe_prize(milestone, tier, task.as_ref(), points, sold_out)));
+    }
+
+    if !unpaid.is_empty() {
+        let paid_any = !prizes.is_empty();
+        state::update_profile(user, |profile| {
+            profile.redeemed_milestones.retain(|redeemed| !unpaid.iter().any(|(milestone, _)| milestone == redeemed));
+            if !paid_any && profile.streak == streak.1 {
+                profile.streak = streak.0;
+                (profile.streak_reached_at, profile.redeemed_milestones) = before.clone();
+            }
+            Ok(())
+        })?;
+        if !paid_any {
+            return Err(format!("The reward couldn't be paid out ({}). It's still yours to redeem.", unpaid[0].1));
+        }
+    }
+
+    let mut reply = match prizes.as_slice() {
+        [(milestone, prize)] => format!("🎡 You spun the wheel for your streak {} reward and won {}!", milestone, prize),
+        _ => {
+            let mut receipt = format!("🎡 You spun the wheel {} times:", prizes.len());
+            for (milestone, prize) in &prizes {
+                receipt.push_str(&format!("\n- Streak {}: {}", milestone, prize));
+            }
+            receipt
+        }
+    };
+    if streak.1 != streak.0 {
+        reply.push_str(&format!(" Your streak went from {} to {}.", streak.0, streak.1));
+    }
+    for (milestone, e) in &unpaid {
+        reply.push_str(&format!("\nYour streak {} reward couldn't be paid out ({}); it's still yours to redeem.", milestone, e));
+    }
+    Ok(reply)
+}
+
+// --- Task Completion ---
+
+const MAX_TASK_PROOF_LEN: usize = 1000;
+
+// Bonus tasks still to do or awaiting review: (task, submission id under review)
+pub fn outstanding_tasks(user: Principal) -> Vec<(RewardTask, Option<u64>)> {
+    let task_of = |id: u64| repository::tasks().get(id);
+    let mut tasks: Vec<(RewardTask, Option<u64>)> = state::get_profile(user)
+        .and_then(|profile| profile.current_redemption_task_id)
+        .and_then(task_of)
+        .map(|task| (task, None))
+        .into_iter()
+        .collect();
+    let in_review: Vec<(u64, u64)> = state::REVIEW_QUEUE.with(|q| {
+        q.borrow().iter()
+            .filter_map(|(id, _)| state::SUBMISSIONS.with(|s| s.borrow().get(&id)))
+            .filter(|submission| submission.user == user)
+            .filter_map(|submission| submission.reward_task_id.map(|task_id| (task_id, submission.id)))
+            .collect()
+    });
+    tasks.extend(in_review.into_iter().filter_map(|(task_id, submission_id)| task_of(task_id).map(|task| (task, Some(submission_id)))));
+    tasks
+}
+
+// Sends the proof for the user's bonus task to review. Returns the submission id.
+pub fn complete_task(user: Principal, proof: &str) -> Result<u64, String> {
+    let proof = proof.trim();
+    if proof.is_empty() { return Err("Proof cannot be empty.".to_string()); }
+    if proof.len() > MAX_TASK_PROOF_LEN {
+        return Err(format!("Proof is too long (max {} bytes).", MAX_TASK_PROOF_LEN));
+    }
+    let submission_id = state::next_submission_id();
+    let task_id = state::update_profile(user, |profile| {
+        profile.current_redemption_task_id.take()
+            .ok_or_else(|| "You don't have a bonus task. Redeem a milestone reward to spin for one.".to_string())
+    })?;
+    let submission = Submission {
+        id: submission_id,
+        user,
+        proof_parts: vec![proof.to_string()],
+        submitted_at: ic_cdk::api::time(),
+        dare_id: None,
+        review: None,
+        chat_id: None,
+        hint_used: None,
+        reward_task_id: Some(task_id),
+        rating: None,
+        dare_text: None,
+        escalation_xp: None,
+        completed_at: None,
+        streak_pending: None,
+        previous_completed_at: None,
+    };
+    state::put_submission(submission);
+    moderation::enqueue(submission_id);
+    Ok(submission_id)
+}
+
+// Called by moderation once a task proof is reviewed
+pub fn task_reviewed(user: Principal, task_id: u64, submission_id: u64, approved: bool) {
+    if approved {
+        history::log_event(user, HistoryEvent::RewardTaskCompleted { task_id, submission_id });
+        return;
+    }
+    // Reopen the task unless the user has been given another one since
+    let _ = state::update_profile(user, |profile| {
+        profile.current_redemption_task_id.get_or_insert(task_id);
+        Ok(())
+    });
+    history::log_event(user, HistoryEvent::RewardTaskRejected { task_id, submission_id });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use RewardTaskStatus::*;
+
+    #[test]
+    fn sold_out_needs_every_task_in_season_sold_out() {
+        assert!(all_sold_out(&[SoldOut, Expired, SoldOut, Upcoming]));
+        assert!(!all_sold_out(&[SoldOut, Available]));
+        assert!(!all_sold_out(&[Available, SoldOut]));
+        assert!(!all_sold_out(&[Expired, Upcoming]));
+        assert!(!all_sold_out(&[]));
+    }
+}
@@ -0,0 +1,85 @@
+use crate::state;
+use crate::timezone;
+use crate::types::HistoryEvent;
+use crate::REWARD_MILESTONES;
+use candid::Principal;
+
+// --- Progression Roadmap (/roadmap) ---
+// Lists every reward milestone with its status and, for the ones still ahead, how many dares are
+// left and when the user would get there at their recent pace (net streak gain over the last two
+// weeks). Dates are shown in the user's time zone.
+
+const PACE_WINDOW_DAYS: u64 = 14;
+const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+// Streak points gained over the pace window: submissions minus undone and rejected ones
+fn recent_gain(user: Principal, now: u64) -> u64 {
+    let since = now.saturating_sub(PACE_WINDOW_DAYS * DAY_NANOS);
+    let (gained, lost) = state::HISTORY.with(|history_ref| {
+        history_ref.borrow().iter()
+            .rev()
+            .map(|(_, entry)| entry)
+            .take_while(|entry| entry.timestamp >= since)
+            .filter(|entry| entry.user == user)
+            .fold((0u64, 0u64), |(gained, lost), entry| match entry.event {
+                HistoryEvent::DareSubmitted { .. }
+                | HistoryEvent::AppealDecided { granted: true, .. }
+                | HistoryEvent::StreakVouched { .. } => (gained + 1, lost),
+                HistoryEvent::SubmissionUndone { .. } | HistoryEvent::SubmissionRejected { .. } => (gained, lost + 1),
+                HistoryEvent::TruthAnswered { .. }
+                | HistoryEvent::RewardSpun { .. }
+                | HistoryEvent::RewardTaskCompleted { .. }
+                | HistoryEvent::RewardTaskRejected { .. }
+                | HistoryEvent::RewardTaskEdited { .. }
+                | HistoryEvent::RewardTaskRemoved { .. }
+                | HistoryEvent::RewardTaskRestocked { .. }
+                | HistoryEvent::RewardTaskScheduled { .. }
+                | HistoryEvent::AppealFiled { .. }
+                | HistoryEvent::ConfigChanged { .. }
+                | HistoryEvent::VouchGiven { .. }
+                | HistoryEvent::BuddyBonus { .. }
+                | HistoryEvent::HardcoreLapsed { .. }
+                | HistoryEvent::StreakFreezeUsed { .. }
+                | HistoryEvent::CheckedIn { .. }
+                | HistoryEvent::QuestCompleted { .. }
+                | HistoryEvent::EscalationCompleted { .. }
+                | HistoryEvent::EscalationLapsed { .. }
+                | HistoryEvent::StreakExpired { .. }
+                | HistoryEvent::AppealDecided { granted: false, .. } => (gained, lost),
+            })
+    });
+    gained.saturating_sub(lost)
+}
+
+pub fn render(user: Principal) -> Result<String, String> {
+    let profile = state::get_profile(user)
+        .ok_or_else(|| "User not found. Please /register first.".to_string())?;
+    let now = ic_cdk::api::time();
+    let gain = recent_gain(user, now);
+
+    let mut lines = vec![format!("🗺️ **Your roadmap** (current streak: {})", profile.streak)];
+    for &milestone in REWARD_MILESTONES {
+        let line = if profile.redeemed_milestones.contains(&milestone) {
+            format!("✅ Streak {}: redeemed", milestone)
+        } else if profile.streak >= milestone {
+            format!("🎁 Streak {}: ready! Use /redeem_reward", milestone)
+        } else {
+            let to_go = (milestone - profile.streak) as u64;
+            let estimate = if gain == 0 {
+                String::new()
+            } else {
+                // Round up to whole days at the current pace
+                let days = (to_go * PACE_WINDOW_DAYS).div_ceil(gain);
+                format!(", ~{} ({} days at your pace)", timezone::local_date(user, now + days * DAY_NANOS), days)
+            };
+            format!("🔒 Streak {}: {} dare(s) to go{}", milestone, to_go, estimate)
+        };
+        lines.push(line);
+    }
+    if gain == 0 && REWARD_MILESTONES.iter().any(|&m| profile.streak < m) {
+        lines.push("Complete a dare to see estimated dates.".to_string());
+    } else if gain > 0 {
+        lines.push(format!("Pace: {} dare(s) in the last {} days.", gain, PACE_WINDOW_DAYS));
+    }
+    Ok(lines.join("\n"))
+}
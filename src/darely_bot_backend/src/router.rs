@@ -0,0 +1,568 @@
+use crate::accounts;
+use crate::api;
+use crate::api_keys;
+use crate::certification;
+use crate::cors;
+use crate::encoding;
+use crate::identity;
+use crate::sessions;
+use crate::types::{ApiScope, HttpRequest, HttpResponse};
+use candid::Principal;
+use serde_bytes::ByteBuf;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+
+// --- API Versioning ---
+// Bump the major version (and prefix) for breaking changes; minor for additive ones.
+pub const API_VERSION: &str = "1.0.0";
+pub const API_PREFIX: &str = "/api/v1";
+
+// --- Route Metadata ---
+
+pub struct QueryParam {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub required: bool,
+}
+
+pub enum Handler {
+    Query(fn(&ParsedRequest) -> HttpResponse), // Served directly by http_request
+    Update(fn(&ParsedRequest) -> HttpResponse), // Upgraded to http_request_update (changes state)
+}
+
+// How a route identifies its caller (sets ParsedRequest::caller)
+#[derive(PartialEq, Eq)]
+pub enum Auth {
+    Public,
+    Session, // `Authorization: Bearer <token>` from create_session
+    Signed, // `X-Identity-Proof` delegation proof (update routes only: consumes a nonce)
+}
+
+// One REST route; the OpenAPI document is generated from these fields
+pub struct Route {
+    pub method: &'static str,
+    pub path: &'static str,
+    pub operation_id: &'static str,
+    pub summary: &'static str,
+    pub params: &'static [QueryParam],
+    pub request_schema: Option<fn() -> Value>,
+    pub response_status: u16,
+    pub response_schema: fn() -> Value,
+    pub handler: Handler,
+    pub certified: bool, // Served from the certified response cache (parameterless GETs only)
+    pub cors: bool, // Callable cross-origin by the configured allowed origins
+    pub auth: Auth,
+    pub api_key_scope: Option<ApiScope>, // Partner API keys with this scope are accepted (in place of `auth`)
+}
+
+const DARE_LIST_PARAMS: &[QueryParam] = &[
+    QueryParam { name: "difficulty", description: "Only return dares of this difficulty (Easy, Medium, Hard).", required: false },
+    QueryParam { name: "offset", description: "Number of items to skip (default 0).", required: false },
+    QueryParam { name: "limit", description: "Maximum number of items to return (default 50, max 100).", required: false },
+];
+
+pub const ROUTES: &[Route] = &[
+    Route {
+        method: "GET",
+        path: "/api/v1/dares",
+        operation_id: "listDares",
+        summary: "List stored dares.",
+        params: DARE_LIST_PARAMS,
+        request_schema: None,
+        response_status: 200,
+        response_schema: api::dare_list_schema,
+        handler: Handler::Query(api::list_dares),
+        certified: false,
+        cors: true,
+        auth: Auth::Public,
+        api_key_scope: Some(ApiScope::ReadDares),
+    },
+    Route {
+        method: "GET",
+        path: "/api/v1/leaderboard",
+        operation_id: "getLeaderboard",
+        summary: "Top players by current streak.",
+        params: &[],
+        request_schema: None,
+        response_status: 200,
+        response_schema: api::leaderboard_schema,
+        handler: Handler::Query(api::leaderboard),
+        certified: true,
+        cors: true,
+        auth: Auth::Public,
+        api_key_scope: Some(ApiScope::ReadLeaderboard),
+    },
+    Route {
+        method: "GET",
+        path: "/api/v1/stats",
+        operation_id: "getStats",
+        summary: "Global usage statistics.",
+        params: &[],
+        request_schema: None,
+        response_status: 200,
+        response_schema: api::stats_schema,
+        handler: Handler::Query(api::stats),
+        certified: true,
+        cors: true,
+        auth: Auth::Public,
+        api_key_scope: None,
+    },
+    Route {
+        method: "GET",
+        path: "/api/v1/me/stats",
+        operation_id: "getMyStats",
+        summary: "The session user's streak and progress.",
+        params: &[],
+        request_schema: None,
+        response_status: 200,
+        response_schema: api::my_stats_schema,
+        handler: Handler::Query(api::my_stats),
+        certified: false,
+        cors: true,
+        auth: Auth::Session,
+        api_key_scope: None,
+    },
+    Route {
+        method: "POST",
+        path: "/api/v1/suggest",
+        operation_id: "suggestDare",
+        summary: "Suggest a new dare for admin review.",
+        params: &[],
+        request_schema: Some(api::suggest_request_schema),
+        response_status: 201,
+        response_schema: api::suggest_response_schema,
+        handler: Handler::Update(api::suggest),
+        certified: false,
+        cors: true,
+        auth: Auth::Signed,
+        api_key_scope: Some(ApiScope::WriteSuggestions),
+    },
+    Route {
+        method: "GET",
+        path: "/openapi.json",
+        operation_id: "getOpenApi",
+        summary: "This OpenAPI document.",
+        params: &[],
+        request_schema: None,
+        response_status: 200,
+        response_schema: openapi_schema,
+        handler: Handler::Query(openapi),
+        certified: true,
+        cors: true,
+        auth: Auth::Public,
+        api_key_scope: None,
+    },
+];
+
+// --- Request Parsing ---
+
+pub struct ParsedRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    pub caller: Option<Principal>, // Set by the router for authenticated routes
+    pub api_key: Option<u64>, // Set when the request was authorized with a partner API key
+}
+
+// Minimal percent-decoding for query strings ('+' as space, %XX escapes)
+fn url_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(byte) => { decoded.push(byte); i += 2; }
+                    None => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+impl ParsedRequest {
+    pub fn parse(request: &HttpRequest) -> Self {
+        let (path, query_string) = request.url.split_once('?').unwrap_or((&request.url, ""));
+        let query = query_string.split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (url_decode(key), url_decode(value))
+            })
+            .collect();
+        ParsedRequest {
+            method: request.method.to_uppercase(),
+            path: path.trim_end_matches('/').to_string(),
+            query,
+            headers: request.headers.clone(),
+            body: request.body.to_vec(),
+            caller: None,
+            api_key: None,
+        }
+    }
+
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    // Header lookup is case-insensitive, as in HTTP
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+}
+
+// --- Responses ---
+
+pub fn json_response(status_code: u16, body: &Value) -> HttpResponse {
+    raw_json_response(status_code, body.to_string().into_bytes())
+}
+
+// JSON response from an already serialized (possibly compressed) body
+fn raw_json_response(status_code: u16, body: Vec<u8>) -> HttpResponse {
+    HttpResponse {
+        status_code,
+        headers: vec![
+            ("Content-Type".to_string(), "application/json".to_string()),
+            ("X-API-Version".to_string(), API_VERSION.to_string()),
+        ],
+        body: ByteBuf::from(body),
+        upgrade: None,
+    }
+}
+
+pub fn error_response(status_code: u16, message: &str) -> HttpResponse {
+    json_response(status_code, &json!({ "error": message }))
+}
+
+// Compresses a handler's JSON body when the client accepts it and the body is large enough
+fn apply_encoding(request: &ParsedRequest, mut response: HttpResponse) -> HttpResponse {
+    response.headers.push(("Vary".to_string(), "Accept-Encoding".to_string()));
+    if response.body.len() < encoding::MIN_COMPRESSIBLE_BYTES {
+        return response;
+    }
+    if let Some(content_encoding) = encoding::negotiate(request.header("Accept-Encoding")) {
+        response.body = ByteBuf::from(encoding::compress(&response.body, content_encoding));
+        response.headers.push(("Content-Encoding".to_string(), content_encoding.header_value().to_string()));
+    }
+    response
+}
+
+// --- Conditional GET ---
+
+// Strong ETag derived from the SHA-256 of the identity body (same for every encoding)
+fn etag_for(hash: &[u8]) -> String {
+    let hex: String = hash[..16].iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("\"{}\"", hex)
+}
+
+// If-None-Match uses weak comparison, so W/"x" matches "x"
+fn etag_matches(request: &ParsedRequest, etag: &str) -> bool {
+    request.header("If-None-Match").is_some_and(|value| {
+        value.split(',').map(str::trim).any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+    })
+}
+
+fn not_modified(etag: &str) -> HttpResponse {
+    HttpResponse {
+        status_code: 304,
+        headers: vec![
+            ("ETag".to_string(), etag.to_string()),
+            ("Vary".to_string(), "Accept-Encoding".to_string()),
+            ("X-API-Version".to_string(), API_VERSION.to_string()),
+        ],
+        body: ByteBuf::new(),
+        upgrade: None,
+    }
+}
+
+// Tags successful GET responses and answers 304 when the client already has this version
+fn apply_etag(request: &ParsedRequest, mut response: HttpResponse) -> HttpResponse {
+    if request.method != "GET" || response.status_code != 200 {
+        return response;
+    }
+    let etag = etag_for(&Sha256::digest(&response.body));
+    if etag_matches(request, &etag) {
+        return not_modified(&etag);
+    }
+    response.headers.push(("ETag".to_string(), etag));
+    response
+}
+
+// Serves a certified route from the cache, with the IC-Certificate header when available
+fn cached_response(request: &ParsedRequest) -> Option<HttpResponse> {
+    let negotiated = encoding::negotiate(request.header("Accept-Encoding"));
+    let mut response = certification::with_cached(&request.path, |cached| {
+        let etag = etag_for(&cached.hash);
+        if etag_matches(request, &etag) {
+            return not_modified(&etag);
+        }
+        let (content_encoding, body) = cached.variant(negotiated);
+        let mut response = raw_json_response(200, body.to_vec());
+        response.headers.push(("ETag".to_string(), etag));
+        response.headers.push(("Vary".to_string(), "Accept-Encoding".to_string()));
+        if let Some(content_encoding) = content_encoding {
+            response.headers.push(("Content-Encoding".to_string(), content_encoding.header_value().to_string()));
+        }
+        response
+    })?;
+    if response.status_code == 200 {
+        if let Some(certificate) = certification::certificate_header(&request.path) {
+            response.headers.push(("IC-Certificate".to_string(), certificate));
+        }
+    }
+    Some(response)
+}
+
+fn upgrade_response() -> HttpResponse {
+    HttpResponse { status_code: 200, headers: Vec::new(), body: ByteBuf::new(), upgrade: Some(true) }
+}
+
+// --- Dispatch ---
+
+// Answers an OPTIONS preflight for a path, listing the CORS-enabled methods registered on it
+fn preflight(request: &ParsedRequest) -> HttpResponse {
+    let methods: Vec<&str> = ROUTES.iter()
+        .filter(|route| route.path == request.path && route.cors)
+        .map(|route| route.method)
+        .collect();
+    match cors::allowed_origin(request.header("Origin")) {
+        Some(origin) if !methods.is_empty() => HttpResponse {
+            status_code: 204,
+            headers: cors::preflight_headers(&origin, &methods),
+            body: ByteBuf::new(),
+            upgrade: None,
+        },
+        _ => error_response(403, "Origin not allowed."),
+    }
+}
+
+fn find_route(request: &ParsedRequest) -> Result<&'static Route, HttpResponse> {
+    let mut path_matches = ROUTES.iter().filter(|route| route.path == request.path).peekable();
+    if path_matches.peek().is_none() {
+        return Err(error_response(404, "Not found."));
+    }
+    if request.method == "OPTIONS" {
+        return Err(preflight(request));
+    }
+    path_matches.find(|route| route.method == request.method)
+        .ok_or_else(|| error_response(405, "Method not allowed."))
+}
+
+// Adds CORS headers when the route allows cross-origin calls from the request's origin
+fn apply_cors(route: &Route, request: &ParsedRequest, mut response: HttpResponse) -> HttpResponse {
+    if route.cors {
+        if let Some(origin) = cors::allowed_origin(request.header("Origin")) {
+            response.headers.extend(cors::response_headers(&origin));
+        }
+    }
+    response
+}
+
+// The partner API key presented for a route that accepts one
+fn presented_api_key<'a>(route: &Route, request: &'a ParsedRequest) -> Option<(ApiScope, &'a str)> {
+    Some((route.api_key_scope?, request.header("X-API-Key")?.trim()))
+}
+
+// Resolves the caller of an authenticated route from its API key, session token or identity proof
+fn authenticate(route: &Route, request: &mut ParsedRequest) -> Result<(), HttpResponse> {
+    if let Some((scope, key)) = presented_api_key(route, request) {
+        let key_id = api_keys::authorize(key, scope).map_err(|(status, e)| error_response(status, &e))?;
+        request.api_key = Some(key_id);
+        return Ok(());
+    }
+    let user = match route.auth {
+        Auth::Public => return Ok(()),
+        Auth::Session => {
+            let token = request.header("Authorization")
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .ok_or_else(|| error_response(401, "Missing session token. Call create_session and send it as a Bearer token."))?;
+            sessions::verify(token.trim())
+        }
+        Auth::Signed => {
+            let proof = request.header("X-Identity-Proof")
+                .ok_or_else(|| error_response(401, "Missing X-Identity-Proof header. Sign the request with your identity."))?;
+            identity::verify_request(proof, &request.method, &request.path, &request.body)
+                .map(|principal| accounts::resolve(accounts::Identity::Direct(principal)))
+        }
+    };
+    request.caller = Some(user.map_err(|e| error_response(401, &e))?);
+    Ok(())
+}
+
+fn run(route: &Route, request: &mut ParsedRequest) -> HttpResponse {
+    if let Err(response) = authenticate(route, request) {
+        return response;
+    }
+    let request = &*request;
+    let response = match route.handler {
+        Handler::Query(handler) | Handler::Update(handler) => handler(request),
+    };
+    let response = apply_etag(request, response);
+    if response.status_code == 304 {
+        return response;
+    }
+    apply_encoding(request, response)
+}
+
+// Entry point for http_request (query): update routes are upgraded
+pub fn handle_query(request: HttpRequest) -> HttpResponse {
+    let mut parsed = ParsedRequest::parse(&request);
+    match find_route(&parsed) {
+        // Keyed requests are metered, which needs an update call
+        Ok(route) if presented_api_key(route, &parsed).is_some() => upgrade_response(),
+        Ok(route) => match route.handler {
+            Handler::Query(_) if route.certified && parsed.query.is_empty() => {
+                let response = cached_response(&parsed).unwrap_or_else(|| run(route, &mut parsed));
+                apply_cors(route, &parsed, response)
+            }
+            Handler::Query(_) => {
+                let response = run(route, &mut parsed);
+                apply_cors(route, &parsed, response)
+            }
+            Handler::Update(_) => upgrade_response(),
+        },
+        Err(response) => response,
+    }
+}
+
+// Entry point for http_request_update
+pub fn handle_update(request: HttpRequest) -> HttpResponse {
+    let mut parsed = ParsedRequest::parse(&request);
+    match find_route(&parsed) {
+        Ok(route) => {
+            let response = run(route, &mut parsed);
+            apply_cors(route, &parsed, response)
+        }
+        Err(response) => response,
+    }
+}
+
+// Re-renders all certified routes and recertifies them (init, post_upgrade, and timer)
+pub fn refresh_certified_routes() {
+    let bodies = ROUTES.iter()
+        .filter(|route| route.certified)
+        .filter_map(|route| match route.handler {
+            Handler::Query(handler) => {
+                let request = ParsedRequest {
+                    method: "GET".to_string(),
+                    path: route.path.to_string(),
+                    query: Vec::new(),
+                    headers: Vec::new(),
+                    body: Vec::new(),
+                    caller: None,
+                    api_key: None,
+                };
+                Some((route.path.to_string(), handler(&request).body.into_vec()))
+            }
+            Handler::Update(_) => None,
+        })
+        .collect();
+    certification::certify(bodies);
+}
+
+// --- OpenAPI ---
+
+fn openapi_operation(route: &Route) -> Value {
+    let mut operation = Map::new();
+    operation.insert("operationId".to_string(), json!(route.operation_id));
+    operation.insert("summary".to_string(), json!(route.summary));
+    if !route.params.is_empty() {
+        let params: Vec<Value> = route.params.iter()
+            .map(|param| json!({
+                "name": param.name,
+                "in": "query",
+                "description": param.description,
+                "required": param.required,
+                "schema": { "type": "string" },
+            }))
+            .collect();
+        operation.insert("parameters".to_string(), json!(params));
+    }
+    if let Some(request_schema) = route.request_schema {
+        operation.insert("requestBody".to_string(), json!({
+            "required": true,
+            "content": { "application/json": { "schema": request_schema() } },
+        }));
+    }
+    // Alternatives: the route's own auth (an empty requirement for public routes) or an API key
+    let mut security = match route.auth {
+        Auth::Public => vec![json!({})],
+        Auth::Session => vec![json!({ "sessionToken": [] })],
+        Auth::Signed => vec![json!({ "identityProof": [] })],
+    };
+    if let Some(scope) = route.api_key_scope {
+        security.push(json!({ "apiKey": [] }));
+        operation.insert("description".to_string(), json!(format!("Accepts partner API keys with the {} scope.", scope.as_str())));
+    }
+    if security.len() > 1 || route.auth != Auth::Public {
+        operation.insert("security".to_string(), json!(security));
+    }
+    operation.insert("responses".to_string(), json!({
+        route.response_status.to_string(): {
+            "description": "Success",
+            "content": { "application/json": { "schema": (route.response_schema)() } },
+        },
+        "default": {
+            "description": "Error",
+            "content": { "application/json": { "schema": {
+                "type": "object",
+                "properties": { "error": { "type": "string" } },
+            } } },
+        },
+    }));
+    Value::Object(operation)
+}
+
+pub fn openapi_document() -> Value {
+    let mut paths = Map::new();
+    for route in ROUTES {
+        let entry = paths.entry(route.path.to_string()).or_insert_with(|| json!({}));
+        entry[route.method.to_lowercase()] = openapi_operation(route);
+    }
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Darely Bot API",
+            "version": API_VERSION,
+            "description": format!("REST interface of the Darely Bot canister. Versioned routes live under {}.", API_PREFIX),
+        },
+        "paths": paths,
+        "components": {
+            "securitySchemes": {
+                "sessionToken": {
+                    "type": "http",
+                    "scheme": "bearer",
+                    "description": "Short-lived token returned by the create_session candid method.",
+                },
+                "apiKey": {
+                    "type": "apiKey",
+                    "in": "header",
+                    "name": "X-API-Key",
+                    "description": "Admin-issued partner key with scopes and a per-minute rate limit. Keyed requests are served as update calls.",
+                },
+                "identityProof": {
+                    "type": "apiKey",
+                    "in": "header",
+                    "name": "X-Identity-Proof",
+                    "description": "Base64 JSON proof: the caller's public key, its delegation chain (e.g. from Internet Identity), and a signature over the method, path, body, nonce and timestamp.",
+                },
+            },
+        },
+    })
+}
+
+fn openapi(_request: &ParsedRequest) -> HttpResponse {
+    json_response(200, &openapi_document())
+}
+
+fn openapi_schema() -> Value {
+    json!({ "type": "object", "description": "OpenAPI 3.0 document." })
+}
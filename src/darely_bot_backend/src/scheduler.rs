@@ -0,0 +1,194 @@
+use crate::history;
+use crate::outbox;
+use crate::ranking;
+use crate::repository::{self, UserRepo};
+use crate::state;
+use crate::submissions;
+use crate::types::{CompositeKey, HistoryEvent, StorablePrincipal, UserProfile};
+use candid::Principal;
+
+// --- Streak Expiry ---
+// Streaks decay once a player stops completing dares: when `streak_expiry_hours` is set in the
+// config, a timer resets the streak of anyone whose last completion (a submitted dare or an open
+// /done claim) is older than that. A streak kept by vouching restarts the clock too. A streak
+// freeze from the reward wheel is spent instead and restarts the clock, as in hardcore mode.
+// Hardcore players are left to hardcore.rs, which has its own lapse rules. Profiles from before
+// completions were timestamped count from when their streak last changed. Unset, streaks never
+// expire. A submission whose streak point waits for approval restarts the clock only once it is
+// approved, and /undo puts the clock back.
+//
+// The timer reads only the profiles that are due: EXPIRY_INDEX holds every profile whose streak
+// can expire under the time its clock last restarted, kept in step by every profile write. An
+// open /done claim isn't part of the key; it is checked when the profile comes up.
+
+pub const MAX_EXPIRY_HOURS: u32 = 365 * 24;
+
+pub fn validate_expiry(hours: u32) -> Result<(), String> {
+    if hours == 0 || hours > MAX_EXPIRY_HOURS {
+        return Err(format!("The streak expiry window must be between 1 and {} hours.", MAX_EXPIRY_HOURS));
+    }
+    Ok(())
+}
+
+// When the streak's clock last restarted, leaving out an open /done claim
+fn clock(profile: &UserProfile) -> u64 {
+    let vouched_at = profile.vouched_at.as_ref().and_then(|vouched| vouched.iter().max().copied());
+    profile.last_completed_at.or(profile.streak_reached_at).unwrap_or(0)
+        .max(profile.freeze_used_at.unwrap_or(0))
+        .max(vouched_at.unwrap_or(0))
+}
+
+fn last_completed(profile: &UserProfile, now: u64) -> u64 {
+    clock(profile).max(submissions::claimed_at(profile, now).unwrap_or(0))
+}
+
+fn expired(profile: &UserProfile, window_nanos: u64, now: u64) -> bool {
+    profile.streak > 0 && profile.hardcore_since.is_none() && now.saturating_sub(last_completed(profile, now)) >= window_nanos
+}
+
+fn expire(user: Principal, hours: u32, window_nanos: u64, now: u64) -> bool {
+    let outcome = state::update_profile(user, |profile| {
+        if !expired(profile, window_nanos, now) {
+            return Ok(None);
+        }
+        if let Some(freezes) = profile.streak_freezes.filter(|&freezes| freezes > 0) {
+            profile.streak_freezes = Some(freezes - 1);
+            profile.freeze_used_at = Some(now);
+            return Ok(Some((profile.streak, Some(freezes - 1))));
+        }
+        let streak_lost = profile.streak;
+        ranking::set_streak(profile, 0);
+        Ok(Some((streak_lost, None)))
+    });
+    let Ok(Some((streak, freezes_left))) = outcome else {
+        return false;
+    };
+    match freezes_left {
+        Some(freezes_left) => {
+            history::log_event(user, HistoryEvent::StreakFreezeUsed { streak });
+            outbox::queue(user, format!(
+                "🧊 {} hours without a dare, but a streak freeze saved your streak of {} ({} left). The clock starts again now.",
+                hours, streak, freezes_left
+            ), None);
+        }
+        None => {
+            history::log_event(user, HistoryEvent::StreakExpired { streak_lost: streak });
+            outbox::queue(user, format!(
+                "⌛ {} hours without a dare, so your streak of {} has expired. Start a new one with /dare.", hours, streak
+            ), None);
+        }
+    }
+    true
+}
+
+// How long until the streak expires under a window of `hours`; None when it can't expire
+pub fn time_left(profile: &UserProfile, hours: u32, now: u64) -> Option<u64> {
+    if profile.streak == 0 || profile.hardcore_since.is_some() {
+        return None;
+    }
+    let window_nanos = u64::from(hours) * 60 * 60 * 1_000_000_000;
+    Some(last_completed(profile, now).saturating_add(window_nanos).saturating_sub(now))
+}
+
+// Timer: expires stale streaks. Returns how many expired (or spent a freeze).
+pub fn run() -> u32 {
+    let Some(hours) = state::get_config().streak_expiry_hours else {
+        return 0;
+    };
+    let window_nanos = u64::from(hours) * 60 * 60 * 1_000_000_000;
+    let now = ic_cdk::api::time();
+    let cutoff = now.saturating_sub(window_nanos);
+    let due: Vec<(u64, Principal)> = state::EXPIRY_INDEX.with(|i| {
+        i.borrow().iter().take_while(|(key, _)| key.0 <= cutoff).map(|(key, _)| (key.0, key.1 .0)).collect()
+    });
+    let mut count = 0;
+    for (clock, user) in due {
+        // Entries left behind by a profile that was quarantined
+        if state::get_profile(user).and_then(|profile| expiry_clock(&profile)) != Some(clock) {
+            state::EXPIRY_INDEX.with(|i| i.borrow_mut().remove(&CompositeKey(clock, StorablePrincipal(user))));
+            continue;
+        }
+        if expire(user, hours, window_nanos, now) {
+            count += 1;
+        }
+    }
+    count
+}
+
+// --- Expiry Index ---
+
+// The index key's time for a profile whose streak can expire
+pub fn expiry_clock(profile: &UserProfile) -> Option<u64> {
+    (profile.streak > 0 && profile.hardcore_since.is_none()).then(|| clock(profile))
+}
+
+// Keeps the index in step with a profile write; `before` is None for a new or expiry-free profile
+pub fn reindex(user: Principal, before: Option<u64>, after: Option<u64>) {
+    if before == after {
+        return;
+    }
+    state::EXPIRY_INDEX.with(|i| {
+        let mut index = i.borrow_mut();
+        if let Some(clock) = before {
+            index.remove(&CompositeKey(clock, StorablePrincipal(user)));
+        }
+        if let Some(clock) = after {
+            index.insert(CompositeKey(clock, StorablePrincipal(user)), ());
+        }
+    });
+}
+
+// Indexes every profile when the index is empty (run on init and post_upgrade)
+pub fn rebuild_index() {
+    if state::EXPIRY_INDEX.with(|i| !i.borrow().is_empty()) {
+        return;
+    }
+    let mut entries = Vec::new();
+    repository::users().scan(None, &mut |user, profile| {
+        if let Some(clock) = expiry_clock(&profile) {
+            entries.push(CompositeKey(clock, StorablePrincipal(user)));
+        }
+        true
+    });
+    state::EXPIRY_INDEX.with(|i| {
+        let mut index = i.borrow_mut();
+        for key in entries {
+            index.insert(key, ());
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::DoneClaim;
+
+    const HOUR: u64 = 60 * 60 * 1_000_000_000;
+
+    #[test]
+    fn streaks_expire_after_the_window_unless_hardcore() {
+        let profile = UserProfile { streak: 3, last_completed_at: Some(10 * HOUR), ..Default::default() };
+        assert!(!expired(&profile, 24 * HOUR, 33 * HOUR));
+        assert!(expired(&profile, 24 * HOUR, 34 * HOUR));
+        let legacy = UserProfile { streak: 3, streak_reached_at: Some(10 * HOUR), ..Default::default() };
+        assert!(expired(&legacy, 24 * HOUR, 34 * HOUR));
+        let frozen = UserProfile { freeze_used_at: Some(20 * HOUR), ..profile.clone() };
+        assert!(!expired(&frozen, 24 * HOUR, 34 * HOUR));
+        let claimed = UserProfile {
+            current_dare_id: Some(4), done_claim: Some(DoneClaim { dare_id: 4, claimed_at: 20 * HOUR }), ..profile.clone()
+        };
+        assert!(!expired(&claimed, 24 * HOUR, 34 * HOUR));
+        let hardcore = UserProfile { hardcore_since: Some(0), ..profile.clone() };
+        assert!(!expired(&hardcore, 24 * HOUR, 34 * HOUR));
+        assert!(!expired(&UserProfile { streak: 0, ..profile.clone() }, 24 * HOUR, 34 * HOUR));
+    }
+
+    #[test]
+    fn index_keeps_only_streaks_that_can_expire() {
+        let profile = UserProfile { streak: 3, last_completed_at: Some(10 * HOUR), ..Default::default() };
+        assert_eq!(expiry_clock(&profile), Some(10 * HOUR));
+        assert_eq!(expiry_clock(&UserProfile { freeze_used_at: Some(20 * HOUR), ..profile.clone() }), Some(20 * HOUR));
+        assert_eq!(expiry_clock(&UserProfile { hardcore_since: Some(0), ..profile.clone() }), None);
+        assert_eq!(expiry_clock(&UserProfile { streak: 0, ..profile }), None);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::popularity;
+use crate::ratings;
+use crate::repository::{self, DareRepo};
+use crate::state;
+use crate::themes;
+use crate::types::{Dare, DareSource, Difficulty, SourceMix, UserProfile};
+
+// --- Weight Limits ---
+pub const DEFAULT_DARE_WEIGHT: u32 = 100;
+const MIN_DARE_WEIGHT: u32 = 1;
+const MAX_DARE_WEIGHT: u32 = 10_000;
+
+// --- Source Selection ---
+
+// Resolves the source mix for a chat (chat override first, then the global default)
+pub fn source_mix_for_chat(chat_id: Option<&str>) -> SourceMix {
+    chat_id
+        .and_then(state::get_chat_config)
+        .and_then(|chat_config| chat_config.source_mix)
+        .unwrap_or_else(|| state::get_config().source_mix)
+}
+
+// Picks which source to serve a dare from. Falls back to the LLM when no curated dares exist.
+pub fn choose_source(mix: &SourceMix, curated_available: bool, seed: u64) -> DareSource {
+    if !curated_available || mix.curated == 0 {
+        return DareSource::Llm;
+    }
+    if mix.llm == 0 {
+        return DareSource::Curated;
+    }
+    let total = mix.curated as u64 + mix.llm as u64;
+    if seed % total < mix.curated as u64 { DareSource::Curated } else { DareSource::Llm }
+}
+
+// --- Dare Selection ---
+
+// Collects repository dares of the given difficulty that `player` has unlocked, optionally
+// restricted to one source. Weights include the trending bonus from this week's likes and the
+// players' fun ratings.
+pub fn candidates(difficulty: &Difficulty, source: Option<DareSource>, player: &UserProfile) -> Vec<Dare> {
+    let weekly_likes = popularity::weekly_like_counts();
+    let theme_boosts = themes::active_boosts();
+    let rating_weights = ratings::weight_percents();
+    let mut candidates = Vec::new();
+    repository::dares().scan(&mut |mut dare| {
+        // Custom dares belong to the member they were written for, never to a pool
+        let pooled = source.map_or(dare.source != DareSource::Custom, |s| dare.source == s);
+        // Weight 0 keeps a dare out of every pool (quest-only steps, see quests.rs)
+        if &dare.difficulty != difficulty || !pooled || dare.weight == 0 || !unlocked(&dare, player) {
+            return true;
+        }
+        if let Some(&boost) = theme_boosts.get(&dare.id) {
+            dare.weight = themes::boosted_weight(dare.weight, boost);
+        }
+        if let Some(&percent) = rating_weights.get(&dare.id) {
+            dare.weight = (dare.weight as u64 * percent as u64 / 100).clamp(MIN_DARE_WEIGHT as u64, MAX_DARE_WEIGHT as u64) as u32;
+        }
+        let likes = weekly_likes.get(&dare.id).copied().unwrap_or(0);
+        dare.weight = dare.weight.saturating_add(popularity::weight_bonus(likes)).min(MAX_DARE_WEIGHT);
+        candidates.push(dare);
+        true
+    });
+    candidates
+}
+
+pub fn count_by_source(source: DareSource) -> usize {
+    let mut count = 0;
+    repository::dares().scan(&mut |dare| {
+        count += usize::from(dare.source == source);
+        true
+    });
+    count
+}
+
+// Weighted random pick: a dare with weight 200 is twice as likely as one with weight 100
+pub fn pick_weighted(candidates: &[Dare], seed: u64) -> Option<&Dare> {
+    let total: u64 = candidates.iter().map(|dare| dare.weight as u64).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut target = seed % total;
+    for dare in candidates {
+        if target < dare.weight as u64 {
+            return Some(dare);
+        }
+        target -= dare.weight as u64;
+    }
+    None
+}
+
+// --- Gating ---
+// Advanced dares can require a minimum level (derived from XP) or streak; players below either
+// never get them from the pools. Admin pins skip the check, since a pin names its player.
+
+pub const XP_PER_LEVEL: u64 = 100;
+const MAX_MIN_LEVEL: u32 = 1_000;
+const MAX_MIN_STREAK: u32 = 10_000;
+
+// Level 1 at 0 XP, then one level per XP_PER_LEVEL
+pub fn level(xp: u64) -> u32 {
+    (xp / XP_PER_LEVEL).min(u32::MAX as u64 - 1) as u32 + 1
+}
+
+pub fn unlocked(dare: &Dare, player: &UserProfile) -> bool {
+    dare.min_level.is_none_or(|min| level(player.xp.unwrap_or(0)) >= min) && dare.min_streak.is_none_or(|min| player.streak >= min)
+}
+
+pub fn validate_gating(min_level: Option<u32>, min_streak: Option<u32>) -> Result<(), String> {
+    if min_level.is_some_and(|min| min == 0 || min > MAX_MIN_LEVEL) {
+        return Err(format!("min_level must be 1-{}.", MAX_MIN_LEVEL));
+    }
+    if min_streak.is_some_and(|min| min == 0 || min > MAX_MIN_STREAK) {
+        return Err(format!("min_streak must be 1-{}.", MAX_MIN_STREAK));
+    }
+    Ok(())
+}
+
+// Replaces a dare's requirements (None removes one)
+pub fn set_gating(dare_id: u64, min_level: Option<u32>, min_streak: Option<u32>) -> Result<(), String> {
+    validate_gating(min_level, min_streak)?;
+    let dares = repository::dares();
+    let mut dare = dares.get(dare_id).ok_or_else(|| format!("Dare #{} not found.", dare_id))?;
+    dare.min_level = min_level;
+    dare.min_streak = min_streak;
+    dares.set(&dare)
+}
+
+pub fn describe_gating(min_level: Option<u32>, min_streak: Option<u32>) -> String {
+    match (min_level, min_streak) {
+        (None, None) => "open to everyone".to_string(),
+        (Some(level), None) => format!("level {}+", level),
+        (None, Some(streak)) => format!("streak {}+", streak),
+        (Some(level), Some(streak)) => format!("level {}+ and streak {}+", level, streak),
+    }
+}
+
+// --- Admin Weight Adjustments ---
+
+pub fn boosted_weight(weight: u32) -> u32 {
+    weight.saturating_mul(2).min(MAX_DARE_WEIGHT)
+}
+
+// Weight 0 (quest-only dares) stays out of the pools
+pub fn demoted_weight(weight: u32) -> u32 {
+    if weight == 0 {
+        return 0;
+    }
+    (weight / 2).max(MIN_DARE_WEIGHT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gated(min_level: Option<u32>, min_streak: Option<u32>) -> Dare {
+        Dare { id: 1, text: "Sing".to_string(), difficulty: Difficulty::Hard, source: DareSource::Curated, weight: 100, issued_by: None, hint: None, creator: None, created_at: None, min_level, min_streak }
+    }
+
+    fn player(xp: Option<u64>, streak: u32) -> UserProfile {
+        UserProfile { xp, streak, ..Default::default() }
+    }
+
+    #[test]
+    fn levels_start_at_one_and_climb_every_hundred_xp() {
+        assert_eq!(level(0), 1);
+        assert_eq!(level(XP_PER_LEVEL - 1), 1);
+        assert_eq!(level(XP_PER_LEVEL), 2);
+        assert_eq!(level(u64::MAX), u32::MAX);
+    }
+
+    #[test]
+    fn gated_dares_unlock_at_both_thresholds() {
+        assert!(unlocked(&gated(None, None), &player(None, 0)));
+        let dare = gated(Some(3), Some(5));
+        assert!(!unlocked(&dare, &player(Some(2 * XP_PER_LEVEL - 1), 5)));
+        assert!(!unlocked(&dare, &player(Some(2 * XP_PER_LEVEL), 4)));
+        assert!(unlocked(&dare, &player(Some(2 * XP_PER_LEVEL), 5)));
+        // Profiles from before XP count as level 1
+        assert!(!unlocked(&gated(Some(2), None), &player(None, 100)));
+    }
+
+    #[test]
+    fn gating_limits_are_validated_and_described() {
+        assert!(validate_gating(None, None).is_ok());
+        assert!(validate_gating(Some(MAX_MIN_LEVEL), Some(MAX_MIN_STREAK)).is_ok());
+        assert!(validate_gating(Some(0), None).is_err());
+        assert!(validate_gating(None, Some(MAX_MIN_STREAK + 1)).is_err());
+        assert_eq!(describe_gating(None, None), "open to everyone");
+        assert_eq!(describe_gating(Some(5), Some(10)), "level 5+ and streak 10+");
+    }
+}
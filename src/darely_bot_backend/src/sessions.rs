@@ -0,0 +1,132 @@
+use crate::state;
+use crate::types::{Session, SessionToken};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL;
+use base64::Engine;
+use candid::Principal;
+use sha2::{Digest, Sha256};
+
+// --- Session Tokens ---
+// Token format: base64url(session_id: u64 BE | expires_at: u64 BE) "." base64url(HMAC-SHA256(payload)).
+// The HMAC key is seeded once from raw_rand and never leaves the canister. Tokens are checked
+// against the stored Session too, so ending a session revokes its token immediately.
+
+const SESSION_TTL_NANOS: u64 = 60 * 60 * 1_000_000_000; // 1 hour
+const MAX_SESSIONS_PER_USER: usize = 5;
+const HMAC_BLOCK_SIZE: usize = 64;
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; HMAC_BLOCK_SIZE];
+    if key.len() > HMAC_BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner = Sha256::new();
+    inner.update(block.map(|b| b ^ 0x36));
+    inner.update(message);
+    let mut outer = Sha256::new();
+    outer.update(block.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+// Constant-time comparison so signature checks don't leak timing
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn secret() -> Vec<u8> {
+    state::SESSION_SECRET.with(|s| s.borrow().get().clone())
+}
+
+// Seeds the signing key from the management canister's randomness (no-op once set)
+pub async fn ensure_secret() -> Result<(), String> {
+    if !secret().is_empty() {
+        return Ok(());
+    }
+    let (random_bytes,) = ic_cdk::api::management_canister::main::raw_rand().await
+        .map_err(|(code, message)| format!("Failed to get randomness: {:?} {}", code, message))?;
+    state::SESSION_SECRET.with(|s| {
+        let mut cell = s.borrow_mut();
+        // Another call may have seeded it while we were awaiting
+        if cell.get().is_empty() {
+            cell.set(random_bytes).expect("Failed to store session secret");
+        }
+    });
+    Ok(())
+}
+
+fn sign(session_id: u64, expires_at: u64) -> String {
+    let mut payload = Vec::with_capacity(16);
+    payload.extend_from_slice(&session_id.to_be_bytes());
+    payload.extend_from_slice(&expires_at.to_be_bytes());
+    let signature = hmac_sha256(&secret(), &payload);
+    format!("{}.{}", BASE64_URL.encode(&payload), BASE64_URL.encode(signature))
+}
+
+// Creates a session for `user` and returns its signed token. Requires the secret to be seeded.
+pub fn create(user: Principal) -> SessionToken {
+    let now = ic_cdk::api::time();
+    let expires_at = now + SESSION_TTL_NANOS;
+    let session_id = state::SESSIONS.with(|sessions_ref| {
+        let mut sessions = sessions_ref.borrow_mut();
+        // Drop this user's expired sessions, and the oldest ones beyond the per-user cap
+        let mut own: Vec<u64> = sessions.iter()
+            .filter(|(_, session)| session.user == user)
+            .map(|(id, _)| id)
+            .collect();
+        own.retain(|id| {
+            let expired = sessions.get(id).is_none_or(|s| s.expires_at <= now);
+            if expired { sessions.remove(id); }
+            !expired
+        });
+        while own.len() >= MAX_SESSIONS_PER_USER {
+            sessions.remove(&own.remove(0));
+        }
+        // Sessions from before the counter existed are skipped over
+        let session_id = state::NEXT_SESSION_ID.with(|c| *c.borrow().get())
+            .max(sessions.last_key_value().map_or(0, |(id, _)| id + 1));
+        state::NEXT_SESSION_ID.with(|c| c.borrow_mut().set(session_id + 1)).expect("Failed to save next session id");
+        sessions.insert(session_id, Session { user, created_at: now, expires_at });
+        session_id
+    });
+    SessionToken { token: sign(session_id, expires_at), expires_at }
+}
+
+// Resolves a token to its user, checking signature, expiry, and that the session it was issued for
+// still exists
+pub fn verify(token: &str) -> Result<Principal, String> {
+    let invalid = || "Invalid session token.".to_string();
+    let (payload_b64, signature_b64) = token.split_once('.').ok_or_else(invalid)?;
+    let payload = BASE64_URL.decode(payload_b64).map_err(|_| invalid())?;
+    let signature = BASE64_URL.decode(signature_b64).map_err(|_| invalid())?;
+    let key = secret();
+    if payload.len() != 16 || key.is_empty() || !constant_time_eq(&hmac_sha256(&key, &payload), &signature) {
+        return Err(invalid());
+    }
+
+    let session_id = u64::from_be_bytes(payload[..8].try_into().unwrap());
+    let expires_at = u64::from_be_bytes(payload[8..].try_into().unwrap());
+    if ic_cdk::api::time() >= expires_at {
+        return Err("Session expired. Create a new one.".to_string());
+    }
+    let session = state::SESSIONS.with(|s| s.borrow().get(&session_id))
+        .filter(|session| session.expires_at == expires_at)
+        .ok_or_else(invalid)?;
+    Ok(session.user)
+}
+
+// Ends all of the user's sessions. Returns how many were removed.
+pub fn end_all(user: Principal) -> usize {
+    state::SESSIONS.with(|sessions_ref| {
+        let mut sessions = sessions_ref.borrow_mut();
+        let own: Vec<u64> = sessions.iter()
+            .filter(|(_, session)| session.user == user)
+            .map(|(id, _)| id)
+            .collect();
+        for id in &own {
+            sessions.remove(id);
+        }
+        own.len()
+    })
+}
@@ -0,0 +1,274 @@
+use crate::chat_scope;
+use crate::config;
+use crate::gallery;
+use crate::llm;
+use crate::state;
+use crate::types::{ChatConfig, DigestSchedule, SetupStage, SetupWizard, SourceMix, StorablePrincipal};
+use candid::Principal;
+
+// --- Group Setup ---
+// When Darely is added to a group it offers /setup, a short conversation that walks a group owner
+// through the chat's settings: the curated/LLM dare mix, whether streaks wait for a moderator's
+// approval, the weekly digest, and the language of LLM dares. Each /setup_reply answers the
+// current question; "skip" keeps the chat's current value and "default" clears it. The answers
+// collect in a per-admin wizard in stable memory (one chat at a time, dropped after
+// WIZARD_TIMEOUT of silence, "cancel" at any stage) and are written to the chat config on "save".
+// The dare mix and verification mode have admin-only setters, so a group owner relayed by the bot
+// is only asked about the digest and language.
+
+const WIZARD_TIMEOUT_NANOS: u64 = 30 * 60 * 1_000_000_000;
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+// Sent by the bot when it's installed in a group
+pub fn welcome() -> String {
+    "👋 Thanks for adding Darely! Send /dare to get a dare and /leaderboard to see the top streaks. Group owners can send /setup to tailor Darely to this chat.".to_string()
+}
+
+fn timed_out(wizard: &SetupWizard, now: u64) -> bool {
+    now.saturating_sub(wizard.updated_at) > WIZARD_TIMEOUT_NANOS
+}
+
+// The admin's open wizard; a timed-out one is removed
+fn wizard(admin: Principal, now: u64) -> Option<SetupWizard> {
+    let wizard = state::SETUP_WIZARDS.with(|w| w.borrow().get(&StorablePrincipal(admin)))?;
+    if timed_out(&wizard, now) {
+        discard_wizard(admin);
+        return None;
+    }
+    Some(wizard)
+}
+
+// Drops every timed-out wizard, including those of admins who never came back
+fn discard_timed_out(now: u64) {
+    state::SETUP_WIZARDS.with(|wizards_ref| {
+        let mut wizards = wizards_ref.borrow_mut();
+        let stale: Vec<StorablePrincipal> = wizards.iter()
+            .filter(|(_, wizard)| timed_out(wizard, now))
+            .map(|(admin, _)| admin)
+            .collect();
+        for admin in stale {
+            wizards.remove(&admin);
+        }
+    });
+}
+
+fn save_wizard(admin: Principal, mut wizard: SetupWizard, now: u64) {
+    wizard.updated_at = now;
+    state::SETUP_WIZARDS.with(|w| w.borrow_mut().insert(StorablePrincipal(admin), wizard));
+}
+
+fn discard_wizard(admin: Principal) {
+    state::SETUP_WIZARDS.with(|w| w.borrow_mut().remove(&StorablePrincipal(admin)));
+}
+
+// The questions asked, in order; `full` is false for a relayed group owner
+fn stages(full: bool) -> &'static [SetupStage] {
+    if full {
+        &[SetupStage::Mix, SetupStage::Verification, SetupStage::Digest, SetupStage::Language]
+    } else {
+        &[SetupStage::Digest, SetupStage::Language]
+    }
+}
+
+fn question(stage: SetupStage) -> &'static str {
+    match stage {
+        SetupStage::Mix => "Dare mix: send \"<curated> <llm>\" weights, e.g. \"3 1\" for three curated dares to every AI-written one.",
+        SetupStage::Verification => "Verification: \"moderated\" (streaks go up once a moderator approves the proof) or \"instant\" (right away, taken back if rejected).",
+        SetupStage::Digest => "Weekly digest: send \"<weekday> <hour>\" in UTC, e.g. \"mon 18\", or \"off\".",
+        SetupStage::Language => "Language for AI-written dares and hints, e.g. \"Spanish\".",
+        SetupStage::Confirm => "Send \"save\" to apply these settings or \"cancel\" to discard them.",
+    }
+}
+
+fn prompt(stage: SetupStage, full: bool) -> String {
+    let asked = stages(full);
+    match asked.iter().position(|&s| s == stage) {
+        Some(index) => format!(
+            "{}/{} {} (\"skip\" keeps the current setting, \"default\" clears it.)", index + 1, asked.len(), question(stage)
+        ),
+        None => question(stage).to_string(),
+    }
+}
+
+fn describe_digest(digest: Option<DigestSchedule>) -> String {
+    digest.map_or_else(|| "off".to_string(), |schedule| format!("{} {}:00 UTC", WEEKDAYS[schedule.weekday as usize % 7], schedule.hour))
+}
+
+fn summary(wizard: &SetupWizard, full: bool) -> String {
+    let draft = &wizard.draft;
+    let mut lines = vec![format!("⚙️ Settings for {}:", wizard.chat_id)];
+    if full {
+        let mix = draft.source_mix.as_ref().map_or_else(|| "default".to_string(), |mix| format!("{} curated : {} AI", mix.curated, mix.llm));
+        let verification = match draft.streak_on_approval {
+            Some(true) => "moderated",
+            Some(false) => "instant",
+            None => "default",
+        };
+        lines.push(format!("Dare mix: {}", mix));
+        lines.push(format!("Verification: {}", verification));
+    }
+    lines.push(format!("Weekly digest: {}", describe_digest(draft.digest)));
+    lines.push(format!("Language: {}", draft.language.as_deref().unwrap_or("English")));
+    lines.push(question(SetupStage::Confirm).to_string());
+    lines.join("\n")
+}
+
+fn parse_mix(text: &str) -> Result<SourceMix, String> {
+    let usage = || "Send two weights, e.g. \"3 1\" for three curated dares to every AI-written one.".to_string();
+    let weights: Vec<u32> = text.split_whitespace().map(str::parse).collect::<Result<_, _>>().map_err(|_| usage())?;
+    let [curated, llm] = weights[..] else {
+        return Err(usage());
+    };
+    let mix = SourceMix { curated, llm };
+    config::validate_mix(&mix)?;
+    Ok(mix)
+}
+
+fn parse_verification(text: &str) -> Result<bool, String> {
+    match text.to_lowercase().as_str() {
+        "moderated" => Ok(true),
+        "instant" => Ok(false),
+        _ => Err("Send \"moderated\" or \"instant\".".to_string()),
+    }
+}
+
+// "<weekday> <hour>" or "off"
+fn parse_digest(text: &str) -> Result<Option<DigestSchedule>, String> {
+    let usage = || "Send a weekday and a UTC hour, e.g. \"mon 18\", or \"off\".".to_string();
+    let text = text.to_lowercase();
+    if text == "off" {
+        return Ok(None);
+    }
+    let mut parts = text.split_whitespace();
+    let day = parts.next().ok_or_else(usage)?;
+    let weekday = WEEKDAYS.iter().position(|name| day.starts_with(name)).ok_or_else(usage)? as u8;
+    let hour = parts.next().and_then(|hour| hour.parse::<u8>().ok()).ok_or_else(usage)?;
+    if parts.next().is_some() {
+        return Err(usage());
+    }
+    let schedule = DigestSchedule { weekday, hour };
+    gallery::validate_digest(&schedule)?;
+    Ok(Some(schedule))
+}
+
+// Applies one answer to the draft; "default" has already been handled
+fn apply(draft: &mut ChatConfig, stage: SetupStage, answer: &str) -> Result<(), String> {
+    match stage {
+        SetupStage::Mix => draft.source_mix = Some(parse_mix(answer)?),
+        SetupStage::Verification => draft.streak_on_approval = Some(parse_verification(answer)?),
+        SetupStage::Digest => draft.digest = parse_digest(answer)?,
+        SetupStage::Language => {
+            llm::validate_language(answer)?;
+            draft.language = Some(answer.to_string());
+        }
+        SetupStage::Confirm => {}
+    }
+    Ok(())
+}
+
+fn clear(draft: &mut ChatConfig, stage: SetupStage) {
+    match stage {
+        SetupStage::Mix => draft.source_mix = None,
+        SetupStage::Verification => draft.streak_on_approval = None,
+        SetupStage::Digest => draft.digest = None,
+        SetupStage::Language => draft.language = None,
+        SetupStage::Confirm => {}
+    }
+}
+
+fn next(stage: SetupStage, full: bool) -> SetupStage {
+    let asked = stages(full);
+    asked.iter().position(|&s| s == stage)
+        .and_then(|index| asked.get(index + 1))
+        .copied()
+        .unwrap_or(SetupStage::Confirm)
+}
+
+// /setup: starts (or restarts) the conversation for `chat_id`. `full` is false for a group owner
+// relayed by the bot, who is only asked the owner-level questions.
+pub fn start(admin: Principal, chat_id: &str, full: bool) -> Result<String, String> {
+    let chat_key = chat_scope::require_chat_key(chat_id)?;
+    let now = ic_cdk::api::time();
+    discard_timed_out(now);
+    let draft = state::get_chat_config(&chat_key).unwrap_or_default();
+    let first = stages(full)[0];
+    save_wizard(admin, SetupWizard { chat_id: chat_key, stage: first, draft, updated_at: now }, now);
+    Ok(format!("⚙️ Let's set Darely up for this chat. Answer with /setup_reply; send \"cancel\" at any point to stop.\n{}", prompt(first, full)))
+}
+
+// /setup_reply <message> in `chat_id`: answers the current question and returns the next one
+pub fn reply(admin: Principal, chat_id: &str, message: &str, full: bool) -> Result<String, String> {
+    let chat_key = chat_scope::require_chat_key(chat_id)?;
+    let now = ic_cdk::api::time();
+    let mut wizard = wizard(admin, now).ok_or_else(|| "No setup in progress. Start with /setup in the group.".to_string())?;
+    if wizard.chat_id != chat_key {
+        return Err("Your setup is for another chat. Answer it there, or send /setup here to start over for this chat.".to_string());
+    }
+    let message = message.trim();
+    if message.eq_ignore_ascii_case("cancel") {
+        discard_wizard(admin);
+        return Ok("Setup cancelled; nothing was changed.".to_string());
+    }
+    if wizard.stage == SetupStage::Confirm {
+        if !message.eq_ignore_ascii_case("save") {
+            return Ok(summary(&wizard, full));
+        }
+        save(&wizard, full);
+        discard_wizard(admin);
+        return Ok("✅ Settings saved. Have fun!".to_string());
+    }
+    if message.eq_ignore_ascii_case("default") {
+        clear(&mut wizard.draft, wizard.stage);
+    } else if !message.eq_ignore_ascii_case("skip") {
+        apply(&mut wizard.draft, wizard.stage, message)?;
+    }
+    wizard.stage = next(wizard.stage, full);
+    let reply = if wizard.stage == SetupStage::Confirm { summary(&wizard, full) } else { prompt(wizard.stage, full) };
+    save_wizard(admin, wizard, now);
+    Ok(reply)
+}
+
+// Writes the wizard's fields onto the chat config as it is now, so a pause or other change made
+// during the conversation is kept. Without `full` the admin-only fields are left alone.
+fn save(wizard: &SetupWizard, full: bool) {
+    state::CHAT_CONFIGS.with(|configs_ref| {
+        let mut configs = configs_ref.borrow_mut();
+        let mut config = configs.get(&wizard.chat_id).unwrap_or_default();
+        if full {
+            config.source_mix = wizard.draft.source_mix.clone();
+            config.streak_on_approval = wizard.draft.streak_on_approval;
+        }
+        if config.digest != wizard.draft.digest {
+            config.digest_sent_at = None;
+        }
+        config.digest = wizard.draft.digest;
+        config.language = wizard.draft.language.clone();
+        configs.insert(wizard.chat_id.clone(), config);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_parse_or_explain() {
+        assert_eq!(parse_mix("3 1"), Ok(SourceMix { curated: 3, llm: 1 }));
+        assert!(parse_mix("0 0").is_err());
+        assert!(parse_mix("3").is_err());
+        assert_eq!(parse_verification("Moderated"), Ok(true));
+        assert_eq!(parse_digest("Monday 18"), Ok(Some(DigestSchedule { weekday: 1, hour: 18 })));
+        assert_eq!(parse_digest("off"), Ok(None));
+        assert!(parse_digest("mon 24").is_err());
+        assert!(parse_digest("someday 5").is_err());
+    }
+
+    #[test]
+    fn owners_skip_the_admin_questions() {
+        assert_eq!(next(SetupStage::Mix, true), SetupStage::Verification);
+        assert_eq!(next(SetupStage::Language, true), SetupStage::Confirm);
+        assert_eq!(stages(false)[0], SetupStage::Digest);
+        assert!(prompt(SetupStage::Digest, false).starts_with("1/2 "));
+        assert!(prompt(SetupStage::Digest, true).starts_with("3/4 "));
+    }
+}
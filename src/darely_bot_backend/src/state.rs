@@ -1,15 +1,99 @@
-use crate::types::{StorablePrincipal, UserProfile, Dare}; // Import types from local module
+use crate::types::{ChatUserKey, CompositeKey, StorablePrincipal, UserProfile, Dare, Config, ChatConfig, Submission, SubmissionDraft, HistoryEntry, PendingDare, CachedGroupMetadata, Suggestion, Session, ReviewTask, ModeratorStats, DareLikes, Campaign, CampaignScan, Enrollments, OutboundMessage, ApiKey, ICRC3Value, SizeHistogram, SystemBalances, Theme, Truth, PinnedDare, RewardTask, Appeal, DareRatings, LeaderboardSnapshot, LeaderboardBuild, InviteCode, RegistrationRequest, WaitlistEntry, StreakIndexKey, ExpiryIndexKey, EscalationIndexKey, PauseIndexKey, LedgerCheckpoint, Environment, FeatureMetrics, ChangelogEntry, CreatorStats, VouchRequest, BuddyLink, BuddyInvite, GlobalEvent, MarketplaceListing, Quest, QuestDraft, ImportJob, PrefillJob, LlmUsage, LlmUsageKey, RejectedDare, Duo, JournalEntry, ReplicatedMap, ReplicationState, ReadReplica, Incident, CorruptRecord, EntropyPool, SetupWizard}; // Import types from local module
+use crate::replication;
+use crate::repository::{self, UserRepo};
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
-use ic_stable_structures::{BTreeMap as StableBTreeMap, DefaultMemoryImpl, StableVec};
+use ic_stable_structures::{BTreeMap as StableBTreeMap, DefaultMemoryImpl, StableCell, StableVec, Storable};
+use candid::Principal;
 use std::cell::RefCell;
 
 // --- Memory Management ---
 pub type Memory = VirtualMemory<DefaultMemoryImpl>; // Make Memory type public
 
 // Define Memory IDs for different stable structures
-const USER_PROFILES_MEM_ID: MemoryId = MemoryId::new(0);
+pub const USER_PROFILES_MEM_ID: MemoryId = MemoryId::new(0);
 // Keep DARES_MEM_ID in case you want to log generated dares or have fallback static ones
-const DARES_MEM_ID: MemoryId = MemoryId::new(1);
+pub const DARES_MEM_ID: MemoryId = MemoryId::new(1);
+pub const CONFIG_MEM_ID: MemoryId = MemoryId::new(2);
+pub const CHAT_CONFIGS_MEM_ID: MemoryId = MemoryId::new(3);
+pub const SUBMISSIONS_MEM_ID: MemoryId = MemoryId::new(4);
+pub const SUBMISSION_DRAFTS_MEM_ID: MemoryId = MemoryId::new(5);
+pub const HISTORY_MEM_ID: MemoryId = MemoryId::new(6);
+pub const PENDING_DARES_MEM_ID: MemoryId = MemoryId::new(7);
+pub const GROUP_METADATA_MEM_ID: MemoryId = MemoryId::new(8);
+pub const SUGGESTIONS_MEM_ID: MemoryId = MemoryId::new(9);
+pub const SESSIONS_MEM_ID: MemoryId = MemoryId::new(10);
+pub const SESSION_SECRET_MEM_ID: MemoryId = MemoryId::new(11);
+pub const USED_NONCES_MEM_ID: MemoryId = MemoryId::new(12);
+pub const REVIEW_QUEUE_MEM_ID: MemoryId = MemoryId::new(13);
+pub const MODERATORS_MEM_ID: MemoryId = MemoryId::new(14);
+pub const DARE_LIKES_MEM_ID: MemoryId = MemoryId::new(15);
+pub const CAMPAIGNS_MEM_ID: MemoryId = MemoryId::new(16);
+pub const ENROLLMENTS_MEM_ID: MemoryId = MemoryId::new(17);
+pub const OUTBOX_MEM_ID: MemoryId = MemoryId::new(18);
+pub const API_KEYS_MEM_ID: MemoryId = MemoryId::new(19);
+pub const POINT_BALANCES_MEM_ID: MemoryId = MemoryId::new(20);
+pub const BLOCKS_MEM_ID: MemoryId = MemoryId::new(21);
+pub const LEDGER_ACCOUNTS_MEM_ID: MemoryId = MemoryId::new(22);
+pub const PROFILE_SIZES_MEM_ID: MemoryId = MemoryId::new(23);
+pub const CHAT_MEMBERS_MEM_ID: MemoryId = MemoryId::new(24);
+pub const ACCOUNT_LINKS_MEM_ID: MemoryId = MemoryId::new(25);
+pub const TRUTHS_MEM_ID: MemoryId = MemoryId::new(26);
+pub const THEMES_MEM_ID: MemoryId = MemoryId::new(27);
+pub const PINNED_DARES_MEM_ID: MemoryId = MemoryId::new(28);
+pub const CHAT_GALLERY_MEM_ID: MemoryId = MemoryId::new(29);
+pub const REWARD_TASKS_MEM_ID: MemoryId = MemoryId::new(30);
+pub const APPEALS_MEM_ID: MemoryId = MemoryId::new(31);
+pub const DARE_RATINGS_MEM_ID: MemoryId = MemoryId::new(32);
+pub const DISABLED_DARES_MEM_ID: MemoryId = MemoryId::new(33);
+pub const LEADERBOARD_MEM_ID: MemoryId = MemoryId::new(34);
+pub const LEADERBOARD_BUILD_MEM_ID: MemoryId = MemoryId::new(35);
+pub const INVITE_CODES_MEM_ID: MemoryId = MemoryId::new(36);
+pub const REGISTRATION_REQUESTS_MEM_ID: MemoryId = MemoryId::new(37);
+pub const WAITLIST_MEM_ID: MemoryId = MemoryId::new(38);
+pub const STREAK_INDEX_MEM_ID: MemoryId = MemoryId::new(39);
+pub const STREAK_COUNTS_MEM_ID: MemoryId = MemoryId::new(40);
+pub const ENVIRONMENT_MEM_ID: MemoryId = MemoryId::new(41);
+pub const DEMO_DARES_MEM_ID: MemoryId = MemoryId::new(42);
+pub const FEATURE_METRICS_MEM_ID: MemoryId = MemoryId::new(43);
+pub const CHANGELOG_MEM_ID: MemoryId = MemoryId::new(44);
+pub const CREATOR_STATS_MEM_ID: MemoryId = MemoryId::new(45);
+pub const VOUCH_REQUESTS_MEM_ID: MemoryId = MemoryId::new(46);
+pub const BUDDIES_MEM_ID: MemoryId = MemoryId::new(47);
+pub const BUDDY_INVITES_MEM_ID: MemoryId = MemoryId::new(48);
+pub const GLOBAL_EVENTS_MEM_ID: MemoryId = MemoryId::new(49);
+pub const EVENT_PARTICIPANTS_MEM_ID: MemoryId = MemoryId::new(50);
+pub const MARKETPLACE_MEM_ID: MemoryId = MemoryId::new(51);
+pub const CHAT_IMPORTS_MEM_ID: MemoryId = MemoryId::new(52);
+pub const QUESTS_MEM_ID: MemoryId = MemoryId::new(53);
+pub const QUEST_DRAFTS_MEM_ID: MemoryId = MemoryId::new(54);
+pub const IMPORT_JOBS_MEM_ID: MemoryId = MemoryId::new(55);
+pub const PREFILL_JOB_MEM_ID: MemoryId = MemoryId::new(56);
+pub const PREFILL_STOCK_MEM_ID: MemoryId = MemoryId::new(57);
+pub const LLM_USAGE_MEM_ID: MemoryId = MemoryId::new(58);
+pub const LLM_SPEND_SUMMARY_MEM_ID: MemoryId = MemoryId::new(59);
+pub const REJECTED_DARES_MEM_ID: MemoryId = MemoryId::new(60);
+pub const DUOS_MEM_ID: MemoryId = MemoryId::new(61);
+pub const DUO_MEMBERS_MEM_ID: MemoryId = MemoryId::new(62);
+pub const DUO_INVITES_MEM_ID: MemoryId = MemoryId::new(63);
+pub const REPLICATION_JOURNAL_MEM_ID: MemoryId = MemoryId::new(64);
+pub const REPLICATION_MEM_ID: MemoryId = MemoryId::new(65);
+pub const READ_REPLICAS_MEM_ID: MemoryId = MemoryId::new(66);
+pub const INCIDENTS_MEM_ID: MemoryId = MemoryId::new(67);
+pub const CORRUPT_RECORDS_MEM_ID: MemoryId = MemoryId::new(68);
+pub const ENTROPY_MEM_ID: MemoryId = MemoryId::new(69);
+pub const SETUP_WIZARDS_MEM_ID: MemoryId = MemoryId::new(70);
+pub const NEXT_SESSION_ID_MEM_ID: MemoryId = MemoryId::new(71);
+pub const DEMO_USERS_MEM_ID: MemoryId = MemoryId::new(72);
+pub const NEXT_CORRUPT_RECORD_ID_MEM_ID: MemoryId = MemoryId::new(73);
+pub const EXPIRY_INDEX_MEM_ID: MemoryId = MemoryId::new(74);
+pub const BANNED_USERS_MEM_ID: MemoryId = MemoryId::new(75);
+pub const ESCALATION_INDEX_MEM_ID: MemoryId = MemoryId::new(76);
+pub const NEXT_REJECTED_DARE_ID_MEM_ID: MemoryId = MemoryId::new(77);
+pub const PAUSE_INDEX_MEM_ID: MemoryId = MemoryId::new(78);
+pub const LEDGER_CHECKPOINT_MEM_ID: MemoryId = MemoryId::new(79);
+pub const REPLAYED_BALANCES_MEM_ID: MemoryId = MemoryId::new(80);
+pub const CAMPAIGN_SCAN_MEM_ID: MemoryId = MemoryId::new(81);
+pub const OUTBOX_LEASES_MEM_ID: MemoryId = MemoryId::new(82);
 
 thread_local! {
     // The memory manager is used to allocate virtual memory for stable structures.
@@ -24,11 +108,724 @@ thread_local! {
         )
     );
 
-    // Stable storage for Dares (currently unused by get_dare, but kept for structure)
-    // Potentially used for logging generated dares or as a fallback.
+    // Stable storage for Dares: curated dares added by admins and logged LLM dares.
+    // A dare's id is its index in the vector.
     pub static DARE_REPOSITORY: RefCell<StableVec<Dare, Memory>> = RefCell::new(
         StableVec::init(
              MEMORY_MANAGER.with(|m| m.borrow().get(DARES_MEM_ID)), // Get memory region
         ).expect("Failed to initialize stable dare repository")
     );
-}
\ No newline at end of file
+
+    // Global configuration
+    pub static CONFIG: RefCell<StableCell<Config, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CONFIG_MEM_ID)),
+            Config::default(),
+        ).expect("Failed to initialize stable config")
+    );
+
+    // Per-chat configuration overrides: chat id -> ChatConfig
+    pub static CHAT_CONFIGS: RefCell<StableBTreeMap<String, ChatConfig, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CHAT_CONFIGS_MEM_ID)),
+        )
+    );
+
+    // Submission records: submission id -> Submission
+    pub static SUBMISSIONS: RefCell<StableBTreeMap<u64, Submission, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(SUBMISSIONS_MEM_ID)),
+        )
+    );
+
+    // In-progress multi-part submissions: Principal -> SubmissionDraft
+    pub static SUBMISSION_DRAFTS: RefCell<StableBTreeMap<StorablePrincipal, SubmissionDraft, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(SUBMISSION_DRAFTS_MEM_ID)),
+        )
+    );
+
+    // Activity log: sequence number -> HistoryEntry
+    pub static HISTORY: RefCell<StableBTreeMap<u64, HistoryEntry, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(HISTORY_MEM_ID)),
+        )
+    );
+
+    // Dares awaiting admin confirmation after /add_dare: admin Principal -> PendingDare
+    pub static PENDING_DARES: RefCell<StableBTreeMap<StorablePrincipal, PendingDare, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(PENDING_DARES_MEM_ID)),
+        )
+    );
+
+    // Cached community registry metadata: chat id -> CachedGroupMetadata
+    pub static GROUP_METADATA: RefCell<StableBTreeMap<String, CachedGroupMetadata, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(GROUP_METADATA_MEM_ID)),
+        )
+    );
+
+    // Player dare suggestions awaiting review: suggestion id -> Suggestion
+    pub static SUGGESTIONS: RefCell<StableBTreeMap<u64, Suggestion, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(SUGGESTIONS_MEM_ID)),
+        )
+    );
+
+    // Web API sessions: session id -> Session
+    pub static SESSIONS: RefCell<StableBTreeMap<u64, Session, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(SESSIONS_MEM_ID)),
+        )
+    );
+
+    // HMAC key for session tokens (empty until seeded from raw_rand)
+    pub static SESSION_SECRET: RefCell<StableCell<Vec<u8>, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(SESSION_SECRET_MEM_ID)),
+            Vec::new(),
+        ).expect("Failed to initialize session secret")
+    );
+
+    // Next web API session id; ids are never reused, so an old token can't match a newer session
+    pub static NEXT_SESSION_ID: RefCell<StableCell<u64, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(NEXT_SESSION_ID_MEM_ID)),
+            0,
+        ).expect("Failed to initialize next session id")
+    );
+
+    // Replay protection for signed web requests: SHA-256(principal | nonce) -> expiry (nanos)
+    pub static USED_NONCES: RefCell<StableBTreeMap<[u8; 32], u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(USED_NONCES_MEM_ID)),
+        )
+    );
+
+    // Submissions awaiting moderator review: submission id -> ReviewTask
+    pub static REVIEW_QUEUE: RefCell<StableBTreeMap<u64, ReviewTask, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(REVIEW_QUEUE_MEM_ID)),
+        )
+    );
+
+    // Moderators and their review stats: Principal -> ModeratorStats
+    pub static MODERATORS: RefCell<StableBTreeMap<StorablePrincipal, ModeratorStats, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MODERATORS_MEM_ID)),
+        )
+    );
+
+    // Dare likes: dare id -> DareLikes
+    pub static DARE_LIKES: RefCell<StableBTreeMap<u64, DareLikes, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(DARE_LIKES_MEM_ID)),
+        )
+    );
+
+    // Re-engagement campaigns: campaign id -> Campaign
+    pub static CAMPAIGNS: RefCell<StableBTreeMap<u64, Campaign, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CAMPAIGNS_MEM_ID)),
+        )
+    );
+
+    // Campaign progress per user: Principal -> Enrollments
+    pub static ENROLLMENTS: RefCell<StableBTreeMap<StorablePrincipal, Enrollments, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(ENROLLMENTS_MEM_ID)),
+        )
+    );
+
+    // The campaign timer's progress through the profiles (see campaigns.rs)
+    pub static CAMPAIGN_SCAN: RefCell<StableCell<CampaignScan, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CAMPAIGN_SCAN_MEM_ID)),
+            CampaignScan::default(),
+        ).expect("Failed to initialize campaign scan")
+    );
+
+    // Direct messages queued for delivery by the OpenChat bot: message id -> OutboundMessage
+    pub static OUTBOX: RefCell<StableBTreeMap<u64, OutboundMessage, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(OUTBOX_MEM_ID)),
+        )
+    );
+
+    // Leased outbox messages the bot hasn't acknowledged yet: message id -> lease end
+    pub static OUTBOX_LEASES: RefCell<StableBTreeMap<u64, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(OUTBOX_LEASES_MEM_ID)),
+        )
+    );
+
+    // Partner API keys for the REST API: key id -> ApiKey
+    pub static API_KEYS: RefCell<StableBTreeMap<u64, ApiKey, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(API_KEYS_MEM_ID)),
+        )
+    );
+
+    // Points balances: Principal -> balance (derived from BLOCKS, kept for fast lookups)
+    pub static POINT_BALANCES: RefCell<StableBTreeMap<StorablePrincipal, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(POINT_BALANCES_MEM_ID)),
+        )
+    );
+
+    // ICRC-3 block log of every points mint, burn and transfer: block index -> block
+    pub static BLOCKS: RefCell<StableBTreeMap<u64, ICRC3Value, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(BLOCKS_MEM_ID)),
+        )
+    );
+
+    // Ledger system accounts (treasury, escrow, burn)
+    pub static LEDGER_ACCOUNTS: RefCell<StableCell<SystemBalances, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(LEDGER_ACCOUNTS_MEM_ID)),
+            SystemBalances::default(),
+        ).expect("Failed to initialize ledger accounts")
+    );
+
+    // How far the ledger self-audit has replayed the block log (see ledger.rs)
+    pub static LEDGER_CHECKPOINT: RefCell<StableCell<LedgerCheckpoint, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(LEDGER_CHECKPOINT_MEM_ID)),
+            LedgerCheckpoint::default(),
+        ).expect("Failed to initialize ledger checkpoint")
+    );
+
+    // User balances as replayed from the block log up to the checkpoint
+    pub static REPLAYED_BALANCES: RefCell<StableBTreeMap<StorablePrincipal, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(REPLAYED_BALANCES_MEM_ID)),
+        )
+    );
+
+    // Encoded sizes of user profile writes
+    pub static PROFILE_SIZES: RefCell<StableCell<SizeHistogram, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(PROFILE_SIZES_MEM_ID)),
+            SizeHistogram::default(),
+        ).expect("Failed to initialize profile size histogram")
+    );
+
+    // Users seen in each chat: (chat id, user) -> last activity (nanos)
+    pub static CHAT_MEMBERS: RefCell<StableBTreeMap<ChatUserKey, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CHAT_MEMBERS_MEM_ID)),
+        )
+    );
+
+    // Identities linked to another account: (identity kind, principal) -> account (see accounts.rs)
+    pub static ACCOUNT_LINKS: RefCell<StableBTreeMap<CompositeKey<String, StorablePrincipal>, StorablePrincipal, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(ACCOUNT_LINKS_MEM_ID)),
+        )
+    );
+
+    // Truth-or-Dare question bank: id -> truth
+    pub static TRUTHS: RefCell<StableBTreeMap<u64, Truth, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(TRUTHS_MEM_ID)),
+        )
+    );
+
+    // Seasonal themes: id -> theme
+    pub static THEMES: RefCell<StableBTreeMap<u64, Theme, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(THEMES_MEM_ID)),
+        )
+    );
+
+    // Pending pinned assignments: user (anonymous = everyone) -> pin (see pins.rs)
+    pub static PINNED_DARES: RefCell<StableBTreeMap<StorablePrincipal, PinnedDare, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(PINNED_DARES_MEM_ID)),
+        )
+    );
+
+    // Approved submissions per chat: (chat id, submission id) -> approval time (see gallery.rs)
+    pub static CHAT_GALLERY: RefCell<StableBTreeMap<CompositeKey<String, u64>, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CHAT_GALLERY_MEM_ID)),
+        )
+    );
+
+    // Bonus tasks handed out by the reward wheel: id -> task
+    pub static REWARD_TASKS: RefCell<StableBTreeMap<u64, RewardTask, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(REWARD_TASKS_MEM_ID)),
+        )
+    );
+
+    // Appeals against rejected submissions: submission id -> appeal
+    pub static APPEALS: RefCell<StableBTreeMap<u64, Appeal, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(APPEALS_MEM_ID)),
+        )
+    );
+
+    // Aggregated difficulty/fun survey answers: dare id -> totals
+    pub static DARE_RATINGS: RefCell<StableBTreeMap<u64, DareRatings, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(DARE_RATINGS_MEM_ID)),
+        )
+    );
+
+    // Dares taken out of selection: (chat id, "" for all chats; dare id) -> disabled at
+    pub static DISABLED_DARES: RefCell<StableBTreeMap<CompositeKey<String, u64>, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(DISABLED_DARES_MEM_ID)),
+        )
+    );
+
+    // The latest published leaderboard snapshot
+    pub static LEADERBOARD: RefCell<StableCell<LeaderboardSnapshot, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(LEADERBOARD_MEM_ID)),
+            LeaderboardSnapshot::default(),
+        ).expect("Failed to initialize leaderboard snapshot")
+    );
+
+    // The snapshot scan in progress, so it resumes across ticks and upgrades
+    pub static LEADERBOARD_BUILD: RefCell<StableCell<LeaderboardBuild, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(LEADERBOARD_BUILD_MEM_ID)),
+            LeaderboardBuild::default(),
+        ).expect("Failed to initialize leaderboard build")
+    );
+
+    // Invite codes for invite-only registration: code -> issuer and uses
+    pub static INVITE_CODES: RefCell<StableBTreeMap<String, InviteCode, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(INVITE_CODES_MEM_ID)),
+        )
+    );
+
+    // Registrations waiting for admin approval, keyed by user
+    pub static REGISTRATION_REQUESTS: RefCell<StableBTreeMap<StorablePrincipal, RegistrationRequest, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(REGISTRATION_REQUESTS_MEM_ID)),
+        )
+    );
+
+    // Users waiting for a spot under the user cap: sequence number (admission order) -> entry
+    pub static WAITLIST: RefCell<StableBTreeMap<u64, WaitlistEntry, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(WAITLIST_MEM_ID)),
+        )
+    );
+
+    // Every profile in leaderboard order, for rank lookups without a full scan
+    pub static STREAK_INDEX: RefCell<StableBTreeMap<StreakIndexKey, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(STREAK_INDEX_MEM_ID)),
+        )
+    );
+
+    // Profiles whose streak can expire, by when their streak's clock last restarted
+    pub static EXPIRY_INDEX: RefCell<StableBTreeMap<ExpiryIndexKey, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(EXPIRY_INDEX_MEM_ID)),
+        )
+    );
+
+    // Profiles with an escalation offered or taken, by its deadline
+    pub static ESCALATION_INDEX: RefCell<StableBTreeMap<EscalationIndexKey, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(ESCALATION_INDEX_MEM_ID)),
+        )
+    );
+
+    // Chats paused for a set time, by when the pause ends
+    pub static PAUSE_INDEX: RefCell<StableBTreeMap<PauseIndexKey, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(PAUSE_INDEX_MEM_ID)),
+        )
+    );
+
+    // Users per streak value: streak -> count
+    pub static STREAK_COUNTS: RefCell<StableBTreeMap<u32, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(STREAK_COUNTS_MEM_ID)),
+        )
+    );
+
+    // Deployment environment from the last install or upgrade that named one
+    pub static ENVIRONMENT: RefCell<StableCell<Environment, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(ENVIRONMENT_MEM_ID)),
+            Environment::default(),
+        ).expect("Failed to initialize stable environment")
+    );
+
+    // Dares added by seed_demo_data: dare id -> seeded at
+    pub static DEMO_DARES: RefCell<StableBTreeMap<u64, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(DEMO_DARES_MEM_ID)),
+        )
+    );
+
+    // Users banned by an admin: principal -> banned at (see bans.rs)
+    pub static BANNED_USERS: RefCell<StableBTreeMap<StorablePrincipal, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(BANNED_USERS_MEM_ID)),
+        )
+    );
+
+    // Users added by seed_demo_data: principal -> seeded at
+    pub static DEMO_USERS: RefCell<StableBTreeMap<StorablePrincipal, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(DEMO_USERS_MEM_ID)),
+        )
+    );
+
+    // Rollout metrics per feature name (see features.rs)
+    pub static FEATURE_METRICS: RefCell<StableBTreeMap<String, FeatureMetrics, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(FEATURE_METRICS_MEM_ID)),
+        )
+    );
+
+    // Release notes: entry id -> entry
+    pub static CHANGELOG: RefCell<StableBTreeMap<u64, ChangelogEntry, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CHANGELOG_MEM_ID)),
+        )
+    );
+
+    // Dare creators: creator -> dares created and completions by others
+    pub static CREATOR_STATS: RefCell<StableBTreeMap<StorablePrincipal, CreatorStats, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CREATOR_STATS_MEM_ID)),
+        )
+    );
+
+    // Open vouch requests: user -> request
+    pub static VOUCH_REQUESTS: RefCell<StableBTreeMap<StorablePrincipal, VouchRequest, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(VOUCH_REQUESTS_MEM_ID)),
+        )
+    );
+
+    // Buddy pairs, stored under both users: user -> their side of the pair
+    pub static BUDDIES: RefCell<StableBTreeMap<StorablePrincipal, BuddyLink, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(BUDDIES_MEM_ID)),
+        )
+    );
+
+    // Pending buddy requests: invitee -> invite
+    pub static BUDDY_INVITES: RefCell<StableBTreeMap<StorablePrincipal, BuddyInvite, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(BUDDY_INVITES_MEM_ID)),
+        )
+    );
+
+    // Global events by id
+    pub static GLOBAL_EVENTS: RefCell<StableBTreeMap<u64, GlobalEvent, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(GLOBAL_EVENTS_MEM_ID)),
+        )
+    );
+
+    // (event id, user) -> dares the user contributed to the event
+    pub static EVENT_PARTICIPANTS: RefCell<StableBTreeMap<CompositeKey<u64, StorablePrincipal>, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(EVENT_PARTICIPANTS_MEM_ID)),
+        )
+    );
+
+    // Marketplace listings by id
+    pub static MARKETPLACE: RefCell<StableBTreeMap<u64, MarketplaceListing, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MARKETPLACE_MEM_ID)),
+        )
+    );
+
+    // (chat id, dare id) -> listing the chat imported the dare from
+    pub static CHAT_IMPORTS: RefCell<StableBTreeMap<CompositeKey<String, u64>, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CHAT_IMPORTS_MEM_ID)),
+        )
+    );
+
+    // Questlines by id
+    pub static QUESTS: RefCell<StableBTreeMap<u64, Quest, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(QUESTS_MEM_ID)),
+        )
+    );
+
+    // Questlines being built through /create_quest: admin -> draft
+    pub static QUEST_DRAFTS: RefCell<StableBTreeMap<StorablePrincipal, QuestDraft, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(QUEST_DRAFTS_MEM_ID)),
+        )
+    );
+
+    // Dare lists fetched by /import_url, imported a chunk per tick: job id -> job
+    pub static IMPORT_JOBS: RefCell<StableBTreeMap<u64, ImportJob, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(IMPORT_JOBS_MEM_ID)),
+        )
+    );
+
+    // The LLM prefill batch in progress (see prefill.rs)
+    pub static PREFILL_JOB: RefCell<StableCell<PrefillJob, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(PREFILL_JOB_MEM_ID)),
+            PrefillJob::default(),
+        ).expect("Failed to initialize prefill job")
+    );
+
+    // Pre-generated LLM dares not yet served as a fresh dare: dare id -> stocked at
+    pub static PREFILL_STOCK: RefCell<StableBTreeMap<u64, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(PREFILL_STOCK_MEM_ID)),
+        )
+    );
+
+    // LLM spend: (UTC day, (feature, chat)) -> usage
+    pub static LLM_USAGE: RefCell<StableBTreeMap<LlmUsageKey, LlmUsage, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(LLM_USAGE_MEM_ID)),
+        )
+    );
+
+    // Last month (year * 12 + month - 1) whose LLM spend summary went out; 0 = none yet
+    pub static LLM_SPEND_SUMMARY: RefCell<StableCell<u64, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(LLM_SPEND_SUMMARY_MEM_ID)),
+            0,
+        ).expect("Failed to initialize LLM spend summary")
+    );
+
+    // LLM dares held back by the safety check, for admin review (see safety.rs)
+    pub static REJECTED_DARES: RefCell<StableBTreeMap<u64, RejectedDare, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(REJECTED_DARES_MEM_ID)),
+        )
+    );
+
+    // Next rejected sample id; ids aren't reused after a sample is dismissed or approved
+    pub static NEXT_REJECTED_DARE_ID: RefCell<StableCell<u64, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(NEXT_REJECTED_DARE_ID_MEM_ID)),
+            0,
+        ).expect("Failed to initialize next rejected dare id")
+    );
+
+    // Shared-streak duos by id (see duos.rs)
+    pub static DUOS: RefCell<StableBTreeMap<u64, Duo, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(DUOS_MEM_ID)),
+        )
+    );
+
+    // user -> the duo they belong to
+    pub static DUO_MEMBERS: RefCell<StableBTreeMap<StorablePrincipal, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(DUO_MEMBERS_MEM_ID)),
+        )
+    );
+
+    // Pending duo requests: invitee -> invite
+    pub static DUO_INVITES: RefCell<StableBTreeMap<StorablePrincipal, BuddyInvite, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(DUO_INVITES_MEM_ID)),
+        )
+    );
+
+    // Writes not yet pushed to the standby and read replicas, by sequence number (see replication.rs)
+    pub static REPLICATION_JOURNAL: RefCell<StableBTreeMap<u64, JournalEntry, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(REPLICATION_JOURNAL_MEM_ID)),
+        )
+    );
+
+    pub static REPLICATION: RefCell<StableCell<ReplicationState, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(REPLICATION_MEM_ID)),
+            ReplicationState::default(),
+        ).expect("Failed to initialize replication state")
+    );
+
+    // Registered read replicas by canister id (see replication.rs)
+    pub static READ_REPLICAS: RefCell<StableBTreeMap<StorablePrincipal, ReadReplica, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(READ_REPLICAS_MEM_ID)),
+        )
+    );
+
+    // Unexpected errors by signature (see incidents.rs)
+    pub static INCIDENTS: RefCell<StableBTreeMap<String, Incident, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(INCIDENTS_MEM_ID)),
+        )
+    );
+
+    // Records that didn't decode, moved out of their maps (see quarantine.rs)
+    pub static CORRUPT_RECORDS: RefCell<StableBTreeMap<u64, CorruptRecord, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CORRUPT_RECORDS_MEM_ID)),
+        )
+    );
+
+    // Next quarantined record id; ids aren't reused after delete_corrupt_record
+    pub static NEXT_CORRUPT_RECORD_ID: RefCell<StableCell<u64, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(NEXT_CORRUPT_RECORD_ID_MEM_ID)),
+            0,
+        ).expect("Failed to initialize next corrupt record id")
+    );
+
+    // Seed and draw counter for dare, truth and reward picks (see randomness.rs)
+    pub static ENTROPY: RefCell<StableCell<EntropyPool, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(ENTROPY_MEM_ID)),
+            EntropyPool::default(),
+        ).expect("Failed to initialize the entropy pool")
+    );
+
+    // Group setups in progress through /setup: admin -> wizard
+    pub static SETUP_WIZARDS: RefCell<StableBTreeMap<StorablePrincipal, SetupWizard, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(SETUP_WIZARDS_MEM_ID)),
+        )
+    );
+}
+
+// --- State Helpers ---
+
+pub fn get_config() -> Config {
+    CONFIG.with(|c| c.borrow().get().clone())
+}
+
+// Applies a mutation to the stored config and writes it back
+pub fn update_config<F: FnOnce(&mut Config)>(f: F) {
+    CONFIG.with(|c| {
+        let mut cell = c.borrow_mut();
+        let mut config = cell.get().clone();
+        f(&mut config);
+        cell.set(config).expect("Failed to write stable config");
+    });
+}
+
+pub fn get_chat_config(chat_id: &str) -> Option<ChatConfig> {
+    CHAT_CONFIGS.with(|c| c.borrow().get(&chat_id.to_string()))
+}
+
+// --- Profile Reads ---
+// Go through the user repository (see repository.rs), whose registration filter means
+// unregistered users cost no stable memory read.
+
+pub fn get_profile(user: Principal) -> Option<UserProfile> {
+    repository::users().get(user)
+}
+
+pub fn is_registered(user: Principal) -> bool {
+    repository::users().contains(user)
+}
+
+// --- Profile Writes ---
+// Profiles carry a version that every write bumps. A handler that read a profile before an
+// await must not write that copy back afterwards: update_profile re-reads the current profile
+// and applies the change on top of it. A message runs without interruption until its next await,
+// so nothing else can write between that read and the write; only `f` itself could, by writing
+// the same profile through another path. That nested write would be lost, so the outer write is
+// refused instead.
+
+pub fn profile_version(profile: &UserProfile) -> u64 {
+    profile.version.unwrap_or(0)
+}
+
+// Writes the profile with a bumped version unless the stored one no longer has `expected_version`
+pub fn put_profile_if_unchanged(user: &StorablePrincipal, profile: UserProfile, expected_version: u64) -> bool {
+    repository::users().put_if_unchanged(user.0, profile, expected_version)
+}
+
+// Applies `f` to the user's current profile and writes it back. Nothing is written when `f`
+// returns an error.
+pub fn update_profile<R>(user: Principal, f: impl FnOnce(&mut UserProfile) -> Result<R, String>) -> Result<R, String> {
+    update_profile_in(&repository::users(), user, f)
+}
+
+pub fn update_profile_in<R>(users: &impl UserRepo, user: Principal, f: impl FnOnce(&mut UserProfile) -> Result<R, String>) -> Result<R, String> {
+    let mut profile = users.get(user)
+        .ok_or_else(|| "User not found. Please /register first.".to_string())?;
+    let version = profile_version(&profile);
+    let result = f(&mut profile)?;
+    if !users.put_if_unchanged(user, profile, version) {
+        return Err("Your profile changed while this command was running. Please try again.".to_string());
+    }
+    Ok(result)
+}
+
+// Every submission write goes through these two, so the replication journal sees it
+pub fn put_submission(submission: Submission) {
+    let id = submission.id;
+    SUBMISSIONS.with(|s| s.borrow_mut().insert(id, submission));
+    replication::note(ReplicatedMap::Submissions, &id);
+}
+
+pub fn remove_submission(id: u64) -> Option<Submission> {
+    let removed = SUBMISSIONS.with(|s| s.borrow_mut().remove(&id));
+    replication::note(ReplicatedMap::Submissions, &id);
+    removed
+}
+
+// Next free submission id (ids are sequential)
+pub fn next_submission_id() -> u64 {
+    SUBMISSIONS.with(|s| s.borrow().last_key_value().map_or(0, |(id, _)| id + 1))
+}
+
+// --- Composite Key Queries ---
+
+// Key parts that can start a range scan
+pub trait KeyPart: Storable + Ord + Clone {
+    fn min_value() -> Self;
+}
+
+impl KeyPart for String {
+    fn min_value() -> Self { String::new() }
+}
+
+impl KeyPart for u64 {
+    fn min_value() -> Self { 0 }
+}
+
+impl KeyPart for StorablePrincipal {
+    // Principals order by their bytes, so the empty principal sorts first
+    fn min_value() -> Self { StorablePrincipal(Principal::from_slice(&[])) }
+}
+
+// Entries whose key starts with `first` (e.g. all users of a chat), ordered by the second part
+pub fn range_by_first<A, B, V>(map: &StableBTreeMap<CompositeKey<A, B>, V, Memory>, first: &A) -> Vec<(B, V)>
+where
+    A: Storable + Ord + Clone,
+    B: KeyPart,
+    V: Storable,
+{
+    map.range(CompositeKey(first.clone(), B::min_value())..)
+        .take_while(|(key, _)| &key.0 == first)
+        .map(|(key, value)| (key.1, value))
+        .collect()
+}
+
+// Entries whose key ends with `second` (e.g. all chats of a user). This scans the whole map;
+// keep a mirrored (second, first) map where it runs often.
+pub fn filter_by_second<A, B, V>(map: &StableBTreeMap<CompositeKey<A, B>, V, Memory>, second: &B) -> Vec<(A, V)>
+where
+    A: Storable + Ord + Clone,
+    B: Storable + Ord + Clone,
+    V: Storable,
+{
+    map.iter()
+        .filter(|(key, _)| &key.1 == second)
+        .map(|(key, value)| (key.0, value))
+        .collect()
+}
@@ -0,0 +1,355 @@
+use crate::buddies;
+use crate::campaigns;
+use crate::duos;
+use crate::escalation;
+use crate::features;
+use crate::hardcore;
+use crate::history;
+use crate::moderation;
+use crate::quests;
+use crate::ranking;
+use crate::rewards;
+use crate::state;
+use crate::types::{DoneClaim, HistoryEvent, StorablePrincipal, Submission, SubmissionDraft, UserProfile};
+use candid::Principal;
+
+// --- Limits ---
+const MAX_PROOF_PART_LEN: usize = 1000;
+const MAX_DRAFT_PARTS: usize = 10;
+const DRAFT_TIMEOUT_NANOS: u64 = 30 * 60 * 1_000_000_000; // Drafts expire after 30 minutes of inactivity
+const UNDO_WINDOW_NANOS: u64 = 5 * 60 * 1_000_000_000;
+const WEEK_NANOS: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+const MAX_UNDOS_PER_WEEK: usize = 2;
+const CLAIM_WINDOW_HOURS: u64 = 24;
+const CLAIM_WINDOW_NANOS: u64 = CLAIM_WINDOW_HOURS * 60 * 60 * 1_000_000_000;
+
+// Experience per dare submitted; taken back if the submission is undone or rejected
+pub const DARE_XP: u64 = 10;
+
+// DARE_XP plus any escalation bonus
+pub fn xp_of(submission: &Submission) -> u64 {
+    DARE_XP + submission.escalation_xp.unwrap_or(0)
+}
+
+// --- Submission Recording ---
+
+// Whether new submissions for a dare from `chat_id` leave the streak to their approval (see
+// moderation.rs). The chat's setting wins over the global one.
+pub fn streak_waits_for_review(chat_id: Option<&str>) -> bool {
+    chat_id.and_then(state::get_chat_config).and_then(|config| config.streak_on_approval)
+        .or(state::get_config().streak_on_approval)
+        .unwrap_or(true)
+}
+
+// The streak part of the reply to the user's latest submission
+pub fn streak_note(user: Principal, streak: u32) -> String {
+    let profile = state::get_profile(user);
+    let pending = profile.as_ref()
+        .and_then(|profile| profile.last_submission_id)
+        .and_then(|id| state::SUBMISSIONS.with(|s| s.borrow().get(&id)))
+        .is_some_and(|submission| submission.streak_pending == Some(true));
+    if pending {
+        return format!("Your streak of {} goes up once a moderator approves it.", streak);
+    }
+    let redeemed = profile.map(|profile| profile.redeemed_milestones).unwrap_or_default();
+    match rewards::milestone_note(streak, &redeemed) {
+        Some(note) => format!("Your new streak is {}. {}", streak, note),
+        None => format!("Your new streak is {}.", streak),
+    }
+}
+
+// The streak after undoing `submission`: only a point it already counted is taken back
+fn streak_after_undo(streak: u32, submission: &Submission) -> u32 {
+    if submission.streak_pending == Some(true) {
+        streak
+    } else {
+        streak.saturating_sub(1)
+    }
+}
+
+// Stores a submission and leaves the streak point to its approval, or increments the streak
+// right away when streak_on_approval is turned off for the dare's chat or globally. The buddy
+// DM waits for the point too (see moderation.rs). Returns the streak.
+pub fn record_submission(user: Principal, proof_parts: Vec<String>) -> Result<u32, String> {
+    let submission_id = state::next_submission_id();
+    let now = ic_cdk::api::time();
+    let (streak, dare_id, chat_id, hint_used, dare_text, escalated, claimed_at, streak_pending, previous_completed_at) = state::update_profile(user, |profile| {
+        // NOTE: Verification logic is simplified; the proof is stored but not checked.
+        let claimed_at = claimed_at(profile, now);
+        let streak_pending = streak_waits_for_review(profile.current_dare_chat.as_deref());
+        profile.done_claim = None;
+        if !streak_pending {
+            ranking::set_streak(profile, profile.streak + 1);
+        }
+        profile.xp = Some(profile.xp.unwrap_or(0) + DARE_XP);
+        let dare_id = profile.current_dare_id.take();
+        let chat_id = profile.current_dare_chat.take();
+        let dare_text = profile.current_dare_text.take().filter(|_| dare_id.is_some());
+        let hint_used = dare_id.is_some() && profile.hint_dare_id.take() == dare_id;
+        profile.last_submission_id = Some(submission_id);
+        quests::note_submission(profile, dare_id, submission_id);
+        // A pending point restarts the expiry clock once approved (see scheduler.rs)
+        let previous_completed_at = profile.last_completed_at;
+        if !streak_pending {
+            profile.last_completed_at = Some(claimed_at.unwrap_or(now));
+        }
+        // Only the penalty dare lifts the penalty; the fallback text (and profiles from before the
+        // id was kept) have no id, and nothing else can be assigned while it is open
+        if profile.penalty_dare_id.is_none_or(|id| dare_id == Some(id)) {
+            profile.penalty_since = None;
+            profile.penalty_dare_id = None;
+        }
+        let escalated = escalation::settle(profile, dare_id, claimed_at.unwrap_or(now), now);
+        Ok((profile.streak, dare_id, chat_id, hint_used, dare_text, escalated, claimed_at, streak_pending, previous_completed_at))
+    })?;
+
+    let submission = Submission {
+        id: submission_id,
+        user,
+        proof_parts,
+        submitted_at: now,
+        dare_id,
+        review: None,
+        chat_id,
+        hint_used: Some(hint_used),
+        reward_task_id: None,
+        rating: None,
+        dare_text,
+        escalation_xp: escalated.map(|(_, bonus_xp)| bonus_xp),
+        completed_at: claimed_at,
+        streak_pending: streak_pending.then_some(true),
+        previous_completed_at: previous_completed_at.filter(|_| !streak_pending),
+    };
+    state::put_submission(submission);
+    moderation::enqueue(submission_id);
+    history::log_event(user, HistoryEvent::DareSubmitted { submission_id, dare_id, hint_used: Some(hint_used) });
+    if let Some((rung, bonus_xp)) = escalated {
+        history::log_event(user, HistoryEvent::EscalationCompleted { rung, bonus_xp });
+    }
+    features::note_completion(user);
+    if !streak_pending {
+        buddies::note_completion(user, streak);
+    }
+    duos::note_completion(user, submission_id);
+    campaigns::note_activity(user, campaigns::Activity::Reactivated);
+    Ok(streak)
+}
+
+// --- Undo ---
+
+// Reverts the user's most recent submission if it is still inside the undo window:
+// the streak increment (if it was counted yet) is rolled back and the dare becomes active again.
+// Returns the restored streak.
+pub fn undo_last_submission(user: Principal) -> Result<u32, String> {
+    let now = ic_cdk::api::time();
+    let (streak, submission) = state::update_profile(user, |profile| {
+        let submission_id = profile.last_submission_id
+            .ok_or_else(|| "You have no submission to undo.".to_string())?;
+        let submission = state::SUBMISSIONS.with(|s| s.borrow().get(&submission_id))
+            .ok_or_else(|| "You have no submission to undo.".to_string())?;
+        // Bringing the old dare back would replace the penalty dare
+        hardcore::check_penalty(profile)?;
+        if submission.review.is_some() {
+            return Err("This submission has already been reviewed and can't be undone.".to_string());
+        }
+        if now.saturating_sub(submission.submitted_at) > UNDO_WINDOW_NANOS {
+            return Err("The undo window has passed (submissions can only be undone within 5 minutes).".to_string());
+        }
+
+        // Keep only undos from the last week, then enforce the weekly limit
+        let mut recent_undos: Vec<u64> = profile.undo_timestamps.take().unwrap_or_default()
+            .into_iter()
+            .filter(|&ts| now.saturating_sub(ts) < WEEK_NANOS)
+            .collect();
+        if recent_undos.len() >= MAX_UNDOS_PER_WEEK {
+            return Err(format!("You can only undo {} submissions per week.", MAX_UNDOS_PER_WEEK));
+        }
+        recent_undos.push(now);
+
+        ranking::set_streak(profile, streak_after_undo(profile.streak, &submission));
+        if submission.streak_pending != Some(true) {
+            profile.last_completed_at = submission.previous_completed_at;
+        }
+        profile.xp = Some(profile.xp.unwrap_or(0).saturating_sub(xp_of(&submission)));
+        profile.escalation = None;
+        profile.current_dare_id = submission.dare_id;
+        profile.current_dare_chat = submission.chat_id.clone();
+        profile.current_dare_text = submission.dare_text.clone();
+        profile.hint_dare_id = submission.dare_id.filter(|_| submission.hint_used == Some(true));
+        profile.last_submission_id = None;
+        profile.undo_timestamps = Some(recent_undos);
+        quests::revert(profile, submission_id);
+        profile.done_claim = submission.dare_id.zip(submission.completed_at)
+            .map(|(dare_id, claimed_at)| DoneClaim { dare_id, claimed_at });
+        Ok((profile.streak, submission))
+    })?;
+
+    state::remove_submission(submission.id);
+    moderation::dequeue(submission.id);
+    duos::revert(&submission);
+    history::log_event(user, HistoryEvent::SubmissionUndone { submission_id: submission.id, dare_id: submission.dare_id });
+    Ok(streak)
+}
+
+// --- Two-Phase Submit ---
+// Some proofs (a video, a long writeup) take a while to prepare. /done records that the active
+// dare is finished, and the proof can follow within CLAIM_WINDOW_HOURS through any submit path.
+// The submission then counts from the /done moment: for the streak expiry, the hardcore clock and
+// escalation deadlines. Review works as usual once the proof arrives. While a claim is open the
+// dare can't be swapped for another. Each dare takes one claim: one that runs out without proof
+// counts as nothing, but it stays on the profile so a second /done can't restart the window.
+
+// The open claim's time, if it is for the active dare and still inside the window
+pub fn claimed_at(profile: &UserProfile, now: u64) -> Option<u64> {
+    profile.done_claim.as_ref()
+        .filter(|claim| Some(claim.dare_id) == profile.current_dare_id && now.saturating_sub(claim.claimed_at) < CLAIM_WINDOW_NANOS)
+        .map(|claim| claim.claimed_at)
+}
+
+// Checked before a new dare replaces the active one
+pub fn check_claim(profile: &UserProfile, now: u64) -> Result<(), String> {
+    if claimed_at(profile, now).is_none() {
+        return Ok(());
+    }
+    Err("You've marked your dare as done. Send its proof with /submit_dare (or /submit start) before taking another.".to_string())
+}
+
+// /done: stops the clock on the active dare
+pub fn claim_done(user: Principal) -> Result<String, String> {
+    let now = ic_cdk::api::time();
+    state::update_profile(user, |profile| {
+        let dare_id = profile.current_dare_id
+            .ok_or_else(|| "You don't have an active dare. Use /get_dare first.".to_string())?;
+        if claimed_at(profile, now).is_some() {
+            return Err("You've already marked this dare as done. Send its proof to finish.".to_string());
+        }
+        if profile.done_claim.as_ref().is_some_and(|claim| claim.dare_id == dare_id) {
+            return Err("Your /done for this dare ran out. Send the proof with /submit_dare to finish it.".to_string());
+        }
+        profile.done_claim = Some(DoneClaim { dare_id, claimed_at: now });
+        Ok(format!(
+            "⏱️ Clock stopped! Your dare counts as done now. Send the proof within {} hours with /submit_dare (or /submit start for several parts).",
+            CLAIM_WINDOW_HOURS
+        ))
+    })
+}
+
+// --- Multi-part Drafts ---
+
+fn is_expired(draft: &SubmissionDraft, now: u64) -> bool {
+    now.saturating_sub(draft.updated_at) > DRAFT_TIMEOUT_NANOS
+}
+
+// Returns the caller's draft, ignoring (and removing) one that has timed out
+fn active_draft(user: &StorablePrincipal, now: u64) -> Option<SubmissionDraft> {
+    state::SUBMISSION_DRAFTS.with(|drafts_ref| {
+        let mut drafts = drafts_ref.borrow_mut();
+        match drafts.get(user) {
+            Some(draft) if is_expired(&draft, now) => {
+                drafts.remove(user);
+                None
+            }
+            other => other,
+        }
+    })
+}
+
+// Parts collected in the user's unexpired draft, if any (read-only, for status queries)
+pub fn draft_parts(user: Principal) -> Option<usize> {
+    state::SUBMISSION_DRAFTS.with(|d| d.borrow().get(&StorablePrincipal(user)))
+        .filter(|draft| !is_expired(draft, ic_cdk::api::time()))
+        .map(|draft| draft.parts.len())
+}
+
+// Starts a new draft, discarding any previous one
+pub fn start_draft(user: Principal) {
+    let now = ic_cdk::api::time();
+    let draft = SubmissionDraft { parts: Vec::new(), started_at: now, updated_at: now };
+    state::SUBMISSION_DRAFTS.with(|d| d.borrow_mut().insert(StorablePrincipal(user), draft));
+}
+
+// Appends a proof part to the caller's draft. Returns the number of parts collected so far.
+pub fn append_part(user: Principal, part: String) -> Result<usize, String> {
+    let part = part.trim().to_string();
+    if part.is_empty() { return Err("Proof part cannot be empty.".to_string()); }
+    if part.len() > MAX_PROOF_PART_LEN {
+        return Err(format!("Proof part is too long (max {} bytes).", MAX_PROOF_PART_LEN));
+    }
+
+    let storable_user = StorablePrincipal(user);
+    let now = ic_cdk::api::time();
+    let mut draft = active_draft(&storable_user, now)
+        .ok_or_else(|| "No submission in progress. Use /submit start first.".to_string())?;
+    if draft.parts.len() >= MAX_DRAFT_PARTS {
+        return Err(format!("A submission can have at most {} parts. Use /submit done to finish.", MAX_DRAFT_PARTS));
+    }
+    draft.parts.push(part);
+    draft.updated_at = now;
+    let count = draft.parts.len();
+    state::SUBMISSION_DRAFTS.with(|d| d.borrow_mut().insert(storable_user, draft));
+    Ok(count)
+}
+
+// Removes the caller's draft and returns its parts for submission
+pub fn take_draft(user: Principal) -> Result<Vec<String>, String> {
+    let storable_user = StorablePrincipal(user);
+    let draft = active_draft(&storable_user, ic_cdk::api::time())
+        .ok_or_else(|| "No submission in progress. Use /submit start first.".to_string())?;
+    if draft.parts.is_empty() {
+        return Err("Your submission has no proof parts yet. Send at least one before /submit done.".to_string());
+    }
+    state::SUBMISSION_DRAFTS.with(|d| d.borrow_mut().remove(&storable_user));
+    Ok(draft.parts)
+}
+
+pub fn discard_draft(user: Principal) -> bool {
+    state::SUBMISSION_DRAFTS.with(|d| d.borrow_mut().remove(&StorablePrincipal(user))).is_some()
+}
+
+// Purge job: drops drafts that expired more than `retention` ago. Returns how many were removed.
+pub fn purge_expired_drafts(retention: u64) -> usize {
+    let now = ic_cdk::api::time();
+    state::SUBMISSION_DRAFTS.with(|drafts_ref| {
+        let mut drafts = drafts_ref.borrow_mut();
+        let expired: Vec<StorablePrincipal> = drafts.iter()
+            .filter(|(_, draft)| is_expired(draft, now.saturating_sub(retention)))
+            .map(|(user, _)| user)
+            .collect();
+        for user in &expired {
+            drafts.remove(user);
+        }
+        expired.len()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn submission(streak_pending: bool) -> Submission {
+        Submission {
+            id: 1,
+            user: Principal::anonymous(),
+            proof_parts: vec!["photo".to_string()],
+            submitted_at: 0,
+            dare_id: Some(7),
+            review: None,
+            chat_id: None,
+            hint_used: Some(false),
+            reward_task_id: None,
+            rating: None,
+            dare_text: None,
+            escalation_xp: None,
+            completed_at: None,
+            streak_pending: streak_pending.then_some(true),
+            previous_completed_at: None,
+        }
+    }
+
+    #[test]
+    fn undo_takes_back_only_a_counted_point() {
+        assert_eq!(streak_after_undo(4, &submission(false)), 3);
+        assert_eq!(streak_after_undo(4, &submission(true)), 4);
+        assert_eq!(streak_after_undo(0, &submission(false)), 0);
+    }
+}
@@ -0,0 +1,40 @@
+use crate::state;
+use crate::types::{Difficulty, Suggestion};
+use candid::Principal;
+
+// Caps the review queue so anonymous web suggestions can't grow stable memory without bound
+const MAX_PENDING_SUGGESTIONS: u64 = 1000;
+
+// Validates and stores a suggestion. Returns its id.
+pub fn add_suggestion(
+    text: String,
+    difficulty: Option<Difficulty>,
+    suggested_by: Option<Principal>,
+    api_key_id: Option<u64>,
+) -> Result<u64, String> {
+    let text = text.trim().to_string();
+    if text.is_empty() { return Err("Suggestion text cannot be empty.".to_string()); }
+    if text.len() > crate::MAX_DARE_TEXT_LEN {
+        return Err(format!("Suggestion is too long (max {} bytes).", crate::MAX_DARE_TEXT_LEN));
+    }
+
+    state::SUGGESTIONS.with(|suggestions_ref| {
+        let mut suggestions = suggestions_ref.borrow_mut();
+        if suggestions.len() >= MAX_PENDING_SUGGESTIONS {
+            return Err("The suggestion queue is full. Please try again later.".to_string());
+        }
+        let id = suggestions.last_key_value().map_or(0, |(id, _)| id + 1);
+        let suggestion = Suggestion { id, text, difficulty, suggested_by, created_at: ic_cdk::api::time(), api_key_id };
+        suggestions.insert(id, suggestion);
+        Ok(id)
+    })
+}
+
+// Pending suggestions, oldest first
+pub fn list(offset: usize, limit: usize) -> Vec<Suggestion> {
+    state::SUGGESTIONS.with(|s| s.borrow().iter().skip(offset).take(limit).map(|(_, suggestion)| suggestion).collect())
+}
+
+pub fn remove(id: u64) -> Option<Suggestion> {
+    state::SUGGESTIONS.with(|s| s.borrow_mut().remove(&id))
+}
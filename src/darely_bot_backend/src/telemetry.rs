@@ -0,0 +1,32 @@
+use crate::state;
+use crate::types::SizeHistogram;
+
+// --- Record Size Telemetry ---
+// Every user profile write records its encoded size in a stable histogram, so operators can
+// watch real record sizes grow over time (profiles are unbounded; large ones cost more
+// instructions per read and write).
+
+// Upper bounds of the histogram buckets in bytes; larger records land in an open-ended bucket
+const BUCKET_BOUNDS: &[u64] = &[64, 128, 256, 512, 1024, 2048, 4096];
+
+pub fn record_profile_size(size: usize) {
+    let size = size as u64;
+    state::PROFILE_SIZES.with(|cell| {
+        let mut cell = cell.borrow_mut();
+        let mut histogram = cell.get().clone();
+        if histogram.buckets.is_empty() {
+            histogram.buckets = BUCKET_BOUNDS.iter().chain(std::iter::once(&u64::MAX)).map(|&bound| (bound, 0)).collect();
+            histogram.since = ic_cdk::api::time();
+        }
+        histogram.writes += 1;
+        histogram.max_size = histogram.max_size.max(size);
+        if let Some(bucket) = histogram.buckets.iter_mut().find(|(bound, _)| size <= *bound) {
+            bucket.1 += 1;
+        }
+        cell.set(histogram).expect("Failed to write profile size histogram");
+    });
+}
+
+pub fn profile_sizes() -> SizeHistogram {
+    state::PROFILE_SIZES.with(|cell| cell.borrow().get().clone())
+}
@@ -0,0 +1,255 @@
+use crate::chat_members;
+use crate::gallery;
+use crate::randomness;
+use crate::timezone;
+use crate::types::{Dare, DareSource};
+use candid::Principal;
+
+// --- Dare Templates ---
+// Curated dares may contain placeholders that are filled in when the dare is assigned:
+//   {random_member}   another member of the chat the dare was requested in ("a friend" if none)
+//   {number:5-20}     a random whole number in the range, both ends included
+//   {day_of_week}     the user's local weekday, e.g. "Friday"
+// Escaping: "{{" and "}}" stand for literal braces. Text that doesn't form a known placeholder
+// (an unknown name, a bad range, a brace without its partner) is kept as written; add_dare
+// rejects it up front so curated dares don't ship with typos. Substituted values are inserted
+// once and never expanded again, so a nickname like "{day_of_week}" shows up verbatim. Only
+// curated dares are rendered; LLM and member-written dares are shown exactly as stored.
+
+const MAX_NUMBER: u64 = 1_000_000;
+const NOBODY: &str = "a friend";
+const WEEKDAYS: [&str; 7] = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+
+#[derive(Debug, PartialEq, Eq)]
+enum Placeholder {
+    RandomMember,
+    Number { min: u64, max: u64 },
+    DayOfWeek,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Segment<'a> {
+    Text(&'a str),
+    Placeholder(Placeholder),
+    Invalid(&'a str), // Kept as written; reported by `validate`
+}
+
+fn placeholder(name: &str) -> Option<Placeholder> {
+    match name {
+        "random_member" => Some(Placeholder::RandomMember),
+        "day_of_week" => Some(Placeholder::DayOfWeek),
+        _ => {
+            let (min, max) = name.strip_prefix("number:")?.split_once('-')?;
+            let (min, max) = (min.trim().parse::<u64>().ok()?, max.trim().parse::<u64>().ok()?);
+            (min <= max && max <= MAX_NUMBER).then_some(Placeholder::Number { min, max })
+        }
+    }
+}
+
+fn parse(text: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut rest = text;
+    while let Some(index) = rest.find(['{', '}']) {
+        if index > 0 {
+            segments.push(Segment::Text(&rest[..index]));
+        }
+        rest = &rest[index..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            segments.push(Segment::Text(&rest[..1]));
+            rest = &rest[2..];
+            continue;
+        }
+        if rest.starts_with('}') {
+            segments.push(Segment::Invalid(&rest[..1]));
+            rest = &rest[1..];
+            continue;
+        }
+        // An opening brace: a placeholder runs to the next closing brace, unless another opens first
+        match rest[1..].find(['{', '}']).filter(|&end| rest.as_bytes()[end + 1] == b'}') {
+            Some(end) => {
+                let raw = &rest[..end + 2];
+                segments.push(placeholder(&raw[1..raw.len() - 1]).map_or(Segment::Invalid(raw), Segment::Placeholder));
+                rest = &rest[end + 2..];
+            }
+            None => {
+                segments.push(Segment::Invalid(&rest[..1]));
+                rest = &rest[1..];
+            }
+        }
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Text(rest));
+    }
+    segments
+}
+
+// Rejects dare text with placeholders that wouldn't render
+pub fn validate(text: &str) -> Result<(), String> {
+    let invalid: Vec<&str> = parse(text).into_iter().filter_map(|segment| match segment {
+        Segment::Invalid(raw) => Some(raw),
+        _ => None,
+    }).collect();
+    if invalid.is_empty() {
+        return Ok(());
+    }
+    Err(format!(
+        "Unknown or malformed placeholder(s): {}. Use {{random_member}}, {{number:<min>-<max>}} (max {}) or {{day_of_week}}, and write literal braces as {{{{ and }}}}.",
+        invalid.join(", "), MAX_NUMBER
+    ))
+}
+
+pub struct Context {
+    pub members: Vec<String>, // Display names of the chat's other members
+    pub weekday: &'static str,
+    pub seed: u64,
+}
+
+fn next_roll(seed: &mut u64) -> u64 {
+    *seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+    *seed >> 33
+}
+
+pub fn render(text: &str, context: &Context) -> String {
+    let mut seed = context.seed;
+    let mut rendered = String::with_capacity(text.len());
+    for segment in parse(text) {
+        match segment {
+            Segment::Text(text) | Segment::Invalid(text) => rendered.push_str(text),
+            Segment::Placeholder(Placeholder::RandomMember) => {
+                let roll = next_roll(&mut seed) as usize;
+                rendered.push_str(context.members.get(roll % context.members.len().max(1)).map_or(NOBODY, String::as_str));
+            }
+            Segment::Placeholder(Placeholder::Number { min, max }) => {
+                let roll = next_roll(&mut seed);
+                rendered.push_str(&(min + roll % (max - min + 1)).to_string());
+            }
+            Segment::Placeholder(Placeholder::DayOfWeek) => rendered.push_str(context.weekday),
+        }
+    }
+    rendered
+}
+
+// Markdown marks in a value (a nickname like "_dan_") would restyle the message around it
+fn escape_markdown(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | '*' | '_' | '~' | '`' | '[' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// Fills a bot-authored markdown template (e.g. the /recap layout, see recap.rs): every `{name}`
+// in `values` is replaced by its value with markdown escaped, and `{{`/`}}` unescape as in dares.
+// Anything else is kept as written.
+pub fn fill(text: &str, values: &[(&str, String)]) -> String {
+    let mut filled = String::with_capacity(text.len());
+    for segment in parse(text) {
+        match segment {
+            Segment::Invalid(raw) => {
+                let value = raw.strip_prefix('{').and_then(|raw| raw.strip_suffix('}'))
+                    .and_then(|name| values.iter().find(|(key, _)| *key == name));
+                filled.push_str(&value.map_or_else(|| raw.to_string(), |(_, value)| escape_markdown(value)));
+            }
+            Segment::Text(text) => filled.push_str(text),
+            // Dare placeholders mean nothing in a message
+            Segment::Placeholder(placeholder) => filled.push_str(&match placeholder {
+                Placeholder::RandomMember => "{random_member}".to_string(),
+                Placeholder::Number { min, max } => format!("{{number:{}-{}}}", min, max),
+                Placeholder::DayOfWeek => "{day_of_week}".to_string(),
+            }),
+        }
+    }
+    filled
+}
+
+// The text `user` gets for `dare` when it's assigned in `chat_id`; None when it is the stored text
+pub fn render_dare(dare: &Dare, user: Principal, chat_id: Option<&str>) -> Option<String> {
+    if dare.source != DareSource::Curated || !dare.text.contains(['{', '}']) {
+        return None;
+    }
+    let now = ic_cdk::api::time();
+    let members = chat_id.map(chat_members::members).unwrap_or_default()
+        .into_iter()
+        .filter(|(member, _)| *member != user)
+        .map(|(member, _)| gallery::display_name(member))
+        .collect();
+    let context = Context {
+        members,
+        weekday: WEEKDAYS[timezone::local_weekday(user, now) as usize],
+        seed: randomness::next_u64(),
+    };
+    let rendered = render(&dare.text, &context);
+    (rendered != dare.text).then_some(rendered)
+}
+
+// The dare as anyone could get it, for prompts whose result is shared by every player (the LLM
+// hint, see hints.rs): placeholders read as "a friend", "5-20" and "today", braces are unescaped
+pub fn generic_dare(dare: &Dare) -> String {
+    if dare.source != DareSource::Curated {
+        return dare.text.clone();
+    }
+    generic(&dare.text)
+}
+
+fn generic(text: &str) -> String {
+    parse(text).into_iter().map(|segment| match segment {
+        Segment::Text(text) | Segment::Invalid(text) => text.to_string(),
+        Segment::Placeholder(Placeholder::RandomMember) => NOBODY.to_string(),
+        Segment::Placeholder(Placeholder::Number { min, max }) if min == max => min.to_string(),
+        Segment::Placeholder(Placeholder::Number { min, max }) => format!("{}-{}", min, max),
+        Segment::Placeholder(Placeholder::DayOfWeek) => "today".to_string(),
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(members: &[&str]) -> Context {
+        Context { members: members.iter().map(|name| name.to_string()).collect(), weekday: "Friday", seed: 42 }
+    }
+
+    #[test]
+    fn renders_every_placeholder() {
+        let rendered = render("Do {number:5-20} push-ups with {random_member} this {day_of_week}", &context(&["alice"]));
+        let number: u64 = rendered.split(' ').nth(1).unwrap().parse().unwrap();
+        assert!((5..=20).contains(&number));
+        assert!(rendered.ends_with("push-ups with alice this Friday"));
+        assert_eq!(render("Call {random_member}", &context(&[])), "Call a friend");
+        assert_eq!(render("Count to {number:7-7}", &context(&[])), "Count to 7");
+    }
+
+    #[test]
+    fn escapes_and_keeps_invalid_text() {
+        assert_eq!(render("Draw {{a box}} and {nope}", &context(&[])), "Draw {a box} and {nope}");
+        assert_eq!(render("Open { only, close } only", &context(&[])), "Open { only, close } only");
+        assert_eq!(render("{number:9-3}", &context(&[])), "{number:9-3}");
+        // Substituted names are not expanded again
+        assert_eq!(render("Hug {random_member}", &context(&["{day_of_week}"])), "Hug {day_of_week}");
+    }
+
+    #[test]
+    fn fill_replaces_named_values_only() {
+        let values = [("month", "March".to_string()), ("dares", "{dares}".to_string())];
+        assert_eq!(fill("{month}: {dares} dares, {{braces}} and {other}", &values), "March: {dares} dares, {braces} and {other}");
+        assert_eq!(fill("{day_of_week} {number:1-2}", &values), "{day_of_week} {number:1-2}");
+        assert_eq!(fill("**{name}**", &[("name", "_dan_*".to_string())]), "**\\_dan\\_\\***");
+    }
+
+    #[test]
+    fn validate_reports_what_would_not_render() {
+        assert!(validate("Sing for {random_member} on {day_of_week}, {{literally}}").is_ok());
+        let error = validate("Do {number:1-2000000} things with {someone} {").unwrap_err();
+        assert!(error.contains("{number:1-2000000}, {someone}, {"));
+        assert!(validate("{ {day_of_week}").is_err());
+    }
+
+    #[test]
+    fn generic_dare_reads_the_same_for_everyone() {
+        assert_eq!(generic("Do {number:5-20} push-ups with {random_member} {day_of_week} {{now}}"), "Do 5-20 push-ups with a friend today {now}");
+        assert_eq!(generic("Count to {number:7-7} {nope}"), "Count to 7 {nope}");
+    }
+}
@@ -0,0 +1,225 @@
+use crate::outbox;
+use crate::repository::{self, DareRepo};
+use crate::state;
+use crate::timezone;
+use crate::types::{Theme, ThemeInput};
+use candid::Principal;
+use std::collections::BTreeMap;
+
+// --- Seasonal Themes ---
+// Themed content packs (Halloween, New Year, ...) that recur every year between two UTC dates.
+// While a theme is active its tagged dares get a selection weight boost, and completing one earns
+// a badge for that year's edition once the submission is approved. The theme timer flips themes on
+// and off and announces each start in the chats subscribed to announcements.
+
+const MAX_NAME_LEN: usize = 40;
+const MAX_ANNOUNCEMENT_LEN: usize = 500;
+const MAX_BOOST_PERCENT: u32 = 1000;
+const MAX_THEME_DARES: usize = 500;
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+fn slugify(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn valid_date(month: u32, day: u32) -> bool {
+    (1..=12).contains(&month) && (1..=31).contains(&day)
+}
+
+pub fn validate(input: &ThemeInput) -> Result<(), String> {
+    if slugify(&input.name).is_empty() || input.name.len() > MAX_NAME_LEN {
+        return Err(format!("Theme name must be 1-{} characters and include a letter or digit.", MAX_NAME_LEN));
+    }
+    if !valid_date(input.start_month, input.start_day) || !valid_date(input.end_month, input.end_day) {
+        return Err("Theme dates need a month of 1-12 and a day of 1-31.".to_string());
+    }
+    if input.boost_percent > MAX_BOOST_PERCENT {
+        return Err(format!("boost_percent can be at most {}.", MAX_BOOST_PERCENT));
+    }
+    if input.announcement.trim().is_empty() || input.announcement.len() > MAX_ANNOUNCEMENT_LEN {
+        return Err(format!("Announcement must be 1-{} characters.", MAX_ANNOUNCEMENT_LEN));
+    }
+    Ok(())
+}
+
+// (year, month, day) in UTC
+fn utc_date(now: u64) -> (i64, u32, u32) {
+    timezone::civil_from_days((now / 1_000_000_000 / DAY_SECS) as i64)
+}
+
+fn in_window(input: &ThemeInput, month: u32, day: u32) -> bool {
+    let (start, end, today) = ((input.start_month, input.start_day), (input.end_month, input.end_day), (month, day));
+    if start <= end {
+        start <= today && today <= end
+    } else {
+        today >= start || today <= end
+    }
+}
+
+// --- Admin ---
+
+pub fn create(input: ThemeInput) -> Result<u64, String> {
+    validate(&input)?;
+    let slug = slugify(&input.name);
+    state::THEMES.with(|themes_ref| {
+        let mut themes = themes_ref.borrow_mut();
+        if themes.iter().any(|(_, theme)| theme.slug == slug) {
+            return Err(format!("A theme named \"{}\" already exists.", input.name));
+        }
+        let id = themes.last_key_value().map_or(0, |(id, _)| id + 1);
+        themes.insert(id, Theme { id, slug, input, dare_ids: Vec::new(), active: false });
+        Ok(id)
+    })
+}
+
+// Adds dares to a theme. Returns how many it now has.
+pub fn tag_dares(theme_id: u64, dare_ids: Vec<u64>) -> Result<usize, String> {
+    let dare_count = repository::dares().len();
+    if let Some(missing) = dare_ids.iter().find(|&&id| id >= dare_count) {
+        return Err(format!("Dare #{} not found.", missing));
+    }
+    state::THEMES.with(|themes_ref| {
+        let mut themes = themes_ref.borrow_mut();
+        let mut theme = themes.get(&theme_id).ok_or_else(|| format!("Theme {} not found.", theme_id))?;
+        for id in dare_ids {
+            if !theme.dare_ids.contains(&id) {
+                theme.dare_ids.push(id);
+            }
+        }
+        if theme.dare_ids.len() > MAX_THEME_DARES {
+            return Err(format!("A theme can have at most {} dares.", MAX_THEME_DARES));
+        }
+        let count = theme.dare_ids.len();
+        themes.insert(theme_id, theme);
+        Ok(count)
+    })
+}
+
+pub fn delete(theme_id: u64) -> Result<Theme, String> {
+    state::THEMES.with(|t| t.borrow_mut().remove(&theme_id))
+        .ok_or_else(|| format!("Theme {} not found.", theme_id))
+}
+
+pub fn list() -> Vec<Theme> {
+    state::THEMES.with(|t| t.borrow().iter().map(|(_, theme)| theme).collect())
+}
+
+// --- Effects ---
+
+fn active_themes() -> Vec<Theme> {
+    state::THEMES.with(|t| t.borrow().iter().map(|(_, theme)| theme).filter(|theme| theme.active).collect())
+}
+
+// Boost percent per dare id across active themes (the largest applies)
+pub fn active_boosts() -> BTreeMap<u64, u32> {
+    let mut boosts = BTreeMap::new();
+    for theme in active_themes() {
+        for &dare_id in &theme.dare_ids {
+            let boost = boosts.entry(dare_id).or_insert(0);
+            *boost = theme.input.boost_percent.max(*boost);
+        }
+    }
+    boosts
+}
+
+pub fn boosted_weight(weight: u32, boost_percent: u32) -> u32 {
+    (weight as u64 * (100 + boost_percent as u64) / 100).min(u32::MAX as u64) as u32
+}
+
+// Awards the badges of themes that include an approved dare and were running when it was completed
+// (the review may come after the theme ended)
+pub fn note_approval(user: Principal, dare_id: Option<u64>, completed_at: u64) {
+    let Some(dare_id) = dare_id else { return };
+    let earned = earned_badges(&list(), dare_id, completed_at);
+    if earned.is_empty() {
+        return;
+    }
+    let _ = state::update_profile(user, |profile| {
+        let badges = profile.theme_badges.get_or_insert_with(Vec::new);
+        for badge in &earned {
+            if !badges.contains(badge) {
+                badges.push(badge.clone());
+            }
+        }
+        Ok(())
+    });
+}
+
+fn earned_badges(themes: &[Theme], dare_id: u64, completed_at: u64) -> Vec<String> {
+    let (year, month, day) = utc_date(completed_at);
+    themes.iter()
+        .filter(|theme| theme.dare_ids.contains(&dare_id) && in_window(&theme.input, month, day))
+        .map(|theme| badge_id(&theme.slug, year))
+        .collect()
+}
+
+pub fn badge_id(slug: &str, year: i64) -> String {
+    format!("theme-{}-{}", slug, year)
+}
+
+// --- Timer ---
+
+// Chats subscribed with set_chat_announcements (also used for changelog posts)
+pub fn subscribed_chats() -> Vec<String> {
+    state::CHAT_CONFIGS.with(|c| {
+        c.borrow().iter()
+            .filter(|(_, config)| config.announcements == Some(true))
+            .map(|(chat_id, _)| chat_id)
+            .collect()
+    })
+}
+
+// Activates and deactivates themes by date; returns how many changed
+pub fn run() -> usize {
+    let (_, month, day) = utc_date(ic_cdk::api::time());
+    let mut changed = 0;
+    for mut theme in list() {
+        let active = in_window(&theme.input, month, day);
+        if active == theme.active {
+            continue;
+        }
+        theme.active = active;
+        if active {
+            for chat_id in subscribed_chats() {
+                outbox::queue_chat(&chat_id, theme.input.announcement.clone());
+            }
+        }
+        state::THEMES.with(|t| t.borrow_mut().insert(theme.id, theme));
+        changed += 1;
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY_NANOS: u64 = DAY_SECS * 1_000_000_000;
+
+    fn halloween() -> Theme {
+        let input = ThemeInput {
+            name: "Halloween".to_string(),
+            start_month: 10,
+            start_day: 25,
+            end_month: 10,
+            end_day: 31,
+            boost_percent: 100,
+            announcement: "Spooky dares are here!".to_string(),
+        };
+        Theme { id: 0, slug: slugify(&input.name), input, dare_ids: vec![3], active: false }
+    }
+
+    #[test]
+    fn badges_follow_the_completion_date_not_the_review() {
+        // 2026-10-30 and 2026-11-02 (UTC), days since the epoch
+        let (in_theme, after_theme) = (20_756 * DAY_NANOS, 20_759 * DAY_NANOS);
+        let themes = vec![halloween()];
+        assert_eq!(earned_badges(&themes, 3, in_theme), vec!["theme-halloween-2026".to_string()]);
+        assert!(earned_badges(&themes, 3, after_theme).is_empty());
+        assert!(earned_badges(&themes, 4, in_theme).is_empty());
+    }
+}
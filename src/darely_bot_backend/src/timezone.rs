@@ -0,0 +1,275 @@
+use crate::state;
+use crate::types::{DayWindow, HistoryEvent};
+use candid::Principal;
+
+// --- Time Zones ---
+// Daily windows (the "today" of daily resets, reminders and quests) run from the user's local
+// midnight to the next one. A profile stores either a fixed UTC offset ("UTC+05:30") or one of
+// the named zones below. Named zones follow the EU or US daylight saving rules where they apply;
+// zones not listed here can be set as an offset. Users without a time zone get UTC days.
+
+const SECS_PER_DAY: i64 = 24 * 60 * 60;
+const NANOS_PER_SEC: i64 = 1_000_000_000;
+const MIN_OFFSET_MINUTES: i32 = -12 * 60;
+const MAX_OFFSET_MINUTES: i32 = 14 * 60;
+
+#[derive(Clone, Copy)]
+enum Dst {
+    None,
+    Eu, // Last Sunday of March 01:00 UTC until the last Sunday of October 01:00 UTC
+    Us, // Second Sunday of March 02:00 local until the first Sunday of November 02:00 local
+}
+
+// (name, standard offset in minutes, daylight saving rule)
+const ZONES: &[(&str, i32, Dst)] = &[
+    ("UTC", 0, Dst::None),
+    ("Europe/London", 0, Dst::Eu),
+    ("Europe/Dublin", 0, Dst::Eu),
+    ("Europe/Lisbon", 0, Dst::Eu),
+    ("Europe/Amsterdam", 60, Dst::Eu),
+    ("Europe/Berlin", 60, Dst::Eu),
+    ("Europe/Madrid", 60, Dst::Eu),
+    ("Europe/Paris", 60, Dst::Eu),
+    ("Europe/Rome", 60, Dst::Eu),
+    ("Europe/Stockholm", 60, Dst::Eu),
+    ("Europe/Warsaw", 60, Dst::Eu),
+    ("Europe/Athens", 120, Dst::Eu),
+    ("Europe/Helsinki", 120, Dst::Eu),
+    ("Europe/Kyiv", 120, Dst::Eu),
+    ("Europe/Istanbul", 180, Dst::None),
+    ("Europe/Moscow", 180, Dst::None),
+    ("Africa/Lagos", 60, Dst::None),
+    ("Africa/Johannesburg", 120, Dst::None),
+    ("Africa/Nairobi", 180, Dst::None),
+    ("Asia/Dubai", 240, Dst::None),
+    ("Asia/Karachi", 300, Dst::None),
+    ("Asia/Kolkata", 330, Dst::None),
+    ("Asia/Dhaka", 360, Dst::None),
+    ("Asia/Bangkok", 420, Dst::None),
+    ("Asia/Jakarta", 420, Dst::None),
+    ("Asia/Hong_Kong", 480, Dst::None),
+    ("Asia/Shanghai", 480, Dst::None),
+    ("Asia/Singapore", 480, Dst::None),
+    ("Asia/Seoul", 540, Dst::None),
+    ("Asia/Tokyo", 540, Dst::None),
+    ("Australia/Brisbane", 600, Dst::None),
+    ("America/Sao_Paulo", -180, Dst::None),
+    ("America/Buenos_Aires", -180, Dst::None),
+    ("America/New_York", -300, Dst::Us),
+    ("America/Toronto", -300, Dst::Us),
+    ("America/Chicago", -360, Dst::Us),
+    ("America/Mexico_City", -360, Dst::None),
+    ("America/Denver", -420, Dst::Us),
+    ("America/Phoenix", -420, Dst::None),
+    ("America/Los_Angeles", -480, Dst::Us),
+    ("America/Anchorage", -540, Dst::Us),
+    ("Pacific/Honolulu", -600, Dst::None),
+];
+
+// A resolved time zone
+#[derive(Clone, Copy)]
+struct Zone {
+    standard_offset: i32,
+    dst: Dst,
+}
+
+// --- Calendar Arithmetic (proleptic Gregorian, days since 1970-01-01) ---
+
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month as i64 + 9) % 12; // March = 0
+    let day_of_year = (153 * month_index + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// 0 = Sunday
+pub fn weekday(days: i64) -> i64 {
+    (days + 4).rem_euclid(7)
+}
+
+// Day number of the n-th (1-based) Sunday of a month
+fn nth_sunday(year: i64, month: u32, n: i64) -> i64 {
+    let first = days_from_civil(year, month, 1);
+    first + (7 - weekday(first)) % 7 + (n - 1) * 7
+}
+
+fn last_sunday(year: i64, month: u32) -> i64 {
+    let next_month = if month == 12 { days_from_civil(year + 1, 1, 1) } else { days_from_civil(year, month + 1, 1) };
+    let last = next_month - 1;
+    last - weekday(last)
+}
+
+impl Zone {
+    // UTC offset in minutes at the given instant (seconds since the epoch)
+    fn offset_at(&self, secs: i64) -> i32 {
+        let year = civil_from_days((secs + self.standard_offset as i64 * 60).div_euclid(SECS_PER_DAY)).0;
+        let (start, end) = match self.dst {
+            Dst::None => return self.standard_offset,
+            Dst::Eu => (
+                last_sunday(year, 3) * SECS_PER_DAY + 3600,
+                last_sunday(year, 10) * SECS_PER_DAY + 3600,
+            ),
+            Dst::Us => {
+                let std = self.standard_offset as i64 * 60;
+                (
+                    nth_sunday(year, 3, 2) * SECS_PER_DAY + 2 * 3600 - std,
+                    nth_sunday(year, 11, 1) * SECS_PER_DAY + 2 * 3600 - (std + 3600),
+                )
+            }
+        };
+        if secs >= start && secs < end { self.standard_offset + 60 } else { self.standard_offset }
+    }
+
+    fn local_day(&self, secs: i64) -> i64 {
+        (secs + self.offset_at(secs) as i64 * 60).div_euclid(SECS_PER_DAY)
+    }
+
+    // The UTC instant (seconds) at which the given local day begins
+    fn day_start(&self, day: i64) -> i64 {
+        let guess = day * SECS_PER_DAY - self.standard_offset as i64 * 60;
+        // DST transitions never happen at midnight in the supported zones, so one correction suffices
+        day * SECS_PER_DAY - self.offset_at(guess) as i64 * 60
+    }
+}
+
+// --- Parsing ---
+
+// "+5", "-03:30", "+0530", "UTC+2", "GMT-3" -> minutes east of UTC
+fn parse_offset(input: &str) -> Option<i32> {
+    let upper = input.to_ascii_uppercase();
+    let rest = upper.strip_prefix("UTC").or_else(|| upper.strip_prefix("GMT")).unwrap_or(&upper).trim();
+    if rest.is_empty() {
+        return Some(0);
+    }
+    let (sign, digits) = match rest.as_bytes()[0] {
+        b'+' => (1, &rest[1..]),
+        b'-' => (-1, &rest[1..]),
+        _ => return None,
+    };
+    if !digits.chars().all(|c| c.is_ascii_digit() || c == ':') {
+        return None;
+    }
+    let (hours, minutes) = match digits.split_once(':') {
+        Some((h, m)) => (h, m),
+        None if digits.len() > 2 => digits.split_at(digits.len() - 2),
+        None => (digits, "0"),
+    };
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    // Checked before `hours * 60`, which a long run of digits would overflow
+    if hours > MAX_OFFSET_MINUTES / 60 || !matches!(minutes, 0 | 30 | 45) {
+        return None;
+    }
+    let offset = sign * (hours * 60 + minutes);
+    (MIN_OFFSET_MINUTES..=MAX_OFFSET_MINUTES).contains(&offset).then_some(offset)
+}
+
+fn format_offset(offset: i32) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    format!("UTC{}{:02}:{:02}", sign, offset.abs() / 60, offset.abs() % 60)
+}
+
+// Named zones match case-insensitively, with or without the region ("berlin", "new york")
+fn find_zone(input: &str) -> Option<(&'static str, i32, Dst)> {
+    let wanted = input.to_lowercase().replace(' ', "_");
+    ZONES.iter().copied().find(|(name, _, _)| {
+        let name = name.to_lowercase();
+        name == wanted || name.rsplit('/').next() == Some(wanted.as_str())
+    })
+}
+
+// Validates user input and returns the canonical form stored on the profile
+pub fn normalize(input: &str) -> Result<String, String> {
+    let input = input.trim();
+    if let Some((name, _, _)) = find_zone(input) {
+        return Ok(name.to_string());
+    }
+    parse_offset(input).map(format_offset).ok_or_else(|| {
+        format!("Unknown time zone '{}'. Use an offset like UTC+02:00 or -05:30, or a zone name like Europe/Berlin.", input)
+    })
+}
+
+// Resolves a stored time zone; unset or unreadable values fall back to UTC
+fn resolve(timezone: Option<&str>) -> Zone {
+    let utc = Zone { standard_offset: 0, dst: Dst::None };
+    let Some(timezone) = timezone else { return utc };
+    if let Some((_, standard_offset, dst)) = find_zone(timezone) {
+        return Zone { standard_offset, dst };
+    }
+    parse_offset(timezone).map_or(utc, |standard_offset| Zone { standard_offset, dst: Dst::None })
+}
+
+fn zone_of(user: Principal) -> Zone {
+    let profile = state::get_profile(user);
+    resolve(profile.and_then(|profile| profile.timezone).as_deref())
+}
+
+// --- Daily Windows ---
+
+// Start and end (nanoseconds, end exclusive) of the user's local day containing `timestamp`
+pub fn day_bounds(user: Principal, timestamp: u64) -> (u64, u64) {
+    let zone = zone_of(user);
+    let day = zone.local_day(timestamp as i64 / NANOS_PER_SEC);
+    let to_nanos = |secs: i64| (secs.max(0) * NANOS_PER_SEC) as u64;
+    (to_nanos(zone.day_start(day)), to_nanos(zone.day_start(day + 1)))
+}
+
+// The user's local calendar date (YYYY-MM-DD) at the given timestamp
+pub fn local_date(user: Principal, timestamp: u64) -> String {
+    let (year, month, day) = civil_from_days(zone_of(user).local_day(timestamp as i64 / NANOS_PER_SEC));
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// Days since 1970-01-01 in the user's zone, for counting consecutive local days
+pub fn local_day_number(user: Principal, timestamp: u64) -> i64 {
+    zone_of(user).local_day(timestamp as i64 / NANOS_PER_SEC)
+}
+
+// The user's local weekday at the given timestamp, 0 = Sunday (1970-01-01 was a Thursday)
+pub fn local_weekday(user: Principal, timestamp: u64) -> u32 {
+    (zone_of(user).local_day(timestamp as i64 / NANOS_PER_SEC) + 4).rem_euclid(7) as u32
+}
+
+// The caller's current daily window, with what they have done in it so far
+pub fn today(user: Principal) -> DayWindow {
+    let now = ic_cdk::api::time();
+    let zone = zone_of(user);
+    let secs = now as i64 / NANOS_PER_SEC;
+    let (starts_at, resets_at) = day_bounds(user, now);
+    // Undone submissions don't count for the day
+    let (submitted, undone) = state::HISTORY.with(|history_ref| {
+        history_ref.borrow().iter()
+            .rev()
+            .map(|(_, entry)| entry)
+            .take_while(|entry| entry.timestamp >= starts_at)
+            .filter(|entry| entry.user == user)
+            .fold((0u32, 0u32), |(submitted, undone), entry| match entry.event {
+                HistoryEvent::DareSubmitted { .. } => (submitted + 1, undone),
+                HistoryEvent::SubmissionUndone { .. } => (submitted, undone + 1),
+                _ => (submitted, undone),
+            })
+    });
+    DayWindow {
+        local_date: local_date(user, now),
+        utc_offset_minutes: zone.offset_at(secs),
+        starts_at,
+        resets_at,
+        submissions_today: submitted.saturating_sub(undone),
+    }
+}
@@ -0,0 +1,133 @@
+use crate::blocklist;
+use crate::history;
+use crate::state;
+use crate::types::{HistoryEvent, Truth};
+use candid::Principal;
+use std::collections::BTreeSet;
+
+// --- Truths ---
+// The second content type of Truth-or-Dare: questions the user answers publicly in the chat.
+// /truth assigns a question from the bank, and answering it counts toward the profile's own truth
+// counter and grants less XP than a dare. Truths don't touch the streak.
+
+// Experience per truth answered (see submissions::DARE_XP)
+pub const TRUTH_XP: u64 = 3;
+const MAX_TRUTH_LEN: usize = 300;
+const MAX_ANSWER_LEN: usize = 1000;
+const MAX_IMPORT_BATCH: usize = 200;
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+fn validate(text: &str) -> Result<(), String> {
+    if text.is_empty() { return Err("Truth text cannot be empty.".to_string()); }
+    if text.len() > MAX_TRUTH_LEN {
+        return Err(format!("Truth is too long (max {} bytes).", MAX_TRUTH_LEN));
+    }
+    Ok(())
+}
+
+fn existing_texts() -> BTreeSet<String> {
+    state::TRUTHS.with(|t| t.borrow().iter().map(|(_, truth)| normalize(&truth.text)).collect())
+}
+
+fn insert(text: String) -> u64 {
+    state::TRUTHS.with(|truths_ref| {
+        let mut truths = truths_ref.borrow_mut();
+        let id = truths.last_key_value().map_or(0, |(id, _)| id + 1);
+        truths.insert(id, Truth { id, text, created_at: ic_cdk::api::time(), times_answered: 0 });
+        id
+    })
+}
+
+// --- Admin Tooling ---
+
+pub fn add(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    validate(text)?;
+    if existing_texts().contains(&normalize(text)) {
+        return Err("That truth is already in the bank.".to_string());
+    }
+    Ok(insert(text.to_string()))
+}
+
+// Adds a batch of truths, skipping invalid ones and duplicates. Returns (added, skipped).
+pub fn import(texts: Vec<String>) -> Result<(usize, usize), String> {
+    if texts.len() > MAX_IMPORT_BATCH {
+        return Err(format!("Import at most {} truths per call.", MAX_IMPORT_BATCH));
+    }
+    let mut seen = existing_texts();
+    let (mut added, mut skipped) = (0, 0);
+    for text in texts {
+        let text = text.trim();
+        if validate(text).is_err() || !seen.insert(normalize(text)) {
+            skipped += 1;
+            continue;
+        }
+        insert(text.to_string());
+        added += 1;
+    }
+    Ok((added, skipped))
+}
+
+pub fn remove(truth_id: u64) -> Result<Truth, String> {
+    state::TRUTHS.with(|t| t.borrow_mut().remove(&truth_id))
+        .ok_or_else(|| format!("Truth #{} not found.", truth_id))
+}
+
+pub fn list(offset: usize, limit: usize) -> Vec<Truth> {
+    state::TRUTHS.with(|t| t.borrow().iter().skip(offset).take(limit).map(|(_, truth)| truth).collect())
+}
+
+// --- Playing ---
+
+// Assigns a truth the user hasn't just had and that avoids their blocked tags
+pub fn assign(user: Principal, seed: u64) -> Result<Truth, String> {
+    let profile = state::get_profile(user)
+        .ok_or_else(|| "User not found. Please /register first.".to_string())?;
+    let blocklist = blocklist::Blocklist::of(&profile);
+    let candidates: Vec<Truth> = state::TRUTHS.with(|t| {
+        t.borrow().iter()
+            .map(|(_, truth)| truth)
+            .filter(|truth| Some(truth.id) != profile.current_truth_id && !blocklist.matches_text(&truth.text))
+            .collect()
+    });
+    if candidates.is_empty() {
+        return Err("There are no truths available right now. Try /get_dare instead.".to_string());
+    }
+    let truth = candidates[(seed % candidates.len() as u64) as usize].clone();
+    state::update_profile(user, |profile| {
+        profile.current_truth_id = Some(truth.id);
+        Ok(())
+    })?;
+    Ok(truth)
+}
+
+// Completes the user's active truth. Returns (truths answered, total XP).
+pub fn answer(user: Principal, answer: &str) -> Result<(u32, u64), String> {
+    let answer = answer.trim();
+    if answer.is_empty() { return Err("Answer cannot be empty.".to_string()); }
+    if answer.len() > MAX_ANSWER_LEN {
+        return Err(format!("Answer is too long (max {} bytes).", MAX_ANSWER_LEN));
+    }
+    let (truth_id, answered, xp) = state::update_profile(user, |profile| {
+        let truth_id = profile.current_truth_id.take()
+            .ok_or_else(|| "You don't have an active truth. Use /truth to get one.".to_string())?;
+        let answered = profile.truths_answered.unwrap_or(0) + 1;
+        let xp = profile.xp.unwrap_or(0) + TRUTH_XP;
+        profile.truths_answered = Some(answered);
+        profile.xp = Some(xp);
+        Ok((truth_id, answered, xp))
+    })?;
+    // The truth may have been removed from the bank since it was assigned
+    state::TRUTHS.with(|truths_ref| {
+        let mut truths = truths_ref.borrow_mut();
+        if let Some(mut truth) = truths.get(&truth_id) {
+            truth.times_answered += 1;
+            truths.insert(truth_id, truth);
+        }
+    });
+    history::log_event(user, HistoryEvent::TruthAnswered { truth_id });
+    Ok((answered, xp))
+}
@@ -1,63 +1,1391 @@
-use candid::{CandidType, Principal, Decode, Encode};
+use candid::{CandidType, Principal, Nat};
 use ic_stable_structures::{storable::Bound, Storable};
 use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
 use std::borrow::Cow;
 
+// --- Candid Records ---
+
+// Stored records are candid. One that doesn't decode is corrupt (or left behind by an incompatible
+// type change). Storable can't fail, so a direct read of one traps with the type's name; scans go
+// through quarantine.rs instead, which decodes with `try_decode_record` and moves unreadable
+// records aside so the rest stays readable.
+pub(crate) fn try_decode_record<T: CandidType + serde::de::DeserializeOwned>(bytes: &[u8]) -> Result<T, String> {
+    candid::decode_one(bytes).map_err(|e| format!("Corrupt {} record: {}", std::any::type_name::<T>(), e))
+}
+
+pub(crate) fn decode_record<T: CandidType + serde::de::DeserializeOwned>(bytes: &[u8]) -> T {
+    try_decode_record(bytes).unwrap_or_else(|e| ic_cdk::trap(&format!(
+        "{}. Run quarantine_corrupt_records to move unreadable records aside.", e
+    )))
+}
+
+pub(crate) fn encode_record<T: CandidType>(value: &T) -> Cow<'static, [u8]> {
+    Cow::Owned(candid::encode_one(value).unwrap_or_else(|e| ic_cdk::trap(&format!("Could not encode {}: {}", std::any::type_name::<T>(), e))))
+}
+
 // --- Storable Principal Wrapper ---
 
-// Wrapper around Principal to implement Storable for stable map keys
-#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct StorablePrincipal(pub Principal); // Make inner field pub if needed directly, or provide methods
+// Wrapper around Principal to implement Storable for stable map keys
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StorablePrincipal(pub Principal); // Make inner field pub if needed directly, or provide methods
+
+impl Storable for StorablePrincipal {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(&self.0) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { StorablePrincipal(decode_record(bytes.as_ref())) }
+    const BOUND: Bound = Bound::Unbounded; // Principal size varies but has system limits
+}
+
+// --- Composite Keys ---
+
+// Two-part stable map key, ordered by the first part and then the second, so all entries
+// sharing a first part are contiguous (see state::range_by_first). Encoded as the first part's
+// length (u32 big-endian), its bytes, then the second part's bytes.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CompositeKey<A, B>(pub A, pub B);
+
+impl<A: Storable, B: Storable> Storable for CompositeKey<A, B> {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        let first = self.0.to_bytes();
+        let second = self.1.to_bytes();
+        let mut bytes = Vec::with_capacity(4 + first.len() + second.len());
+        bytes.extend_from_slice(&(first.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&first);
+        bytes.extend_from_slice(&second);
+        Cow::Owned(bytes)
+    }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        let (len, rest) = bytes.split_at(4);
+        let len = u32::from_be_bytes(len.try_into().unwrap()) as usize;
+        let (first, second) = rest.split_at(len);
+        CompositeKey(A::from_bytes(Cow::Borrowed(first)), B::from_bytes(Cow::Borrowed(second)))
+    }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Per-chat per-user state: (chat id, user)
+pub type ChatUserKey = CompositeKey<String, StorablePrincipal>;
+
+// Streak index entry: (u32::MAX - streak, (streak_reached_at, user)), so keys iterate in
+// leaderboard order (see ranking.rs)
+pub type StreakIndexKey = CompositeKey<u64, CompositeKey<u64, StorablePrincipal>>;
+
+// Streak expiry entry: (when the streak's clock last restarted, user) (see scheduler.rs)
+pub type ExpiryIndexKey = CompositeKey<u64, StorablePrincipal>;
+
+// Escalation entry: (offer or escalation deadline, user) (see escalation.rs)
+pub type EscalationIndexKey = CompositeKey<u64, StorablePrincipal>;
+
+// Chat pause entry: (when the pause ends, chat key) (see pauses.rs)
+pub type PauseIndexKey = CompositeKey<u64, String>;
+
+// LLM spend entry: (UTC day number, (feature, chat id or "")) (see llm_usage.rs)
+pub type LlmUsageKey = CompositeKey<u64, CompositeKey<String, String>>;
+
+// --- Core Application Types ---
+
+// Difficulty Enum (used as input for get_dare)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Difficulty { Easy, Medium, Hard, }
+
+// Storable implementation for Difficulty (needed if stored, e.g., in Dare struct)
+impl Storable for Difficulty {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Bounded { max_size: 10, is_fixed_size: false }; // Small fixed size
+}
+
+// Where a dare came from: added by an admin or generated by the LLM
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DareSource { Curated, Llm, Custom, } // Custom: written by one member for another (see custom_dares.rs)
+
+// Dare struct (curated dares and logged LLM dares)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Dare {
+    pub id: u64, // Keep fields pub for access from other modules
+    pub text: String,
+    pub difficulty: Difficulty,
+    pub source: DareSource,
+    pub weight: u32, // Relative selection weight within its source pool (see selection.rs)
+    pub issued_by: Option<Principal>, // Author of a Custom dare
+    pub hint: Option<String>, // Set by /add_dare or generated on the first /hint (see hints.rs)
+    pub creator: Option<Principal>, // Admin who added a curated dare or member who wrote a custom one; None for LLM dares
+    pub created_at: Option<u64>, // None for dares stored before attribution
+    pub min_level: Option<u32>, // Only offered to players at this level or above (see selection.rs)
+    pub min_streak: Option<u32>, // Only offered to players with at least this streak
+}
+
+// Storable implementation for Dare
+impl Storable for Dare {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    // Adjust max_size based on expected max dare text length
+    const BOUND: Bound = Bound::Bounded { max_size: 1024, is_fixed_size: false };
+}
+
+// Heuristic analysis of a new dare, shown to admins on /add_dare
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DareAnalysis {
+    pub suggested_difficulty: Difficulty,
+    pub estimated_minutes: u32,
+    pub near_duplicate_of: Option<u64>, // Id of the most similar existing dare, if similar enough
+    pub similarity_percent: u32,
+}
+
+// A dare held for admin confirmation (accept the suggestion or override it)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PendingDare {
+    pub text: String,
+    pub requested_difficulty: Option<Difficulty>,
+    pub hint: Option<String>,
+    pub analysis: DareAnalysis,
+    pub created_at: u64,
+    pub min_level: Option<u32>,
+    pub min_streak: Option<u32>,
+}
+
+impl Storable for PendingDare {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// --- Configuration Types ---
+
+// Relative weights used to pick which source a dare is served from.
+// e.g. { curated: 3, llm: 1 } serves curated dares roughly 75% of the time.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SourceMix {
+    pub curated: u32,
+    pub llm: u32,
+}
+
+impl Default for SourceMix {
+    fn default() -> Self { SourceMix { curated: 3, llm: 1 } }
+}
+
+// Global canister configuration (stored in a StableCell)
+// NOTE: Fields added after the initial release are Option so the stored config still decodes.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Config {
+    pub source_mix: SourceMix, // Default mix for chats without an override
+    pub registry_canister: Option<Principal>, // OpenChat community registry used for group metadata
+    pub cors_allowed_origins: Option<Vec<String>>, // Origins allowed to call the REST API from browsers
+    pub ic_root_key: Option<Vec<u8>>, // DER root key for verifying II delegations (None = mainnet)
+    pub review_sla: Option<ReviewSla>, // None = submissions wait for a moderator indefinitely
+    pub alert_recipients: Option<Vec<Principal>>, // Admins who get operational alerts as DMs
+    pub trusted_bots: Option<Vec<Principal>>, // Bots allowed to act on behalf of OpenChat users (see accounts.rs)
+    pub reward_wheel: Option<RewardWheel>, // None = RewardWheel::default()
+    pub redemption_policy: Option<RedemptionPolicy>, // None = RedemptionPolicy::Keep
+    pub queue_alerts: Option<QueueAlerts>, // None = no backlog alerts
+    pub rating_survey: Option<bool>, // Ask users to rate dares after approval; None = off
+    pub purge_retention: Option<PurgeRetention>, // None = purge stale state as soon as it expires
+    pub registration: Option<RegistrationPolicy>, // None = open registration, no user cap
+    pub command_visibility: Option<Vec<(String, Visibility)>>, // Per-command reply visibility overrides (see commands.rs)
+    pub outbox_max_in_flight: Option<u32>, // Cap on unacknowledged deliveries; None = drained messages are removed at once
+    pub feature_rollouts: Option<Vec<(String, u8)>>, // Percent of users per feature (see features.rs); unlisted = everyone
+    pub prefill: Option<PrefillSchedule>, // None = LLM dares are only pre-generated on /prefill_dares, at any hour
+    pub llm_params: Option<Vec<(Difficulty, LlmParams)>>, // Dare generation per difficulty (see llm.rs); unlisted = the defaults
+    pub llm_blocked_terms: Option<Vec<String>>, // Words or phrases that get an LLM dare rejected (see safety.rs)
+    pub incident_channel: Option<String>, // Chat key that gets unexpected-error reports (see incidents.rs); None = the alert recipients
+    pub streak_expiry_hours: Option<u32>, // Streaks reset after this long without a completion (see scheduler.rs); None = never
+    pub daily_declines: Option<u32>, // /dare proposes and allows this many /decline per day (see proposals.rs); None = dares are assigned at once
+    pub streak_on_approval: Option<bool>, // A submission raises the streak once approved (None = on); false = on submission, taken back if rejected
+}
+
+// Deployment environment, set through the init/upgrade args (see environment.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Environment {
+    Dev,
+    Staging,
+    #[default]
+    Prod,
+}
+
+impl Storable for Environment {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Optional argument to install and upgrade; unset fields keep their stored value
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct InitOrUpgradeArgs {
+    pub environment: Option<Environment>,
+}
+
+// Where a bot command may run (see commands.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScopePolicy {
+    Any,
+    DirectOnly, // The user's direct chat with the bot (or a direct call)
+    GroupOnly, // A group chat or community channel, including their threads
+}
+
+// Who sees a command's reply in the chat (see commands.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Visibility {
+    Public, // Everyone in the chat
+    Ephemeral, // Only the user who sent the command
+}
+
+// A bot command as the OpenChat bot should declare it
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CommandDefinition {
+    pub name: String,
+    pub description: String,
+    pub scope: ScopePolicy,
+    pub visibility: Visibility, // For successful replies; errors are always ephemeral
+    pub owner_only: bool, // The bot only relays it from the chat's owners
+}
+
+// Candid interface version reported by api_version (see api_version.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ApiVersion {
+    pub version: u32,
+    pub frozen: Vec<u32>, // Versions with a frozen interface under candid/
+    pub deprecated: Vec<DeprecatedMethod>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DeprecatedMethod {
+    pub method: String,
+    pub since: u32,
+    pub replacement: Option<String>,
+    pub removed_in: Option<u32>, // First version without the method, once scheduled
+}
+
+// Who may /register (see registration.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct RegistrationPolicy {
+    pub max_users: Option<u64>, // None = no cap
+    pub invite_only: bool, // Registering needs an invite code or admin approval
+    pub open: Option<bool>, // Canister-wide switch, the only gate for /register without a chat; None = open
+}
+
+// How long expired records are kept before the purge timer deletes them (see purge.rs).
+// Unset categories are purged as soon as they expire.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PurgeRetention {
+    pub drafts_hours: Option<u32>,
+    pub sessions_hours: Option<u32>,
+    pub pending_dares_hours: Option<u32>,
+    pub nonces_hours: Option<u32>,
+}
+
+// What redeeming a milestone does to the streak (see rewards.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RedemptionPolicy {
+    Reset,
+    Keep,
+    Decay { percent: u8 }, // Removes this share of the streak, rounded down
+}
+
+// Relative odds of each reward tier when a milestone is redeemed (see rewards.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RewardWheel {
+    pub task: u32,
+    pub points: u32,
+    pub streak_freeze: u32,
+    pub badge: u32,
+    pub points_amount: u64, // Points granted by the points tier
+}
+
+impl Default for RewardWheel {
+    fn default() -> Self {
+        RewardWheel { task: 40, points: 30, streak_freeze: 20, badge: 10, points_amount: 25 }
+    }
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RewardTier { Task, Points, StreakFreeze, Badge }
+
+// A bonus task the reward wheel can hand out
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RewardTask {
+    pub id: u64,
+    pub text: String,
+    pub created_at: u64,
+    pub stock: Option<u32>, // Assignments left; None = unlimited
+    pub starts_at: Option<u64>, // Not handed out before this time (nanos)
+    pub ends_at: Option<u64>, // Not handed out from this time on (nanos)
+}
+
+// Where a reward task stands in the admin listing
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RewardTaskStatus { Upcoming, Available, Expired, SoldOut }
+
+impl Storable for RewardTask {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Storable for Config {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Per-chat configuration overrides, keyed by OpenChat chat id
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ChatConfig {
+    pub source_mix: Option<SourceMix>, // None = use Config::source_mix
+    pub announcements: Option<bool>, // Subscribed to seasonal theme announcements (see themes.rs)
+    pub redemption_policy: Option<RedemptionPolicy>, // None = use Config::redemption_policy
+    pub registration_open: Option<bool>, // None = use RegistrationPolicy::open; false turns /register away in this chat
+    pub pause: Option<ChatPause>, // Set by /pause_bot until it expires or /resume_bot (see pauses.rs)
+    pub streak_on_approval: Option<bool>, // None = use Config::streak_on_approval
+    pub digest: Option<DigestSchedule>, // Weekly digest post (see gallery.rs); None = off
+    pub digest_sent_at: Option<u64>,
+    pub language: Option<String>, // LLM dares and hints for this chat are written in it (see llm.rs); None = English
+}
+
+// When a chat's weekly digest is posted
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DigestSchedule {
+    pub weekday: u8, // 0 = Sunday
+    pub hour: u8, // UTC
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ChatPause {
+    pub paused_at: u64,
+    pub until: Option<u64>, // None = until /resume_bot
+    pub commands_disabled: bool, // Gameplay commands are turned away too, not just proactive posts
+    pub paused_by: Principal,
+}
+
+impl Storable for ChatConfig {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// --- Community Registry ---
+
+// Group metadata as returned by the community registry canister
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct GroupMetadata {
+    pub name: String,
+    pub member_count: u32,
+}
+
+// Locally cached copy of a group's metadata
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CachedGroupMetadata {
+    pub metadata: GroupMetadata,
+    pub fetched_at: u64,
+}
+
+impl Storable for CachedGroupMetadata {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// UserProfile struct
+// NOTE: Fields added after the initial release are Option so previously stored profiles still decode.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct UserProfile {
+    pub streak: u32,
+    pub redeemed_milestones: Vec<u32>, // Using Vec as BTreeSet isn't easily Storable
+    pub current_dare_id: Option<u64>, // Dare assigned by the last get_dare (LLM dares are logged, so they have ids too)
+    pub last_submission_id: Option<u64>, // Most recent submission, eligible for /undo
+    pub undo_timestamps: Option<Vec<u64>>, // Undos within the last week (for rate limiting)
+    pub blocked_dare_ids: Option<Vec<u64>>, // Dares never to serve this user again (see blocklist.rs)
+    pub blocked_tags: Option<Vec<String>>, // Keywords the user's dares must not involve
+    pub plain_text: Option<bool>, // Accessibility mode: replies without emoji/markdown (see formatting.rs)
+    pub timezone: Option<String>, // Zone name or "UTC+hh:mm" offset for daily windows (see timezone.rs)
+    pub accepts_custom_dares: Option<bool>, // Opted in to dares written by other members (None = no)
+    pub xp: Option<u64>, // Experience: DARE_XP per dare submitted, TRUTH_XP per truth answered
+    pub current_truth_id: Option<u64>, // Truth assigned by the last /truth (see truths.rs)
+    pub truths_answered: Option<u32>,
+    pub theme_badges: Option<Vec<String>>, // Seasonal badges earned, e.g. "theme-halloween-2026"
+    pub event_badges: Option<Vec<u64>>, // Global events this user helped win (see events.rs)
+    pub pin_seen_at: Option<u64>, // pinned_at of the last "all" pin this user received (see pins.rs)
+    pub current_dare_chat: Option<String>, // Chat the active dare was requested in
+    pub nickname: Option<String>, // Shown in the gallery instead of the principal
+    pub hint_dare_id: Option<u64>, // Dare whose hint the user has paid for (see hints.rs)
+    pub streak_freezes: Option<u32>, // Won on the reward wheel; each protects the streak once
+    pub current_redemption_task_id: Option<u64>, // Bonus task from the reward wheel, not yet submitted
+    pub lucky_badges: Option<Vec<u32>>, // Milestones whose spin landed on the badge tier
+    pub streak_reached_at: Option<u64>, // When the streak last changed to its current value (leaderboard tie-break)
+    pub current_dare_text: Option<String>, // The active dare as rendered from its template (see templates.rs); None = the stored text
+    pub version: Option<u64>, // Bumped on every write (see state::update_profile); None = never rewritten
+    pub vouched_at: Option<Vec<u64>>, // Streaks kept by vouching in the last 30 days (see vouches.rs)
+    pub vouches_given_at: Option<Vec<u64>>, // Vouches this user gave in the last 30 days
+    pub hardcore_since: Option<u64>, // Opted in to hardcore mode (see hardcore.rs); None = off
+    pub penalty_since: Option<u64>, // Lapsed in hardcore mode; /dare stays locked until the penalty dare is submitted
+    pub penalty_dare_id: Option<u64>, // The open penalty's dare; None = the fallback text
+    pub freeze_used_at: Option<u64>, // A streak freeze last stood in for a hardcore lapse
+    pub last_checkin_day: Option<i64>, // Local day number of the last /checkin (see checkin.rs)
+    pub checkin_streak: Option<u32>, // Consecutive local days checked in, separate from the dare streak
+    pub quest: Option<QuestProgress>, // Questline in progress (see quests.rs)
+    pub quests_completed: Option<Vec<u64>>,
+    pub escalation: Option<Escalation>, // Harder follow-up offered after a completion, or taken (see escalation.rs)
+    pub last_completed_at: Option<u64>, // Last dare submitted or vouched for (see scheduler.rs)
+    pub done_claim: Option<DoneClaim>, // /done before the proof arrives (see submissions.rs)
+    pub proposed_dare: Option<DareProposal>, // /dare's pick awaiting /accept or /decline (see proposals.rs)
+    pub declined_at: Option<Vec<u64>>, // Proposals declined in the last day
+}
+
+// Storable implementation for UserProfile
+// Unbounded: redeemed milestones, blocklists and later fields grow without a fixed limit. The map
+// always had an unbounded key, so existing profiles load unchanged.
+impl Storable for UserProfile {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// --- Submissions ---
+
+// A completed dare submission. Multi-part proofs (see SubmissionDraft) keep one entry per part.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Submission {
+    pub id: u64,
+    pub user: Principal,
+    pub proof_parts: Vec<String>,
+    pub submitted_at: u64, // Nanoseconds since epoch (ic_cdk::api::time)
+    pub dare_id: Option<u64>, // The dare that was active when submitting
+    pub review: Option<Review>, // Set once a moderator approves or rejects it
+    pub chat_id: Option<String>, // Chat the dare was requested in (see gallery.rs)
+    pub hint_used: Option<bool>, // The submitter bought the dare's hint
+    pub reward_task_id: Option<u64>, // Set for /complete_task proofs instead of a dare (see rewards.rs)
+    pub rating: Option<DareRating>, // The submitter's survey answer after approval (see ratings.rs)
+    pub dare_text: Option<String>, // The dare as the user got it, when rendered from a template
+    pub escalation_xp: Option<u64>, // Bonus XP for finishing an escalation in time, taken back with DARE_XP (see escalation.rs)
+    pub completed_at: Option<u64>, // The /done moment when the proof came later; None = submitted_at
+    pub streak_pending: Option<bool>, // The streak point waits for approval (see moderation.rs); None = counted on submission
+    pub previous_completed_at: Option<u64>, // The profile's last_completed_at before this submission counted, put back by /undo
+}
+
+impl Storable for Submission {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// A dare offered by /dare, waiting for /accept or /decline (see proposals.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DareProposal {
+    pub dare_id: Option<u64>,
+    pub chat_id: Option<String>,
+    pub rendered: Option<String>, // Becomes current_dare_text on /accept
+    pub text: String, // As shown to the player
+    pub proposed_at: u64,
+    pub source: Option<ProposalSource>, // Used up on /accept rather than when proposed
+}
+
+// A pin or the prefill stock, whose dare is only used up once assigned (see proposals.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ProposalSource {
+    OwnPin,
+    AllPin { pinned_at: u64 },
+    Prefill,
+}
+
+// /done: the active dare is finished and its proof will follow (see submissions.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DoneClaim {
+    pub dare_id: u64,
+    pub claimed_at: u64,
+}
+
+// Proof parts collected between submit_start and submit_done
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SubmissionDraft {
+    pub parts: Vec<String>,
+    pub started_at: u64,
+    pub updated_at: u64, // Drafts expire relative to their last activity
+}
+
+impl Storable for SubmissionDraft {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+
+// --- History ---
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum HistoryEvent {
+    DareSubmitted { submission_id: u64, dare_id: Option<u64>, hint_used: Option<bool> },
+    SubmissionUndone { submission_id: u64, dare_id: Option<u64> },
+    SubmissionRejected { submission_id: u64, dare_id: Option<u64> },
+    TruthAnswered { truth_id: u64 },
+    RewardSpun { milestone: u32, tier: RewardTier, task_id: Option<u64>, points: Option<u64> },
+    RewardTaskCompleted { task_id: u64, submission_id: u64 },
+    RewardTaskRejected { task_id: u64, submission_id: u64 },
+    RewardTaskEdited { task_id: u64, previous_text: String }, // Logged for the admin
+    RewardTaskRemoved { task_id: u64, text: String }, // Logged for the admin
+    RewardTaskRestocked { task_id: u64, previous: Option<u32>, stock: Option<u32> }, // Logged for the admin
+    RewardTaskScheduled { task_id: u64, starts_at: Option<u64>, ends_at: Option<u64> }, // Logged for the admin
+    AppealFiled { submission_id: u64 },
+    AppealDecided { submission_id: u64, granted: bool },
+    ConfigChanged { fields: Vec<String>, previous: String }, // Logged for the admin; previous values as JSON
+    VouchGiven { for_user: Principal, dare_id: u64 }, // Logged for the voucher
+    StreakVouched { dare_id: u64, vouchers: Vec<Principal> },
+    BuddyBonus { buddy: Principal, xp: u64 }, // Both buddies kept their streak for a week
+    HardcoreLapsed { streak_lost: u32, penalty_dare_id: Option<u64> },
+    StreakFreezeUsed { streak: u32 },
+    CheckedIn { checkin_streak: u32, points: u64 },
+    QuestCompleted { quest_id: u64, points: u64, xp: u64 },
+    EscalationCompleted { rung: u32, bonus_xp: u64 },
+    EscalationLapsed { rung: u32 }, // An accepted escalation ran out; only the bonus is lost
+    StreakExpired { streak_lost: u32 }, // No completion within the configured window
+}
+
+// One entry in the append-only activity log
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct HistoryEntry {
+    pub user: Principal,
+    pub timestamp: u64,
+    pub event: HistoryEvent,
+}
+
+impl Storable for HistoryEntry {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+
+// --- Moderation ---
+
+// What happens to a submission nobody reviewed in time
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum SlaAction {
+    Approve,
+    Reject,
+}
+
+// Backlog thresholds that make the monitoring timer alert admins (see queue_alerts.rs). Unset
+// limits aren't checked.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct QueueAlerts {
+    pub max_pending_submissions: Option<u32>,
+    pub max_submission_age_hours: Option<u32>,
+    pub max_pending_suggestions: Option<u32>,
+    pub max_suggestion_age_hours: Option<u32>,
+    pub cooldown_hours: u32, // Minimum time between two alerts
+}
+
+// How the LLM generates dares of one difficulty
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LlmParams {
+    pub model: String, // OpenAI model name, e.g. "gpt-4o-mini"
+    pub temperature: f32, // 0.0 - 2.0
+    pub max_tokens: u32,
+}
+
+// Why a generated dare was held back (see safety.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SafetyCategory {
+    Blocklisted, // Matched an admin-configured term
+    SelfHarm,
+    Violence,
+    Sexual,
+    Illegal,
+    Dangerous,
+    Harassment,
+    PromptLeak, // A refusal, jailbreak echo or talk about the model instead of a dare
+}
+
+// An LLM dare the safety check rejected, kept for admin review
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RejectedDare {
+    pub id: u64,
+    pub text: String,
+    pub difficulty: Difficulty,
+    pub category: SafetyCategory,
+    pub rejected_at: u64,
+}
+
+impl Storable for RejectedDare {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Daily top-up of pre-generated LLM dares during quiet hours (see prefill.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PrefillSchedule {
+    pub per_difficulty: u32, // Unserved dares to keep in stock for each difficulty
+    pub start_hour_utc: u8, // Off-peak window; may wrap past midnight (22 -> 5)
+    pub end_hour_utc: u8,
+}
+
+// Auto-review policy for submissions pending longer than `max_pending_hours`
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ReviewSla {
+    pub max_pending_hours: u32,
+    pub action: SlaAction,
+}
+
+// A moderator's decision on a submission
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Review {
+    pub moderator: Principal, // The canister itself for SLA auto-reviews
+    pub approved: bool,
+    pub reviewed_at: u64,
+    pub sla_applied: Option<ReviewSla>, // Set when the decision came from the SLA policy
+    pub reason: Option<String>, // Shown to the submitter with /submission_status
+}
+
+// A user's request to overturn a rejected submission (see appeals.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Appeal {
+    pub submission_id: u64,
+    pub user: Principal,
+    pub argument: String,
+    pub filed_at: u64,
+    pub rejected_by: Principal, // Never decides the appeal
+    pub assigned_to: Option<Principal>, // None when no other moderator was available
+    pub decision: Option<AppealDecision>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct AppealDecision {
+    pub moderator: Principal,
+    pub granted: bool,
+    pub decided_at: u64,
+    pub note: Option<String>,
+}
+
+impl Storable for Appeal {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// A submission waiting in the review queue
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ReviewTask {
+    pub submission_id: u64,
+    pub queued_at: u64,
+    pub assigned_to: Option<Principal>, // None until a moderator is available
+    pub assigned_at: Option<u64>,
+    pub claimed_at: Option<u64>, // Set by /review claim; the stale timeout restarts from here
+    pub passed_by: Vec<Principal>, // Moderators who passed; not assigned this task again
+}
+
+impl Storable for ReviewTask {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Per-moderator counters (a moderator is anyone with an entry in MODERATORS)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ModeratorStats {
+    pub added_at: u64,
+    pub assigned: u64,
+    pub approved: u64,
+    pub rejected: u64,
+    pub passed: u64,
+    pub timed_out: u64, // Assignments reassigned because the claim went stale
+    pub last_assigned_at: Option<u64>, // Drives the round-robin order
+}
+
+impl Storable for ModeratorStats {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// What /review claim returns to the moderator
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ReviewItem {
+    pub submission: Submission,
+    pub dare_text: Option<String>,
+}
+
+// --- Registration ---
+
+// An admin-issued code that lets its holder register while registration is invite-only
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct InviteCode {
+    pub created_by: Principal,
+    pub created_at: u64,
+    pub max_uses: Option<u32>, // None = unlimited
+    pub uses: u32,
+}
+
+impl Storable for InviteCode {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// A user waiting for an admin to approve their registration
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RegistrationRequest {
+    pub user: Principal,
+    pub requested_at: u64,
+    pub chat_id: Option<String>, // Where they asked from
+}
+
+impl Storable for RegistrationRequest {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// A user who was let in while the user cap was reached, admitted once a spot opens
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct WaitlistEntry {
+    pub user: Principal,
+    pub joined_at: u64,
+    pub chat_id: Option<String>,
+}
+
+impl Storable for WaitlistEntry {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// --- Dare Popularity ---
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Like {
+    pub user: Principal,
+    pub liked_at: u64,
+}
+
+// Everyone who liked a dare (one like per user)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DareLikes {
+    pub likes: Vec<Like>,
+}
+
+impl Storable for DareLikes {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// One row of /trending
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct TrendingDare {
+    pub dare_id: u64,
+    pub text: String,
+    pub difficulty: Difficulty,
+    pub likes_this_week: u64,
+    pub total_likes: u64,
+}
+
+// --- Dare Ratings ---
+
+// How hard and how fun a dare felt, each 1-5
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct DareRating {
+    pub difficulty: u8,
+    pub fun: u8,
+}
+
+// Running totals of a dare's survey answers
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DareRatings {
+    pub count: u64,
+    pub difficulty_sum: u64,
+    pub fun_sum: u64,
+}
+
+impl Storable for DareRatings {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// One row of the admin ratings report
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DareRatingReport {
+    pub dare_id: u64,
+    pub text: String,
+    pub difficulty: Difficulty,
+    pub ratings: u64,
+    pub avg_difficulty: f32,
+    pub avg_fun: f32,
+    pub suggested_difficulty: Option<Difficulty>, // Set when enough ratings disagree with `difficulty`
+    pub weight_percent: u32, // Selection weight adjustment from the fun score
+}
+
+// The caller's current local day (see timezone.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DayWindow {
+    pub local_date: String, // YYYY-MM-DD
+    pub utc_offset_minutes: i32,
+    pub starts_at: u64, // Local midnight (nanoseconds since the epoch)
+    pub resets_at: u64, // Next local midnight
+    pub submissions_today: u32,
+}
+
+// --- Dare Suggestions ---
+
+// A question from the truth bank, answered publicly in the chat (see truths.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Truth {
+    pub id: u64,
+    pub text: String,
+    pub created_at: u64,
+    pub times_answered: u64,
+}
+
+impl Storable for Truth {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// A seasonal content pack, active every year between two UTC dates (see themes.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ThemeInput {
+    pub name: String, // e.g. "Halloween"
+    pub start_month: u32,
+    pub start_day: u32,
+    pub end_month: u32, // Inclusive; an end before the start wraps into the next year (New Year)
+    pub end_day: u32,
+    pub boost_percent: u32, // Extra selection weight for the theme's dares while active
+    pub announcement: String, // Posted to subscribed chats when the theme starts
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Theme {
+    pub id: u64,
+    pub slug: String, // Badge ids are "theme-<slug>-<year>"
+    pub input: ThemeInput,
+    pub dare_ids: Vec<u64>, // Dares tagged with the theme
+    pub active: bool, // Maintained by the theme timer
+}
+
+impl Storable for Theme {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// A community-wide goal counted across all chats (see events.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct GlobalEvent {
+    pub id: u64,
+    pub title: String,
+    pub target: u64, // Dares to complete between starts_at and ends_at
+    pub starts_at: u64,
+    pub ends_at: u64,
+    pub progress: u64,
+    pub participants: u64,
+    pub announced_percent: u8, // Highest progress threshold announced so far
+    pub succeeded_at: Option<u64>, // Set by the event timer once the badge has been handed out
+    pub closed: bool, // Ended and announced
+    pub created_by: Principal,
+    pub badge_cursor: Option<Principal>, // Last participant given the badge while the timer works through them
+}
+
+impl Storable for GlobalEvent {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Curated dares a chat shared with other chats (see marketplace.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MarketplaceListing {
+    pub id: u64,
+    pub title: String,
+    pub dare_ids: Vec<u64>, // One dare or a whole pack
+    pub publisher_chat: String,
+    pub published_by: Principal,
+    pub published_at: u64,
+    pub imports: u64, // Chats that imported at least one of its dares
+    pub completions: Option<u64>, // Approved completions of its dares, counted since the counter was added
+}
+
+impl Storable for MarketplaceListing {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// A sequence of dares finished one after another for a reward (see quests.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Quest {
+    pub id: u64,
+    pub name: String,
+    pub steps: Vec<u64>, // Dare ids in order
+    pub reward_points: u64,
+    pub reward_xp: u64,
+    pub created_by: Principal,
+    pub created_at: u64,
+    pub archived: bool, // No new players; players already on it can finish
+}
+
+impl Storable for Quest {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuestDraftStage {
+    Name,
+    Steps,
+    Reward,
+    Confirm,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum QuestStep {
+    Existing(u64), // "#12": a stored dare
+    New(String), // Stored as a quest-only dare when the quest is saved
+}
+
+// An admin's questline in the making, one message at a time (see quests.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct QuestDraft {
+    pub stage: QuestDraftStage,
+    pub name: Option<String>,
+    pub steps: Vec<QuestStep>,
+    pub reward_points: u64,
+    pub reward_xp: u64,
+    pub updated_at: u64,
+}
+
+impl Storable for QuestDraft {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// A player's place in a quest
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct QuestProgress {
+    pub quest_id: u64,
+    pub step: u32, // Index of the next step to complete
+    pub started_at: u64,
+    pub pending_submission: Option<u64>, // The current step's submission, waiting for review
+}
+
+// A harder follow-up dare offered after a completion (see escalation.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Escalation {
+    pub rung: u32, // 1 for the first follow-up; pays DARE_XP times rung + 1
+    pub difficulty: Difficulty, // Of the follow-up dare
+    pub deadline: u64, // To accept the offer, then to submit the accepted dare
+    pub dare_id: Option<u64>, // Set once accepted; None = still an offer
+}
+
+// One validated row of a dare list fetched by /import_url (see imports.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ImportRow {
+    pub text: String,
+    pub difficulty: Option<Difficulty>, // None = the analysis' suggestion
+    pub hint: Option<String>,
+}
+
+// A fetched dare list being added to the repository a chunk at a time
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ImportJob {
+    pub id: u64,
+    pub url: String,
+    pub created_by: Principal,
+    pub created_at: u64,
+    pub rows: Vec<ImportRow>, // Still to import; emptied as chunks are stored
+    pub fetched: u32, // Rows in the file
+    pub invalid: u32,
+    pub duplicates: u32, // In the file or already stored, including ones found while importing
+    pub imported: u32,
+    pub finished_at: Option<u64>,
+    pub correlation_id: Option<String>, // The /import_url request; chunks log under it
+}
+
+impl Storable for ImportJob {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// A dare an admin has pinned as someone's next assignment (see pins.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PinnedDare {
+    pub dare_id: u64,
+    pub pinned_at: u64,
+    pub pinned_by: Principal,
+}
+
+impl Storable for PinnedDare {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// An approved submission shown by /gallery
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct GalleryEntry {
+    pub submission_id: u64,
+    pub submitter: String, // Nickname, or a shortened principal
+    pub dare_text: Option<String>,
+    pub proof: String, // A link from the proof if it has one, otherwise the start of the text
+    pub approved_at: u64,
+}
+
+// A dare suggested by a player (e.g. via POST /api/v1/suggest), awaiting admin review
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Suggestion {
+    pub id: u64,
+    pub text: String,
+    pub difficulty: Option<Difficulty>,
+    pub suggested_by: Option<Principal>, // None for anonymous web suggestions
+    pub created_at: u64,
+    pub api_key_id: Option<u64>, // Set when submitted by a partner through an API key
+}
+
+impl Storable for Suggestion {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+
+// --- Admin Listings ---
+
+// One page of an admin listing (0-based page number)
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct Page {
+    pub number: u32,
+    pub size: Option<u32>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub enum UserSort {
+    StreakDesc,
+    StreakAsc,
+    LastActiveDesc,
+    LastActiveAsc,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct UserFilter {
+    pub min_streak: Option<u32>,
+    pub inactive_days: Option<u32>, // No activity (per the history log) for at least this many days
+    pub banned: Option<bool>, // Only banned (true) or only other (false) users (see bans.rs)
+    pub sort: Option<UserSort>, // Defaults to StreakDesc
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct UserSummary {
+    pub user: Principal,
+    pub profile: UserProfile,
+    pub last_active_at: Option<u64>,
+    pub banned_at: Option<u64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct UserPage {
+    pub users: Vec<UserSummary>,
+    pub total: u64, // Matching users across all pages
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum SubmissionStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub enum SubmissionSort {
+    NewestFirst,
+    OldestFirst,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SubmissionPage {
+    pub submissions: Vec<Submission>,
+    pub total: u64,
+}
+
+
+// --- Re-engagement Campaigns ---
+
+// Who a campaign targets: users whose last submission is at least `lapsed_days` old
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CampaignAudience {
+    pub lapsed_days: u32,
+    pub min_streak: Option<u32>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Campaign {
+    pub id: u64,
+    pub name: String,
+    pub template: String, // Placeholders: {streak}, {days_inactive}, {stage}
+    pub audience: CampaignAudience,
+    pub stage_days: Vec<u32>, // Days after enrollment at which each message is sent, ascending
+    pub created_at: u64,
+    pub active: bool,
+    pub enrolled: u64,
+    pub messages_sent: u64,
+    pub responded: u64, // Enrolled users who asked for a dare after a message
+    pub reactivated: u64, // Enrolled users who submitted a dare after a message
+}
 
-impl Storable for StorablePrincipal {
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> { Cow::Owned(Encode!(&self.0).unwrap()) }
-    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { StorablePrincipal(Decode!(bytes.as_ref(), Principal).unwrap()) }
-    const BOUND: Bound = Bound::Unbounded; // Principal size varies but has system limits
+impl Storable for Campaign {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
 }
 
-// --- Core Application Types ---
+// A user's progress through one campaign
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Enrollment {
+    pub campaign_id: u64,
+    pub enrolled_at: u64,
+    pub stages_sent: u32,
+    pub last_sent_at: Option<u64>,
+    pub responded_at: Option<u64>,
+    pub reactivated_at: Option<u64>,
+}
 
-// Difficulty Enum (used as input for get_dare)
-#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub enum Difficulty { Easy, Medium, Hard, }
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Enrollments {
+    pub entries: Vec<Enrollment>,
+}
 
-// Storable implementation for Difficulty (needed if stored, e.g., in Dare struct)
-impl Storable for Difficulty {
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> { Cow::Owned(Encode!(self).unwrap()) }
-    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { Decode!(bytes.as_ref(), Self).unwrap() }
-    const BOUND: Bound = Bound::Bounded { max_size: 10, is_fixed_size: false }; // Small fixed size
+impl Storable for Enrollments {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Where the campaign timer resumes its pass over the profiles
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CampaignScan {
+    pub cursor: Option<Principal>, // Last profile visited; None = between passes
+    pub started_at: u64, // When the current (or last) pass began; 0 = never
+}
+
+impl Storable for CampaignScan {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
 }
 
-// Dare struct (potentially for logging/fallback)
+// A direct message waiting for the OpenChat bot to deliver it
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
-pub struct Dare {
-    pub id: u64, // Keep fields pub for access from other modules
+pub struct OutboundMessage {
+    pub id: u64,
+    pub user: Principal,
     pub text: String,
-    pub difficulty: Difficulty,
+    pub campaign_id: Option<u64>,
+    pub queued_at: u64,
+    pub chat_id: Option<String>, // Post to this group chat instead of DMing `user` (then the canister's own id)
+    pub leased_until: Option<u64>, // Handed to the bot and awaiting its ack until then (see outbox.rs)
+    pub correlation_id: Option<String>, // The request that queued it (see correlation.rs)
 }
 
-// Storable implementation for Dare
-impl Storable for Dare {
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> { Cow::Owned(Encode!(self).unwrap()) }
-    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { Decode!(bytes.as_ref(), Self).unwrap() }
-    // Adjust max_size based on expected max dare text length
-    const BOUND: Bound = Bound::Bounded { max_size: 1024, is_fixed_size: false };
+impl Storable for OutboundMessage {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
 }
 
-// UserProfile struct - NOTE: current_dare_id is removed for LLM integration simplicity
-#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
-pub struct UserProfile {
-    pub streak: u32,
-    // current_dare_id: Option<u64>, // Removed: Not tracking specific LLM dare assigned
-    pub redeemed_milestones: Vec<u32>, // Using Vec as BTreeSet isn't easily Storable
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct CampaignInput {
+    pub name: String,
+    pub template: String,
+    pub audience: CampaignAudience,
+    pub stage_days: Vec<u32>,
 }
 
-// Storable implementation for UserProfile
-impl Storable for UserProfile {
-     fn to_bytes(&self) -> std::borrow::Cow<[u8]> { Cow::Owned(Encode!(self).unwrap()) }
-    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { Decode!(bytes.as_ref(), Self).unwrap() }
-     // Estimate max size needed
-     const BOUND: Bound = Bound::Bounded { max_size: 128, is_fixed_size: false };
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CampaignReport {
+    pub campaign: Campaign,
+    pub response_rate_percent: u32, // Of enrolled users who received at least one message
+    pub reactivation_rate_percent: u32,
+}
+
+
+// --- Completion Certificates ---
+
+// A canister-signed attestation that a user completed a dare (see certificates.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CompletionCertificate {
+    pub payload: String, // JSON; the signed message is a domain separator followed by these bytes
+    pub signature: ByteBuf, // CBOR canister signature {certificate, tree}
+    pub public_key: ByteBuf, // DER canister signature public key
+}
+
+
+// --- Achievements ---
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Badge {
+    pub id: String,
+    pub description: String,
+}
+
+
+// --- Web Sessions ---
+
+// A web API session created by create_session; the signed token references it by id
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Session {
+    pub user: Principal,
+    pub created_at: u64,
+    pub expires_at: u64,
+}
+
+impl Storable for Session {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Returned by create_session
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SessionToken {
+    pub token: String,
+    pub expires_at: u64,
+}
+
+
+// --- API Keys ---
+
+// What a partner API key may access on the REST API
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ApiScope {
+    ReadLeaderboard, // read:leaderboard
+    ReadDares, // read:dares
+    WriteSuggestions, // write:suggestions
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ApiKey {
+    pub id: u64,
+    pub name: String,
+    pub key_hash: Vec<u8>, // SHA-256 of the raw key; the key itself is only shown once
+    pub scopes: Vec<ApiScope>,
+    pub rate_limit_per_minute: u32,
+    pub created_at: u64,
+    pub revoked_at: Option<u64>,
+    pub last_used_at: Option<u64>,
+    pub request_count: u64,
+    pub window_started_at: u64, // Current rate limit window
+    pub window_count: u32,
+}
+
+impl Storable for ApiKey {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// An API key as shown to admins (without its hash)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ApiKeyInfo {
+    pub id: u64,
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    pub rate_limit_per_minute: u32,
+    pub created_at: u64,
+    pub revoked_at: Option<u64>,
+    pub last_used_at: Option<u64>,
+    pub request_count: u64,
+}
+
+
+// --- Points Ledger (ICRC-3) ---
+
+// Generic ICRC-3 block value
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ICRC3Value {
+    Blob(ByteBuf),
+    Text(String),
+    Nat(Nat),
+    Int(candid::Int),
+    Array(Vec<ICRC3Value>),
+    Map(Vec<(String, ICRC3Value)>),
+}
+
+impl Storable for ICRC3Value {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct GetBlocksArgs {
+    pub start: Nat,
+    pub length: Nat,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct BlockWithId {
+    pub id: Nat,
+    pub block: ICRC3Value,
+}
+
+candid::define_function!(pub GetBlocksCallback : (Vec<GetBlocksArgs>) -> (GetBlocksResult) query);
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ArchivedBlocks {
+    pub args: Vec<GetBlocksArgs>,
+    pub callback: GetBlocksCallback,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct GetBlocksResult {
+    pub log_length: Nat,
+    pub blocks: Vec<BlockWithId>,
+    pub archived_blocks: Vec<ArchivedBlocks>, // Always empty: every block stays in this canister
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct GetArchivesArgs {
+    pub from: Option<Principal>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ICRC3ArchiveInfo {
+    pub canister_id: Principal,
+    pub start: Nat,
+    pub end: Nat,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct SupportedBlockType {
+    pub block_type: String,
+    pub url: String,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ICRC3DataCertificate {
+    pub certificate: ByteBuf,
+    pub hash_tree: ByteBuf,
+}
+
+
+// --- HTTP Gateway Types ---
+
+// Request/response shapes of the `http_request` canister interface
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: ByteBuf,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct HttpResponse {
+    pub status_code: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: ByteBuf,
+    pub upgrade: Option<bool>, // Some(true) asks the gateway to retry as http_request_update
 }
 
 
@@ -87,7 +1415,13 @@ pub struct OpenAIResponse {
     // pub created: u64, // Optional
     // pub model: String, // Optional
     pub choices: Vec<OpenAIChoice>,
-    // pub usage: OpenAIUsage, // Optional: track token usage
+    pub usage: Option<OpenAIUsage>, // Token counts for spend accounting (see llm_usage.rs)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OpenAIUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
 }
 
 #[derive(Deserialize, Debug)]
@@ -101,4 +1435,475 @@ pub struct OpenAIChoice {
 pub struct OpenAIMessageResponse {
     // pub role: String, // Optional: should be "assistant"
     pub content: String, // The generated dare text
-}
\ No newline at end of file
+}
+
+// --- LLM Spend ---
+
+// Totals for one day, feature and chat (see llm_usage.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct LlmUsage {
+    pub calls: u64,
+    pub failures: u64, // Outcalls that returned an error or no usable reply
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cycles: u128, // Charged for the outcalls, after refunds
+    pub token_usd_micros: Option<u64>, // Token cost at each call's model prices; None in entries from before models were priced
+}
+
+impl Storable for LlmUsage {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum SpendPeriod {
+    Month(String), // YYYY-MM (UTC)
+    LastDays(u32), // Including today
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct LlmSpendReport {
+    pub from_date: String, // YYYY-MM-DD, inclusive (UTC)
+    pub to_date: String,
+    pub total: LlmUsage,
+    pub estimated_usd: f64, // Tokens at the prices of the model each call used, plus cycles at their XDR value
+    pub by_feature: Vec<(String, LlmUsage)>,
+    pub by_chat: Vec<(String, LlmUsage)>, // "" = calls without a chat (direct calls, batch jobs)
+    pub by_day: Vec<(String, LlmUsage)>,
+}
+
+// --- Ledger ---
+
+// Balances of the ledger's system accounts; user balances live in POINT_BALANCES
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SystemBalances {
+    pub treasury: i64, // Negative by everything minted
+    pub escrow: u64,
+    pub burned: u64,
+}
+
+impl Storable for SystemBalances {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct LedgerAudit {
+    pub checked_at: u64,
+    pub ok: bool,
+    pub user_total: u64,
+    pub accounts: Vec<(String, i64)>, // System account -> balance
+    pub blocks_replayed: u64, // By this audit, on top of the checkpoint
+    pub blocks_pending: u64, // Left for the next audits; the balance checks wait until this is 0
+    pub problems: Vec<String>,
+}
+
+// How far the self-audit has replayed the block log; the balances it implies for each user are in
+// REPLAYED_BALANCES (see ledger.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct LedgerCheckpoint {
+    pub next_block: u64,
+    pub minted: u64,
+    pub burned: u64,
+    pub problems: Vec<String>, // Unreadable blocks found so far, reported by every audit
+}
+
+impl Storable for LedgerCheckpoint {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// --- Leaderboard Snapshots ---
+
+// The leaderboard as last computed (see ranking.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct LeaderboardSnapshot {
+    pub entries: Vec<(Principal, u32)>, // Highest streak first
+    pub computed_at: u64, // When the scan that produced it finished (nanos)
+    pub users_ranked: u64,
+}
+
+impl Storable for LeaderboardSnapshot {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// A snapshot scan in progress: the top entries merged so far and where to resume
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct LeaderboardBuild {
+    pub cursor: Option<Principal>, // Last profile scanned; None = start from the beginning
+    pub started_at: u64, // 0 = no scan running
+    pub scanned: u64,
+    // (user, streak, streak_reached_at); builds stored before the timestamp was tracked decode it as None
+    pub partial: Vec<(Principal, u32, Option<u64>)>,
+}
+
+impl Storable for LeaderboardBuild {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// The LLM prefill batch in progress: dares still to generate per difficulty (see prefill.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PrefillJob {
+    pub remaining: Vec<(Difficulty, u32)>, // Empty = no batch running
+    pub started_at: u64,
+    pub generated: u32,
+    pub failed: u32,
+    pub last_scheduled_day: Option<u64>, // UTC day number of the last scheduled top-up
+    pub correlation_id: Option<String>, // The request or timer run that queued the batch
+}
+
+impl Storable for PrefillJob {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// --- Replication (see replication.rs) ---
+
+// The collections mirrored to a warm standby
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ReplicatedMap { Profiles, Submissions, History, Dares }
+
+// A journaled write: the key changed, and its current value is what gets pushed
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct JournalEntry {
+    pub map: ReplicatedMap,
+    pub key: ByteBuf, // The key's Storable bytes
+}
+
+impl Storable for JournalEntry {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// One key's state as pushed to the standby; None = removed
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ReplicatedWrite {
+    pub map: ReplicatedMap,
+    pub key: ByteBuf,
+    pub value: Option<ByteBuf>, // The value's Storable bytes
+}
+
+// Where the initial copy of a new standby has got to
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SnapshotCursor {
+    pub map: ReplicatedMap,
+    pub after: Option<ByteBuf>, // Last key copied; None = from the start of `map`
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ReplicationState {
+    pub standby: Option<Principal>, // On the primary: where state is pushed
+    pub primary: Option<Principal>, // On a standby: the only canister allowed to push
+    pub snapshot: Option<SnapshotCursor>, // Initial copy still running
+    pub batches_pushed: u64,
+    pub last_push_at: Option<u64>,
+    pub failures: u32, // Pushes failed in a row
+    pub last_error: Option<String>,
+    pub promoted_at: Option<u64>, // This canister was a standby until promoted
+    pub next_seq: Option<u64>, // First journal entry the standby hasn't got; None = the oldest one
+    pub epoch: Option<u64>, // Bumped whenever the standby starts over; pushes from an older epoch are dropped
+}
+
+impl Storable for ReplicationState {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// A read-only mirror serving heavy queries; fed from the same journal as the standby
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ReadReplica {
+    pub canister: Principal,
+    pub endpoint: String, // Advertised by get_status
+    pub registered_at: u64,
+    pub snapshot: Option<SnapshotCursor>, // Initial copy still running
+    pub next_seq: u64, // First journal entry not yet pushed
+    pub batches_pushed: u64,
+    pub last_push_at: Option<u64>,
+    pub failures: u32, // Pushes failed in a row
+    pub last_error: Option<String>,
+    pub epoch: Option<u64>, // As ReplicationState::epoch
+}
+
+impl Storable for ReadReplica {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// --- Incidents (see incidents.rs) ---
+
+// Occurrences of one unexpected error, keyed by signature
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Incident {
+    pub signature: String,
+    pub operation: String, // The command, route or timer it happened in
+    pub count: u64,
+    pub first_seen: u64,
+    pub last_seen: u64,
+    pub last_error: String,
+    pub last_correlation_id: String,
+    pub reported_at: Option<u64>, // Last post to the admin channel
+    pub unreported: u64, // Occurrences since then
+}
+
+impl Storable for Incident {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// --- Quarantine (see quarantine.rs) ---
+
+// A record that didn't decode, moved out of its map with its raw key and value
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CorruptRecord {
+    pub id: u64,
+    pub store: String, // The map it came from, named as in the preflight report
+    pub key: ByteBuf, // The key's Storable bytes
+    pub value: ByteBuf, // The value as stored
+    pub error: String,
+    pub quarantined_at: u64,
+}
+
+impl Storable for CorruptRecord {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// --- Group Setup (see setup.rs) ---
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SetupStage {
+    Mix,
+    Verification,
+    Digest,
+    Language,
+    Confirm,
+}
+
+// An admin's /setup conversation for one chat; `draft` holds the answers so far
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SetupWizard {
+    pub chat_id: String,
+    pub stage: SetupStage,
+    pub draft: ChatConfig,
+    pub updated_at: u64,
+}
+
+impl Storable for SetupWizard {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// --- Randomness (see randomness.rs) ---
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EntropyPool {
+    pub seed: ByteBuf, // Empty until the first raw_rand arrives
+    pub draws: u64, // Draws taken from this seed lineage
+    pub seeded_at: Option<u64>, // Last reseed
+}
+
+impl Storable for EntropyPool {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// --- State Validation ---
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct StateFinding {
+    pub check: String, // e.g. "current_dare", "review_queue"
+    pub detail: String,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct StateReport {
+    pub dry_run: bool,
+    pub total_findings: u64,
+    pub repaired: u64,
+    pub findings: Vec<StateFinding>, // First 200 only
+}
+
+// --- Upgrade Preflight ---
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct StoreReport {
+    pub name: String,
+    pub records: u64,
+    pub undecodable: u64,
+    pub first_error: Option<String>,
+    pub max_size: u64, // Largest encoded record in bytes
+    pub bound: Option<u64>, // Bound::Bounded max_size; None = unbounded or fixed size
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PreflightReport {
+    pub safe: bool,
+    pub upgrade_blocked: bool,
+    pub stores: Vec<StoreReport>,
+    pub warnings: Vec<String>,
+}
+
+// --- Telemetry ---
+
+// A release note added with add_changelog (see changelog.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ChangelogEntry {
+    pub id: u64,
+    pub version: String,
+    pub notes: String,
+    pub added_at: u64,
+    pub added_by: Principal,
+    pub announced_at: Option<u64>, // Posted to subscribed chats after the upgrade that followed it
+}
+
+impl Storable for ChangelogEntry {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Dares a user created and how often others completed them (see creators.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CreatorStats {
+    pub dares_created: u64,
+    pub completions: u64, // Approved submissions of their dares by other users
+    pub last_completed_at: Option<u64>,
+}
+
+impl Storable for CreatorStats {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// An open request for other members to confirm an offline dare (see vouches.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct VouchRequest {
+    pub dare_id: u64,
+    pub requested_at: u64,
+    pub expires_at: u64,
+    pub vouchers: Vec<Principal>,
+}
+
+impl Storable for VouchRequest {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// One side of a buddy pair (see buddies.rs); each user has at most one buddy
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct BuddyLink {
+    pub buddy: Principal,
+    pub paired_at: u64,
+    pub week_started_at: u64,
+    pub streak_at_week_start: u32,
+    pub last_completed_at: Option<u64>,
+    pub lapse_notified: bool, // The buddy was told about this user's current inactivity
+    pub lapsed_this_week: bool,
+    pub weeks_together: u32, // Weeks both kept their streak
+}
+
+impl Storable for BuddyLink {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// A pairing request waiting for the invitee's answer (buddies and duos alike)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct BuddyInvite {
+    pub from: Principal,
+    pub invited_at: u64,
+}
+
+impl Storable for BuddyInvite {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Two members sharing one streak (see duos.rs); their own profiles and histories stay separate
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Duo {
+    pub id: u64,
+    pub members: Vec<Principal>, // Always two
+    pub formed_at: u64,
+    pub streak: u32, // Consecutive UTC days on which either member submitted a dare
+    pub best_streak: u32,
+    pub last_day: Option<u64>, // UTC day number of the last counted submission
+    pub last_completed_by: Option<Principal>,
+    pub completions: u64, // Dares submitted by either member since forming
+    pub latest_day: Option<DuoDay>, // How last_day was counted, so an undo or rejection can take it back
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DuoDay {
+    pub submissions: Vec<u64>, // Submissions counted on the duo's last_day
+    pub streak: u32, // The duo's streak, best streak and last day from before that day counted
+    pub best_streak: u32,
+    pub last_day: Option<u64>,
+}
+
+impl Storable for Duo {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Per-cohort counts for a feature rollout (see features.rs)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CohortStats {
+    pub exposures: u64,
+    pub completions: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct FeatureMetrics {
+    pub enabled: CohortStats,
+    pub disabled: CohortStats,
+    pub since: u64, // First count since the rollout last changed
+}
+
+impl Storable for FeatureMetrics {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct FeatureRollout {
+    pub feature: String,
+    pub percent: u8,
+    pub metrics: FeatureMetrics,
+}
+
+// Encoded sizes of written records, bucketed by powers of two
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SizeHistogram {
+    pub writes: u64,
+    pub max_size: u64,
+    pub buckets: Vec<(u64, u64)>, // (upper bound in bytes, writes); the last bucket is open-ended
+    pub since: u64, // First recorded write
+}
+
+impl Storable for SizeHistogram {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> { encode_record(self) }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self { decode_record(bytes.as_ref()) }
+    const BOUND: Bound = Bound::Unbounded;
+}
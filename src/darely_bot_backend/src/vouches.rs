@@ -0,0 +1,173 @@
+use crate::gallery;
+use crate::history;
+use crate::outbox;
+use crate::scheduler;
+use crate::state;
+use crate::types::{HistoryEvent, StorablePrincipal, UserProfile, VouchRequest};
+use candid::Principal;
+
+// --- Vouching ---
+// A user who did their dare away from the app (no photo, no signal) can keep a streak that is
+// about to lapse by asking others to vouch for them. With streak expiry on (see scheduler.rs),
+// /request_vouch opens a request for the active dare during the streak's last 24 hours, and once
+// two other registered members have sent /vouch for them, the dare is closed and the expiry clock
+// restarts. The streak is kept, not raised, and there is no XP, since there is no proof to review.
+// Each user can keep their streak this way twice per 30 days and give five vouches per 30 days; a
+// vouch only counts against that allowance once it has been recorded. Requests expire after a
+// day. Every vouch and every vouched streak is logged to the history.
+
+const HOUR_NANOS: u64 = 60 * 60 * 1_000_000_000;
+const REQUEST_TTL_NANOS: u64 = 24 * HOUR_NANOS;
+const LAPSE_NOTICE_NANOS: u64 = 24 * HOUR_NANOS;
+const WINDOW_NANOS: u64 = 30 * 24 * 60 * 60 * 1_000_000_000;
+pub const REQUIRED_VOUCHES: usize = 2;
+const MAX_VOUCHED_PER_WINDOW: usize = 2;
+const MAX_GIVEN_PER_WINDOW: usize = 5;
+
+fn recent(timestamps: Option<Vec<u64>>, now: u64) -> Vec<u64> {
+    timestamps.unwrap_or_default().into_iter().filter(|&ts| now.saturating_sub(ts) < WINDOW_NANOS).collect()
+}
+
+// Whether fewer than `max` of the timestamps fall in the last 30 days
+fn allowance_left(timestamps: &Option<Vec<u64>>, max: usize, now: u64) -> bool {
+    recent(timestamps.clone(), now).len() < max
+}
+
+// Vouches keep a streak that would otherwise expire within LAPSE_NOTICE_NANOS
+fn check_lapsing(profile: &UserProfile, expiry_hours: Option<u32>, now: u64) -> Result<(), String> {
+    if profile.streak == 0 {
+        return Err("You don't have a streak to keep yet.".to_string());
+    }
+    let Some(left) = expiry_hours.and_then(|hours| scheduler::time_left(profile, hours, now)) else {
+        return Err("Your streak doesn't expire, so there's nothing to vouch for.".to_string());
+    };
+    if left > LAPSE_NOTICE_NANOS {
+        return Err(format!(
+            "Your streak has {} hours left. Vouches are for its last {} hours.",
+            left.div_ceil(HOUR_NANOS), LAPSE_NOTICE_NANOS / HOUR_NANOS
+        ));
+    }
+    Ok(())
+}
+
+fn open_request(user: Principal, now: u64) -> Option<VouchRequest> {
+    state::VOUCH_REQUESTS.with(|r| r.borrow().get(&StorablePrincipal(user))).filter(|request| request.expires_at > now)
+}
+
+pub fn request(user: Principal) -> Result<String, String> {
+    let now = ic_cdk::api::time();
+    let profile = state::get_profile(user).ok_or_else(|| "User not found. Please /register first.".to_string())?;
+    let dare_id = profile.current_dare_id
+        .ok_or_else(|| "You don't have an active dare to be vouched for. Use /get_dare first.".to_string())?;
+    check_lapsing(&profile, state::get_config().streak_expiry_hours, now)?;
+    if !allowance_left(&profile.vouched_at, MAX_VOUCHED_PER_WINDOW, now) {
+        return Err(format!("You can keep your streak by vouching {} times per 30 days.", MAX_VOUCHED_PER_WINDOW));
+    }
+    if open_request(user, now).is_some_and(|request| request.dare_id == dare_id) {
+        return Err("You already asked for vouches for this dare.".to_string());
+    }
+    let request = VouchRequest { dare_id, requested_at: now, expires_at: now + REQUEST_TTL_NANOS, vouchers: Vec::new() };
+    state::VOUCH_REQUESTS.with(|r| r.borrow_mut().insert(StorablePrincipal(user), request));
+    Ok(format!(
+        "🤝 {} did their dare offline and needs {} members to vouch for them within 24 hours: /vouch @{}",
+        gallery::display_name(user), REQUIRED_VOUCHES, gallery::display_name(user)
+    ))
+}
+
+pub fn vouch(voucher: Principal, user: Principal) -> Result<String, String> {
+    let now = ic_cdk::api::time();
+    if voucher == user {
+        return Err("You can't vouch for yourself.".to_string());
+    }
+    let mut request = open_request(user, now)
+        .ok_or_else(|| format!("{} hasn't asked for vouches (or the request expired).", gallery::display_name(user)))?;
+    if request.vouchers.contains(&voucher) {
+        return Err(format!("You already vouched for {}.", gallery::display_name(user)));
+    }
+    if state::get_profile(user).and_then(|profile| profile.current_dare_id) != Some(request.dare_id) {
+        state::VOUCH_REQUESTS.with(|r| r.borrow_mut().remove(&StorablePrincipal(user)));
+        return Err(format!("{} has moved on to another dare, so the request was closed.", gallery::display_name(user)));
+    }
+    // The voucher must be registered with vouches left; the vouch is charged once it went through
+    let voucher_profile = state::get_profile(voucher).ok_or_else(|| "User not found. Please /register first.".to_string())?;
+    if !allowance_left(&voucher_profile.vouches_given_at, MAX_GIVEN_PER_WINDOW, now) {
+        return Err(format!("You can give {} vouches per 30 days.", MAX_GIVEN_PER_WINDOW));
+    }
+    request.vouchers.push(voucher);
+
+    if request.vouchers.len() < REQUIRED_VOUCHES {
+        let count = request.vouchers.len();
+        let dare_id = request.dare_id;
+        charge(voucher, user, dare_id, now)?;
+        state::VOUCH_REQUESTS.with(|r| r.borrow_mut().insert(StorablePrincipal(user), request));
+        outbox::queue(user, format!("🤝 {} vouched for you ({}/{}).", gallery::display_name(voucher), count, REQUIRED_VOUCHES), None);
+        return Ok(format!("Thanks! {} needs {} more vouch(es).", gallery::display_name(user), REQUIRED_VOUCHES - count));
+    }
+
+    state::VOUCH_REQUESTS.with(|r| r.borrow_mut().remove(&StorablePrincipal(user)));
+    let dare_id = request.dare_id;
+    let streak = state::update_profile(user, |profile| {
+        if profile.current_dare_id != Some(dare_id) {
+            return Err(format!("{} has moved on to another dare, so the vouch no longer applies.", gallery::display_name(user)));
+        }
+        let mut vouched = recent(profile.vouched_at.take(), now);
+        vouched.push(now);
+        profile.vouched_at = Some(vouched);
+        profile.current_dare_id = None;
+        profile.current_dare_chat = None;
+        profile.current_dare_text = None;
+        // The streak stays as it is; the vouch restarts its expiry clock (see scheduler.rs)
+        Ok(profile.streak)
+    })?;
+    charge(voucher, user, dare_id, now)?;
+    history::log_event(user, HistoryEvent::StreakVouched { dare_id, vouchers: request.vouchers });
+    outbox::queue(user, format!("🔥 Your dare was vouched for! Your streak of {} is safe.", streak), None);
+    Ok(format!("Thanks! {}'s streak is safe at {}.", gallery::display_name(user), streak))
+}
+
+// Counts a recorded vouch against the voucher's allowance
+fn charge(voucher: Principal, user: Principal, dare_id: u64, now: u64) -> Result<(), String> {
+    state::update_profile(voucher, |profile| {
+        let mut given = recent(profile.vouches_given_at.take(), now);
+        given.push(now);
+        profile.vouches_given_at = Some(given);
+        Ok(())
+    })?;
+    history::log_event(voucher, HistoryEvent::VouchGiven { for_user: user, dare_id });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY_NANOS: u64 = 24 * HOUR_NANOS;
+
+    #[test]
+    fn requests_need_a_streak_in_its_last_day() {
+        let profile = UserProfile { streak: 4, last_completed_at: Some(10 * DAY_NANOS), ..Default::default() };
+        // A 48-hour window: the streak expires on day 12
+        assert!(check_lapsing(&profile, Some(48), 10 * DAY_NANOS + HOUR_NANOS).is_err());
+        assert!(check_lapsing(&profile, Some(48), 11 * DAY_NANOS).is_ok());
+        assert!(check_lapsing(&profile, Some(48), 12 * DAY_NANOS - 1).is_ok());
+        // Without expiry, or without a streak, there is nothing to keep
+        assert!(check_lapsing(&profile, None, 11 * DAY_NANOS).is_err());
+        let fresh = UserProfile { streak: 0, ..profile.clone() };
+        assert!(check_lapsing(&fresh, Some(48), 11 * DAY_NANOS).is_err());
+        // A vouch restarts the clock, so the next request waits for the next lapse
+        let vouched = UserProfile { vouched_at: Some(vec![11 * DAY_NANOS]), ..profile };
+        assert!(check_lapsing(&vouched, Some(48), 12 * DAY_NANOS - HOUR_NANOS).is_err());
+        assert!(check_lapsing(&vouched, Some(48), 12 * DAY_NANOS + HOUR_NANOS).is_ok());
+    }
+
+    #[test]
+    fn allowances_count_the_last_30_days() {
+        let now = 100 * DAY_NANOS;
+        let used = Some(vec![now - 30 * DAY_NANOS, now - 29 * DAY_NANOS, now - DAY_NANOS]);
+        // The oldest one has dropped out of the window
+        assert!(!allowance_left(&used, MAX_VOUCHED_PER_WINDOW, now));
+        assert!(allowance_left(&used, MAX_VOUCHED_PER_WINDOW, now + DAY_NANOS));
+        assert!(allowance_left(&used, MAX_GIVEN_PER_WINDOW, now));
+        assert!(allowance_left(&None, MAX_VOUCHED_PER_WINDOW, now));
+    }
+}